@@ -1,11 +1,15 @@
+#![allow(clippy::redundant_closure, clippy::approx_constant, clippy::unit_arg, unused_must_use)]
+
 #[path = "data/cbor_flat_map.rs"]
 pub mod cbor_flat_map;
 use criterion::{Criterion, criterion_group, criterion_main};
 use spooky_db_module::db::{BulkRecord, DbMutation, Operation, SpookyDb};
 use spooky_db_module::deserialization::RecordDeserialize;
-use spooky_db_module::serialization::{from_bytes, from_cbor, from_spooky, serialize_into};
+use spooky_db_module::serialization::{
+    from_bytes, from_cbor, from_cbor_slice, from_spooky, serialize_into,
+};
 use spooky_db_module::spooky_record::record_mut::SpookyRecordMut;
-use spooky_db_module::spooky_record::{SpookyReadable, SpookyRecord};
+use spooky_db_module::spooky_record::{IndexedRecord, PerfectIndex, SpookyReadable, SpookyRecord};
 use spooky_db_module::spooky_value::SpookyValue;
 use smol_str::SmolStr;
 use std::hint::black_box;
@@ -109,6 +113,11 @@ fn bench_creating_spooky_record(c: &mut Criterion) {
         })
     });
 
+    // 1c. Streaming: CBOR bytes → record bytes, no intermediate tree
+    group.bench_function("from_cbor_slice", |b| {
+        b.iter(|| from_cbor_slice(black_box(BENCH_CBOR)).unwrap())
+    });
+
     // 2. SpookyRecordMut::new_empty
     group.bench_function("SpookyRecordMut::new_empty", |b| {
         b.iter(|| SpookyRecordMut::new_empty())
@@ -486,6 +495,7 @@ fn bench_apply_batch(c: &mut Criterion) {
                             op: Operation::Create,
                             data: Some(data.clone()),
                             version: Some(i as u64),
+                            expires_at: None,
                         })
                         .collect();
                     (db, mutations, dir)
@@ -600,6 +610,58 @@ fn bench_bulk_load(c: &mut Criterion) {
     group.finish();
 }
 
+// ─── Group: wide_record ─────────────────────────────────────────────────────
+//
+// Lookups in a 32-field record: find_field (bisection + vectorised scan)
+// against the plain binary search, a precomputed hash and a PerfectIndex.
+
+fn bench_wide_record(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wide_record");
+    let mut map = spooky_db_module::spooky_value::FastMap::new();
+    for i in 0..32 {
+        map.insert(SmolStr::new(format!("col_{i}")), SpookyValue::from(i as i64));
+    }
+    let (buf, fc) = from_spooky(&SpookyValue::Object(map)).unwrap();
+    let record = SpookyRecord::new(&buf, fc);
+    let hash = spooky_db_module::field_hash!("col_17");
+    let names: Vec<String> = (0..32).map(|i| format!("col_{i}")).collect();
+    let index = PerfectIndex::new(&names);
+    let indexed = IndexedRecord::new(record, &index);
+
+    // Every field in turn, so branch history does not favour one path.
+    let hashes: Vec<u64> = record.iter_fields().map(|f| f.name_hash).collect();
+
+    group.bench_function("find_field_by_hash (all 32)", |b| {
+        b.iter(|| {
+            for h in &hashes {
+                black_box(record.find_field_by_hash(black_box(*h)).ok());
+            }
+        })
+    });
+
+    group.bench_function("binary_hash_search (all 32)", |b| {
+        b.iter(|| {
+            for h in &hashes {
+                black_box(record.binary_hash_search(fc, black_box(*h)).ok());
+            }
+        })
+    });
+
+    group.bench_function("get_i64 (by name)", |b| {
+        b.iter(|| black_box(record.get_i64(black_box("col_17"))))
+    });
+
+    group.bench_function("get_i64_by_hash", |b| {
+        b.iter(|| black_box(record.get_i64_by_hash(black_box(hash))))
+    });
+
+    group.bench_function("IndexedRecord::get_i64_by_hash", |b| {
+        b.iter(|| black_box(indexed.get_i64_by_hash(black_box(hash))))
+    });
+
+    group.finish();
+}
+
 // ─── Criterion Main ─────────────────────────────────────────────────────────
 
 criterion_group!(
@@ -614,5 +676,6 @@ criterion_group!(
     bench_get_record_bytes,
     bench_rebuild_zsets,
     bench_bulk_load,
+    bench_wide_record,
 );
 criterion_main!(benches);
@@ -1,3 +1,5 @@
+#![allow(clippy::box_collection)]
+
 use serde::Serialize;
 use std::collections::BTreeMap;
 use std::fs::File;
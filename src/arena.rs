@@ -0,0 +1,590 @@
+use crate::error::RecordError;
+use crate::serialization::{RecordSerialize, write_entries};
+use crate::spooky_value::{FastMap, ObjectKey, SpookyNumber, SpookyValue};
+use crate::types::*;
+use arrayvec::ArrayVec;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use smol_str::SmolStr;
+use xxhash_rust::const_xxh64::xxh64;
+
+// ─── Arena-backed Value Tree ────────────────────────────────────────────────
+//
+// A `SpookyValue` tree costs one heap allocation per array/object node. For
+// bulk CBOR ingest that is millions of tiny `Vec`/`BTreeMap` allocations.
+// The arena stores every node in three flat vectors instead:
+//
+//   nodes:   [ArenaNode]            one per value
+//   items:   [NodeId]               array children, contiguous per array
+//   entries: [(SmolStr, NodeId)]    object members, contiguous per object,
+//                                   sorted by key (same order as BTreeMap)
+//
+// `clear()` keeps all capacity, so one arena can be reused across a batch.
+
+/// Handle to a node inside a [`SpookyValueArena`]. Only meaningful for the
+/// arena that produced it, and only until that arena is cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+/// A single value stored in the arena. Containers reference ranges of the
+/// arena's item / entry storage.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArenaNode {
+    Null,
+    Bool(bool),
+    Number(SpookyNumber),
+    Str(SmolStr),
+    Array { start: u32, len: u32 },
+    Object { start: u32, len: u32 },
+}
+
+#[derive(Debug, Default)]
+pub struct SpookyValueArena {
+    nodes: Vec<ArenaNode>,
+    items: Vec<NodeId>,
+    entries: Vec<(SmolStr, NodeId)>,
+    // Children of containers still being built; drained into
+    // `items` / `entries` when the container closes.
+    pending_items: Vec<NodeId>,
+    pending_entries: Vec<(SmolStr, NodeId)>,
+}
+
+impl SpookyValueArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pre-size node storage for roughly `nodes` values.
+    pub fn with_capacity(nodes: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(nodes),
+            items: Vec::with_capacity(nodes / 2),
+            entries: Vec::with_capacity(nodes / 2),
+            ..Self::default()
+        }
+    }
+
+    /// Drop all nodes, keeping allocated capacity. Invalidates every `NodeId`.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.items.clear();
+        self.entries.clear();
+        self.pending_items.clear();
+        self.pending_entries.clear();
+    }
+
+    /// Number of nodes currently stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // ─── Node access ────────────────────────────────────────────────────
+
+    /// Panics if `id` does not belong to this arena.
+    #[inline]
+    pub fn node(&self, id: NodeId) -> &ArenaNode {
+        &self.nodes[id.0 as usize]
+    }
+
+    /// Children of an array node. Empty for non-arrays.
+    #[inline]
+    pub fn array_items(&self, id: NodeId) -> &[NodeId] {
+        match *self.node(id) {
+            ArenaNode::Array { start, len } => &self.items[start as usize..(start + len) as usize],
+            _ => &[],
+        }
+    }
+
+    /// Members of an object node, sorted by key. Empty for non-objects.
+    #[inline]
+    pub fn object_entries(&self, id: NodeId) -> &[(SmolStr, NodeId)] {
+        match *self.node(id) {
+            ArenaNode::Object { start, len } => {
+                &self.entries[start as usize..(start + len) as usize]
+            }
+            _ => &[],
+        }
+    }
+
+    /// Look up an object member by key (binary search).
+    pub fn object_get(&self, id: NodeId, key: &str) -> Option<NodeId> {
+        let entries = self.object_entries(id);
+        entries
+            .binary_search_by(|(k, _)| k.as_str().cmp(key))
+            .ok()
+            .map(|i| entries[i].1)
+    }
+
+    // ─── Building ───────────────────────────────────────────────────────
+
+    #[inline]
+    fn push(&mut self, node: ArenaNode) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(node);
+        id
+    }
+
+    /// Close an array whose children were pushed to `pending_items[mark..]`.
+    fn finish_array(&mut self, mark: usize) -> NodeId {
+        let start = self.items.len() as u32;
+        self.items.extend(self.pending_items.drain(mark..));
+        let len = self.items.len() as u32 - start;
+        self.push(ArenaNode::Array { start, len })
+    }
+
+    /// Close an object whose members were pushed to `pending_entries[mark..]`.
+    /// Members are sorted by key; on duplicate keys the last one wins, matching
+    /// `BTreeMap::insert` semantics.
+    fn finish_object(&mut self, mark: usize) -> NodeId {
+        // Stable sort keeps duplicates in insertion order.
+        self.pending_entries[mark..].sort_by(|a, b| a.0.cmp(&b.0));
+        let start = self.entries.len();
+        for (k, v) in self.pending_entries.drain(mark..) {
+            let in_object = self.entries.len() > start;
+            match self.entries.last_mut() {
+                Some(last) if in_object && last.0 == k => last.1 = v,
+                _ => self.entries.push((k, v)),
+            }
+        }
+        let len = (self.entries.len() - start) as u32;
+        self.push(ArenaNode::Object {
+            start: start as u32,
+            len,
+        })
+    }
+
+    /// Copy a `SpookyValue` tree into the arena.
+    pub fn insert_value(&mut self, value: &SpookyValue) -> NodeId {
+        match value {
+            SpookyValue::Null => self.push(ArenaNode::Null),
+            SpookyValue::Bool(b) => self.push(ArenaNode::Bool(*b)),
+            SpookyValue::Number(n) => self.push(ArenaNode::Number(*n)),
+            SpookyValue::Str(s) => self.push(ArenaNode::Str(s.clone())),
+            SpookyValue::Array(arr) => {
+                let mark = self.pending_items.len();
+                for v in arr {
+                    let id = self.insert_value(v);
+                    self.pending_items.push(id);
+                }
+                self.finish_array(mark)
+            }
+            SpookyValue::Object(map) => {
+                let mark = self.pending_entries.len();
+                for (k, v) in map {
+                    let id = self.insert_value(v);
+                    self.pending_entries.push((k.clone(), id));
+                }
+                self.finish_object(mark)
+            }
+            // The arena has no tagged node; only the payload is kept.
+            SpookyValue::Tagged(_, inner) => self.insert_value(inner),
+        }
+    }
+
+    /// Decode one CBOR item straight into the arena and return its root.
+    ///
+    /// No intermediate `cbor4ii::core::Value` or `SpookyValue` is built.
+    /// On error the arena may hold orphaned nodes from the partial parse;
+    /// they are harmless and reclaimed by `clear()`.
+    pub fn parse_cbor(&mut self, bytes: &[u8]) -> Result<NodeId, RecordError> {
+        let mut de =
+            cbor4ii::serde::Deserializer::new(cbor4ii::core::utils::SliceReader::new(bytes));
+        ArenaSeed { arena: self }
+            .deserialize(&mut de)
+            .map_err(|e| RecordError::CborError(e.to_string()))
+    }
+
+    // ─── Export ─────────────────────────────────────────────────────────
+
+    /// Borrowed view of a node, usable wherever a `RecordSerialize` or
+    /// `serde::Serialize` value is expected.
+    #[inline]
+    pub fn value(&self, id: NodeId) -> ArenaValue<'_> {
+        ArenaValue { arena: self, id }
+    }
+
+    /// Materialize a node as an owned `SpookyValue` tree.
+    pub fn to_value(&self, id: NodeId) -> SpookyValue {
+        match self.node(id) {
+            ArenaNode::Null => SpookyValue::Null,
+            ArenaNode::Bool(b) => SpookyValue::Bool(*b),
+            ArenaNode::Number(n) => SpookyValue::Number(*n),
+            ArenaNode::Str(s) => SpookyValue::Str(s.clone()),
+            ArenaNode::Array { .. } => SpookyValue::Array(
+                self.array_items(id)
+                    .iter()
+                    .map(|c| self.to_value(*c))
+                    .collect(),
+            ),
+            ArenaNode::Object { .. } => SpookyValue::Object(
+                self.object_entries(id)
+                    .iter()
+                    .map(|(k, c)| (k.clone(), self.to_value(*c)))
+                    .collect::<FastMap<_, _>>(),
+            ),
+        }
+    }
+
+    /// Serialize an object node into the hybrid binary format.
+    ///
+    /// Byte-for-byte identical to `from_spooky(&arena.to_value(id))`, without
+    /// building the intermediate tree.
+    pub fn to_record(&self, id: NodeId) -> Result<(Vec<u8>, usize), RecordError> {
+        let mut buf = Vec::new();
+        let field_count = self.write_record_into(id, &mut buf)?;
+        Ok((buf, field_count))
+    }
+
+    /// Like `to_record`, but reuses the caller's buffer. The buffer is
+    /// cleared first; its capacity is retained.
+    pub fn write_record_into(&self, id: NodeId, buf: &mut Vec<u8>) -> Result<usize, RecordError> {
+        if !matches!(self.node(id), ArenaNode::Object { .. }) {
+            return Err(RecordError::InvalidBuffer);
+        }
+        let members = self.object_entries(id);
+        let field_count = members.len();
+        if field_count > 32 {
+            return Err(RecordError::TooManyFields);
+        }
+
+        let values: ArrayVec<ArenaValue<'_>, 32> =
+            members.iter().map(|(_, c)| self.value(*c)).collect();
+        let mut entries: ArrayVec<(&ArenaValue<'_>, u64), 32> = values
+            .iter()
+            .zip(members)
+            .map(|(v, (k, _))| (v, xxh64(k.as_bytes(), 0)))
+            .collect();
+
+        buf.clear();
+        buf.resize(HEADER_SIZE + field_count * INDEX_ENTRY_SIZE, 0);
+        write_entries(buf, &mut entries, field_count)?;
+        Ok(field_count)
+    }
+}
+
+// ─── ArenaValue ─────────────────────────────────────────────────────────────
+
+/// A `(arena, node)` pair. Serializes exactly like the equivalent `SpookyValue`.
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaValue<'a> {
+    arena: &'a SpookyValueArena,
+    id: NodeId,
+}
+
+impl<'a> ArenaValue<'a> {
+    #[inline]
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    #[inline]
+    pub fn node(&self) -> &'a ArenaNode {
+        self.arena.node(self.id)
+    }
+
+    /// Object member by key, if this is an object.
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<ArenaValue<'a>> {
+        self.arena
+            .object_get(self.id, key)
+            .map(|id| self.arena.value(id))
+    }
+}
+
+impl serde::Serialize for ArenaValue<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.node() {
+            ArenaNode::Null => serializer.serialize_none(),
+            ArenaNode::Bool(b) => serializer.serialize_bool(*b),
+            ArenaNode::Number(n) => match n {
+                SpookyNumber::I64(i) => serializer.serialize_i64(*i),
+                SpookyNumber::U64(u) => serializer.serialize_u64(*u),
+                SpookyNumber::F64(f) => serializer.serialize_f64(*f),
+            },
+            ArenaNode::Str(s) => serializer.serialize_str(s),
+            ArenaNode::Array { .. } => {
+                let items = self.arena.array_items(self.id);
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for c in items {
+                    seq.serialize_element(&self.arena.value(*c))?;
+                }
+                seq.end()
+            }
+            ArenaNode::Object { .. } => {
+                let members = self.arena.object_entries(self.id);
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (k, c) in members {
+                    map.serialize_entry(k.as_str(), &self.arena.value(*c))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl RecordSerialize for ArenaValue<'_> {
+    #[inline]
+    fn is_null(&self) -> bool {
+        matches!(self.node(), ArenaNode::Null)
+    }
+
+    #[inline]
+    fn as_bool(&self) -> Option<bool> {
+        match self.node() {
+            ArenaNode::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn as_i64(&self) -> Option<i64> {
+        match self.node() {
+            ArenaNode::Number(SpookyNumber::I64(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn as_u64(&self) -> Option<u64> {
+        match self.node() {
+            ArenaNode::Number(SpookyNumber::U64(u)) => Some(*u),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn as_f64(&self) -> Option<f64> {
+        match self.node() {
+            ArenaNode::Number(SpookyNumber::F64(f)) => Some(*f),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn as_str(&self) -> Option<&str> {
+        match self.node() {
+            ArenaNode::Str(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn is_nested(&self) -> bool {
+        matches!(
+            self.node(),
+            ArenaNode::Array { .. } | ArenaNode::Object { .. }
+        )
+    }
+}
+
+// ─── CBOR → Arena ───────────────────────────────────────────────────────────
+
+/// Deserialize seed that writes into the arena. Number and key handling
+/// mirrors `SpookyValue`'s `Deserialize` impl.
+struct ArenaSeed<'a> {
+    arena: &'a mut SpookyValueArena,
+}
+
+impl<'de> DeserializeSeed<'de> for ArenaSeed<'_> {
+    type Value = NodeId;
+
+    #[inline]
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<NodeId, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ArenaSeed<'_> {
+    type Value = NodeId;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("any SpookyValue")
+    }
+
+    #[inline]
+    fn visit_unit<E: de::Error>(self) -> Result<NodeId, E> {
+        Ok(self.arena.push(ArenaNode::Null))
+    }
+
+    #[inline]
+    fn visit_none<E: de::Error>(self) -> Result<NodeId, E> {
+        Ok(self.arena.push(ArenaNode::Null))
+    }
+
+    #[inline]
+    fn visit_some<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<NodeId, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    #[inline]
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<NodeId, E> {
+        Ok(self.arena.push(ArenaNode::Bool(b)))
+    }
+
+    #[inline]
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<NodeId, E> {
+        Ok(self.arena.push(ArenaNode::Number(SpookyNumber::I64(i))))
+    }
+
+    #[inline]
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<NodeId, E> {
+        let n = match i64::try_from(u) {
+            Ok(i) => SpookyNumber::I64(i),
+            Err(_) => SpookyNumber::U64(u),
+        };
+        Ok(self.arena.push(ArenaNode::Number(n)))
+    }
+
+    #[inline]
+    fn visit_i128<E: de::Error>(self, i: i128) -> Result<NodeId, E> {
+        let n = crate::cbor::int_to_number(i);
+        Ok(self.arena.push(ArenaNode::Number(n)))
+    }
+
+    #[inline]
+    fn visit_u128<E: de::Error>(self, u: u128) -> Result<NodeId, E> {
+        let n = crate::cbor::uint_to_number(u);
+        Ok(self.arena.push(ArenaNode::Number(n)))
+    }
+
+    #[inline]
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<NodeId, E> {
+        Ok(self.arena.push(ArenaNode::Number(SpookyNumber::F64(f))))
+    }
+
+    #[inline]
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<NodeId, E> {
+        Ok(self.arena.push(ArenaNode::Str(SmolStr::new(s))))
+    }
+
+    #[inline]
+    fn visit_string<E: de::Error>(self, s: String) -> Result<NodeId, E> {
+        Ok(self.arena.push(ArenaNode::Str(SmolStr::from(s))))
+    }
+
+    /// Byte strings become an array of byte values, as in `SpookyValue`.
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<NodeId, E> {
+        let mark = self.arena.pending_items.len();
+        for b in bytes {
+            let id = self
+                .arena
+                .push(ArenaNode::Number(SpookyNumber::I64(*b as i64)));
+            self.arena.pending_items.push(id);
+        }
+        Ok(self.arena.finish_array(mark))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<NodeId, A::Error> {
+        let mark = self.arena.pending_items.len();
+        while let Some(id) = seq.next_element_seed(ArenaSeed {
+            arena: &mut *self.arena,
+        })? {
+            self.arena.pending_items.push(id);
+        }
+        Ok(self.arena.finish_array(mark))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<NodeId, A::Error> {
+        let mark = self.arena.pending_entries.len();
+        while let Some(ObjectKey(k)) = access.next_key::<ObjectKey>()? {
+            let id = access.next_value_seed(ArenaSeed {
+                arena: &mut *self.arena,
+            })?;
+            self.arena.pending_entries.push((k, id));
+        }
+        Ok(self.arena.finish_object(mark))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::from_spooky;
+
+    fn sample() -> SpookyValue {
+        SpookyValue::from_json_str(
+            r#"{"id":"user:1","age":42,"big":18446744073709551615,"score":9.5,"active":true,
+                "none":null,"tags":["a","b",[1,2]],"profile":{"name":"Ada","langs":{"rust":1}}}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_value_roundtrip() {
+        let v = sample();
+        let mut arena = SpookyValueArena::new();
+        let root = arena.insert_value(&v);
+        assert_eq!(arena.to_value(root), v);
+        assert_eq!(
+            arena
+                .value(root)
+                .get("profile")
+                .unwrap()
+                .get("name")
+                .unwrap()
+                .as_str(),
+            Some("Ada")
+        );
+    }
+
+    #[test]
+    fn test_parse_cbor_matches_spooky_value() {
+        let v = sample();
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &v).unwrap();
+        let mut arena = SpookyValueArena::with_capacity(64);
+        let root = arena.parse_cbor(&bytes).unwrap();
+        assert_eq!(arena.to_value(root), v);
+    }
+
+    #[test]
+    fn test_to_record_identical_to_from_spooky() {
+        let v = sample();
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &v).unwrap();
+        let mut arena = SpookyValueArena::new();
+        let root = arena.parse_cbor(&bytes).unwrap();
+        let expected = from_spooky(&v).unwrap();
+        assert_eq!(arena.to_record(root).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_clear_reuses_arena_and_buffer() {
+        let mut arena = SpookyValueArena::new();
+        let mut buf = Vec::new();
+        for i in 0..3i64 {
+            arena.clear();
+            let v = SpookyValue::from_json_str(&format!(r#"{{"n":{i},"s":"x{i}"}}"#)).unwrap();
+            let bytes = cbor4ii::serde::to_vec(Vec::new(), &v).unwrap();
+            let root = arena.parse_cbor(&bytes).unwrap();
+            assert_eq!(arena.len(), 3);
+            arena.write_record_into(root, &mut buf).unwrap();
+            assert_eq!(buf, from_spooky(&v).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_keys_last_wins() {
+        // {"a": 1, "a": 2} — built by hand since encoders dedupe.
+        let bytes = [0xa2, 0x61, b'a', 0x01, 0x61, b'a', 0x02];
+        let mut arena = SpookyValueArena::new();
+        let root = arena.parse_cbor(&bytes).unwrap();
+        assert_eq!(arena.object_entries(root).len(), 1);
+        let a = arena.object_get(root, "a").unwrap();
+        assert_eq!(arena.node(a), &ArenaNode::Number(SpookyNumber::I64(2)));
+    }
+
+    #[test]
+    fn test_non_object_root_rejected() {
+        let mut arena = SpookyValueArena::new();
+        let root = arena.insert_value(&SpookyValue::from(1i64));
+        assert!(matches!(
+            arena.to_record(root),
+            Err(RecordError::InvalidBuffer)
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+//! Criterion benchmarks over your own records (`bench` feature).
+//!
+//! `benches/spooky_bench.rs` measures one fixed sample record. [`record_paths`]
+//! runs the same serialize, get, set and splice paths over a record you
+//! supply, so a regression shows up on the shapes your workload stores:
+//!
+//! ```rust,ignore
+//! use spooky_db_module::bench::{Criterion, record_paths};
+//!
+//! fn benches(c: &mut Criterion) {
+//!     record_paths(c, "user", &sample_user(), "name");
+//! }
+//! criterion::criterion_group!(user, benches);
+//! criterion::criterion_main!(user);
+//! ```
+//!
+//! For timings inside a running application, see `SpookyDb::set_timing_hook`.
+
+use std::hint::black_box;
+
+pub use criterion::Criterion;
+
+use crate::serialization::{from_spooky, serialize_into_buf};
+use crate::spooky_record::SpookyReadable;
+use crate::spooky_record::record_mut::SpookyRecordMut;
+use crate::spooky_value::SpookyValue;
+
+/// Benchmark `value` (a `SpookyValue::Object`) in a criterion group named
+/// `group`:
+///
+/// - `serialize` — `serialize_into_buf` into a reused buffer.
+/// - `get_raw` — look up `field` in the serialized record.
+/// - `set (same len)` — overwrite `field` with its own value, in place.
+/// - `set (splice)` — alternate `field` between its value and a longer
+///   string, so every write moves the bytes after it.
+///
+/// Panics if `value` is not an object or has no `field`.
+pub fn record_paths(c: &mut Criterion, group: &str, value: &SpookyValue, field: &str) {
+    let own = value
+        .get(field)
+        .unwrap_or_else(|| panic!("benchmark record has no field {field:?}"))
+        .clone();
+    let (bytes, count) = from_spooky(value).expect("benchmark record must be an object");
+    let record = SpookyRecordMut::new(bytes.clone(), count);
+    let old_len = record.get_raw(field).map_or(0, |raw| raw.data.len());
+    let longer = SpookyValue::from("x".repeat(old_len + 16).as_str());
+
+    let mut group = c.benchmark_group(group);
+
+    group.bench_function("serialize", |b| {
+        let mut buf = Vec::new();
+        b.iter(|| black_box(serialize_into_buf(black_box(value), &mut buf)))
+    });
+
+    group.bench_function("get_raw", |b| {
+        b.iter(|| black_box(record.get_raw(black_box(field)).is_some()))
+    });
+
+    group.bench_function("set (same len)", |b| {
+        let mut rec = SpookyRecordMut::new(bytes.clone(), count);
+        b.iter(|| black_box(rec.set_field(black_box(field), black_box(&own))))
+    });
+
+    group.bench_function("set (splice)", |b| {
+        let mut rec = SpookyRecordMut::new(bytes.clone(), count);
+        let mut toggle = false;
+        b.iter(|| {
+            let next = if toggle { &own } else { &longer };
+            toggle = !toggle;
+            black_box(rec.set_field(black_box(field), black_box(next)))
+        })
+    });
+
+    group.finish();
+}
@@ -0,0 +1,171 @@
+//! Reusable `Vec<u8>` buffers for serialization.
+//!
+//! `serialize_into` and `serialize_into_buf` reuse the caller's buffer, but a
+//! caller that gives its buffer away (into a `DbMutation`, across a channel)
+//! needs a new one per record. A [`BufferPool`] keeps returned buffers with
+//! their capacity and hands them out again, so a steady-state write loop
+//! stops allocating record buffers:
+//!
+//! ```rust,ignore
+//! let pool = BufferPool::new();
+//! let mut buf = pool.get(); // back in the pool when dropped
+//! serialize_into_buf(&value, &mut buf)?;
+//!
+//! let owned = pool.take(); // a plain Vec<u8>; hand it back with `put`
+//! ```
+//!
+//! The pool is `Sync`; share it behind an `Arc`. `SpookyDb::set_buffer_pool`
+//! makes batch writes return their buffers to it.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError};
+
+/// A stack of idle serialization buffers. See the module docs.
+#[derive(Debug)]
+pub struct BufferPool {
+    idle: Mutex<Vec<Vec<u8>>>,
+    max_idle: usize,
+    max_capacity: usize,
+}
+
+impl BufferPool {
+    /// Idle buffers kept by [`new`](Self::new).
+    pub const DEFAULT_MAX_IDLE: usize = 64;
+    /// Largest buffer capacity [`new`](Self::new) keeps (1 MiB).
+    pub const DEFAULT_MAX_CAPACITY: usize = 1 << 20;
+
+    pub fn new() -> Self {
+        Self::with_limits(Self::DEFAULT_MAX_IDLE, Self::DEFAULT_MAX_CAPACITY)
+    }
+
+    /// Keep at most `max_idle` buffers, and none whose capacity grew past
+    /// `max_capacity`, so one huge record does not pin its memory.
+    pub fn with_limits(max_idle: usize, max_capacity: usize) -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+            max_idle,
+            max_capacity,
+        }
+    }
+
+    /// An empty buffer: the most recently returned one, or a new `Vec`.
+    pub fn take(&self) -> Vec<u8> {
+        self.lock().pop().unwrap_or_default()
+    }
+
+    /// Return `buf` for reuse. It is cleared; it is dropped instead if it
+    /// never allocated, is over the capacity limit, or the pool is full.
+    pub fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > self.max_capacity {
+            return;
+        }
+        buf.clear();
+        let mut idle = self.lock();
+        if idle.len() < self.max_idle {
+            idle.push(buf);
+        }
+    }
+
+    /// [`take`](Self::take) wrapped in a guard that `put`s it back on drop.
+    pub fn get(&self) -> PooledBuf<'_> {
+        PooledBuf {
+            buf: self.take(),
+            pool: self,
+        }
+    }
+
+    /// Number of idle buffers.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // A panic elsewhere cannot leave the stack half-updated.
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A buffer on loan from a [`BufferPool`]; derefs to `Vec<u8>` and returns
+/// to the pool when dropped.
+#[derive(Debug)]
+pub struct PooledBuf<'a> {
+    buf: Vec<u8>,
+    pool: &'a BufferPool,
+}
+
+impl PooledBuf<'_> {
+    /// Keep the buffer instead of returning it.
+    pub fn into_inner(mut self) -> Vec<u8> {
+        // Leaves an unallocated Vec, which `put` ignores on drop.
+        std::mem::take(&mut self.buf)
+    }
+}
+
+impl Deref for PooledBuf<'_> {
+    type Target = Vec<u8>;
+
+    #[inline]
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuf<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuf<'_> {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::serialize_into_buf;
+    use crate::spooky_value::SpookyValue;
+
+    #[test]
+    fn test_pool_reuses_buffers() {
+        let pool = BufferPool::new();
+        let value = SpookyValue::from_json_str(r#"{"name":"Alice","age":28}"#).unwrap();
+        let ptr = {
+            let mut buf = pool.get();
+            serialize_into_buf(&value, &mut buf).unwrap();
+            buf.as_ptr()
+        };
+        assert_eq!(pool.idle(), 1);
+
+        // Same allocation, cleared.
+        let mut buf = pool.get();
+        assert!(buf.is_empty());
+        serialize_into_buf(&value, &mut buf).unwrap();
+        assert_eq!(buf.as_ptr(), ptr);
+        let owned = buf.into_inner();
+        assert_eq!(pool.idle(), 0);
+        pool.put(owned);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn test_pool_limits() {
+        let pool = BufferPool::with_limits(2, 64);
+        pool.put(Vec::new()); // never allocated
+        pool.put(Vec::with_capacity(128)); // too large
+        assert_eq!(pool.idle(), 0);
+        for _ in 0..3 {
+            pool.put(Vec::with_capacity(16));
+        }
+        assert_eq!(pool.idle(), 2);
+    }
+}
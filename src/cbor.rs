@@ -0,0 +1,863 @@
+use crate::error::RecordError;
+use crate::spooky_value::{FastMap, SpookyNumber, SpookyValue};
+use crate::surreal;
+use smol_str::SmolStr;
+use std::io::{BufRead, Read};
+
+// ─── CBOR bridge ────────────────────────────────────────────────────────────
+//
+// Every CBOR value model the crate accepts goes through this module, so the
+// conversion policy is written once:
+//
+//   * integers  → I64 when they fit, else U64, else F64 (lossy)
+//   * bignums   → tags 2/3 over a byte string, decoded like any other integer
+//   * SurrealDB tags (datetimes, durations, record ids, uuids, decimals)
+//                 → native scalars, see `crate::surreal`
+//   * other tags → `SpookyValue::Tagged(tag, item)`, with the item converted
+//                 under these same rules; encoding writes the tag back out
+//   * bytes     → array of byte values (matches the serde `visit_bytes` path)
+//   * map keys  → text as-is; integer and bool keys stringified; anything
+//                 else rendered with the SurrealQL `Display` form
+//
+// The serde visitors for `SpookyValue` and the arena share `int_to_number`,
+// so decoding from bytes and converting from a parsed value tree agree.
+// serde has no notion of tags, so `Tagged` only survives the paths here:
+// `decode_slice`, the value-model conversions, and `to_vec`.
+//
+// Backends: `cbor4ii::core::Value` is always available; `ciborium::Value`
+// sits behind the `ciborium` feature.
+
+/// CBOR tag for an unsigned bignum (RFC 8949 §3.4.3).
+pub const TAG_POS_BIGNUM: u64 = 2;
+/// CBOR tag for a negative bignum: value is `-1 - n`.
+pub const TAG_NEG_BIGNUM: u64 = 3;
+
+/// Integer promotion shared by all decoders: I64 → U64 → F64.
+#[inline]
+pub(crate) fn int_to_number(i: i128) -> SpookyNumber {
+    if let Ok(v) = i64::try_from(i) {
+        SpookyNumber::I64(v)
+    } else if let Ok(v) = u64::try_from(i) {
+        SpookyNumber::U64(v)
+    } else {
+        SpookyNumber::F64(i as f64)
+    }
+}
+
+/// `u128` counterpart of [`int_to_number`] for serde's `visit_u128`.
+#[inline]
+pub(crate) fn uint_to_number(u: u128) -> SpookyNumber {
+    match i128::try_from(u) {
+        Ok(i) => int_to_number(i),
+        Err(_) => SpookyNumber::F64(u as f64),
+    }
+}
+
+/// Decode a bignum magnitude (big-endian bytes). Beyond 128 bits the result
+/// is the nearest f64.
+fn bignum(negative: bool, bytes: &[u8]) -> SpookyNumber {
+    let digits = {
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        &bytes[skip..]
+    };
+    if digits.len() <= 16 {
+        let n = digits.iter().fold(0u128, |acc, b| (acc << 8) | *b as u128);
+        if negative {
+            match i128::try_from(n) {
+                Ok(n) => int_to_number(-1 - n),
+                Err(_) => SpookyNumber::F64(-1.0 - n as f64),
+            }
+        } else {
+            uint_to_number(n)
+        }
+    } else {
+        let n = digits.iter().fold(0f64, |acc, b| acc * 256.0 + *b as f64);
+        SpookyNumber::F64(if negative { -1.0 - n } else { n })
+    }
+}
+
+#[inline]
+pub(crate) fn bytes_to_array(bytes: &[u8]) -> SpookyValue {
+    SpookyValue::Array(
+        bytes
+            .iter()
+            .map(|b| SpookyValue::Number(SpookyNumber::I64(*b as i64)))
+            .collect(),
+    )
+}
+
+/// Shape shared by the CBOR value models; each backend maps onto it 1:1.
+enum Node<V> {
+    Null,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<V>),
+    Map(Vec<(V, V)>),
+    Tag(u64, Box<V>),
+}
+
+trait CborBackend: Sized {
+    fn into_node(self) -> Node<Self>;
+    fn from_node(node: Node<Self>) -> Self;
+}
+
+fn decode<V: CborBackend>(value: V) -> SpookyValue {
+    match value.into_node() {
+        Node::Null => SpookyValue::Null,
+        Node::Bool(b) => SpookyValue::Bool(b),
+        Node::Integer(i) => SpookyValue::Number(int_to_number(i)),
+        Node::Float(f) => SpookyValue::Number(SpookyNumber::F64(f)),
+        Node::Bytes(b) => bytes_to_array(&b),
+        Node::Text(s) => SpookyValue::Str(SmolStr::from(s)),
+        Node::Array(arr) => SpookyValue::Array(arr.into_iter().map(decode).collect()),
+        Node::Map(entries) => {
+            let mut map = FastMap::new();
+            for (k, v) in entries {
+                map.insert(decode_key(k), decode(v));
+            }
+            SpookyValue::Object(map)
+        }
+        Node::Tag(tag, inner) => match inner.into_node() {
+            Node::Bytes(b) => tagged_bytes(tag, &b),
+            other => surreal::apply_tag(tag, decode(V::from_node(other))),
+        },
+    }
+}
+
+/// A tag over a byte string: bignums and binary UUIDs consume the bytes,
+/// every other tag sees them as an array of byte values.
+fn tagged_bytes(tag: u64, bytes: &[u8]) -> SpookyValue {
+    match tag {
+        TAG_POS_BIGNUM | TAG_NEG_BIGNUM => {
+            SpookyValue::Number(bignum(tag == TAG_NEG_BIGNUM, bytes))
+        }
+        _ => surreal::apply_bytes_tag(tag, bytes)
+            .unwrap_or_else(|| surreal::apply_tag(tag, bytes_to_array(bytes))),
+    }
+}
+
+fn decode_key<V: CborBackend>(key: V) -> SmolStr {
+    match decode(key) {
+        SpookyValue::Str(s) => s,
+        other => SmolStr::from(other.to_string()),
+    }
+}
+
+fn encode<V: CborBackend>(value: SpookyValue) -> V {
+    V::from_node(match value {
+        SpookyValue::Null => Node::Null,
+        SpookyValue::Bool(b) => Node::Bool(b),
+        SpookyValue::Number(SpookyNumber::I64(i)) => Node::Integer(i as i128),
+        SpookyValue::Number(SpookyNumber::U64(u)) => Node::Integer(u as i128),
+        SpookyValue::Number(SpookyNumber::F64(f)) => Node::Float(f),
+        SpookyValue::Str(s) => Node::Text(s.to_string()),
+        SpookyValue::Array(arr) => Node::Array(arr.into_iter().map(encode).collect()),
+        SpookyValue::Object(obj) => Node::Map(
+            obj.into_iter()
+                .map(|(k, v)| (V::from_node(Node::Text(k.to_string())), encode(v)))
+                .collect(),
+        ),
+        SpookyValue::Tagged(tag, inner) => Node::Tag(tag, Box::new(encode(*inner))),
+    })
+}
+
+// ─── Encoder ────────────────────────────────────────────────────────────────
+
+/// Same bytes as the serde `Serialize` impl, except that `Tagged` values are
+/// written with their tag.
+impl cbor4ii::core::enc::Encode for SpookyValue {
+    fn encode<W: cbor4ii::core::enc::Write>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), cbor4ii::core::enc::Error<W::Error>> {
+        use cbor4ii::core::types;
+        match self {
+            SpookyValue::Null => types::Null.encode(writer),
+            SpookyValue::Bool(b) => b.encode(writer),
+            SpookyValue::Number(SpookyNumber::I64(i)) => i.encode(writer),
+            SpookyValue::Number(SpookyNumber::U64(u)) => u.encode(writer),
+            SpookyValue::Number(SpookyNumber::F64(f)) => f.encode(writer),
+            SpookyValue::Str(s) => s.as_str().encode(writer),
+            SpookyValue::Array(arr) => arr.as_slice().encode(writer),
+            SpookyValue::Object(map) => {
+                types::Map::bounded(map.len(), writer)?;
+                for (k, v) in map {
+                    k.as_str().encode(writer)?;
+                    v.encode(writer)?;
+                }
+                Ok(())
+            }
+            SpookyValue::Tagged(tag, inner) => types::Tag(*tag, &**inner).encode(writer),
+        }
+    }
+}
+
+/// Append the CBOR encoding of `value` to `buf`, tags included.
+pub(crate) fn encode_into(buf: &mut Vec<u8>, value: &SpookyValue) -> Result<(), RecordError> {
+    use cbor4ii::core::enc::Encode;
+    value
+        .encode(&mut cbor4ii::core::utils::IoWriter::new(buf))
+        .map_err(|e| RecordError::CborError(e.to_string()))
+}
+
+/// Encode a `SpookyValue` as CBOR. Unlike `cbor4ii::serde::to_vec`, this
+/// writes `Tagged` values back out with their tag.
+pub fn to_vec(value: &SpookyValue) -> Result<Vec<u8>, RecordError> {
+    let mut buf = Vec::new();
+    encode_into(&mut buf, value)?;
+    Ok(buf)
+}
+
+// ─── cbor4ii backend ────────────────────────────────────────────────────────
+
+impl CborBackend for cbor4ii::core::Value {
+    fn into_node(self) -> Node<Self> {
+        use cbor4ii::core::Value;
+        match self {
+            Value::Null => Node::Null,
+            Value::Bool(b) => Node::Bool(b),
+            Value::Integer(i) => Node::Integer(i),
+            Value::Float(f) => Node::Float(f),
+            Value::Bytes(b) => Node::Bytes(b),
+            Value::Text(s) => Node::Text(s),
+            Value::Array(arr) => Node::Array(arr),
+            Value::Map(entries) => Node::Map(entries),
+            Value::Tag(tag, inner) => Node::Tag(tag, inner),
+            // `cbor4ii::core::Value` is non-exhaustive; nothing else exists in 1.2.
+            _ => Node::Null,
+        }
+    }
+
+    fn from_node(node: Node<Self>) -> Self {
+        use cbor4ii::core::Value;
+        match node {
+            Node::Null => Value::Null,
+            Node::Bool(b) => Value::Bool(b),
+            Node::Integer(i) => Value::Integer(i),
+            Node::Float(f) => Value::Float(f),
+            Node::Bytes(b) => Value::Bytes(b),
+            Node::Text(s) => Value::Text(s),
+            Node::Array(arr) => Value::Array(arr),
+            Node::Map(entries) => Value::Map(entries),
+            Node::Tag(tag, inner) => Value::Tag(tag, inner),
+        }
+    }
+}
+
+/// Whether a tag occurs anywhere in `value`.
+pub(crate) fn has_tags(value: &cbor4ii::core::Value) -> bool {
+    use cbor4ii::core::Value;
+    match value {
+        Value::Tag(..) => true,
+        Value::Array(arr) => arr.iter().any(has_tags),
+        Value::Map(entries) => entries.iter().any(|(k, v)| has_tags(k) || has_tags(v)),
+        _ => false,
+    }
+}
+
+impl From<cbor4ii::core::Value> for SpookyValue {
+    #[inline]
+    fn from(v: cbor4ii::core::Value) -> Self {
+        decode(v)
+    }
+}
+
+impl From<SpookyValue> for cbor4ii::core::Value {
+    #[inline]
+    fn from(val: SpookyValue) -> Self {
+        encode(val)
+    }
+}
+
+// ─── ciborium backend ───────────────────────────────────────────────────────
+
+#[cfg(feature = "ciborium")]
+impl CborBackend for ciborium::Value {
+    fn into_node(self) -> Node<Self> {
+        use ciborium::Value;
+        match self {
+            Value::Null => Node::Null,
+            Value::Bool(b) => Node::Bool(b),
+            Value::Integer(i) => Node::Integer(i.into()),
+            Value::Float(f) => Node::Float(f),
+            Value::Bytes(b) => Node::Bytes(b),
+            Value::Text(s) => Node::Text(s),
+            Value::Array(arr) => Node::Array(arr),
+            Value::Map(entries) => Node::Map(entries),
+            Value::Tag(tag, inner) => Node::Tag(tag, inner),
+            // `ciborium::Value` is non-exhaustive; nothing else exists in 0.2.
+            _ => Node::Null,
+        }
+    }
+
+    fn from_node(node: Node<Self>) -> Self {
+        use ciborium::Value;
+        match node {
+            Node::Null => Value::Null,
+            Node::Bool(b) => Value::Bool(b),
+            // CBOR integers span [-2^64, 2^64); wider values only come from
+            // bignum tags and are re-emitted as floats.
+            Node::Integer(i) => match ciborium::value::Integer::try_from(i) {
+                Ok(i) => Value::Integer(i),
+                Err(_) => Value::Float(i as f64),
+            },
+            Node::Float(f) => Value::Float(f),
+            Node::Bytes(b) => Value::Bytes(b),
+            Node::Text(s) => Value::Text(s),
+            Node::Array(arr) => Value::Array(arr),
+            Node::Map(entries) => Value::Map(entries),
+            Node::Tag(tag, inner) => Value::Tag(tag, inner),
+        }
+    }
+}
+
+#[cfg(feature = "ciborium")]
+impl From<ciborium::Value> for SpookyValue {
+    #[inline]
+    fn from(v: ciborium::Value) -> Self {
+        decode(v)
+    }
+}
+
+#[cfg(feature = "ciborium")]
+impl From<SpookyValue> for ciborium::Value {
+    #[inline]
+    fn from(val: SpookyValue) -> Self {
+        encode(val)
+    }
+}
+
+// ─── Pull reader ────────────────────────────────────────────────────────────
+//
+// Minimal CBOR head reader over a borrowed slice, for encoders that want to
+// walk the input once without materialising a value tree. Items it does not
+// interpret can be skipped as a whole, yielding their raw byte span.
+
+/// Nesting limit for `skip`, so hostile input cannot overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// One CBOR data item head. Lengths are `None` for indefinite-length items.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Head {
+    Unsigned(u64),
+    /// Encoded argument `n`; the value is `-1 - n`.
+    Negative(u64),
+    Bytes(Option<u64>),
+    Text(Option<u64>),
+    Array(Option<u64>),
+    Map(Option<u64>),
+    Tag(u64),
+    Bool(bool),
+    /// `null` and `undefined`.
+    Null,
+    Float(f64),
+    Break,
+}
+
+pub(crate) struct SliceReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+#[inline]
+fn malformed(what: &str) -> RecordError {
+    RecordError::CborError(what.to_string())
+}
+
+impl<'a> SliceReader<'a> {
+    #[inline]
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    #[inline]
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    /// Bytes from `start` up to the current position.
+    #[inline]
+    pub(crate) fn since(&self, start: usize) -> &'a [u8] {
+        &self.buf[start..self.pos]
+    }
+
+    #[inline]
+    fn take(&mut self, n: usize) -> Result<&'a [u8], RecordError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or_else(|| malformed("unexpected end of input"))?;
+        let out = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    #[inline]
+    fn be<const N: usize>(&mut self) -> Result<[u8; N], RecordError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    /// Argument for additional info `ai`; `None` means indefinite length.
+    fn argument(&mut self, ai: u8) -> Result<Option<u64>, RecordError> {
+        Ok(Some(match ai {
+            0..=23 => ai as u64,
+            24 => self.be::<1>()?[0] as u64,
+            25 => u16::from_be_bytes(self.be()?) as u64,
+            26 => u32::from_be_bytes(self.be()?) as u64,
+            27 => u64::from_be_bytes(self.be()?),
+            31 => return Ok(None),
+            _ => return Err(malformed("reserved additional info")),
+        }))
+    }
+
+    fn definite(&mut self, ai: u8) -> Result<u64, RecordError> {
+        self.argument(ai)?
+            .ok_or_else(|| malformed("indefinite length not allowed here"))
+    }
+
+    pub(crate) fn head(&mut self) -> Result<Head, RecordError> {
+        let byte = self.be::<1>()?[0];
+        let (major, ai) = (byte >> 5, byte & 0x1f);
+        Ok(match major {
+            0 => Head::Unsigned(self.definite(ai)?),
+            1 => Head::Negative(self.definite(ai)?),
+            2 => Head::Bytes(self.argument(ai)?),
+            3 => Head::Text(self.argument(ai)?),
+            4 => Head::Array(self.argument(ai)?),
+            5 => Head::Map(self.argument(ai)?),
+            6 => Head::Tag(self.definite(ai)?),
+            _ => match ai {
+                20 => Head::Bool(false),
+                21 => Head::Bool(true),
+                22 | 23 => Head::Null,
+                25 => Head::Float(f16_to_f64(u16::from_be_bytes(self.be()?))),
+                26 => Head::Float(f32::from_be_bytes(self.be()?) as f64),
+                27 => Head::Float(f64::from_be_bytes(self.be()?)),
+                31 => Head::Break,
+                _ => return Err(malformed("unsupported simple value")),
+            },
+        })
+    }
+
+    /// Payload of a byte or text string whose head was just read. Indefinite
+    /// strings are reassembled from their chunks.
+    pub(crate) fn string_body(
+        &mut self,
+        len: Option<u64>,
+        major: u8,
+    ) -> Result<std::borrow::Cow<'a, [u8]>, RecordError> {
+        match len {
+            Some(n) => {
+                let n = usize::try_from(n).map_err(|_| malformed("string too long"))?;
+                Ok(std::borrow::Cow::Borrowed(self.take(n)?))
+            }
+            None => {
+                let mut out = Vec::new();
+                loop {
+                    let byte = self.be::<1>()?[0];
+                    if byte == 0xff {
+                        break;
+                    }
+                    if byte >> 5 != major {
+                        return Err(malformed("mismatched string chunk"));
+                    }
+                    let n = self.definite(byte & 0x1f)?;
+                    let n = usize::try_from(n).map_err(|_| malformed("string too long"))?;
+                    out.extend_from_slice(self.take(n)?);
+                }
+                Ok(std::borrow::Cow::Owned(out))
+            }
+        }
+    }
+
+    /// Text payload for a `Head::Text`, validated as UTF-8.
+    pub(crate) fn text_body(
+        &mut self,
+        len: Option<u64>,
+    ) -> Result<std::borrow::Cow<'a, str>, RecordError> {
+        match self.string_body(len, 3)? {
+            std::borrow::Cow::Borrowed(b) => std::str::from_utf8(b)
+                .map(std::borrow::Cow::Borrowed)
+                .map_err(|_| malformed("invalid UTF-8 in text string")),
+            std::borrow::Cow::Owned(b) => String::from_utf8(b)
+                .map(std::borrow::Cow::Owned)
+                .map_err(|_| malformed("invalid UTF-8 in text string")),
+        }
+    }
+
+    /// Skip the remainder of an item whose head was just read. Returns
+    /// whether a tag occurred anywhere inside it.
+    pub(crate) fn skip_body(&mut self, head: Head) -> Result<bool, RecordError> {
+        self.skip_body_at(head, 0)
+    }
+
+    fn skip_body_at(&mut self, head: Head, depth: usize) -> Result<bool, RecordError> {
+        if depth > MAX_DEPTH {
+            return Err(malformed("nesting too deep"));
+        }
+        let items = |n: u64, per: u64| {
+            n.checked_mul(per)
+                .ok_or_else(|| malformed("length overflow"))
+        };
+        match head {
+            Head::Bytes(len) => self.string_body(len, 2).map(|_| false),
+            Head::Text(len) => self.string_body(len, 3).map(|_| false),
+            Head::Array(Some(n)) => self.skip_items(items(n, 1)?, depth),
+            Head::Map(Some(n)) => self.skip_items(items(n, 2)?, depth),
+            Head::Array(None) | Head::Map(None) => {
+                let mut tagged = false;
+                loop {
+                    match self.head()? {
+                        Head::Break => return Ok(tagged),
+                        inner => tagged |= self.skip_body_at(inner, depth + 1)?,
+                    }
+                }
+            }
+            Head::Tag(_) => {
+                let inner = self.head()?;
+                self.skip_body_at(inner, depth + 1).map(|_| true)
+            }
+            Head::Break => Err(malformed("unexpected break")),
+            _ => Ok(false),
+        }
+    }
+
+    fn skip_items(&mut self, n: u64, depth: usize) -> Result<bool, RecordError> {
+        let mut tagged = false;
+        for _ in 0..n {
+            let inner = self.head()?;
+            tagged |= self.skip_body_at(inner, depth + 1)?;
+        }
+        Ok(tagged)
+    }
+
+    /// Decode one complete item into a `SpookyValue` under the bridge policy.
+    pub(crate) fn read_value(&mut self) -> Result<SpookyValue, RecordError> {
+        let head = self.head()?;
+        self.read_body_at(head, 0)
+    }
+
+    /// Rewind to an earlier `position()`.
+    #[inline]
+    pub(crate) fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    fn read_body_at(&mut self, head: Head, depth: usize) -> Result<SpookyValue, RecordError> {
+        if depth > MAX_DEPTH {
+            return Err(malformed("nesting too deep"));
+        }
+        Ok(match head {
+            Head::Null => SpookyValue::Null,
+            Head::Bool(b) => SpookyValue::Bool(b),
+            Head::Unsigned(u) => SpookyValue::Number(int_to_number(u as i128)),
+            Head::Negative(n) => SpookyValue::Number(int_to_number(-1 - n as i128)),
+            Head::Float(f) => SpookyValue::Number(SpookyNumber::F64(f)),
+            Head::Text(len) => SpookyValue::Str(SmolStr::from(self.text_body(len)?)),
+            Head::Bytes(len) => bytes_to_array(&self.string_body(len, 2)?),
+            Head::Array(len) => {
+                // Cap the pre-allocation so a hostile length prefix can't OOM us.
+                let cap = len.unwrap_or(0).min(4096) as usize;
+                let mut arr = Vec::with_capacity(cap);
+                while let Some(head) = self.next_in(len, arr.len())? {
+                    arr.push(self.read_body_at(head, depth + 1)?);
+                }
+                SpookyValue::Array(arr)
+            }
+            Head::Map(len) => {
+                let mut map = FastMap::new();
+                let mut n = 0;
+                while let Some(head) = self.next_in(len, n)? {
+                    let key = match self.read_body_at(head, depth + 1)? {
+                        SpookyValue::Str(s) => s,
+                        other => SmolStr::from(other.to_string()),
+                    };
+                    let head = self.head()?;
+                    map.insert(key, self.read_body_at(head, depth + 1)?);
+                    n += 1;
+                }
+                SpookyValue::Object(map)
+            }
+            Head::Tag(tag) => match self.head()? {
+                Head::Bytes(len) => tagged_bytes(tag, &self.string_body(len, 2)?),
+                inner => surreal::apply_tag(tag, self.read_body_at(inner, depth + 1)?),
+            },
+            Head::Break => return Err(malformed("unexpected break")),
+        })
+    }
+
+    /// Head of the next element of a container with `len` entries, of which
+    /// `done` were read; `None` at the end (definite count or break).
+    fn next_in(&mut self, len: Option<u64>, done: usize) -> Result<Option<Head>, RecordError> {
+        match len {
+            Some(n) if done as u64 >= n => Ok(None),
+            Some(_) => self.head().map(Some),
+            None => match self.head()? {
+                Head::Break => Ok(None),
+                head => Ok(Some(head)),
+            },
+        }
+    }
+}
+
+/// Decode a single CBOR item into a `SpookyValue`, applying the bridge policy
+/// (including SurrealDB tags) without an intermediate value tree. Trailing
+/// bytes are an error.
+pub fn decode_slice(data: &[u8]) -> Result<SpookyValue, RecordError> {
+    let mut reader = SliceReader::new(data);
+    let value = reader.read_value()?;
+    if !reader.is_empty() {
+        return Err(malformed("trailing bytes after CBOR item"));
+    }
+    Ok(value)
+}
+
+// ─── Item reader ────────────────────────────────────────────────────────────
+//
+// Copies whole CBOR items off a `BufRead` without interpreting them, so a
+// large array or sequence can be handed to `SliceReader`-based encoders one
+// item at a time instead of being read into memory first.
+
+pub(crate) struct ItemReader<R> {
+    input: R,
+}
+
+fn truncated(e: std::io::Error) -> RecordError {
+    match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => malformed("unexpected end of input"),
+        _ => RecordError::CborError(e.to_string()),
+    }
+}
+
+impl<R: BufRead> ItemReader<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self { input }
+    }
+
+    /// `true` once the input is exhausted.
+    pub(crate) fn at_end(&mut self) -> Result<bool, RecordError> {
+        Ok(self.input.fill_buf().map_err(truncated)?.is_empty())
+    }
+
+    /// If the next item is an array, consume its head and return its length
+    /// (`None` for indefinite length).
+    pub(crate) fn array_head(&mut self) -> Result<Option<Option<u64>>, RecordError> {
+        match self.input.fill_buf().map_err(truncated)?.first() {
+            Some(byte) if byte >> 5 == 4 => {
+                let mut head = Vec::with_capacity(9);
+                let (_, len) = self.head(&mut head)?;
+                Ok(Some(len))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Append the next complete item to `out`. Returns `false`, appending
+    /// nothing, if the next byte is a break (the end of an indefinite
+    /// container).
+    pub(crate) fn copy_item(&mut self, out: &mut Vec<u8>) -> Result<bool, RecordError> {
+        self.copy_item_at(out, 0)
+    }
+
+    fn copy_item_at(&mut self, out: &mut Vec<u8>, depth: usize) -> Result<bool, RecordError> {
+        if depth > MAX_DEPTH {
+            return Err(malformed("nesting too deep"));
+        }
+        let start = out.len();
+        let (major, len) = self.head(out)?;
+        let items = match (major, len) {
+            (7, None) => {
+                out.truncate(start);
+                return Ok(false);
+            }
+            (0 | 1 | 7, _) => return Ok(true),
+            (2 | 3, Some(n)) => {
+                let copied = (&mut self.input).take(n).read_to_end(out).map_err(truncated)?;
+                if copied as u64 != n {
+                    return Err(malformed("unexpected end of input"));
+                }
+                return Ok(true);
+            }
+            (6, _) => Some(1),
+            (4, Some(n)) => Some(n),
+            (5, Some(n)) => Some(n.checked_mul(2).ok_or_else(|| malformed("length overflow"))?),
+            // Indefinite strings, arrays and maps: items up to a break.
+            _ => None,
+        };
+        match items {
+            Some(n) => {
+                for _ in 0..n {
+                    if !self.copy_item_at(out, depth + 1)? {
+                        return Err(malformed("unexpected break"));
+                    }
+                }
+            }
+            None => {
+                while self.copy_item_at(out, depth + 1)? {}
+                out.push(0xff);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Copy one head to `out`; returns its major type and argument (`None`
+    /// for indefinite length, or a break for major type 7).
+    fn head(&mut self, out: &mut Vec<u8>) -> Result<(u8, Option<u64>), RecordError> {
+        let mut byte = [0u8; 1];
+        self.input.read_exact(&mut byte).map_err(truncated)?;
+        out.push(byte[0]);
+        let (major, ai) = (byte[0] >> 5, byte[0] & 0x1f);
+        let arg = match ai {
+            0..=23 => Some(ai as u64),
+            24..=27 => {
+                let mut be = [0u8; 8];
+                let n = 1 << (ai - 24);
+                self.input.read_exact(&mut be[8 - n..]).map_err(truncated)?;
+                out.extend_from_slice(&be[8 - n..]);
+                Some(u64::from_be_bytes(be))
+            }
+            31 if matches!(major, 2..=5 | 7) => None,
+            _ => return Err(malformed("reserved additional info")),
+        };
+        Ok((major, arg))
+    }
+}
+
+/// IEEE 754 half precision → f64.
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let frac = (bits & 0x3ff) as f64;
+    sign * match exp {
+        0 => frac * 2f64.powi(-24),
+        31 if frac == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + frac / 1024.0) * 2f64.powi(exp - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cbor4ii::core::Value;
+
+    fn v(text: &str) -> SpookyValue {
+        SpookyValue::from_json_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_integer_promotion_matches_serde_path() {
+        for i in [0i128, -1, i64::MAX as i128, u64::MAX as i128, 1 << 100] {
+            let from_tree = SpookyValue::from(Value::Integer(i));
+            let bytes = cbor4ii::serde::to_vec(Vec::new(), &Value::Integer(i)).unwrap();
+            let from_bytes: SpookyValue = cbor4ii::serde::from_slice(&bytes).unwrap();
+            assert_eq!(from_tree, from_bytes, "integer {i}");
+        }
+        assert!(matches!(
+            SpookyValue::from(Value::Integer(u64::MAX as i128)),
+            SpookyValue::Number(SpookyNumber::U64(u64::MAX))
+        ));
+    }
+
+    #[test]
+    fn test_tags_bytes_and_keys() {
+        let tagged = Value::Tag(32, Box::new(Value::Text("https://x.dev".into())));
+        assert_eq!(
+            SpookyValue::from(tagged),
+            SpookyValue::Tagged(32, Box::new(SpookyValue::from("https://x.dev")))
+        );
+
+        let big = Value::Tag(TAG_POS_BIGNUM, Box::new(Value::Bytes(vec![0x01, 0x00])));
+        assert_eq!(SpookyValue::from(big), SpookyValue::from(256i64));
+        let neg = Value::Tag(TAG_NEG_BIGNUM, Box::new(Value::Bytes(vec![0x09])));
+        assert_eq!(SpookyValue::from(neg), SpookyValue::from(-10i64));
+
+        assert_eq!(SpookyValue::from(Value::Bytes(vec![1, 2])), v("[1,2]"));
+
+        let map = Value::Map(vec![
+            (Value::Integer(7), Value::Null),
+            (Value::Bool(true), Value::Null),
+            (Value::Float(1.5), Value::Null),
+        ]);
+        assert_eq!(
+            SpookyValue::from(map),
+            v(r#"{"7":null,"true":null,"1.5f":null}"#)
+        );
+    }
+
+    #[test]
+    fn test_cbor4ii_round_trip() {
+        let original = v(r#"{"a":[1,-2,2.5,"x"],"b":{"c":true,"d":null}}"#);
+        let cbor: Value = original.clone().into();
+        assert_eq!(SpookyValue::from(cbor), original);
+    }
+
+    #[test]
+    fn test_unknown_tags_round_trip() {
+        use crate::spooky_record::{SpookyReadable, SpookyRecord};
+        use cbor4ii::core::enc::Encode;
+
+        let uri = Value::Tag(32, Box::new(Value::Text("https://x.dev".into())));
+        let items = vec![Value::Integer(1), Value::Integer(2)];
+        let pair = Value::Tag(1234, Box::new(Value::Array(items)));
+        let tagged = Value::Map(vec![
+            (Value::Text("p".into()), pair.clone()),
+            (Value::Text("u".into()), uri),
+        ]);
+        let mut writer = cbor4ii::core::utils::BufWriter::new(Vec::new());
+        tagged.encode(&mut writer).unwrap();
+        let bytes = writer.into_inner();
+
+        let value = decode_slice(&bytes).unwrap();
+        assert_eq!(
+            value.get("p"),
+            Some(&SpookyValue::Tagged(1234, Box::new(v("[1,2]"))))
+        );
+        assert_eq!(SpookyValue::from(tagged.clone()), value);
+        assert_eq!(Value::from(value.clone()), tagged);
+        assert_eq!(to_vec(&value).unwrap(), bytes);
+        assert_eq!(value.to_json_string(), r#"{"p":[1,2],"u":"https://x.dev"}"#);
+
+        // Records store the tag in the nested CBOR, however the value came in.
+        let (buf, fc) = crate::serialization::from_spooky(&value).unwrap();
+        assert_eq!(crate::serialization::from_cbor_slice(&bytes).unwrap(), (buf.clone(), fc));
+        let record = SpookyRecord::new(&buf, fc);
+        assert_eq!(record.get_field::<SpookyValue>("u"), value.get("u").cloned());
+        assert_eq!(record.get_field::<Value>("p"), Some(pair));
+
+        // Without tags the encoder matches the serde output byte for byte.
+        let plain = v(r#"{"a":[1,-2,2.5,"x",null],"b":{"c":true},"u":18446744073709551615}"#);
+        assert_eq!(to_vec(&plain).unwrap(), cbor4ii::serde::to_vec(Vec::new(), &plain).unwrap());
+    }
+
+    #[cfg(feature = "ciborium")]
+    #[test]
+    fn test_backends_agree() {
+        let original =
+            v(r#"{"a":[1,-2,2.5,"x"],"b":{"c":true,"d":null},"u":18446744073709551615}"#);
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &original).unwrap();
+
+        let via_cbor4ii: Value = cbor4ii::serde::from_slice(&bytes).unwrap();
+        let via_ciborium: ciborium::Value = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(SpookyValue::from(via_cbor4ii), original);
+        assert_eq!(SpookyValue::from(via_ciborium), original);
+
+        let back: ciborium::Value = original.clone().into();
+        assert_eq!(SpookyValue::from(back), original);
+
+        let tagged =
+            ciborium::Value::Tag(TAG_NEG_BIGNUM, Box::new(ciborium::Value::Bytes(vec![0x09])));
+        assert_eq!(SpookyValue::from(tagged), SpookyValue::from(-10i64));
+    }
+}
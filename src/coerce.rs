@@ -0,0 +1,296 @@
+use crate::deserialization::decode_field;
+use crate::spooky_value::{SpookyNumber, SpookyValue};
+use crate::types::*;
+use std::cmp::Ordering;
+
+// ─── Coercion & Comparison ──────────────────────────────────────────────────
+//
+// Shared rules for query/view evaluation, modelled on SurrealQL:
+//
+//   - Truthiness: null, false, 0, NaN, "", [] and {} are falsy.
+//   - Ordering:   Null < Bool < Number < Str < Array < Object, with numbers
+//                 compared across I64/U64/F64 — exactly `SpookyValue::cmp`.
+//   - ORDER BY:   a missing field sorts before every present value (NONE < NULL).
+
+/// String comparison mode for [`order_by_cmp`], mirroring `ORDER BY ... COLLATE` / `NUMERIC`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortMode {
+    /// Byte-wise string order — identical to [`compare`].
+    #[default]
+    Canonical,
+    /// Case-insensitive (Unicode lowercase) string order.
+    Collate,
+    /// Natural order: runs of digits inside strings compare by numeric value.
+    Numeric,
+}
+
+/// SurrealQL truthiness.
+pub fn is_truthy(v: &SpookyValue) -> bool {
+    match v {
+        SpookyValue::Null => false,
+        SpookyValue::Bool(b) => *b,
+        SpookyValue::Number(n) => {
+            let f = n.as_f64();
+            f != 0.0 && !f.is_nan()
+        }
+        SpookyValue::Str(s) => !s.is_empty(),
+        SpookyValue::Array(a) => !a.is_empty(),
+        SpookyValue::Object(o) => !o.is_empty(),
+        SpookyValue::Tagged(_, inner) => is_truthy(inner),
+    }
+}
+
+/// Coerce to a number. Strings are parsed (trimmed) with the same
+/// I64 → U64 → F64 promotion as the CBOR bridge; bools become 0/1.
+pub fn to_number(v: &SpookyValue) -> Option<SpookyNumber> {
+    match v {
+        SpookyValue::Number(n) => Some(*n),
+        SpookyValue::Bool(b) => Some(SpookyNumber::I64(*b as i64)),
+        SpookyValue::Str(s) => parse_number(s.trim()),
+        _ => None,
+    }
+}
+
+/// Coerce to f64. See [`to_number`].
+#[inline]
+pub fn to_f64(v: &SpookyValue) -> Option<f64> {
+    to_number(v).map(SpookyNumber::as_f64)
+}
+
+/// Coerce to i64. Floats must be whole and in range. See [`to_number`].
+#[inline]
+pub fn to_i64(v: &SpookyValue) -> Option<i64> {
+    to_number(v).and_then(SpookyNumber::as_i64)
+}
+
+fn parse_number(s: &str) -> Option<SpookyNumber> {
+    if let Ok(i) = s.parse::<i64>() {
+        return Some(SpookyNumber::I64(i));
+    }
+    if let Ok(u) = s.parse::<u64>() {
+        return Some(SpookyNumber::U64(u));
+    }
+    s.parse::<f64>().ok().map(SpookyNumber::F64)
+}
+
+/// Canonical total order over values. Same as `SpookyValue::cmp`.
+#[inline]
+pub fn compare(a: &SpookyValue, b: &SpookyValue) -> Ordering {
+    a.cmp(b)
+}
+
+/// ORDER BY comparison over possibly-missing values.
+///
+/// `None` (field absent) sorts before everything, including `Null`.
+/// `mode` only changes how two strings compare; every other pair uses [`compare`].
+pub fn order_by_cmp(a: Option<&SpookyValue>, b: Option<&SpookyValue>, mode: SortMode) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(SpookyValue::Str(x)), Some(SpookyValue::Str(y))) => compare_str(x, y, mode),
+        (Some(x), Some(y)) => compare(x, y),
+    }
+}
+
+/// String comparison under a [`SortMode`].
+pub fn compare_str(a: &str, b: &str, mode: SortMode) -> Ordering {
+    match mode {
+        SortMode::Canonical => a.cmp(b),
+        SortMode::Collate => a
+            .chars()
+            .flat_map(char::to_lowercase)
+            .cmp(b.chars().flat_map(char::to_lowercase))
+            .then_with(|| a.cmp(b)),
+        SortMode::Numeric => natural_cmp(a, b).then_with(|| a.cmp(b)),
+    }
+}
+
+/// Natural ordering: "item2" < "item10".
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let na = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let nb = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                // Strip leading zeros, then longer digit run = larger number.
+                let da = trim_zeros(&a[..na]);
+                let db = trim_zeros(&b[..nb]);
+                let ord = da.len().cmp(&db.len()).then_with(|| da.cmp(db));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a = &a[na..];
+                b = &b[nb..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+#[inline]
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let n = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[n..]
+}
+
+// ─── Record-level comparison ────────────────────────────────────────────────
+
+/// Rank of a type tag in the canonical ordering. Nested CBOR is resolved
+/// by decoding, since arrays and objects share TAG_NESTED_CBOR.
+#[inline]
+fn tag_rank(tag: u8) -> Option<u8> {
+    match tag {
+        TAG_NULL => Some(0),
+        TAG_BOOL => Some(1),
+        TAG_I64 | TAG_U64 | TAG_F64 => Some(2),
+        TAG_STR => Some(3),
+        _ => None,
+    }
+}
+
+#[inline]
+fn field_number(f: &FieldRef<'_>) -> Option<SpookyNumber> {
+    let bytes: [u8; 8] = f.data.try_into().ok()?;
+    Some(match f.type_tag {
+        TAG_I64 => SpookyNumber::I64(i64::from_le_bytes(bytes)),
+        TAG_U64 => SpookyNumber::U64(u64::from_le_bytes(bytes)),
+        TAG_F64 => SpookyNumber::F64(f64::from_le_bytes(bytes)),
+        _ => return None,
+    })
+}
+
+/// Compare two raw record fields with the same result as decoding both to
+/// `SpookyValue` and calling [`compare`] — but without allocating for flat types.
+///
+/// Fields that fail to decode sort as `Null`.
+pub fn compare_fields(a: &FieldRef<'_>, b: &FieldRef<'_>) -> Ordering {
+    match (tag_rank(a.type_tag), tag_rank(b.type_tag)) {
+        (Some(ra), Some(rb)) if ra != rb => ra.cmp(&rb),
+        (Some(_), Some(_)) => match a.type_tag {
+            TAG_NULL => Ordering::Equal,
+            TAG_BOOL => a.data.first().cmp(&b.data.first()),
+            TAG_STR => a.data.cmp(b.data),
+            _ => match (field_number(a), field_number(b)) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                _ => Ordering::Equal,
+            },
+        },
+        // One flat, one nested: nested always ranks above every flat type.
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => {
+            let x: SpookyValue = decode_field(*a).unwrap_or_default();
+            let y: SpookyValue = decode_field(*b).unwrap_or_default();
+            compare(&x, &y)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::from_spooky;
+    use crate::spooky_record::{SpookyReadable, SpookyRecord};
+
+    fn v(text: &str) -> SpookyValue {
+        SpookyValue::from_json_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_truthiness() {
+        for falsy in ["null", "false", "0", "0.0", "\"\"", "[]", "{}"] {
+            assert!(!is_truthy(&v(falsy)), "{falsy} should be falsy");
+        }
+        for truthy in ["true", "-1", "0.5", "\"0\"", "[0]", "{\"a\":null}"] {
+            assert!(is_truthy(&v(truthy)), "{truthy} should be truthy");
+        }
+        assert!(!is_truthy(&SpookyValue::from(f64::NAN)));
+    }
+
+    #[test]
+    fn test_numeric_coercion() {
+        assert_eq!(to_i64(&v("\" 42 \"")), Some(42));
+        assert_eq!(to_f64(&v("\"2.5\"")), Some(2.5));
+        assert_eq!(to_i64(&v("true")), Some(1));
+        assert_eq!(to_i64(&v("2.5")), None);
+        assert!(matches!(
+            to_number(&v("\"18446744073709551615\"")),
+            Some(SpookyNumber::U64(u64::MAX))
+        ));
+        assert_eq!(to_f64(&v("\"abc\"")), None);
+        assert_eq!(to_f64(&v("null")), None);
+    }
+
+    #[test]
+    fn test_cross_type_ordering() {
+        let mut vals = vec![
+            v("{}"),
+            v("[]"),
+            v("\"a\""),
+            v("1.5"),
+            v("1"),
+            v("true"),
+            v("null"),
+        ];
+        vals.sort_by(compare);
+        assert_eq!(
+            SpookyValue::Array(vals).to_json_string(),
+            r#"[null,true,1,1.5,"a",[],{}]"#
+        );
+    }
+
+    #[test]
+    fn test_order_by_missing_and_modes() {
+        let null = SpookyValue::Null;
+        assert_eq!(
+            order_by_cmp(None, Some(&null), SortMode::Canonical),
+            Ordering::Less
+        );
+        let (a, b) = (SpookyValue::from("item10"), SpookyValue::from("item2"));
+        assert_eq!(
+            order_by_cmp(Some(&a), Some(&b), SortMode::Canonical),
+            Ordering::Less
+        );
+        assert_eq!(
+            order_by_cmp(Some(&a), Some(&b), SortMode::Numeric),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_str("Bob", "alice", SortMode::Canonical),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_str("Bob", "alice", SortMode::Collate),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_compare_fields_matches_value_order() {
+        let rec = v(
+            r#"{"n":null,"b":true,"i":-3,"u":18446744073709551615,"f":2.5,"s":"x","a":[1],"o":{"k":1},"a2":[0]}"#,
+        );
+        let (buf, count) = from_spooky(&rec).unwrap();
+        let record = SpookyRecord::new(&buf, count);
+        let names = ["n", "b", "i", "u", "f", "s", "a", "o", "a2"];
+        for x in names {
+            for y in names {
+                let fx = record.get_raw(x).unwrap();
+                let fy = record.get_raw(y).unwrap();
+                let expected = compare(rec.get(x).unwrap(), rec.get(y).unwrap());
+                assert_eq!(compare_fields(&fx, &fy), expected, "{x} vs {y}");
+            }
+        }
+    }
+}
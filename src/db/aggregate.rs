@@ -0,0 +1,398 @@
+//! Numeric aggregates (count / sum / min / max / avg) with optional grouping.
+//!
+//! [`Aggregator`] folds zero-copy records one at a time, reading the value
+//! with `get_number_as_f64` and the group key as raw field bytes, so a fold
+//! allocates once per distinct group rather than once per record. Records
+//! carry a ZSet weight: `+1` adds a row, `-1` retracts one, which lets the
+//! same fold run over a whole table or over an incremental delta.
+//!
+//! [`CountBy`], [`SumBy`] and [`AvgBy`] keep per-group results up to date
+//! instead: they remember what each record contributed, so feeding a record
+//! again after an update, or removing it after a delete, retracts the old
+//! contribution without the caller holding the old bytes.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use smol_str::SmolStr;
+
+use super::index::key_value;
+use super::types::{FastMap, Weight};
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+use crate::spooky_value::SpookyValue;
+use crate::types::TAG_NULL;
+
+/// Running aggregate for one group.
+///
+/// `count` is the weighted number of rows; `sum`, `numeric` and `avg` only
+/// see rows whose value field is numeric. `min` / `max` cannot be retracted,
+/// so they only consider rows folded with a positive weight.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Aggregate {
+    /// Weighted row count, numeric or not.
+    pub count: i64,
+    /// Weighted count of rows with a numeric value.
+    pub numeric: i64,
+    /// Weighted sum of numeric values.
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Aggregate {
+    /// Mean of the numeric values. `None` when there are none.
+    pub fn avg(&self) -> Option<f64> {
+        (self.numeric != 0).then(|| self.sum / self.numeric as f64)
+    }
+
+    /// Fold one row carrying `weight`.
+    pub fn add(&mut self, value: Option<f64>, weight: Weight) {
+        self.count += weight;
+        let Some(v) = value else {
+            return;
+        };
+        self.numeric += weight;
+        self.sum += v * weight as f64;
+        if weight > 0 {
+            self.min = Some(self.min.map_or(v, |m| m.min(v)));
+            self.max = Some(self.max.map_or(v, |m| m.max(v)));
+        }
+    }
+
+    /// Combine two partial aggregates.
+    pub fn merge(&mut self, other: &Aggregate) {
+        self.count += other.count;
+        self.numeric += other.numeric;
+        self.sum += other.sum;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// Incremental fold of records into per-group [`Aggregate`]s.
+///
+/// ```rust,ignore
+/// let mut agg = Aggregator::new("amount").group_by("region");
+/// for record in records {
+///     agg.add(&record, 1);
+/// }
+/// let by_region = agg.finish(); // BTreeMap<SpookyValue, Aggregate>
+/// ```
+#[derive(Debug)]
+pub struct Aggregator {
+    value_field: String,
+    group_field: Option<String>,
+    /// Raw group key (`[type_tag, data @ ..]`) → aggregate.
+    groups: FastMap<Vec<u8>, Aggregate>,
+    key_buf: Vec<u8>,
+}
+
+impl Aggregator {
+    /// Aggregate `value_field` over every folded record as a single group.
+    pub fn new(value_field: &str) -> Self {
+        Self {
+            value_field: value_field.to_owned(),
+            group_field: None,
+            groups: FastMap::default(),
+            key_buf: Vec::new(),
+        }
+    }
+
+    /// Group rows by the value of `field`. Rows without it group under `Null`.
+    pub fn group_by(mut self, field: &str) -> Self {
+        self.group_field = Some(field.to_owned());
+        self
+    }
+
+    /// Fold one record with ZSet `weight`.
+    pub fn add(&mut self, record: &impl SpookyReadable, weight: Weight) {
+        if weight == 0 {
+            return;
+        }
+        self.key_buf.clear();
+        match self.group_field.as_deref().and_then(|f| record.get_raw(f)) {
+            Some(raw) => {
+                self.key_buf.push(raw.type_tag);
+                self.key_buf.extend_from_slice(raw.data);
+            }
+            None => self.key_buf.push(TAG_NULL),
+        }
+        let value = record.get_number_as_f64(&self.value_field);
+        match self.groups.get_mut(self.key_buf.as_slice()) {
+            Some(agg) => agg.add(value, weight),
+            None => {
+                let mut agg = Aggregate::default();
+                agg.add(value, weight);
+                self.groups.insert(self.key_buf.clone(), agg);
+            }
+        }
+    }
+
+    /// Per-group results, ordered by group value. Groups that compare equal
+    /// as values (e.g. `1` and `1.0`) are merged. Without `group_by`, the
+    /// single group is keyed `Null`.
+    pub fn finish(self) -> BTreeMap<SpookyValue, Aggregate> {
+        let mut out: BTreeMap<SpookyValue, Aggregate> = BTreeMap::new();
+        for (key, agg) in self.groups {
+            let group = key_value(&key);
+            out.entry(group).or_default().merge(&agg);
+        }
+        out
+    }
+
+    /// Result of an ungrouped fold (all groups merged).
+    pub fn total(self) -> Aggregate {
+        let mut total = Aggregate::default();
+        for agg in self.groups.values() {
+            total.merge(agg);
+        }
+        total
+    }
+}
+
+/// A stateful operator fed one record at a time, e.g. from
+/// `SpookyDb::fold_table` and `SpookyDb::fold_batch`. Feeding the same id
+/// again replaces its previous contribution, so operators handle updates
+/// and deletes without the caller knowing the old record.
+pub trait RecordOperator {
+    /// Insert record `id`, or replace its previous contribution.
+    fn add(&mut self, id: &str, record: &SpookyRecord<'_>);
+    /// Retract record `id`'s contribution, e.g. after it was deleted.
+    fn remove(&mut self, id: &str);
+}
+
+/// Per-group running aggregate with retraction, shared by [`CountBy`],
+/// [`SumBy`] and [`AvgBy`]. Each record's last contribution is kept so an
+/// update or delete can take it back.
+#[derive(Debug, Clone)]
+struct Running {
+    group_field: SmolStr,
+    value_field: Option<SmolStr>,
+    /// Record id → (group, value) it contributed.
+    rows: FastMap<SmolStr, (SpookyValue, Option<f64>)>,
+    groups: BTreeMap<SpookyValue, Aggregate>,
+    /// Groups changed since the last `changes`.
+    changed: BTreeSet<SpookyValue>,
+}
+
+impl Running {
+    fn new(group_field: &str, value_field: Option<&str>) -> Self {
+        Self {
+            group_field: SmolStr::new(group_field),
+            value_field: value_field.map(SmolStr::new),
+            rows: FastMap::default(),
+            groups: BTreeMap::new(),
+            changed: BTreeSet::new(),
+        }
+    }
+
+    fn add(&mut self, id: &str, record: &impl SpookyReadable) {
+        let group = record.get_field::<SpookyValue>(&self.group_field);
+        let group = group.unwrap_or(SpookyValue::Null);
+        let value = self.value_field.as_ref().and_then(|f| record.get_number_as_f64(f));
+        if let Some((old_group, old_value)) = self.rows.get(id) {
+            if *old_group == group && *old_value == value {
+                return;
+            }
+            self.remove(id);
+        }
+        self.fold(group.clone(), value, 1);
+        self.rows.insert(SmolStr::new(id), (group, value));
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some((group, value)) = self.rows.remove(id) {
+            self.fold(group, value, -1);
+        }
+    }
+
+    fn fold(&mut self, group: SpookyValue, value: Option<f64>, weight: Weight) {
+        let agg = self.groups.entry(group.clone()).or_default();
+        agg.add(value, weight);
+        if agg.count == 0 {
+            self.groups.remove(&group);
+        }
+        self.changed.insert(group);
+    }
+
+    fn snapshot<T>(&self, output: impl Fn(&Aggregate) -> Option<T>) -> BTreeMap<SpookyValue, T> {
+        let outputs = self.groups.iter().filter_map(|(g, agg)| Some((g.clone(), output(agg)?)));
+        outputs.collect()
+    }
+
+    fn changes<T>(
+        &mut self,
+        output: impl Fn(&Aggregate) -> Option<T>,
+    ) -> BTreeMap<SpookyValue, Option<T>> {
+        let changed = std::mem::take(&mut self.changed);
+        let value = |group: &SpookyValue| self.groups.get(group).and_then(&output);
+        changed
+            .into_iter()
+            .map(|group| {
+                let output = value(&group);
+                (group, output)
+            })
+            .collect()
+    }
+}
+
+/// Running row count per value of a grouping field, with retraction. Rows
+/// without the field count under `Null`.
+#[derive(Debug, Clone)]
+pub struct CountBy(Running);
+
+impl CountBy {
+    /// Count rows per value of `group_field`.
+    pub fn new(group_field: &str) -> Self {
+        Self(Running::new(group_field, None))
+    }
+
+    /// Fold record `id`, replacing its previous contribution.
+    pub fn add(&mut self, id: &str, record: &impl SpookyReadable) {
+        self.0.add(id, record);
+    }
+
+    /// Retract record `id`'s contribution.
+    pub fn remove(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+
+    /// One group's row count.
+    pub fn get(&self, group: &SpookyValue) -> Option<i64> {
+        self.0.groups.get(group).and_then(CountBy::output)
+    }
+
+    /// Every group's value, ordered by group.
+    pub fn snapshot(&self) -> BTreeMap<SpookyValue, i64> {
+        self.0.snapshot(CountBy::output)
+    }
+
+    /// Groups changed since the last call with their new value, `None`
+    /// for a group that has none left.
+    pub fn changes(&mut self) -> BTreeMap<SpookyValue, Option<i64>> {
+        self.0.changes(CountBy::output)
+    }
+
+    fn output(agg: &Aggregate) -> Option<i64> {
+        Some(agg.count)
+    }
+}
+
+impl RecordOperator for CountBy {
+    fn add(&mut self, id: &str, record: &SpookyRecord<'_>) {
+        self.0.add(id, record);
+    }
+
+    fn remove(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+}
+
+/// Running sum of a numeric field per value of a grouping field, with
+/// retraction. Non-numeric values add nothing.
+#[derive(Debug, Clone)]
+pub struct SumBy(Running);
+
+impl SumBy {
+    /// Sum `value_field` per value of `group_field`.
+    pub fn new(group_field: &str, value_field: &str) -> Self {
+        Self(Running::new(group_field, Some(value_field)))
+    }
+
+    /// Fold record `id`, replacing its previous contribution.
+    pub fn add(&mut self, id: &str, record: &impl SpookyReadable) {
+        self.0.add(id, record);
+    }
+
+    /// Retract record `id`'s contribution.
+    pub fn remove(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+
+    /// One group's sum.
+    pub fn get(&self, group: &SpookyValue) -> Option<f64> {
+        self.0.groups.get(group).and_then(SumBy::output)
+    }
+
+    /// Every group's value, ordered by group.
+    pub fn snapshot(&self) -> BTreeMap<SpookyValue, f64> {
+        self.0.snapshot(SumBy::output)
+    }
+
+    /// Groups changed since the last call with their new value, `None`
+    /// for a group that has none left.
+    pub fn changes(&mut self) -> BTreeMap<SpookyValue, Option<f64>> {
+        self.0.changes(SumBy::output)
+    }
+
+    fn output(agg: &Aggregate) -> Option<f64> {
+        Some(agg.sum)
+    }
+}
+
+impl RecordOperator for SumBy {
+    fn add(&mut self, id: &str, record: &SpookyRecord<'_>) {
+        self.0.add(id, record);
+    }
+
+    fn remove(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+}
+
+/// Running mean of a numeric field per value of a grouping field, with
+/// retraction. A group without numeric values has no mean.
+#[derive(Debug, Clone)]
+pub struct AvgBy(Running);
+
+impl AvgBy {
+    /// Average `value_field` per value of `group_field`.
+    pub fn new(group_field: &str, value_field: &str) -> Self {
+        Self(Running::new(group_field, Some(value_field)))
+    }
+
+    /// Fold record `id`, replacing its previous contribution.
+    pub fn add(&mut self, id: &str, record: &impl SpookyReadable) {
+        self.0.add(id, record);
+    }
+
+    /// Retract record `id`'s contribution.
+    pub fn remove(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+
+    /// One group's mean.
+    pub fn get(&self, group: &SpookyValue) -> Option<f64> {
+        self.0.groups.get(group).and_then(AvgBy::output)
+    }
+
+    /// Every group's value, ordered by group.
+    pub fn snapshot(&self) -> BTreeMap<SpookyValue, f64> {
+        self.0.snapshot(AvgBy::output)
+    }
+
+    /// Groups changed since the last call with their new value, `None`
+    /// for a group that has none left.
+    pub fn changes(&mut self) -> BTreeMap<SpookyValue, Option<f64>> {
+        self.0.changes(AvgBy::output)
+    }
+
+    fn output(agg: &Aggregate) -> Option<f64> {
+        agg.avg()
+    }
+}
+
+impl RecordOperator for AvgBy {
+    fn add(&mut self, id: &str, record: &SpookyRecord<'_>) {
+        self.0.add(id, record);
+    }
+
+    fn remove(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+}
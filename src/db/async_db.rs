@@ -0,0 +1,361 @@
+//! Future-based writes for async servers (feature `async`).
+//!
+//! [`AsyncSpookyDb`] owns a background writer thread. Write methods enqueue
+//! their mutations and return a [`Commit`] future that resolves once the
+//! transaction holding them has committed, so an async task never blocks its
+//! runtime on an fsync. Whatever queues up while the writer is busy is
+//! committed together through [`SharedSpookyDb::apply_batches`] — one
+//! transaction and one fsync for the group — and every caller still gets its
+//! own result.
+//!
+//! The futures are plain `std::future::Future`s woken through their `Waker`,
+//! so they work under any executor. Reads are synchronous and go through the
+//! wrapped [`SharedSpookyDb`]; they never wait for the writer.
+//!
+//! ```rust,ignore
+//! let db = AsyncSpookyDb::new("data.redb")?;
+//! let (id, weight) = db
+//!     .apply_mutation("users", Operation::Create, "alice", Some(&bytes), Some(1))
+//!     .await?;
+//! let age = db.shared().with_row_record("users", "alice", |r| r.get_i64("age"))?;
+//! ```
+
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+use smol_str::SmolStr;
+
+use super::shared::SharedSpookyDb;
+use super::types::{BatchMutationResult, DbMutation, Operation, SpookyDbConfig, SpookyDbError};
+
+/// Stop draining the queue into the current group once it holds this many
+/// mutations.
+const MAX_GROUP_MUTATIONS: usize = 10_000;
+
+/// Handle to a database written by a background thread. Cloning is cheap;
+/// dropping the last handle commits whatever is still queued, then joins
+/// the writer (blocking the dropping thread for that long).
+#[derive(Clone)]
+pub struct AsyncSpookyDb {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    db: SharedSpookyDb,
+    queue: Option<Sender<Job>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+/// One caller's batch and where its result goes.
+struct Job {
+    mutations: Vec<DbMutation>,
+    done: Box<dyn FnOnce(Result<BatchMutationResult, SpookyDbError>) + Send>,
+}
+
+// ─── Construction ─────────────────────────────────────────────────────────────
+
+impl AsyncSpookyDb {
+    /// Open or create the database at `path` and start its writer thread.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, SpookyDbError> {
+        Self::new_with_config(path, SpookyDbConfig::default())
+    }
+
+    /// As [`SharedSpookyDb::new_with_config`], plus the writer thread.
+    pub fn new_with_config(
+        path: impl AsRef<Path>,
+        config: SpookyDbConfig,
+    ) -> Result<Self, SpookyDbError> {
+        let db = SharedSpookyDb::new_with_config(path, config)?;
+        let (queue, jobs) = mpsc::channel();
+        let writer_db = db.clone();
+        let writer = std::thread::Builder::new()
+            .name("spooky-writer".into())
+            .spawn(move || run_writer(writer_db, jobs))
+            .map_err(|e| SpookyDbError::Writer(e.to_string()))?;
+        let inner = Inner {
+            db,
+            queue: Some(queue),
+            writer: Some(writer),
+        };
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+}
+
+// ─── Write Operations ─────────────────────────────────────────────────────────
+
+impl AsyncSpookyDb {
+    /// Queue one mutation. Resolves to what `SpookyDb::apply_mutation` would
+    /// return, once committed.
+    pub fn apply_mutation(
+        &self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Commit<(SmolStr, i64)> {
+        let mutation = DbMutation {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+            op,
+            data: data.map(<[u8]>::to_vec),
+            version,
+            expires_at: None,
+        };
+        let id = mutation.id.clone();
+        self.enqueue(vec![mutation], move |_| (id, op.weight()))
+    }
+
+    /// Queue a batch. It commits atomically, possibly in the same transaction
+    /// as other callers' batches, and resolves to its own result.
+    pub fn apply_batch(&self, mutations: Vec<DbMutation>) -> Commit<BatchMutationResult> {
+        self.enqueue(mutations, |result| result)
+    }
+
+    /// Resolves once everything queued before it has committed.
+    pub fn flush(&self) -> Commit<()> {
+        self.enqueue(Vec::new(), |_| ())
+    }
+
+    fn enqueue<T: Send + 'static>(
+        &self,
+        mutations: Vec<DbMutation>,
+        map: impl FnOnce(BatchMutationResult) -> T + Send + 'static,
+    ) -> Commit<T> {
+        let slot = Arc::new(Mutex::new(Slot {
+            value: None,
+            waker: None,
+        }));
+        let completer = Completer {
+            slot: Some(Arc::clone(&slot)),
+        };
+        let job = Job {
+            mutations,
+            done: Box::new(move |result| completer.complete(result.map(map))),
+        };
+        if let Some(queue) = &self.inner.queue {
+            // A send error drops the job, and with it the completer, which
+            // resolves the future with `SpookyDbError::Writer`.
+            let _ = queue.send(job);
+        }
+        Commit { slot }
+    }
+}
+
+// ─── Reads ────────────────────────────────────────────────────────────────────
+
+impl AsyncSpookyDb {
+    /// Synchronous reads, bulk loads and configuration. Writes made through
+    /// it bypass the queue but are still serialized with the writer thread.
+    pub fn shared(&self) -> &SharedSpookyDb {
+        &self.inner.db
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Closing the queue ends the writer loop after it drains.
+        drop(self.queue.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Commit queued jobs in groups until every sender is gone.
+fn run_writer(db: SharedSpookyDb, jobs: Receiver<Job>) {
+    while let Ok(first) = jobs.recv() {
+        let mut size = first.mutations.len();
+        let mut group = vec![first];
+        while size < MAX_GROUP_MUTATIONS
+            && let Ok(job) = jobs.try_recv()
+        {
+            size += job.mutations.len();
+            group.push(job);
+        }
+
+        let (batches, waiters): (Vec<_>, Vec<_>) =
+            group.into_iter().map(|j| (j.mutations, j.done)).unzip();
+        match db.apply_batches(batches) {
+            Ok(results) => {
+                for (done, result) in waiters.into_iter().zip(results) {
+                    done(result);
+                }
+            }
+            Err(e) => {
+                // Errors are not Clone: the first caller gets the original.
+                let message = format!("group commit failed: {e}");
+                let mut waiters = waiters.into_iter();
+                if let Some(done) = waiters.next() {
+                    done(Err(e));
+                }
+                for done in waiters {
+                    done(Err(SpookyDbError::Writer(message.clone())));
+                }
+            }
+        }
+    }
+}
+
+// ─── Commit future ────────────────────────────────────────────────────────────
+
+struct Slot<T> {
+    value: Option<Result<T, SpookyDbError>>,
+    waker: Option<Waker>,
+}
+
+/// Future returned by [`AsyncSpookyDb`] writes. Resolves after the commit
+/// (or its failure); dropping it does not cancel the write.
+pub struct Commit<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Future for Commit<T> {
+    type Output = Result<T, SpookyDbError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+        match slot.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Writer side of a [`Commit`]. Dropped without completing — the writer
+/// panicked or is gone — it resolves the future with an error.
+struct Completer<T> {
+    slot: Option<Arc<Mutex<Slot<T>>>>,
+}
+
+impl<T> Completer<T> {
+    fn complete(mut self, value: Result<T, SpookyDbError>) {
+        self.fill(value);
+    }
+
+    fn fill(&mut self, value: Result<T, SpookyDbError>) {
+        let Some(slot) = self.slot.take() else {
+            return;
+        };
+        let waker = {
+            let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+            slot.value = Some(value);
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        self.fill(Err(SpookyDbError::Writer("writer stopped".into())));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::record;
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::thread::Thread;
+    use tempfile::NamedTempFile;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                return value;
+            }
+            std::thread::park();
+        }
+    }
+
+    #[test]
+    fn test_async_writes_resolve_on_commit() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = AsyncSpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"name":"Alice"}"#);
+        let pending: Vec<_> = (0..20)
+            .map(|i| {
+                let id = format!("u{i}");
+                db.apply_mutation("users", Operation::Create, &id, Some(&bytes), Some(i))
+            })
+            .collect();
+        for (i, commit) in pending.into_iter().enumerate() {
+            let (id, weight) = block_on(commit)?;
+            assert_eq!((id.as_str(), weight), (format!("u{i}").as_str(), 1));
+        }
+        assert_eq!(db.shared().table_len("users"), 20);
+        assert_eq!(db.shared().get_version("users", "u7")?, Some(7));
+        Ok(())
+    }
+
+    #[test]
+    fn test_async_rejected_batch_only_fails_itself() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = AsyncSpookyDb::new(tmp.path())?;
+        let bad = DbMutation {
+            table: SmolStr::new("bad\u{1f}table"),
+            id: SmolStr::new("x"),
+            op: Operation::Create,
+            data: Some(record(r#"{"n":1}"#)),
+            version: None,
+            expires_at: None,
+        };
+        let rejected = db.apply_batch(vec![bad]);
+        let good = db.apply_mutation(
+            "t",
+            Operation::Create,
+            "a",
+            Some(&record(r#"{"n":2}"#)),
+            None,
+        );
+        assert!(matches!(
+            block_on(rejected),
+            Err(SpookyDbError::InvalidKey(_))
+        ));
+        assert_eq!(block_on(good)?.1, 1);
+        block_on(db.flush())?;
+        assert_eq!(db.shared().get_zset_weight("t", "a"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_async_drop_commits_queue() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        {
+            let db = AsyncSpookyDb::new(tmp.path())?;
+            let bytes = record(r#"{"n":1}"#);
+            for i in 0..10 {
+                let id = format!("r{i}");
+                drop(db.apply_mutation("t", Operation::Create, &id, Some(&bytes), None));
+            }
+        }
+        let db = SharedSpookyDb::new(tmp.path())?;
+        assert_eq!(db.table_len("t"), 10);
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+//! Portable backup format for `SpookyDb::backup` / `SpookyDb::restore`.
+//!
+//! A CBOR sequence (RFC 8742) — independent of redb's file format:
+//!
+//! ```text
+//! {"format": "spooky_db_backup", "version": 1, "created_at": ms, "tables": {name: records}}
+//! [table, id, h'record bytes', version | null]      one item per record
+//! {"end": record_count}
+//! ```
+//!
+//! The trailer makes a truncated file detectable. Readers reject a newer
+//! `version` rather than guess.
+
+use std::io::{BufRead, Write};
+
+use cbor4ii::core::Value;
+use smol_str::SmolStr;
+
+use super::types::{BulkRecord, SpookyDbError};
+
+const FORMAT: &str = "spooky_db_backup";
+const VERSION: i128 = 1;
+
+fn corrupt(msg: impl std::fmt::Display) -> SpookyDbError {
+    SpookyDbError::Serialization(format!("backup: {msg}"))
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(s.to_owned())
+}
+
+fn get<'a>(map: &'a [(Value, Value)], key: &str) -> Option<&'a Value> {
+    map.iter()
+        .find(|(k, _)| matches!(k, Value::Text(k) if k == key))
+        .map(|(_, v)| v)
+}
+
+pub(super) struct BackupWriter<W: Write> {
+    out: W,
+    records: u64,
+}
+
+impl<W: Write> BackupWriter<W> {
+    /// Write the header listing `tables` with their record counts.
+    pub(super) fn new(
+        out: W,
+        created_at: u64,
+        tables: &[(SmolStr, u64)],
+    ) -> Result<Self, SpookyDbError> {
+        let tables = tables
+            .iter()
+            .map(|(t, n)| (text(t), Value::Integer(*n as i128)))
+            .collect();
+        let header = Value::Map(vec![
+            (text("format"), text(FORMAT)),
+            (text("version"), Value::Integer(VERSION)),
+            (text("created_at"), Value::Integer(created_at as i128)),
+            (text("tables"), Value::Map(tables)),
+        ]);
+        let mut writer = Self { out, records: 0 };
+        writer.write(&header)?;
+        Ok(writer)
+    }
+
+    pub(super) fn record(
+        &mut self,
+        table: &str,
+        id: &str,
+        data: &[u8],
+        version: Option<u64>,
+    ) -> Result<(), SpookyDbError> {
+        let version = version.map_or(Value::Null, |v| Value::Integer(v as i128));
+        let item = Value::Array(vec![
+            text(table),
+            text(id),
+            Value::Bytes(data.to_vec()),
+            version,
+        ]);
+        self.write(&item)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Write the trailer and flush. Returns the number of records written.
+    pub(super) fn finish(mut self) -> Result<u64, SpookyDbError> {
+        let trailer = Value::Map(vec![(text("end"), Value::Integer(self.records as i128))]);
+        self.write(&trailer)?;
+        self.out.flush()?;
+        Ok(self.records)
+    }
+
+    fn write(&mut self, value: &Value) -> Result<(), SpookyDbError> {
+        cbor4ii::serde::to_writer(&mut self.out, value).map_err(corrupt)
+    }
+}
+
+pub(super) struct BackupReader<R: BufRead> {
+    input: R,
+    records: u64,
+}
+
+impl<R: BufRead> BackupReader<R> {
+    /// Read and check the header.
+    pub(super) fn new(mut input: R) -> Result<Self, SpookyDbError> {
+        let header: Value = cbor4ii::serde::from_reader(&mut input).map_err(corrupt)?;
+        let Value::Map(header) = header else {
+            return Err(corrupt("missing header"));
+        };
+        if !matches!(get(&header, "format"), Some(Value::Text(f)) if f == FORMAT) {
+            return Err(corrupt("not a SpookyDb backup"));
+        }
+        match get(&header, "version") {
+            Some(Value::Integer(v)) if *v <= VERSION => {}
+            Some(Value::Integer(v)) => return Err(corrupt(format!("unsupported version {v}"))),
+            _ => return Err(corrupt("missing version")),
+        }
+        Ok(Self { input, records: 0 })
+    }
+
+    /// The next record, or `None` after a trailer that matches the count.
+    pub(super) fn next_record(&mut self) -> Result<Option<BulkRecord>, SpookyDbError> {
+        if self.input.fill_buf()?.is_empty() {
+            return Err(corrupt("truncated: no trailer"));
+        }
+        let item: Value = cbor4ii::serde::from_reader(&mut self.input).map_err(corrupt)?;
+        match item {
+            Value::Array(fields) => {
+                let mut fields = fields.into_iter();
+                let (Some(Value::Text(table)), Some(Value::Text(id)), Some(Value::Bytes(data))) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(corrupt(format!("bad record {}", self.records)));
+                };
+                let version = match fields.next() {
+                    Some(Value::Integer(v)) => {
+                        Some(u64::try_from(v).map_err(|_| corrupt(format!("bad version {v}")))?)
+                    }
+                    _ => None,
+                };
+                self.records += 1;
+                Ok(Some(BulkRecord {
+                    table: SmolStr::new(table),
+                    id: SmolStr::new(id),
+                    data,
+                    version,
+                }))
+            }
+            Value::Map(trailer) => match get(&trailer, "end") {
+                Some(Value::Integer(n)) if *n == self.records as i128 => Ok(None),
+                Some(Value::Integer(n)) => Err(corrupt(format!(
+                    "trailer says {n} records, read {}",
+                    self.records
+                ))),
+                _ => Err(corrupt("bad trailer")),
+            },
+            _ => Err(corrupt(format!(
+                "unexpected item after {} records",
+                self.records
+            ))),
+        }
+    }
+}
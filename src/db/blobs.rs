@@ -0,0 +1,212 @@
+//! Out-of-line storage for oversized fields.
+//!
+//! In a table with a blob threshold, every field whose data is at least the
+//! threshold moves into BLOBS_TABLE, keyed by the xxh3-128 hash of its
+//! bytes, and the record keeps a `TAG_BLOB_REF` field in its place:
+//!
+//! ```text
+//! original type tag: u8 | data length: u64 LE | hash: u128 LE      (25 bytes)
+//! ```
+//!
+//! The index order and name hashes are unchanged, so the record stays a
+//! valid SpookyRecord. Equal contents share one blob; BLOB_REFS_TABLE counts
+//! the references from RECORDS_TABLE and a blob goes with its last one.
+//!
+//! Values that do not parse as records are stored and released untouched.
+
+use std::borrow::Cow;
+
+use redb::{ReadableTable, Table, WriteTransaction};
+use xxhash_rust::xxh3::xxh3_128;
+
+use super::db::{BLOB_REFS_TABLE, BLOBS_TABLE};
+use super::types::SpookyDbError;
+use crate::types::{HEADER_SIZE, INDEX_ENTRY_SIZE, TAG_BLOB_REF};
+
+const REF_LEN: usize = 25;
+
+/// A `TAG_BLOB_REF` field's payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct BlobRef {
+    pub(super) type_tag: u8,
+    pub(super) len: u64,
+    pub(super) hash: u128,
+}
+
+impl BlobRef {
+    pub(super) fn parse(data: &[u8]) -> Option<Self> {
+        let data: &[u8; REF_LEN] = data.try_into().ok()?;
+        Some(Self {
+            type_tag: data[0],
+            len: u64::from_le_bytes(data[1..9].try_into().ok()?),
+            hash: u128::from_le_bytes(data[9..].try_into().ok()?),
+        })
+    }
+
+    fn encode(&self) -> [u8; REF_LEN] {
+        let mut out = [0; REF_LEN];
+        out[0] = self.type_tag;
+        out[1..9].copy_from_slice(&self.len.to_le_bytes());
+        out[9..].copy_from_slice(&self.hash.to_le_bytes());
+        out
+    }
+}
+
+/// One index entry: `(name_hash, type_tag, data)`.
+type Field<'a> = (u64, u8, &'a [u8]);
+
+/// The fields of `bytes` in index order, or `None` if it is not a
+/// well-formed record.
+fn fields(bytes: &[u8]) -> Option<Vec<Field<'_>>> {
+    let count = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?) as usize;
+    let mut out = Vec::with_capacity(count);
+    for i in 0..count {
+        let at = HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+        let entry = bytes.get(at..at + INDEX_ENTRY_SIZE)?;
+        let hash = u64::from_le_bytes(entry[0..8].try_into().ok()?);
+        let offset = u32::from_le_bytes(entry[8..12].try_into().ok()?) as usize;
+        let len = u32::from_le_bytes(entry[12..16].try_into().ok()?) as usize;
+        let data = bytes.get(offset..offset.checked_add(len)?)?;
+        out.push((hash, entry[16], data));
+    }
+    Some(out)
+}
+
+/// A record with `fields` in the given (sorted) order.
+fn build(fields: &[(u64, u8, Cow<'_, [u8]>)]) -> Vec<u8> {
+    let data_start = HEADER_SIZE + fields.len() * INDEX_ENTRY_SIZE;
+    let data_len: usize = fields.iter().map(|(_, _, d)| d.len()).sum();
+    let mut buf = vec![0; data_start];
+    buf.reserve(data_len);
+    buf[0..4].copy_from_slice(&(fields.len() as u32).to_le_bytes());
+    for (i, (hash, tag, data)) in fields.iter().enumerate() {
+        let at = HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+        let offset = buf.len();
+        buf[at..at + 8].copy_from_slice(&hash.to_le_bytes());
+        buf[at + 8..at + 12].copy_from_slice(&(offset as u32).to_le_bytes());
+        buf[at + 12..at + 16].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        buf[at + 16] = *tag;
+        buf.extend_from_slice(data);
+    }
+    buf
+}
+
+/// The blob references in a stored record.
+pub(super) fn refs(bytes: &[u8]) -> Vec<BlobRef> {
+    fields(bytes)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, tag, _)| *tag == TAG_BLOB_REF)
+        .filter_map(|(_, _, data)| BlobRef::parse(data))
+        .collect()
+}
+
+/// A field moved out of a record: `(hash, data)`.
+pub(super) type Moved<'a> = (u128, &'a [u8]);
+
+/// `bytes` with every field of at least `min` bytes replaced by a
+/// reference, and the moved fields. Borrowed when nothing moves.
+pub(super) fn extract(bytes: &[u8], min: usize) -> (Cow<'_, [u8]>, Vec<Moved<'_>>) {
+    let Some(fields) = fields(bytes) else {
+        return (Cow::Borrowed(bytes), Vec::new());
+    };
+    let oversized = |(_, tag, data): &Field<'_>| *tag != TAG_BLOB_REF && data.len() >= min;
+    if !fields.iter().any(oversized) {
+        return (Cow::Borrowed(bytes), Vec::new());
+    }
+    let mut moved = Vec::new();
+    let rebuilt: Vec<_> = fields
+        .iter()
+        .map(|field @ &(name, tag, data)| {
+            if !oversized(field) {
+                return (name, tag, Cow::Borrowed(data));
+            }
+            let hash = xxh3_128(data);
+            moved.push((hash, data));
+            let blob = BlobRef {
+                type_tag: tag,
+                len: data.len() as u64,
+                hash,
+            };
+            (name, TAG_BLOB_REF, Cow::Owned(blob.encode().to_vec()))
+        })
+        .collect();
+    (Cow::Owned(build(&rebuilt)), moved)
+}
+
+/// `bytes` with every reference replaced by its blob, fetched by `get`.
+/// Borrowed when there are none.
+pub(super) fn inline<'a>(
+    bytes: &'a [u8],
+    mut get: impl FnMut(u128) -> Result<Option<Vec<u8>>, SpookyDbError>,
+) -> Result<Cow<'a, [u8]>, SpookyDbError> {
+    let Some(fields) = fields(bytes) else {
+        return Ok(Cow::Borrowed(bytes));
+    };
+    if !fields.iter().any(|(_, tag, _)| *tag == TAG_BLOB_REF) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let mut rebuilt = Vec::with_capacity(fields.len());
+    for (name, tag, data) in fields {
+        match BlobRef::parse(data).filter(|_| tag == TAG_BLOB_REF) {
+            Some(blob) => {
+                let data = get(blob.hash)?.ok_or_else(|| missing(blob.hash))?;
+                rebuilt.push((name, blob.type_tag, Cow::Owned(data)));
+            }
+            None => rebuilt.push((name, tag, Cow::Borrowed(data))),
+        }
+    }
+    Ok(Cow::Owned(build(&rebuilt)))
+}
+
+pub(super) fn missing(hash: u128) -> SpookyDbError {
+    SpookyDbError::Serialization(format!("missing blob {hash:032x}"))
+}
+
+/// BLOBS_TABLE and BLOB_REFS_TABLE opened in one write transaction.
+pub(super) struct BlobStage<'txn> {
+    blobs: Table<'txn, u128, &'static [u8]>,
+    refs: Table<'txn, u128, u64>,
+    /// Whether this transaction stored a reference.
+    pub(super) added: bool,
+}
+
+impl<'txn> BlobStage<'txn> {
+    pub(super) fn open(txn: &'txn WriteTransaction) -> Result<Self, SpookyDbError> {
+        Ok(Self {
+            blobs: txn.open_table(BLOBS_TABLE)?,
+            refs: txn.open_table(BLOB_REFS_TABLE)?,
+            added: false,
+        })
+    }
+
+    /// Store `data` under `hash`, or count one more reference to it.
+    pub(super) fn add(&mut self, hash: u128, data: &[u8]) -> Result<(), SpookyDbError> {
+        let count = self.refs.get(hash)?.map_or(0, |c| c.value());
+        if count == 0 {
+            self.blobs.insert(hash, data)?;
+        } else if self.blobs.get(hash)?.is_none_or(|b| b.value() != data) {
+            return Err(SpookyDbError::Serialization(format!(
+                "blob hash collision {hash:032x}"
+            )));
+        }
+        self.refs.insert(hash, count + 1)?;
+        self.added = true;
+        Ok(())
+    }
+
+    /// Drop the references held by a stored record being overwritten or
+    /// removed.
+    pub(super) fn release(&mut self, stored: &[u8]) -> Result<(), SpookyDbError> {
+        for blob in refs(stored) {
+            let count = self.refs.get(blob.hash)?.map_or(0, |c| c.value());
+            if count > 1 {
+                self.refs.insert(blob.hash, count - 1)?;
+            } else {
+                self.refs.remove(blob.hash)?;
+                self.blobs.remove(blob.hash)?;
+            }
+        }
+        Ok(())
+    }
+}
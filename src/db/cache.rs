@@ -0,0 +1,407 @@
+//! Row cache bounded by entry count and, optionally, by total bytes.
+//!
+//! An entry weighs its value's length plus its table and id lengths. A value
+//! heavier than the whole byte budget is not cached at all, so one large
+//! record cannot flush everything else.
+//!
+//! Tables follow a [`CachePolicy`]: by default they compete in one shared
+//! LRU, but a table can get a reserved partition with its own limits, or be
+//! pinned into an unbounded one that never evicts.
+//!
+//! `SpookyDb` caches [`CachedRow`]s: rows of up to [`INLINE_ROW`] bytes live
+//! inside the entry itself, so the many tiny rows of a typical table cost no
+//! allocation beyond the LRU node. Weights count the row's length either way.
+
+use std::borrow::Cow;
+use std::num::NonZeroUsize;
+use std::ops::Deref;
+
+use lru::LruCache;
+use smol_str::SmolStr;
+
+use super::types::{CachePolicy, FastMap};
+
+pub(super) type RowKey = (SmolStr, SmolStr);
+
+/// Longest row stored inline. With the length byte and the tag, a
+/// `CachedRow` is 128 bytes.
+pub(super) const INLINE_ROW: usize = 126;
+
+/// The bytes of one cached row: inline up to [`INLINE_ROW`], else on the heap.
+#[derive(Clone)]
+pub(super) enum CachedRow {
+    Inline { len: u8, bytes: [u8; INLINE_ROW] },
+    Heap(Vec<u8>),
+}
+
+impl Deref for CachedRow {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        match self {
+            CachedRow::Inline { len, bytes } => &bytes[..*len as usize],
+            CachedRow::Heap(bytes) => bytes,
+        }
+    }
+}
+
+impl AsRef<[u8]> for CachedRow {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<&[u8]> for CachedRow {
+    /// Copies `row`; allocates only if it is too long to inline.
+    fn from(row: &[u8]) -> Self {
+        if row.len() > INLINE_ROW {
+            return CachedRow::Heap(row.to_vec());
+        }
+        let mut bytes = [0; INLINE_ROW];
+        bytes[..row.len()].copy_from_slice(row);
+        CachedRow::Inline {
+            len: row.len() as u8,
+            bytes,
+        }
+    }
+}
+
+impl From<Vec<u8>> for CachedRow {
+    /// Keeps a long `row`'s buffer; a short one is copied inline and freed.
+    fn from(row: Vec<u8>) -> Self {
+        match row.len() > INLINE_ROW {
+            true => CachedRow::Heap(row),
+            false => CachedRow::from(row.as_slice()),
+        }
+    }
+}
+
+impl From<Cow<'_, [u8]>> for CachedRow {
+    fn from(row: Cow<'_, [u8]>) -> Self {
+        match row {
+            Cow::Borrowed(row) => row.into(),
+            Cow::Owned(row) => row.into(),
+        }
+    }
+}
+
+pub(super) struct RowCache<V> {
+    /// Rows of tables on `CachePolicy::Shared`.
+    shared: Lru<V>,
+    /// Tables with a partition of their own.
+    tables: FastMap<SmolStr, (CachePolicy, Lru<V>)>,
+}
+
+/// One LRU partition with its byte accounting.
+struct Lru<V> {
+    lru: LruCache<RowKey, V>,
+    /// Sum of entry weights.
+    bytes: usize,
+    max_bytes: Option<usize>,
+}
+
+fn weight(key: &RowKey, value: &[u8]) -> usize {
+    key.0.len() + key.1.len() + value.len()
+}
+
+impl<V: AsRef<[u8]>> Lru<V> {
+    fn new(policy: CachePolicy, shared: &Lru<V>) -> Self {
+        let (lru, max_bytes) = match policy {
+            CachePolicy::Shared => (LruCache::new(shared.lru.cap()), shared.max_bytes),
+            CachePolicy::Reserved { entries, max_bytes } => (LruCache::new(entries), max_bytes),
+            CachePolicy::Pinned => (LruCache::unbounded(), None),
+        };
+        Self {
+            lru,
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    fn put(&mut self, key: RowKey, value: V) -> usize {
+        let w = weight(&key, value.as_ref());
+        if self.max_bytes.is_some_and(|max| w > max) {
+            self.pop(&key);
+            return 0;
+        }
+        self.bytes += w;
+        let mut evicted = 0;
+        if let Some((old_key, old)) = self.lru.push(key.clone(), value) {
+            self.bytes -= weight(&old_key, old.as_ref());
+            evicted += usize::from(old_key != key);
+        }
+        while self.max_bytes.is_some_and(|max| self.bytes > max) {
+            let Some((old_key, old)) = self.lru.pop_lru() else {
+                break;
+            };
+            self.bytes -= weight(&old_key, old.as_ref());
+            evicted += 1;
+        }
+        evicted
+    }
+
+    fn pop(&mut self, key: &RowKey) -> Option<V> {
+        let value = self.lru.pop(key)?;
+        self.bytes -= weight(key, value.as_ref());
+        Some(value)
+    }
+
+    fn clear(&mut self) {
+        self.lru.clear();
+        self.bytes = 0;
+    }
+}
+
+impl<V: AsRef<[u8]>> RowCache<V> {
+    pub(super) fn new(capacity: NonZeroUsize, max_bytes: Option<usize>) -> Self {
+        Self {
+            shared: Lru {
+                lru: LruCache::new(capacity),
+                bytes: 0,
+                max_bytes,
+            },
+            tables: FastMap::default(),
+        }
+    }
+
+    fn partition(&self, table: &str) -> &Lru<V> {
+        self.tables.get(table).map_or(&self.shared, |(_, lru)| lru)
+    }
+
+    fn partition_mut(&mut self, table: &str) -> &mut Lru<V> {
+        match self.tables.get_mut(table) {
+            Some((_, lru)) => lru,
+            None => &mut self.shared,
+        }
+    }
+
+    /// Insert or replace `key`, evicting least-recently-used entries of the
+    /// same partition until its limits hold. Returns how many other entries
+    /// were evicted.
+    pub(super) fn put(&mut self, key: RowKey, value: V) -> usize {
+        self.partition_mut(&key.0).put(key, value)
+    }
+
+    /// Look up and mark as most recently used.
+    pub(super) fn get(&mut self, key: &RowKey) -> Option<&V> {
+        self.partition_mut(&key.0).lru.get(key)
+    }
+
+    /// Look up without touching recency.
+    pub(super) fn peek(&self, key: &RowKey) -> Option<&V> {
+        self.partition(&key.0).lru.peek(key)
+    }
+
+    pub(super) fn pop(&mut self, key: &RowKey) -> Option<V> {
+        self.partition_mut(&key.0).pop(key)
+    }
+
+    /// Drop every row. Policies stay.
+    pub(super) fn clear(&mut self) {
+        self.shared.clear();
+        for (_, lru) in self.tables.values_mut() {
+            lru.clear();
+        }
+    }
+
+    /// Move `table`'s cached rows into a partition for `policy`, keeping
+    /// their recency order. Returns how many rows the new limits evicted.
+    pub(super) fn set_policy(&mut self, table: &str, policy: CachePolicy) -> usize {
+        let old = self.take(table);
+        self.install(table, policy);
+        self.reinsert(old, |key| key)
+    }
+
+    /// Re-key `old`'s cached rows and policy to `new`. Returns how many rows
+    /// `new`'s partition evicted (only possible for a shared `old`).
+    pub(super) fn rename(&mut self, old: &str, new: &str) -> usize {
+        let policy = self.policy(old);
+        let rows = self.take(old);
+        self.install(new, policy);
+        let new = SmolStr::new(new);
+        self.reinsert(rows, |(_, id)| (new.clone(), id))
+    }
+
+    /// Remove `table`'s rows, oldest first, and return them with its
+    /// policy. The table reverts to `CachePolicy::Shared`.
+    pub(super) fn remove_table(&mut self, table: &str) -> (CachePolicy, Vec<(RowKey, V)>) {
+        let policy = self.policy(table);
+        let mut rows = self.take(table);
+        let mut out = Vec::with_capacity(rows.lru.len());
+        while let Some(row) = rows.lru.pop_lru() {
+            out.push(row);
+        }
+        (policy, out)
+    }
+
+    /// Remove `table`'s rows, with their partition if it has one.
+    fn take(&mut self, table: &str) -> Lru<V> {
+        match self.tables.remove(table) {
+            Some((_, lru)) => lru,
+            None => {
+                let keys: Vec<RowKey> = self
+                    .shared
+                    .lru
+                    .iter()
+                    .filter(|(key, _)| key.0 == table)
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                let mut moved = Lru::new(CachePolicy::Pinned, &self.shared);
+                // `iter` is most recent first; re-insert oldest first.
+                for key in keys.into_iter().rev() {
+                    if let Some(value) = self.shared.pop(&key) {
+                        moved.put(key, value);
+                    }
+                }
+                moved
+            }
+        }
+    }
+
+    fn install(&mut self, table: &str, policy: CachePolicy) {
+        if policy != CachePolicy::Shared {
+            let lru = Lru::new(policy, &self.shared);
+            self.tables.insert(SmolStr::new(table), (policy, lru));
+        }
+    }
+
+    /// Put `rows` back oldest first, keyed by `rekey`.
+    fn reinsert(&mut self, mut rows: Lru<V>, rekey: impl Fn(RowKey) -> RowKey) -> usize {
+        let mut evicted = 0;
+        while let Some((key, value)) = rows.lru.pop_lru() {
+            evicted += self.put(rekey(key), value);
+        }
+        evicted
+    }
+
+    pub(super) fn policy(&self, table: &str) -> CachePolicy {
+        self.tables
+            .get(table)
+            .map_or(CachePolicy::Shared, |(policy, _)| *policy)
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&RowKey, &V)> {
+        let partitions = self.tables.values().map(|(_, lru)| lru);
+        std::iter::once(&self.shared)
+            .chain(partitions)
+            .flat_map(|lru| lru.lru.iter())
+    }
+
+    pub(super) fn len(&self) -> usize {
+        let reserved: usize = self.tables.values().map(|(_, lru)| lru.lru.len()).sum();
+        self.shared.lru.len() + reserved
+    }
+
+    /// Entry limit of the shared partition plus every reserved one. Pinned
+    /// partitions are unbounded and not counted.
+    pub(super) fn cap(&self) -> NonZeroUsize {
+        let reserved: usize = self
+            .tables
+            .values()
+            .filter_map(|(policy, _)| match policy {
+                CachePolicy::Reserved { entries, .. } => Some(entries.get()),
+                _ => None,
+            })
+            .sum();
+        self.shared.lru.cap().saturating_add(reserved)
+    }
+
+    /// Sum of entry weights across all partitions.
+    pub(super) fn bytes(&self) -> usize {
+        let reserved: usize = self.tables.values().map(|(_, lru)| lru.bytes).sum();
+        self.shared.bytes + reserved
+    }
+
+    /// Byte limit of the shared partition.
+    pub(super) fn max_bytes(&self) -> Option<usize> {
+        self.shared.max_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str) -> RowKey {
+        (SmolStr::new("t"), SmolStr::new(id))
+    }
+
+    #[test]
+    fn test_byte_bound_evicts_lru_first() {
+        let mut cache = RowCache::new(NonZeroUsize::new(100).unwrap(), Some(30));
+        assert_eq!(cache.put(key("a"), vec![0; 8]), 0); // weight 10
+        assert_eq!(cache.put(key("b"), vec![0; 8]), 0);
+        cache.get(&key("a"));
+        assert_eq!(cache.put(key("c"), vec![0; 18]), 1); // weight 20: evicts b
+        assert!(cache.peek(&key("b")).is_none());
+        assert_eq!((cache.len(), cache.bytes()), (2, 30));
+
+        // Replacing an entry re-weighs it and evicts nothing else.
+        assert_eq!(cache.put(key("c"), vec![0; 8]), 0);
+        assert_eq!(cache.bytes(), 20);
+        assert_eq!(cache.pop(&key("a")).map(|v| v.len()), Some(8));
+        assert_eq!(cache.bytes(), 10);
+    }
+
+    #[test]
+    fn test_cached_row_inlines_small_rows() {
+        assert_eq!(std::mem::size_of::<CachedRow>(), 128);
+        let small = CachedRow::from(vec![7; INLINE_ROW]);
+        assert!(matches!(small, CachedRow::Inline { .. }));
+        assert_eq!(&*small, &[7; INLINE_ROW][..]);
+        let large = CachedRow::from(&[7; INLINE_ROW + 1][..]);
+        assert!(matches!(large, CachedRow::Heap(_)));
+        assert_eq!(large.len(), INLINE_ROW + 1);
+        assert!(CachedRow::from(Cow::Borrowed(&[][..])).is_empty());
+
+        // Inline rows weigh their length, not the entry size.
+        let mut cache = RowCache::new(NonZeroUsize::new(100).unwrap(), None);
+        cache.put(key("a"), CachedRow::from(&[1, 2, 3][..]));
+        assert_eq!(cache.bytes(), 5);
+        assert_eq!(cache.peek(&key("a")).map(|row| &row[..]), Some(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn test_oversized_value_is_not_cached() {
+        let mut cache = RowCache::new(NonZeroUsize::new(100).unwrap(), Some(30));
+        cache.put(key("a"), vec![0; 8]);
+        cache.put(key("big"), vec![0; 64]);
+        assert!(cache.peek(&key("big")).is_none());
+        assert!(cache.peek(&key("a")).is_some());
+        // An oversized replacement drops the stale entry.
+        cache.put(key("a"), vec![0; 64]);
+        assert_eq!((cache.len(), cache.bytes()), (0, 0));
+    }
+
+    #[test]
+    fn test_reserved_and_pinned_partitions() {
+        let mut cache = RowCache::new(NonZeroUsize::new(2).unwrap(), None);
+        let lookup = |id: &str| (SmolStr::new("lookup"), SmolStr::new(id));
+        cache.put(lookup("x"), vec![0; 8]);
+        cache.set_policy("lookup", CachePolicy::Pinned);
+        for i in 0..5 {
+            cache.put(lookup(&i.to_string()), vec![0; 8]);
+            cache.put(key(&i.to_string()), vec![0; 8]);
+        }
+        // Shared LRU keeps 2 of "t"; the pinned table keeps all 6 rows.
+        assert_eq!(cache.len(), 8);
+        assert!(cache.peek(&lookup("x")).is_some());
+        assert_eq!(cache.cap().get(), 2);
+
+        let reserved = CachePolicy::Reserved {
+            entries: NonZeroUsize::new(3).unwrap(),
+            max_bytes: None,
+        };
+        assert_eq!(cache.set_policy("lookup", reserved), 3);
+        assert_eq!((cache.len(), cache.cap().get()), (5, 5));
+        // The most recent rows survive the move.
+        assert!(cache.peek(&lookup("4")).is_some());
+        assert!(cache.peek(&lookup("x")).is_none());
+
+        assert_eq!(cache.set_policy("lookup", CachePolicy::Shared), 3);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.policy("lookup"), CachePolicy::Shared);
+    }
+}
@@ -0,0 +1,269 @@
+//! Composable dataflow over table deltas.
+//!
+//! A [`Circuit`] is a DAG built from the operators of [`zset`](super::zset):
+//! sources are tables, nodes transform ZSets of ids, and sinks are named
+//! outputs. `step` feeds one committed batch's membership deltas in and
+//! pushes the change through every node in build order, so each tick costs
+//! O(delta) — linear operators (filter, map, union, difference) work on the
+//! delta alone, while `distinct` and `join` keep the integrated state of
+//! their inputs. Nodes see ids only; for filters and projections over
+//! record content use a [`View`](super::View).
+//!
+//! ```rust,ignore
+//! let mut circuit = Circuit::new();
+//! let comments = circuit.source("comments");
+//! let posts = circuit.source("posts");
+//! let post_of = |id: &RowKey| id.split_once(':').map(|(post, _)| SmolStr::new(post));
+//! let post_id = |id: &RowKey| Some(id.clone());
+//! let joined = circuit.join(comments, posts, post_of, post_id, |_, post| post.clone());
+//! let discussed = circuit.distinct(joined);
+//! circuit.sink("discussed", discussed);
+//! db.seed_circuit(&mut circuit);
+//! let changes = circuit.step(&db.apply_batch(mutations)?);
+//! ```
+
+use smol_str::SmolStr;
+
+use super::types::{BatchMutationResult, FastMap, RowKey, ZSet};
+use super::zset::{self, add_weight};
+
+type Predicate = Box<dyn Fn(&RowKey) -> bool + Send + Sync>;
+type KeyFn = Box<dyn Fn(&RowKey) -> Option<RowKey> + Send + Sync>;
+type MapFn = Box<dyn Fn(&RowKey) -> RowKey + Send + Sync>;
+type OutputFn = Box<dyn Fn(&RowKey, &RowKey) -> RowKey + Send + Sync>;
+
+/// A node's output within the [`Circuit`] that created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stream(usize);
+
+/// See the module docs.
+#[derive(Default)]
+pub struct Circuit {
+    /// In build order, which is a topological order: a node only reads
+    /// streams created before it.
+    nodes: Vec<Node>,
+    sinks: Vec<Sink>,
+}
+
+enum Node {
+    Source(SmolStr),
+    Filter(Stream, Predicate),
+    Map(Stream, MapFn),
+    Union(Stream, Stream),
+    Difference(Stream, Stream),
+    Distinct {
+        input: Stream,
+        /// Integrated input.
+        state: ZSet,
+    },
+    Join {
+        left: Stream,
+        right: Stream,
+        left_key: KeyFn,
+        right_key: KeyFn,
+        output: OutputFn,
+        /// Integrated inputs, grouped by join key.
+        left_state: FastMap<RowKey, ZSet>,
+        right_state: FastMap<RowKey, ZSet>,
+    },
+}
+
+struct Sink {
+    name: SmolStr,
+    input: Stream,
+    /// Integrated output.
+    state: ZSet,
+}
+
+impl Circuit {
+    /// An empty circuit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The membership deltas of `table`.
+    pub fn source(&mut self, table: &str) -> Stream {
+        self.push(Node::Source(SmolStr::new(table)))
+    }
+
+    /// The entries of `input` whose id satisfies `keep`.
+    pub fn filter(
+        &mut self,
+        input: Stream,
+        keep: impl Fn(&RowKey) -> bool + Send + Sync + 'static,
+    ) -> Stream {
+        self.push(Node::Filter(input, Box::new(keep)))
+    }
+
+    /// `input` re-keyed by `f`; colliding keys have their weights summed.
+    pub fn map(
+        &mut self,
+        input: Stream,
+        f: impl Fn(&RowKey) -> RowKey + Send + Sync + 'static,
+    ) -> Stream {
+        self.push(Node::Map(input, Box::new(f)))
+    }
+
+    /// `a + b`.
+    pub fn union(&mut self, a: Stream, b: Stream) -> Stream {
+        self.push(Node::Union(a, b))
+    }
+
+    /// `a - b`.
+    pub fn difference(&mut self, a: Stream, b: Stream) -> Stream {
+        self.push(Node::Difference(a, b))
+    }
+
+    /// Weight 1 for every key whose integrated weight is positive.
+    pub fn distinct(&mut self, input: Stream) -> Stream {
+        self.push(Node::Distinct {
+            input,
+            state: ZSet::default(),
+        })
+    }
+
+    /// Equi-join as [`zset::join_by_key`]: each left and right key whose
+    /// `left_key` and `right_key` agree produce `output(left, right)` with
+    /// the product of their weights.
+    pub fn join(
+        &mut self,
+        left: Stream,
+        right: Stream,
+        left_key: impl Fn(&RowKey) -> Option<RowKey> + Send + Sync + 'static,
+        right_key: impl Fn(&RowKey) -> Option<RowKey> + Send + Sync + 'static,
+        output: impl Fn(&RowKey, &RowKey) -> RowKey + Send + Sync + 'static,
+    ) -> Stream {
+        self.push(Node::Join {
+            left,
+            right,
+            left_key: Box::new(left_key),
+            right_key: Box::new(right_key),
+            output: Box::new(output),
+            left_state: FastMap::default(),
+            right_state: FastMap::default(),
+        })
+    }
+
+    /// Publish `input` as the output `name`. A name used twice keeps the
+    /// first sink.
+    pub fn sink(&mut self, name: &str, input: Stream) {
+        self.check(input);
+        if self.sinks.iter().all(|sink| sink.name != name) {
+            self.sinks.push(Sink {
+                name: SmolStr::new(name),
+                input,
+                state: ZSet::default(),
+            });
+        }
+    }
+
+    /// Tables read by the circuit's sources, in build order.
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().filter_map(|node| match node {
+            Node::Source(table) => Some(table.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Integrated output of sink `name`.
+    pub fn output(&self, name: &str) -> Option<&ZSet> {
+        self.sinks.iter().find(|sink| sink.name == name).map(|sink| &sink.state)
+    }
+
+    /// Names of the sinks, in the order they were added.
+    pub fn sink_names(&self) -> impl Iterator<Item = &str> {
+        self.sinks.iter().map(|sink| sink.name.as_str())
+    }
+
+    /// Propagate a committed batch's membership deltas. Returns the change
+    /// of every sink that changed.
+    pub fn step(&mut self, result: &BatchMutationResult) -> FastMap<SmolStr, ZSet> {
+        self.step_deltas(&result.membership_deltas)
+    }
+
+    /// [`step`](Self::step) from per-table deltas, e.g. a `DeltaBatch`'s.
+    pub fn step_deltas(&mut self, deltas: &FastMap<SmolStr, ZSet>) -> FastMap<SmolStr, ZSet> {
+        let mut out: Vec<ZSet> = Vec::with_capacity(self.nodes.len());
+        for node in &mut self.nodes {
+            let delta = match node {
+                Node::Source(table) => deltas.get(table).cloned().unwrap_or_default(),
+                Node::Filter(input, keep) => zset::filter(&out[input.0], &**keep),
+                Node::Map(input, f) => zset::map_keys(&out[input.0], &**f),
+                Node::Union(a, b) => zset::union(&out[a.0], &out[b.0]),
+                Node::Difference(a, b) => zset::difference(&out[a.0], &out[b.0]),
+                Node::Distinct { input, state } => {
+                    let delta = zset::distinct_delta(state, &out[input.0]);
+                    zset::union_into(state, &out[input.0]);
+                    delta
+                }
+                Node::Join {
+                    left,
+                    right,
+                    left_key,
+                    right_key,
+                    output,
+                    left_state,
+                    right_state,
+                } => {
+                    // Δ(a ⋈ b) = Δa ⋈ b + (a + Δa) ⋈ Δb
+                    let mut delta = ZSet::default();
+                    for (id, &weight) in &out[left.0] {
+                        let Some(key) = left_key(id) else { continue };
+                        for (other, &other_weight) in right_state.get(&key).into_iter().flatten() {
+                            add_weight(&mut delta, &output(id, other), weight * other_weight);
+                        }
+                        add_grouped(left_state, key, id, weight);
+                    }
+                    for (id, &weight) in &out[right.0] {
+                        let Some(key) = right_key(id) else { continue };
+                        for (other, &other_weight) in left_state.get(&key).into_iter().flatten() {
+                            add_weight(&mut delta, &output(other, id), other_weight * weight);
+                        }
+                        add_grouped(right_state, key, id, weight);
+                    }
+                    delta
+                }
+            };
+            out.push(delta);
+        }
+
+        let mut changes = FastMap::default();
+        for sink in &mut self.sinks {
+            let delta = &out[sink.input.0];
+            if !delta.is_empty() {
+                zset::union_into(&mut sink.state, delta);
+                changes.insert(sink.name.clone(), delta.clone());
+            }
+        }
+        changes
+    }
+
+    fn push(&mut self, node: Node) -> Stream {
+        let inputs = match &node {
+            Node::Source(_) => vec![],
+            Node::Filter(input, _) | Node::Map(input, _) | Node::Distinct { input, .. } => {
+                vec![*input]
+            }
+            Node::Union(a, b) | Node::Difference(a, b) => vec![*a, *b],
+            Node::Join { left, right, .. } => vec![*left, *right],
+        };
+        for input in inputs {
+            self.check(input);
+        }
+        self.nodes.push(node);
+        Stream(self.nodes.len() - 1)
+    }
+
+    fn check(&self, stream: Stream) {
+        assert!(stream.0 < self.nodes.len(), "stream {stream:?} is not from this circuit");
+    }
+}
+
+/// Add `weight` to `id` in group `key`, dropping the group when it empties.
+fn add_grouped(state: &mut FastMap<RowKey, ZSet>, key: RowKey, id: &RowKey, weight: i64) {
+    let group = state.entry(key.clone()).or_default();
+    add_weight(group, id, weight);
+    if group.is_empty() {
+        state.remove(&key);
+    }
+}
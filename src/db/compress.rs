@@ -0,0 +1,277 @@
+//! Transparent at-rest compression of record bytes.
+//!
+//! A RECORDS_TABLE value is either plain SpookyRecord bytes or
+//!
+//! ```text
+//! FF FF FF FF  01  <compressed SpookyRecord bytes>
+//! FF FF FF FF  02  <u32 LE schema version>  <plain or compressed value>
+//! ```
+//!
+//! A SpookyRecord starts with its u32 LE field count, and a record cannot
+//! have `u32::MAX` fields, so the marker never collides with a plain value.
+//! The fifth byte tags the encoding; `01` means [`Compressor`] output and
+//! `02` the schema version of a migrated table the value was written at
+//! (see `SpookyDb::migrate_lazy`).
+//!
+//! Compression is applied per table to values of at least its threshold and
+//! kept only when it saves bytes. Decoding never looks at the table, so
+//! changing or clearing a threshold leaves existing values readable.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use smol_str::SmolStr;
+
+use super::types::{FastMap, SpookyDbError};
+
+/// A byte codec for at-rest compression. The `zstd` feature ships
+/// [`ZstdCompressor`]; other codecs implement the trait themselves:
+///
+/// ```rust,ignore
+/// struct Lz4;
+/// impl Compressor for Lz4 {
+///     fn compress(&self, bytes: &[u8]) -> Vec<u8> {
+///         lz4_flex::compress_prepend_size(bytes)
+///     }
+///     fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+///         lz4_flex::decompress_size_prepended(payload).map_err(|e| e.to_string())
+///     }
+/// }
+/// ```
+///
+/// Values written with one compressor must be read with a compatible one.
+pub trait Compressor: Send + Sync {
+    fn compress(&self, bytes: &[u8]) -> Vec<u8>;
+    fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// zstd at a fixed level (`zstd` feature). Any level reads any other's
+/// output.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy)]
+pub struct ZstdCompressor {
+    level: i32,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCompressor {
+    /// Compress at `level` (1–22; 0 means zstd's default, 3). Out-of-range
+    /// levels are clamped.
+    pub fn new(level: i32) -> Self {
+        let range = zstd::compression_level_range();
+        Self {
+            level: level.clamp(*range.start(), *range.end()),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Default for ZstdCompressor {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Compressor for ZstdCompressor {
+    fn compress(&self, bytes: &[u8]) -> Vec<u8> {
+        zstd::bulk::compress(bytes, self.level).expect("in-memory zstd at a valid level")
+    }
+
+    fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+        zstd::stream::decode_all(payload).map_err(|e| e.to_string())
+    }
+}
+
+const MARKER: [u8; 4] = [0xFF; 4];
+const TAG_COMPRESSED: u8 = 0x01;
+const TAG_VERSIONED: u8 = 0x02;
+const HEADER_LEN: usize = MARKER.len() + 1;
+
+/// The configured compressor and each table's threshold.
+#[derive(Default)]
+pub(super) struct Compression {
+    compressor: Option<Arc<dyn Compressor>>,
+    thresholds: FastMap<SmolStr, usize>,
+}
+
+impl Compression {
+    pub(super) fn new(compressor: Option<Arc<dyn Compressor>>) -> Self {
+        Self {
+            compressor,
+            thresholds: FastMap::default(),
+        }
+    }
+
+    pub(super) fn compressor(&self) -> Option<&Arc<dyn Compressor>> {
+        self.compressor.as_ref()
+    }
+
+    /// Compress `table`'s values of at least `min_bytes`; `None` stops.
+    pub(super) fn set(
+        &mut self,
+        table: &str,
+        min_bytes: Option<usize>,
+    ) -> Result<(), SpookyDbError> {
+        match min_bytes {
+            Some(_) if self.compressor.is_none() => Err(SpookyDbError::Compression(
+                "no compressor configured".to_owned(),
+            )),
+            Some(min) => {
+                self.thresholds.insert(SmolStr::new(table), min);
+                Ok(())
+            }
+            None => {
+                self.thresholds.remove(table);
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn threshold(&self, table: &str) -> Option<usize> {
+        self.thresholds.get(table).copied()
+    }
+
+    pub(super) fn rename(&mut self, old: &str, new: &str) {
+        if let Some(min) = self.thresholds.remove(old) {
+            self.thresholds.insert(SmolStr::new(new), min);
+        }
+    }
+
+    /// The value to store for `bytes` in `table`.
+    pub(super) fn encode<'a>(&self, table: &str, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        let (Some(compressor), Some(min)) = (&self.compressor, self.threshold(table)) else {
+            return Cow::Borrowed(bytes);
+        };
+        if bytes.len() < min {
+            return Cow::Borrowed(bytes);
+        }
+        let payload = compressor.compress(bytes);
+        if payload.len() + HEADER_LEN >= bytes.len() {
+            return Cow::Borrowed(bytes);
+        }
+        let mut stored = Vec::with_capacity(HEADER_LEN + payload.len());
+        stored.extend_from_slice(&MARKER);
+        stored.push(TAG_COMPRESSED);
+        stored.extend_from_slice(&payload);
+        Cow::Owned(stored)
+    }
+}
+
+/// `stored` tagged with schema `version`; version 0 is left untagged.
+pub(super) fn stamp(version: u32, stored: Cow<'_, [u8]>) -> Cow<'_, [u8]> {
+    if version == 0 {
+        return stored;
+    }
+    let mut out = Vec::with_capacity(HEADER_LEN + 4 + stored.len());
+    out.extend_from_slice(&MARKER);
+    out.push(TAG_VERSIONED);
+    out.extend_from_slice(&version.to_le_bytes());
+    out.extend_from_slice(&stored);
+    Cow::Owned(out)
+}
+
+/// Schema version a stored value was written at; 0 if untagged.
+pub(super) fn version(stored: &[u8]) -> u32 {
+    versioned(stored).map_or(0, |(version, _)| version)
+}
+
+/// The version tag of `stored` and the value it wraps.
+fn versioned(stored: &[u8]) -> Option<(u32, &[u8])> {
+    let rest = stored.strip_prefix(&MARKER)?.strip_prefix(&[TAG_VERSIONED])?;
+    let (version, inner) = rest.split_first_chunk::<4>()?;
+    Some((u32::from_le_bytes(*version), inner))
+}
+
+/// The record bytes of a stored value. Plain values are borrowed.
+pub(super) fn decode<'a>(
+    compressor: Option<&Arc<dyn Compressor>>,
+    stored: &'a [u8],
+) -> Result<Cow<'a, [u8]>, SpookyDbError> {
+    let stored = versioned(stored).map_or(stored, |(_, inner)| inner);
+    let Some(rest) = stored.strip_prefix(&MARKER) else {
+        return Ok(Cow::Borrowed(stored));
+    };
+    match rest.split_first() {
+        Some((&TAG_COMPRESSED, payload)) => {
+            let compressor = compressor.ok_or_else(|| {
+                SpookyDbError::Compression("compressed record but no compressor".to_owned())
+            })?;
+            compressor
+                .decompress(payload)
+                .map(Cow::Owned)
+                .map_err(SpookyDbError::Compression)
+        }
+        _ => Err(SpookyDbError::Compression(
+            "unknown record encoding".to_owned(),
+        )),
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Run-length coding: enough to exercise the framing.
+    pub(in crate::db) struct Rle;
+
+    impl Compressor for Rle {
+        fn compress(&self, bytes: &[u8]) -> Vec<u8> {
+            let mut out = Vec::new();
+            for run in bytes.chunk_by(|a, b| a == b) {
+                for part in run.chunks(255) {
+                    out.extend_from_slice(&[part.len() as u8, part[0]]);
+                }
+            }
+            out
+        }
+
+        fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+            if !payload.len().is_multiple_of(2) {
+                return Err("odd payload".to_owned());
+            }
+            let runs = payload.chunks(2);
+            Ok(runs
+                .flat_map(|r| std::iter::repeat_n(r[1], r[0] as usize))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let rle: Arc<dyn Compressor> = Arc::new(Rle);
+        let mut compression = Compression::new(Some(Arc::clone(&rle)));
+        compression.set("docs", Some(16)).unwrap();
+        let big = vec![7u8; 1000];
+        let stored = compression.encode("docs", &big);
+        assert!(stored.len() < 20);
+        assert_eq!(decode(Some(&rle), &stored).unwrap(), big);
+        assert!(decode(None, &stored).is_err());
+
+        // Below the threshold, incompressible, or another table: stored as is.
+        assert!(matches!(
+            compression.encode("docs", &[1; 8]),
+            Cow::Borrowed(_)
+        ));
+        let noisy: Vec<u8> = (0..64).collect();
+        assert!(matches!(
+            compression.encode("docs", &noisy),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(
+            compression.encode("users", &big),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(decode(None, &big).unwrap(), Cow::Borrowed(_)));
+
+        assert!(Compression::default().set("docs", Some(16)).is_err());
+
+        // A version tag wraps either form.
+        let tagged = stamp(3, stored.clone());
+        assert_eq!(version(&tagged), 3);
+        assert_eq!(decode(Some(&rle), &tagged).unwrap(), big);
+        assert_eq!(decode(None, &stamp(2, Cow::Borrowed(&big))).unwrap(), big);
+        assert_eq!(version(&big), 0);
+        assert!(matches!(stamp(0, Cow::Borrowed(&big)), Cow::Borrowed(_)));
+    }
+}
@@ -1,58 +1,364 @@
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, BinaryHeap};
+use std::io::{BufRead, Write};
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use arrayvec::ArrayString;
-use redb::{Database as RedbDatabase, ReadableDatabase, ReadableTable, TableDefinition};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use redb::{
+    Database as RedbDatabase, ReadTransaction, ReadableDatabase, ReadableTable,
+    ReadableTableMetadata, Table, TableDefinition, WriteTransaction,
+};
 use smol_str::SmolStr;
 
+use super::aggregate::{Aggregator, RecordOperator};
+use super::circuit::Circuit;
+use super::backup::{BackupReader, BackupWriter};
+use super::blobs::{self, BlobRef, BlobStage};
+use super::cache::{CachedRow, INLINE_ROW, RowCache};
+use super::compress::{self, Compression};
+use super::index::{
+    IndexUpdate, JoinIndex, JoinUpdate, UniqueCheck, UniqueIndex, index_key, key_value,
+};
+use super::migrate;
+use super::namespace::Namespace;
+use super::oplog;
+use super::reader::SpookyDbReader;
+use super::record_id::RecordId;
+use super::topk::TopK;
+use super::typed;
 use super::types::{
-    BatchMutationResult, BulkRecord, DbMutation, FastHashSet, FastMap, Operation,
-    SpookyDbConfig, SpookyDbError, ZSet,
+    BackupProgress, BatchMutationResult, BulkRecord, CachePolicy, ChangeEvent, CoalesceConfig,
+    CompactionReport, DbMutation, DbStats, DeltaBatch, Durability, DurabilityCallback, FastHashSet,
+    FastMap, IntegrityReport, Migration, MigrationProgress, Operation, OplogEntry, OplogMode,
+    RecordChange, RecordMeta, SavepointBatchResult, SortDirection, SpookyDbConfig, SpookyDbError,
+    StatsHook, TableDeltas, TableStats, TimedOp, TimingHook, ZSet, ZSetSnapshot,
+};
+use super::view::View;
+use super::zset::{self, add_weight};
+use super::zsets::{self, TableDelta, ZSets};
+use crate::buffer_pool::BufferPool;
+use crate::cbor::ItemReader;
+use crate::coerce::compare_fields;
+use crate::error::RecordError;
+use crate::schema::Schema;
+use crate::serialization::{
+    from_bytes, from_cbor_record_into, from_cbor_slice_into, serialize_into_buf,
+};
+use crate::spooky_record::{
+    IndexedRecord, PerfectIndex, SpookyReadable, SpookyRecord, to_record_into,
 };
-use crate::serialization::from_bytes;
-use crate::spooky_record::{SpookyReadable, SpookyRecord};
 use crate::spooky_value::SpookyValue;
+use crate::types::{
+    FieldRef, TAG_BLOB_REF, TAG_BOOL, TAG_F64, TAG_I64, TAG_NESTED_CBOR, TAG_STR, TAG_U64,
+};
 
 // ─── Table definitions ───────────────────────────────────────────────────────
 //
 // Flat string key: "table_name:record_id"
-// INVARIANT: table names must not contain ':'. IDs may contain ':'; only the
-// first ':' in the key is the separator (split_once(':') is used everywhere).
+// A ':' inside the table name is stored as U+001F (see `make_key`), so the
+// first ':' in the key is always the separator and IDs may contain ':'.
+// Parse keys with `RecordId::from_db_key` (or `split_key`), never with a
+// bare split_once(':').
 
 /// Primary record store. Key: "table:id" → Value: serialized SpookyRecord bytes.
-const RECORDS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("records");
+pub(super) const RECORDS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("records");
 
 /// Version store for sync / conflict detection.
 /// Key: "table:id" → Value: version u64 (read from the "spooky_rv" field or explicit).
-const VERSION_TABLE: TableDefinition<&str, u64> = TableDefinition::new("versions");
+pub(super) const VERSION_TABLE: TableDefinition<&str, u64> = TableDefinition::new("versions");
+
+/// Append-only operation log. Key: sequence number → Value: `oplog::encode` bytes.
+/// Written in the same transaction as the mutation it records.
+const OPLOG_TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("oplog");
+
+/// Net membership deltas per commit, with `SpookyDbConfig::delta_log`.
+/// Key: commit tick → Value: `oplog::encode_deltas` bytes. Commits that
+/// change no membership have no entry.
+const DELTA_TABLE: TableDefinition<u64, &[u8]> = TableDefinition::new("deltas");
+
+/// Soft-delete tombstones. Key: "table:id" → Value: deleted_at (ms since UNIX epoch).
+/// A tombstoned key has no RECORDS_TABLE entry; a later write removes the tombstone.
+const TOMBSTONE_TABLE: TableDefinition<&str, u64> = TableDefinition::new("tombstones");
+
+/// Per-table stats, so opening skips the RECORDS_TABLE scan (see `zsets`).
+/// Key: table name → Value: (records, record bytes). Written in the same transaction.
+pub(super) const META_TABLE: TableDefinition<&str, (u64, u64)> = TableDefinition::new("table_meta");
+
+/// Record expiry for `sweep_expired`. Key: "table:id" → Value: expires_at (caller's clock).
+const TTL_TABLE: TableDefinition<&str, u64> = TableDefinition::new("ttl");
+
+/// Write timestamps for `set_timestamps` tables.
+/// Key: "table:id" → Value: (created_at, updated_at) (ms since UNIX epoch).
+const TIMES_TABLE: TableDefinition<&str, (u64, u64)> = TableDefinition::new("record_times");
+
+/// Out-of-line field data (see `blobs`). Key: xxh3-128 of the bytes → Value: bytes.
+pub(super) const BLOBS_TABLE: TableDefinition<u128, &[u8]> = TableDefinition::new("blobs");
+
+/// References to each blob from RECORDS_TABLE. Key: blob hash → Value: count.
+pub(super) const BLOB_REFS_TABLE: TableDefinition<u128, u64> = TableDefinition::new("blob_refs");
+
+/// ZSets saved by the last `checkpoint`. Key: "table:id" → Value: weight.
+/// Replaced as a whole by each checkpoint.
+const CHECKPOINT_TABLE: TableDefinition<&str, i64> = TableDefinition::new("checkpoint_zsets");
+
+/// Tick counters saved by the last `checkpoint`. Key: counter name → Value: tick.
+const CHECKPOINT_TICKS_TABLE: TableDefinition<&str, u64> = TableDefinition::new("checkpoint_ticks");
+
+/// Tick of the last record-writing commit, under `TICK_KEY`. Written in the
+/// same transaction as the records.
+const COMMIT_TICK_TABLE: TableDefinition<&str, u64> = TableDefinition::new("commit_tick");
+const TICK_KEY: &str = "last";
+
+/// Schema version each table has been migrated to. Key: table → Value: version.
+const SCHEMA_VERSION_TABLE: TableDefinition<&str, u32> = TableDefinition::new("schema_versions");
 
 // ─── SpookyDb ─────────────────────────────────────────────────────────────────
 
 /// Persistent record store backed by redb.
 ///
 /// **Ownership**: `SpookyDb` is meant to be owned exclusively by one component
-/// (e.g. a streaming data processor). No `Arc`, no `Mutex` — callers hold `&mut self`
-/// for write operations.
+/// (e.g. a streaming data processor). No `Mutex` — callers hold `&mut self`
+/// for write operations. To read from worker threads while one thread writes,
+/// use [`SharedSpookyDb`](super::SharedSpookyDb) instead.
 ///
 /// **ZSet**: a per-table in-memory `FastMap<record_id, weight>` that shadows
-/// RECORDS_TABLE. Rebuilt from a sequential RECORDS_TABLE scan on startup.
-/// All view-evaluation ZSet reads are pure memory — zero I/O.
+/// RECORDS_TABLE. Rebuilt by a range scan of the table's keys the first time
+/// it is accessed; after that, all view-evaluation ZSet reads are pure
+/// memory — zero I/O.
 pub struct SpookyDb {
     /// On-disk KV store. Written on every mutation; read only during startup.
-    db: RedbDatabase,
+    /// Shared with `SharedSpookyDb` readers, which open their own read transactions.
+    db: Arc<RedbDatabase>,
+
+    /// Database file, as passed to `new`.
+    path: PathBuf,
 
     /// Hot ZSet per table. Key: table name → Value: (record_id → weight).
-    /// INVARIANT: table names must not contain ':'.
     /// Weight 1 = record present; absent = deleted.
-    zsets: FastMap<SmolStr, ZSet>,
+    /// Each table is loaded on first access; counts come from META_TABLE.
+    zsets: ZSets,
 
-    /// Bounded LRU row cache. Key: (table_name, record_id) → SpookyRecord bytes.
+    /// Bounded LRU row cache. Key: (table_name, record_id) → SpookyRecord
+    /// bytes, held inline in the entry when small (see `CachedRow`).
     ///
     /// Write-through: populated on every Create/Update/bulk_load. Evicts the
-    /// least-recently-written entry when capacity is reached. On cache miss,
-    /// `get_record_bytes` falls back to a redb read. The cache starts cold on
-    /// every open — ZSet is rebuilt from a full scan but record bytes are NOT
-    /// pre-loaded.
-    row_cache: lru::LruCache<(SmolStr, SmolStr), Vec<u8>>,
+    /// least-recently-written entries of a partition when its entry or byte
+    /// limit is reached; tables share one partition unless given their own
+    /// with `set_cache_policy`. On cache miss, `get_record_bytes` falls back
+    /// to a redb read. The cache starts cold on every open — record bytes are
+    /// NOT pre-loaded.
+    row_cache: RowCache<CachedRow>,
+
+    /// The last record `get_row_record_mut` read that was too large for the
+    /// cache's byte budget; it borrows from here instead.
+    uncached_row: Vec<u8>,
+
+    /// The configured compressor and per-table thresholds for values at
+    /// rest. Thresholds are in-memory only — set them again after reopening;
+    /// values already compressed stay readable either way.
+    compression: Compression,
+
+    /// Per-table field size from which field data moves to BLOBS_TABLE.
+    /// In-memory only — set again after reopening.
+    blob_thresholds: FastMap<SmolStr, usize>,
+
+    /// BLOB_REFS_TABLE is non-empty, so overwrites and removes must release
+    /// references. Never reset while open.
+    has_blobs: bool,
+
+    /// Serialization buffer reused by `apply_mutation_cbor`,
+    /// `apply_mutation_value` and `put_struct`. Keeps the capacity of the
+    /// largest record.
+    scratch: Vec<u8>,
+
+    /// Optional per-table schemas, checked before any write reaches redb,
+    /// each with the slot lookup over its fields. In-memory only —
+    /// re-register after reopening.
+    schemas: FastMap<SmolStr, (Schema, Arc<PerfectIndex>)>,
+
+    /// Unique-field indexes per table, checked and maintained on every write.
+    /// In-memory only — rebuilt by a table scan when declared with `add_unique`.
+    unique: FastMap<SmolStr, Vec<UniqueIndex>>,
+
+    /// Reverse indexes of reference fields per table, maintained on every
+    /// write. In-memory only — rebuilt by a table scan in `register_join`.
+    joins: FastMap<SmolStr, Vec<JoinIndex>>,
+
+    /// Change-feed senders per table. Disconnected receivers are dropped on
+    /// the next event for their table.
+    subscribers: FastMap<SmolStr, Vec<Subscriber>>,
+
+    /// `watch` senders per table, then per record id. Pruned like
+    /// `subscribers`.
+    watchers: FastMap<SmolStr, FastMap<SmolStr, Vec<Sender<RecordChange>>>>,
+
+    /// Mirror of SCHEMA_VERSION_TABLE. Writes to a table in it tag their
+    /// stored values with its version (see `compress`). Rebuilt on open.
+    schema_versions: FastMap<SmolStr, u32>,
+
+    /// Migrations registered with `migrate_lazy`, per table in version
+    /// order. In-memory only — re-register after reopening.
+    lazy: FastMap<SmolStr, Vec<Migration>>,
+
+    /// Records upgraded on read and not yet written back; `flush` writes
+    /// them. Locked because `&self` reads add to it.
+    upgrades: Mutex<FastHashSet<(SmolStr, SmolStr)>>,
+
+    /// Views registered with `register_view`, by name. In-memory only.
+    views: FastMap<SmolStr, View>,
+
+    /// A persisted view's records are being written; their commit takes no
+    /// tick, skips the oplog and delta log, and does not maintain views or
+    /// feed `delta_stream`.
+    storing_views: bool,
+
+    /// `delta_stream` senders. Disconnected ones are dropped on the next
+    /// commit.
+    delta_streams: Vec<SyncSender<DeltaBatch>>,
+
+    /// Mirror of COMMIT_TICK_TABLE: the tick of the last committed
+    /// `apply_mutation`, `apply_batch(es)` or `bulk_load`, 0 before the first.
+    commit_tick: u64,
+
+    /// What each mutation appends to OPLOG_TABLE.
+    oplog_mode: OplogMode,
+
+    /// `SpookyDbConfig::delta_log`.
+    delta_log: bool,
+
+    /// `SpookyDbConfig::auto_version`.
+    auto_version: bool,
+
+    /// Tables whose Deletes write a tombstone. In-memory only — re-enable after reopening.
+    soft_delete: FastHashSet<SmolStr>,
+
+    /// Tables whose writes maintain TIMES_TABLE. In-memory only — re-enable after reopening.
+    timestamped: FastHashSet<SmolStr>,
+
+    /// TIMES_TABLE may be non-empty, so Deletes must clear entries.
+    has_times: bool,
+
+    /// Mirror of TOMBSTONE_TABLE: table → (id → deleted_at). Rebuilt on open.
+    tombstones: FastMap<SmolStr, FastMap<SmolStr, u64>>,
+
+    /// Mirror of TTL_TABLE ordered by expiry, so a sweep is a range scan.
+    /// `expiry` and `expires` always hold the same (table, id) set. Rebuilt on open.
+    expiry: BTreeSet<(u64, SmolStr, SmolStr)>,
+    expires: FastMap<(SmolStr, SmolStr), u64>,
+
+    /// Tick counters set with `set_tick`, starting from the last checkpoint's.
+    /// Persisted only by `checkpoint`.
+    ticks: FastMap<SmolStr, u64>,
+
+    /// Sequence number of the next OPLOG_TABLE entry. Resumed from the last
+    /// key on open; advanced only after a successful commit.
+    next_seq: u64,
+
+    /// Write-coalescing window for `apply_mutation`; `None` commits each call.
+    coalesce: Option<CoalesceConfig>,
+
+    /// Validated mutations waiting for the window's shared commit, in call
+    /// order. Not visible to reads until flushed.
+    pending: Vec<PendingWrite>,
+
+    /// When the oldest `pending` entry was buffered.
+    pending_since: Option<Instant>,
+
+    /// Commit durability; switchable at runtime with `set_durability`.
+    durability: Durability,
+
+    /// Start of the last fsync'd commit (for `Durability::Eventual`).
+    last_sync: Instant,
+
+    /// A non-durable commit has happened since the last `sync`.
+    unsynced: bool,
+
+    /// Cumulative counters behind `stats`.
+    counters: Counters,
+
+    /// Exporter registered with `set_stats_hook`.
+    stats_hook: Option<StatsHookState>,
+
+    /// Callback registered with `set_timing_hook`. Unset, no clock is read.
+    timing_hook: Option<TimingHook>,
+
+    /// Pool set with `set_buffer_pool`. Batch and bulk writes return the
+    /// record buffers of rows the cache stores inline to it.
+    buffer_pool: Option<Arc<BufferPool>>,
+}
+
+/// Counters behind `stats`. The cache ones are atomic because `&self`
+/// reads count too.
+#[derive(Default)]
+struct Counters {
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    cache_evictions: u64,
+    commits: u64,
+    commit_time: Duration,
+    max_commit_time: Duration,
+    bytes_written: u64,
+}
+
+impl Counters {
+    fn cache_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+struct StatsHookState {
+    every: Duration,
+    last: Instant,
+    hook: StatsHook,
+}
+
+/// One buffered `apply_mutation` and its durability callback.
+struct PendingWrite {
+    mutation: DbMutation,
+    on_durable: Option<DurabilityCallback>,
+}
+
+/// One `subscribe` registration.
+struct Subscriber {
+    tx: Sender<ChangeEvent>,
+    with_data: bool,
+}
+
+/// The records of a write transaction in progress, as seen by the
+/// `validate` callback of `SpookyDb::apply_batch_with_savepoint`.
+pub struct StagedView<'a> {
+    db: &'a SpookyDb,
+    records: &'a Table<'a, &'static str, &'static [u8]>,
+}
+
+impl StagedView<'_> {
+    /// A record's bytes as staged so far: committed, then written or
+    /// deleted by the segments staged before.
+    pub fn get(&self, table: &str, id: &str) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        validate_table_name(table)?;
+        match self.records.get(make_key(table, id)?.as_str())? {
+            Some(guard) => Ok(Some(self.db.decode(guard.value())?.into_owned())),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether the record is present as staged so far.
+    pub fn contains(&self, table: &str, id: &str) -> Result<bool, SpookyDbError> {
+        validate_table_name(table)?;
+        Ok(self.records.get(make_key(table, id)?.as_str())?.is_some())
+    }
 }
 
 // ─── Construction ─────────────────────────────────────────────────────────────
@@ -60,106 +366,471 @@ pub struct SpookyDb {
 impl SpookyDb {
     /// Open or create the database at `path` with default cache capacity (10 000 records).
     ///
-    /// Initialises redb tables on first open. Rebuilds all in-memory ZSets
-    /// from a sequential RECORDS_TABLE scan — O(N records), ~20–100ms per
-    /// million records on an SSD. The LRU row cache starts cold.
+    /// Initialises redb tables on first open. Reads per-table record counts
+    /// from META_TABLE — O(tables); each table's ZSet is built on first
+    /// access (see `load_tables`). A file without META_TABLE gets one full
+    /// RECORDS_TABLE scan, ~20–100ms per million records on an SSD. The LRU
+    /// row cache starts cold.
     pub fn new(path: impl AsRef<Path>) -> Result<Self, SpookyDbError> {
         Self::new_with_config(path, SpookyDbConfig::default())
     }
 
     /// Open or create the database at `path` with explicit configuration.
     ///
-    /// `config.cache_capacity` bounds peak memory for record bytes. Records beyond
-    /// this limit are evicted from memory and re-read from redb on demand.
+    /// `config.cache_capacity` and `config.cache_max_bytes` bound peak memory
+    /// for record bytes. Records beyond these limits are evicted from memory
+    /// and re-read from redb on demand.
     /// Setting a capacity larger than the total number of records is equivalent
     /// to the old full-memory design (but without the startup pre-load cost).
     pub fn new_with_config(
         path: impl AsRef<Path>,
         config: SpookyDbConfig,
     ) -> Result<Self, SpookyDbError> {
-        let db = RedbDatabase::create(path)?;
+        let mut builder = redb::Builder::new();
+        if let Some(bytes) = config.redb_cache_size {
+            builder.set_cache_size(bytes);
+        }
+        let path = path.as_ref().to_path_buf();
+        let db = Arc::new(builder.create(&path)?);
 
         // Ensure tables exist (idempotent).
         {
             let write_txn = db.begin_write()?;
             let _ = write_txn.open_table(RECORDS_TABLE)?;
             let _ = write_txn.open_table(VERSION_TABLE)?;
+            let _ = write_txn.open_table(OPLOG_TABLE)?;
+            let _ = write_txn.open_table(DELTA_TABLE)?;
+            let _ = write_txn.open_table(TOMBSTONE_TABLE)?;
+            let _ = write_txn.open_table(TTL_TABLE)?;
+            let _ = write_txn.open_table(TIMES_TABLE)?;
+            let _ = write_txn.open_table(META_TABLE)?;
+            let _ = write_txn.open_table(BLOBS_TABLE)?;
+            let _ = write_txn.open_table(BLOB_REFS_TABLE)?;
+            let _ = write_txn.open_table(CHECKPOINT_TABLE)?;
+            let _ = write_txn.open_table(CHECKPOINT_TICKS_TABLE)?;
+            let _ = write_txn.open_table(SCHEMA_VERSION_TABLE)?;
+            let _ = write_txn.open_table(COMMIT_TICK_TABLE)?;
             write_txn.commit()?;
         }
+        let next_seq = {
+            let read_txn = db.begin_read()?;
+            let oplog = read_txn.open_table(OPLOG_TABLE)?;
+            oplog.last()?.map_or(1, |(seq, _)| seq.value() + 1)
+        };
 
         let mut spooky = SpookyDb {
             db,
-            zsets: FastMap::default(),
-            row_cache: lru::LruCache::new(config.cache_capacity),
+            path,
+            zsets: ZSets::default(),
+            row_cache: RowCache::new(config.cache_capacity, config.cache_max_bytes),
+            uncached_row: Vec::new(),
+            compression: Compression::new(config.compressor),
+            blob_thresholds: FastMap::default(),
+            has_blobs: false,
+            scratch: Vec::new(),
+            schemas: FastMap::default(),
+            unique: FastMap::default(),
+            joins: FastMap::default(),
+            subscribers: FastMap::default(),
+            watchers: FastMap::default(),
+            schema_versions: FastMap::default(),
+            lazy: FastMap::default(),
+            upgrades: Mutex::default(),
+            views: FastMap::default(),
+            storing_views: false,
+            delta_streams: Vec::new(),
+            commit_tick: 0,
+            soft_delete: FastHashSet::default(),
+            timestamped: FastHashSet::default(),
+            has_times: false,
+            tombstones: FastMap::default(),
+            expiry: BTreeSet::new(),
+            expires: FastMap::default(),
+            ticks: FastMap::default(),
+            oplog_mode: config.oplog,
+            delta_log: config.delta_log,
+            auto_version: config.auto_version,
+            next_seq,
+            coalesce: config.coalesce,
+            pending: Vec::new(),
+            pending_since: None,
+            durability: config.durability,
+            last_sync: Instant::now(),
+            unsynced: false,
+            counters: Counters::default(),
+            stats_hook: None,
+            timing_hook: None,
+            buffer_pool: None,
         };
-        spooky.rebuild_from_records()?;
+        spooky.rebuild_memory()?;
+        if config.lazy_migrations {
+            spooky.migrate_lazy(&config.migrations)?;
+        } else if !config.migrations.is_empty() {
+            spooky.migrate(&config.migrations, |_| {})?;
+        }
         Ok(spooky)
     }
 
-    /// Sequential scan of RECORDS_TABLE on startup.
-    ///
-    /// Rebuilds `zsets` (weight=1 per key) in a single pass — O(N records),
-    /// approximately 20–80ms per million records on SSD. The LRU row cache
+    /// Rebuild in-memory state on startup: table counts, tombstones,
+    /// expiries and checkpointed tick counters. ZSets stay unloaded until first access. The LRU row cache
     /// starts cold; it warms as records are written or read via `get_record_bytes`.
-    ///
-    /// Startup memory: only ZSet keys (one SmolStr per record) — no record bytes loaded.
-    fn rebuild_from_records(&mut self) -> Result<(), SpookyDbError> {
+    fn rebuild_memory(&mut self) -> Result<(), SpookyDbError> {
+        self.zsets = ZSets::open(&self.db)?;
         let read_txn = self.db.begin_read()?;
-        let table = read_txn.open_table(RECORDS_TABLE)?;
-        for entry in table.iter()? {
-            let (key_guard, _val_guard) = entry?;
-            let key_str: &str = key_guard.value();
-            if let Some((table_name, id)) = key_str.split_once(':') {
-                let t = SmolStr::new(table_name);
-                let i = SmolStr::new(id);
-                self.zsets.entry(t).or_default().insert(i, 1);
+        let tombstones = read_txn.open_table(TOMBSTONE_TABLE)?;
+        for entry in tombstones.iter()? {
+            let (key_guard, at_guard) = entry?;
+            let rid = RecordId::from_db_key(key_guard.value());
+            if let Some((table, id)) = rid.map(RecordId::into_parts) {
+                self.tombstones.entry(table).or_default().insert(id, at_guard.value());
             }
         }
+        self.has_blobs = !read_txn.open_table(BLOB_REFS_TABLE)?.is_empty()?;
+        self.has_times = !read_txn.open_table(TIMES_TABLE)?.is_empty()?;
+        let ttl = read_txn.open_table(TTL_TABLE)?;
+        for entry in ttl.iter()? {
+            let (key_guard, at_guard) = entry?;
+            let rid = RecordId::from_db_key(key_guard.value());
+            if let Some((table, id)) = rid.map(RecordId::into_parts) {
+                self.set_expiry_memory(table, id, Some(at_guard.value()));
+            }
+        }
+        let ticks = read_txn.open_table(CHECKPOINT_TICKS_TABLE)?;
+        for entry in ticks.iter()? {
+            let (name, tick) = entry?;
+            self.ticks.insert(SmolStr::new(name.value()), tick.value());
+        }
+        let versions = read_txn.open_table(SCHEMA_VERSION_TABLE)?;
+        for entry in versions.iter()? {
+            let (table, version) = entry?;
+            self.schema_versions.insert(SmolStr::new(table.value()), version.value());
+        }
+        let tick = read_txn.open_table(COMMIT_TICK_TABLE)?.get(TICK_KEY)?;
+        self.commit_tick = tick.map_or(0, |guard| guard.value());
+        Ok(())
+    }
+
+    /// The underlying redb handle, for readers that bypass `&self`.
+    pub(super) fn redb(&self) -> &Arc<RedbDatabase> {
+        &self.db
+    }
+}
+
+// ─── Durability ──────────────────────────────────────────────────────────────
+
+/// Under `Durability::Eventual`, the longest a commit goes without an fsync.
+const EVENTUAL_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+impl SpookyDb {
+    /// Change commit durability from the next write on — e.g. `None` for a
+    /// bulk-load phase, then back to `Immediate`. Leaving a non-durable level
+    /// for `Immediate` syncs first, so everything written so far is durable
+    /// when this returns.
+    pub fn set_durability(&mut self, durability: Durability) -> Result<(), SpookyDbError> {
+        if durability == Durability::Immediate {
+            self.sync()?;
+        }
+        self.durability = durability;
+        Ok(())
+    }
+
+    /// Current commit durability.
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Make every commit so far durable with one fsync'd empty commit.
+    /// A no-op when nothing non-durable has been committed.
+    pub fn sync(&mut self) -> Result<(), SpookyDbError> {
+        self.flush()?;
+        if !self.unsynced {
+            return Ok(());
+        }
+        let txn = self.db.begin_write()?;
+        self.commit(txn)?;
+        self.unsynced = false;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Open a write transaction at the current durability level.
+    fn begin_write(&mut self) -> Result<WriteTransaction, SpookyDbError> {
+        let mut txn = self.db.begin_write()?;
+        let durable = match self.durability {
+            Durability::Immediate => true,
+            Durability::Eventual => self.last_sync.elapsed() >= EVENTUAL_SYNC_INTERVAL,
+            Durability::None => false,
+        };
+        if durable {
+            self.last_sync = Instant::now();
+        } else {
+            txn.set_durability(redb::Durability::None)?;
+            // Cleared only by `sync`: a durable commit that later fails must
+            // not hide earlier non-durable ones.
+            self.unsynced = true;
+        }
+        Ok(txn)
+    }
+
+    /// Commit `txn`, timing it for `stats`.
+    fn commit(&mut self, txn: WriteTransaction) -> Result<(), SpookyDbError> {
+        let start = Instant::now();
+        txn.commit()?;
+        let elapsed = start.elapsed();
+        let counters = &mut self.counters;
+        counters.commits += 1;
+        counters.commit_time += elapsed;
+        counters.max_commit_time = counters.max_commit_time.max(elapsed);
+        if let Some(hook) = &self.timing_hook {
+            hook(TimedOp::Commit, elapsed);
+        }
         Ok(())
     }
 }
 
 // ─── Helpers ──────────────────────────────────────────────────────────────────
 
+/// Stands in for ':' inside the table part of a key. One byte, like ':',
+/// so escaping never changes a key's length; tables without ':' are stored
+/// exactly as before.
+const TABLE_COLON: &str = "\u{1f}";
+
+/// Longest flat key `make_key` can build: `table.len() + 1 + id.len()`
+/// must not exceed it.
+pub const MAX_KEY_LEN: usize = 512;
+
 /// Build a flat redb key `"table:id"` without a heap allocation.
 ///
-/// Uses a stack-allocated `ArrayString<512>`. The combined key must fit
-/// in 512 bytes — table names and ids are expected to be well under this
-/// limit in practice.
-///
-/// # Panics
-/// Panics (debug) / truncates (release) if `table.len() + 1 + id.len() > 512`.
+/// Uses a stack-allocated `ArrayString<MAX_KEY_LEN>`. A ':' in `table` is
+/// written as `TABLE_COLON`, so `"ns:users"` + `"1"` becomes
+/// `"ns\u{1f}users:1"`. Fails with `SpookyDbError::InvalidKey` if the key
+/// is longer than `MAX_KEY_LEN` (see `validate_key`).
 #[inline]
-fn make_key(table: &str, id: &str) -> ArrayString<512> {
-    let mut key = ArrayString::<512>::new();
-    key.push_str(table);
+pub(super) fn make_key(table: &str, id: &str) -> Result<ArrayString<MAX_KEY_LEN>, SpookyDbError> {
+    validate_key(table, id)?;
+    let mut key = ArrayString::<MAX_KEY_LEN>::new();
+    key.push_str(&table_key(table));
     key.push(':');
     key.push_str(id);
-    key
+    Ok(key)
+}
+
+/// Key bounds `(lo, hi)` covering every record of `table`. `';'` is the
+/// byte after `':'`, so `"table;"` bounds every `"table:…"` key, and the
+/// ids start at `lo.len()`.
+pub(super) fn key_range(table: &str) -> (String, String) {
+    let table = table_key(table);
+    (format!("{table}:"), format!("{table};"))
+}
+
+/// `table` with each ':' escaped, as it appears in RECORDS_TABLE and
+/// META_TABLE keys. Borrowed when there is nothing to escape.
+pub(super) fn table_key(table: &str) -> Cow<'_, str> {
+    match table.contains(':') {
+        true => Cow::Owned(table.replace(':', TABLE_COLON)),
+        false => Cow::Borrowed(table),
+    }
+}
+
+/// The table name a `table_key` stands for.
+pub(super) fn table_from_key(key: &str) -> Cow<'_, str> {
+    match key.contains(TABLE_COLON) {
+        true => Cow::Owned(key.replace(TABLE_COLON, ":")),
+        false => Cow::Borrowed(key),
+    }
 }
 
-/// Reject table names containing ':' before they can corrupt the flat key namespace.
+/// Split a flat key into its table name and id, undoing `make_key`.
+pub(super) fn split_key(key: &str) -> Option<(Cow<'_, str>, &str)> {
+    let (table, id) = key.split_once(':')?;
+    Some((table_from_key(table), id))
+}
+
+/// Reject table names the flat key namespace cannot hold.
 ///
-/// The "table:id" key format uses ':' as the only separator.
-/// `split_once(':')` in `rebuild_from_records` would mis-parse keys stored
-/// under a table name that itself contains ':', silently moving records to the
-/// wrong table on every restart.
+/// ':' is allowed and escaped by `make_key`; `TABLE_COLON` itself is not,
+/// since a name containing it would share keys with its ':' spelling.
 #[inline]
-fn validate_table_name(table: &str) -> Result<(), SpookyDbError> {
+pub(super) fn validate_table_name(table: &str) -> Result<(), SpookyDbError> {
     if table.is_empty() {
         return Err(SpookyDbError::InvalidKey(
             "table name must not be empty".into(),
         ));
     }
-    if table.contains(':') {
+    if table.contains(TABLE_COLON) {
         return Err(SpookyDbError::InvalidKey(format!(
-            "table name must not contain ':': received {:?}",
+            "table name must not contain U+001F: received {:?}",
             table
         )));
     }
     Ok(())
 }
 
+/// Reject a table and id whose flat key would not fit in `MAX_KEY_LEN`.
+#[inline]
+pub(crate) fn validate_key(table: &str, id: &str) -> Result<(), SpookyDbError> {
+    let len = table.len() + 1 + id.len();
+    if len > MAX_KEY_LEN {
+        return Err(SpookyDbError::InvalidKey(format!(
+            "key for {table:?} is {len} bytes; at most {MAX_KEY_LEN} fit"
+        )));
+    }
+    Ok(())
+}
+
+impl SpookyDb {
+    /// Record bytes of a RECORDS_TABLE value (see `compress`).
+    fn decode<'a>(&self, stored: &'a [u8]) -> Result<Cow<'a, [u8]>, SpookyDbError> {
+        compress::decode(self.compression.compressor(), stored)
+    }
+
+    /// `decode` for reads of `table:id`: a value older than a migration
+    /// registered with `migrate_lazy` is upgraded and queued for `flush`
+    /// to write back.
+    fn read_form<'a>(
+        &self,
+        table: &str,
+        id: &str,
+        stored: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, SpookyDbError> {
+        let (bytes, stale) = self.upgrade(table, id, stored)?;
+        if stale {
+            let mut upgrades = self.upgrades.lock().unwrap_or_else(PoisonError::into_inner);
+            upgrades.insert((SmolStr::new(table), SmolStr::new(id)));
+        }
+        Ok(bytes)
+    }
+
+    /// The record bytes of `stored` after the lazy migrations of `table`
+    /// above its version tag, and whether there were any.
+    fn upgrade<'a>(
+        &self,
+        table: &str,
+        id: &str,
+        stored: &'a [u8],
+    ) -> Result<(Cow<'a, [u8]>, bool), SpookyDbError> {
+        let bytes = self.decode(stored)?;
+        let Some(migrations) = self.lazy.get(table) else {
+            return Ok((bytes, false));
+        };
+        let at = compress::version(stored);
+        let mut upgraded: Option<Vec<u8>> = None;
+        let mut stale = false;
+        for migration in migrations.iter().filter(|m| m.version > at) {
+            stale = true;
+            let current = upgraded.as_deref().unwrap_or(&bytes);
+            let migrated = migrate::migrate_record(current, &migration.steps).map_err(|reason| {
+                SpookyDbError::MigrationFailed {
+                    table: migration.table.clone(),
+                    id: SmolStr::new(id),
+                    version: migration.version,
+                    reason,
+                }
+            })?;
+            upgraded = migrated.or(upgraded);
+        }
+        Ok((upgraded.map_or(bytes, Cow::Owned), stale))
+    }
+
+    /// Blob bookkeeping for a write transaction, if any of its writes to
+    /// `tables` can add or drop a blob reference.
+    fn blob_stage<'txn, 't>(
+        &self,
+        txn: &'txn WriteTransaction,
+        mut tables: impl Iterator<Item = &'t str>,
+    ) -> Result<Option<BlobStage<'txn>>, SpookyDbError> {
+        if self.has_blobs || tables.any(|t| self.blob_thresholds.contains_key(t)) {
+            return BlobStage::open(txn).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// The RECORDS_TABLE value for `bytes` written to `table`: oversized
+    /// fields moved out through `stage`, then compressed, then tagged with
+    /// the table's schema version.
+    fn stored_form<'a>(
+        &self,
+        table: &str,
+        bytes: &'a [u8],
+        stage: Option<&mut BlobStage<'_>>,
+    ) -> Result<Cow<'a, [u8]>, SpookyDbError> {
+        let record = match (self.blob_thresholds.get(table), stage) {
+            (Some(&min), Some(stage)) => {
+                let (record, moved) = blobs::extract(bytes, min);
+                for (hash, data) in moved {
+                    stage.add(hash, data)?;
+                }
+                record
+            }
+            _ => Cow::Borrowed(bytes),
+        };
+        let stored = match record {
+            Cow::Borrowed(bytes) => self.compression.encode(table, bytes),
+            Cow::Owned(bytes) => Cow::Owned(self.compression.encode(table, &bytes).into_owned()),
+        };
+        let version = self.schema_versions.get(table).copied().unwrap_or(0);
+        Ok(compress::stamp(version, stored))
+    }
+
+    /// Drop the blob references of a RECORDS_TABLE value that was just
+    /// overwritten or removed.
+    fn release_blobs(
+        &self,
+        stage: Option<&mut BlobStage<'_>>,
+        old: &[u8],
+    ) -> Result<(), SpookyDbError> {
+        match stage {
+            Some(stage) => stage.release(&self.decode(old)?),
+            None => Ok(()),
+        }
+    }
+
+    /// What `get_record_bytes` returns after writing `bytes` to `table`:
+    /// `bytes` with oversized fields replaced by blob references. Used to
+    /// fill row caches on write.
+    pub(super) fn written_form(&self, table: &str, bytes: Vec<u8>) -> Vec<u8> {
+        let Some(&min) = self.blob_thresholds.get(table) else {
+            return bytes;
+        };
+        match blobs::extract(&bytes, min).0 {
+            Cow::Owned(record) => record,
+            Cow::Borrowed(_) => bytes,
+        }
+    }
+
+    /// `written_form` of borrowed bytes as a row-cache entry; a small row
+    /// is copied inline without allocating.
+    fn cached_form(&self, table: &str, bytes: &[u8]) -> CachedRow {
+        match self.blob_thresholds.get(table) {
+            Some(&min) => blobs::extract(bytes, min).0.into(),
+            None => bytes.into(),
+        }
+    }
+
+    /// Cache the row written from the owned `bytes`. Without a pool, or for
+    /// a row too long to inline, the cache takes the buffer; otherwise the
+    /// row is copied inline and the buffer goes back to the pool.
+    fn cache_written(&mut self, key: (SmolStr, SmolStr), bytes: Vec<u8>) {
+        let row = match &self.buffer_pool {
+            Some(pool) if bytes.len() <= INLINE_ROW => {
+                let row = self.cached_form(&key.0, &bytes);
+                pool.put(bytes);
+                row
+            }
+            _ => self.written_form(&key.0, bytes).into(),
+        };
+        self.cache_put(key, row);
+    }
+}
+
+/// Wall-clock milliseconds since the UNIX epoch (0 if the clock is before it).
+#[inline]
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
 // ─── Write Operations ─────────────────────────────────────────────────────────
 
 impl SpookyDb {
@@ -175,6 +846,12 @@ impl SpookyDb {
     /// entry (if any) is left unchanged. Callers must provide `version: Some(v)` on
     /// every mutation where version tracking matters, or accept that `get_version` may
     /// return a stale value after an update with `version: None`.
+    ///
+    /// # Coalescing
+    ///
+    /// With `SpookyDbConfig::coalesce` set, the mutation is validated and
+    /// buffered instead, and committed with the rest of its window — see
+    /// [`flush`](Self::flush).
     pub fn apply_mutation(
         &mut self,
         table: &str,
@@ -183,48 +860,251 @@ impl SpookyDb {
         data: Option<&[u8]>,
         version: Option<u64>,
     ) -> Result<(SmolStr, i64), SpookyDbError> {
+        if self.coalesce.is_some() {
+            return self.buffer_mutation(table, op, id, data, version, None);
+        }
+        let (id, weight, _) = self.write_one(table, op, id, data, version, None)?;
+        Ok((id, weight))
+    }
+
+    /// `apply_mutation` that also returns the version it wrote — `version`
+    /// itself, or the incremented one under `SpookyDbConfig::auto_version`.
+    /// `None` for a Delete or an unversioned write. Never coalesced: pending
+    /// writes are flushed and this one commits before returning.
+    pub fn apply_mutation_versioned(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64, Option<u64>), SpookyDbError> {
+        self.write_one(table, op, id, data, version, None)
+    }
+
+    /// `apply_mutation` that only commits if the record's current
+    /// VERSION_TABLE entry equals `expected_version`.
+    ///
+    /// The check and the write share one write transaction, so no other write
+    /// can slip in between. `expected_version: None` means "no version entry"
+    /// — the usual precondition for a Create. On mismatch nothing is written
+    /// and `SpookyDbError::VersionConflict { expected, actual }` is returned.
+    pub fn apply_mutation_cas(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        expected_version: Option<u64>,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let expected = Some(expected_version);
+        let (id, weight, _) = self.write_one(table, op, id, data, version, expected)?;
+        Ok((id, weight))
+    }
+
+    /// `apply_mutation` with the record as CBOR map bytes, serialized by
+    /// `from_cbor_slice` into a buffer reused across calls. A malformed map
+    /// fails with `SpookyDbError::Serialization` before anything is written.
+    pub fn apply_mutation_cbor(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        cbor: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let encode = cbor.map(|cbor| move |buf: &mut Vec<u8>| from_cbor_slice_into(cbor, buf));
+        self.apply_encoded(table, op, id, encode, version)
+    }
+
+    /// `apply_mutation` with the record as a `SpookyValue::Object`,
+    /// serialized into a buffer reused across calls. Anything but an object
+    /// fails with `SpookyDbError::Serialization`.
+    pub fn apply_mutation_value(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        value: Option<&SpookyValue>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let encode = value.map(|value| move |buf: &mut Vec<u8>| serialize_into_buf(value, buf));
+        self.apply_encoded(table, op, id, encode, version)
+    }
+
+    /// Write a serde struct as record `id`: a Create if it is absent,
+    /// otherwise an Update. `value` is serialized straight to record bytes
+    /// (see `spooky_record::to_record`) in a buffer reused across calls; a
+    /// value that is not a struct or string-keyed map fails with
+    /// `SpookyDbError::Serialization` before anything is written.
+    pub fn put_struct<T: Serialize + ?Sized>(
+        &mut self,
+        table: &str,
+        id: &str,
+        value: &T,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let op = match self.get_zset_weight(table, id) > 0 {
+            true => Operation::Update,
+            false => Operation::Create,
+        };
+        let encode = move |buf: &mut Vec<u8>| to_record_into(value, buf);
+        self.apply_encoded(table, op, id, Some(encode), version)
+    }
+
+    /// `apply_mutation` with the bytes `encode` writes into `self.scratch`.
+    fn apply_encoded<T>(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        encode: Option<impl FnOnce(&mut Vec<u8>) -> Result<T, RecordError>>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let Some(encode) = encode else {
+            return self.apply_mutation(table, op, id, None, version);
+        };
+        let mut buf = std::mem::take(&mut self.scratch);
+        let start = self.timer();
+        let encoded = encode(&mut buf);
+        self.report_timing(TimedOp::Encode, start);
+        let result = match encoded {
+            Ok(_) => self.apply_mutation(table, op, id, Some(&buf), version),
+            Err(e) => Err(e.into()),
+        };
+        self.scratch = buf;
+        result
+    }
+
+    /// Shared body of `apply_mutation` / `apply_mutation_cas`. `expected` is
+    /// `Some(v)` to require VERSION_TABLE to hold `v` before writing.
+    /// Returns the version written as well.
+    fn write_one(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+        expected: Option<Option<u64>>,
+    ) -> Result<(SmolStr, i64, Option<u64>), SpookyDbError> {
+        let start = self.timer();
+        let result = self.write_one_untimed(table, op, id, data, version, expected);
+        self.report_timing(TimedOp::Write, start);
+        result
+    }
+
+    fn write_one_untimed(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        mut version: Option<u64>,
+        expected: Option<Option<u64>>,
+    ) -> Result<(SmolStr, i64, Option<u64>), SpookyDbError> {
+        self.flush()?;
         validate_table_name(table)?;
+        validate_key(table, id)?;
+        if !matches!(op, Operation::Delete) {
+            self.check_schema(table, id, data)?;
+        }
+        let mut unique = UniqueCheck::new(&self.unique);
+        unique.write(table, id, matches!(op, Operation::Delete), data)?;
+        let index_updates = unique.finish();
+        let join_updates = self.stage_joins([(table, id, matches!(op, Operation::Delete), data)])?;
 
-        let key = make_key(table, id);
+        let key = make_key(table, id)?;
         let weight = op.weight();
+        self.zsets.load(&self.db, table)?;
 
         // 1. Persist to redb FIRST — if commit fails, in-memory state is untouched.
-        let write_txn = self.db.begin_write()?;
+        let write_txn = self.begin_write()?;
+        let mut delta = TableDelta::default();
+        let mut stage = self.blob_stage(&write_txn, std::iter::once(table))?;
         {
             let mut records = write_txn.open_table(RECORDS_TABLE)?;
             let mut versions = write_txn.open_table(VERSION_TABLE)?;
+            let current = versions.get(key.as_str())?.map(|guard| guard.value());
+            if let Some(expected) = expected
+                && current != expected
+            {
+                // Dropping the uncommitted transaction aborts it.
+                return Err(SpookyDbError::VersionConflict {
+                    expected,
+                    actual: current,
+                });
+            }
             if matches!(op, Operation::Delete) {
-                records.remove(key.as_str())?;
+                let old = records.remove(key.as_str())?;
+                if let Some(old) = &old {
+                    self.release_blobs(stage.as_mut(), old.value())?;
+                }
+                delta.remove(old.map(|old| old.value().len()));
                 versions.remove(key.as_str())?;
             } else {
                 if let Some(bytes) = data {
-                    records.insert(key.as_str(), bytes)?;
+                    let stored = self.stored_form(table, bytes, stage.as_mut())?;
+                    let old = records.insert(key.as_str(), &*stored)?;
+                    if let Some(old) = &old {
+                        self.release_blobs(stage.as_mut(), old.value())?;
+                    }
+                    delta.insert(old.map(|old| old.value().len()), stored.len());
+                }
+                if self.auto_version && version.is_none() {
+                    version = Some(current.unwrap_or(0) + 1);
                 }
                 if let Some(ver) = version {
                     versions.insert(key.as_str(), ver)?;
                 }
             }
         }
-        write_txn.commit()?;
+        let added_blobs = stage.is_some_and(|stage| stage.added);
+        let deltas = FastMap::from_iter([(SmolStr::new(table), delta)]);
+        let stats = self.zsets.stage_stats(&write_txn, deltas)?;
+        let delete = matches!(op, Operation::Delete);
+        let tombstones = self.stage_tombstones(&write_txn, [(table, id, delete)])?;
+        let expiry = self.stage_expiry(&write_txn, [(table, id, delete, None)])?;
+        self.stage_times(&write_txn, [(table, id, delete)])?;
+        let next_seq = self.log_ops(&write_txn, [(table, id, op, version, data)])?;
+        let tick = self.stage_tick(&write_txn)?;
+        self.log_deltas(&write_txn, tick, [(table, id, delete)])?;
+        self.commit(write_txn)?;
 
         // 2. Update in-memory state AFTER successful commit.
-        let zset = self.zsets.entry(SmolStr::new(table)).or_default();
+        self.next_seq = next_seq;
+        self.commit_tick = tick;
+        self.has_blobs |= added_blobs;
+        self.zsets.apply_stats(stats);
+        self.counters.bytes_written += data.map_or(0, |d| d.len() as u64);
+        self.apply_tombstones(tombstones);
+        self.apply_expiry(expiry);
+        self.apply_index_updates(index_updates);
+        self.apply_joins(join_updates);
+        let zset = self.zsets.loaded_mut(table);
 
         if matches!(op, Operation::Delete) {
-            zset.remove(id);
+            let was_present = zset.remove(id).is_some();
             self.row_cache.pop(&(SmolStr::new(table), SmolStr::new(id)));
+            if was_present {
+                self.notify(table, id, op, version, None);
+                self.committed_one(table, id, -1);
+            }
         } else {
-            zset.insert(SmolStr::new(id), 1);
+            let was_present = zset.insert(SmolStr::new(id), 1).is_some();
             if let Some(bytes) = data {
-                self.row_cache.put(
-                    (SmolStr::new(table), SmolStr::new(id)),
-                    bytes.to_vec(),
-                );
+                let row = self.cached_form(table, bytes);
+                self.cache_put((SmolStr::new(table), SmolStr::new(id)), row);
             }
+            self.notify(table, id, op, version, data);
+            self.committed_one(table, id, !was_present as i64);
         }
+        self.report_stats_if_due();
 
         // Return bare id — consistent with apply_batch membership_deltas ZSet key format.
-        Ok((SmolStr::new(id), weight))
+        let written = version.filter(|_| !matches!(op, Operation::Delete));
+        Ok((SmolStr::new(id), weight, written))
     }
 
     /// Batch mutations in **one** write transaction (one fsync).
@@ -238,34 +1118,197 @@ impl SpookyDb {
         &mut self,
         mutations: Vec<DbMutation>,
     ) -> Result<BatchMutationResult, SpookyDbError> {
-        // Validate all table names before touching redb.
-        for m in &mutations {
-            validate_table_name(&m.table)?;
+        self.apply_batches(vec![mutations])?.remove(0)
+    }
+
+    /// Several independent batches in **one** write transaction (one fsync),
+    /// with a result per batch — group commit for callers that each built
+    /// their own batch.
+    ///
+    /// Each batch is validated (table names, schemas, unique constraints) on
+    /// its own, in order, against the committed state plus the batches
+    /// accepted before it. A batch that fails validation gets its error and
+    /// writes nothing; the rest still commit. The outer `Err` is a storage
+    /// error, in which case no batch was written.
+    pub fn apply_batches(
+        &mut self,
+        batches: Vec<Vec<DbMutation>>,
+    ) -> Result<Vec<Result<BatchMutationResult, SpookyDbError>>, SpookyDbError> {
+        self.flush()?;
+        let start = self.timer();
+        let result = self.commit_batches(batches);
+        self.report_timing(TimedOp::Batch, start);
+        result
+    }
+
+    /// Apply `segments` in order in one write transaction, rolling back to
+    /// the end of the last good segment when one fails validation: the
+    /// segments before it commit, it and the rest are dropped.
+    ///
+    /// Each segment is checked like an `apply_batches` batch (table names,
+    /// schemas, unique constraints), staged, and then each of its mutations
+    /// is passed to `validate` with a `StagedView` of the transaction —
+    /// committed records plus every segment staged so far, this one
+    /// included — so checks like "the referenced parent exists" can see
+    /// earlier parts of the batch.
+    ///
+    /// The staging transaction is the savepoint: redb cannot roll a
+    /// transaction back to a point after its tables were opened, so it is
+    /// aborted once the first failure (or the end) is found, and the
+    /// accepted segments are then written in one new transaction — one
+    /// commit, one fsync, but every mutation is staged twice. The outer
+    /// `Err` is a storage error, in which case nothing was written.
+    pub fn apply_batch_with_savepoint(
+        &mut self,
+        segments: Vec<Vec<DbMutation>>,
+        mut validate: impl FnMut(&StagedView<'_>, &DbMutation) -> Result<(), SpookyDbError>,
+    ) -> Result<SavepointBatchResult, SpookyDbError> {
+        self.flush()?;
+        let staging = self.db.begin_write()?;
+        let mut committed = 0;
+        let mut rolled_back = None;
+        {
+            let mut records = staging.open_table(RECORDS_TABLE)?;
+            let mut unique = UniqueCheck::new(&self.unique);
+            for segment in &segments {
+                let mut trial = unique.clone();
+                let checked = segment.iter().try_for_each(|m| {
+                    validate_table_name(&m.table)?;
+                    validate_key(&m.table, &m.id)?;
+                    let delete = matches!(m.op, Operation::Delete);
+                    if !delete {
+                        self.check_schema(&m.table, &m.id, m.data.as_deref())?;
+                    }
+                    trial.write(&m.table, &m.id, delete, m.data.as_deref())
+                });
+                if let Err(e) = checked {
+                    rolled_back = Some(e);
+                    break;
+                }
+                for m in segment {
+                    let key = make_key(&m.table, &m.id)?;
+                    match (&m.op, &m.data) {
+                        (Operation::Delete, _) => drop(records.remove(key.as_str())?),
+                        (_, Some(bytes)) => {
+                            let stored = self.stored_form(&m.table, bytes, None)?;
+                            records.insert(key.as_str(), &*stored)?;
+                        }
+                        (_, None) => {}
+                    }
+                }
+                let view = StagedView {
+                    db: self,
+                    records: &records,
+                };
+                if let Err(e) = segment.iter().try_for_each(|m| validate(&view, m)) {
+                    rolled_back = Some(e);
+                    break;
+                }
+                unique = trial;
+                committed += 1;
+            }
+        }
+        staging.abort()?;
+
+        let accepted = segments.into_iter().take(committed).flatten().collect();
+        let result = self.commit_batches(vec![accepted])?.remove(0)?;
+        Ok(SavepointBatchResult {
+            result,
+            committed,
+            rolled_back,
+        })
+    }
+
+    /// Body of `apply_batches`, without flushing coalesced writes first.
+    fn commit_batches(
+        &mut self,
+        batches: Vec<Vec<DbMutation>>,
+    ) -> Result<Vec<Result<BatchMutationResult, SpookyDbError>>, SpookyDbError> {
+        // Validate all table names (and schemas) before touching redb.
+        // Unique constraints are checked in caller order, so a batch may move
+        // a value from one id to another.
+        let mut results: Vec<Result<BatchMutationResult, SpookyDbError>> =
+            Vec::with_capacity(batches.len());
+        let mut accepted: Vec<(usize, DbMutation)> = Vec::new();
+        let mut unique = UniqueCheck::new(&self.unique);
+        for (group, mutations) in batches.into_iter().enumerate() {
+            let mut trial = unique.clone();
+            let checked = mutations.iter().try_for_each(|m| {
+                validate_table_name(&m.table)?;
+                validate_key(&m.table, &m.id)?;
+                let delete = matches!(m.op, Operation::Delete);
+                if !delete {
+                    self.check_schema(&m.table, &m.id, m.data.as_deref())?;
+                }
+                trial.write(&m.table, &m.id, delete, m.data.as_deref())
+            });
+            match checked {
+                Ok(()) => {
+                    unique = trial;
+                    accepted.extend(mutations.into_iter().map(|m| (group, m)));
+                    results.push(Ok(BatchMutationResult {
+                        tick: 0,
+                        membership_deltas: FastMap::default(),
+                        content_updates: FastMap::default(),
+                        changed_tables: Vec::new(),
+                        versions: FastMap::default(),
+                    }));
+                }
+                Err(e) => results.push(Err(e)),
+            }
         }
+        let index_updates = unique.finish();
+        if accepted.is_empty() {
+            return Ok(results);
+        }
+        let join_updates = self.stage_joins(accepted.iter().map(|(_, m)| {
+            let delete = matches!(m.op, Operation::Delete);
+            (m.table.as_str(), m.id.as_str(), delete, m.data.as_deref())
+        }))?;
 
         // Sort by table to improve cache locality on the in-memory writes.
         // O(n log n) but n is typically small (< 10k) and cheap relative to
         // redb I/O. The redb write loop also iterates the sorted slice.
-        let mut mutations = mutations;
-        mutations.sort_unstable_by(|a, b| a.table.cmp(&b.table));
-
-        let mut membership_deltas: FastMap<SmolStr, ZSet> = FastMap::default();
-        let mut content_updates: FastMap<SmolStr, FastHashSet<SmolStr>> = FastMap::default();
-        let mut changed_tables: Vec<SmolStr> = Vec::new();
+        // Stable, so repeated writes to one id keep caller order (last wins),
+        // across batches too.
+        let mut mutations = accepted;
+        mutations.sort_by(|a, b| a.1.table.cmp(&b.1.table));
+        for (_, m) in &mutations {
+            self.zsets.load(&self.db, &m.table)?;
+        }
 
         // 1. All redb writes in one transaction.
-        let write_txn = self.db.begin_write()?;
+        let write_txn = self.begin_write()?;
+        let mut deltas: FastMap<SmolStr, TableDelta> = FastMap::default();
+        let mut bytes_written = 0;
+        let tables = mutations.iter().map(|(_, m)| m.table.as_str());
+        let mut stage = self.blob_stage(&write_txn, tables)?;
         {
             let mut records = write_txn.open_table(RECORDS_TABLE)?;
             let mut versions = write_txn.open_table(VERSION_TABLE)?;
-            for mutation in &mutations {
-                let key = make_key(&mutation.table, &mutation.id);
+            for (_, mutation) in &mut mutations {
+                let key = make_key(&mutation.table, &mutation.id)?;
+                let delta = deltas.entry(mutation.table.clone()).or_default();
                 if matches!(mutation.op, Operation::Delete) {
-                    records.remove(key.as_str())?;
+                    let old = records.remove(key.as_str())?;
+                    if let Some(old) = &old {
+                        self.release_blobs(stage.as_mut(), old.value())?;
+                    }
+                    delta.remove(old.map(|old| old.value().len()));
                     versions.remove(key.as_str())?;
                 } else {
                     if let Some(ref bytes) = mutation.data {
-                        records.insert(key.as_str(), bytes.as_slice())?;
+                        let stored = self.stored_form(&mutation.table, bytes, stage.as_mut())?;
+                        let old = records.insert(key.as_str(), &*stored)?;
+                        if let Some(old) = &old {
+                            self.release_blobs(stage.as_mut(), old.value())?;
+                        }
+                        delta.insert(old.map(|old| old.value().len()), stored.len());
+                        bytes_written += bytes.len() as u64;
+                    }
+                    if self.auto_version && mutation.version.is_none() {
+                        let current = versions.get(key.as_str())?.map(|guard| guard.value());
+                        mutation.version = Some(current.unwrap_or(0) + 1);
                     }
                     if let Some(ver) = mutation.version {
                         versions.insert(key.as_str(), ver)?;
@@ -273,60 +1316,132 @@ impl SpookyDb {
                 }
             }
         }
-        write_txn.commit()?;
+        let tombstones = self.stage_tombstones(
+            &write_txn,
+            mutations.iter().map(|(_, m)| {
+                let delete = matches!(m.op, Operation::Delete);
+                (m.table.as_str(), m.id.as_str(), delete)
+            }),
+        )?;
+        let expiry = self.stage_expiry(
+            &write_txn,
+            mutations.iter().map(|(_, m)| {
+                let delete = matches!(m.op, Operation::Delete);
+                (m.table.as_str(), m.id.as_str(), delete, m.expires_at)
+            }),
+        )?;
+        self.stage_times(
+            &write_txn,
+            mutations.iter().map(|(_, m)| {
+                let delete = matches!(m.op, Operation::Delete);
+                (m.table.as_str(), m.id.as_str(), delete)
+            }),
+        )?;
+        // A persisted view's rows ride on the commit that changed the view:
+        // they take no tick of their own and stay out of the oplog and the
+        // delta log, so consumers see one commit per user write.
+        let internal = self.storing_views;
+        let next_seq = match internal {
+            true => self.next_seq,
+            false => self.log_ops(
+                &write_txn,
+                mutations.iter().map(|(_, m)| {
+                    let data = m.data.as_deref();
+                    (m.table.as_str(), m.id.as_str(), m.op, m.version, data)
+                }),
+            )?,
+        };
+        let added_blobs = stage.is_some_and(|stage| stage.added);
+        let stats = self.zsets.stage_stats(&write_txn, deltas)?;
+        let tick = match internal {
+            true => self.commit_tick,
+            false => {
+                let tick = self.stage_tick(&write_txn)?;
+                self.log_deltas(
+                    &write_txn,
+                    tick,
+                    mutations.iter().map(|(_, m)| {
+                        let delete = matches!(m.op, Operation::Delete);
+                        (m.table.as_str(), m.id.as_str(), delete)
+                    }),
+                )?;
+                tick
+            }
+        };
+        self.commit(write_txn)?;
 
         // 2. Update in-memory state AFTER successful commit.
-        for mutation in mutations {
-            let DbMutation { table, id, op, data, .. } = mutation;
-
-            let was_present = self
-                .zsets
-                .get(&table)
-                .and_then(|z| z.get(&id))
-                .copied()
-                .unwrap_or(0) > 0;
-
-            let zset = self.zsets.entry(table.clone()).or_default();
+        self.next_seq = next_seq;
+        self.commit_tick = tick;
+        for result in results.iter_mut().flatten() {
+            result.tick = tick;
+        }
+        self.has_blobs |= added_blobs;
+        self.zsets.apply_stats(stats);
+        self.counters.bytes_written += bytes_written;
+        self.apply_tombstones(tombstones);
+        self.apply_expiry(expiry);
+        self.apply_index_updates(index_updates);
+        self.apply_joins(join_updates);
+        for (group, mutation) in mutations {
+            let DbMutation {
+                table,
+                id,
+                op,
+                data,
+                version,
+                ..
+            } = mutation;
+            let Some(Ok(result)) = results.get_mut(group) else {
+                unreachable!("accepted mutation without an accepted batch");
+            };
+
+            let zset = self.zsets.loaded_mut(&table);
+            // The delta is the actual weight change, so a batch's deltas sum
+            // to the difference between the ZSets before and after it.
+            let before = zset.get(&id).copied().unwrap_or(0);
 
             if matches!(op, Operation::Delete) {
                 zset.remove(&id);
                 self.row_cache.pop(&(table.clone(), id.clone()));
-                if was_present {
-                    membership_deltas
-                        .entry(table.clone())
-                        .or_default()
-                        .insert(id.clone(), -1);
+                if before > 0 {
+                    let delta = result.membership_deltas.entry(table.clone()).or_default();
+                    add_weight(delta, &id, -before);
+                    self.notify(&table, &id, op, version, None);
                 }
             } else {
                 zset.insert(id.clone(), 1);
+                self.notify(&table, &id, op, version, data.as_deref());
                 if let Some(bytes) = data {
-                    self.row_cache.put((table.clone(), id.clone()), bytes);
+                    self.cache_written((table.clone(), id.clone()), bytes);
                 }
-                let weight = op.weight();
-                if weight != 0 {
-                    membership_deltas
-                        .entry(table.clone())
-                        .or_default()
-                        .insert(id.clone(), weight);
+                if before != 1 {
+                    let delta = result.membership_deltas.entry(table.clone()).or_default();
+                    add_weight(delta, &id, 1 - before);
                 }
-                content_updates
+                result
+                    .content_updates
                     .entry(table.clone())
                     .or_default()
                     .insert(id.clone());
+                if let Some(version) = version {
+                    let versions = result.versions.entry(table.clone()).or_default();
+                    versions.insert(id.clone(), version);
+                }
             }
 
-            // Mutations are sorted by table, so consecutive entries share the same table.
-            // Compare against the last pushed value instead of scanning the whole vec.
-            if changed_tables.last() != Some(&table) {
-                changed_tables.push(table);
+            // Mutations are sorted by table, so each batch's entries for a
+            // table are consecutive. Compare against the batch's last pushed
+            // value instead of scanning the whole vec.
+            if result.changed_tables.last() != Some(&table) {
+                result.changed_tables.push(table);
             }
         }
 
-        Ok(BatchMutationResult {
-            membership_deltas,
-            content_updates,
-            changed_tables,
-        })
+        let committed: Vec<_> = results.iter().flatten().collect();
+        self.committed(&committed);
+        self.report_stats_if_due();
+        Ok(results)
     }
 
     /// Bulk initial load: all records in **one** write transaction.
@@ -337,33 +1452,337 @@ impl SpookyDb {
         &mut self,
         records: Vec<BulkRecord>,
     ) -> Result<(), SpookyDbError> {
+        self.flush()?;
+        let start = self.timer();
+        let result = self.load_records(records);
+        self.report_timing(TimedOp::BulkLoad, start);
+        result
+    }
+
+    fn load_records(&mut self, records: Vec<BulkRecord>) -> Result<(), SpookyDbError> {
+        let mut unique = UniqueCheck::new(&self.unique);
         for r in &records {
             validate_table_name(&r.table)?;
+            validate_key(&r.table, &r.id)?;
+            self.check_schema(&r.table, &r.id, Some(&r.data))?;
+            unique.write(&r.table, &r.id, false, Some(&r.data))?;
+        }
+        let index_updates = unique.finish();
+        let join_updates = self.stage_joins(
+            records
+                .iter()
+                .map(|r| (r.table.as_str(), r.id.as_str(), false, Some(r.data.as_slice()))),
+        )?;
+        for r in &records {
+            self.zsets.load(&self.db, &r.table)?;
         }
         // --- 1. Write all records to redb in one transaction ---
-        let write_txn = self.db.begin_write()?;
+        let write_txn = self.begin_write()?;
+        let mut deltas: FastMap<SmolStr, TableDelta> = FastMap::default();
+        let mut stage = self.blob_stage(&write_txn, records.iter().map(|r| r.table.as_str()))?;
         {
             let mut rec_table = write_txn.open_table(RECORDS_TABLE)?;
             let mut ver_table = write_txn.open_table(VERSION_TABLE)?;
             for record in &records {
-                let key = make_key(&record.table, &record.id);
-                rec_table.insert(key.as_str(), record.data.as_slice())?;
+                let key = make_key(&record.table, &record.id)?;
+                let stored = self.stored_form(&record.table, &record.data, stage.as_mut())?;
+                let old = rec_table.insert(key.as_str(), &*stored)?;
+                if let Some(old) = &old {
+                    self.release_blobs(stage.as_mut(), old.value())?;
+                }
+                let delta = deltas.entry(record.table.clone()).or_default();
+                delta.insert(old.map(|old| old.value().len()), stored.len());
                 if let Some(ver) = record.version {
                     ver_table.insert(key.as_str(), ver)?;
                 }
             }
         }
-        write_txn.commit()?;
+        let tombstones = self.stage_tombstones(
+            &write_txn,
+            records
+                .iter()
+                .map(|r| (r.table.as_str(), r.id.as_str(), false)),
+        )?;
+        self.stage_times(
+            &write_txn,
+            records
+                .iter()
+                .map(|r| (r.table.as_str(), r.id.as_str(), false)),
+        )?;
+        let next_seq = self.log_ops(
+            &write_txn,
+            records.iter().map(|r| {
+                let (table, id, data) = (r.table.as_str(), r.id.as_str(), Some(r.data.as_slice()));
+                (table, id, Operation::Create, r.version, data)
+            }),
+        )?;
+        let added_blobs = stage.is_some_and(|stage| stage.added);
+        let stats = self.zsets.stage_stats(&write_txn, deltas)?;
+        let tick = self.stage_tick(&write_txn)?;
+        self.log_deltas(
+            &write_txn,
+            tick,
+            records
+                .iter()
+                .map(|r| (r.table.as_str(), r.id.as_str(), false)),
+        )?;
+        self.commit(write_txn)?;
 
         // --- 2. Update in-memory state after successful commit ---
-        for BulkRecord { table, id, data, .. } in records {
-            self.zsets.entry(table.clone()).or_default().insert(id.clone(), 1);
-            self.row_cache.put((table, id), data);
+        self.next_seq = next_seq;
+        self.commit_tick = tick;
+        self.has_blobs |= added_blobs;
+        self.zsets.apply_stats(stats);
+        self.counters.bytes_written += records.iter().map(|r| r.data.len() as u64).sum::<u64>();
+        self.apply_tombstones(tombstones);
+        self.apply_index_updates(index_updates);
+        self.apply_joins(join_updates);
+        let mut loaded = BatchMutationResult {
+            tick,
+            membership_deltas: FastMap::default(),
+            content_updates: FastMap::default(),
+            changed_tables: Vec::new(),
+            versions: FastMap::default(),
+        };
+        for BulkRecord {
+            table,
+            id,
+            data,
+            version,
+        } in records
+        {
+            let before = self.zsets.loaded_mut(&table).insert(id.clone(), 1).unwrap_or(0);
+            self.notify(&table, &id, Operation::Create, version, Some(&data));
+            if !self.views.is_empty() || !self.delta_streams.is_empty() {
+                if before != 1 {
+                    let delta = loaded.membership_deltas.entry(table.clone()).or_default();
+                    add_weight(delta, &id, 1 - before);
+                }
+                loaded.content_updates.entry(table.clone()).or_default().insert(id.clone());
+                // Unsorted, but a load spans few tables.
+                if !loaded.changed_tables.contains(&table) {
+                    loaded.changed_tables.push(table.clone());
+                }
+            }
+            self.cache_written((table, id), data);
+        }
+        self.committed(&[&loaded]);
+        self.report_stats_if_due();
+        Ok(())
+    }
+}
+
+// ─── Write Coalescing ────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// `apply_mutation` that also reports durability: `on_durable` runs exactly
+    /// once, with `Ok(())` once the mutation's transaction has committed or
+    /// with the error that stopped it.
+    ///
+    /// Without coalescing that happens before this returns. With coalescing it
+    /// happens at the window's flush; an `Err` returned here (validation) means
+    /// nothing was buffered, and `on_durable` has already been called with it.
+    pub fn apply_mutation_durable(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+        on_durable: impl FnOnce(Result<(), &SpookyDbError>) + Send + 'static,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let on_durable: DurabilityCallback = Box::new(on_durable);
+        if self.coalesce.is_some() {
+            return self.buffer_mutation(table, op, id, data, version, Some(on_durable));
+        }
+        let result = self.write_one(table, op, id, data, version, None);
+        on_durable(result.as_ref().map(|_| ()));
+        result.map(|(id, weight, _)| (id, weight))
+    }
+
+    /// Commit every buffered mutation in one write transaction and run their
+    /// durability callbacks. Returns the number committed (0 when the buffer
+    /// is empty, including when coalescing is off).
+    ///
+    /// On a storage error the buffered mutations are dropped, not retried:
+    /// each callback and the returned `Err` report the failure.
+    ///
+    /// Records upgraded on read by `migrate_lazy` are written back first, in
+    /// a transaction of their own.
+    ///
+    /// Every other write path — and dropping the `SpookyDb` — flushes first,
+    /// so writes always commit in call order. Reads do not flush: a buffered
+    /// mutation is invisible to `get_record_bytes`, ZSets and the oplog until
+    /// its window commits.
+    pub fn flush(&mut self) -> Result<usize, SpookyDbError> {
+        self.write_back_upgrades()?;
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+        self.pending_since = None;
+        let (mutations, callbacks): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|p| (p.mutation, p.on_durable))
+            .unzip();
+        let count = mutations.len();
+        let outcome = self
+            .commit_batches(vec![mutations])
+            .and_then(|mut results| results.remove(0));
+        let status = outcome.as_ref().map(|_| ());
+        for on_durable in callbacks.into_iter().flatten() {
+            on_durable(status);
+        }
+        outcome.map(|_| count)
+    }
+
+    /// `flush` if the oldest buffered mutation has waited `max_delay`.
+    /// Call this from an idle loop or timer: the window is otherwise only
+    /// checked when the next mutation arrives.
+    pub fn flush_if_due(&mut self) -> Result<usize, SpookyDbError> {
+        match (self.coalesce, self.pending_since) {
+            (Some(window), Some(since)) if since.elapsed() >= window.max_delay => self.flush(),
+            _ => Ok(0),
+        }
+    }
+
+    /// Number of buffered, not yet committed mutations.
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Validate a mutation as `apply_mutation` would, buffer it, and flush
+    /// once the window is full or old enough.
+    fn buffer_mutation(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+        on_durable: Option<DurabilityCallback>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        if let Err(e) = self.check_buffered(table, op, id, data) {
+            if let Some(on_durable) = on_durable {
+                on_durable(Err(&e));
+            }
+            return Err(e);
+        }
+        self.pending.push(PendingWrite {
+            mutation: DbMutation {
+                table: SmolStr::new(table),
+                id: SmolStr::new(id),
+                op,
+                data: data.map(<[u8]>::to_vec),
+                version,
+                expires_at: None,
+            },
+            on_durable,
+        });
+        let since = *self.pending_since.get_or_insert_with(Instant::now);
+        if let Some(window) = self.coalesce
+            && (self.pending.len() >= window.max_mutations || since.elapsed() >= window.max_delay)
+        {
+            self.flush()?;
+        }
+        Ok((SmolStr::new(id), op.weight()))
+    }
+
+    /// Up-front validation for a buffered write, so the window's commit can
+    /// only fail on storage errors. Unique constraints are checked against
+    /// the committed state plus everything already buffered.
+    fn check_buffered(
+        &self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+    ) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        validate_key(table, id)?;
+        let delete = matches!(op, Operation::Delete);
+        if !delete {
+            self.check_schema(table, id, data)?;
+        }
+        if self.unique.contains_key(table) {
+            let mut unique = UniqueCheck::new(&self.unique);
+            for p in &self.pending {
+                let m = &p.mutation;
+                let delete = matches!(m.op, Operation::Delete);
+                unique.write(&m.table, &m.id, delete, m.data.as_deref())?;
+            }
+            unique.write(table, id, delete, data)?;
         }
         Ok(())
     }
 }
 
+impl Drop for SpookyDb {
+    /// Commits any coalesced writes and syncs non-durable commits. Errors
+    /// reach the durability callbacks, if any.
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+// ─── Buffer Pool ─────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Share `pool` with this database. `apply_batch(es)`, `bulk_load` and
+    /// `put_values` then return each record buffer to it once the row is
+    /// cached inline, and `put_values` serializes into buffers taken from
+    /// it, so a steady-state batch loop allocates no record buffers. Rows
+    /// too long to inline keep their buffer in the cache, as without a pool.
+    pub fn set_buffer_pool(&mut self, pool: Arc<BufferPool>) {
+        self.buffer_pool = Some(pool);
+    }
+
+    /// Stop returning buffers to the pool set with `set_buffer_pool`.
+    pub fn clear_buffer_pool(&mut self) -> Option<Arc<BufferPool>> {
+        self.buffer_pool.take()
+    }
+
+    pub fn buffer_pool(&self) -> Option<&Arc<BufferPool>> {
+        self.buffer_pool.as_ref()
+    }
+
+    /// Write `records` of `table` in one `apply_batch`: each a Create if
+    /// absent, otherwise an Update, like `put_struct`. Values are serialized
+    /// into buffers from the pool (fresh ones without a pool). A value that
+    /// is not an object fails with `SpookyDbError::Serialization` before
+    /// anything is written.
+    pub fn put_values<'v>(
+        &mut self,
+        table: &str,
+        records: impl IntoIterator<Item = (&'v str, &'v SpookyValue)>,
+    ) -> Result<BatchMutationResult, SpookyDbError> {
+        let pool = self.buffer_pool.clone();
+        let mut mutations = Vec::new();
+        for (id, value) in records {
+            let mut buf = pool.as_deref().map(BufferPool::take).unwrap_or_default();
+            if let Err(e) = serialize_into_buf(value, &mut buf) {
+                if let Some(pool) = &pool {
+                    let bufs = mutations.into_iter().filter_map(|m: DbMutation| m.data);
+                    bufs.chain([buf]).for_each(|buf| pool.put(buf));
+                }
+                return Err(e.into());
+            }
+            let op = match self.get_zset_weight(table, id) > 0 {
+                true => Operation::Update,
+                false => Operation::Create,
+            };
+            mutations.push(DbMutation {
+                table: SmolStr::new(table),
+                id: SmolStr::new(id),
+                op,
+                data: Some(buf),
+                version: None,
+                expires_at: None,
+            });
+        }
+        self.apply_batch(mutations)
+    }
+}
+
 // ─── Read Operations ──────────────────────────────────────────────────────────
 
 impl SpookyDb {
@@ -391,12 +1810,19 @@ impl SpookyDb {
         table: &str,
         id: &str,
     ) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        let start = self.timer();
+        let result = self.read_record_bytes(table, id);
+        self.report_timing(TimedOp::Read, start);
+        result
+    }
+
+    fn read_record_bytes(&self, table: &str, id: &str) -> Result<Option<Vec<u8>>, SpookyDbError> {
         validate_table_name(table)?;
 
         // ZSet guard — avoids unnecessary redb open for absent records.
         let present = self
             .zsets
-            .get(table)
+            .get(&self.db, table)?
             .and_then(|z| z.get(id))
             .copied()
             .unwrap_or(0)
@@ -407,20 +1833,166 @@ impl SpookyDb {
 
         // Cache hit — peek does not update LRU recency (requires &mut self).
         let cache_key = (SmolStr::new(table), SmolStr::new(id));
-        if let Some(bytes) = self.row_cache.peek(&cache_key) {
-            return Ok(Some(bytes.clone()));
+        let cached = self.row_cache.peek(&cache_key);
+        self.counters.cache_lookup(cached.is_some());
+        if let Some(bytes) = cached {
+            return Ok(Some(bytes.to_vec()));
         }
 
         // Cache miss — fall back to redb; propagate storage errors.
-        let db_key = make_key(table, id);
+        let db_key = make_key(table, id)?;
         let read_txn = self.db.begin_read()?;
         let tbl = read_txn.open_table(RECORDS_TABLE)?;
         match tbl.get(db_key.as_str())? {
-            Some(guard) => Ok(Some(guard.value().to_vec())),
+            Some(guard) => Ok(Some(self.read_form(table, id, guard.value())?.into_owned())),
             None => Ok(None),
         }
     }
 
+    /// `get_record_bytes` for many ids of one table, in the order given.
+    ///
+    /// Cache hits are served first; all misses are then read in a single
+    /// redb read transaction rather than one per miss. Like
+    /// `get_record_bytes`, misses do not populate the cache.
+    pub fn get_records_bulk(
+        &self,
+        table: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Option<Vec<u8>>>, SpookyDbError> {
+        validate_table_name(table)?;
+        let Some(zset) = self.zsets.get(&self.db, table)? else {
+            return Ok(vec![None; ids.len()]);
+        };
+        let table_key = SmolStr::new(table);
+        let mut out = Vec::with_capacity(ids.len());
+        let mut misses = Vec::new();
+        for (i, &id) in ids.iter().enumerate() {
+            if zset.get(id).copied().unwrap_or(0) <= 0 {
+                out.push(None);
+                continue;
+            }
+            let cached = self.row_cache.peek(&(table_key.clone(), SmolStr::new(id)));
+            self.counters.cache_lookup(cached.is_some());
+            if cached.is_none() {
+                misses.push(i);
+            }
+            out.push(cached.map(|row| row.to_vec()));
+        }
+        if misses.is_empty() {
+            return Ok(out);
+        }
+
+        let read_txn = self.db.begin_read()?;
+        let tbl = read_txn.open_table(RECORDS_TABLE)?;
+        for i in misses {
+            let db_key = make_key(table, ids[i])?;
+            if let Some(guard) = tbl.get(db_key.as_str())? {
+                out[i] = Some(self.read_form(table, ids[i], guard.value())?.into_owned());
+            }
+        }
+        Ok(out)
+    }
+
+    /// Call `visit(id, record)` for each present record among `ids` of
+    /// `table`, in the order given, and return how many were visited.
+    ///
+    /// The zero-copy counterpart of `get_records_bulk`: cache hits are
+    /// borrowed from the row cache and misses from the guards of one redb
+    /// read transaction, opened at the first miss. Nothing is cloned unless
+    /// the stored value is compressed. Absent ids are skipped; misses do not
+    /// populate the cache.
+    pub fn visit_records(
+        &self,
+        table: &str,
+        ids: &[&str],
+        mut visit: impl FnMut(&str, &SpookyRecord<'_>),
+    ) -> Result<usize, SpookyDbError> {
+        validate_table_name(table)?;
+        let Some(zset) = self.zsets.get(&self.db, table)? else {
+            return Ok(0);
+        };
+        let table_key = SmolStr::new(table);
+        let mut records = None;
+        let mut visited = 0;
+        for &id in ids {
+            if zset.get(id).copied().unwrap_or(0) <= 0 {
+                continue;
+            }
+            let cached = self.row_cache.peek(&(table_key.clone(), SmolStr::new(id)));
+            self.counters.cache_lookup(cached.is_some());
+            let guard;
+            let decoded;
+            let bytes = match cached {
+                Some(bytes) => &bytes[..],
+                None => {
+                    let tbl = match &records {
+                        Some(tbl) => tbl,
+                        None => records.insert(self.db.begin_read()?.open_table(RECORDS_TABLE)?),
+                    };
+                    let Some(value) = tbl.get(make_key(table, id)?.as_str())? else {
+                        continue;
+                    };
+                    guard = value;
+                    decoded = self.read_form(table, id, guard.value())?;
+                    &decoded
+                }
+            };
+            if let Ok((buf, count)) = from_bytes(bytes) {
+                visit(id, &SpookyRecord::new(buf, count));
+                visited += 1;
+            }
+        }
+        Ok(visited)
+    }
+
+    /// Run `f` on record `id` of `table` and return its result, or `None` if
+    /// the record is absent.
+    ///
+    /// The single-record counterpart of `visit_records`: a cache hit is
+    /// borrowed from the row cache, a miss from the redb read guard, which
+    /// stays alive until `f` returns. Nothing is cloned unless the stored
+    /// value is compressed or upgraded by a lazy migration. Misses do not
+    /// populate the cache. As with `get_record_bytes`, fields stored as blobs
+    /// appear as references.
+    ///
+    /// ```rust,ignore
+    /// let age = db.with_record("users", "alice", |rec| rec.get_i64("age"))?.flatten();
+    /// ```
+    pub fn with_record<R>(
+        &self,
+        table: &str,
+        id: &str,
+        f: impl FnOnce(&SpookyRecord<'_>) -> R,
+    ) -> Result<Option<R>, SpookyDbError> {
+        validate_table_name(table)?;
+        let present = self
+            .zsets
+            .get(&self.db, table)?
+            .and_then(|z| z.get(id))
+            .copied()
+            .unwrap_or(0)
+            > 0;
+        if !present {
+            return Ok(None);
+        }
+
+        let cached = self.row_cache.peek(&(SmolStr::new(table), SmolStr::new(id)));
+        self.counters.cache_lookup(cached.is_some());
+        if let Some(bytes) = cached {
+            let (buf, count) = from_bytes(bytes)?;
+            return Ok(Some(f(&SpookyRecord::new(buf, count))));
+        }
+
+        let read_txn = self.db.begin_read()?;
+        let tbl = read_txn.open_table(RECORDS_TABLE)?;
+        let Some(guard) = tbl.get(make_key(table, id)?.as_str())? else {
+            return Ok(None);
+        };
+        let bytes = self.read_form(table, id, guard.value())?;
+        let (buf, count) = from_bytes(&bytes)?;
+        Ok(Some(f(&SpookyRecord::new(buf, count))))
+    }
+
     /// Zero-copy borrowed SpookyRecord for the view evaluation hot path.
     ///
     /// Returns `Ok(Some(SpookyRecord<'a>))` if and only if the record is in the LRU row cache.
@@ -428,7 +2000,8 @@ impl SpookyDb {
     /// evicted from the cache.
     /// Returns `Err` if the table name is invalid.
     ///
-    /// **Cache miss fallback**: call `get_record_bytes(table, id)` which reads from redb.
+    /// **Cache miss fallback**: call `get_row_record_mut(table, id)`, which reads
+    /// through to redb, or `get_record_bytes(table, id)` for an owned copy.
     ///
     /// For the streaming pipeline hot path (write then read in the same tick), records
     /// are always in the cache — writes populate it immediately. Zero I/O, zero allocation.
@@ -442,7 +2015,7 @@ impl SpookyDb {
         // ZSet guard — avoid cache lookup for absent records.
         let present = self
             .zsets
-            .get(table)
+            .get(&self.db, table)?
             .and_then(|z| z.get(id))
             .copied()
             .unwrap_or(0)
@@ -453,8 +2026,60 @@ impl SpookyDb {
 
         // Cache-only — peek returns &Vec<u8> with lifetime 'a.
         let cache_key = (SmolStr::new(table), SmolStr::new(id));
-        let Some(bytes) = self.row_cache.peek(&cache_key) else {
+        let cached = self.row_cache.peek(&cache_key);
+        self.counters.cache_lookup(cached.is_some());
+        let Some(bytes) = cached else {
+            return Ok(None);
+        };
+        let (buf, count) = match from_bytes(bytes) {
+            Ok(pair) => pair,
+            Err(_) => return Ok(None),
+        };
+        Ok(Some(SpookyRecord::new(buf, count)))
+    }
+
+    /// Read-through `get_row_record`: on a cache miss the bytes are read from
+    /// redb and inserted into the row cache, then borrowed from there.
+    ///
+    /// A hit marks the row as recently used. `Ok(None)` means the record is
+    /// absent. A record larger than the cache's byte budget is not cached;
+    /// it is held until the next call instead, so the borrow still works.
+    pub fn get_row_record_mut(
+        &mut self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<SpookyRecord<'_>>, SpookyDbError> {
+        validate_table_name(table)?;
+        let present = self
+            .zsets
+            .get(&self.db, table)?
+            .and_then(|z| z.get(id))
+            .copied()
+            .unwrap_or(0)
+            > 0;
+        if !present {
             return Ok(None);
+        }
+
+        let cache_key = (SmolStr::new(table), SmolStr::new(id));
+        let hit = self.row_cache.peek(&cache_key).is_some();
+        self.counters.cache_lookup(hit);
+        if !hit {
+            let db_key = make_key(table, id)?;
+            let read_txn = self.db.begin_read()?;
+            let tbl = read_txn.open_table(RECORDS_TABLE)?;
+            let Some(guard) = tbl.get(db_key.as_str())? else {
+                return Ok(None);
+            };
+            let bytes = self.read_form(table, id, guard.value())?;
+            self.cache_put(cache_key.clone(), bytes);
+            if self.row_cache.peek(&cache_key).is_none() {
+                self.uncached_row = self.read_form(table, id, guard.value())?.into_owned();
+            }
+        }
+        let bytes = match self.row_cache.get(&cache_key) {
+            Some(bytes) => &bytes[..],
+            None => &self.uncached_row[..],
         };
         let (buf, count) = match from_bytes(bytes) {
             Ok(pair) => pair,
@@ -479,21 +2104,33 @@ impl SpookyDb {
         id: &str,
         fields: &[&str],
     ) -> Result<Option<SpookyValue>, SpookyDbError> {
-        let raw = match self.get_record_bytes(table, id)? {
-            Some(b) => b,
-            None => return Ok(None),
-        };
-
-        let (buf, count) = from_bytes(&raw)?;
-        let record = SpookyRecord::new(buf, count);
+        self.with_record(table, id, |rec| record_typed(rec.data_buf, fields))?
+            .transpose()
+    }
 
-        let mut map = std::collections::BTreeMap::new();
-        for &name in fields {
-            if let Some(val) = record.get_field::<SpookyValue>(name) {
-                map.insert(SmolStr::new(name), val);
-            }
-        }
-        Ok(Some(SpookyValue::Object(map)))
+    /// The record `id` deserialized into `T`, a struct with named fields;
+    /// `None` if it is absent.
+    ///
+    /// Reads like `get_record_bytes` (row cache, then redb), brings fields
+    /// stored as blobs back in line, and deserializes with
+    /// [`from_record`](crate::spooky_record::from_record): no `SpookyValue`
+    /// is built. Errors name the field that failed. The id is not part of
+    /// the record, so a field for it must be `Option` or `#[serde(default)]`.
+    pub fn get_as<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<T>, SpookyDbError> {
+        let Some(raw) = self.get_record_bytes(table, id)? else {
+            return Ok(None);
+        };
+        let bytes = blobs::inline(&raw, |hash| {
+            let read_txn = self.db.begin_read()?;
+            let blob = read_txn.open_table(BLOBS_TABLE)?.get(hash)?;
+            Ok(blob.map(|blob| blob.value().to_vec()))
+        })?;
+        let (buf, count) = from_bytes(&bytes)?;
+        Ok(Some(crate::spooky_record::from_record(&SpookyRecord::new(buf, count))?))
     }
 
     /// Version for a record (sync / conflict detection).
@@ -507,7 +2144,7 @@ impl SpookyDb {
         // Fast path: absent from ZSet → definitely not in VERSION_TABLE.
         let present = self
             .zsets
-            .get(table)
+            .get(&self.db, table)?
             .and_then(|z| z.get(id))
             .copied()
             .unwrap_or(0)
@@ -518,271 +2155,3467 @@ impl SpookyDb {
 
         // Slow path: record is present — check VERSION_TABLE (version is not
         // cached in memory; a record may exist with no version entry).
-        let key = make_key(table, id);
+        let key = make_key(table, id)?;
         let read_txn = self.db.begin_read()?;
         let tbl = read_txn.open_table(VERSION_TABLE)?;
         Ok(tbl
             .get(key.as_str())?
             .map(|guard: redb::AccessGuard<u64>| guard.value()))
     }
+
+    /// `get_version` for many ids of one table, in the order given, from one
+    /// VERSION_TABLE read transaction. Ids absent from the ZSet are `None`
+    /// without a lookup; the transaction is skipped if all are.
+    pub fn get_versions(
+        &self,
+        table: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Option<u64>>, SpookyDbError> {
+        validate_table_name(table)?;
+        let Some(zset) = self.zsets.get(&self.db, table)? else {
+            return Ok(vec![None; ids.len()]);
+        };
+        let present = |id: &str| zset.get(id).copied().unwrap_or(0) > 0;
+        if !ids.iter().any(|id| present(id)) {
+            return Ok(vec![None; ids.len()]);
+        }
+        let read_txn = self.db.begin_read()?;
+        let tbl = read_txn.open_table(VERSION_TABLE)?;
+        ids.iter()
+            .map(|&id| {
+                if !present(id) {
+                    return Ok(None);
+                }
+                let key = make_key(table, id)?;
+                Ok(tbl.get(key.as_str())?.map(|guard| guard.value()))
+            })
+            .collect()
+    }
 }
 
-// ─── ZSet Operations (pure memory, zero I/O) ─────────────────────────────────
+/// The named `fields` of record bytes `raw` as a `SpookyValue::Object`
+/// (see `get_record_typed`).
+pub(super) fn record_typed(raw: &[u8], fields: &[&str]) -> Result<SpookyValue, SpookyDbError> {
+    let (buf, count) = from_bytes(raw)?;
+    let record = SpookyRecord::new(buf, count);
+
+    let mut map = crate::spooky_value::FastMap::new();
+    for &name in fields {
+        if let Some(val) = record.get_field::<SpookyValue>(name) {
+            map.insert(SmolStr::new(name), val);
+        }
+    }
+    Ok(SpookyValue::Object(map))
+}
+
+// ─── Ordered Scans (redb key order) ──────────────────────────────────────────
 
 impl SpookyDb {
-    /// Full ZSet for a table. Pure memory, zero I/O.
-    ///
-    /// Returns `None` if the table has never had any records.
-    /// The borrow is valid until the next `&mut self` call.
+    /// Ids in `table` that start with `prefix`, in ascending byte order.
     ///
-    /// This is what `eval_snapshot(Scan)` borrows for the duration of a view tick.
-    pub fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
-        validate_table_name(table).ok()?;
-        self.zsets.get(table)
+    /// Reads RECORDS_TABLE directly — redb keys are sorted, so this is a single
+    /// seek plus a sequential walk over the matching keys. An empty prefix
+    /// returns every id in the table.
+    pub fn scan_ids(&self, table: &str, prefix: &str) -> Result<Vec<SmolStr>, SpookyDbError> {
+        let mut ids = Vec::new();
+        self.scan_keys(table, Bound::Included(prefix), Bound::Unbounded, |id, _| {
+            if !id.starts_with(prefix) {
+                return false;
+            }
+            ids.push(SmolStr::new(id));
+            true
+        })?;
+        Ok(ids)
     }
 
-    /// Weight for a single record. Returns 0 if absent (standard ZSet semantics).
-    pub fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
-        self.zsets
-            .get(table)
-            .and_then(|z| z.get(id).copied())
-            .unwrap_or(0)
+    /// Ids of the records in `table`, in ascending byte order, the sorted
+    /// counterpart of `ids`: `scan_ids(table, "")`.
+    pub fn ids_sorted(&self, table: &str) -> Result<Vec<SmolStr>, SpookyDbError> {
+        self.scan_ids(table, "")
     }
 
-    /// Applies a pre-computed ZSet delta to the in-memory state.
+    /// Ids in `table` within `range`, in ascending byte order.
     ///
-    /// This is `pub(crate)` because it is intended only for checkpoint-recovery paths
-    /// where the delta has already been validated and committed to disk. Do not call
-    /// this from general application code — use `apply_mutation` or `apply_batch` instead,
-    /// which maintain ZSet/disk atomicity.
-    #[allow(dead_code)]
-    pub(crate) fn apply_zset_delta_memory(&mut self, table: &str, delta: &ZSet) {
-        let zset = self.zsets.entry(SmolStr::new(table)).or_default();
-        for (id, weight) in delta {
-            let entry = zset.entry(id.clone()).or_insert(0);
-            *entry += weight;
-            debug_assert!(
-                *entry == 0 || *entry == 1,
-                "apply_zset_delta_memory: weight out of range after delta {weight}: got {entry}",
-                entry = *entry
-            );
-            // Remove entries that have reached zero weight.
-            if *entry == 0 {
-                zset.remove(id);
-            }
-        }
-    }
-}
-
-// ─── Table Info (pure memory, O(1)) ──────────────────────────────────────────
-
-impl SpookyDb {
-    /// Returns `true` if the table has at least one record in the in-memory ZSet.
-    pub fn table_exists(&self, table: &str) -> bool {
-        self.zsets
-            .get(table)
-            .map(|z| !z.is_empty())
-            .unwrap_or(false)
+    /// Intended for time-ordered ids (ULID, KSUID): `db.scan_range("events",
+    /// from.as_str()..to.as_str())` answers "everything in the last N hours"
+    /// without touching records outside the window.
+    pub fn scan_range<'r>(
+        &self,
+        table: &str,
+        range: impl RangeBounds<&'r str>,
+    ) -> Result<Vec<SmolStr>, SpookyDbError> {
+        let mut ids = Vec::new();
+        let (start, end) = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.scan_keys(table, start, end, |id, _| {
+            ids.push(SmolStr::new(id));
+            true
+        })?;
+        Ok(ids)
     }
 
-    /// All known table names (derived from in-memory ZSet keys).
-    pub fn table_names(&self) -> impl Iterator<Item = &SmolStr> {
-        self.zsets.keys()
+    /// Like `scan_range`, but also returns a copy of each record's bytes.
+    ///
+    /// Bytes come from the same read transaction as the keys, so the result is
+    /// a consistent snapshot. The row cache is neither consulted nor populated.
+    pub fn scan_range_records<'r>(
+        &self,
+        table: &str,
+        range: impl RangeBounds<&'r str>,
+    ) -> Result<Vec<(SmolStr, Vec<u8>)>, SpookyDbError> {
+        let mut rows = Vec::new();
+        let (start, end) = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.scan_keys(table, start, end, |id, bytes| {
+            rows.push((SmolStr::new(id), bytes.to_vec()));
+            true
+        })?;
+        Ok(rows)
     }
 
-    /// Record count for a table.
+    /// Every record of `table` as `(id, bytes)`, in redb key order or its
+    /// reverse. With lexicographically time-ordered ids (ULID, KSUID),
+    /// `SortDirection::Desc` lists the latest first.
     ///
-    /// O(1) — ZSet entries = records present.
-    pub fn table_len(&self, table: &str) -> usize {
-        self.zsets.get(table).map(|z| z.len()).unwrap_or(0)
+    /// Streams from one read transaction, held by the iterator, so the rows
+    /// are a consistent snapshot and stopping early reads no further. Like
+    /// `scan_range_records`, the row cache is neither consulted nor
+    /// populated.
+    pub fn iter_table_ordered(
+        &self,
+        table: &str,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<(SmolStr, Vec<u8>), SpookyDbError>> + '_, SpookyDbError>
+    {
+        validate_table_name(table)?;
+        let (prefix, table_end) = key_range(table);
+        let prefix_len = prefix.len();
+        let table = SmolStr::new(table);
+        let read_txn = self.db.begin_read()?;
+        let tbl = read_txn.open_table(RECORDS_TABLE)?;
+        let range = tbl.range::<&str>(prefix.as_str()..table_end.as_str())?;
+        let range: Box<dyn Iterator<Item = _>> = match direction {
+            SortDirection::Asc => Box::new(range),
+            SortDirection::Desc => Box::new(range.rev()),
+        };
+        Ok(range.map(move |entry| {
+            let (key, value) = entry?;
+            let id = SmolStr::new(&key.value()[prefix_len..]);
+            let bytes = self.read_form(&table, &id, value.value())?.into_owned();
+            Ok((id, bytes))
+        }))
     }
 
-    /// Ensures an in-memory ZSet entry exists for `table`.
-    ///
-    /// This guarantees that subsequent calls to `get_table_zset` return `Some(&ZSet)`
-    /// rather than `None`, even before any records are inserted. However, `table_exists`
-    /// checks whether the ZSet is non-empty — an ensured but empty table still returns
-    /// `false` from `table_exists`.
-    ///
-    /// Use this to pre-allocate the ZSet slot before bulk operations.
+    /// Walk RECORDS_TABLE keys of `table` between the id bounds in key order.
     ///
-    /// Returns `Err(SpookyDbError::InvalidKey)` if the table name contains `':'`.
-    pub fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+    /// `visit(id, bytes)` receives the bare id (table prefix stripped) and
+    /// returns `false` to stop early. An unbounded end stops at `"table;"`
+    /// (see `key_range`).
+    pub(super) fn scan_keys(
+        &self,
+        table: &str,
+        start: Bound<&str>,
+        end: Bound<&str>,
+        mut visit: impl FnMut(&str, &[u8]) -> bool,
+    ) -> Result<(), SpookyDbError> {
         validate_table_name(table)?;
-        self.zsets.entry(SmolStr::new(table)).or_default();
+        let (prefix, table_end) = key_range(table);
+        let prefix_len = prefix.len();
+        let key = |id: &str| format!("{prefix}{id}");
+        let lo = match start {
+            Bound::Included(id) => Bound::Included(key(id)),
+            Bound::Excluded(id) => Bound::Excluded(key(id)),
+            Bound::Unbounded => Bound::Included(key("")),
+        };
+        let hi = match end {
+            Bound::Included(id) => Bound::Included(key(id)),
+            Bound::Excluded(id) => Bound::Excluded(key(id)),
+            Bound::Unbounded => Bound::Excluded(table_end),
+        };
+        // redb panics on an inverted range; an empty result is the useful answer.
+        let inverted = match (&lo, &hi) {
+            (Bound::Included(l), Bound::Included(h)) => l > h,
+            (Bound::Included(l) | Bound::Excluded(l), Bound::Included(h) | Bound::Excluded(h)) => {
+                l >= h
+            }
+            _ => false,
+        };
+        if inverted {
+            return Ok(());
+        }
+
+        let read_txn = self.db.begin_read()?;
+        let tbl = read_txn.open_table(RECORDS_TABLE)?;
+        let bounds = (
+            lo.as_ref().map(String::as_str),
+            hi.as_ref().map(String::as_str),
+        );
+        for entry in tbl.range::<&str>(bounds)? {
+            let (key_guard, val_guard) = entry?;
+            let id = &key_guard.value()[prefix_len..];
+            if !visit(id, &self.read_form(table, id, val_guard.value())?) {
+                break;
+            }
+        }
         Ok(())
     }
 }
 
-// ─── DbBackend trait ──────────────────────────────────────────────────────────
+// ─── Queries ─────────────────────────────────────────────────────────────────
 
-/// Thin adapter trait for incremental migration from the old in-memory
-/// `Database` struct to `SpookyDb`. Implement for both; wire `circuit.rs`
-/// against the trait.
-///
-/// All write operations return `Result` — a disk-full or corruption error must
-/// never silently become a no-op. Callers must handle or propagate write errors.
-pub trait DbBackend {
-    /// Zero-copy ZSet access. Borrowed from memory — zero I/O.
-    fn get_table_zset(&self, table: &str) -> Option<&ZSet>;
+impl SpookyDb {
+    /// Ids of every record in `table` for which `pred` returns `true`.
+    ///
+    /// Cached rows are evaluated first, zero-copy and with zero I/O. If any
+    /// present record has been evicted from the row cache, one streaming
+    /// RECORDS_TABLE scan evaluates the rest straight from redb's pages — no
+    /// per-record transaction, no copy into the cache. Records that fail to
+    /// parse are skipped. Result order is unspecified.
+    pub fn query<F>(&self, table: &str, mut pred: F) -> Result<Vec<SmolStr>, SpookyDbError>
+    where
+        F: FnMut(&SpookyRecord<'_>) -> bool,
+    {
+        let mut matches = Vec::new();
+        self.for_each_record(table, |id, record| {
+            if pred(record) {
+                matches.push(SmolStr::new(id));
+            }
+        })?;
+        Ok(matches)
+    }
 
-    /// Raw bytes for a record, served from in-memory cache with redb fallback.
-    /// Returns `Ok(None)` if the record is absent. Returns `Err` on storage errors.
-    fn get_record_bytes(
+    /// Ids of the first `limit` records of `table` ordered by `field`.
+    ///
+    /// Sort keys are compared as raw fields (`coerce::compare_fields`), so
+    /// only the at most `limit` entries held in a bounded heap ever copy their
+    /// key bytes — "top 50 by score" costs one pass and 50 small copies, never
+    /// a decode of the table. Records without `field` sort first ascending and
+    /// last descending. Ties break by id, ascending.
+    pub fn query_sorted(
         &self,
         table: &str,
-        id: &str,
-    ) -> Result<Option<Vec<u8>>, SpookyDbError>;
+        field: &str,
+        direction: SortDirection,
+        limit: usize,
+    ) -> Result<Vec<SmolStr>, SpookyDbError> {
+        validate_table_name(table)?;
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut heap = BinaryHeap::with_capacity(limit.min(self.table_len(table)));
+        self.for_each_record(table, |id, record| {
+            let key = record.get_raw(field);
+            if heap.len() == limit {
+                let worst: &SortEntry = heap.peek().expect("heap is full");
+                let worst_key = worst.field();
+                if sort_cmp(key.as_ref(), id, worst_key.as_ref(), &worst.id, direction).is_ge() {
+                    return;
+                }
+                heap.pop();
+            }
+            heap.push(SortEntry {
+                key: key.map(|f| (f.type_tag, f.data.to_vec())),
+                id: SmolStr::new(id),
+                direction,
+            });
+        })?;
+        Ok(heap.into_sorted_vec().into_iter().map(|e| e.id).collect())
+    }
 
-    /// Zero-copy borrowed record access. Returns `None` if the record is absent.
+    /// Fold every present record of `table` into `agg` with weight 1.
     ///
-    /// Default implementation returns `None` (falls back to `get_record_bytes` for
-    /// backends without an in-memory row cache). Backends with an in-memory row
-    /// cache should override this for hot-path efficiency.
-    fn get_row_record_bytes<'a>(&'a self, _table: &str, _id: &str) -> Option<&'a [u8]> {
-        None
+    /// Same single-pass, cache-then-scan strategy as `query`. Call
+    /// `agg.finish()` for per-group results or `agg.total()` when ungrouped.
+    pub fn aggregate(&self, table: &str, agg: &mut Aggregator) -> Result<(), SpookyDbError> {
+        self.for_each_record(table, |_, record| agg.add(record, 1))
     }
 
-    /// Register an empty table.
+    /// Fold the records named by a ZSet `delta` into `agg`, each with its weight.
     ///
-    /// Returns `Err(SpookyDbError::InvalidKey)` if `table` contains `':'`.
-    fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError>;
+    /// Ids that are no longer present are skipped — a deleted record's bytes
+    /// are gone, so retract it with `Aggregator::add(&old_record, -1)` before
+    /// or while applying the delete.
+    pub fn aggregate_delta(
+        &self,
+        table: &str,
+        delta: &ZSet,
+        agg: &mut Aggregator,
+    ) -> Result<(), SpookyDbError> {
+        for (id, &weight) in delta {
+            if let Some(bytes) = self.get_record_bytes(table, id)? {
+                let (buf, count) = from_bytes(&bytes)?;
+                agg.add(&SpookyRecord::new(buf, count), weight);
+            }
+        }
+        Ok(())
+    }
 
-    /// Single mutation: record write + ZSet update.
-    fn apply_mutation(
-        &mut self,
+    /// Feed every present record of `table` to `op`, with the same
+    /// single-pass, cache-then-scan strategy as `query`.
+    pub fn fold_table(
+        &self,
         table: &str,
-        op: Operation,
-        id: &str,
-        data: Option<&[u8]>,
-        version: Option<u64>,
-    ) -> Result<(SmolStr, i64), SpookyDbError>;
+        op: &mut impl RecordOperator,
+    ) -> Result<(), SpookyDbError> {
+        self.for_each_record(table, |id, record| op.add(id, record))
+    }
 
-    /// Batch mutations in one transaction.
-    fn apply_batch(
-        &mut self,
-        mutations: Vec<DbMutation>,
-    ) -> Result<BatchMutationResult, SpookyDbError>;
+    /// Bring `op` up to date with a committed batch: ids it removed from
+    /// `table` are retracted, and written ones are fed again, zero-copy from
+    /// the row cache or one read transaction (see `visit_records`).
+    pub fn fold_batch(
+        &self,
+        table: &str,
+        result: &BatchMutationResult,
+        op: &mut impl RecordOperator,
+    ) -> Result<(), SpookyDbError> {
+        let mut ids: Vec<&str> = Vec::new();
+        for (id, &weight) in result.membership_deltas.get(table).into_iter().flatten() {
+            match weight < 0 {
+                true => op.remove(id),
+                false => ids.push(id),
+            }
+        }
+        let written = result.content_updates.get(table).into_iter().flatten();
+        ids.extend(written.map(SmolStr::as_str));
+        self.visit_records(table, &ids, |id, record| op.add(id, record))?;
+        Ok(())
+    }
 
-    /// Bulk initial load.
-    fn bulk_load(
-        &mut self,
-        records: Vec<BulkRecord>,
-    ) -> Result<(), SpookyDbError>;
+    /// Score every present record of `table` into `topk` (`fold_table`).
+    pub fn top_k(&self, table: &str, topk: &mut TopK) -> Result<(), SpookyDbError> {
+        self.fold_table(table, topk)
+    }
 
-    /// Weight for one record. Returns 0 if absent.
-    fn get_zset_weight(&self, table: &str, id: &str) -> i64;
+    /// Apply a committed batch to `topk` (`fold_batch`). Call
+    /// `TopK::changes` afterwards for the ranks that moved.
+    pub fn top_k_delta(
+        &self,
+        table: &str,
+        result: &BatchMutationResult,
+        topk: &mut TopK,
+    ) -> Result<(), SpookyDbError> {
+        self.fold_batch(table, result, topk)
+    }
 
-    /// Reconstruct a partial `SpookyValue::Object` from a stored record.
-    ///
-    /// Only fields whose names are listed in `fields` are included. Field names
-    /// are not recoverable from hashes — callers must supply the expected names.
-    /// Returns `Ok(None)` if the record does not exist.
-    fn get_record_typed(
+    /// Run `visit(id, record)` once for every parseable present record of
+    /// `table`: cached rows first, then one RECORDS_TABLE scan for the
+    /// evicted remainder (skipped entirely when every row is cached).
+    fn for_each_record(
         &self,
         table: &str,
-        id: &str,
-        fields: &[&str],
-    ) -> Result<Option<SpookyValue>, SpookyDbError>;
+        mut visit: impl FnMut(&str, &SpookyRecord<'_>),
+    ) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        let Some(zset) = self.zsets.get(&self.db, table)? else {
+            return Ok(());
+        };
+
+        let mut misses: FastHashSet<&str> = FastHashSet::default();
+        let t = SmolStr::new(table);
+        for id in zset.keys() {
+            match self.row_cache.peek(&(t.clone(), id.clone())) {
+                Some(bytes) => {
+                    if let Ok((buf, count)) = from_bytes(bytes) {
+                        visit(id, &SpookyRecord::new(buf, count));
+                    }
+                }
+                None => {
+                    misses.insert(id.as_str());
+                }
+            }
+        }
+        let (hits, missed) = ((zset.len() - misses.len()) as u64, misses.len() as u64);
+        let counters = &self.counters;
+        counters.cache_hits.fetch_add(hits, Ordering::Relaxed);
+        counters.cache_misses.fetch_add(missed, Ordering::Relaxed);
+        if misses.is_empty() {
+            return Ok(());
+        }
+
+        self.scan_keys(table, Bound::Unbounded, Bound::Unbounded, |id, bytes| {
+            if misses.remove(id)
+                && let Ok((buf, count)) = from_bytes(bytes)
+            {
+                visit(id, &SpookyRecord::new(buf, count));
+            }
+            !misses.is_empty()
+        })
+    }
 }
 
-impl DbBackend for SpookyDb {
-    fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
-        self.get_table_zset(table)
+/// Heap entry for `query_sorted`. `Ord` is result order, so the max-heap top
+/// is the entry that drops out first.
+struct SortEntry {
+    key: Option<(u8, Vec<u8>)>,
+    id: SmolStr,
+    direction: SortDirection,
+}
+
+impl SortEntry {
+    fn field(&self) -> Option<FieldRef<'_>> {
+        self.key.as_ref().map(|(type_tag, data)| FieldRef {
+            name_hash: 0,
+            type_tag: *type_tag,
+            data,
+        })
     }
+}
 
-    fn get_record_bytes(
-        &self,
-        table: &str,
-        id: &str,
-    ) -> Result<Option<Vec<u8>>, SpookyDbError> {
-        SpookyDb::get_record_bytes(self, table, id)
+impl Ord for SortEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        sort_cmp(
+            self.field().as_ref(),
+            &self.id,
+            other.field().as_ref(),
+            &other.id,
+            self.direction,
+        )
     }
+}
 
-    fn get_row_record_bytes<'a>(&'a self, table: &str, id: &str) -> Option<&'a [u8]> {
-        // Cache-only — None on cache miss (same semantics as get_row_record).
-        let cache_key = (SmolStr::new(table), SmolStr::new(id));
-        self.row_cache.peek(&cache_key).map(|v| v.as_slice())
+impl PartialOrd for SortEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
-        SpookyDb::ensure_table(self, table)
+impl PartialEq for SortEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
     }
+}
 
-    fn apply_mutation(
-        &mut self,
-        table: &str,
-        op: Operation,
-        id: &str,
-        data: Option<&[u8]>,
-        version: Option<u64>,
-    ) -> Result<(SmolStr, i64), SpookyDbError> {
-        SpookyDb::apply_mutation(self, table, op, id, data, version)
+impl Eq for SortEntry {}
+
+/// Result order for `query_sorted`: absent keys lowest, then
+/// `coerce::compare_fields`, flipped for `Desc`; ties by id ascending.
+fn sort_cmp(
+    a: Option<&FieldRef<'_>>,
+    a_id: &str,
+    b: Option<&FieldRef<'_>>,
+    b_id: &str,
+    direction: SortDirection,
+) -> std::cmp::Ordering {
+    let by_key = match (a, b) {
+        (Some(a), Some(b)) => compare_fields(a, b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    };
+    let by_key = match direction {
+        SortDirection::Asc => by_key,
+        SortDirection::Desc => by_key.reverse(),
+    };
+    by_key.then_with(|| a_id.cmp(b_id))
+}
+
+// ─── ZSet Operations (pure memory, zero I/O) ─────────────────────────────────
+
+impl SpookyDb {
+    /// Full ZSet for a table. Pure memory, zero I/O once the table is loaded.
+    ///
+    /// Returns `None` if the table has never had any records.
+    /// The borrow is valid until the next `&mut self` call.
+    ///
+    /// This is what `eval_snapshot(Scan)` borrows for the duration of a view tick.
+    ///
+    /// The first access to a table loads it from RECORDS_TABLE; a storage
+    /// error during that load also reads as `None` (and is retried on the
+    /// next access). Call `load_tables` at startup to surface it instead.
+    pub fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
+        validate_table_name(table).ok()?;
+        self.zsets.get(&self.db, table).ok()?
     }
 
-    fn apply_batch(
-        &mut self,
-        mutations: Vec<DbMutation>,
-    ) -> Result<BatchMutationResult, SpookyDbError> {
-        SpookyDb::apply_batch(self, mutations)
+    /// Weight for a single record. Returns 0 if absent (standard ZSet semantics),
+    /// or if loading the table fails (see `get_table_zset`).
+    pub fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
+        self.get_table_zset(table)
+            .and_then(|z| z.get(id).copied())
+            .unwrap_or(0)
     }
 
-    fn bulk_load(
-        &mut self,
-        records: Vec<BulkRecord>,
-    ) -> Result<(), SpookyDbError> {
-        SpookyDb::bulk_load(self, records)
+    /// Ids of the records present in `table`, in no particular order. Pure
+    /// memory once the table is loaded; empty for an unknown table or a
+    /// failed load (see `get_table_zset`).
+    ///
+    /// Use `ids_sorted` for byte order.
+    pub fn ids(&self, table: &str) -> impl Iterator<Item = &SmolStr> {
+        self.get_table_zset(table)
+            .into_iter()
+            .flatten()
+            .filter(|(_, weight)| **weight > 0)
+            .map(|(id, _)| id)
     }
 
-    fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
-        self.get_zset_weight(table, id)
+    /// Load every table's ZSet now rather than on first access: moves the
+    /// RECORDS_TABLE scan to a point of the caller's choosing and reports
+    /// storage errors that `get_table_zset` would swallow.
+    pub fn load_tables(&self) -> Result<(), SpookyDbError> {
+        self.zsets.load_all(&self.db)
     }
 
-    fn get_record_typed(
-        &self,
+    /// Applies a pre-computed ZSet delta to the in-memory state.
+    ///
+    /// Weights are summed as multiplicities of any size or sign; an entry
+    /// whose weight reaches zero is removed.
+    ///
+    /// This is `pub(crate)` because it is intended only for recovery paths where the
+    /// delta has already been validated and committed to disk. Do not call this from
+    /// general application code — use `apply_mutation` or `apply_batch` instead, which
+    /// maintain ZSet/disk atomicity. Circuits recover with `checkpoint` and
+    /// `restore_from_checkpoint`.
+    #[allow(dead_code)]
+    pub(crate) fn apply_zset_delta_memory(
+        &mut self,
         table: &str,
-        id: &str,
-        fields: &[&str],
-    ) -> Result<Option<SpookyValue>, SpookyDbError> {
-        SpookyDb::get_record_typed(self, table, id, fields)
+        delta: &ZSet,
+    ) -> Result<(), SpookyDbError> {
+        self.zsets.load(&self.db, table)?;
+        let zset = self.zsets.loaded_mut(table);
+        for (id, &weight) in delta {
+            add_weight(zset, id, weight);
+        }
+        Ok(())
     }
 }
 
-// ─── Tests ────────────────────────────────────────────────────────────────────
+// ─── Checkpoints ─────────────────────────────────────────────────────────────
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::serialization::from_cbor;
-    use tempfile::NamedTempFile;
+impl SpookyDb {
+    /// Set tick counter `name`, e.g. the last tick a circuit has fully
+    /// processed. Kept in memory; the next `checkpoint` persists it together
+    /// with the ZSets.
+    pub fn set_tick(&mut self, name: &str, tick: u64) {
+        self.ticks.insert(SmolStr::new(name), tick);
+    }
 
-    // BENCH_CBOR: a pre-serialized CBOR map (12 fields) representing a realistic
-    // user record. Used by all test helpers that need pre-built SpookyRecord bytes.
-    //
-    // Fields and values (as CBOR):
-    //   active:      true                              (bool)
-    //   age:         28                                (uint/i64)
-    //   count:       1000                              (uint)
-    //   deleted:     false                             (bool)
-    //   history:     [{action:"login",  timestamp:1234567890},
-    //                 {action:"update", timestamp:1234567900}]  (array of 2 objects)
-    //   id:          "user:abc123"                     (str)
-    //   metadata:    null
-    //   mixed_array: [42, "text", true, {nested:"value"}]       (array of 4)
-    //   name:        "Alice"                           (str)
-    //   profile:     {avatar:"https://example.com/avatar.jpg",
-    //                 bio:"Software engineer",
-    //                 settings:{notifications:true,
-    //                           privacy:{level:3, public:false},
-    //                           theme:"dark"}}         (nested object)
-    //   score:       99.5                              (f64)
-    //   tags:        ["developer", "rust", "database"] (array of 3 strings)
-    //
-    // Regenerate: build a cbor4ii::core::Value::Map with the above fields, call
-    // `from_cbor(&val)` to obtain the SpookyRecord bytes, and print them as a
-    // Rust byte-array literal.
-    const BENCH_CBOR: &[u8] = &[
-        172, 102, 97, 99, 116, 105, 118, 101, 245, 99, 97, 103, 101, 24, 28, 101, 99, 111, 117,
+    /// Tick counter `name` as last set, or as of the last checkpoint after
+    /// reopening.
+    pub fn tick(&self, name: &str) -> Option<u64> {
+        self.ticks.get(name).copied()
+    }
+
+    /// Tick of the last committed `apply_mutation`, `apply_batch(es)` or
+    /// `bulk_load` (0 before the first), persisted with its records. Every
+    /// such commit gets the next tick — its `BatchMutationResult::tick` —
+    /// so ticks totally order the batches, across restarts too. A read
+    /// through `&self` observes exactly this tick; tag results with it.
+    pub fn commit_tick(&self) -> u64 {
+        self.commit_tick
+    }
+
+    /// Write the next commit tick inside `txn`. Returns it, to store in
+    /// `commit_tick` after commit.
+    fn stage_tick(&self, txn: &redb::WriteTransaction) -> Result<u64, SpookyDbError> {
+        let tick = self.commit_tick + 1;
+        txn.open_table(COMMIT_TICK_TABLE)?.insert(TICK_KEY, tick)?;
+        Ok(tick)
+    }
+
+    /// Persist every table's ZSet and the tick counters in one write
+    /// transaction, replacing the previous checkpoint, and return them.
+    ///
+    /// Flushes coalesced writes and loads every table first. Rewrites
+    /// CHECKPOINT_TABLE in full — O(records), so take one per recovery
+    /// point, not per tick.
+    pub fn checkpoint(&mut self) -> Result<ZSetSnapshot, SpookyDbError> {
+        self.flush()?;
+        self.zsets.load_all(&self.db)?;
+        let snapshot = ZSetSnapshot {
+            tables: self
+                .zsets
+                .iter_loaded()
+                .filter(|(_, zset)| !zset.is_empty())
+                .map(|(table, zset)| (table.clone(), zset.clone()))
+                .collect(),
+            ticks: self.ticks.clone(),
+        };
+
+        let write_txn = self.begin_write()?;
+        {
+            let mut zsets = write_txn.open_table(CHECKPOINT_TABLE)?;
+            zsets.retain(|_, _| false)?;
+            for (table, zset) in &snapshot.tables {
+                for (id, &weight) in zset {
+                    zsets.insert(make_key(table, id)?.as_str(), weight)?;
+                }
+            }
+            let mut ticks = write_txn.open_table(CHECKPOINT_TICKS_TABLE)?;
+            ticks.retain(|_, _| false)?;
+            for (name, &tick) in &snapshot.ticks {
+                ticks.insert(name.as_str(), tick)?;
+            }
+        }
+        self.commit(write_txn)?;
+        Ok(snapshot)
+    }
+
+    /// The checkpoint persisted by the last `checkpoint`, or `None` if none
+    /// was ever taken.
+    pub fn load_checkpoint(&self) -> Result<Option<ZSetSnapshot>, SpookyDbError> {
+        let read_txn = self.db.begin_read()?;
+        let zsets = read_txn.open_table(CHECKPOINT_TABLE)?;
+        let ticks = read_txn.open_table(CHECKPOINT_TICKS_TABLE)?;
+        if zsets.is_empty()? && ticks.is_empty()? {
+            return Ok(None);
+        }
+        let mut snapshot = ZSetSnapshot::default();
+        for entry in zsets.iter()? {
+            let (key, weight) = entry?;
+            let rid = RecordId::from_db_key(key.value());
+            if let Some((table, id)) = rid.map(RecordId::into_parts) {
+                snapshot.tables.entry(table).or_default().insert(id, weight.value());
+            }
+        }
+        for entry in ticks.iter()? {
+            let (name, tick) = entry?;
+            snapshot.ticks.insert(SmolStr::new(name.value()), tick.value());
+        }
+        Ok(Some(snapshot))
+    }
+
+    /// Recover a circuit from `snapshot`: adopt its tick counters and return,
+    /// per table, the ZSet delta from the snapshot to the committed state —
+    /// what state restored to `snapshot` must still apply to catch up. Tables
+    /// without changes are left out, so an empty map means nothing was
+    /// written since.
+    ///
+    /// The in-memory ZSets are not touched: they always mirror
+    /// RECORDS_TABLE. Flushes coalesced writes and loads every table first.
+    pub fn restore_from_checkpoint(
+        &mut self,
+        snapshot: &ZSetSnapshot,
+    ) -> Result<FastMap<SmolStr, ZSet>, SpookyDbError> {
+        self.flush()?;
+        self.zsets.load_all(&self.db)?;
+        let empty = ZSet::default();
+        let mut deltas: FastMap<SmolStr, ZSet> = FastMap::default();
+        for (table, current) in self.zsets.iter_loaded() {
+            let before = snapshot.tables.get(table).unwrap_or(&empty);
+            let delta = zset::difference(current, before);
+            if !delta.is_empty() {
+                deltas.insert(table.clone(), delta);
+            }
+        }
+        for (table, before) in &snapshot.tables {
+            if self.zsets.peek(table).is_none() && !before.is_empty() {
+                deltas.insert(table.clone(), zset::difference(&empty, before));
+            }
+        }
+        self.ticks = snapshot.ticks.clone();
+        Ok(deltas)
+    }
+}
+
+// ─── Table Info (pure memory, O(1)) ──────────────────────────────────────────
+
+impl SpookyDb {
+    /// Returns `true` if the table has at least one record.
+    pub fn table_exists(&self, table: &str) -> bool {
+        self.zsets.len(table) > 0
+    }
+
+    /// All known table names (from META_TABLE and in-memory ZSet keys).
+    pub fn table_names(&self) -> impl Iterator<Item = &SmolStr> {
+        self.zsets.names()
+    }
+
+    /// Record count for a table.
+    ///
+    /// O(1) — ZSet entries = records present, or the persisted count if the
+    /// table is not loaded yet. Never loads it.
+    pub fn table_len(&self, table: &str) -> usize {
+        self.zsets.len(table)
+    }
+
+    /// Ensures an in-memory ZSet entry exists for `table`.
+    ///
+    /// This guarantees that subsequent calls to `get_table_zset` return `Some(&ZSet)`
+    /// rather than `None`, even before any records are inserted. However, `table_exists`
+    /// checks whether the ZSet is non-empty — an ensured but empty table still returns
+    /// `false` from `table_exists`.
+    ///
+    /// Use this to pre-allocate the ZSet slot before bulk operations.
+    ///
+    /// Returns `Err(SpookyDbError::InvalidKey)` if the table name is empty or contains U+001F.
+    pub fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        self.zsets.load(&self.db, table)
+    }
+}
+
+// ─── Table Management ────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Remove every record of `table` in one write transaction and return
+    /// how many there were.
+    ///
+    /// RECORDS_TABLE, VERSION_TABLE and TTL_TABLE entries go by range removal
+    /// on the `"table:"` prefix. Otherwise each record is treated as a Delete:
+    /// soft-delete tables get tombstones, the oplog gets one Delete entry per
+    /// record, and subscribers get one event per record. The removal takes
+    /// a commit tick and reaches the delta log, views and delta streams as
+    /// one −1 per record. The table itself stays, with its schema, unique
+    /// constraints, subscriptions and other options.
+    pub fn truncate_table(&mut self, table: &str) -> Result<usize, SpookyDbError> {
+        self.remove_table(table, false)
+    }
+
+    /// `truncate_table`, then forget the table: its tombstones are removed,
+    /// its schema, schema version, lazy migrations, unique constraints, join
+    /// indexes, soft-delete option and cache policy are cleared, and its subscribers
+    /// are disconnected. It drops out of
+    /// `table_names`. Returns the number of records removed. No tombstones
+    /// are written.
+    pub fn drop_table(&mut self, table: &str) -> Result<usize, SpookyDbError> {
+        self.remove_table(table, true)
+    }
+
+    /// Move every record of `old` to `new` in one write transaction and
+    /// return how many moved. Schema migrations use this.
+    ///
+    /// Keys in RECORDS_TABLE, VERSION_TABLE, TTL_TABLE and TOMBSTONE_TABLE
+    /// are rewritten under the `"new:"` prefix; the table's records pass
+    /// through memory once. ZSet, stats, cache rows and policy, schema,
+    /// schema version, lazy migrations, unique constraints and the
+    /// soft-delete option move with it.
+    /// Subscriptions stay on `old` and receive no events; the oplog records
+    /// a Delete from `old` and a Create in `new` per record. The move takes
+    /// a commit tick, and the delta log, views and delta streams see −1 in
+    /// `old` and +1 in `new` per record.
+    ///
+    /// Fails with `SpookyDbError::TableExists` if `new` has records.
+    pub fn rename_table(&mut self, old: &str, new: &str) -> Result<usize, SpookyDbError> {
+        validate_table_name(old)?;
+        validate_table_name(new)?;
+        self.flush()?;
+        if self.zsets.len(new) > 0 {
+            return Err(SpookyDbError::TableExists(SmolStr::new(new)));
+        }
+        if old == new {
+            return Ok(0);
+        }
+        self.zsets.load(&self.db, old)?;
+
+        let write_txn = self.begin_write()?;
+        let mut moved: Vec<(SmolStr, Vec<u8>)> = Vec::new();
+        {
+            let mut records = write_txn.open_table(RECORDS_TABLE)?;
+            let (lo, hi) = key_range(old);
+            let range = lo.as_str()..hi.as_str();
+            for entry in records.extract_from_if::<&str, _>(range, |_, _| true)? {
+                let (key, value) = entry?;
+                let id = SmolStr::new(&key.value()[lo.len()..]);
+                moved.push((id, value.value().to_vec()));
+            }
+            for (id, bytes) in &moved {
+                records.insert(make_key(new, id)?.as_str(), bytes.as_slice())?;
+            }
+        }
+        let versions = rename_keys(&write_txn, VERSION_TABLE, old, new)?;
+        let versions: FastMap<SmolStr, u64> = versions.into_iter().collect();
+        rename_keys(&write_txn, TTL_TABLE, old, new)?;
+        rename_keys(&write_txn, TOMBSTONE_TABLE, old, new)?;
+        rename_keys(&write_txn, TIMES_TABLE, old, new)?;
+        {
+            let mut schema_versions = write_txn.open_table(SCHEMA_VERSION_TABLE)?;
+            let version = schema_versions.remove(old)?.map(|v| v.value());
+            if let Some(version) = version {
+                schema_versions.insert(new, version)?;
+            }
+        }
+        let ops = moved.iter().flat_map(|(id, bytes)| {
+            let version = versions.get(id).copied();
+            let data = Some(bytes.as_slice());
+            [
+                (old, id.as_str(), Operation::Delete, None, None),
+                (new, id.as_str(), Operation::Create, version, data),
+            ]
+        });
+        let next_seq = self.log_ops(&write_txn, ops)?;
+        let stats = self.zsets.stats(old);
+        let renamed = [
+            (SmolStr::new(old), TableStats::default()),
+            (SmolStr::new(new), stats),
+        ];
+        zsets::write_stats(&write_txn, &renamed)?;
+        let tick = self.stage_tick(&write_txn)?;
+        let moves = moved
+            .iter()
+            .flat_map(|(id, _)| [(old, id.as_str(), true), (new, id.as_str(), false)]);
+        self.log_deltas(&write_txn, tick, moves)?;
+        self.commit(write_txn)?;
+
+        // In-memory state, after the commit.
+        self.next_seq = next_seq;
+        self.commit_tick = tick;
+        self.zsets.rename(old, new);
+        let new = SmolStr::new(new);
+        for (id, _) in &moved {
+            let slot = (SmolStr::new(old), id.clone());
+            if let Some(at) = self.expires.get(&slot).copied() {
+                self.set_expiry_memory(slot.0, slot.1, None);
+                self.set_expiry_memory(new.clone(), id.clone(), Some(at));
+            }
+        }
+        if let Some(ids) = self.tombstones.remove(old) {
+            self.tombstones.entry(new.clone()).or_default().extend(ids);
+        }
+        if let Some(schema) = self.schemas.remove(old) {
+            self.schemas.insert(new.clone(), schema);
+        }
+        if let Some(version) = self.schema_versions.remove(old) {
+            self.schema_versions.insert(new.clone(), version);
+        }
+        if let Some(mut migrations) = self.lazy.remove(old) {
+            for migration in &mut migrations {
+                migration.table = new.clone();
+            }
+            self.lazy.insert(new.clone(), migrations);
+        }
+        self.compression.rename(old, &new);
+        if let Some(min) = self.blob_thresholds.remove(old) {
+            self.blob_thresholds.insert(new.clone(), min);
+        }
+        if let Some(indexes) = self.unique.remove(old) {
+            self.unique.insert(new.clone(), indexes);
+        }
+        if let Some(indexes) = self.joins.remove(old) {
+            self.joins.insert(new.clone(), indexes);
+        }
+        for index in self.joins.values_mut().flatten() {
+            if index.target == old {
+                index.target = new.clone();
+            }
+        }
+        if self.soft_delete.remove(old) {
+            self.soft_delete.insert(new.clone());
+        }
+        if self.timestamped.remove(old) {
+            self.timestamped.insert(new.clone());
+        }
+        self.counters.cache_evictions += self.row_cache.rename(old, &new) as u64;
+        let old = SmolStr::new(old);
+        let mut result = BatchMutationResult {
+            tick,
+            membership_deltas: FastMap::default(),
+            content_updates: FastMap::default(),
+            changed_tables: Vec::new(),
+            versions: FastMap::default(),
+        };
+        if !moved.is_empty() {
+            let ids = moved.iter().map(|(id, _)| id);
+            let removed = ids.clone().map(|id| (id.clone(), -1)).collect();
+            let added = ids.clone().map(|id| (id.clone(), 1)).collect();
+            result.membership_deltas.insert(old.clone(), removed);
+            result.membership_deltas.insert(new.clone(), added);
+            result.content_updates.insert(new.clone(), ids.cloned().collect());
+            result.changed_tables = vec![old, new];
+        }
+        self.committed(&[&result]);
+        self.report_stats_if_due();
+        Ok(moved.len())
+    }
+
+    fn remove_table(&mut self, table: &str, drop: bool) -> Result<usize, SpookyDbError> {
+        validate_table_name(table)?;
+        self.flush()?;
+        self.zsets.load(&self.db, table)?;
+        let ids: Vec<SmolStr> = self.zsets.loaded_mut(table).keys().cloned().collect();
+        let (lo, hi) = key_range(table);
+        let range = lo.as_str()..hi.as_str();
+
+        let write_txn = self.begin_write()?;
+        let mut removed = 0;
+        {
+            let mut records = write_txn.open_table(RECORDS_TABLE)?;
+            let mut released = Vec::new();
+            records.retain_in::<&str, _>(range.clone(), |_, value| {
+                removed += 1;
+                if self.has_blobs {
+                    released.push(value.to_vec());
+                }
+                false
+            })?;
+            if let Some(mut stage) = self.blob_stage(&write_txn, std::iter::empty())? {
+                for value in &released {
+                    self.release_blobs(Some(&mut stage), value)?;
+                }
+            }
+            let mut versions = write_txn.open_table(VERSION_TABLE)?;
+            versions.retain_in::<&str, _>(range.clone(), |_, _| false)?;
+            let mut ttl = write_txn.open_table(TTL_TABLE)?;
+            ttl.retain_in::<&str, _>(range.clone(), |_, _| false)?;
+            let mut times = write_txn.open_table(TIMES_TABLE)?;
+            times.retain_in::<&str, _>(range.clone(), |_, _| false)?;
+        }
+        let tombstones = if drop {
+            let mut tombstones = write_txn.open_table(TOMBSTONE_TABLE)?;
+            tombstones.retain_in::<&str, _>(range, |_, _| false)?;
+            write_txn.open_table(SCHEMA_VERSION_TABLE)?.remove(table)?;
+            FastMap::default()
+        } else {
+            let ops = ids.iter().map(|id| (table, id.as_str(), true));
+            self.stage_tombstones(&write_txn, ops)?
+        };
+        let deletes = ids
+            .iter()
+            .map(|id| (table, id.as_str(), Operation::Delete, None, None));
+        let next_seq = self.log_ops(&write_txn, deletes)?;
+        zsets::write_stats(&write_txn, &[(SmolStr::new(table), TableStats::default())])?;
+        let tick = self.stage_tick(&write_txn)?;
+        self.log_deltas(&write_txn, tick, ids.iter().map(|id| (table, id.as_str(), true)))?;
+        self.commit(write_txn)?;
+
+        // In-memory state, after the commit.
+        self.next_seq = next_seq;
+        self.commit_tick = tick;
+        self.apply_tombstones(tombstones);
+        if let Some(indexes) = self.unique.get_mut(table) {
+            for index in indexes {
+                *index = UniqueIndex::new(index.field.clone());
+            }
+        }
+        for index in self.joins.get_mut(table).into_iter().flatten() {
+            index.clear();
+        }
+        let table = SmolStr::new(table);
+        for id in &ids {
+            self.set_expiry_memory(table.clone(), id.clone(), None);
+            self.row_cache.pop(&(table.clone(), id.clone()));
+            self.notify(&table, id, Operation::Delete, None, None);
+        }
+        if drop {
+            self.zsets.remove(&table);
+            self.tombstones.remove(&table);
+            self.schemas.remove(&table);
+            self.schema_versions.remove(&table);
+            self.lazy.remove(&table);
+            self.compression.set(&table, None)?;
+            self.blob_thresholds.remove(&table);
+            self.unique.remove(&table);
+            self.joins.remove(&table);
+            self.soft_delete.remove(&table);
+            self.subscribers.remove(&table);
+            self.watchers.remove(&table);
+            self.row_cache.set_policy(&table, CachePolicy::Shared);
+        } else {
+            self.zsets.loaded_mut(&table).clear();
+            self.zsets.apply_stats(vec![(table.clone(), TableStats::default())]);
+        }
+        let mut result = BatchMutationResult {
+            tick,
+            membership_deltas: FastMap::default(),
+            content_updates: FastMap::default(),
+            changed_tables: Vec::new(),
+            versions: FastMap::default(),
+        };
+        if !ids.is_empty() {
+            let delta = ids.into_iter().map(|id| (id, -1)).collect();
+            result.membership_deltas.insert(table.clone(), delta);
+            result.changed_tables.push(table);
+        }
+        self.committed(&[&result]);
+        self.report_stats_if_due();
+        Ok(removed)
+    }
+}
+
+/// Rewrite every `"old:…"` key of a `"table:id"`-keyed table under
+/// `"new:…"`. Returns the moved `(id, value)` pairs.
+fn rename_keys<V>(
+    txn: &WriteTransaction,
+    def: TableDefinition<&str, V>,
+    old: &str,
+    new: &str,
+) -> Result<Vec<(SmolStr, V)>, SpookyDbError>
+where
+    V: for<'a> redb::Value<SelfType<'a> = V> + Copy + 'static,
+{
+    let mut table = txn.open_table(def)?;
+    let (lo, hi) = key_range(old);
+    let mut moved = Vec::new();
+    for entry in table.extract_from_if::<&str, _>(lo.as_str()..hi.as_str(), |_, _| true)? {
+        let (key, value) = entry?;
+        moved.push((SmolStr::new(&key.value()[lo.len()..]), value.value()));
+    }
+    for (id, value) in &moved {
+        table.insert(make_key(new, id)?.as_str(), value)?;
+    }
+    Ok(moved)
+}
+
+// ─── Namespaces ──────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// A handle that scopes every table to tenant `name`: its table `users`
+    /// is stored as `"name:users"`, with its own ZSet, cache entries and
+    /// stats. See [`Namespace`].
+    ///
+    /// Returns `Err(SpookyDbError::InvalidKey)` if `name` is empty or
+    /// contains ':'.
+    pub fn namespace(&mut self, name: &str) -> Result<Namespace<'_>, SpookyDbError> {
+        Namespace::new(self, name)
+    }
+}
+
+// ─── Typed Tables ────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// A handle that reads and writes `name` as values of the serde struct
+    /// `T`. See [`typed::Table`].
+    ///
+    /// Returns `Err(SpookyDbError::Serialization)` if `T` does not
+    /// deserialize from a struct with named fields.
+    pub fn table<T: Serialize + DeserializeOwned>(
+        &mut self,
+        name: &str,
+    ) -> Result<typed::Table<'_, T>, SpookyDbError> {
+        typed::Table::new(self, name)
+    }
+}
+
+// ─── Snapshot Readers ────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// A `Send + Sync` reader over the committed state as of now: one redb
+    /// read transaction plus a copy of every ZSet. See [`SpookyDbReader`].
+    ///
+    /// Loads and copies all ZSets, O(records); clone the reader to share it
+    /// between workers rather than calling this per task. Buffered
+    /// coalesced writes are not included, and lazy migrations are not
+    /// applied to what it reads.
+    pub fn reader(&self) -> Result<SpookyDbReader, SpookyDbError> {
+        self.load_tables()?;
+        let zsets = self
+            .table_names()
+            .filter_map(|t| Some((t.clone(), self.get_table_zset(t)?.clone())))
+            .collect();
+        let txn = self.db.begin_read()?;
+        let compressor = self.compression.compressor().cloned();
+        Ok(SpookyDbReader::new(txn, zsets, compressor, self.commit_tick))
+    }
+}
+
+// ─── Materialized Views ──────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Register `view`, replacing any view of the same name, and compute it
+    /// from a full scan of its table. From then on every committed write —
+    /// `apply_mutation`, `apply_batch`, `bulk_load` and everything built on
+    /// them — updates it from its deltas.
+    ///
+    /// Views are in-memory only — re-register after reopening.
+    /// `truncate_table`, `drop_table` and `rename_table` update them too;
+    /// `migrate` and `restore` do not, so call `refresh_view` after them.
+    ///
+    /// A `persisted` view whose table already holds records is loaded from
+    /// it instead. Those records are as of the view's last update, so writes
+    /// made while it was not registered are missing until `refresh_view`:
+    /// re-register persisted views right after opening.
+    pub fn register_view(&mut self, mut view: View) -> Result<(), SpookyDbError> {
+        validate_table_name(view.table())?;
+        self.flush()?;
+        let name = SmolStr::new(view.name());
+        if view.restore(self)? {
+            self.views.insert(name, view);
+            return Ok(());
+        }
+        view.rebuild(self)?;
+        self.views.insert(name.clone(), view);
+        self.store_view(&name, true)
+    }
+
+    /// A registered view.
+    pub fn view(&self, name: &str) -> Option<&View> {
+        self.views.get(name)
+    }
+
+    /// Names of the registered views, in no particular order.
+    pub fn view_names(&self) -> impl Iterator<Item = &SmolStr> {
+        self.views.keys()
+    }
+
+    /// Unregister a view, returning it with its last state.
+    pub fn drop_view(&mut self, name: &str) -> Option<View> {
+        self.views.remove(name)
+    }
+
+    /// Recompute a view from a full scan of its table, e.g. after it became
+    /// stale (`View::is_stale`) or after a table-level operation. Returns
+    /// `false` if no view has that name.
+    pub fn refresh_view(&mut self, name: &str) -> Result<bool, SpookyDbError> {
+        let Some(mut view) = self.views.remove(name) else {
+            return Ok(false);
+        };
+        let rebuilt = view.rebuild(self);
+        self.views.insert(SmolStr::new(name), view);
+        rebuilt?;
+        self.store_view(name, true)?;
+        Ok(true)
+    }
+
+    /// Feed the current ZSet of every table `circuit` reads to it as one
+    /// delta, so later `step`s start from the present state. Returns the
+    /// sinks' changes, i.e. their initial contents.
+    pub fn seed_circuit(
+        &self,
+        circuit: &mut Circuit,
+    ) -> Result<FastMap<SmolStr, ZSet>, SpookyDbError> {
+        let mut tables: FastMap<SmolStr, ZSet> = FastMap::default();
+        for table in circuit.tables() {
+            validate_table_name(table)?;
+            let zset = self.zsets.get(&self.db, table)?.cloned().unwrap_or_default();
+            tables.insert(SmolStr::new(table), zset);
+        }
+        Ok(circuit.step_deltas(&tables))
+    }
+
+    /// Feed committed `results` to every registered view. A read failure
+    /// marks the view stale rather than failing the write.
+    fn maintain_views(&mut self, results: &[&BatchMutationResult]) {
+        if self.views.is_empty() || results.is_empty() {
+            return;
+        }
+        let mut views = std::mem::take(&mut self.views);
+        for view in views.values_mut() {
+            view.apply(self, results);
+        }
+        let persisted: Vec<SmolStr> = views
+            .values()
+            .filter(|view| view.storage_table().is_some())
+            .map(|view| SmolStr::new(view.name()))
+            .collect();
+        self.views = views;
+        for name in persisted {
+            if self.store_view(&name, false).is_err()
+                && let Some(view) = self.views.get_mut(&name)
+            {
+                view.mark_stale();
+            }
+        }
+    }
+
+    /// Write a persisted view's last change — or all of it if `full` — to
+    /// its table, in one transaction that reuses the current commit tick and
+    /// skips the oplog and delta log (see `commit_batches`).
+    fn store_view(&mut self, name: &str, full: bool) -> Result<(), SpookyDbError> {
+        let Some(view) = self.views.get(name) else {
+            return Ok(());
+        };
+        let Some(table) = view.storage_table() else {
+            return Ok(());
+        };
+        let stored = match full {
+            true => self.get_table_zset(&table),
+            false => None,
+        };
+        let writes = view.storage_writes(stored, full);
+        if writes.is_empty() {
+            return Ok(());
+        }
+        self.storing_views = true;
+        let stored = self.commit_batches(vec![writes]);
+        self.storing_views = false;
+        stored?.remove(0).map(|_| ())
+    }
+}
+
+// ─── Schema Enforcement ──────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Enforce `schema` on every Create/Update/bulk_load write to `table`.
+    ///
+    /// Validation runs before the write transaction opens; a violating record
+    /// fails the whole call (or batch) with `SpookyDbError::SchemaViolation`.
+    /// Existing records are not re-checked. Replaces any previous schema.
+    ///
+    /// Also builds the schema's `PerfectIndex` (see `perfect_index`), which
+    /// validation reads records through.
+    pub fn set_schema(&mut self, table: &str, schema: Schema) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        let index = Arc::new(schema.perfect_index());
+        self.schemas.insert(SmolStr::new(table), (schema, index));
+        Ok(())
+    }
+
+    /// Stop enforcing a schema on `table`. Returns the removed schema.
+    pub fn clear_schema(&mut self, table: &str) -> Option<Schema> {
+        self.schemas.remove(table).map(|(schema, _)| schema)
+    }
+
+    /// Schema currently enforced on `table`, if any.
+    pub fn schema(&self, table: &str) -> Option<&Schema> {
+        self.schemas.get(table).map(|(schema, _)| schema)
+    }
+
+    /// Slot lookup over the fields of `table`'s schema, built by
+    /// `set_schema`. Wrap a record of the table in an `IndexedRecord` (or
+    /// pass it to `SpookyRecordMut::with_index`) to read fields without a
+    /// binary search.
+    pub fn perfect_index(&self, table: &str) -> Option<&Arc<PerfectIndex>> {
+        self.schemas.get(table).map(|(_, index)| index)
+    }
+
+    /// No-op unless `table` has a schema and `data` is present.
+    fn check_schema(
+        &self,
+        table: &str,
+        id: &str,
+        data: Option<&[u8]>,
+    ) -> Result<(), SpookyDbError> {
+        let (Some((schema, index)), Some(bytes)) = (self.schemas.get(table), data) else {
+            return Ok(());
+        };
+        let (buf, count) = from_bytes(bytes)?;
+        schema
+            .validate_record(&IndexedRecord::new(SpookyRecord::new(buf, count), index))
+            .map_err(|source| SpookyDbError::SchemaViolation {
+                table: SmolStr::new(table),
+                id: SmolStr::new(id),
+                source,
+            })
+    }
+}
+
+// ─── Migrations ──────────────────────────────────────────────────────────────
+
+/// Records rewritten per write transaction by `migrate`.
+const MIGRATION_BATCH: usize = 1_000;
+
+impl SpookyDb {
+    /// Schema version `table` has been migrated to; 0 if it never was.
+    pub fn schema_version(&self, table: &str) -> Result<u32, SpookyDbError> {
+        validate_table_name(table)?;
+        Ok(self.schema_versions.get(table).copied().unwrap_or(0))
+    }
+
+    /// Apply `migrations` whose version is above their table's schema
+    /// version, in (table, version) order. Returns how many were applied.
+    ///
+    /// Each migration rewrites the table in transactions of up to 1 000
+    /// records, as Updates, calling `on_progress` after each one. The new
+    /// version is persisted once the last batch has committed. If a step
+    /// fails with `SpookyDbError::MigrationFailed`, the committed batches
+    /// stay and the version does not move; since steps skip records they
+    /// already hold for, running the migration again picks up where it
+    /// stopped.
+    pub fn migrate(
+        &mut self,
+        migrations: &[Migration],
+        mut on_progress: impl FnMut(&MigrationProgress),
+    ) -> Result<usize, SpookyDbError> {
+        self.flush()?;
+        let mut pending: Vec<&Migration> = migrations.iter().collect();
+        pending.sort_by(|a, b| (&a.table, a.version).cmp(&(&b.table, b.version)));
+        let mut applied = 0;
+        for migration in pending {
+            let previous = self.schema_version(&migration.table)?;
+            if migration.version <= previous {
+                continue;
+            }
+            // Rewritten records are tagged with the version they now hold.
+            let table = migration.table.clone();
+            self.schema_versions.insert(table.clone(), migration.version);
+            if let Err(e) = self.rewrite_table(migration, &mut on_progress) {
+                match previous {
+                    0 => self.schema_versions.remove(&table),
+                    _ => self.schema_versions.insert(table, previous),
+                };
+                return Err(e);
+            }
+            self.persist_schema_version(&table, migration.version)?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// Upgrade records of the migrations' tables as they are read instead
+    /// of rewriting them. Returns how many migrations are newer than their
+    /// table's schema version, which moves to the newest one at once.
+    ///
+    /// A stored record carries the schema version it was written at, so a
+    /// read applies only the registered migrations above it. The upgraded
+    /// bytes are what the read returns and what the row cache holds; `flush`
+    /// (and so the next write, or `sync`) writes them back. Registrations
+    /// are in memory only and replace earlier ones for the same (table,
+    /// version): pass the same list on every open, or set
+    /// `SpookyDbConfig::lazy_migrations`.
+    pub fn migrate_lazy(&mut self, migrations: &[Migration]) -> Result<usize, SpookyDbError> {
+        self.flush()?;
+        let mut newer = 0;
+        let mut tables: Vec<SmolStr> = Vec::new();
+        for migration in migrations {
+            let current = self.schema_version(&migration.table)?;
+            newer += usize::from(migration.version > current);
+            let registered = self.lazy.entry(migration.table.clone()).or_default();
+            registered.retain(|m| m.version != migration.version);
+            registered.push(migration.clone());
+            if !tables.contains(&migration.table) {
+                tables.push(migration.table.clone());
+            }
+        }
+        for table in tables {
+            let registered = self.lazy.get_mut(&table).expect("registered above");
+            registered.sort_by_key(|m| m.version);
+            let newest = registered.last().map_or(0, |m| m.version);
+            if newest > self.schema_version(&table)? {
+                self.persist_schema_version(&table, newest)?;
+            }
+            // Cached rows were filled before the registration.
+            let cached: Vec<_> = self
+                .row_cache
+                .iter()
+                .filter(|((t, _), _)| *t == table)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in cached {
+                self.row_cache.pop(&key);
+            }
+        }
+        Ok(newer)
+    }
+
+    /// One eager migration of `migrate`, batch by batch.
+    fn rewrite_table(
+        &mut self,
+        migration: &Migration,
+        on_progress: &mut impl FnMut(&MigrationProgress),
+    ) -> Result<(), SpookyDbError> {
+        let table = migration.table.as_str();
+        let ids = self.ids_sorted(table)?;
+        let mut progress = MigrationProgress {
+            table: migration.table.clone(),
+            version: migration.version,
+            done: 0,
+            total: ids.len() as u64,
+        };
+        for chunk in ids.chunks(MIGRATION_BATCH) {
+            let keys: Vec<&str> = chunk.iter().map(SmolStr::as_str).collect();
+            let mut batch = Vec::new();
+            for (id, bytes) in chunk.iter().zip(self.get_records_bulk(table, &keys)?) {
+                let Some(bytes) = bytes else { continue };
+                let migrated = migrate::migrate_record(&bytes, &migration.steps).map_err(
+                    |reason| SpookyDbError::MigrationFailed {
+                        table: migration.table.clone(),
+                        id: id.clone(),
+                        version: migration.version,
+                        reason,
+                    },
+                )?;
+                if let Some(data) = migrated {
+                    batch.push(DbMutation {
+                        table: migration.table.clone(),
+                        id: id.clone(),
+                        op: Operation::Update,
+                        data: Some(data),
+                        version: None,
+                        expires_at: None,
+                    });
+                }
+            }
+            if !batch.is_empty() {
+                self.apply_batch(batch)?;
+            }
+            progress.done += chunk.len() as u64;
+            on_progress(&progress);
+        }
+        Ok(())
+    }
+
+    fn persist_schema_version(&mut self, table: &str, version: u32) -> Result<(), SpookyDbError> {
+        let write_txn = self.begin_write()?;
+        write_txn.open_table(SCHEMA_VERSION_TABLE)?.insert(table, version)?;
+        self.commit(write_txn)?;
+        self.schema_versions.insert(SmolStr::new(table), version);
+        Ok(())
+    }
+
+    /// Write back the records `read_form` upgraded, in one transaction.
+    /// Records deleted or rewritten since are skipped.
+    fn write_back_upgrades(&mut self) -> Result<usize, SpookyDbError> {
+        let queued = std::mem::take(
+            self.upgrades
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        if queued.is_empty() {
+            return Ok(0);
+        }
+        for (table, _) in &queued {
+            self.zsets.load(&self.db, table)?;
+        }
+        let write_txn = self.begin_write()?;
+        let mut deltas: FastMap<SmolStr, TableDelta> = FastMap::default();
+        let mut written = 0;
+        let mut stage = self.blob_stage(&write_txn, queued.iter().map(|(t, _)| t.as_str()))?;
+        {
+            let mut records = write_txn.open_table(RECORDS_TABLE)?;
+            for (table, id) in &queued {
+                let key = make_key(table, id)?;
+                let Some(old) = records.get(key.as_str())?.map(|g| g.value().to_vec()) else {
+                    continue;
+                };
+                let (bytes, stale) = self.upgrade(table, id, &old)?;
+                if !stale {
+                    continue;
+                }
+                let stored = self.stored_form(table, &bytes, stage.as_mut())?.into_owned();
+                records.insert(key.as_str(), stored.as_slice())?;
+                self.release_blobs(stage.as_mut(), &old)?;
+                let delta = deltas.entry(table.clone()).or_default();
+                delta.insert(Some(old.len()), stored.len());
+                written += 1;
+            }
+        }
+        let added_blobs = stage.is_some_and(|stage| stage.added);
+        let stats = self.zsets.stage_stats(&write_txn, deltas)?;
+        self.commit(write_txn)?;
+        self.has_blobs |= added_blobs;
+        self.zsets.apply_stats(stats);
+        Ok(written)
+    }
+}
+
+// ─── Unique Constraints ──────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Reject any write that would give `field`'s value in `table` to a
+    /// second id, with `SpookyDbError::UniqueViolation`.
+    ///
+    /// Builds the index with one RECORDS_TABLE scan; fails without installing
+    /// it if existing records already collide. Null and absent values are not
+    /// constrained. In-memory only — re-declare after reopening. Declaring the
+    /// same field twice is a no-op.
+    pub fn add_unique(&mut self, table: &str, field: &str) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        self.flush()?;
+        if self.unique_fields(table).any(|f| f == field) {
+            return Ok(());
+        }
+
+        let mut index = UniqueIndex::new(SmolStr::new(field));
+        let mut failure = None;
+        self.scan_keys(table, Bound::Unbounded, Bound::Unbounded, |id, bytes| {
+            let key = match from_bytes(bytes) {
+                Ok((buf, count)) => index_key(&SpookyRecord::new(buf, count), field),
+                Err(e) => {
+                    failure = Some(e.into());
+                    return false;
+                }
+            };
+            if let Some(key) = key {
+                if let Some(existing) = index.holder(&key) {
+                    failure = Some(SpookyDbError::UniqueViolation {
+                        field: SmolStr::new(field),
+                        value: key_value(&key),
+                        existing_id: existing.clone(),
+                    });
+                    return false;
+                }
+                index.set(&SmolStr::new(id), Some(key));
+            }
+            true
+        })?;
+        if let Some(e) = failure {
+            return Err(e);
+        }
+
+        self.unique
+            .entry(SmolStr::new(table))
+            .or_default()
+            .push(index);
+        Ok(())
+    }
+
+    /// Stop enforcing uniqueness of `field` in `table`. Returns `true` if it was declared.
+    pub fn drop_unique(&mut self, table: &str, field: &str) -> bool {
+        let Some(indexes) = self.unique.get_mut(table) else {
+            return false;
+        };
+        let before = indexes.len();
+        indexes.retain(|index| index.field != field);
+        let removed = indexes.len() != before;
+        if indexes.is_empty() {
+            self.unique.remove(table);
+        }
+        removed
+    }
+
+    /// Fields declared unique on `table`, in declaration order.
+    pub fn unique_fields(&self, table: &str) -> impl Iterator<Item = &str> {
+        self.unique
+            .get(table)
+            .into_iter()
+            .flatten()
+            .map(|index| index.field.as_str())
+    }
+
+    /// Apply index changes staged by `UniqueCheck`. Call only after commit.
+    fn apply_index_updates(&mut self, updates: Vec<IndexUpdate>) {
+        for IndexUpdate {
+            table,
+            slot,
+            id,
+            key,
+        } in updates
+        {
+            if let Some(index) = self.unique.get_mut(&table).and_then(|v| v.get_mut(slot)) {
+                index.set(&id, key);
+            }
+        }
+    }
+}
+
+// ─── Join Indexes ────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Maintain a reverse index of `table`'s string `field`, which holds ids
+    /// of `target` (e.g. `posts.author` → `users`), so `joined_ids` finds
+    /// the records naming a target id without a scan.
+    ///
+    /// Builds the index with one RECORDS_TABLE scan; afterwards every write
+    /// stages its change alongside the unique checks and applies it once the
+    /// transaction commits. In-memory only — re-register after reopening.
+    /// Registering `field` again only changes its target table.
+    pub fn register_join(
+        &mut self,
+        table: &str,
+        field: &str,
+        target: &str,
+    ) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        validate_table_name(target)?;
+        self.flush()?;
+        let mut indexes = self.joins.get_mut(table).into_iter().flatten();
+        if let Some(index) = indexes.find(|index| index.field == field) {
+            index.target = SmolStr::new(target);
+            return Ok(());
+        }
+
+        let mut index = JoinIndex::new(SmolStr::new(field), SmolStr::new(target));
+        let mut failure = None;
+        self.scan_keys(table, Bound::Unbounded, Bound::Unbounded, |id, bytes| {
+            match from_bytes(bytes) {
+                Ok((buf, count)) => {
+                    let target_id = SpookyRecord::new(buf, count).get_str(field).map(SmolStr::new);
+                    index.set(&SmolStr::new(id), target_id);
+                    true
+                }
+                Err(e) => {
+                    failure = Some(e.into());
+                    false
+                }
+            }
+        })?;
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        self.joins.entry(SmolStr::new(table)).or_default().push(index);
+        Ok(())
+    }
+
+    /// Stop maintaining the join index on `table`'s `field`. Returns `true`
+    /// if it was registered.
+    pub fn drop_join(&mut self, table: &str, field: &str) -> bool {
+        let Some(indexes) = self.joins.get_mut(table) else {
+            return false;
+        };
+        let before = indexes.len();
+        indexes.retain(|index| index.field != field);
+        let removed = indexes.len() != before;
+        if indexes.is_empty() {
+            self.joins.remove(table);
+        }
+        removed
+    }
+
+    /// Ids of `table` whose joined `field` names `target_id`, in no
+    /// particular order. Empty if no join is registered on `field`.
+    pub fn joined_ids(
+        &self,
+        table: &str,
+        field: &str,
+        target_id: &str,
+    ) -> impl Iterator<Item = &SmolStr> {
+        self.joins
+            .get(table)
+            .into_iter()
+            .flatten()
+            .filter(move |index| index.field == field)
+            .flat_map(move |index| index.referrers(target_id))
+    }
+
+    /// Joined fields of `table` with their target tables, in registration
+    /// order.
+    pub fn joins(&self, table: &str) -> impl Iterator<Item = (&str, &str)> {
+        self.joins
+            .get(table)
+            .into_iter()
+            .flatten()
+            .map(|index| (index.field.as_str(), index.target.as_str()))
+    }
+
+    /// Join index changes for `(table, id, delete, data)` writes in order.
+    /// `data: None` on a non-delete leaves the record — and its join — as is.
+    fn stage_joins<'a>(
+        &self,
+        writes: impl IntoIterator<Item = (&'a str, &'a str, bool, Option<&'a [u8]>)>,
+    ) -> Result<Vec<JoinUpdate>, SpookyDbError> {
+        let mut staged = Vec::new();
+        if self.joins.is_empty() {
+            return Ok(staged);
+        }
+        for (table, id, delete, data) in writes {
+            let Some(indexes) = self.joins.get(table) else {
+                continue;
+            };
+            let record = match (delete, data) {
+                (true, _) => None,
+                (false, Some(bytes)) => {
+                    let (buf, count) = from_bytes(bytes)?;
+                    Some(SpookyRecord::new(buf, count))
+                }
+                (false, None) => continue,
+            };
+            for (slot, index) in indexes.iter().enumerate() {
+                let target_id = record.as_ref().and_then(|r| r.get_str(&index.field));
+                let target_id = target_id.map(SmolStr::new);
+                staged.push(JoinUpdate {
+                    table: SmolStr::new(table),
+                    slot,
+                    id: SmolStr::new(id),
+                    target_id,
+                });
+            }
+        }
+        Ok(staged)
+    }
+
+    /// Apply changes staged by `stage_joins`. Call only after commit.
+    fn apply_joins(&mut self, staged: Vec<JoinUpdate>) {
+        for JoinUpdate {
+            table,
+            slot,
+            id,
+            target_id,
+        } in staged
+        {
+            if let Some(index) = self.joins.get_mut(&table).and_then(|v| v.get_mut(slot)) {
+                index.set(&id, target_id);
+            }
+        }
+    }
+}
+
+// ─── Change Feed ─────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Receive a `ChangeEvent` for every committed write to `table`.
+    ///
+    /// Events are sent after the write transaction commits and in-memory
+    /// state is updated — one per Create/Update/bulk_load record and one per
+    /// Delete of a present record, in apply order. `data` is always `None`;
+    /// use `subscribe_with_data` to receive record bytes. Dropping the
+    /// receiver unsubscribes. The channel is unbounded: a receiver that is
+    /// never drained grows without limit.
+    pub fn subscribe(&mut self, table: &str) -> Result<Receiver<ChangeEvent>, SpookyDbError> {
+        self.add_subscriber(table, false)
+    }
+
+    /// Like `subscribe`, but Create/Update events carry a copy of the written bytes.
+    pub fn subscribe_with_data(
+        &mut self,
+        table: &str,
+    ) -> Result<Receiver<ChangeEvent>, SpookyDbError> {
+        self.add_subscriber(table, true)
+    }
+
+    fn add_subscriber(
+        &mut self,
+        table: &str,
+        with_data: bool,
+    ) -> Result<Receiver<ChangeEvent>, SpookyDbError> {
+        validate_table_name(table)?;
+        let (tx, rx) = mpsc::channel();
+        self.subscribers
+            .entry(SmolStr::new(table))
+            .or_default()
+            .push(Subscriber { tx, with_data });
+        Ok(rx)
+    }
+
+    /// Receive a `RecordChange` whenever the record `id` of `table` is
+    /// created, updated or deleted — a table subscription narrowed to one
+    /// record, for detail views.
+    ///
+    /// Delivered on the same path and under the same rules as `subscribe`
+    /// events; Create/Update changes always carry the written bytes. The
+    /// record need not exist yet. Dropping the receiver unwatches it.
+    pub fn watch(
+        &mut self,
+        table: &str,
+        id: &str,
+    ) -> Result<Receiver<RecordChange>, SpookyDbError> {
+        validate_table_name(table)?;
+        let (tx, rx) = mpsc::channel();
+        self.watchers
+            .entry(SmolStr::new(table))
+            .or_default()
+            .entry(SmolStr::new(id))
+            .or_default()
+            .push(tx);
+        Ok(rx)
+    }
+
+    /// Receive the deltas of every committed write transaction — one
+    /// `DeltaBatch` per `apply_mutation`, `apply_batch`/`apply_batches`
+    /// call, coalesced flush or `bulk_load` — for incremental compute
+    /// outside the database.
+    ///
+    /// The channel holds at most `capacity` batches. When it is full, the
+    /// next commit blocks after writing until the consumer takes one, so a
+    /// slow consumer slows writers down instead of growing a buffer. Drain
+    /// it on another thread: a receiver read only by the writing thread
+    /// deadlocks once full. Dropping the receiver ends the stream.
+    pub fn delta_stream(&mut self, capacity: usize) -> Receiver<DeltaBatch> {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        self.delta_streams.push(tx);
+        rx
+    }
+
+    /// Hand committed `results` — everything one write transaction did — to
+    /// views and delta streams. Runs after the in-memory state is updated,
+    /// so both see what was just written.
+    fn committed(&mut self, results: &[&BatchMutationResult]) {
+        if self.storing_views {
+            return;
+        }
+        self.maintain_views(results);
+        if self.delta_streams.is_empty() || results.iter().all(|r| r.changed_tables.is_empty()) {
+            return;
+        }
+        // Every result of one transaction carries its tick.
+        let mut batch = DeltaBatch {
+            tick: results.first().map_or(self.commit_tick, |result| result.tick),
+            membership_deltas: FastMap::default(),
+            content_updates: FastMap::default(),
+        };
+        for result in results {
+            for (table, delta) in &result.membership_deltas {
+                zset::union_into(batch.membership_deltas.entry(table.clone()).or_default(), delta);
+            }
+            for (table, ids) in &result.content_updates {
+                let updates = batch.content_updates.entry(table.clone()).or_default();
+                updates.extend(ids.iter().cloned());
+            }
+        }
+        self.delta_streams.retain(|tx| tx.send(batch.clone()).is_ok());
+    }
+
+    /// `committed` for one write that changed `id`'s weight by `weight`.
+    fn committed_one(&mut self, table: &str, id: &str, weight: i64) {
+        if self.views.is_empty() && self.delta_streams.is_empty() {
+            return;
+        }
+        let (table, id) = (SmolStr::new(table), SmolStr::new(id));
+        let mut result = BatchMutationResult {
+            tick: self.commit_tick,
+            membership_deltas: FastMap::default(),
+            content_updates: FastMap::default(),
+            changed_tables: vec![table.clone()],
+            versions: FastMap::default(),
+        };
+        if weight != 0 {
+            let delta = result.membership_deltas.entry(table.clone()).or_default();
+            delta.insert(id.clone(), weight);
+        }
+        if weight >= 0 {
+            result.content_updates.entry(table).or_default().insert(id);
+        }
+        self.committed(&[&result]);
+    }
+
+    /// Fan one committed change out to `table`'s subscribers and the
+    /// record's watchers. No-op (two hash lookups) when nobody listens.
+    fn notify(
+        &mut self,
+        table: &str,
+        id: &str,
+        op: Operation,
+        version: Option<u64>,
+        data: Option<&[u8]>,
+    ) {
+        self.notify_watchers(table, id, op, version, data);
+        let Some(subs) = self.subscribers.get_mut(table) else {
+            return;
+        };
+        subs.retain(|sub| {
+            let event = ChangeEvent {
+                table: SmolStr::new(table),
+                id: SmolStr::new(id),
+                op,
+                version,
+                data: data.filter(|_| sub.with_data).map(<[u8]>::to_vec),
+            };
+            sub.tx.send(event).is_ok()
+        });
+        if subs.is_empty() {
+            self.subscribers.remove(table);
+        }
+    }
+
+    fn notify_watchers(
+        &mut self,
+        table: &str,
+        id: &str,
+        op: Operation,
+        version: Option<u64>,
+        data: Option<&[u8]>,
+    ) {
+        let Some(records) = self.watchers.get_mut(table) else {
+            return;
+        };
+        let Some(senders) = records.get_mut(id) else {
+            return;
+        };
+        senders.retain(|tx| {
+            let change = RecordChange {
+                op,
+                version,
+                data: data.map(<[u8]>::to_vec),
+            };
+            tx.send(change).is_ok()
+        });
+        if senders.is_empty() {
+            records.remove(id);
+            if records.is_empty() {
+                self.watchers.remove(table);
+            }
+        }
+    }
+}
+
+// ─── Soft Delete ─────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Make Deletes on `table` leave a tombstone carrying `deleted_at`.
+    ///
+    /// The record bytes, version and ZSet entry are removed exactly as for a
+    /// hard delete — the record is absent to every read and view — but a
+    /// TOMBSTONE_TABLE entry survives so sync peers can learn of the deletion
+    /// after reconnecting. A later Create/Update of the id clears it. The
+    /// option is in-memory only (re-enable after reopening); tombstones are
+    /// persistent. Disabling leaves existing tombstones in place.
+    pub fn set_soft_delete(&mut self, table: &str, enabled: bool) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        if enabled {
+            self.soft_delete.insert(SmolStr::new(table));
+        } else {
+            self.soft_delete.remove(table);
+        }
+        Ok(())
+    }
+
+    /// Whether Deletes on `table` currently write tombstones.
+    pub fn is_soft_delete(&self, table: &str) -> bool {
+        self.soft_delete.contains(table)
+    }
+
+    /// `deleted_at` (ms since UNIX epoch) of `id`'s tombstone, if any. Pure memory.
+    pub fn tombstone(&self, table: &str, id: &str) -> Option<u64> {
+        self.tombstones.get(table)?.get(id).copied()
+    }
+
+    /// Every tombstone in `table` as `(id, deleted_at)`, sorted by id. Pure memory.
+    pub fn tombstones(&self, table: &str) -> Vec<(SmolStr, u64)> {
+        let mut out: Vec<_> = self
+            .tombstones
+            .get(table)
+            .into_iter()
+            .flatten()
+            .map(|(id, &at)| (id.clone(), at))
+            .collect();
+        out.sort_unstable();
+        out
+    }
+
+    /// Drop every tombstone (in every table) with `deleted_at < older_than`,
+    /// in one write transaction. Returns the number removed.
+    pub fn purge_tombstones(&mut self, older_than: u64) -> Result<usize, SpookyDbError> {
+        self.flush()?;
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(TOMBSTONE_TABLE)?;
+            table.retain(|_, deleted_at| deleted_at >= older_than)?;
+        }
+        self.commit(write_txn)?;
+
+        let mut removed = 0;
+        self.tombstones.retain(|_, ids| {
+            let before = ids.len();
+            ids.retain(|_, at| *at >= older_than);
+            removed += before - ids.len();
+            !ids.is_empty()
+        });
+        Ok(removed)
+    }
+
+    /// Write tombstone changes for `ops` (`(table, id, is_delete)`) inside
+    /// `txn`. Returns the in-memory changes for `apply_tombstones` after commit.
+    /// Free when no table is soft-delete and no tombstone exists.
+    fn stage_tombstones<'a>(
+        &self,
+        txn: &redb::WriteTransaction,
+        ops: impl IntoIterator<Item = (&'a str, &'a str, bool)>,
+    ) -> Result<FastMap<(SmolStr, SmolStr), Option<u64>>, SpookyDbError> {
+        let mut staged: FastMap<(SmolStr, SmolStr), Option<u64>> = FastMap::default();
+        if self.soft_delete.is_empty() && self.tombstones.is_empty() {
+            return Ok(staged);
+        }
+        let now = now_millis();
+        let mut table = txn.open_table(TOMBSTONE_TABLE)?;
+        for (t, id, delete) in ops {
+            let key = make_key(t, id)?;
+            let slot = (SmolStr::new(t), SmolStr::new(id));
+            if delete {
+                if self.soft_delete.contains(t) {
+                    table.insert(key.as_str(), now)?;
+                    staged.insert(slot, Some(now));
+                }
+            } else {
+                let exists = match staged.get(&slot) {
+                    Some(at) => at.is_some(),
+                    None => self.tombstone(t, id).is_some(),
+                };
+                if exists {
+                    table.remove(key.as_str())?;
+                    staged.insert(slot, None);
+                }
+            }
+        }
+        Ok(staged)
+    }
+
+    fn apply_tombstones(&mut self, staged: FastMap<(SmolStr, SmolStr), Option<u64>>) {
+        for ((table, id), at) in staged {
+            match at {
+                Some(at) => {
+                    self.tombstones.entry(table).or_default().insert(id, at);
+                }
+                None => {
+                    if let Some(ids) = self.tombstones.get_mut(&table) {
+                        ids.remove(&id);
+                        if ids.is_empty() {
+                            self.tombstones.remove(&table);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ─── Timestamps ──────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Keep `created_at` / `updated_at` for every record of `table` in
+    /// TIMES_TABLE, for last-modified ordering without a schema field. Each
+    /// Create/Update/bulk_load write sets `updated_at` to the wall clock and
+    /// `created_at` on the first write seen; a Delete clears both.
+    ///
+    /// In-memory option — re-enable after reopening; writes while it is off
+    /// leave the timestamps as they were. Disabling keeps existing entries.
+    pub fn set_timestamps(&mut self, table: &str, enabled: bool) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        if enabled {
+            self.timestamped.insert(SmolStr::new(table));
+            self.has_times = true;
+        } else {
+            self.timestamped.remove(table);
+        }
+        Ok(())
+    }
+
+    /// Whether writes to `table` currently maintain timestamps.
+    pub fn has_timestamps(&self, table: &str) -> bool {
+        self.timestamped.contains(table)
+    }
+
+    /// Timestamps of a present record, or `None` if it is absent or was
+    /// never written with timestamps on.
+    pub fn get_record_meta(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<RecordMeta>, SpookyDbError> {
+        validate_table_name(table)?;
+        if self.get_zset_weight(table, id) <= 0 {
+            return Ok(None);
+        }
+        read_record_meta(&self.db, table, id)
+    }
+
+    /// Write timestamp changes for `ops` (`(table, id, is_delete)`) inside
+    /// `txn`. Free when no table has had timestamps.
+    fn stage_times<'a>(
+        &self,
+        txn: &redb::WriteTransaction,
+        ops: impl IntoIterator<Item = (&'a str, &'a str, bool)>,
+    ) -> Result<(), SpookyDbError> {
+        if !self.has_times {
+            return Ok(());
+        }
+        let now = now_millis();
+        let mut times = txn.open_table(TIMES_TABLE)?;
+        for (table, id, delete) in ops {
+            let key = make_key(table, id)?;
+            if delete {
+                times.remove(key.as_str())?;
+            } else if self.timestamped.contains(table) {
+                let previous = times.get(key.as_str())?.map(|guard| guard.value());
+                let created = previous.map_or(now, |(created, _)| created);
+                times.insert(key.as_str(), (created, now))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// TIMES_TABLE entry for `table:id`.
+pub(super) fn read_record_meta(
+    db: &RedbDatabase,
+    table: &str,
+    id: &str,
+) -> Result<Option<RecordMeta>, SpookyDbError> {
+    let key = make_key(table, id)?;
+    let read_txn = db.begin_read()?;
+    let times = read_txn.open_table(TIMES_TABLE)?;
+    Ok(times.get(key.as_str())?.map(|guard| {
+        let (created_at, updated_at) = guard.value();
+        RecordMeta {
+            created_at,
+            updated_at,
+        }
+    }))
+}
+
+// ─── Expiry (TTL) ────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Delete every record whose expiry is `<= now`, in one write transaction.
+    ///
+    /// `now` must use the same clock as the `expires_at` values written.
+    /// Runs through `apply_batch`, so tombstones, the oplog, unique indexes
+    /// and subscribers all see ordinary Deletes. Returns the batch result —
+    /// `membership_deltas` holds a `-1` per swept record. Expired records stay
+    /// readable until swept.
+    pub fn sweep_expired(&mut self, now: u64) -> Result<BatchMutationResult, SpookyDbError> {
+        let mutations: Vec<DbMutation> = self
+            .expiry
+            .iter()
+            .take_while(|(at, _, _)| *at <= now)
+            .map(|(_, table, id)| DbMutation {
+                table: table.clone(),
+                id: id.clone(),
+                op: Operation::Delete,
+                data: None,
+                version: None,
+                expires_at: None,
+            })
+            .collect();
+        if mutations.is_empty() {
+            return Ok(BatchMutationResult {
+                tick: self.commit_tick,
+                membership_deltas: FastMap::default(),
+                content_updates: FastMap::default(),
+                changed_tables: Vec::new(),
+                versions: FastMap::default(),
+            });
+        }
+        self.apply_batch(mutations)
+    }
+
+    /// Set (`Some`) or clear (`None`) the expiry of a present record.
+    /// Returns `false` without writing if the record is absent.
+    pub fn set_expiry(
+        &mut self,
+        table: &str,
+        id: &str,
+        expires_at: Option<u64>,
+    ) -> Result<bool, SpookyDbError> {
+        validate_table_name(table)?;
+        self.flush()?;
+        if self.get_zset_weight(table, id) <= 0 {
+            return Ok(false);
+        }
+        let key = make_key(table, id)?;
+        let write_txn = self.begin_write()?;
+        {
+            let mut ttl = write_txn.open_table(TTL_TABLE)?;
+            match expires_at {
+                Some(at) => ttl.insert(key.as_str(), at)?,
+                None => ttl.remove(key.as_str())?,
+            };
+        }
+        self.commit(write_txn)?;
+        self.set_expiry_memory(SmolStr::new(table), SmolStr::new(id), expires_at);
+        Ok(true)
+    }
+
+    /// Expiry of a record, if one is set. Pure memory.
+    pub fn expires_at(&self, table: &str, id: &str) -> Option<u64> {
+        self.expires
+            .get(&(SmolStr::new(table), SmolStr::new(id)))
+            .copied()
+    }
+
+    /// Write expiry changes for `ops` (`(table, id, is_delete, expires_at)`)
+    /// inside `txn`. Deletes clear the expiry; `Some` on a write sets it.
+    /// Returns the in-memory changes for `apply_expiry` after commit.
+    fn stage_expiry<'a>(
+        &self,
+        txn: &redb::WriteTransaction,
+        ops: impl IntoIterator<Item = (&'a str, &'a str, bool, Option<u64>)>,
+    ) -> Result<FastMap<(SmolStr, SmolStr), Option<u64>>, SpookyDbError> {
+        let mut staged: FastMap<(SmolStr, SmolStr), Option<u64>> = FastMap::default();
+        let mut ttl = None;
+        for (table, id, delete, expires_at) in ops {
+            let slot = (SmolStr::new(table), SmolStr::new(id));
+            let change = if delete {
+                let has_expiry = match staged.get(&slot) {
+                    Some(at) => at.is_some(),
+                    None => self.expires.contains_key(&slot),
+                };
+                if !has_expiry {
+                    continue;
+                }
+                None
+            } else if let Some(at) = expires_at {
+                Some(at)
+            } else {
+                continue;
+            };
+            let ttl = match &mut ttl {
+                Some(t) => t,
+                None => ttl.insert(txn.open_table(TTL_TABLE)?),
+            };
+            let key = make_key(table, id)?;
+            match change {
+                Some(at) => ttl.insert(key.as_str(), at)?,
+                None => ttl.remove(key.as_str())?,
+            };
+            staged.insert(slot, change);
+        }
+        Ok(staged)
+    }
+
+    fn apply_expiry(&mut self, staged: FastMap<(SmolStr, SmolStr), Option<u64>>) {
+        for ((table, id), at) in staged {
+            self.set_expiry_memory(table, id, at);
+        }
+    }
+
+    /// Keep `expiry` and `expires` in step.
+    fn set_expiry_memory(&mut self, table: SmolStr, id: SmolStr, at: Option<u64>) {
+        let slot = (table, id);
+        if let Some(old) = self.expires.remove(&slot) {
+            self.expiry.remove(&(old, slot.0.clone(), slot.1.clone()));
+        }
+        if let Some(at) = at {
+            self.expiry.insert((at, slot.0.clone(), slot.1.clone()));
+            self.expires.insert(slot, at);
+        }
+    }
+}
+
+// ─── Operation Log ───────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Every OPLOG_TABLE entry with `seq > since_seq`, in commit order.
+    ///
+    /// Pass `0` for the whole log, or the last `seq` a consumer has applied
+    /// to resume. Entries are only recorded while `SpookyDbConfig::oplog` is
+    /// not `Off`; gaps never occur within a run, but `trim_oplog` removes
+    /// the head.
+    pub fn read_oplog(&self, since_seq: u64) -> Result<Vec<OplogEntry>, SpookyDbError> {
+        let read_txn = self.db.begin_read()?;
+        let oplog = read_txn.open_table(OPLOG_TABLE)?;
+        let mut entries = Vec::new();
+        for entry in oplog.range::<u64>((Bound::Excluded(since_seq), Bound::Unbounded))? {
+            let (seq, bytes) = entry?;
+            entries.push(oplog::decode(seq.value(), bytes.value())?);
+        }
+        Ok(entries)
+    }
+
+    /// Feed every entry with `seq > since_seq` to `apply(table, id, op,
+    /// bytes)` in commit order, streamed from one read snapshot, and return
+    /// the last `seq` applied (`since_seq` if there was none). A consumer —
+    /// search indexer, cache, replica — stores that and passes it back to
+    /// catch up after downtime without diffing tables.
+    ///
+    /// `bytes` are the written record for Create/Update under
+    /// `OplogMode::Full`, `None` otherwise. If entries after `since_seq`
+    /// were trimmed, fails with `SpookyDbError::OplogTrimmed` before calling
+    /// `apply`: the consumer has to resync from the tables.
+    pub fn replay(
+        &self,
+        since_seq: u64,
+        mut apply: impl FnMut(&str, &str, Operation, Option<&[u8]>),
+    ) -> Result<u64, SpookyDbError> {
+        let read_txn = self.db.begin_read()?;
+        let oplog = read_txn.open_table(OPLOG_TABLE)?;
+        let mut entries = oplog
+            .range::<u64>((Bound::Excluded(since_seq), Bound::Unbounded))?
+            .peekable();
+        let oldest = match entries.peek() {
+            Some(Ok((seq, _))) => seq.value(),
+            // Surfaced by the loop below.
+            Some(Err(_)) => since_seq + 1,
+            None => self.next_seq,
+        };
+        if oldest > since_seq + 1 {
+            return Err(SpookyDbError::OplogTrimmed {
+                requested: since_seq,
+                oldest,
+            });
+        }
+        let mut last = since_seq;
+        for entry in entries {
+            let (seq, bytes) = entry?;
+            let e = oplog::decode(seq.value(), bytes.value())?;
+            apply(&e.table, &e.id, e.op, e.data.as_deref());
+            last = e.seq;
+        }
+        Ok(last)
+    }
+
+    /// Sequence number of the most recent entry, or `0` if none was ever written.
+    pub fn oplog_last_seq(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    /// Delete every entry with `seq <= through_seq` once all consumers have
+    /// applied them. Returns the number removed. Sequence numbers are never
+    /// reused, even after trimming the whole log.
+    pub fn trim_oplog(&mut self, through_seq: u64) -> Result<u64, SpookyDbError> {
+        let write_txn = self.begin_write()?;
+        let removed = {
+            let mut oplog = write_txn.open_table(OPLOG_TABLE)?;
+            let before = oplog.len()?;
+            oplog.retain_in(..=through_seq, |_, _| false)?;
+            before - oplog.len()?
+        };
+        self.commit(write_txn)?;
+        Ok(removed)
+    }
+
+    /// Append `ops` to OPLOG_TABLE inside `txn` according to `oplog_mode`.
+    /// Returns the sequence number to store in `next_seq` after commit.
+    fn log_ops<'a>(
+        &self,
+        txn: &redb::WriteTransaction,
+        ops: impl IntoIterator<Item = (&'a str, &'a str, Operation, Option<u64>, Option<&'a [u8]>)>,
+    ) -> Result<u64, SpookyDbError> {
+        let mut seq = self.next_seq;
+        if self.oplog_mode == OplogMode::Off {
+            return Ok(seq);
+        }
+        let mut oplog = txn.open_table(OPLOG_TABLE)?;
+        for (table, id, op, version, data) in ops {
+            let keep_data = self.oplog_mode == OplogMode::Full && !matches!(op, Operation::Delete);
+            let data = data.filter(|_| keep_data);
+            oplog.insert(seq, oplog::encode(table, id, op, version, data).as_slice())?;
+            seq += 1;
+        }
+        Ok(seq)
+    }
+}
+
+// ─── Delta Log ───────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// The net membership deltas of every commit whose tick is in `ticks`,
+    /// in tick order, as recorded with `SpookyDbConfig::delta_log`.
+    ///
+    /// Each entry is what that commit's `BatchMutationResult` carried in
+    /// `membership_deltas`, so a view pipeline — e.g. a `Circuit` fed with
+    /// `step_deltas` — can be re-run from a checkpointed tick after a crash
+    /// without re-diffing tables. Commits that changed no membership, and
+    /// ones made while the log was off, have no entry.
+    pub fn read_deltas(
+        &self,
+        ticks: impl RangeBounds<u64>,
+    ) -> Result<Vec<(u64, TableDeltas)>, SpookyDbError> {
+        let bounds = (ticks.start_bound().cloned(), ticks.end_bound().cloned());
+        let read_txn = self.db.begin_read()?;
+        let log = read_txn.open_table(DELTA_TABLE)?;
+        let mut entries = Vec::new();
+        for entry in log.range::<u64>(bounds)? {
+            let (tick, bytes) = entry?;
+            let tick = tick.value();
+            entries.push((tick, oplog::decode_deltas(tick, bytes.value())?));
+        }
+        Ok(entries)
+    }
+
+    /// Delete every entry with `tick <= through_tick`, e.g. once a checkpoint
+    /// covers them. Returns the number removed.
+    pub fn trim_deltas(&mut self, through_tick: u64) -> Result<u64, SpookyDbError> {
+        let write_txn = self.begin_write()?;
+        let removed = {
+            let mut log = write_txn.open_table(DELTA_TABLE)?;
+            let before = log.len()?;
+            log.retain_in(..=through_tick, |_, _| false)?;
+            before - log.len()?
+        };
+        self.commit(write_txn)?;
+        Ok(removed)
+    }
+
+    /// Record the net membership change of `ops` — `(table, id, delete)` in
+    /// commit order — under `tick`, if `delta_log` is on. Membership before
+    /// the commit comes from the in-memory ZSets.
+    fn log_deltas<'a>(
+        &self,
+        txn: &redb::WriteTransaction,
+        tick: u64,
+        ops: impl IntoIterator<Item = (&'a str, &'a str, bool)>,
+    ) -> Result<(), SpookyDbError> {
+        if !self.delta_log {
+            return Ok(());
+        }
+        // (before, after) weight per touched record.
+        let mut weights: FastMap<(&str, &str), (i64, i64)> = FastMap::default();
+        for (table, id, delete) in ops {
+            let weight = weights
+                .entry((table, id))
+                .or_insert_with(|| (self.get_zset_weight(table, id), 0));
+            weight.1 = if delete { 0 } else { 1 };
+        }
+        let mut deltas = TableDeltas::default();
+        for ((table, id), (before, after)) in weights {
+            if before != after {
+                let delta = deltas.entry(SmolStr::new(table)).or_default();
+                delta.insert(SmolStr::new(id), after - before);
+            }
+        }
+        if !deltas.is_empty() {
+            let bytes = oplog::encode_deltas(&deltas);
+            txn.open_table(DELTA_TABLE)?.insert(tick, bytes.as_slice())?;
+        }
+        Ok(())
+    }
+}
+
+// ─── JSON Lines ──────────────────────────────────────────────────────────────
+
+/// Key that carries the record id in each JSON Lines object and each
+/// `bulk_load_cbor_stream` map.
+const JSONL_ID: &str = "id";
+
+/// Records per `bulk_load` transaction in `import_jsonl` and `restore`.
+const IMPORT_CHUNK: usize = 4096;
+
+impl SpookyDb {
+    /// Write every record of `table` to `writer` as one JSON object per line,
+    /// in id order, and return how many were written. For tooling that does
+    /// not speak the binary format.
+    ///
+    /// Field names are not stored in records, so they come from `fields`, or
+    /// from the table's schema when `fields` is `None`. Absent fields are
+    /// left out. The record id is written under `"id"`, replacing any field
+    /// of that name. Out-of-line fields are written inline. Reads one
+    /// RECORDS_TABLE snapshot and streams it: memory stays O(one record).
+    pub fn export_jsonl(
+        &self,
+        table: &str,
+        fields: Option<&[&str]>,
+        mut writer: impl Write,
+    ) -> Result<usize, SpookyDbError> {
+        let names: Vec<SmolStr> = match fields {
+            Some(fields) => fields.iter().map(SmolStr::new).collect(),
+            None => match self.schemas.get(table) {
+                Some((schema, _)) => schema.fields().map(|(name, _)| name.clone()).collect(),
+                None => {
+                    return Err(SpookyDbError::Serialization(format!(
+                        "export_jsonl: no field list given and no schema set on {table:?}"
+                    )));
+                }
+            },
+        };
+        let mut written = 0;
+        let mut failure = None;
+        let mut line = String::new();
+        let blob_txn = self.db.begin_read()?;
+        let blob_table = blob_txn.open_table(BLOBS_TABLE)?;
+        self.scan_keys(table, Bound::Unbounded, Bound::Unbounded, |id, bytes| {
+            let bytes = match blobs::inline(bytes, |hash| {
+                Ok(blob_table.get(hash)?.map(|blob| blob.value().to_vec()))
+            }) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    failure = Some(e);
+                    return false;
+                }
+            };
+            let (buf, count) = match from_bytes(&bytes) {
+                Ok(pair) => pair,
+                Err(e) => {
+                    failure = Some(e.into());
+                    return false;
+                }
+            };
+            let record = SpookyRecord::new(buf, count);
+            let mut map = crate::spooky_value::FastMap::new();
+            for name in &names {
+                if let Some(value) = record.get_field::<SpookyValue>(name) {
+                    map.insert(name.clone(), value);
+                }
+            }
+            map.insert(SmolStr::new(JSONL_ID), SpookyValue::Str(SmolStr::new(id)));
+            line.clear();
+            line.push_str(&SpookyValue::Object(map).to_json_string());
+            line.push('\n');
+            if let Err(e) = writer.write_all(line.as_bytes()) {
+                failure = Some(e.into());
+                return false;
+            }
+            written += 1;
+            true
+        })?;
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Read JSON objects, one per line, into `table` and return how many
+    /// were stored. Each object's `"id"` string becomes the record id and is
+    /// not stored as a field; blank lines are skipped.
+    ///
+    /// Records go through `bulk_load` in chunks of 4096, so schemas and unique
+    /// constraints apply and memory stays bounded. A bad line fails the call
+    /// with the chunks before it already committed; the error names the line.
+    pub fn import_jsonl(
+        &mut self,
+        table: &str,
+        reader: impl BufRead,
+    ) -> Result<usize, SpookyDbError> {
+        validate_table_name(table)?;
+        let table = SmolStr::new(table);
+        let mut chunk = Vec::with_capacity(IMPORT_CHUNK);
+        let mut imported = 0;
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bad = |msg: String| SpookyDbError::Serialization(format!("line {}: {msg}", n + 1));
+            let value = SpookyValue::from_json_str(&line).map_err(|e| bad(e.to_string()))?;
+            let SpookyValue::Object(mut map) = value else {
+                return Err(bad("expected a JSON object".into()));
+            };
+            let Some(SpookyValue::Str(id)) = map.remove(JSONL_ID) else {
+                return Err(bad(format!("missing string {JSONL_ID:?}")));
+            };
+            let (data, _) = crate::serialization::from_spooky(&SpookyValue::Object(map))?;
+            chunk.push(BulkRecord {
+                table: table.clone(),
+                id,
+                data,
+                version: None,
+            });
+            if chunk.len() == IMPORT_CHUNK {
+                imported += chunk.len();
+                self.bulk_load(std::mem::take(&mut chunk))?;
+            }
+        }
+        imported += chunk.len();
+        if !chunk.is_empty() {
+            self.bulk_load(chunk)?;
+        }
+        Ok(imported)
+    }
+
+    /// Load a CBOR array, or a CBOR sequence (RFC 8742), of record maps into
+    /// `table` and return how many were stored. Each map's `"id"` text
+    /// becomes the record id and is not stored as a field.
+    ///
+    /// Items are read one at a time and serialized straight to record bytes
+    /// as by `from_cbor_slice`, through buffers reused across items, so no
+    /// value tree or `Vec<BulkRecord>` of the whole input is built. Records
+    /// go through `bulk_load` in chunks of 4096, as in `import_jsonl`; a bad
+    /// item fails the call with the chunks before it already committed, and
+    /// the error names the item.
+    pub fn bulk_load_cbor_stream(
+        &mut self,
+        table: &str,
+        reader: impl BufRead,
+    ) -> Result<usize, SpookyDbError> {
+        validate_table_name(table)?;
+        let table = SmolStr::new(table);
+        let mut items = ItemReader::new(reader);
+        // `None` for a sequence, else the array's length (`None` if indefinite).
+        let array = items.array_head()?;
+        let mut remaining = array.flatten();
+        let (mut item, mut buf) = (Vec::new(), Vec::new());
+        let mut chunk = Vec::with_capacity(IMPORT_CHUNK);
+        let mut imported = 0;
+        for n in 0.. {
+            let bad = |msg: String| SpookyDbError::Serialization(format!("item {n}: {msg}"));
+            match (array, &mut remaining) {
+                (Some(_), Some(0)) => break,
+                (Some(_), Some(left)) => *left -= 1,
+                (None, _) if items.at_end()? => break,
+                _ => {}
+            }
+            item.clear();
+            if !items.copy_item(&mut item).map_err(|e| bad(e.to_string()))? {
+                if array == Some(None) {
+                    break;
+                }
+                return Err(bad("unexpected break".into()));
+            }
+            let (_, id) = from_cbor_record_into(&item, JSONL_ID, &mut buf)
+                .map_err(|e| bad(e.to_string()))?;
+            let Some(id) = id else {
+                return Err(bad(format!("missing text {JSONL_ID:?}")));
+            };
+            chunk.push(BulkRecord {
+                table: table.clone(),
+                id,
+                data: buf.as_slice().to_vec(),
+                version: None,
+            });
+            if chunk.len() == IMPORT_CHUNK {
+                imported += chunk.len();
+                self.bulk_load(std::mem::take(&mut chunk))?;
+            }
+        }
+        if array.is_some() && !items.at_end()? {
+            return Err(SpookyDbError::Serialization(
+                "bulk_load_cbor_stream: trailing bytes after the array".into(),
+            ));
+        }
+        imported += chunk.len();
+        if !chunk.is_empty() {
+            self.bulk_load(chunk)?;
+        }
+        Ok(imported)
+    }
+}
+
+// ─── CSV ─────────────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Write `table` to `writer` as CSV: a header row of `fields`, then one
+    /// row per record in id order. Returns how many records were written.
+    /// For dumping a table into a spreadsheet.
+    ///
+    /// The field `"id"` is the record id. Strings, numbers and bools are
+    /// written as read by the typed getters; arrays and objects as JSON;
+    /// absent and null fields as empty cells. Cells holding a comma, quote,
+    /// CR or LF are quoted as RFC 4180 has it, and rows end in CRLF. Reads
+    /// one RECORDS_TABLE snapshot, like `export_jsonl`.
+    pub fn export_csv(
+        &self,
+        table: &str,
+        fields: &[&str],
+        mut writer: impl Write,
+    ) -> Result<usize, SpookyDbError> {
+        let mut row = String::new();
+        for (i, name) in fields.iter().enumerate() {
+            csv_cell(&mut row, i, name);
+        }
+        row.push_str("\r\n");
+        writer.write_all(row.as_bytes())?;
+
+        let mut written = 0;
+        let mut failure = None;
+        let mut cell = String::new();
+        let blob_txn = self.db.begin_read()?;
+        let blob_table = blob_txn.open_table(BLOBS_TABLE)?;
+        self.scan_keys(table, Bound::Unbounded, Bound::Unbounded, |id, bytes| {
+            let bytes = match blobs::inline(bytes, |hash| {
+                Ok(blob_table.get(hash)?.map(|blob| blob.value().to_vec()))
+            }) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    failure = Some(e);
+                    return false;
+                }
+            };
+            let (buf, count) = match from_bytes(&bytes) {
+                Ok(pair) => pair,
+                Err(e) => {
+                    failure = Some(e.into());
+                    return false;
+                }
+            };
+            let record = SpookyRecord::new(buf, count);
+            row.clear();
+            for (i, &name) in fields.iter().enumerate() {
+                cell.clear();
+                if name == JSONL_ID {
+                    cell.push_str(id);
+                } else {
+                    csv_value(&mut cell, &record, name);
+                }
+                csv_cell(&mut row, i, &cell);
+            }
+            row.push_str("\r\n");
+            if let Err(e) = writer.write_all(row.as_bytes()) {
+                failure = Some(e.into());
+                return false;
+            }
+            written += 1;
+            true
+        })?;
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+/// Field `name` of `record` as CSV cell text, unquoted.
+fn csv_value(out: &mut String, record: &SpookyRecord<'_>, name: &str) {
+    let text = match record.field_type(name) {
+        Some(TAG_STR) => return out.push_str(record.get_str(name).unwrap_or_default()),
+        Some(TAG_I64) => record.get_i64(name).map(|v| v.to_string()),
+        Some(TAG_U64) => record.get_u64(name).map(|v| v.to_string()),
+        Some(TAG_F64) => record.get_f64(name).map(|v| v.to_string()),
+        Some(TAG_BOOL) => record.get_bool(name).map(|v| v.to_string()),
+        Some(TAG_NESTED_CBOR) => record
+            .get_field::<SpookyValue>(name)
+            .map(|v| v.to_json_string()),
+        _ => None,
+    };
+    out.push_str(text.as_deref().unwrap_or_default());
+}
+
+/// Append `text` to `row` as its `i`th cell, quoted if it must be.
+fn csv_cell(row: &mut String, i: usize, text: &str) {
+    if i > 0 {
+        row.push(',');
+    }
+    if text.contains([',', '"', '\r', '\n']) {
+        row.push('"');
+        row.push_str(&text.replace('"', "\"\""));
+        row.push('"');
+    } else {
+        row.push_str(text);
+    }
+}
+
+// ─── Backup and Restore ──────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Write every table's records and versions to `path` as a
+    /// self-describing CBOR dump (see `backup` module docs) and return the
+    /// number of records. The format does not depend on redb's, so a backup
+    /// survives storage upgrades; `restore` reads it back.
+    ///
+    /// Flushes coalesced writes, then streams one read snapshot.
+    pub fn backup(&mut self, path: impl AsRef<Path>) -> Result<u64, SpookyDbError> {
+        self.flush()?;
+        let tables: Vec<(SmolStr, u64)> = self
+            .zsets
+            .names()
+            .map(|t| (t.clone(), self.zsets.stats(t).records))
+            .filter(|(_, records)| *records > 0)
+            .collect();
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut out = BackupWriter::new(file, now_millis(), &tables)?;
+
+        let read_txn = self.db.begin_read()?;
+        let records = read_txn.open_table(RECORDS_TABLE)?;
+        let versions = read_txn.open_table(VERSION_TABLE)?;
+        let blob_table = read_txn.open_table(BLOBS_TABLE)?;
+        // Both tables are keyed "table:id", so one merge walk pairs them.
+        let mut versions = versions.iter()?.peekable();
+        for entry in records.iter()? {
+            let (key, value) = entry?;
+            let key = key.value();
+            let mut version = None;
+            while let Some(Ok((vkey, _))) = versions.peek() {
+                if vkey.value() > key {
+                    break;
+                }
+                let (vkey, v) = versions.next().expect("peeked")?;
+                if vkey.value() == key {
+                    version = Some(v.value());
+                }
+            }
+            if let Some(rid) = RecordId::from_db_key(key) {
+                let bytes = self.decode(value.value())?;
+                let bytes = blobs::inline(&bytes, |hash| {
+                    Ok(blob_table.get(hash)?.map(|blob| blob.value().to_vec()))
+                })?;
+                out.record(rid.table(), rid.id(), &bytes, version)?;
+            }
+        }
+        out.finish()
+    }
+
+    /// Load a dump written by `backup` and return the number of records.
+    ///
+    /// The database must hold no records (`SpookyDbError::TableExists`
+    /// otherwise). Records go through `bulk_load` in chunks of 4096, so a
+    /// corrupt or truncated file fails with `SpookyDbError::Serialization`
+    /// after the chunks before the damage have committed — restore into a
+    /// fresh file and discard it on error.
+    pub fn restore(&mut self, path: impl AsRef<Path>) -> Result<u64, SpookyDbError> {
+        self.flush()?;
+        if let Some(table) = self.zsets.names().find(|t| self.zsets.len(t) > 0) {
+            return Err(SpookyDbError::TableExists(table.clone()));
+        }
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut input = BackupReader::new(file)?;
+        let mut chunk = Vec::with_capacity(IMPORT_CHUNK);
+        let mut restored = 0;
+        while let Some(record) = input.next_record()? {
+            chunk.push(record);
+            if chunk.len() == IMPORT_CHUNK {
+                restored += chunk.len() as u64;
+                self.bulk_load(std::mem::take(&mut chunk))?;
+            }
+        }
+        restored += chunk.len() as u64;
+        if !chunk.is_empty() {
+            self.bulk_load(chunk)?;
+        }
+        Ok(restored)
+    }
+
+    /// Copy the database into a new redb file at `dest` without stopping
+    /// it, calling `on_progress` after each chunk. The copy is one read
+    /// snapshot, so it is consistent, and opens with `SpookyDb::new`.
+    /// Writes still coalescing are not part of it.
+    ///
+    /// On a `SpookyDb` this holds `&self` for the whole copy; use
+    /// `SharedSpookyDb::backup_online` to keep writing meanwhile.
+    pub fn backup_online(
+        &self,
+        dest: impl AsRef<Path>,
+        on_progress: impl FnMut(&BackupProgress),
+    ) -> Result<BackupProgress, SpookyDbError> {
+        copy_snapshot(&self.db, dest.as_ref(), on_progress)
+    }
+}
+
+/// Entries per destination transaction in `copy_snapshot`.
+const COPY_CHUNK: u64 = 4096;
+
+/// Copy every table of one read snapshot of `src` to a new file at `dest`.
+///
+/// The copy is built at `"<dest>.partial"` and renamed into place once its
+/// final, durable commit is done, so `dest` never holds a partial copy.
+/// Chunks commit without fsync. The snapshot pins `src`'s pages for the
+/// duration, so `src` may grow while writes continue.
+pub(super) fn copy_snapshot(
+    src: &RedbDatabase,
+    dest: &Path,
+    mut on_progress: impl FnMut(&BackupProgress),
+) -> Result<BackupProgress, SpookyDbError> {
+    if dest.exists() {
+        let msg = format!("{} already exists", dest.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, msg).into());
+    }
+    let mut partial = dest.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    if partial.exists() {
+        std::fs::remove_file(&partial)?;
+    }
+
+    let snapshot = src.begin_read()?;
+    let mut progress = BackupProgress::default();
+    progress.total += snapshot.open_table(RECORDS_TABLE)?.len()?;
+    progress.total += snapshot.open_table(VERSION_TABLE)?.len()?;
+    progress.total += snapshot.open_table(OPLOG_TABLE)?.len()?;
+    progress.total += snapshot.open_table(DELTA_TABLE)?.len()?;
+    progress.total += snapshot.open_table(TOMBSTONE_TABLE)?.len()?;
+    progress.total += snapshot.open_table(TTL_TABLE)?.len()?;
+    progress.total += snapshot.open_table(TIMES_TABLE)?.len()?;
+    progress.total += snapshot.open_table(META_TABLE)?.len()?;
+    progress.total += snapshot.open_table(BLOBS_TABLE)?.len()?;
+    progress.total += snapshot.open_table(BLOB_REFS_TABLE)?.len()?;
+    progress.total += snapshot.open_table(CHECKPOINT_TABLE)?.len()?;
+    progress.total += snapshot.open_table(CHECKPOINT_TICKS_TABLE)?.len()?;
+    progress.total += snapshot.open_table(SCHEMA_VERSION_TABLE)?.len()?;
+    progress.total += snapshot.open_table(COMMIT_TICK_TABLE)?.len()?;
+    on_progress(&progress);
+
+    let copy = RedbDatabase::create(&partial)?;
+    let report = &mut |copied: u64| {
+        progress.copied += copied;
+        on_progress(&progress);
+    };
+    copy_table(&snapshot, &copy, RECORDS_TABLE, report)?;
+    copy_table(&snapshot, &copy, VERSION_TABLE, report)?;
+    copy_table(&snapshot, &copy, OPLOG_TABLE, report)?;
+    copy_table(&snapshot, &copy, DELTA_TABLE, report)?;
+    copy_table(&snapshot, &copy, TOMBSTONE_TABLE, report)?;
+    copy_table(&snapshot, &copy, TTL_TABLE, report)?;
+    copy_table(&snapshot, &copy, TIMES_TABLE, report)?;
+    copy_table(&snapshot, &copy, META_TABLE, report)?;
+    copy_table(&snapshot, &copy, BLOBS_TABLE, report)?;
+    copy_table(&snapshot, &copy, BLOB_REFS_TABLE, report)?;
+    copy_table(&snapshot, &copy, CHECKPOINT_TABLE, report)?;
+    copy_table(&snapshot, &copy, CHECKPOINT_TICKS_TABLE, report)?;
+    copy_table(&snapshot, &copy, SCHEMA_VERSION_TABLE, report)?;
+    copy_table(&snapshot, &copy, COMMIT_TICK_TABLE, report)?;
+    // A durable commit makes the earlier non-durable ones durable too.
+    copy.begin_write()?.commit()?;
+    drop(copy);
+    std::fs::rename(&partial, dest)?;
+    Ok(progress)
+}
+
+/// Stream one table from `snapshot` into `dest`, `COPY_CHUNK` entries per
+/// transaction, passing each chunk's size to `report`. Creates the table
+/// even when it is empty.
+fn copy_table<K: redb::Key + 'static, V: redb::Value + 'static>(
+    snapshot: &ReadTransaction,
+    dest: &RedbDatabase,
+    def: TableDefinition<K, V>,
+    report: &mut impl FnMut(u64),
+) -> Result<(), SpookyDbError> {
+    let source = snapshot.open_table(def)?;
+    let mut entries = source.iter()?;
+    loop {
+        let mut txn = dest.begin_write()?;
+        txn.set_durability(redb::Durability::None)?;
+        let mut copied = 0;
+        {
+            let mut table = txn.open_table(def)?;
+            while copied < COPY_CHUNK {
+                let Some(entry) = entries.next() else {
+                    break;
+                };
+                let (key, value) = entry?;
+                table.insert(key.value(), value.value())?;
+                copied += 1;
+            }
+        }
+        txn.commit()?;
+        if copied > 0 {
+            report(copied);
+        }
+        if copied < COPY_CHUNK {
+            return Ok(());
+        }
+    }
+}
+
+// ─── Compression ─────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Compress `table`'s record bytes of at least `min_bytes` with the
+    /// configured `SpookyDbConfig::compressor` before they reach redb; `None`
+    /// stops compressing new writes. Values are stored compressed only when
+    /// that saves bytes, and reads decompress transparently, so the row
+    /// cache, scans and `get_row_record` see plain record bytes.
+    ///
+    /// Applies from the next write; existing values are not rewritten.
+    /// In-memory option — set again after reopening. Fails with
+    /// `SpookyDbError::Compression` when no compressor is configured.
+    pub fn set_compression(
+        &mut self,
+        table: &str,
+        min_bytes: Option<usize>,
+    ) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        self.flush()?;
+        self.compression.set(table, min_bytes)
+    }
+
+    /// `table`'s compression threshold, if set.
+    pub fn compression(&self, table: &str) -> Option<usize> {
+        self.compression.threshold(table)
+    }
+}
+
+// ─── Blobs ───────────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Store `table`'s fields of at least `min_bytes` out of line, in
+    /// BLOBS_TABLE keyed by content hash; `None` stops moving new writes.
+    /// The record keeps a 25-byte `TAG_BLOB_REF` field in place of each, so
+    /// the row cache and scans stay small. Equal contents are stored once.
+    ///
+    /// `get_record_bytes`, queries and views see the reference, not the
+    /// data: read it with `get_blob`, and do not index, filter or check a
+    /// schema on such fields. `backup` and `export_jsonl` write the data
+    /// inline.
+    ///
+    /// Applies from the next write; existing values are not rewritten.
+    /// In-memory option — set again after reopening.
+    pub fn set_blob_threshold(
+        &mut self,
+        table: &str,
+        min_bytes: Option<usize>,
+    ) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        self.flush()?;
+        match min_bytes {
+            Some(min) => self.blob_thresholds.insert(SmolStr::new(table), min),
+            None => self.blob_thresholds.remove(table),
+        };
+        Ok(())
+    }
+
+    /// `table`'s blob threshold, if set.
+    pub fn blob_threshold(&self, table: &str) -> Option<usize> {
+        self.blob_thresholds.get(table).copied()
+    }
+
+    /// The data of `field` in record `id`, read from BLOBS_TABLE when it is
+    /// stored out of line. `None` if the record or field is absent.
+    pub fn get_blob(
+        &self,
+        table: &str,
+        id: &str,
+        field: &str,
+    ) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        let Some(bytes) = self.get_record_bytes(table, id)? else {
+            return Ok(None);
+        };
+        resolve_blob(&self.db, &bytes, field)
+    }
+}
+
+/// The data of `field` in `bytes`, following a blob reference into `db`.
+pub(super) fn resolve_blob(
+    db: &RedbDatabase,
+    bytes: &[u8],
+    field: &str,
+) -> Result<Option<Vec<u8>>, SpookyDbError> {
+    let (buf, count) = from_bytes(bytes)?;
+    let record = SpookyRecord::new(buf, count);
+    let Some(raw) = record.get_raw(field) else {
+        return Ok(None);
+    };
+    match BlobRef::parse(raw.data).filter(|_| raw.type_tag == TAG_BLOB_REF) {
+        Some(blob) => {
+            let read_txn = db.begin_read()?;
+            let data = read_txn.open_table(BLOBS_TABLE)?.get(blob.hash)?;
+            let data = data.ok_or_else(|| blobs::missing(blob.hash))?;
+            Ok(Some(data.value().to_vec()))
+        }
+        None => Ok(Some(raw.data.to_vec())),
+    }
+}
+
+// ─── Row Cache Policy ────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Choose how `table`'s rows share the row cache. Rows already cached
+    /// move to the new partition, most recent first, and anything over its
+    /// limits is evicted. `CachePolicy::Pinned` also reads the whole table
+    /// into memory. In-memory only — set again after reopening.
+    pub fn set_cache_policy(
+        &mut self,
+        table: &str,
+        policy: CachePolicy,
+    ) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        let evicted = self.row_cache.set_policy(table, policy);
+        self.counters.cache_evictions += evicted as u64;
+        if policy == CachePolicy::Pinned {
+            let mut rows = Vec::new();
+            self.scan_keys(table, Bound::Unbounded, Bound::Unbounded, |id, bytes| {
+                rows.push((SmolStr::new(id), CachedRow::from(bytes)));
+                true
+            })?;
+            let table = SmolStr::new(table);
+            for (id, bytes) in rows {
+                self.row_cache.put((table.clone(), id), bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep every row of `table` in memory: shorthand for
+    /// `set_cache_policy(table, CachePolicy::Pinned)`. Meant for small, hot
+    /// tables; a pinned table's rows ignore `cache_capacity` and
+    /// `cache_max_bytes`.
+    pub fn pin_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+        self.set_cache_policy(table, CachePolicy::Pinned)
+    }
+
+    /// Return `table` to the shared LRU.
+    pub fn unpin_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+        self.set_cache_policy(table, CachePolicy::Shared)
+    }
+
+    pub fn cache_policy(&self, table: &str) -> CachePolicy {
+        self.row_cache.policy(table)
+    }
+
+    /// Load the records `ids` of `table` into the row cache, reading every
+    /// one not cached yet in a single read transaction, and return how many
+    /// were loaded. Absent ids are skipped.
+    ///
+    /// For pre-warming hot rows after reopen; the cache's limits still
+    /// apply, so prefetching more than fits evicts the earliest loaded.
+    pub fn prefetch(&mut self, table: &str, ids: &[&str]) -> Result<usize, SpookyDbError> {
+        validate_table_name(table)?;
+        let Some(zset) = self.zsets.get(&self.db, table)? else {
+            return Ok(0);
+        };
+        let table_key = SmolStr::new(table);
+        let misses: Vec<&str> = ids
+            .iter()
+            .copied()
+            .filter(|&id| zset.get(id).copied().unwrap_or(0) > 0)
+            .filter(|&id| self.row_cache.peek(&(table_key.clone(), SmolStr::new(id))).is_none())
+            .collect();
+        if misses.is_empty() {
+            return Ok(0);
+        }
+        let mut rows = Vec::with_capacity(misses.len());
+        {
+            let read_txn = self.db.begin_read()?;
+            let tbl = read_txn.open_table(RECORDS_TABLE)?;
+            for id in misses {
+                if let Some(guard) = tbl.get(make_key(table, id)?.as_str())? {
+                    let bytes = self.read_form(table, id, guard.value())?;
+                    rows.push((SmolStr::new(id), CachedRow::from(bytes)));
+                }
+            }
+        }
+        let loaded = rows.len();
+        for (id, bytes) in rows {
+            self.cache_put((table_key.clone(), id), bytes);
+        }
+        Ok(loaded)
+    }
+
+    /// Load up to `limit` records of `table`, in key order, into the row
+    /// cache from one read transaction, and return how many were loaded.
+    /// Rows already cached count towards `limit` but are not re-read.
+    pub fn warm_table(&mut self, table: &str, limit: usize) -> Result<usize, SpookyDbError> {
+        let table_key = SmolStr::new(table);
+        let mut rows = Vec::new();
+        let mut seen = 0;
+        self.scan_keys(table, Bound::Unbounded, Bound::Unbounded, |id, bytes| {
+            if seen == limit {
+                return false;
+            }
+            seen += 1;
+            let key = (table_key.clone(), SmolStr::new(id));
+            if self.row_cache.peek(&key).is_none() {
+                rows.push((key, CachedRow::from(bytes)));
+            }
+            true
+        })?;
+        let loaded = rows.len();
+        for (key, bytes) in rows {
+            self.cache_put(key, bytes);
+        }
+        Ok(loaded)
+    }
+}
+
+// ─── Stats ───────────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Counters since open, plus current cache occupancy and per-table sizes.
+    /// O(tables); never loads a table.
+    pub fn stats(&self) -> DbStats {
+        let c = &self.counters;
+        let tables = self
+            .zsets
+            .names()
+            .map(|table| (table.clone(), self.zsets.stats(table)))
+            .collect();
+        DbStats {
+            cache_hits: c.cache_hits.load(Ordering::Relaxed),
+            cache_misses: c.cache_misses.load(Ordering::Relaxed),
+            cache_evictions: c.cache_evictions,
+            cache_len: self.row_cache.len(),
+            cache_capacity: self.row_cache.cap().get(),
+            cache_bytes: self.row_cache.bytes(),
+            cache_max_bytes: self.row_cache.max_bytes(),
+            commits: c.commits,
+            commit_time: c.commit_time,
+            max_commit_time: c.max_commit_time,
+            bytes_written: c.bytes_written,
+            tables,
+        }
+    }
+
+    /// Stats for one table, as persisted with its last write.
+    pub fn table_stats(&self, table: &str) -> TableStats {
+        self.zsets.stats(table)
+    }
+
+    /// Call `hook` with a fresh `stats()` snapshot after a record write once
+    /// at least `every` has passed since the last call — for exporting to a
+    /// metrics system. There is no background timer: an idle database
+    /// reports nothing. Replaces any previous hook.
+    pub fn set_stats_hook(&mut self, every: Duration, hook: impl FnMut(&DbStats) + Send + 'static) {
+        self.stats_hook = Some(StatsHookState {
+            every,
+            last: Instant::now(),
+            hook: Box::new(hook),
+        });
+    }
+
+    /// Remove the hook set with `set_stats_hook`.
+    pub fn clear_stats_hook(&mut self) {
+        self.stats_hook = None;
+    }
+
+    fn report_stats_if_due(&mut self) {
+        let due = self
+            .stats_hook
+            .as_ref()
+            .is_some_and(|h| h.last.elapsed() >= h.every);
+        if !due {
+            return;
+        }
+        let stats = self.stats();
+        if let Some(state) = &mut self.stats_hook {
+            state.last = Instant::now();
+            (state.hook)(&stats);
+        }
+    }
+
+    /// Call `hook` with the duration of each write, batch, bulk load,
+    /// commit, record encode and `get_record_bytes` — see [`TimedOp`] — to
+    /// watch for regressions in a real workload. Runs on the calling thread,
+    /// inside the operation, so keep it cheap (e.g. record into a histogram).
+    /// Failed operations are reported too. Replaces any previous hook.
+    pub fn set_timing_hook(&mut self, hook: impl Fn(TimedOp, Duration) + Send + 'static) {
+        self.timing_hook = Some(Box::new(hook));
+    }
+
+    /// Remove the hook set with `set_timing_hook`.
+    pub fn clear_timing_hook(&mut self) {
+        self.timing_hook = None;
+    }
+
+    /// Start time for `report_timing`; `None` without a timing hook.
+    #[inline]
+    fn timer(&self) -> Option<Instant> {
+        self.timing_hook.as_ref().map(|_| Instant::now())
+    }
+
+    #[inline]
+    fn report_timing(&self, op: TimedOp, start: Option<Instant>) {
+        if let (Some(hook), Some(start)) = (&self.timing_hook, start) {
+            hook(op, start.elapsed());
+        }
+    }
+
+    /// `row_cache.put` that counts evictions.
+    fn cache_put(&mut self, key: (SmolStr, SmolStr), bytes: impl Into<CachedRow>) {
+        self.counters.cache_evictions += self.row_cache.put(key, bytes.into()) as u64;
+    }
+}
+
+// ─── Maintenance ─────────────────────────────────────────────────────────────
+
+impl SpookyDb {
+    /// Reclaim free space: relocate pages to the front of the file and
+    /// shrink it. Heavy delete churn otherwise leaves the file at its
+    /// high-water mark.
+    ///
+    /// Flushes and syncs first. Blocks for the whole rewrite — seconds per
+    /// GiB. Fails with `redb::Error::TransactionInProgress` while a
+    /// `SharedSpookyDb` (or any other holder of the redb handle) exists.
+    pub fn compact(&mut self) -> Result<CompactionReport, SpookyDbError> {
+        self.sync()?;
+        let bytes_before = self.file_size()?;
+        let db = Arc::get_mut(&mut self.db)
+            .ok_or(SpookyDbError::Redb(redb::Error::TransactionInProgress))?;
+        let compacted = db.compact()?;
+        Ok(CompactionReport {
+            bytes_before,
+            bytes_after: self.file_size()?,
+            compacted,
+        })
+    }
+
+    /// Scan RECORDS_TABLE and compare it with the in-memory state: ZSet
+    /// membership and weights, row-cache bytes and the persisted table
+    /// stats. For use after a crash or a bug in a higher layer that may have
+    /// written around `SpookyDb`. Tables that are not loaded yet only have
+    /// their stats checked — loading reads their ZSet straight from disk.
+    ///
+    /// With `repair`, ZSets are rebuilt to match disk, stale cache entries
+    /// are evicted and wrong stats rewritten; the report still lists what
+    /// was wrong. Buffered writes are flushed first. O(N records) plus one
+    /// lookup per ZSet entry.
+    pub fn integrity_check(&mut self, repair: bool) -> Result<IntegrityReport, SpookyDbError> {
+        self.flush()?;
+        let mut report = IntegrityReport::default();
+        let read_txn = self.db.begin_read()?;
+        let records = read_txn.open_table(RECORDS_TABLE)?;
+
+        // Keys are sorted, so each table's records are contiguous.
+        let mut stats: Vec<(SmolStr, TableStats)> = Vec::new();
+        for entry in records.iter()? {
+            let (key_guard, value) = entry?;
+            report.records_scanned += 1;
+            let Some(rid) = RecordId::from_db_key(key_guard.value()) else {
+                continue;
+            };
+            let (table, id) = (rid.table(), rid.id());
+            if stats.last().is_none_or(|(t, _)| t != table) {
+                stats.push((SmolStr::new(table), TableStats::default()));
+            }
+            if let Some((_, s)) = stats.last_mut() {
+                s.records += 1;
+                s.bytes += value.value().len() as u64;
+            }
+            let loaded = self.zsets.peek(table);
+            if loaded.is_none() && self.zsets.stats(table).records > 0 {
+                continue;
+            }
+            match loaded.and_then(|z| z.get(id)) {
+                None => report
+                    .missing_from_zset
+                    .push((SmolStr::new(table), SmolStr::new(id))),
+                Some(&w) if w != 1 => {
+                    report
+                        .wrong_weight
+                        .push((SmolStr::new(table), SmolStr::new(id), w))
+                }
+                Some(_) => {}
+            }
+        }
+        for (table, zset) in self.zsets.iter_loaded() {
+            for id in zset.keys() {
+                if records.get(make_key(table, id)?.as_str())?.is_none() {
+                    report.missing_from_disk.push((table.clone(), id.clone()));
+                }
+            }
+        }
+        for ((table, id), cached) in self.row_cache.iter() {
+            let on_disk = records.get(make_key(table, id)?.as_str())?;
+            let on_disk =
+                on_disk.map(|bytes| self.read_form(table, id, bytes.value()).map(Cow::into_owned));
+            if on_disk.transpose()?.is_none_or(|bytes| bytes[..] != cached[..]) {
+                report.stale_cache.push((table.clone(), id.clone()));
+            }
+        }
+        let on_disk = |table: &SmolStr| stats.iter().any(|(t, _)| t == table);
+        let gone: Vec<_> = self.zsets.names().filter(|t| !on_disk(t)).collect();
+        stats.extend(gone.into_iter().map(|t| (t.clone(), TableStats::default())));
+        stats.retain(|(table, s)| self.zsets.stats(table) != *s);
+        for (table, on_disk) in &stats {
+            let entry = (table.clone(), self.zsets.stats(table), *on_disk);
+            report.wrong_table_stats.push(entry);
+        }
+        drop((records, read_txn));
+
+        if repair && !report.is_clean() {
+            let missing = report.missing_from_zset.iter().map(|(t, i)| (t, i));
+            let wrong = report.wrong_weight.iter().map(|(t, i, _)| (t, i));
+            for (table, id) in missing.chain(wrong) {
+                self.zsets.load(&self.db, table)?;
+                self.zsets.loaded_mut(table).insert(id.clone(), 1);
+            }
+            for (table, id) in &report.missing_from_disk {
+                self.zsets.loaded_mut(table).remove(id);
+            }
+            for key in &report.stale_cache {
+                self.row_cache.pop(key);
+            }
+            if !stats.is_empty() {
+                let write_txn = self.begin_write()?;
+                zsets::write_stats(&write_txn, &stats)?;
+                self.commit(write_txn)?;
+                self.zsets.apply_stats(stats);
+            }
+            report.repaired = true;
+        }
+        Ok(report)
+    }
+
+    fn file_size(&self) -> Result<u64, SpookyDbError> {
+        std::fs::metadata(&self.path)
+            .map(|m| m.len())
+            .map_err(|e| redb::StorageError::Io(e).into())
+    }
+}
+
+// ─── DbBackend trait ──────────────────────────────────────────────────────────
+
+/// Thin adapter trait for incremental migration from the old in-memory
+/// `Database` struct to `SpookyDb`. Implement for both; wire `circuit.rs`
+/// against the trait.
+///
+/// All write operations return `Result` — a disk-full or corruption error must
+/// never silently become a no-op. Callers must handle or propagate write errors.
+pub trait DbBackend {
+    /// Zero-copy ZSet access. Borrowed from memory — zero I/O.
+    fn get_table_zset(&self, table: &str) -> Option<&ZSet>;
+
+    /// Raw bytes for a record, served from in-memory cache with redb fallback.
+    /// Returns `Ok(None)` if the record is absent. Returns `Err` on storage errors.
+    fn get_record_bytes(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<Vec<u8>>, SpookyDbError>;
+
+    /// Zero-copy borrowed record access. Returns `None` if the record is absent.
+    ///
+    /// Default implementation returns `None` (falls back to `get_record_bytes` for
+    /// backends without an in-memory row cache). Backends with an in-memory row
+    /// cache should override this for hot-path efficiency.
+    fn get_row_record_bytes<'a>(&'a self, _table: &str, _id: &str) -> Option<&'a [u8]> {
+        None
+    }
+
+    /// Register an empty table.
+    ///
+    /// Returns `Err(SpookyDbError::InvalidKey)` if `table` is empty or contains U+001F.
+    fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError>;
+
+    /// Single mutation: record write + ZSet update.
+    fn apply_mutation(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError>;
+
+    /// Batch mutations in one transaction.
+    fn apply_batch(
+        &mut self,
+        mutations: Vec<DbMutation>,
+    ) -> Result<BatchMutationResult, SpookyDbError>;
+
+    /// Bulk initial load.
+    fn bulk_load(
+        &mut self,
+        records: Vec<BulkRecord>,
+    ) -> Result<(), SpookyDbError>;
+
+    /// Weight for one record. Returns 0 if absent.
+    fn get_zset_weight(&self, table: &str, id: &str) -> i64;
+
+    /// Reconstruct a partial `SpookyValue::Object` from a stored record.
+    ///
+    /// Only fields whose names are listed in `fields` are included. Field names
+    /// are not recoverable from hashes — callers must supply the expected names.
+    /// Returns `Ok(None)` if the record does not exist.
+    fn get_record_typed(
+        &self,
+        table: &str,
+        id: &str,
+        fields: &[&str],
+    ) -> Result<Option<SpookyValue>, SpookyDbError>;
+}
+
+impl DbBackend for SpookyDb {
+    fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
+        self.get_table_zset(table)
+    }
+
+    fn get_record_bytes(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        SpookyDb::get_record_bytes(self, table, id)
+    }
+
+    fn get_row_record_bytes<'a>(&'a self, table: &str, id: &str) -> Option<&'a [u8]> {
+        // Cache-only — None on cache miss (same semantics as get_row_record).
+        let cache_key = (SmolStr::new(table), SmolStr::new(id));
+        self.row_cache.peek(&cache_key).map(|v| &v[..])
+    }
+
+    fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+        SpookyDb::ensure_table(self, table)
+    }
+
+    fn apply_mutation(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        SpookyDb::apply_mutation(self, table, op, id, data, version)
+    }
+
+    fn apply_batch(
+        &mut self,
+        mutations: Vec<DbMutation>,
+    ) -> Result<BatchMutationResult, SpookyDbError> {
+        SpookyDb::apply_batch(self, mutations)
+    }
+
+    fn bulk_load(
+        &mut self,
+        records: Vec<BulkRecord>,
+    ) -> Result<(), SpookyDbError> {
+        SpookyDb::bulk_load(self, records)
+    }
+
+    fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
+        self.get_zset_weight(table, id)
+    }
+
+    fn get_record_typed(
+        &self,
+        table: &str,
+        id: &str,
+        fields: &[&str],
+    ) -> Result<Option<SpookyValue>, SpookyDbError> {
+        SpookyDb::get_record_typed(self, table, id, fields)
+    }
+}
+
+// ─── Tests ────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::record;
+    use crate::serialization::from_cbor;
+    use tempfile::NamedTempFile;
+
+    // BENCH_CBOR: a pre-serialized CBOR map (12 fields) representing a realistic
+    // user record. Used by all test helpers that need pre-built SpookyRecord bytes.
+    //
+    // Fields and values (as CBOR):
+    //   active:      true                              (bool)
+    //   age:         28                                (uint/i64)
+    //   count:       1000                              (uint)
+    //   deleted:     false                             (bool)
+    //   history:     [{action:"login",  timestamp:1234567890},
+    //                 {action:"update", timestamp:1234567900}]  (array of 2 objects)
+    //   id:          "user:abc123"                     (str)
+    //   metadata:    null
+    //   mixed_array: [42, "text", true, {nested:"value"}]       (array of 4)
+    //   name:        "Alice"                           (str)
+    //   profile:     {avatar:"https://example.com/avatar.jpg",
+    //                 bio:"Software engineer",
+    //                 settings:{notifications:true,
+    //                           privacy:{level:3, public:false},
+    //                           theme:"dark"}}         (nested object)
+    //   score:       99.5                              (f64)
+    //   tags:        ["developer", "rust", "database"] (array of 3 strings)
+    //
+    // Regenerate: build a cbor4ii::core::Value::Map with the above fields, call
+    // `from_cbor(&val)` to obtain the SpookyRecord bytes, and print them as a
+    // Rust byte-array literal.
+    const BENCH_CBOR: &[u8] = &[
+        172, 102, 97, 99, 116, 105, 118, 101, 245, 99, 97, 103, 101, 24, 28, 101, 99, 111, 117,
         110, 116, 25, 3, 232, 103, 100, 101, 108, 101, 116, 101, 100, 244, 103, 104, 105, 115,
         116, 111, 114, 121, 130, 162, 102, 97, 99, 116, 105, 111, 110, 101, 108, 111, 103, 105,
         110, 105, 116, 105, 109, 101, 115, 116, 97, 109, 112, 26, 73, 150, 2, 210, 162, 102, 97,
@@ -804,529 +5637,3668 @@ mod tests {
     ];
 
     #[test]
-    fn test_new_opens_empty_db() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_new_opens_empty_db() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = SpookyDb::new(tmp.path())?;
+        assert!(!db.table_exists("users"));
+        assert_eq!(db.table_len("users"), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_mutation_create_get_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        // Create
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), Some(1))?;
+        assert!(db.table_exists("users"));
+        assert_eq!(db.table_len("users"), 1);
+        assert_eq!(db.get_zset_weight("users", "alice"), 1);
+
+        // Get raw bytes back
+        let fetched = db.get_record_bytes("users", "alice")?.expect("should exist");
+        assert_eq!(fetched, data);
+
+        // Version
+        assert_eq!(db.get_version("users", "alice")?, Some(1));
+
+        // Delete
+        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
+        assert_eq!(db.get_zset_weight("users", "alice"), 0);
+        assert!(db.get_record_bytes("users", "alice")?.is_none());
+        assert_eq!(db.table_len("users"), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_batch_one_txn() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        let mutations = vec![
+            DbMutation {
+                table: SmolStr::new("users"),
+                id: SmolStr::new("u1"),
+                op: Operation::Create,
+                data: Some(data.clone()),
+                version: Some(1),
+                expires_at: None,
+            },
+            DbMutation {
+                table: SmolStr::new("users"),
+                id: SmolStr::new("u2"),
+                op: Operation::Create,
+                data: Some(data.clone()),
+                version: Some(1),
+                expires_at: None,
+            },
+            DbMutation {
+                table: SmolStr::new("posts"),
+                id: SmolStr::new("p1"),
+                op: Operation::Create,
+                data: Some(data.clone()),
+                version: Some(1),
+                expires_at: None,
+            },
+        ];
+
+        let result = db.apply_batch(mutations)?;
+
+        assert_eq!(db.table_len("users"), 2);
+        assert_eq!(db.table_len("posts"), 1);
+        assert_eq!(result.membership_deltas["users"].len(), 2);
+        assert_eq!(result.membership_deltas["posts"].len(), 1);
+        assert!(result.changed_tables.contains(&SmolStr::new("users")));
+        assert!(result.changed_tables.contains(&SmolStr::new("posts")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_load() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        let records = vec![
+            BulkRecord {
+                table: SmolStr::new("items"),
+                id: SmolStr::new("i1"),
+                data: data.clone(),
+                version: None,
+            },
+            BulkRecord {
+                table: SmolStr::new("items"),
+                id: SmolStr::new("i2"),
+                data: data.clone(),
+                version: None,
+            },
+        ];
+
+        db.bulk_load(records)?;
+        assert_eq!(db.table_len("items"), 2);
+        assert_eq!(db.get_zset_weight("items", "i1"), 1);
+        assert_eq!(db.get_zset_weight("items", "i2"), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_survives_reopen() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_path_buf();
+        // Keep file alive but drop NamedTempFile handle so only the path remains.
+        // Use a regular tempdir file to keep the path valid.
+        let tmp_dir = tempfile::tempdir()?;
+        let db_path = tmp_dir.path().join("test.redb");
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        {
+            let mut db = SpookyDb::new(&db_path)?;
+            db.apply_mutation("users", Operation::Create, "alice", Some(&data), Some(1))?;
+            db.apply_mutation("users", Operation::Create, "bob", Some(&data), Some(2))?;
+            assert_eq!(db.table_len("users"), 2);
+        }
+
+        // Reopen — ZSet must be rebuilt from RECORDS_TABLE.
+        let db2 = SpookyDb::new(&db_path)?;
+        assert_eq!(db2.table_len("users"), 2);
+        assert_eq!(db2.get_zset_weight("users", "alice"), 1);
+        assert_eq!(db2.get_zset_weight("users", "bob"), 1);
+
+        // Suppress unused path warning.
+        let _ = path;
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_record_typed_partial() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+
+        // The CBOR fixture has an "age" field (i64 = 28) and "active" (bool).
+        let val = db
+            .get_record_typed("users", "alice", &["age", "active"])?
+            .expect("should exist");
+
+        assert!(matches!(val, SpookyValue::Object(_)));
+        if let SpookyValue::Object(map) = val {
+            // "age" and "active" should be present.
+            assert!(map.contains_key("age"), "age field missing");
+            assert!(map.contains_key("active"), "active field missing");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_as_deserializes_and_names_bad_fields() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        enum Role {
+            Admin,
+            Guest { until: u64 },
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct User {
+            name: String,
+            age: u8,
+            tags: Vec<String>,
+            role: Role,
+            nick: Option<String>,
+            #[serde(default)]
+            bio: String,
+        }
+
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        db.set_blob_threshold("users", Some(64))?;
+        let bio = "x".repeat(100);
+        let put = |db: &mut SpookyDb, id: &str, json: &str| -> Result<(), SpookyDbError> {
+            let (bytes, _) = crate::serialization::from_json_str(json)?;
+            db.apply_mutation("users", Operation::Create, id, Some(&bytes), None)?;
+            Ok(())
+        };
+        let ann = format!(
+            r#"{{"name":"Ann","age":40,"tags":["a"],"role":"Admin","nick":null,"bio":"{bio}"}}"#
+        );
+        put(&mut db, "ann", &ann)?;
+        put(&mut db, "bob", r#"{"name":"Bob","age":7,"tags":[],"role":{"Guest":{"until":9}}}"#)?;
+        put(&mut db, "old", r#"{"name":"Old","age":300,"tags":[],"role":"Admin"}"#)?;
+
+        let stored = db.get_record_bytes("users", "ann")?.expect("present");
+        let (buf, count) = from_bytes(&stored)?;
+        let bio_type = SpookyRecord::new(buf, count).field_type("bio");
+        assert_eq!(bio_type, Some(crate::types::TAG_BLOB_REF));
+        let ann: User = db.get_as("users", "ann")?.expect("present");
+        assert_eq!((ann.age, ann.role, ann.nick, ann.bio.len()), (40, Role::Admin, None, 100));
+        let bob: User = db.get_as("users", "bob")?.expect("present");
+        assert_eq!((bob.role, bob.bio.as_str()), (Role::Guest { until: 9 }, ""));
+        assert!(db.get_as::<User>("users", "nobody")?.is_none());
+
+        let err = db.get_as::<User>("users", "old").unwrap_err().to_string();
+        assert!(err.contains(r#"field "age""#), "{err}");
+        #[derive(Debug, serde::Deserialize)]
+        struct NeedsEmail {
+            #[allow(dead_code)]
+            email: String,
+        }
+        let err = db.get_as::<NeedsEmail>("users", "bob").unwrap_err().to_string();
+        assert!(err.contains(r#"field "email": missing field"#), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_put_struct_creates_then_updates() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct User {
+            name: String,
+            age: u32,
+            tags: Vec<String>,
+        }
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let mut ann = User {
+            name: "Ann".into(),
+            age: 40,
+            tags: vec!["a".into()],
+        };
+
+        assert_eq!(db.put_struct("users", "ann", &ann, None)?.1, 1);
+        ann.age = 41;
+        assert_eq!(db.put_struct("users", "ann", &ann, Some(7))?.1, 0);
+        assert_eq!(db.table_len("users"), 1);
+        assert_eq!(db.get_version("users", "ann")?, Some(7));
+        assert_eq!(db.get_as::<User>("users", "ann")?, Some(ann));
+
+        assert!(db.put_struct("users", "bad", &[1, 2], None).is_err());
+        assert!(db.get_record_bytes("users", "bad")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ensure_table_and_table_names() {
+        let tmp = NamedTempFile::new().unwrap();
+        let mut db = SpookyDb::new(tmp.path()).unwrap();
+
+        assert!(!db.table_exists("empty_table"));
+        db.ensure_table("empty_table").unwrap();
+        // ensure_table creates the ZSet entry, but table_exists checks for non-empty.
+        // An empty ZSet → table_exists returns false (no records yet).
+        assert!(!db.table_exists("empty_table"));
+        // But table_names() still lists it.
+        let names: Vec<&SmolStr> = db.table_names().collect();
+        assert!(names.contains(&&SmolStr::new("empty_table")));
+
+        // Table names containing U+001F must be rejected.
+        assert!(matches!(
+            db.ensure_table("bad\u{1f}table"),
+            Err(SpookyDbError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_long_keys_are_invalid() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"n":1}"#);
+        let fits = "x".repeat(MAX_KEY_LEN - 2);
+        let long = "x".repeat(MAX_KEY_LEN);
+        let invalid = |e: Option<SpookyDbError>| matches!(e, Some(SpookyDbError::InvalidKey(_)));
+
+        db.apply_mutation("t", Operation::Create, &fits, Some(&bytes), None)?;
+        let err = db.apply_mutation("t", Operation::Create, &long, Some(&bytes), None).err();
+        assert!(invalid(err));
+        // No such record can exist, so reads find nothing.
+        assert_eq!(db.get_record_bytes("t", &long)?, None);
+        let load = BulkRecord {
+            table: SmolStr::new("t"),
+            id: SmolStr::new(&long),
+            data: bytes.clone(),
+            version: None,
+        };
+        assert!(invalid(db.bulk_load(vec![load]).err()));
+
+        // In `apply_batches`, only the batch holding the key fails.
+        let write = |id: &str| DbMutation {
+            table: SmolStr::new("t"),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(bytes.clone()),
+            version: None,
+            expires_at: None,
+        };
+        let results = db.apply_batches(vec![vec![write(&long)], vec![write("ok")]])?;
+        assert!(matches!(results[0], Err(SpookyDbError::InvalidKey(_))));
+        assert!(results[1].is_ok());
+        assert_eq!(db.ids_sorted("t")?, ["ok", fits.as_str()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_cache_populated_on_create() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+
+        // get_record_bytes must return without touching redb.
+        assert_eq!(db.get_record_bytes("users", "alice")?, Some(data.clone()));
+
+        // get_row_record must return a valid borrowed record.
+        let record = db.get_row_record("users", "alice")?.expect("should be in cache");
+        let age = record.get_i64("age");
+        assert!(age.is_some(), "age field should be readable from cached record");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_cache_evicted_on_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
+
+        assert_eq!(db.get_record_bytes("users", "alice")?, None);
+        assert!(db.get_row_record("users", "alice")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_cache_rebuilt_on_reopen() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let db_path = tmp_dir.path().join("test.redb");
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        {
+            let mut db = SpookyDb::new(&db_path)?;
+            db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+        }
+
+        // After reopen: ZSet is rebuilt from RECORDS_TABLE; LRU cache starts cold.
+        let db2 = SpookyDb::new(&db_path)?;
+
+        // ZSet is correct — record is known present.
+        assert_eq!(db2.get_zset_weight("users", "alice"), 1);
+
+        // get_record_bytes falls back to redb on cache miss — still returns data.
+        assert_eq!(db2.get_record_bytes("users", "alice")?, Some(data));
+
+        // get_row_record returns None because the cache is cold after reopen.
+        assert!(
+            db2.get_row_record("users", "alice")?.is_none(),
+            "cold cache: get_row_record must return None until a write warms the entry"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_name_with_unit_separator_rejected() {
+        let tmp = NamedTempFile::new().unwrap();
+        let mut db = SpookyDb::new(tmp.path()).unwrap();
+        let result = db.apply_mutation("a\u{1f}b", Operation::Create, "id1", Some(&[]), None);
+        assert!(matches!(result, Err(SpookyDbError::InvalidKey(_))));
+        // Its ':' spelling would share the escaped keys.
+        assert!(validate_table_name("ns\u{1f}db").is_err());
+        assert!(validate_table_name("ns:db").is_ok());
+    }
+
+    #[test]
+    fn test_namespaced_table_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let (users, people) = ("ns:db:users", "ns:db:people");
+        let bytes = record(r#"{"name":"alice"}"#);
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.apply_mutation(users, Operation::Create, "a:1", Some(&bytes), Some(3))?;
+            db.apply_mutation(users, Operation::Create, "b", Some(&bytes), None)?;
+            // A table named like the prefix of the other stays separate.
+            db.apply_mutation("ns", Operation::Create, "db:users:c", Some(&bytes), None)?;
+            assert_eq!(db.get_record_bytes(users, "a:1")?, Some(bytes.clone()));
+            assert_eq!(db.ids_sorted(users)?, ["a:1", "b"]);
+            assert_eq!(db.ids_sorted("ns")?, ["db:users:c"]);
+            let mut ids: Vec<&str> = db.ids(users).map(SmolStr::as_str).collect();
+            ids.sort_unstable();
+            assert_eq!(ids, ["a:1", "b"]);
+
+            // The table's key range holds only its own keys, which split
+            // back into the unescaped name and the id.
+            let (lo, hi) = key_range(users);
+            let read_txn = db.db.begin_read()?;
+            let records = read_txn.open_table(RECORDS_TABLE)?;
+            let mut keys = Vec::new();
+            for entry in records.range::<&str>(lo.as_str()..hi.as_str())? {
+                let key = entry?.0.value().to_owned();
+                let (table, id) = split_key(&key).ok_or("key without ':'")?;
+                keys.push((table.into_owned(), id.to_owned()));
+            }
+            assert_eq!(keys, [(users.into(), "a:1".into()), (users.into(), "b".into())]);
+        }
+
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.table_len(users), 2);
+        assert_eq!(db.get_version(users, "a:1")?, Some(3));
+        assert_eq!(db.get_record_bytes(users, "b")?, Some(bytes.clone()));
+        assert_eq!(db.ids(users).count(), 2);
+        assert_eq!(db.scan_ids(users, "a")?, ["a:1"]);
+        let scanned: Vec<SmolStr> = db
+            .iter_table_ordered(users, SortDirection::Desc)?
+            .map(|entry| entry.map(|(id, _)| id))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(scanned, ["b", "a:1"]);
+        assert!(db.integrity_check(false)?.is_clean());
+
+        assert_eq!(db.rename_table(users, people)?, 2);
+        assert_eq!(db.get_version(people, "a:1")?, Some(3));
+        assert_eq!(db.truncate_table(people)?, 2);
+        assert_eq!(db.table_len("ns"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_id_keys_with_colon_table() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let rid = RecordId::parse("`ns:users`:a")?;
+        let table = rid.table();
+        let bytes = record(r#"{"name":"alice"}"#);
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.set_soft_delete(table, true)?;
+            db.apply_mutation(table, Operation::Create, rid.id(), Some(&bytes), None)?;
+            db.apply_mutation(table, Operation::Create, "b", Some(&bytes), None)?;
+            db.apply_mutation(table, Operation::Delete, "b", None, None)?;
+            assert!(db.set_expiry(table, rid.id(), Some(u64::MAX))?);
+
+            // The key on disk is the one `db_key` builds, and reads back.
+            let read_txn = db.db.begin_read()?;
+            let records = read_txn.open_table(RECORDS_TABLE)?;
+            let keys: Vec<String> = records
+                .iter()?
+                .map(|entry| entry.map(|(key, _)| key.value().to_owned()))
+                .collect::<Result<_, _>>()?;
+            assert_eq!(keys, [rid.db_key()?]);
+            assert_eq!(RecordId::from_db_key(&keys[0]).as_ref(), Some(&rid));
+        }
+
+        // Tombstones and expiries are rebuilt under the unescaped name.
+        let db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.get_record_bytes(rid.table(), rid.id())?, Some(bytes));
+        assert_eq!(db.expires_at(table, rid.id()), Some(u64::MAX));
+        let tombstoned: Vec<SmolStr> = db.tombstones(table).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(tombstoned, ["b"]);
+        assert!(db.tombstones("ns").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zset_not_diverged_after_create() -> Result<(), Box<dyn std::error::Error>> {
+        // Verify that ZSet and rows are in sync after apply_mutation.
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+        assert_eq!(db.get_zset_weight("users", "alice"), 1);
+        assert!(db.get_record_bytes("users", "alice")?.is_some());
+
+        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
+        assert_eq!(db.get_zset_weight("users", "alice"), 0);
+        assert!(db.get_record_bytes("users", "alice")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::unnecessary_map_or)]
+    fn test_delete_nonexistent_emits_no_delta() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+
+        let result = db.apply_batch(vec![DbMutation {
+            table: SmolStr::new("users"),
+            id: SmolStr::new("ghost"),
+            op: Operation::Delete,
+            data: None,
+            version: None,
+            expires_at: None,
+        }])?;
+
+        // No record was present → membership_deltas must be empty.
+        assert!(
+            result.membership_deltas.get("users").map_or(true, |z| z.is_empty()),
+            "spurious -1 delta emitted for a record that never existed"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dyn_dbbackend_compiles() {
+        // This test exists purely to assert DbBackend is object-safe.
+        // It will fail to compile if bulk_load still uses impl IntoIterator.
+        let tmp = NamedTempFile::new().unwrap();
+        let db = SpookyDb::new(tmp.path()).unwrap();
+        let _: Box<dyn DbBackend> = Box::new(db);
+    }
+
+    #[test]
+    fn test_cache_miss_falls_back_to_redb() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp_dir = tempfile::tempdir()?;
+        let db_path = tmp_dir.path().join("test.redb");
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        // Write a record and close the DB.
+        {
+            let mut db = SpookyDb::new(&db_path)?;
+            db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+        }
+
+        // Reopen — cache is cold but ZSet is rebuilt.
+        let db2 = SpookyDb::new(&db_path)?;
+        assert_eq!(db2.get_zset_weight("users", "alice"), 1); // ZSet present
+
+        // get_row_record returns None (cold cache after reopen).
+        assert!(db2.get_row_record("users", "alice")?.is_none());
+
+        // get_record_bytes falls back to redb — still returns data.
+        let fetched = db2
+            .get_record_bytes("users", "alice")?
+            .expect("redb fallback must work on cache miss");
+        assert_eq!(fetched, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_eviction_correctness() -> Result<(), Box<dyn std::error::Error>> {
+        // Cache capacity 2, insert 3 records. 3rd insert evicts the 1st.
+        // Verify: ZSet has all 3; get_record_bytes works for all 3 (redb fallback);
+        // get_row_record returns None for the evicted record.
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new_with_config(
+            tmp.path(),
+            SpookyDbConfig {
+                cache_capacity: std::num::NonZeroUsize::new(2).unwrap(),
+                ..Default::default()
+            },
+        )?;
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        db.apply_mutation("t", Operation::Create, "r1", Some(&data), None)?;
+        db.apply_mutation("t", Operation::Create, "r2", Some(&data), None)?;
+        db.apply_mutation("t", Operation::Create, "r3", Some(&data), None)?; // evicts r1
+
+        // ZSet has all 3.
+        assert_eq!(db.get_zset_weight("t", "r1"), 1);
+        assert_eq!(db.get_zset_weight("t", "r2"), 1);
+        assert_eq!(db.get_zset_weight("t", "r3"), 1);
+
+        // get_record_bytes works for all 3 (redb fallback for evicted r1).
+        assert!(db.get_record_bytes("t", "r1")?.is_some(), "redb fallback for evicted r1");
+        assert!(db.get_record_bytes("t", "r2")?.is_some());
+        assert!(db.get_record_bytes("t", "r3")?.is_some());
+
+        // get_row_record: r1 evicted, r2 and r3 still in cache.
+        assert!(db.get_row_record("t", "r1")?.is_none(), "r1 should be evicted from cache");
+        assert!(db.get_row_record("t", "r2")?.is_some(), "r2 should still be in cache");
+        assert!(db.get_row_record("t", "r3")?.is_some(), "r3 should be in cache");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_capacity_bounds_memory() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new_with_config(
+            tmp.path(),
+            SpookyDbConfig {
+                cache_capacity: std::num::NonZeroUsize::new(5).unwrap(),
+                ..Default::default()
+            },
+        )?;
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        // Insert 10 records into a cache of capacity 5.
+        for i in 0u32..10 {
+            let id = format!("r{i}");
+            db.apply_mutation("t", Operation::Create, &id, Some(&data), None)?;
+        }
+
+        // ZSet has all 10.
+        assert_eq!(db.table_len("t"), 10);
+
+        // Cache has at most 5.
+        let cached_count = (0u32..10)
+            .filter(|i| db.get_row_record("t", &format!("r{i}")).ok().flatten().is_some())
+            .count();
+        assert!(cached_count <= 5, "cache exceeded capacity: {cached_count} entries cached");
+
+        // get_record_bytes works for all 10 via redb fallback.
+        for i in 0u32..10 {
+            let id = format!("r{i}");
+            assert!(
+                db.get_record_bytes("t", &id)?.is_some(),
+                "redb fallback failed for r{i}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_removes_from_cache() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        db.apply_mutation("t", Operation::Create, "r1", Some(&data), None)?;
+        assert!(db.get_row_record("t", "r1")?.is_some(), "r1 should be in cache after create");
+
+        db.apply_mutation("t", Operation::Delete, "r1", None, None)?;
+        // ZSet and cache must both be gone; ZSet guard prevents redb read.
+        assert_eq!(db.get_zset_weight("t", "r1"), 0);
+        assert!(db.get_row_record("t", "r1")?.is_none());
+        assert!(db.get_record_bytes("t", "r1")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_row_record_zero_copy() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        // Non-existent record returns None.
+        assert!(db.get_row_record("users", "alice")?.is_none());
+
+        // Insert a record, then verify we can read a field from the zero-copy view.
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+
+        let record = db.get_row_record("users", "alice")?.expect("should be in cache");
+        // The CBOR fixture has "age" = 28 (i64).
+        let age = record.get_i64("age");
+        assert!(age.is_some(), "age field should be readable from cached record");
+        assert_eq!(age.unwrap(), 28);
+
+        Ok(())
+    }
+
+    #[test]
+    fn zset_not_mutated_before_commit() {
+        use crate::spooky_value::{SpookyNumber, SpookyValue};
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
+
+        let mut buf = Vec::new();
+        let mut m = crate::spooky_value::FastMap::new();
+        m.insert(SmolStr::new("x"), SpookyValue::Number(SpookyNumber::I64(1)));
+        crate::serialization::serialize_into(&m, &mut buf).unwrap();
+
+        let result = db.apply_batch(vec![DbMutation {
+            table: SmolStr::new("users"),
+            id: SmolStr::new("u1"),
+            op: Operation::Create,
+            data: Some(buf),
+            version: None,
+            expires_at: None,
+        }]).unwrap();
+
+        let zset = db.get_table_zset("users").unwrap();
+        assert_eq!(zset.get("u1"), Some(&1i64));
+        assert_eq!(result.membership_deltas["users"].get("u1"), Some(&1i64));
+    }
+
+    #[test]
+    fn rejects_unit_separator_in_table_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
+
+        let result = db.apply_batch(vec![DbMutation {
+            table: SmolStr::new("bad\u{1f}name"),
+            id: SmolStr::new("rec1"),
+            op: Operation::Delete,
+            data: None,
+            version: None,
+            expires_at: None,
+        }]);
+
+        assert!(result.is_err());
+        let msg = result.unwrap_err().to_string();
+        assert!(msg.contains("U+001F"), "should name the character: {msg}");
+    }
+
+    #[test]
+    fn rejects_empty_table_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
+
+        let result = db.apply_batch(vec![DbMutation {
+            table: SmolStr::new(""),
+            id: SmolStr::new("rec1"),
+            op: Operation::Delete,
+            data: None,
+            version: None,
+            expires_at: None,
+        }]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn get_record_returns_none_for_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
+
+        let result = db.get_row_record("users", "nonexistent");
+        assert!(result.is_ok(), "expected Ok, got {result:?}");
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_schema_enforced_on_writes() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::schema::FieldType;
+
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (good, _) = from_cbor(&cbor)?;
+
+        db.set_schema(
+            "users",
+            Schema::new()
+                .required("name", FieldType::Str)
+                .required("age", FieldType::Int),
+        )?;
+        db.apply_mutation("users", Operation::Create, "alice", Some(&good), None)?;
+        let index = db.perfect_index("users").expect("built by set_schema").clone();
+        let stored = db.get_record_bytes("users", "alice")?.expect("present");
+        let (buf, count) = from_bytes(&stored)?;
+        let alice = IndexedRecord::new(SpookyRecord::new(buf, count), &index);
+        assert_eq!(alice.get_str("name"), SpookyRecord::new(buf, count).get_str("name"));
+
+        let bad = SpookyValue::from_json_str(r#"{"name":"Bob","age":"old"}"#)?;
+        let (bad, _) = crate::serialization::from_spooky(&bad)?;
+        let err = db
+            .apply_mutation("users", Operation::Create, "bob", Some(&bad), None)
+            .unwrap_err();
+        assert!(matches!(err, SpookyDbError::SchemaViolation { ref id, .. } if id == "bob"));
+        assert_eq!(db.get_zset_weight("users", "bob"), 0);
+
+        // One bad record rejects the whole batch before anything is written.
+        let batch = vec![
+            DbMutation {
+                table: SmolStr::new("users"),
+                id: SmolStr::new("carol"),
+                op: Operation::Create,
+                data: Some(good.clone()),
+                version: None,
+                expires_at: None,
+            },
+            DbMutation {
+                table: SmolStr::new("users"),
+                id: SmolStr::new("bob"),
+                op: Operation::Create,
+                data: Some(bad.clone()),
+                version: None,
+                expires_at: None,
+            },
+        ];
+        assert!(db.apply_batch(batch).is_err());
+        assert_eq!(db.table_len("users"), 1);
+
+        // Other tables and deletes are unaffected; clearing lifts enforcement.
+        db.apply_mutation("posts", Operation::Create, "p1", Some(&bad), None)?;
+        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
+        assert!(db.clear_schema("users").is_some());
+        assert!(db.perfect_index("users").is_none());
+        db.apply_mutation("users", Operation::Create, "bob", Some(&bad), None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_ids_and_ranges() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        for id in ["01H5", "01H6", "01H7:a", "01J0", "02A0"] {
+            db.apply_mutation("events", Operation::Create, id, Some(&data), None)?;
+        }
+        // Neighbouring tables must never leak into a scan.
+        db.apply_mutation("event", Operation::Create, "01H5", Some(&data), None)?;
+        db.apply_mutation("events2", Operation::Create, "01H5", Some(&data), None)?;
+
+        assert_eq!(db.scan_ids("events", "01H")?, ["01H5", "01H6", "01H7:a"]);
+        assert_eq!(db.scan_ids("events", "")?.len(), 5);
+        assert!(db.scan_ids("events", "9")?.is_empty());
+
+        assert_eq!(
+            db.scan_range("events", "01H6".."02")?,
+            ["01H6", "01H7:a", "01J0"]
+        );
+        assert_eq!(db.scan_range("events", "01J0"..)?, ["01J0", "02A0"]);
+        assert_eq!(db.scan_range("events", ..="01H5")?, ["01H5"]);
+        assert_eq!(db.scan_range("events", ..)?.len(), 5);
+        assert!(db.scan_range("events", "02".."01")?.is_empty());
+
+        let rows = db.scan_range_records("events", "01J0".."02A0")?;
+        assert_eq!(rows, vec![(SmolStr::new("01J0"), data.clone())]);
+
+        db.apply_mutation("events", Operation::Delete, "01H6", None, None)?;
+        assert_eq!(db.scan_ids("events", "01H")?, ["01H5", "01H7:a"]);
+        assert!(db.scan_ids("bad\u{1f}table", "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_constraint() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let user = |email: &str| {
+            let v = SpookyValue::from_json_str(&format!(r#"{{"email":"{email}"}}"#)).unwrap();
+            crate::serialization::from_spooky(&v).unwrap().0
+        };
+        let create = |id: &str, email: &str| DbMutation {
+            table: SmolStr::new("users"),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(user(email)),
+            version: None,
+            expires_at: None,
+        };
+
+        db.apply_mutation("users", Operation::Create, "a", Some(&user("x@y")), None)?;
+        db.apply_mutation("users", Operation::Create, "b", Some(&user("x@y")), None)?;
+        // Existing duplicates block the declaration.
+        let err = db.add_unique("users", "email").unwrap_err();
+        assert!(matches!(err, SpookyDbError::UniqueViolation { .. }));
+        assert_eq!(db.unique_fields("users").count(), 0);
+
+        db.apply_mutation("users", Operation::Update, "b", Some(&user("b@y")), None)?;
+        db.add_unique("users", "email")?;
+        assert_eq!(db.unique_fields("users").collect::<Vec<_>>(), ["email"]);
+
+        let err = db.apply_batch(vec![create("c", "x@y")]).unwrap_err();
+        match err {
+            SpookyDbError::UniqueViolation {
+                field,
+                value,
+                existing_id,
+            } => {
+                assert_eq!(field, "email");
+                assert_eq!(value, SpookyValue::from("x@y"));
+                assert_eq!(existing_id, "a");
+            }
+            other => panic!("expected UniqueViolation, got {other:?}"),
+        }
+        assert_eq!(db.get_zset_weight("users", "c"), 0);
+
+        // Rewriting an id with its own value is fine.
+        db.apply_mutation("users", Operation::Update, "a", Some(&user("x@y")), None)?;
+
+        // Two ids claiming one value in the same batch.
+        assert!(
+            db.apply_batch(vec![create("c", "new"), create("d", "new")])
+                .is_err()
+        );
+        assert_eq!(db.table_len("users"), 2);
+
+        // Moving a value within a batch: release first, then claim.
+        let mut release = create("a", "a@y");
+        release.op = Operation::Update;
+        db.apply_batch(vec![release, create("c", "x@y")])?;
+        assert_eq!(db.table_len("users"), 3);
+
+        // Deletes free the value; null fields are never constrained.
+        db.apply_mutation("users", Operation::Delete, "c", None, None)?;
+        db.apply_mutation("users", Operation::Create, "e", Some(&user("x@y")), None)?;
+        let none = record(r#"{"email":null}"#);
+        db.apply_mutation("users", Operation::Create, "f", Some(&none), None)?;
+        db.apply_mutation("users", Operation::Create, "g", Some(&none), None)?;
+
+        assert!(db.drop_unique("users", "email"));
+        db.apply_mutation("users", Operation::Create, "h", Some(&user("x@y")), None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_cached_and_evicted() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            cache_capacity: std::num::NonZeroUsize::new(4).unwrap(),
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        for i in 0..10i64 {
+            let v = SpookyValue::from_json_str(&format!(r#"{{"n":{i}}}"#))?;
+            let (bytes, _) = crate::serialization::from_spooky(&v)?;
+            let id = format!("k{i}");
+            db.apply_mutation("nums", Operation::Create, &id, Some(&bytes), None)?;
+        }
+
+        // Six records are on disk only, four in cache: both paths run.
+        let mut evens = db.query("nums", |r| r.get_i64("n").is_some_and(|n| n % 2 == 0))?;
+        evens.sort();
+        assert_eq!(evens, ["k0", "k2", "k4", "k6", "k8"]);
+
+        let mut seen = 0;
+        db.query("nums", |_| {
+            seen += 1;
+            false
+        })?;
+        assert_eq!(seen, 10, "each record is evaluated exactly once");
+
+        db.apply_mutation("nums", Operation::Delete, "k0", None, None)?;
+        assert_eq!(db.query("nums", |_| true)?.len(), 9);
+        assert!(db.query("missing", |_| true)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_sorted_bounded() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            cache_capacity: std::num::NonZeroUsize::new(3).unwrap(),
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let rows = [
+            ("a", r#"{"score":5}"#),
+            ("b", r#"{"score":9.5}"#),
+            ("c", r#"{"score":-2}"#),
+            ("d", r#"{"other":1}"#),
+            ("e", r#"{"score":5}"#),
+            ("f", r#"{"score":100}"#),
+        ];
+        for (id, json) in rows {
+            let bytes = record(json);
+            db.apply_mutation("players", Operation::Create, id, Some(&bytes), None)?;
+        }
+
+        let top = db.query_sorted("players", "score", SortDirection::Desc, 3)?;
+        assert_eq!(top, ["f", "b", "a"]);
+        let all = db.query_sorted("players", "score", SortDirection::Asc, 100)?;
+        assert_eq!(all, ["d", "c", "a", "e", "b", "f"]);
+        let last = db.query_sorted("players", "score", SortDirection::Desc, 10)?;
+        assert_eq!(last.last().map(SmolStr::as_str), Some("d"));
+        let none = db.query_sorted("players", "score", SortDirection::Asc, 0)?;
+        assert!(none.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregate_grouped_and_delta() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let rows = [
+            ("o1", r#"{"region":"eu","amount":10}"#),
+            ("o2", r#"{"region":"eu","amount":2.5}"#),
+            ("o3", r#"{"region":"us","amount":7}"#),
+            ("o4", r#"{"region":"us","amount":"n/a"}"#),
+            ("o5", r#"{"amount":1}"#),
+        ];
+        for (id, json) in rows {
+            let bytes = record(json);
+            db.apply_mutation("orders", Operation::Create, id, Some(&bytes), None)?;
+        }
+
+        let mut agg = Aggregator::new("amount").group_by("region");
+        db.aggregate("orders", &mut agg)?;
+        let groups = agg.finish();
+        assert_eq!(groups.len(), 3);
+        let eu = &groups[&SpookyValue::from("eu")];
+        assert_eq!(
+            (eu.count, eu.sum, eu.min, eu.max),
+            (2, 12.5, Some(2.5), Some(10.0))
+        );
+        assert_eq!(eu.avg(), Some(6.25));
+        let us = &groups[&SpookyValue::from("us")];
+        assert_eq!((us.count, us.numeric, us.avg()), (2, 1, Some(7.0)));
+        assert_eq!(groups[&SpookyValue::Null].sum, 1.0);
+
+        let mut total = Aggregator::new("amount");
+        db.aggregate("orders", &mut total)?;
+        let total = total.total();
+        assert_eq!((total.count, total.sum), (5, 20.5));
+
+        // Delta fold: retract o1, count o3 again; absent ids are skipped.
+        let mut delta = ZSet::default();
+        delta.insert(SmolStr::new("o1"), -1);
+        delta.insert(SmolStr::new("o3"), 1);
+        delta.insert(SmolStr::new("gone"), -1);
+        let mut agg = Aggregator::new("amount").group_by("region");
+        db.aggregate_delta("orders", &delta, &mut agg)?;
+        let groups = agg.finish();
+        assert_eq!(groups[&SpookyValue::from("eu")].count, -1);
+        assert_eq!(groups[&SpookyValue::from("eu")].sum, -10.0);
+        assert_eq!(groups[&SpookyValue::from("eu")].min, None);
+        assert_eq!(groups[&SpookyValue::from("us")].sum, 7.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_change_events() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        let ids_only = db.subscribe("users")?;
+        let with_data = db.subscribe_with_data("users")?;
+        let dropped = db.subscribe("users")?;
+        drop(dropped);
+
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), Some(1))?;
+        db.apply_mutation("posts", Operation::Create, "p1", Some(&data), None)?;
+        db.apply_batch(vec![
+            DbMutation {
+                table: SmolStr::new("users"),
+                id: SmolStr::new("alice"),
+                op: Operation::Update,
+                data: Some(data.clone()),
+                version: Some(2),
+                expires_at: None,
+            },
+            DbMutation {
+                table: SmolStr::new("users"),
+                id: SmolStr::new("ghost"),
+                op: Operation::Delete,
+                data: None,
+                version: None,
+                expires_at: None,
+            },
+        ])?;
+        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
+
+        let events: Vec<ChangeEvent> = ids_only.try_iter().collect();
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| (e.id.as_str(), e.op, e.version))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("alice", Operation::Create, Some(1)),
+                ("alice", Operation::Update, Some(2)),
+                ("alice", Operation::Delete, None),
+            ]
+        );
+        assert!(events.iter().all(|e| e.data.is_none()));
+
+        let events: Vec<ChangeEvent> = with_data.try_iter().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].data.as_deref(), Some(data.as_slice()));
+        assert!(events[2].data.is_none());
+
+        // The dropped receiver was pruned; dropping the rest empties the registry.
+        assert_eq!(db.subscribers["users"].len(), 2);
+        drop((ids_only, with_data));
+        db.apply_mutation("users", Operation::Create, "bob", Some(&data), None)?;
+        assert!(db.subscribers.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_oplog_records_and_resumes() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        let full = || SpookyDbConfig {
+            oplog: OplogMode::Full,
+            ..Default::default()
+        };
+        {
+            let mut db = SpookyDb::new_with_config(tmp.path(), full())?;
+            assert_eq!(db.oplog_last_seq(), 0);
+            db.apply_mutation("users", Operation::Create, "alice", Some(&data), Some(1))?;
+            db.apply_batch(vec![
+                DbMutation {
+                    table: SmolStr::new("users"),
+                    id: SmolStr::new("bob"),
+                    op: Operation::Create,
+                    data: Some(data.clone()),
+                    version: None,
+                    expires_at: None,
+                },
+                DbMutation {
+                    table: SmolStr::new("users"),
+                    id: SmolStr::new("alice"),
+                    op: Operation::Delete,
+                    data: None,
+                    version: Some(2),
+                    expires_at: None,
+                },
+            ])?;
+            assert_eq!(db.oplog_last_seq(), 3);
+        }
+
+        // Sequence numbers resume after reopen.
+        let mut db = SpookyDb::new_with_config(tmp.path(), full())?;
+        db.bulk_load(vec![BulkRecord {
+            table: SmolStr::new("posts"),
+            id: SmolStr::new("p1"),
+            data: data.clone(),
+            version: None,
+        }])?;
+        let log = db.read_oplog(0)?;
+        let summary: Vec<_> = log
+            .iter()
+            .map(|e| (e.seq, e.id.as_str(), e.op, e.version))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1, "alice", Operation::Create, Some(1)),
+                (2, "bob", Operation::Create, None),
+                (3, "alice", Operation::Delete, Some(2)),
+                (4, "p1", Operation::Create, None),
+            ]
+        );
+        assert_eq!(log[0].data.as_deref(), Some(data.as_slice()));
+        assert!(log[2].data.is_none());
+        assert_eq!(log[3].table, "posts");
+        assert_eq!(db.read_oplog(3)?.len(), 1);
+
+        let mut seen = Vec::new();
+        let last = db.replay(1, |table, id, op, bytes| {
+            seen.push((table.to_string(), id.to_string(), op, bytes.map(<[u8]>::len)));
+        })?;
+        assert_eq!(last, 4);
+        assert_eq!(seen.len(), 3);
+        assert_eq!(seen[0], ("users".into(), "bob".into(), Operation::Create, Some(data.len())));
+        assert_eq!(seen[1].3, None);
+        assert_eq!(db.replay(4, |_, _, _, _| panic!("nothing after 4"))?, 4);
+
+        assert_eq!(db.trim_oplog(2)?, 2);
+        assert_eq!(db.read_oplog(0)?.first().map(|e| e.seq), Some(3));
+        assert_eq!(db.replay(2, |_, _, _, _| {})?, 4);
+        assert!(matches!(
+            db.replay(0, |_, _, _, _| panic!("trimmed")),
+            Err(SpookyDbError::OplogTrimmed { requested: 0, oldest: 3 })
+        ));
+        drop(db);
+
+        // Metadata mode drops the bytes; Off records nothing.
+        let meta = SpookyDbConfig {
+            oplog: OplogMode::Metadata,
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), meta)?;
+        db.apply_mutation("users", Operation::Update, "bob", Some(&data), None)?;
+        assert_eq!(db.read_oplog(4)?[0].data, None);
+        drop(db);
+        let mut db = SpookyDb::new(tmp.path())?;
+        db.apply_mutation("users", Operation::Update, "bob", Some(&data), None)?;
+        assert_eq!(db.oplog_last_seq(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_from_start_and_mid_log() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            oplog: OplogMode::Full,
+            ..SpookyDbConfig::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let (v1, v2) = (record(r#"{"n":1}"#), record(r#"{"n":2}"#));
+        db.apply_mutation("users", Operation::Create, "a", Some(&v1), None)?;
+        db.apply_mutation("users", Operation::Update, "a", Some(&v2), None)?;
+        db.apply_mutation("posts", Operation::Create, "p", Some(&v1), None)?;
+        db.apply_mutation("users", Operation::Delete, "a", None, None)?;
+
+        type Call = (String, String, Operation, Option<Vec<u8>>);
+        let replay = |db: &SpookyDb, since| -> Result<(u64, Vec<Call>), SpookyDbError> {
+            let mut calls = Vec::new();
+            let last = db.replay(since, |table, id, op, bytes| {
+                calls.push((table.to_owned(), id.to_owned(), op, bytes.map(<[u8]>::to_vec)));
+            })?;
+            Ok((last, calls))
+        };
+        let call = |table: &str, id: &str, op, bytes: Option<&Vec<u8>>| {
+            (table.to_owned(), id.to_owned(), op, bytes.cloned())
+        };
+
+        let (last, calls) = replay(&db, 0)?;
+        assert_eq!(last, 4);
+        assert_eq!(
+            calls,
+            [
+                call("users", "a", Operation::Create, Some(&v1)),
+                call("users", "a", Operation::Update, Some(&v2)),
+                call("posts", "p", Operation::Create, Some(&v1)),
+                call("users", "a", Operation::Delete, None),
+            ]
+        );
+
+        // From the middle: only what came after, and the last seq again.
+        let (last, calls) = replay(&db, 2)?;
+        assert_eq!(last, 4);
+        assert_eq!(
+            calls,
+            [
+                call("posts", "p", Operation::Create, Some(&v1)),
+                call("users", "a", Operation::Delete, None),
+            ]
+        );
+        assert_eq!(replay(&db, 4)?, (4, Vec::new()));
+
+        // Once the head is trimmed, resuming before it fails without a call.
+        db.trim_oplog(2)?;
+        let err = replay(&db, 1);
+        assert!(matches!(
+            err,
+            Err(SpookyDbError::OplogTrimmed { requested: 1, oldest: 3 })
+        ));
+        assert_eq!(replay(&db, 2)?.1.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_mutation_cas() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+
+        db.apply_mutation_cas("docs", Operation::Create, "d1", None, Some(&data), Some(1))?;
+        // A second "create if absent" loses the race.
+        let err = db
+            .apply_mutation_cas("docs", Operation::Create, "d1", None, Some(&data), Some(1))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SpookyDbError::VersionConflict {
+                expected: None,
+                actual: Some(1)
+            }
+        ));
+
+        let update = Operation::Update;
+        db.apply_mutation_cas("docs", update, "d1", Some(1), Some(&data), Some(2))?;
+        let err = db
+            .apply_mutation_cas("docs", Operation::Update, "d1", Some(1), Some(&[]), Some(3))
+            .unwrap_err();
+        let SpookyDbError::VersionConflict { actual, .. } = err else {
+            panic!("expected VersionConflict, got {err:?}");
+        };
+        assert_eq!(actual, Some(2));
+        // The rejected write left disk and memory untouched.
+        assert_eq!(db.get_version("docs", "d1")?, Some(2));
+        assert_eq!(db.get_record_bytes("docs", "d1")?, Some(data.clone()));
+
+        db.apply_mutation_cas("docs", Operation::Delete, "d1", Some(2), None, None)?;
+        assert_eq!(db.get_zset_weight("docs", "d1"), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_soft_delete_tombstones() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.set_soft_delete("notes", true)?;
+            assert!(db.is_soft_delete("notes"));
+            for id in ["n1", "n2", "n3"] {
+                db.apply_mutation("notes", Operation::Create, id, Some(&data), Some(1))?;
+            }
+            db.apply_mutation("posts", Operation::Create, "p1", Some(&data), None)?;
+
+            db.apply_mutation("notes", Operation::Delete, "n1", None, None)?;
+            db.apply_batch(vec![DbMutation {
+                table: SmolStr::new("notes"),
+                id: SmolStr::new("n2"),
+                op: Operation::Delete,
+                data: None,
+                version: None,
+                expires_at: None,
+            }])?;
+            db.apply_mutation("posts", Operation::Delete, "p1", None, None)?;
+
+            // Absent to reads, present as tombstones.
+            assert_eq!(db.get_zset_weight("notes", "n1"), 0);
+            assert!(db.get_record_bytes("notes", "n1")?.is_none());
+            assert_eq!(db.scan_ids("notes", "")?, ["n3"]);
+            assert!(db.tombstone("notes", "n1").is_some());
+            assert!(db.tombstones("posts").is_empty(), "hard-delete table");
+        }
+
+        // Tombstones persist; recreating an id clears its tombstone.
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert!(!db.is_soft_delete("notes"));
+        let tombstones = db.tombstones("notes");
+        assert_eq!(tombstones.len(), 2);
+        assert_eq!(tombstones[0].0, "n1");
+        assert_eq!(tombstones[1].0, "n2");
+        db.apply_mutation("notes", Operation::Create, "n1", Some(&data), None)?;
+        assert_eq!(db.tombstone("notes", "n1"), None);
+        drop(db);
+
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.tombstones("notes").len(), 1);
+        assert_eq!(db.purge_tombstones(0)?, 0);
+        assert_eq!(db.purge_tombstones(u64::MAX)?, 1);
+        assert!(db.tombstones("notes").is_empty());
+        drop(db);
+        assert!(SpookyDb::new(tmp.path())?.tombstones("notes").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ttl_sweep_expired() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        let session = |id: &str, expires_at: Option<u64>| DbMutation {
+            table: SmolStr::new("sessions"),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(data.clone()),
+            version: None,
+            expires_at,
+        };
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.apply_batch(vec![
+                session("s1", Some(100)),
+                session("s2", Some(200)),
+                session("s3", None),
+                session("s4", Some(150)),
+            ])?;
+            // Deleting a record drops its expiry.
+            db.apply_mutation("sessions", Operation::Delete, "s4", None, None)?;
+            assert_eq!(db.expires_at("sessions", "s4"), None);
+            assert!(db.set_expiry("sessions", "s3", Some(300))?);
+            assert!(!db.set_expiry("sessions", "missing", Some(1))?);
+        }
+
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.expires_at("sessions", "s2"), Some(200));
+        assert!(db.sweep_expired(99)?.membership_deltas.is_empty());
+
+        let result = db.sweep_expired(200)?;
+        let swept = &result.membership_deltas["sessions"];
+        assert_eq!(swept.len(), 2);
+        assert_eq!((swept["s1"], swept["s2"]), (-1, -1));
+        assert_eq!(db.table_len("sessions"), 1);
+        assert_eq!(db.expires_at("sessions", "s1"), None);
+
+        // Clearing an expiry keeps the record past its old deadline.
+        db.set_expiry("sessions", "s3", None)?;
+        assert!(db.sweep_expired(u64::MAX)?.changed_tables.is_empty());
+        assert_eq!(db.table_len("sessions"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_batches_rejects_per_batch() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        db.add_unique("users", "email")?;
+        let create = |id: &str, email: &str| DbMutation {
+            table: SmolStr::new("users"),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(
+                crate::serialization::from_spooky(
+                    &SpookyValue::from_json_str(&format!(r#"{{"email":"{email}"}}"#)).unwrap(),
+                )
+                .unwrap()
+                .0,
+            ),
+            version: None,
+            expires_at: None,
+        };
+
+        // The second batch collides with the first; the third is independent.
+        let results = db.apply_batches(vec![
+            vec![create("a", "x@y")],
+            vec![create("b", "x@y"), create("c", "c@y")],
+            vec![create("d", "d@y")],
+        ])?;
+        assert_eq!(results.len(), 3);
+        let first = results[0].as_ref().unwrap();
+        assert_eq!(first.membership_deltas["users"]["a"], 1);
+        assert!(matches!(
+            results[1],
+            Err(SpookyDbError::UniqueViolation { .. })
+        ));
+        assert_eq!(results[2].as_ref().unwrap().changed_tables, vec!["users"]);
+        assert_eq!(db.table_len("users"), 2);
+        assert_eq!(db.get_zset_weight("users", "c"), 0);
+
+        // The rejected batch left no trace in the unique index.
+        let c = create("c", "c@y").data.unwrap();
+        db.apply_mutation("users", Operation::Create, "c", Some(&c), None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_coalescing_window() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            coalesce: Some(CoalesceConfig {
+                max_delay: Duration::from_secs(3600),
+                max_mutations: 3,
+            }),
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let durable = Arc::new(AtomicUsize::new(0));
+        let data = Some(BENCH_CBOR);
+        let count = |durable: &Arc<AtomicUsize>| {
+            let durable = Arc::clone(durable);
+            move |r: Result<(), &SpookyDbError>| {
+                assert!(r.is_ok());
+                durable.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+
+        let on_durable = count(&durable);
+        db.apply_mutation_durable("t", Operation::Create, "a", data, None, on_durable)?;
+        db.apply_mutation("t", Operation::Create, "b", Some(BENCH_CBOR), Some(2))?;
+        // Buffered: not committed, not visible.
+        assert_eq!(db.pending_len(), 2);
+        assert_eq!(db.get_zset_weight("t", "a"), 0);
+        assert_eq!(durable.load(Ordering::SeqCst), 0);
+
+        // The third mutation fills the window.
+        let on_durable = count(&durable);
+        db.apply_mutation_durable("t", Operation::Create, "c", data, None, on_durable)?;
+        assert_eq!(db.pending_len(), 0);
+        assert_eq!(durable.load(Ordering::SeqCst), 2);
+        assert_eq!(db.table_len("t"), 3);
+        assert_eq!(db.get_version("t", "b")?, Some(2));
+
+        // Validation errors are immediate and reach the callback.
+        let failed = Arc::new(AtomicUsize::new(0));
+        let failed_cb = Arc::clone(&failed);
+        let on_durable = move |r: Result<(), &SpookyDbError>| {
+            assert!(r.is_err());
+            failed_cb.fetch_add(1, Ordering::SeqCst);
+        };
+        let err =
+            db.apply_mutation_durable("bad\u{1f}t", Operation::Create, "x", data, None, on_durable);
+        assert!(matches!(err, Err(SpookyDbError::InvalidKey(_))));
+        assert_eq!(failed.load(Ordering::SeqCst), 1);
+
+        // Other write paths commit the buffer first, keeping call order.
+        db.apply_mutation("t", Operation::Delete, "a", None, None)?;
+        db.apply_batch(vec![DbMutation {
+            table: SmolStr::new("t"),
+            id: SmolStr::new("a"),
+            op: Operation::Create,
+            data: Some(BENCH_CBOR.to_vec()),
+            version: None,
+            expires_at: None,
+        }])?;
+        assert_eq!(db.get_zset_weight("t", "a"), 1);
+
+        // Dropping flushes what is left.
+        db.apply_mutation("t", Operation::Delete, "c", None, None)?;
+        assert_eq!(db.flush_if_due()?, 0);
+        drop(db);
+        let db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.get_zset_weight("t", "c"), 0);
+        assert_eq!(db.table_len("t"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_durability_levels() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            durability: Durability::None,
+            redb_cache_size: Some(1 << 20),
+            ..Default::default()
+        };
+        {
+            let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+            db.apply_mutation("t", Operation::Create, "a", Some(BENCH_CBOR), None)?;
+            // Switching back to Immediate makes the bulk phase durable.
+            db.set_durability(Durability::Immediate)?;
+            assert_eq!(db.durability(), Durability::Immediate);
+            db.set_durability(Durability::Eventual)?;
+            db.apply_mutation("t", Operation::Create, "b", Some(BENCH_CBOR), None)?;
+            // Dropping syncs whatever is left.
+        }
+        let db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.table_len("t"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_reclaims_deleted_space() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let json = format!(r#"{{"blob":"{}"}}"#, "x".repeat(64 * 1024));
+        let blob = SpookyValue::from_json_str(&json)?;
+        let (record, _) = crate::serialization::from_spooky(&blob)?;
+        let creates = (0..64)
+            .map(|i| DbMutation {
+                table: SmolStr::new("t"),
+                id: SmolStr::new(format!("r{i}")),
+                op: Operation::Create,
+                data: Some(record.clone()),
+                version: None,
+                expires_at: None,
+            })
+            .collect();
+        db.apply_batch(creates)?;
+        let deletes = (0..64)
+            .map(|i| DbMutation {
+                table: SmolStr::new("t"),
+                id: SmolStr::new(format!("r{i}")),
+                op: Operation::Delete,
+                data: None,
+                version: None,
+                expires_at: None,
+            })
+            .collect();
+        db.apply_batch(deletes)?;
+
+        let report = db.compact()?;
+        assert!(report.compacted);
+        assert!(report.bytes_after < report.bytes_before);
+        assert_eq!(report.reclaimed(), report.bytes_before - report.bytes_after);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_check_detects_and_repairs() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        db.apply_mutation("t", Operation::Create, "a", Some(BENCH_CBOR), None)?;
+        db.apply_mutation("t", Operation::Create, "b", Some(BENCH_CBOR), None)?;
+        assert!(db.integrity_check(false)?.is_clean());
+
+        // Simulate a higher layer corrupting memory.
+        let zset = db.zsets.loaded_mut("t");
+        zset.remove("a");
+        zset.insert(SmolStr::new("b"), 3);
+        zset.insert(SmolStr::new("ghost"), 1);
+        db.row_cache
+            .put((SmolStr::new("t"), SmolStr::new("b")), vec![0; 4].into());
+
+        let report = db.integrity_check(false)?;
+        assert_eq!(report.records_scanned, 2);
+        assert_eq!(report.missing_from_zset, [("t".into(), "a".into())]);
+        assert_eq!(report.missing_from_disk, [("t".into(), "ghost".into())]);
+        assert_eq!(report.wrong_weight, [("t".into(), "b".into(), 3)]);
+        assert_eq!(report.stale_cache, [("t".into(), "b".into())]);
+        assert!(!report.repaired);
+
+        assert!(db.integrity_check(true)?.repaired);
+        assert!(db.integrity_check(false)?.is_clean());
+        assert_eq!(db.get_zset_weight("t", "a"), 1);
+        assert_eq!(db.get_zset_weight("t", "ghost"), 0);
+        assert_eq!(db.get_record_bytes("t", "b")?.as_deref(), Some(BENCH_CBOR));
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_counts_persist_and_zsets_load_lazily() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            for id in ["a", "b", "c"] {
+                db.apply_mutation("users", Operation::Create, id, Some(BENCH_CBOR), None)?;
+            }
+            db.apply_mutation("users", Operation::Delete, "b", None, None)?;
+            db.apply_mutation("users", Operation::Update, "c", Some(BENCH_CBOR), None)?;
+            db.apply_mutation("posts", Operation::Create, "p", Some(BENCH_CBOR), None)?;
+            db.apply_mutation("posts", Operation::Delete, "p", None, None)?;
+        }
+
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert!(db.zsets.peek("users").is_none());
+        assert_eq!(db.table_len("users"), 2);
+        assert!(db.table_exists("users"));
+        assert!(!db.table_exists("posts"));
+        assert_eq!(db.table_names().collect::<Vec<_>>(), ["users"]);
+        // First access loads the table.
+        assert_eq!(db.get_zset_weight("users", "c"), 1);
+        assert_eq!(db.zsets.peek("users").map(|z| z.len()), Some(2));
+        assert!(db.integrity_check(false)?.is_clean());
+
+        // A wrong persisted count is reported and rewritten.
+        {
+            let write_txn = db.db.begin_write()?;
+            let wrong = TableStats {
+                records: 5,
+                bytes: 1,
+            };
+            zsets::write_stats(&write_txn, &[(SmolStr::new("users"), wrong)])?;
+            write_txn.commit()?;
+        }
+        drop(db);
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.table_len("users"), 5);
+        let report = db.integrity_check(true)?;
+        let (table, persisted, on_disk) = &report.wrong_table_stats[0];
+        assert_eq!((table.as_str(), persisted.records), ("users", 5));
+        let bytes = 2 * BENCH_CBOR.len() as u64;
+        assert_eq!(*on_disk, TableStats { records: 2, bytes });
+        drop(db);
+        let db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.table_len("users"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_without_table_counts_scans_once() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.apply_mutation("users", Operation::Create, "a", Some(BENCH_CBOR), None)?;
+            // Simulate a file written before META_TABLE existed.
+            let write_txn = db.db.begin_write()?;
+            write_txn.delete_table(META_TABLE)?;
+            write_txn.commit()?;
+        }
+        let db = SpookyDb::new(tmp.path())?;
+        assert!(db.zsets.peek("users").is_some());
+        assert_eq!(db.table_len("users"), 1);
+        drop(db);
+        let db = SpookyDb::new(tmp.path())?;
+        assert!(db.zsets.peek("users").is_none());
+        assert_eq!(db.table_len("users"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_counters_and_hook() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            cache_capacity: std::num::NonZeroUsize::new(2).unwrap(),
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        db.set_stats_hook(Duration::ZERO, move |stats| {
+            sink.lock().unwrap().push(stats.commits);
+        });
+        for id in ["a", "b", "c"] {
+            db.apply_mutation("t", Operation::Create, id, Some(BENCH_CBOR), None)?;
+        }
+        db.apply_mutation("t", Operation::Delete, "b", None, None)?;
+        assert!(db.get_record_bytes("t", "a")?.is_some()); // evicted: miss
+        assert!(db.get_record_bytes("t", "c")?.is_some()); // hit
+
+        let stats = db.stats();
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 1));
+        assert_eq!(stats.cache_evictions, 1);
+        assert_eq!((stats.cache_len, stats.cache_capacity), (1, 2));
+        assert_eq!(stats.commits, 4);
+        assert!(stats.max_commit_time <= stats.commit_time);
+        assert_eq!(stats.bytes_written, 3 * BENCH_CBOR.len() as u64);
+        let expected = TableStats {
+            records: 2,
+            bytes: 2 * BENCH_CBOR.len() as u64,
+        };
+        assert_eq!(stats.tables["t"], expected);
+        assert_eq!(*reports.lock().unwrap(), [1, 2, 3, 4]);
+
+        db.clear_stats_hook();
+        drop(db);
+        let db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.table_stats("t"), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timing_hook_reports_each_operation() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let ops = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&ops);
+        db.set_timing_hook(move |op, _| sink.lock().unwrap().push(op));
+
+        db.apply_mutation("t", Operation::Create, "a", Some(BENCH_CBOR), None)?;
+        let value = SpookyValue::Object(Default::default());
+        db.apply_mutation_value("t", Operation::Create, "b", Some(&value), None)?;
+        db.apply_batch(vec![DbMutation {
+            table: SmolStr::new("t"),
+            id: SmolStr::new("b"),
+            op: Operation::Delete,
+            data: None,
+            version: None,
+            expires_at: None,
+        }])?;
+        db.get_record_bytes("t", "a")?;
+        use TimedOp::*;
+        assert_eq!(
+            *ops.lock().unwrap(),
+            [Commit, Write, Encode, Commit, Write, Commit, Batch, Read]
+        );
+
+        db.clear_timing_hook();
+        db.get_record_bytes("t", "a")?;
+        assert_eq!(ops.lock().unwrap().len(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_max_bytes_bounds_memory() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        let weight = data.len() + "t".len() + "r0".len();
+        let mut db = SpookyDb::new_with_config(
+            tmp.path(),
+            SpookyDbConfig {
+                cache_max_bytes: Some(weight * 3),
+                ..Default::default()
+            },
+        )?;
+        for i in 0u32..10 {
+            db.apply_mutation("t", Operation::Create, &format!("r{i}"), Some(&data), None)?;
+        }
+        let stats = db.stats();
+        assert_eq!((stats.cache_len, stats.cache_bytes), (3, weight * 3));
+        assert_eq!(stats.cache_evictions, 7);
+        assert!(db.get_row_record("t", "r9")?.is_some());
+        assert!(db.get_row_record("t", "r0")?.is_none());
+        assert!(db.get_record_bytes("t", "r0")?.is_some());
+
+        // A record heavier than the whole bound is stored but never cached.
+        let big = vec![0u8; weight * 4];
+        db.apply_mutation("t", Operation::Create, "big", Some(&big), None)?;
+        assert!(db.get_row_record("t", "big")?.is_none());
+        let stored = db.get_record_bytes("t", "big")?;
+        assert_eq!(stored.as_deref(), Some(big.as_slice()));
+        assert_eq!(db.stats().cache_len, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pin_table_keeps_rows_resident() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new_with_config(
+            tmp.path(),
+            SpookyDbConfig {
+                cache_capacity: std::num::NonZeroUsize::new(2).unwrap(),
+                ..Default::default()
+            },
+        )?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        for i in 0u32..4 {
+            let id = format!("k{i}");
+            db.apply_mutation("lookup", Operation::Create, &id, Some(&data), None)?;
+        }
+        assert!(db.get_row_record("lookup", "k0")?.is_none());
+
+        // Pinning reads the whole table in, and churn elsewhere leaves it.
+        db.pin_table("lookup")?;
+        assert_eq!(db.cache_policy("lookup"), CachePolicy::Pinned);
+        db.apply_mutation("lookup", Operation::Create, "k4", Some(&data), None)?;
+        for i in 0u32..10 {
+            db.apply_mutation("t", Operation::Create, &format!("r{i}"), Some(&data), None)?;
+        }
+        for i in 0u32..5 {
+            assert!(db.get_row_record("lookup", &format!("k{i}"))?.is_some());
+        }
+        assert_eq!(db.stats().cache_len, 7);
+
+        // A reserved share bounds the table on its own.
+        let reserved = CachePolicy::Reserved {
+            entries: std::num::NonZeroUsize::new(3).unwrap(),
+            max_bytes: None,
+        };
+        db.set_cache_policy("lookup", reserved)?;
+        let stats = db.stats();
+        assert_eq!((stats.cache_len, stats.cache_capacity), (5, 5));
+        assert!(db.get_row_record("lookup", "k4")?.is_some());
+
+        db.unpin_table("lookup")?;
+        assert_eq!(db.cache_policy("lookup"), CachePolicy::Shared);
+        assert_eq!(db.stats().cache_len, 2);
+        assert!(db.pin_table("bad\u{1f}table").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_and_drop_table() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            oplog: OplogMode::Metadata,
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        for id in ["a", "b", "c"] {
+            db.apply_mutation("t", Operation::Create, id, Some(&data), Some(1))?;
+        }
+        db.apply_mutation("tt", Operation::Create, "x", Some(&data), None)?;
+        db.set_expiry("t", "a", Some(100))?;
+        db.set_soft_delete("t", true)?;
+        let rx = db.subscribe("t")?;
+        let seq = db.oplog_last_seq();
+
+        assert_eq!(db.truncate_table("t")?, 3);
+        assert_eq!(db.table_len("t"), 0);
+        assert!(db.table_names().any(|t| t == "t"));
+        assert!(db.get_record_bytes("t", "a")?.is_none());
+        assert_eq!(db.get_version("t", "a")?, None);
+        assert_eq!(db.expires_at("t", "a"), None);
+        assert_eq!(db.tombstones("t").len(), 3);
+        assert_eq!(db.read_oplog(seq)?.len(), 3);
+        assert_eq!(rx.try_iter().count(), 3);
+        // The neighbouring "tt" prefix is untouched.
+        assert!(db.get_record_bytes("tt", "x")?.is_some());
+        assert!(db.integrity_check(false)?.is_clean());
+
+        db.apply_mutation("t", Operation::Create, "d", Some(&data), None)?;
+        assert_eq!(db.drop_table("t")?, 1);
+        assert!(!db.table_names().any(|t| t == "t"));
+        assert!(db.tombstones("t").is_empty());
+        assert!(!db.is_soft_delete("t"));
+        // Create and Delete of "d", then disconnected.
+        assert_eq!(rx.try_iter().count(), 2);
+        assert!(rx.recv().is_err());
+        assert_eq!(db.drop_table("missing")?, 0);
+        drop(db);
+
+        let db = SpookyDb::new(tmp.path())?;
+        assert!(!db.table_names().any(|t| t == "t"));
+        assert_eq!(db.table_len("tt"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_table_moves_records_and_options() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        for id in ["a", "b"] {
+            db.apply_mutation("old", Operation::Create, id, Some(&data), Some(7))?;
+        }
+        db.apply_mutation("other", Operation::Create, "x", Some(&data), None)?;
+        db.set_expiry("old", "a", Some(100))?;
+        db.set_soft_delete("old", true)?;
+        db.apply_mutation("old", Operation::Delete, "b", None, None)?;
+        db.pin_table("old")?;
+
+        assert!(matches!(
+            db.rename_table("old", "other"),
+            Err(SpookyDbError::TableExists(_))
+        ));
+        assert_eq!(db.rename_table("old", "new")?, 1);
+        assert!(!db.table_names().any(|t| t == "old"));
+        assert_eq!(db.table_len("new"), 1);
+        assert!(db.get_record_bytes("old", "a")?.is_none());
+        assert!(db.get_row_record("new", "a")?.is_some());
+        assert_eq!(db.get_version("new", "a")?, Some(7));
+        assert_eq!(db.expires_at("new", "a"), Some(100));
+        assert!(db.tombstone("new", "b").is_some());
+        assert!(db.is_soft_delete("new"));
+        assert_eq!(db.cache_policy("new"), CachePolicy::Pinned);
+        assert_eq!(db.cache_policy("old"), CachePolicy::Shared);
+        assert!(db.integrity_check(false)?.is_clean());
+        drop(db);
+
+        let db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.table_len("new"), 1);
+        assert_eq!(db.table_len("old"), 0);
+        assert_eq!(db.tombstones("new").len(), 1);
+        assert_eq!(db.expires_at("new", "a"), Some(100));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ids_and_ids_sorted() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        for id in ["c", "a", "b"] {
+            db.apply_mutation("t", Operation::Create, id, Some(BENCH_CBOR), None)?;
+        }
+        db.apply_mutation("t", Operation::Delete, "b", None, None)?;
+
+        let mut ids: Vec<&SmolStr> = db.ids("t").collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "c"]);
+        assert_eq!(db.ids_sorted("t")?, ["a", "c"]);
+        assert_eq!(db.ids("missing").count(), 0);
+        assert!(db.ids_sorted("bad\u{1f}table").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsonl_export_import_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let rows = [("b", r#"{"name":"Bob","age":40}"#), ("a", r#"{"name":"Al"}"#)];
+        for (id, json) in rows {
+            let bytes = record(json);
+            db.apply_mutation("users", Operation::Create, id, Some(&bytes), None)?;
+        }
+        assert!(db.export_jsonl("users", None, Vec::new()).is_err());
+
+        let mut out = Vec::new();
+        let exported = db.export_jsonl("users", Some(&["name", "age"]), &mut out)?;
+        assert_eq!(exported, 2);
+        let text = String::from_utf8(out.clone())?;
+        let lines: Vec<SpookyValue> = text
+            .lines()
+            .map(SpookyValue::from_json_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines[0].get("id").and_then(|v| v.as_str()), Some("a"));
+        assert!(lines[0].get("age").is_none());
+        assert_eq!(lines[1].get("age").and_then(|v| v.as_i64()), Some(40));
+
+        let tmp2 = NamedTempFile::new()?;
+        let mut copy = SpookyDb::new(tmp2.path())?;
+        assert_eq!(copy.import_jsonl("people", &out[..])?, 2);
+        let fields = ["name", "age", "id"];
+        let bob = copy.get_record_typed("people", "b", &fields)?;
+        assert_eq!(bob, db.get_record_typed("users", "b", &fields)?);
+
+        let err = copy.import_jsonl("people", "\n{\"name\":\"x\"}\n".as_bytes());
+        assert!(matches!(err, Err(SpookyDbError::Serialization(msg)) if msg.starts_with("line 2")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_csv_quotes_and_selects_fields() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let rows = [
+            ("b", r#"{"name":"Bob \"B\", Jr.","age":40,"tags":["x"]}"#),
+            ("a", r#"{"name":"Al","score":1.5,"ok":true}"#),
+        ];
+        for (id, json) in rows {
+            let bytes = record(json);
+            db.apply_mutation("users", Operation::Create, id, Some(&bytes), None)?;
+        }
+
+        let mut out = Vec::new();
+        let fields = ["id", "name", "age", "score", "ok", "tags"];
+        assert_eq!(db.export_csv("users", &fields, &mut out)?, 2);
+        assert_eq!(
+            String::from_utf8(out)?,
+            "id,name,age,score,ok,tags\r\n\
+             a,Al,,1.5,true,\r\n\
+             b,\"Bob \"\"B\"\", Jr.\",40,,,\"[\"\"x\"\"]\"\r\n"
+        );
+
+        let mut out = Vec::new();
+        assert_eq!(db.export_csv("missing", &["id"], &mut out)?, 0);
+        assert_eq!(out, b"id\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_restore_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let rows = [("users", "a", Some(3)), ("users", "b", None), ("posts", "p", Some(1))];
+        for (table, id, version) in rows {
+            let bytes = record(&format!(r#"{{"id":"{id}"}}"#));
+            db.apply_mutation(table, Operation::Create, id, Some(&bytes), version)?;
+        }
+        let dump = NamedTempFile::new()?;
+        assert_eq!(db.backup(dump.path())?, 3);
+
+        let tmp2 = NamedTempFile::new()?;
+        let mut copy = SpookyDb::new(tmp2.path())?;
+        assert_eq!(copy.restore(dump.path())?, 3);
+        for (table, id, version) in rows {
+            let original = db.get_record_bytes(table, id)?;
+            assert_eq!(copy.get_record_bytes(table, id)?, original);
+            assert_eq!(copy.get_version(table, id)?, version);
+        }
+        assert_eq!(copy.table_len("users"), 2);
+
+        // The target must be empty.
+        let err = copy.restore(dump.path());
+        assert!(matches!(err, Err(SpookyDbError::TableExists(_))));
+
+        // A dump cut short is rejected.
+        let bytes = std::fs::read(dump.path())?;
+        std::fs::write(dump.path(), &bytes[..bytes.len() - 4])?;
+        let tmp3 = NamedTempFile::new()?;
+        let mut partial = SpookyDb::new(tmp3.path())?;
+        let err = partial.restore(dump.path());
+        assert!(matches!(err, Err(SpookyDbError::Serialization(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compression_at_rest() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = || SpookyDbConfig {
+            compressor: Some(Arc::new(crate::db::compress::tests::Rle)),
+            ..SpookyDbConfig::default()
+        };
+        let json = format!(r#"{{"body":"{}"}}"#, "a".repeat(4000));
+        let bytes = record(&json);
+        {
+            let mut db = SpookyDb::new_with_config(tmp.path(), config())?;
+            db.set_compression("docs", Some(256))?;
+            db.apply_mutation("docs", Operation::Create, "d1", Some(&bytes), None)?;
+            db.apply_mutation("plain", Operation::Create, "p1", Some(&bytes), None)?;
+            assert!(db.table_stats("docs").bytes < 200);
+            assert_eq!(db.table_stats("plain").bytes, bytes.len() as u64);
+        }
+
+        let db = SpookyDb::new_with_config(tmp.path(), config())?;
+        assert_eq!(db.get_record_bytes("docs", "d1")?, Some(bytes.clone()));
+        let mut scanned = Vec::new();
+        db.scan_keys("docs", Bound::Unbounded, Bound::Unbounded, |_, data| {
+            scanned.push(data.to_vec());
+            true
+        })?;
+        assert_eq!(scanned, vec![bytes.clone()]);
+        drop(db);
+
+        // Compressed values need the compressor; plain ones do not.
+        let mut db = SpookyDb::new(tmp.path())?;
+        let err = db.get_record_bytes("docs", "d1");
+        assert!(matches!(err, Err(SpookyDbError::Compression(_))));
+        assert_eq!(db.get_record_bytes("plain", "p1")?, Some(bytes));
+        assert!(db.set_compression("docs", Some(256)).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::ZstdCompressor;
+        let tmp = NamedTempFile::new()?;
+        let config = || SpookyDbConfig {
+            compressor: Some(Arc::new(ZstdCompressor::default())),
+            ..SpookyDbConfig::default()
+        };
+        let json = format!(r#"{{"body":"{}","n":1}}"#, "spooky ".repeat(600));
+        let bytes = record(&json);
+        {
+            let mut db = SpookyDb::new_with_config(tmp.path(), config())?;
+            db.set_compression("docs", Some(256))?;
+            db.apply_mutation("docs", Operation::Create, "d1", Some(&bytes), None)?;
+            assert!(db.table_stats("docs").bytes < bytes.len() as u64 / 10);
+        }
+
+        // Another level reads it back.
+        let config = SpookyDbConfig {
+            compressor: Some(Arc::new(ZstdCompressor::new(19))),
+            ..SpookyDbConfig::default()
+        };
+        let db = SpookyDb::new_with_config(tmp.path(), config)?;
+        assert_eq!(db.get_record_bytes("docs", "d1")?, Some(bytes));
+        let n = db.with_record("docs", "d1", |record| record.get_i64("n"))?;
+        assert_eq!(n, Some(Some(1)));
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_reads_uncompressed_values() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::ZstdCompressor;
+        let tmp = NamedTempFile::new()?;
+        let json = format!(r#"{{"body":"{}"}}"#, "a".repeat(4000));
+        let old = record(&json);
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.apply_mutation("docs", Operation::Create, "old", Some(&old), None)?;
+        }
+
+        let config = SpookyDbConfig {
+            compressor: Some(Arc::new(ZstdCompressor::default())),
+            ..SpookyDbConfig::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        db.set_compression("docs", Some(256))?;
+        assert_eq!(db.get_record_bytes("docs", "old")?, Some(old.clone()));
+
+        // New writes compress; the old value is left as it was.
+        db.apply_mutation("docs", Operation::Create, "new", Some(&old), None)?;
+        assert!(db.table_stats("docs").bytes < 2 * old.len() as u64);
+        assert_eq!(db.get_record_bytes("docs", "new")?, Some(old.clone()));
+        assert_eq!(db.get_record_bytes("docs", "old")?, Some(old));
+        Ok(())
+    }
+
+    #[test]
+    fn test_blob_storage() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let body = "a".repeat(4000);
+        let json = format!(r#"{{"body":"{body}","title":"x"}}"#);
+        let bytes = record(&json);
+        let blob_counts = |db: &SpookyDb| -> Result<(u64, Option<u64>), SpookyDbError> {
+            let read_txn = db.db.begin_read()?;
+            let blobs = read_txn.open_table(BLOBS_TABLE)?.len()?;
+            let refs = read_txn.open_table(BLOB_REFS_TABLE)?;
+            let count = refs.iter()?.next().transpose()?.map(|(_, c)| c.value());
+            Ok((blobs, count))
+        };
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.set_blob_threshold("docs", Some(1024))?;
+            db.apply_mutation("docs", Operation::Create, "d1", Some(&bytes), None)?;
+            db.apply_mutation("docs", Operation::Create, "d2", Some(&bytes), None)?;
+            assert!(db.table_stats("docs").bytes < 200);
+            assert_eq!(blob_counts(&db)?, (1, Some(2)));
+            let stored = db.get_record_bytes("docs", "d1")?.unwrap();
+            assert!(stored.len() < 100);
+            let (buf, count) = from_bytes(&stored)?;
+            let record = SpookyRecord::new(buf, count);
+            assert_eq!(record.get_str("title"), Some("x"));
+            assert_eq!(record.field_type("body"), Some(TAG_BLOB_REF));
+        }
+
+        let mut db = SpookyDb::new(tmp.path())?;
+        let stored_body = db.get_blob("docs", "d1", "body")?;
+        assert_eq!(stored_body, Some(body.clone().into_bytes()));
+        assert_eq!(db.get_blob("docs", "d1", "title")?, Some(b"x".to_vec()));
+        assert_eq!(db.get_blob("docs", "d1", "nope")?, None);
+        assert_eq!(db.get_blob("docs", "d9", "body")?, None);
+
+        // The last reference takes the blob with it.
+        db.apply_mutation("docs", Operation::Delete, "d1", None, None)?;
+        assert_eq!(blob_counts(&db)?, (1, Some(1)));
+        let small = SpookyValue::from_json_str(r#"{"body":"short"}"#)?;
+        let (small, _) = crate::serialization::from_spooky(&small)?;
+        db.apply_mutation("docs", Operation::Update, "d2", Some(&small), None)?;
+        assert_eq!(blob_counts(&db)?, (0, None));
+
+        db.set_blob_threshold("docs", Some(1024))?;
+        db.apply_mutation("docs", Operation::Create, "d3", Some(&bytes), None)?;
+        let dump = NamedTempFile::new()?;
+        db.backup(dump.path())?;
+        db.truncate_table("docs")?;
+        assert_eq!(blob_counts(&db)?, (0, None));
+        db.restore(dump.path())?;
+        assert_eq!(db.get_blob("docs", "d3", "body")?, Some(body.into_bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_records_bulk() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let record = |n: i64| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+            let value = SpookyValue::from_json_str(&format!(r#"{{"n":{n}}}"#))?;
+            Ok(crate::serialization::from_spooky(&value)?.0)
+        };
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            for n in 0..3 {
+                let id = format!("r{n}");
+                db.apply_mutation("t", Operation::Create, &id, Some(&record(n)?), None)?;
+            }
+        }
+
+        // Reopened: r0 is cached again by a write, r1 and r2 are read from redb.
+        let mut db = SpookyDb::new(tmp.path())?;
+        db.apply_mutation("t", Operation::Update, "r0", Some(&record(10)?), None)?;
+        let got = db.get_records_bulk("t", &["r2", "missing", "r0", "r1"])?;
+        let want = [Some(record(2)?), None, Some(record(10)?), Some(record(1)?)];
+        assert_eq!(got, want);
+        let stats = db.stats();
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 2));
+
+        assert_eq!(db.get_records_bulk("nope", &["r0"])?, vec![None]);
+        assert!(db.get_records_bulk("bad\u{1f}table", &["r0"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_mutation_cbor_and_value() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let value = SpookyValue::from_json_str(r#"{"name":"alice","age":30}"#)?;
+        let (expected, _) = crate::serialization::from_spooky(&value)?;
+
+        db.apply_mutation_value("users", Operation::Create, "u1", Some(&value), Some(1))?;
+        assert_eq!(db.get_record_bytes("users", "u1")?, Some(expected.clone()));
+
+        let cbor = cbor4ii::serde::to_vec(Vec::new(), &value)?;
+        db.apply_mutation_cbor("users", Operation::Create, "u2", Some(&cbor), None)?;
+        assert_eq!(db.get_record_bytes("users", "u2")?, Some(expected));
+
+        // Bad input writes nothing; deletes need no data.
+        let err = db.apply_mutation_cbor("users", Operation::Create, "u3", Some(&[0x01]), None);
+        assert!(matches!(err, Err(SpookyDbError::Serialization(_))));
+        let not_object = SpookyValue::from_json_str("[1, 2]")?;
+        let not_object = Some(&not_object);
+        let err = db.apply_mutation_value("users", Operation::Create, "u3", not_object, None);
+        assert!(matches!(err, Err(SpookyDbError::Serialization(_))));
+        assert_eq!(db.table_len("users"), 2);
+        db.apply_mutation_value("users", Operation::Delete, "u1", None, None)?;
+        assert_eq!(db.table_len("users"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_version() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let config = SpookyDbConfig {
+            auto_version: true,
+            ..SpookyDbConfig::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let bytes = record(r#"{"n":1}"#);
+        let data = Some(bytes.as_slice());
+
+        let (_, _, v) = db.apply_mutation_versioned("t", Operation::Create, "a", data, None)?;
+        assert_eq!(v, Some(1));
+        db.apply_mutation("t", Operation::Update, "a", Some(&bytes), None)?;
+        assert_eq!(db.get_version("t", "a")?, Some(2));
+        // A given version is written as is, and counted from.
+        db.apply_mutation("t", Operation::Update, "a", Some(&bytes), Some(10))?;
+        let (_, _, v) = db.apply_mutation_versioned("t", Operation::Update, "a", data, None)?;
+        assert_eq!(v, Some(11));
+
+        let batch = vec![
+            DbMutation {
+                table: SmolStr::new("t"),
+                id: SmolStr::new("a"),
+                op: Operation::Update,
+                data: Some(bytes.clone()),
+                version: None,
+                expires_at: None,
+            },
+            DbMutation {
+                table: SmolStr::new("t"),
+                id: SmolStr::new("b"),
+                op: Operation::Create,
+                data: Some(bytes.clone()),
+                version: None,
+                expires_at: None,
+            },
+        ];
+        let result = db.apply_batch(batch)?;
+        assert_eq!(result.versions["t"]["a"], 12);
+        assert_eq!(result.versions["t"]["b"], 1);
+
+        // A Delete clears the version, so a re-create starts over.
+        let (_, _, v) = db.apply_mutation_versioned("t", Operation::Delete, "b", None, None)?;
+        assert_eq!(v, None);
+        db.apply_mutation("t", Operation::Create, "b", Some(&bytes), None)?;
+        assert_eq!(db.get_version("t", "b")?, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_versions() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"n":1}"#);
+        db.apply_mutation("t", Operation::Create, "a", Some(&bytes), Some(3))?;
+        db.apply_mutation("t", Operation::Create, "b", Some(&bytes), None)?;
+        db.apply_mutation("t", Operation::Create, "c", Some(&bytes), Some(7))?;
+        db.apply_mutation("t", Operation::Delete, "c", None, None)?;
+
+        let got = db.get_versions("t", &["c", "a", "missing", "b", "a"])?;
+        assert_eq!(got, [None, Some(3), None, None, Some(3)]);
+        assert_eq!(db.get_versions("nope", &["a", "b"])?, [None, None]);
+        assert!(db.get_versions("t", &[])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_timestamps() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let bytes = record(r#"{"n":1}"#);
+        let created = {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.apply_mutation("off", Operation::Create, "a", Some(&bytes), None)?;
+            assert_eq!(db.get_record_meta("off", "a")?, None);
+
+            db.set_timestamps("t", true)?;
+            let before = now_millis();
+            db.apply_mutation("t", Operation::Create, "a", Some(&bytes), None)?;
+            let meta = db.get_record_meta("t", "a")?.unwrap();
+            assert!(meta.created_at >= before);
+            assert_eq!(meta.created_at, meta.updated_at);
+            std::thread::sleep(Duration::from_millis(5));
+            db.apply_mutation("t", Operation::Update, "a", Some(&bytes), None)?;
+            let meta = db.get_record_meta("t", "a")?.unwrap();
+            assert!(meta.updated_at > meta.created_at);
+            meta
+        };
+
+        // Persistent; the option is not.
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert!(!db.has_timestamps("t"));
+        assert_eq!(db.get_record_meta("t", "a")?, Some(created));
+        db.rename_table("t", "u")?;
+        assert_eq!(db.get_record_meta("u", "a")?, Some(created));
+        assert_eq!(db.get_record_meta("t", "a")?, None);
+
+        // A Delete clears the entry even with the option off.
+        db.apply_mutation("u", Operation::Delete, "a", None, None)?;
+        db.apply_mutation("u", Operation::Create, "a", Some(&bytes), None)?;
+        assert_eq!(db.get_record_meta("u", "a")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_table_keeps_timestamps() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let bytes = record(r#"{"n":1}"#);
+        let mut db = SpookyDb::new(tmp.path())?;
+        db.set_timestamps("t", true)?;
+        db.apply_mutation("t", Operation::Create, "a", Some(&bytes), None)?;
+        let created = db.get_record_meta("t", "a")?.unwrap();
+
+        db.rename_table("t", "u")?;
+        assert!(db.has_timestamps("u"));
+        assert!(!db.has_timestamps("t"));
+        std::thread::sleep(Duration::from_millis(5));
+        db.apply_mutation("u", Operation::Update, "a", Some(&bytes), None)?;
+        db.apply_mutation("u", Operation::Create, "b", Some(&bytes), None)?;
+        let meta = db.get_record_meta("u", "a")?.unwrap();
+        assert_eq!(meta.created_at, created.created_at);
+        assert!(meta.updated_at > created.updated_at);
+        assert!(db.get_record_meta("u", "b")?.is_some());
+
+        // The old name is free again and starts without the option.
+        db.apply_mutation("t", Operation::Create, "c", Some(&bytes), None)?;
+        assert_eq!(db.get_record_meta("t", "c")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespaces() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"name":"alice"}"#);
+
+        let mut a = db.namespace("tenant_a")?;
+        assert_eq!(a.name(), "tenant_a");
+        a.apply_mutation("users", Operation::Create, "1", Some(&bytes), Some(2))?;
+        let result = a.apply_batch(vec![DbMutation {
+            table: SmolStr::new("orders"),
+            id: SmolStr::new("o1"),
+            op: Operation::Create,
+            data: Some(bytes.clone()),
+            version: None,
+            expires_at: None,
+        }])?;
+        assert_eq!(result.changed_tables, ["orders"]);
+        assert!(result.membership_deltas.contains_key("orders"));
+
+        let mut b = db.namespace("tenant_b")?;
+        b.apply_mutation("users", Operation::Create, "2", Some(&bytes), None)?;
+        assert_eq!(b.get_record_bytes("users", "1")?, None);
+        assert_eq!(b.ids_sorted("users")?, ["2"]);
+        assert_eq!(b.table_names().collect::<Vec<_>>(), ["users"]);
+
+        let a = db.namespace("tenant_a")?;
+        assert_eq!(a.get_version("users", "1")?, Some(2));
+        assert_eq!(a.get_zset_weight("users", "1"), 1);
+        let mut names: Vec<&str> = a.table_names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["orders", "users"]);
+        assert!(a.get_record_bytes("", "1").is_err());
+
+        // The unscoped handle sees full names.
+        assert_eq!(db.table_len("tenant_a:users"), 1);
+        assert_eq!(db.namespace("tenant_a")?.drop_all()?, 2);
+        assert_eq!(db.table_len("tenant_a:users"), 0);
+        assert_eq!(db.table_len("tenant_b:users"), 1);
+
+        assert!(db.namespace("").is_err());
+        assert!(db.namespace("a:b").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_and_restore() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let bytes = record(r#"{"name":"alice"}"#);
+        let snapshot = {
+            let mut db = SpookyDb::new(tmp.path())?;
+            assert_eq!(db.load_checkpoint()?, None);
+            db.apply_mutation("users", Operation::Create, "1", Some(&bytes), None)?;
+            db.apply_mutation("users", Operation::Create, "2", Some(&bytes), None)?;
+            db.apply_mutation("posts", Operation::Create, "p", Some(&bytes), None)?;
+            db.set_tick("circuit", 7);
+            let snapshot = db.checkpoint()?;
+            assert_eq!(snapshot.tables["users"].len(), 2);
+            assert_eq!(snapshot.ticks["circuit"], 7);
+
+            db.set_tick("circuit", 8);
+            db.apply_mutation("users", Operation::Delete, "1", None, None)?;
+            db.apply_mutation("users", Operation::Create, "3", Some(&bytes), None)?;
+            db.drop_table("posts")?;
+            snapshot
+        };
+
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.tick("circuit"), Some(7));
+        assert_eq!(db.load_checkpoint()?.as_ref(), Some(&snapshot));
+        db.set_tick("circuit", 9);
+        let deltas = db.restore_from_checkpoint(&snapshot)?;
+        assert_eq!(db.tick("circuit"), Some(7));
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas["users"].len(), 2);
+        assert_eq!(deltas["users"]["1"], -1);
+        assert_eq!(deltas["users"]["3"], 1);
+        assert_eq!(deltas["posts"]["p"], -1);
+        assert_eq!(db.get_zset_weight("users", "3"), 1);
+
+        let current = db.checkpoint()?;
+        assert!(db.restore_from_checkpoint(&current)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_single_record() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let data = record(r#"{"name":"alice"}"#);
+
+        let rx = db.watch("users", "alice")?;
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), Some(1))?;
+        db.apply_mutation("users", Operation::Create, "bob", Some(&data), None)?;
+        db.apply_mutation("posts", Operation::Create, "alice", Some(&data), None)?;
+        db.apply_mutation("users", Operation::Delete, "alice", None, Some(2))?;
+
+        let changes: Vec<RecordChange> = rx.try_iter().collect();
+        assert_eq!(
+            changes,
+            [
+                RecordChange {
+                    op: Operation::Create,
+                    version: Some(1),
+                    data: Some(data.clone()),
+                },
+                RecordChange {
+                    op: Operation::Delete,
+                    version: Some(2),
+                    data: None,
+                },
+            ]
+        );
+
+        drop(rx);
+        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+        assert!(db.watchers.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_row_record_mut_reads_through() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
+        let (data, _) = from_cbor(&cbor)?;
+        let long = format!(r#"{{"name":"{}"}}"#, "x".repeat(data.len() * 2));
+        let big = record(&long);
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+            db.apply_mutation("users", Operation::Create, "big", Some(&big), None)?;
+        }
+
+        let config = SpookyDbConfig {
+            cache_max_bytes: Some(data.len() * 2),
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        assert!(db.get_row_record("users", "alice")?.is_none());
+        let record = db.get_row_record_mut("users", "alice")?.expect("read from redb");
+        assert!(record.get_i64("age").is_some());
+        assert!(db.get_row_record("users", "alice")?.is_some(), "now cached");
+        assert!(db.get_row_record_mut("users", "nobody")?.is_none());
+
+        // Too large for the cache, but still borrowable.
+        let record = db.get_row_record_mut("users", "big")?.expect("read from redb");
+        assert_eq!(record.get_str("name").map(str::len), Some(data.len() * 2));
+        assert!(db.get_row_record("users", "big")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_visit_records_cache_and_disk() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        for (id, age) in [("a", 1), ("b", 2), ("c", 3)] {
+            let data = record(&format!(r#"{{"age":{age}}}"#));
+            db.apply_mutation("users", Operation::Create, id, Some(&data), None)?;
+        }
+        drop(db);
+
+        // Cold cache, then one row read through: a mix of hits and misses.
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert!(db.get_row_record_mut("users", "b")?.is_some());
+        let mut seen = Vec::new();
+        let visited = db.visit_records("users", &["c", "missing", "b", "a"], |id, record| {
+            seen.push((id.to_string(), record.get_i64("age")));
+        })?;
+        assert_eq!(visited, 3);
+        assert_eq!(
+            seen,
+            [
+                ("c".to_string(), Some(3)),
+                ("b".to_string(), Some(2)),
+                ("a".to_string(), Some(1)),
+            ]
+        );
+        assert_eq!(db.visit_records("nope", &["a"], |_, _| panic!("absent"))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_record_borrows_hits_and_misses() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
-        let db = SpookyDb::new(tmp.path())?;
-        assert!(!db.table_exists("users"));
-        assert_eq!(db.table_len("users"), 0);
+        let mut db = SpookyDb::new(tmp.path())?;
+        for (id, age) in [("a", 1), ("b", 2)] {
+            let data = record(&format!(r#"{{"age":{age}}}"#));
+            db.apply_mutation("users", Operation::Create, id, Some(&data), None)?;
+        }
+        drop(db);
+
+        // Cold cache: "a" is read from its redb guard, "b" after a read-through.
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert!(db.get_row_record_mut("users", "b")?.is_some());
+        assert_eq!(db.with_record("users", "a", |rec| rec.get_i64("age"))?, Some(Some(1)));
+        assert_eq!(db.with_record("users", "b", |rec| rec.get_i64("age"))?, Some(Some(2)));
+        let stats = db.stats();
+        assert_eq!((stats.cache_hits, stats.cache_misses, stats.cache_len), (1, 2, 1));
+
+        assert_eq!(db.with_record("users", "missing", |_| panic!("absent"))?, None::<()>);
+        assert_eq!(db.with_record("nope", "a", |_| panic!("absent"))?, None::<()>);
         Ok(())
     }
 
     #[test]
-    fn test_apply_mutation_create_get_delete() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_put_values_recycles_pooled_buffers() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
+        let pool = Arc::new(BufferPool::new());
+        db.set_buffer_pool(Arc::clone(&pool));
 
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        // Create
-        db.apply_mutation("users", Operation::Create, "alice", Some(&data), Some(1))?;
-        assert!(db.table_exists("users"));
-        assert_eq!(db.table_len("users"), 1);
-        assert_eq!(db.get_zset_weight("users", "alice"), 1);
-
-        // Get raw bytes back
-        let fetched = db.get_record_bytes("users", "alice")?.expect("should exist");
-        assert_eq!(fetched, data);
+        let small = SpookyValue::from_json_str(r#"{"age":1}"#)?;
+        let large = SpookyValue::from_json_str(&format!(r#"{{"bio":"{}"}}"#, "x".repeat(200)))?;
+        let result = db.put_values("users", [("a", &small), ("b", &large)])?;
+        assert_eq!(result.membership_deltas["users"].len(), 2);
+        // The small row is cached inline; the large one keeps its buffer.
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(db.with_record("users", "a", |rec| rec.get_i64("age"))?, Some(Some(1)));
+
+        // The update reuses the pooled buffer and gives it back.
+        db.put_values("users", [("a", &small)])?;
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(db.get_zset_weight("users", "a"), 1);
+
+        let err = db.put_values("users", [("c", &small), ("d", &SpookyValue::from(1i64))]);
+        assert!(matches!(err, Err(SpookyDbError::Serialization(_))));
+        assert_eq!((db.table_len("users"), pool.idle()), (2, 1));
+        Ok(())
+    }
 
-        // Version
-        assert_eq!(db.get_version("users", "alice")?, Some(1));
+    #[test]
+    fn test_iter_table_ordered_both_directions() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        for id in ["01H2", "01H0", "01H1"] {
+            let data = record(&format!(r#"{{"id":"{id}"}}"#));
+            db.apply_mutation("events", Operation::Create, id, Some(&data), None)?;
+            db.apply_mutation("events_archive", Operation::Create, id, Some(&data), None)?;
+        }
 
-        // Delete
-        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
-        assert_eq!(db.get_zset_weight("users", "alice"), 0);
-        assert!(db.get_record_bytes("users", "alice")?.is_none());
-        assert_eq!(db.table_len("users"), 0);
+        let ids = |direction| -> Result<Vec<SmolStr>, SpookyDbError> {
+            db.iter_table_ordered("events", direction)?
+                .map(|row| row.map(|(id, _)| id))
+                .collect()
+        };
+        assert_eq!(ids(SortDirection::Asc)?, ["01H0", "01H1", "01H2"]);
+        assert_eq!(ids(SortDirection::Desc)?, ["01H2", "01H1", "01H0"]);
 
+        let (id, bytes) = db.iter_table_ordered("events", SortDirection::Desc)?.next().unwrap()?;
+        assert_eq!(id, "01H2");
+        assert_eq!(db.get_record_bytes("events", "01H2")?, Some(bytes));
+        assert_eq!(db.iter_table_ordered("missing", SortDirection::Asc)?.count(), 0);
         Ok(())
     }
 
     #[test]
-    fn test_apply_batch_one_txn() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_apply_batch_with_savepoint_rolls_back_segment() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
-
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        let mutations = vec![
-            DbMutation {
-                table: SmolStr::new("users"),
-                id: SmolStr::new("u1"),
-                op: Operation::Create,
-                data: Some(data.clone()),
-                version: Some(1),
-            },
-            DbMutation {
-                table: SmolStr::new("users"),
-                id: SmolStr::new("u2"),
-                op: Operation::Create,
-                data: Some(data.clone()),
-                version: Some(1),
-            },
-            DbMutation {
-                table: SmolStr::new("posts"),
-                id: SmolStr::new("p1"),
-                op: Operation::Create,
-                data: Some(data.clone()),
-                version: Some(1),
-            },
+        let create = |table: &str, id: &str, data: Vec<u8>| DbMutation {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(data),
+            version: None,
+            expires_at: None,
+        };
+        db.apply_mutation("users", Operation::Create, "alice", Some(&record(r#"{"n":1}"#)), None)?;
+
+        // A post is valid only if its author exists, committed or staged earlier.
+        let segments = vec![
+            vec![
+                create("users", "bob", record(r#"{"n":2}"#)),
+                create("posts", "p1", record(r#"{"author":"bob"}"#)),
+            ],
+            vec![create("posts", "p2", record(r#"{"author":"alice"}"#))],
+            vec![create("posts", "p3", record(r#"{"author":"carol"}"#))],
+            vec![create("posts", "p4", record(r#"{"author":"alice"}"#))],
         ];
-
-        let result = db.apply_batch(mutations)?;
-
+        let outcome = db.apply_batch_with_savepoint(segments, |view, m| {
+            if m.table != "posts" {
+                return Ok(());
+            }
+            let (buf, count) = from_bytes(m.data.as_deref().unwrap_or_default())?;
+            let post = SpookyRecord::new(buf, count);
+            let author = post.get_str("author").unwrap_or_default();
+            match view.contains("users", author)? {
+                true => Ok(()),
+                false => Err(SpookyDbError::InvalidKey(format!("no author {author}"))),
+            }
+        })?;
+        assert_eq!(outcome.committed, 2);
+        assert!(matches!(outcome.rolled_back, Some(SpookyDbError::InvalidKey(_))));
+        assert_eq!(outcome.result.membership_deltas["posts"].len(), 2);
         assert_eq!(db.table_len("users"), 2);
-        assert_eq!(db.table_len("posts"), 1);
-        assert_eq!(result.membership_deltas["users"].len(), 2);
-        assert_eq!(result.membership_deltas["posts"].len(), 1);
-        assert!(result.changed_tables.contains(&SmolStr::new("users")));
-        assert!(result.changed_tables.contains(&SmolStr::new("posts")));
+        assert_eq!(db.ids_sorted("posts")?, ["p1", "p2"]);
+        assert!(db.get_record_bytes("posts", "p3")?.is_none());
 
+        // A segment failing the built-in checks stops the batch the same way.
+        let outcome = db.apply_batch_with_savepoint(
+            vec![vec![create("t", "a", record("{}"))], vec![create("", "x", record("{}"))]],
+            |_, _| Ok(()),
+        )?;
+        assert_eq!(outcome.committed, 1);
+        assert!(outcome.rolled_back.is_some());
+        assert_eq!(db.table_len("t"), 1);
         Ok(())
     }
 
     #[test]
-    fn test_bulk_load() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_prefetch_and_warm_table() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
+        for i in 0..5 {
+            let data = record(&format!(r#"{{"n":{i}}}"#));
+            db.apply_mutation("t", Operation::Create, &format!("r{i}"), Some(&data), None)?;
+        }
+        drop(db);
 
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        let records = vec![
-            BulkRecord {
-                table: SmolStr::new("items"),
-                id: SmolStr::new("i1"),
-                data: data.clone(),
-                version: None,
-            },
-            BulkRecord {
-                table: SmolStr::new("items"),
-                id: SmolStr::new("i2"),
-                data: data.clone(),
-                version: None,
-            },
-        ];
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.prefetch("t", &["r3", "r1", "missing"])?, 2);
+        assert_eq!(db.prefetch("t", &["r3", "r4"])?, 1, "r3 already cached");
+        assert!(db.get_row_record("t", "r1")?.is_some());
+        assert!(db.get_row_record("t", "r0")?.is_none());
+
+        // r0..r2 by key order; r1 was already cached.
+        assert_eq!(db.warm_table("t", 3)?, 2);
+        assert!(db.get_row_record("t", "r0")?.is_some());
+        assert_eq!(db.stats().cache_len, 5);
+        assert_eq!(db.warm_table("t", 100)?, 0);
+        assert_eq!(db.prefetch("none", &["r0"])?, 0);
+        Ok(())
+    }
 
-        db.bulk_load(records)?;
-        assert_eq!(db.table_len("items"), 2);
-        assert_eq!(db.get_zset_weight("items", "i1"), 1);
-        assert_eq!(db.get_zset_weight("items", "i2"), 1);
+    #[test]
+    fn test_typed_table_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Address {
+            city: String,
+        }
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct User {
+            name: String,
+            age: u32,
+            tags: Vec<String>,
+            address: Address,
+            nick: Option<String>,
+        }
+        let user = |name: &str, age| User {
+            name: name.into(),
+            age,
+            tags: vec!["a".into(), "b".into()],
+            address: Address { city: "Oslo".into() },
+            nick: None,
+        };
 
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let mut users = db.table::<User>("users")?;
+        users.put("bob", &user("Bob", 30))?;
+        users.put("alice", &user("Alice", 41))?;
+        users.put("bob", &User {
+            nick: Some("bobby".into()),
+            ..user("Bob", 31)
+        })?;
+        assert_eq!(users.get("alice")?, Some(user("Alice", 41)));
+        assert_eq!(users.get("nobody")?, None);
+        let all: Vec<(SmolStr, User)> = users.iter()?.collect::<Result<_, _>>()?;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].0, "bob");
+        assert_eq!(all[1].1.nick.as_deref(), Some("bobby"));
+        assert_eq!(all[1].1.age, 31);
+
+        // Stored as ordinary records.
+        assert_eq!(db.table_len("users"), 2);
+        let record = db.get_row_record("users", "alice")?.expect("cached");
+        assert_eq!(record.get_str("name"), Some("Alice"));
+        assert!(db.table::<u64>("numbers").is_err());
         Ok(())
     }
 
     #[test]
-    fn test_zset_survives_reopen() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_migrate_rewrites_and_persists_version() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::types::{MigrationStep, ValueKind};
         let tmp = NamedTempFile::new()?;
-        let path = tmp.path().to_path_buf();
-        // Keep file alive but drop NamedTempFile handle so only the path remains.
-        // Use a regular tempdir file to keep the path valid.
-        let tmp_dir = tempfile::tempdir()?;
-        let db_path = tmp_dir.path().join("test.redb");
+        let v1 = Migration {
+            table: SmolStr::new("users"),
+            version: 1,
+            steps: vec![
+                MigrationStep::RenameField {
+                    from: SmolStr::new("nm"),
+                    to: SmolStr::new("name"),
+                },
+                MigrationStep::DropField { name: SmolStr::new("legacy") },
+                MigrationStep::AddField {
+                    name: SmolStr::new("active"),
+                    default: SpookyValue::Bool(true),
+                },
+                MigrationStep::ChangeType {
+                    name: SmolStr::new("age"),
+                    to: ValueKind::Int,
+                },
+            ],
+        };
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            for i in 0..1_500 {
+                let json = format!(r#"{{"nm":"u{i}","age":"{i}","legacy":1}}"#);
+                let id = format!("u{i:04}");
+                db.apply_mutation("users", Operation::Create, &id, Some(&record(&json)), None)?;
+            }
+            let mut reports = Vec::new();
+            assert_eq!(db.migrate(std::slice::from_ref(&v1), |p| reports.push(p.done))?, 1);
+            assert_eq!(reports, vec![1_000, 1_500]);
+            assert_eq!(db.schema_version("users")?, 1);
+            let rec = db.get_row_record("users", "u0042")?.expect("present");
+            assert_eq!(rec.get_str("name"), Some("u42"));
+            assert_eq!(rec.get_i64("age"), Some(42));
+            assert_eq!(rec.get_bool("active"), Some(true));
+            assert!(!rec.has_field("nm") && !rec.has_field("legacy"));
+            assert_eq!(db.migrate(std::slice::from_ref(&v1), |_| {})?, 0);
+
+            let bad = Migration {
+                table: SmolStr::new("users"),
+                version: 2,
+                steps: vec![MigrationStep::ChangeType {
+                    name: SmolStr::new("name"),
+                    to: ValueKind::Int,
+                }],
+            };
+            let err = db.migrate(&[bad], |_| {}).unwrap_err();
+            assert!(matches!(err, SpookyDbError::MigrationFailed { version: 2, .. }));
+            assert_eq!(db.schema_version("users")?, 1);
+        }
 
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
+        // Run at open through the config: only version 2 is new.
+        let v2 = Migration {
+            table: SmolStr::new("users"),
+            version: 2,
+            steps: vec![MigrationStep::DropField { name: SmolStr::new("active") }],
+        };
+        let config = SpookyDbConfig {
+            migrations: vec![v1, v2],
+            ..Default::default()
+        };
+        let db = SpookyDb::new_with_config(tmp.path(), config)?;
+        assert_eq!(db.schema_version("users")?, 2);
+        let rec = db.get_row_record("users", "u1499")?.expect("present");
+        assert_eq!(rec.get_i64("age"), Some(1_499));
+        assert!(!rec.has_field("active"));
+        assert_eq!(db.schema_version("other")?, 0);
+        Ok(())
+    }
 
+    #[test]
+    fn test_migrate_lazy_upgrades_on_read() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::types::MigrationStep;
+        let tmp = NamedTempFile::new()?;
+        let read = |db: &SpookyDb, id: &str| -> Result<SpookyValue, SpookyDbError> {
+            let bytes = db.get_record_bytes("users", id)?.expect("present");
+            record_typed(&bytes, &["nm", "name", "active"])
+        };
+        let v1 = Migration {
+            table: SmolStr::new("users"),
+            version: 1,
+            steps: vec![
+                MigrationStep::RenameField {
+                    from: SmolStr::new("nm"),
+                    to: SmolStr::new("name"),
+                },
+                MigrationStep::AddField {
+                    name: SmolStr::new("active"),
+                    default: SpookyValue::Bool(true),
+                },
+            ],
+        };
         {
-            let mut db = SpookyDb::new(&db_path)?;
-            db.apply_mutation("users", Operation::Create, "alice", Some(&data), Some(1))?;
-            db.apply_mutation("users", Operation::Create, "bob", Some(&data), Some(2))?;
-            assert_eq!(db.table_len("users"), 2);
+            let mut db = SpookyDb::new(tmp.path())?;
+            for id in ["a", "b"] {
+                let bytes = record(r#"{"nm":"x"}"#);
+                db.apply_mutation("users", Operation::Create, id, Some(&bytes), None)?;
+            }
+            assert_eq!(db.migrate_lazy(std::slice::from_ref(&v1))?, 1);
+            assert_eq!(db.schema_version("users")?, 1);
+            let upgraded = SpookyValue::from_json_str(r#"{"name":"x","active":true}"#)?;
+            assert_eq!(read(&db, "a")?, upgraded);
+            let rec = db.get_row_record_mut("users", "b")?.expect("present");
+            assert_eq!(rec.get_str("name"), Some("x"));
+
+            // Written at version 1, so not upgraded again.
+            let bytes = record(r#"{"nm":"y"}"#);
+            db.apply_mutation("users", Operation::Create, "c", Some(&bytes), None)?;
+            assert_eq!(read(&db, "c")?, SpookyValue::from_json_str(r#"{"nm":"y"}"#)?);
+            db.apply_mutation("users", Operation::Delete, "b", None, None)?;
         }
 
-        // Reopen — ZSet must be rebuilt from RECORDS_TABLE.
-        let db2 = SpookyDb::new(&db_path)?;
-        assert_eq!(db2.table_len("users"), 2);
-        assert_eq!(db2.get_zset_weight("users", "alice"), 1);
-        assert_eq!(db2.get_zset_weight("users", "bob"), 1);
-
-        // Suppress unused path warning.
-        let _ = path;
+        // "a" was written back; "b" was deleted before its write-back.
+        let db = SpookyDb::new(tmp.path())?;
+        assert_eq!(read(&db, "a")?, SpookyValue::from_json_str(r#"{"name":"x","active":true}"#)?);
+        assert_eq!(db.get_record_bytes("users", "b")?, None);
+        assert_eq!(read(&db, "c")?, SpookyValue::from_json_str(r#"{"nm":"y"}"#)?);
+        drop(db);
+
+        let config = SpookyDbConfig {
+            migrations: vec![v1],
+            lazy_migrations: true,
+            ..Default::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let bytes = record(r#"{"nm":"z"}"#);
+        db.apply_mutation("users", Operation::Create, "d", Some(&bytes), None)?;
+        assert_eq!(read(&db, "d")?, SpookyValue::from_json_str(r#"{"nm":"z"}"#)?);
+        assert!(db.integrity_check(false)?.stale_cache.is_empty());
         Ok(())
     }
 
     #[test]
-    fn test_get_record_typed_partial() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_reader_snapshot_across_threads() -> Result<(), Box<dyn std::error::Error>> {
+        fn assert_send_sync<T: Send + Sync + Clone>(_: &T) {}
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
-
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
-
-        // The CBOR fixture has an "age" field (i64 = 28) and "active" (bool).
-        let val = db
-            .get_record_typed("users", "alice", &["age", "active"])?
-            .expect("should exist");
-
-        assert!(matches!(val, SpookyValue::Object(_)));
-        if let SpookyValue::Object(map) = val {
-            // "age" and "active" should be present.
-            assert!(map.contains_key("age"), "age field missing");
-            assert!(map.contains_key("active"), "active field missing");
+        for i in 0..8 {
+            let bytes = record(&format!(r#"{{"n":{i}}}"#));
+            db.apply_mutation("t", Operation::Create, &format!("r{i}"), Some(&bytes), Some(1))?;
         }
-
+        let reader = db.reader()?;
+        assert_send_sync(&reader);
+
+        let workers: Vec<_> = (0..4)
+            .map(|w| {
+                let reader = reader.clone();
+                std::thread::spawn(move || {
+                    (w * 2..w * 2 + 2)
+                        .map(|i| {
+                            let id = format!("r{i}");
+                            let n = reader.with_row_record("t", &id, |r| r.get_i64("n"));
+                            n.map(Option::flatten)
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+            })
+            .collect();
+        // The owner keeps writing meanwhile.
+        db.apply_mutation("t", Operation::Update, "r0", Some(&record(r#"{"n":100}"#)), Some(2))?;
+        db.apply_mutation("t", Operation::Delete, "r7", None, None)?;
+        let mut seen = Vec::new();
+        for worker in workers {
+            seen.extend(worker.join().expect("worker")?);
+        }
+        assert_eq!(seen, (0..8).map(Some).collect::<Vec<_>>());
+
+        assert_eq!(reader.table_len("t"), 8);
+        assert_eq!(reader.get_zset_weight("t", "r7"), 1);
+        assert_eq!(reader.get_version("t", "r0")?, Some(1));
+        assert!(reader.get_record_bytes("t", "r7")?.is_some());
+        let typed = reader.get_record_typed("t", "r0", &["n"])?.expect("present");
+        assert_eq!(typed, SpookyValue::from_json_str(r#"{"n":0}"#)?);
+        assert_eq!(reader.get_record_bytes("t", "missing")?, None);
+        assert_eq!(db.reader()?.get_zset_weight("t", "r7"), 0);
         Ok(())
     }
 
     #[test]
-    fn test_ensure_table_and_table_names() {
-        let tmp = NamedTempFile::new().unwrap();
-        let mut db = SpookyDb::new(tmp.path()).unwrap();
-
-        assert!(!db.table_exists("empty_table"));
-        db.ensure_table("empty_table").unwrap();
-        // ensure_table creates the ZSet entry, but table_exists checks for non-empty.
-        // An empty ZSet → table_exists returns false (no records yet).
-        assert!(!db.table_exists("empty_table"));
-        // But table_names() still lists it.
-        let names: Vec<&SmolStr> = db.table_names().collect();
-        assert!(names.contains(&&SmolStr::new("empty_table")));
-
-        // Table names containing ':' must be rejected.
-        assert!(matches!(
-            db.ensure_table("bad:table"),
-            Err(SpookyDbError::InvalidKey(_))
-        ));
+    fn test_weighted_zset_deltas() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"n":1}"#);
+        let m = |op, id: &str| DbMutation {
+            table: SmolStr::new("t"),
+            id: SmolStr::new(id),
+            op,
+            data: (op != Operation::Delete).then(|| bytes.clone()),
+            version: None,
+            expires_at: None,
+        };
+        db.apply_mutation("t", Operation::Create, "kept", Some(&bytes), None)?;
+
+        let result = db.apply_batch(vec![
+            m(Operation::Create, "a"),
+            m(Operation::Delete, "a"),
+            m(Operation::Create, "b"),
+            m(Operation::Create, "b"),
+            m(Operation::Update, "c"),
+            m(Operation::Create, "kept"),
+            m(Operation::Delete, "kept"),
+        ])?;
+        let deltas = &result.membership_deltas["t"];
+        let mut deltas: Vec<_> = deltas.iter().map(|(id, &w)| (id.as_str(), w)).collect();
+        deltas.sort();
+        assert_eq!(deltas, [("b", 1), ("c", 1), ("kept", -1)]);
+
+        // Deltas applied in memory carry any multiplicity.
+        let mut delta = ZSet::default();
+        delta.insert(SmolStr::new("b"), 2);
+        delta.insert(SmolStr::new("c"), -1);
+        db.apply_zset_delta_memory("t", &delta)?;
+        assert_eq!(db.get_zset_weight("t", "b"), 3);
+        assert_eq!(db.get_zset_weight("t", "c"), 0);
+        assert!(db.get_table_zset("t").is_some_and(|z| !z.contains_key("c")));
+        delta.insert(SmolStr::new("b"), -5);
+        db.apply_zset_delta_memory("t", &delta)?;
+        assert_eq!(db.get_zset_weight("t", "b"), -2);
+        assert_eq!(db.get_zset_weight("t", "c"), -1);
+        Ok(())
     }
 
     #[test]
-    fn test_row_cache_populated_on_create() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_view_maintained_from_deltas() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
-
-        // get_record_bytes must return without touching redb.
-        assert_eq!(db.get_record_bytes("users", "alice")?, Some(data.clone()));
-
-        // get_row_record must return a valid borrowed record.
-        let record = db.get_row_record("users", "alice")?.expect("should be in cache");
-        let age = record.get_i64("age");
-        assert!(age.is_some(), "age field should be readable from cached record");
+        let put = |table: &str, id: &str, json: &str| DbMutation {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(record(json)),
+            version: None,
+            expires_at: None,
+        };
+        db.apply_batch(vec![
+            put("users", "u1", r#"{"name":"alice","age":30}"#),
+            put("posts", "p1", r#"{"title":"a","author":"u1","published":true,"n":1}"#),
+            put("posts", "p2", r#"{"title":"b","author":"u1","published":false,"n":2}"#),
+        ])?;
+
+        let view = View::new("feed", "posts")
+            .filter(|r| r.get_bool("published") == Some(true))
+            .project(&["title", "author"])
+            .join("author", "users", &["name"]);
+        db.register_view(view)?;
+        let feed = db.view("feed").expect("registered");
+        assert_eq!(feed.len(), 1);
+        let expected = r#"{"title":"a","author":{"name":"alice"}}"#;
+        assert_eq!(feed.get("p1"), Some(record(expected).as_slice()));
+        let title = feed.get_record("p1").map(|r| r.get_str("title").map(str::to_owned));
+        assert_eq!(title, Some(Some("a".to_owned())));
+        assert!(feed.get("p2").is_none());
+
+        // A batch that publishes p2 and adds an unpublished p3.
+        db.apply_batch(vec![
+            put("posts", "p2", r#"{"title":"b","author":"u1","published":true}"#),
+            put("posts", "p3", r#"{"title":"c","author":"u1","published":false}"#),
+        ])?;
+        let feed = db.view("feed").expect("registered");
+        assert_eq!(feed.delta().get("p2"), Some(&1));
+        assert_eq!(feed.delta().len(), 1);
+        assert_eq!(feed.len(), 2);
+
+        // A change to the joined table reaches every row naming it.
+        let renamed = record(r#"{"name":"alicia","age":30}"#);
+        db.apply_mutation("users", Operation::Update, "u1", Some(&renamed), None)?;
+        let feed = db.view("feed").expect("registered");
+        assert!(feed.delta().is_empty());
+        let mut updated: Vec<_> = feed.updated().iter().map(SmolStr::as_str).collect();
+        updated.sort();
+        assert_eq!(updated, ["p1", "p2"]);
+        let expected = r#"{"title":"b","author":{"name":"alicia"}}"#;
+        assert_eq!(feed.get("p2"), Some(record(expected).as_slice()));
+
+        // Without the joined record the inner join drops the rows.
+        db.apply_mutation("users", Operation::Delete, "u1", None, None)?;
+        let feed = db.view("feed").expect("registered");
+        assert!(feed.is_empty());
+        assert_eq!(feed.delta().values().sum::<i64>(), -2);
+
+        db.bulk_load(vec![BulkRecord {
+            table: SmolStr::new("users"),
+            id: SmolStr::new("u1"),
+            data: record(r#"{"name":"al"}"#),
+            version: None,
+        }])?;
+        assert_eq!(db.view("feed").expect("registered").len(), 2);
 
+        db.truncate_table("posts")?;
+        assert!(db.view("feed").expect("registered").is_empty());
+        assert!(db.refresh_view("feed")?);
+        assert!(db.drop_view("feed").is_some());
+        assert!(!db.refresh_view("feed")?);
         Ok(())
     }
 
     #[test]
-    fn test_row_cache_evicted_on_delete() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_bulk_load_feeds_delta_stream() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
-        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
-
-        assert_eq!(db.get_record_bytes("users", "alice")?, None);
-        assert!(db.get_row_record("users", "alice")?.is_none());
+        let bytes = record(r#"{"n":1}"#);
+        db.apply_mutation("users", Operation::Create, "a", Some(&bytes), None)?;
+        let rx = db.delta_stream(4);
+
+        let load = |table: &str, id: &str| BulkRecord {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+            data: bytes.clone(),
+            version: None,
+        };
+        db.bulk_load(vec![load("users", "a"), load("posts", "p1"), load("users", "b")])?;
+        let batch = rx.try_recv()?;
+        assert_eq!(batch.tick, db.commit_tick());
+        // "a" was already present: updated, but its membership is unchanged.
+        assert_eq!(batch.membership_deltas["users"].len(), 1);
+        assert_eq!(batch.membership_deltas["users"]["b"], 1);
+        assert_eq!(batch.membership_deltas["posts"]["p1"], 1);
+        assert_eq!(batch.content_updates["users"].len(), 2);
+        assert!(rx.try_recv().is_err());
         Ok(())
     }
 
     #[test]
-    fn test_row_cache_rebuilt_on_reopen() -> Result<(), Box<dyn std::error::Error>> {
-        let tmp_dir = tempfile::tempdir()?;
-        let db_path = tmp_dir.path().join("test.redb");
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        {
-            let mut db = SpookyDb::new(&db_path)?;
-            db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+    fn test_delta_stream_backpressure() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"n":1}"#);
+        let rx = db.delta_stream(1);
+
+        db.apply_mutation("t", Operation::Create, "a", Some(&bytes), None)?;
+        let first = rx.try_recv()?;
+        assert_eq!(first.tick, 1);
+        assert_eq!(first.membership_deltas["t"]["a"], 1);
+        assert!(first.content_updates["t"].contains("a"));
+        db.apply_mutation("t", Operation::Delete, "missing", None, None)?;
+        assert!(rx.try_recv().is_err(), "no-op writes send nothing");
+
+        // With one slot, the second of two commits waits for the consumer.
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let writer = {
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                db.apply_mutation("t", Operation::Update, "a", Some(&bytes), None)?;
+                db.apply_mutation("t", Operation::Delete, "a", None, None)?;
+                done.store(true, Ordering::SeqCst);
+                Ok::<_, SpookyDbError>(db)
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!done.load(Ordering::SeqCst));
+        let update = rx.recv()?;
+        // The no-op delete committed too, so it took tick 2.
+        assert_eq!((update.tick, update.membership_deltas.len()), (3, 0));
+        let delete = rx.recv()?;
+        assert_eq!(delete.membership_deltas["t"]["a"], -1);
+        let mut db = writer.join().expect("writer")?;
+        assert!(done.load(Ordering::SeqCst));
+
+        drop(rx);
+        for id in ["b", "c"] {
+            let bytes = record(r#"{"n":2}"#);
+            db.apply_mutation("t", Operation::Create, id, Some(&bytes), None)?;
         }
+        Ok(())
+    }
 
-        // After reopen: ZSet is rebuilt from RECORDS_TABLE; LRU cache starts cold.
-        let db2 = SpookyDb::new(&db_path)?;
-
-        // ZSet is correct — record is known present.
-        assert_eq!(db2.get_zset_weight("users", "alice"), 1);
-
-        // get_record_bytes falls back to redb on cache miss — still returns data.
-        assert_eq!(db2.get_record_bytes("users", "alice")?, Some(data));
-
-        // get_row_record returns None because the cache is cold after reopen.
-        assert!(
-            db2.get_row_record("users", "alice")?.is_none(),
-            "cold cache: get_row_record must return None until a write warms the entry"
+    #[test]
+    fn test_top_k_rank_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let put = |op, id: &str, score: Option<i64>| DbMutation {
+            table: SmolStr::new("players"),
+            id: SmolStr::new(id),
+            op,
+            data: score.map(|n| {
+                let value = SpookyValue::from_json_str(&format!(r#"{{"score":{n}}}"#));
+                crate::serialization::from_spooky(&value.expect("valid JSON")).expect("object").0
+            }),
+            version: None,
+            expires_at: None,
+        };
+        let scores = [("a", 50), ("b", 40), ("c", 30), ("d", 20), ("e", 10)];
+        let players = scores.iter().map(|&(id, n)| put(Operation::Create, id, Some(n)));
+        db.apply_batch(players.collect())?;
+
+        let mut topk = TopK::new("score", 3, SortDirection::Desc);
+        db.top_k("players", &mut topk)?;
+        let top: Vec<_> = topk.top().map(|(id, score)| (id.as_str(), score)).collect();
+        assert_eq!(top, [("a", 50.0), ("b", 40.0), ("c", 30.0)]);
+        assert_eq!(topk.changes().len(), 3);
+        assert!(topk.changes().is_empty());
+
+        // e jumps to first, b leaves, a slips; a low newcomer changes nothing.
+        let result = db.apply_batch(vec![
+            put(Operation::Update, "e", Some(99)),
+            put(Operation::Delete, "b", None),
+            put(Operation::Create, "f", Some(1)),
+        ])?;
+        db.top_k_delta("players", &result, &mut topk)?;
+        let change = |id: &str, from, to| crate::db::RankChange { id: SmolStr::new(id), from, to };
+        assert_eq!(
+            topk.changes(),
+            [change("e", None, Some(0)), change("a", Some(0), Some(1)), change("b", Some(1), None)]
         );
+        assert_eq!((topk.rank("c"), topk.rank("d"), topk.len()), (Some(2), None, 5));
+
+        let result = db.apply_batch(vec![put(Operation::Update, "d", Some(35))])?;
+        db.top_k_delta("players", &result, &mut topk)?;
+        assert_eq!(topk.changes(), [change("d", None, Some(2)), change("c", Some(2), None)]);
         Ok(())
     }
 
     #[test]
-    fn test_table_name_with_colon_rejected() {
-        let tmp = NamedTempFile::new().unwrap();
-        let mut db = SpookyDb::new(tmp.path()).unwrap();
-        let result = db.apply_mutation("a:b", Operation::Create, "id1", Some(&[]), None);
-        assert!(matches!(result, Err(SpookyDbError::InvalidKey(_))));
+    fn test_grouped_operators_retract() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::{AvgBy, CountBy, SumBy};
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let order = |op, id: &str, json: Option<&str>| DbMutation {
+            table: SmolStr::new("orders"),
+            id: SmolStr::new(id),
+            op,
+            data: json.map(record),
+            version: None,
+            expires_at: None,
+        };
+        db.apply_batch(vec![
+            order(Operation::Create, "o1", Some(r#"{"region":"eu","amount":10}"#)),
+            order(Operation::Create, "o2", Some(r#"{"region":"eu","amount":30}"#)),
+            order(Operation::Create, "o3", Some(r#"{"region":"us","amount":5}"#)),
+        ])?;
+        let (mut count, mut sum) = (CountBy::new("region"), SumBy::new("region", "amount"));
+        let mut avg = AvgBy::new("region", "amount");
+        db.fold_table("orders", &mut count)?;
+        db.fold_table("orders", &mut sum)?;
+        db.fold_table("orders", &mut avg)?;
+        let (eu, us) = (SpookyValue::from("eu"), SpookyValue::from("us"));
+        assert_eq!(count.snapshot(), [(eu.clone(), 2), (us.clone(), 1)].into());
+        assert_eq!(avg.get(&eu), Some(20.0));
+        count.changes();
+        sum.changes();
+
+        // o2 moves to us, o3 is deleted: eu loses 30, us swaps 5 for 30.
+        let result = db.apply_batch(vec![
+            order(Operation::Update, "o2", Some(r#"{"region":"us","amount":30}"#)),
+            order(Operation::Delete, "o3", None),
+        ])?;
+        db.fold_batch("orders", &result, &mut count)?;
+        db.fold_batch("orders", &result, &mut sum)?;
+        db.fold_batch("orders", &result, &mut avg)?;
+        assert_eq!(count.snapshot(), [(eu.clone(), 1), (us.clone(), 1)].into());
+        assert_eq!(sum.changes(), [(eu.clone(), Some(10.0)), (us.clone(), Some(30.0))].into());
+        assert_eq!((avg.get(&eu), avg.get(&us)), (Some(10.0), Some(30.0)));
+
+        let result = db.apply_batch(vec![order(Operation::Delete, "o1", None)])?;
+        db.fold_batch("orders", &result, &mut count)?;
+        count.changes();
+        assert_eq!(count.snapshot(), [(us, 1)].into());
+        assert_eq!(count.get(&eu), None);
+        Ok(())
     }
 
     #[test]
-    fn test_zset_not_diverged_after_create() -> Result<(), Box<dyn std::error::Error>> {
-        // Verify that ZSet and rows are in sync after apply_mutation.
+    fn test_join_index_follows_writes() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
-        assert_eq!(db.get_zset_weight("users", "alice"), 1);
-        assert!(db.get_record_bytes("users", "alice")?.is_some());
-
-        db.apply_mutation("users", Operation::Delete, "alice", None, None)?;
-        assert_eq!(db.get_zset_weight("users", "alice"), 0);
-        assert!(db.get_record_bytes("users", "alice")?.is_none());
+        let post = |op, id: &str, author: Option<&str>| DbMutation {
+            table: SmolStr::new("posts"),
+            id: SmolStr::new(id),
+            op,
+            data: author.map(|author| {
+                let json = format!(r#"{{"author":"{author}"}}"#);
+                let value = SpookyValue::from_json_str(&json).expect("valid JSON");
+                crate::serialization::from_spooky(&value).expect("object").0
+            }),
+            version: None,
+            expires_at: None,
+        };
+        let sorted = |db: &SpookyDb, author: &str| {
+            let mut ids: Vec<String> =
+                db.joined_ids("posts", "author", author).map(|id| id.to_string()).collect();
+            ids.sort();
+            ids
+        };
+        db.apply_batch(vec![
+            post(Operation::Create, "p1", Some("alice")),
+            post(Operation::Create, "p2", Some("alice")),
+        ])?;
+        db.register_join("posts", "author", "users")?;
+        assert_eq!(db.joins("posts").collect::<Vec<_>>(), [("author", "users")]);
+        assert_eq!(sorted(&db, "alice"), ["p1", "p2"]);
+
+        db.apply_batch(vec![
+            post(Operation::Update, "p1", Some("bob")),
+            post(Operation::Delete, "p2", None),
+            post(Operation::Create, "p3", Some("alice")),
+        ])?;
+        let p4 = post(Operation::Create, "p4", Some("bob"));
+        db.apply_mutation("posts", p4.op, &p4.id, p4.data.as_deref(), None)?;
+        assert_eq!(sorted(&db, "alice"), ["p3"]);
+        assert_eq!(sorted(&db, "bob"), ["p1", "p4"]);
+
+        db.rename_table("posts", "articles")?;
+        assert_eq!(db.joined_ids("articles", "author", "bob").count(), 2);
+        db.truncate_table("articles")?;
+        assert_eq!(db.joined_ids("articles", "author", "bob").count(), 0);
+        assert!(db.drop_join("articles", "author"));
+        assert_eq!(db.joins("articles").count(), 0);
         Ok(())
     }
 
     #[test]
-    fn test_delete_nonexistent_emits_no_delta() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_sliding_window_expires_events() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::TimeWindow;
+        use std::time::Duration;
         let tmp = NamedTempFile::new()?;
         let mut db = SpookyDb::new(tmp.path())?;
+        let event = |id: &str, at: u64| {
+            let data = record(&format!(r#"{{"at":{at}}}"#));
+            Ok::<_, Box<dyn std::error::Error>>(DbMutation {
+                table: SmolStr::new("events"),
+                id: SmolStr::new(id),
+                op: Operation::Create,
+                data: Some(data),
+                version: None,
+                expires_at: None,
+            })
+        };
+        let ids = |zset: Option<&ZSet>| {
+            let ids = zset.into_iter().flat_map(ZSet::keys);
+            let mut ids: Vec<String> = ids.map(SmolStr::to_string).collect();
+            ids.sort();
+            ids
+        };
+        // 300 ms windows sliding by 100 ms: e1 falls in [0,300), [100,400), [200,500).
+        let (size, slide) = (Duration::from_millis(300), Duration::from_millis(100));
+        let mut recent = TimeWindow::sliding("at", size, slide);
+        let result = db.apply_batch(vec![event("e1", 250)?, event("e2", 320)?])?;
+        db.fold_batch("events", &result, &mut recent)?;
+        assert_eq!(ids(recent.window(200)), ["e1", "e2"]);
+        assert_eq!(ids(recent.window(300)), ["e2"]);
+        assert_eq!(recent.changes()[&0], ZSet::from_iter([(SmolStr::new("e1"), 1)]));
+
+        let closed = recent.advance(400);
+        let closed: Vec<_> = closed.iter().map(|w| (w.start, w.end, w.ids.len())).collect();
+        assert_eq!(closed, [(0, 300, 1), (100, 400, 2)]);
+        assert_eq!(ids(recent.oldest()), ["e1", "e2"]);
+        assert_eq!(recent.changes()[&100].values().sum::<i64>(), -2);
+
+        // e1 leaves with the last window covering it; late events are dropped.
+        recent.advance(500);
+        assert_eq!((recent.len(), ids(recent.oldest())), (1, vec!["e2".to_string()]));
+        let result = db.apply_batch(vec![event("late", 150)?])?;
+        db.fold_batch("events", &result, &mut recent)?;
+        assert_eq!(recent.len(), 1);
 
         let result = db.apply_batch(vec![DbMutation {
-            table: SmolStr::new("users"),
-            id: SmolStr::new("ghost"),
             op: Operation::Delete,
             data: None,
-            version: None,
+            ..event("e2", 0)?
         }])?;
-
-        // No record was present → membership_deltas must be empty.
-        assert!(
-            result.membership_deltas.get("users").map_or(true, |z| z.is_empty()),
-            "spurious -1 delta emitted for a record that never existed"
-        );
+        db.fold_batch("events", &result, &mut recent)?;
+        assert!(recent.is_empty() && recent.windows().next().is_none());
         Ok(())
     }
 
     #[test]
-    fn test_dyn_dbbackend_compiles() {
-        // This test exists purely to assert DbBackend is object-safe.
-        // It will fail to compile if bulk_load still uses impl IntoIterator.
-        let tmp = NamedTempFile::new().unwrap();
-        let db = SpookyDb::new(tmp.path()).unwrap();
-        let _: Box<dyn DbBackend> = Box::new(db);
+    fn test_circuit_join_distinct_steps() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::Circuit;
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let record = |table: &str, op, id: &str| DbMutation {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+            op,
+            data: (op != Operation::Delete).then(|| {
+                let value = SpookyValue::from_json_str("{}").expect("valid JSON");
+                crate::serialization::from_spooky(&value).expect("object").0
+            }),
+            version: None,
+            expires_at: None,
+        };
+        db.apply_batch(vec![
+            record("posts", Operation::Create, "p1"),
+            record("posts", Operation::Create, "p2"),
+            record("comments", Operation::Create, "p1:c1"),
+        ])?;
+
+        // Posts with at least one comment, by the post part of comment ids.
+        let mut circuit = Circuit::new();
+        let comments = circuit.source("comments");
+        let posts = circuit.source("posts");
+        let post_of = |id: &SmolStr| id.split_once(':').map(|(post, _)| SmolStr::new(post));
+        let post_id = |id: &SmolStr| Some(id.clone());
+        let joined = circuit.join(comments, posts, post_of, post_id, |_, post| post.clone());
+        let discussed = circuit.distinct(joined);
+        circuit.sink("discussed", discussed);
+        let p = |id: &str, weight: i64| (SmolStr::new(id), weight);
+        let seeded = db.seed_circuit(&mut circuit)?;
+        assert_eq!(seeded["discussed"], ZSet::from_iter([p("p1", 1)]));
+
+        // A second comment on p1 changes nothing; one on p2 adds it.
+        let result = db.apply_batch(vec![
+            record("comments", Operation::Create, "p1:c2"),
+            record("comments", Operation::Create, "p2:c1"),
+        ])?;
+        assert_eq!(circuit.step(&result)["discussed"], ZSet::from_iter([p("p2", 1)]));
+
+        // Deleting p1 retracts it even though its comments remain.
+        let result = db.apply_batch(vec![
+            record("posts", Operation::Delete, "p1"),
+            record("comments", Operation::Delete, "p2:c1"),
+            record("comments", Operation::Create, "p3:c1"),
+        ])?;
+        let changes = circuit.step(&result);
+        assert_eq!(changes["discussed"], ZSet::from_iter([p("p1", -1), p("p2", -1)]));
+        assert!(circuit.output("discussed").is_some_and(ZSet::is_empty));
+
+        let result = db.apply_batch(vec![record("posts", Operation::Create, "p3")])?;
+        assert_eq!(circuit.step(&result)["discussed"], ZSet::from_iter([p("p3", 1)]));
+        assert_eq!(circuit.sink_names().collect::<Vec<_>>(), ["discussed"]);
+        Ok(())
     }
 
     #[test]
-    fn test_cache_miss_falls_back_to_redb() -> Result<(), Box<dyn std::error::Error>> {
-        let tmp_dir = tempfile::tempdir()?;
-        let db_path = tmp_dir.path().join("test.redb");
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        // Write a record and close the DB.
+    fn test_persisted_view_survives_reopen() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let view = || {
+            View::new("active", "users")
+                .filter(|r| r.get_bool("active") == Some(true))
+                .project(&["name"])
+                .persisted()
+        };
         {
-            let mut db = SpookyDb::new(&db_path)?;
-            db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
+            let mut db = SpookyDb::new(tmp.path())?;
+            let alice = record(r#"{"name":"alice","active":true}"#);
+            db.apply_mutation("users", Operation::Create, "u1", Some(&alice), None)?;
+            db.register_view(view())?;
+            let bob = record(r#"{"name":"bob","active":true}"#);
+            db.apply_mutation("users", Operation::Create, "u2", Some(&bob), None)?;
+            db.apply_mutation("users", Operation::Delete, "u1", None, None)?;
+
+            // The view's records are a table like any other.
+            let stored = db.get_record_bytes("__view:active", "u2")?;
+            assert_eq!(stored, Some(record(r#"{"name":"bob"}"#)));
+            assert_eq!(db.get_record_bytes("__view:active", "u1")?, None);
+            assert_eq!(db.table_len("__view:active"), 1);
         }
 
-        // Reopen — cache is cold but ZSet is rebuilt.
-        let db2 = SpookyDb::new(&db_path)?;
-        assert_eq!(db2.get_zset_weight("users", "alice"), 1); // ZSet present
-
-        // get_row_record returns None (cold cache after reopen).
-        assert!(db2.get_row_record("users", "alice")?.is_none());
-
-        // get_record_bytes falls back to redb — still returns data.
-        let fetched = db2
-            .get_record_bytes("users", "alice")?
-            .expect("redb fallback must work on cache miss");
-        assert_eq!(fetched, data);
-
+        let mut db = SpookyDb::new(tmp.path())?;
+        // Written while the view is not registered: invisible until a refresh,
+        // which shows the view was loaded rather than recomputed.
+        let carol = record(r#"{"name":"carol","active":true}"#);
+        db.apply_mutation("users", Operation::Create, "u3", Some(&carol), None)?;
+        db.register_view(view())?;
+        let active = db.view("active").expect("registered");
+        assert_eq!(active.ids().collect::<Vec<_>>(), ["u2"]);
+        assert_eq!(active.get("u2"), Some(record(r#"{"name":"bob"}"#).as_slice()));
+
+        assert!(db.refresh_view("active")?);
+        assert_eq!(db.view("active").map(View::len), Some(2));
+        assert_eq!(db.table_len("__view:active"), 2);
+        db.apply_mutation("users", Operation::Delete, "u2", None, None)?;
+        assert_eq!(db.ids("__view:active").collect::<Vec<_>>(), ["u3"]);
         Ok(())
     }
 
     #[test]
-    fn test_cache_eviction_correctness() -> Result<(), Box<dyn std::error::Error>> {
-        // Cache capacity 2, insert 3 records. 3rd insert evicts the 1st.
-        // Verify: ZSet has all 3; get_record_bytes works for all 3 (redb fallback);
-        // get_row_record returns None for the evicted record.
+    fn test_persisted_view_shares_the_user_tick() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
-        let mut db = SpookyDb::new_with_config(
-            tmp.path(),
-            SpookyDbConfig {
-                cache_capacity: std::num::NonZeroUsize::new(2).unwrap(),
-            },
-        )?;
-
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        db.apply_mutation("t", Operation::Create, "r1", Some(&data), None)?;
-        db.apply_mutation("t", Operation::Create, "r2", Some(&data), None)?;
-        db.apply_mutation("t", Operation::Create, "r3", Some(&data), None)?; // evicts r1
-
-        // ZSet has all 3.
-        assert_eq!(db.get_zset_weight("t", "r1"), 1);
-        assert_eq!(db.get_zset_weight("t", "r2"), 1);
-        assert_eq!(db.get_zset_weight("t", "r3"), 1);
-
-        // get_record_bytes works for all 3 (redb fallback for evicted r1).
-        assert!(db.get_record_bytes("t", "r1")?.is_some(), "redb fallback for evicted r1");
-        assert!(db.get_record_bytes("t", "r2")?.is_some());
-        assert!(db.get_record_bytes("t", "r3")?.is_some());
-
-        // get_row_record: r1 evicted, r2 and r3 still in cache.
-        assert!(db.get_row_record("t", "r1")?.is_none(), "r1 should be evicted from cache");
-        assert!(db.get_row_record("t", "r2")?.is_some(), "r2 should still be in cache");
-        assert!(db.get_row_record("t", "r3")?.is_some(), "r3 should be in cache");
+        let config = SpookyDbConfig {
+            delta_log: true,
+            oplog: OplogMode::Metadata,
+            ..SpookyDbConfig::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        db.register_view(View::new("v", "users").project(&["name"]).persisted())?;
+        let rx = db.delta_stream(4);
 
+        let alice = record(r#"{"name":"alice"}"#);
+        let write = DbMutation {
+            table: SmolStr::new("users"),
+            id: SmolStr::new("u1"),
+            op: Operation::Create,
+            data: Some(alice),
+            version: None,
+            expires_at: None,
+        };
+        let result = db.apply_batch(vec![write])?;
+        assert_eq!(db.table_len("__view:v"), 1);
+        assert_eq!((result.tick, db.commit_tick()), (1, 1));
+        let batch = rx.try_recv()?;
+        assert_eq!(batch.tick, 1);
+        assert_eq!(batch.membership_deltas.keys().collect::<Vec<_>>(), ["users"]);
+        assert!(rx.try_recv().is_err());
+
+        // Only the user's write reaches the delta log and the oplog.
+        let logged = db.read_deltas(..)?;
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].1.keys().collect::<Vec<_>>(), ["users"]);
+        let oplog = db.read_oplog(0)?;
+        assert_eq!(oplog.iter().map(|e| e.table.as_str()).collect::<Vec<_>>(), ["users"]);
         Ok(())
     }
 
     #[test]
-    fn test_cache_capacity_bounds_memory() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_commit_ticks_persist_and_tag_reads() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
-        let mut db = SpookyDb::new_with_config(
-            tmp.path(),
-            SpookyDbConfig {
-                cache_capacity: std::num::NonZeroUsize::new(5).unwrap(),
-            },
-        )?;
-
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        // Insert 10 records into a cache of capacity 5.
-        for i in 0u32..10 {
-            let id = format!("r{i}");
-            db.apply_mutation("t", Operation::Create, &id, Some(&data), None)?;
-        }
-
-        // ZSet has all 10.
-        assert_eq!(db.table_len("t"), 10);
-
-        // Cache has at most 5.
-        let cached_count = (0u32..10)
-            .filter(|i| db.get_row_record("t", &format!("r{i}")).ok().flatten().is_some())
-            .count();
-        assert!(cached_count <= 5, "cache exceeded capacity: {cached_count} entries cached");
-
-        // get_record_bytes works for all 10 via redb fallback.
-        for i in 0u32..10 {
-            let id = format!("r{i}");
-            assert!(
-                db.get_record_bytes("t", &id)?.is_some(),
-                "redb fallback failed for r{i}"
-            );
+        let bytes = record(r#"{"n":1}"#);
+        let put = |id: &str| DbMutation {
+            table: SmolStr::new("t"),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(bytes.clone()),
+            version: None,
+            expires_at: None,
+        };
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            assert_eq!(db.commit_tick(), 0);
+            assert_eq!(db.apply_batch(vec![put("a")])?.tick, 1);
+            db.apply_mutation("t", Operation::Create, "b", Some(&bytes), None)?;
+            let both = db.apply_batches(vec![vec![put("c")], vec![put("d")]])?;
+            let ticks: Result<Vec<u64>, _> = both.into_iter().map(|r| r.map(|r| r.tick)).collect();
+            assert_eq!(ticks?, [3, 3]);
+            db.bulk_load(vec![BulkRecord {
+                table: SmolStr::new("t"),
+                id: SmolStr::new("e"),
+                data: bytes.clone(),
+                version: None,
+            }])?;
+            assert_eq!(db.commit_tick(), 4);
         }
 
+        let mut db = SpookyDb::new(tmp.path())?;
+        assert_eq!(db.commit_tick(), 4);
+        let reader = db.reader()?;
+        assert_eq!(db.apply_batch(vec![put("f")])?.tick, 5);
+        // The snapshot predates tick 5 and says so.
+        assert_eq!((reader.commit_tick(), reader.get_zset_weight("t", "f")), (4, 0));
+        assert_eq!(db.sweep_expired(0)?.tick, 5);
         Ok(())
     }
 
     #[test]
-    fn test_delete_removes_from_cache() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_delta_log_replays_by_tick() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::Circuit;
         let tmp = NamedTempFile::new()?;
-        let mut db = SpookyDb::new(tmp.path())?;
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        db.apply_mutation("t", Operation::Create, "r1", Some(&data), None)?;
-        assert!(db.get_row_record("t", "r1")?.is_some(), "r1 should be in cache after create");
+        let bytes = record(r#"{"n":1}"#);
+        let write = |op, id: &str| DbMutation {
+            table: SmolStr::new("t"),
+            id: SmolStr::new(id),
+            op,
+            data: (op != Operation::Delete).then(|| bytes.clone()),
+            version: None,
+            expires_at: None,
+        };
+        let config = || SpookyDbConfig {
+            delta_log: true,
+            ..SpookyDbConfig::default()
+        };
+        let mut results = Vec::new();
+        {
+            let mut db = SpookyDb::new_with_config(tmp.path(), config())?;
+            results.push(db.apply_batch(vec![write(Operation::Create, "a")])?);
+            results.push(db.apply_batch(vec![
+                write(Operation::Create, "b"),
+                write(Operation::Delete, "a"),
+            ])?);
+            // Neither changes membership, so neither is logged.
+            db.apply_batch(vec![write(Operation::Update, "b")])?;
+            db.apply_batch(vec![write(Operation::Create, "c"), write(Operation::Delete, "c")])?;
+            results.push(db.apply_batch(vec![write(Operation::Delete, "b")])?);
+        }
 
-        db.apply_mutation("t", Operation::Delete, "r1", None, None)?;
-        // ZSet and cache must both be gone; ZSet guard prevents redb read.
-        assert_eq!(db.get_zset_weight("t", "r1"), 0);
-        assert!(db.get_row_record("t", "r1")?.is_none());
-        assert!(db.get_record_bytes("t", "r1")?.is_none());
+        let mut db = SpookyDb::new_with_config(tmp.path(), config())?;
+        let logged = db.read_deltas(..)?;
+        let ticks: Vec<u64> = logged.iter().map(|(tick, _)| *tick).collect();
+        assert_eq!(ticks, [1, 2, 5]);
+        for ((_, deltas), result) in logged.iter().zip(&results) {
+            assert_eq!(deltas, &result.membership_deltas);
+        }
+        assert_eq!(db.read_deltas(2..5)?.len(), 1);
+
+        // Replaying the log drives a circuit to the same output as live steps.
+        let mut live = Circuit::new();
+        let mut replayed = Circuit::new();
+        for circuit in [&mut live, &mut replayed] {
+            let source = circuit.source("t");
+            circuit.sink("all", source);
+        }
+        for result in &results {
+            live.step(result);
+        }
+        for (_, deltas) in &logged {
+            replayed.step_deltas(deltas);
+        }
+        assert_eq!(live.output("all"), replayed.output("all"));
 
+        assert_eq!(db.trim_deltas(2)?, 2);
+        assert_eq!(db.read_deltas(..)?.len(), 1);
         Ok(())
     }
 
     #[test]
-    fn test_get_row_record_zero_copy() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_table_removal_logs_deltas() -> Result<(), Box<dyn std::error::Error>> {
         let tmp = NamedTempFile::new()?;
-        let mut db = SpookyDb::new(tmp.path())?;
-
-        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(BENCH_CBOR)?;
-        let (data, _) = from_cbor(&cbor)?;
-
-        // Non-existent record returns None.
-        assert!(db.get_row_record("users", "alice")?.is_none());
-
-        // Insert a record, then verify we can read a field from the zero-copy view.
-        db.apply_mutation("users", Operation::Create, "alice", Some(&data), None)?;
-
-        let record = db.get_row_record("users", "alice")?.expect("should be in cache");
-        // The CBOR fixture has "age" = 28 (i64).
-        let age = record.get_i64("age");
-        assert!(age.is_some(), "age field should be readable from cached record");
-        assert_eq!(age.unwrap(), 28);
-
+        let config = SpookyDbConfig {
+            delta_log: true,
+            ..SpookyDbConfig::default()
+        };
+        let mut db = SpookyDb::new_with_config(tmp.path(), config)?;
+        let bytes = record(r#"{"n":1}"#);
+        for (table, id) in [("a", "1"), ("a", "2"), ("b", "1")] {
+            db.apply_mutation(table, Operation::Create, id, Some(&bytes), None)?;
+        }
+        let rx = db.delta_stream(4);
+
+        assert_eq!(db.truncate_table("a")?, 2);
+        assert_eq!(db.rename_table("b", "c")?, 1);
+        let logged = db.read_deltas(4..)?;
+        assert_eq!(logged.len(), 2);
+        let (_, truncated) = &logged[0];
+        assert_eq!((truncated["a"]["1"], truncated["a"]["2"]), (-1, -1));
+        let (_, renamed) = &logged[1];
+        assert_eq!((renamed["b"]["1"], renamed["c"]["1"]), (-1, 1));
+
+        // The stream sees the same batches, at the same ticks.
+        for (tick, deltas) in &logged {
+            let batch = rx.try_recv()?;
+            assert_eq!((&batch.tick, &batch.membership_deltas), (tick, deltas));
+        }
+        // Nothing left to remove: nothing to send.
+        assert_eq!(db.drop_table("a")?, 0);
+        assert!(rx.try_recv().is_err());
         Ok(())
     }
 
     #[test]
-    fn zset_not_mutated_before_commit() {
-        use crate::spooky_value::{SpookyNumber, SpookyValue};
-        let dir = tempfile::tempdir().unwrap();
-        let mut db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
-
-        let mut buf = Vec::new();
-        let mut m = std::collections::BTreeMap::new();
-        m.insert(SmolStr::new("x"), SpookyValue::Number(SpookyNumber::I64(1)));
-        crate::serialization::serialize_into(&m, &mut buf).unwrap();
-
-        let result = db.apply_batch(vec![DbMutation {
-            table: SmolStr::new("users"),
-            id: SmolStr::new("u1"),
-            op: Operation::Create,
-            data: Some(buf),
-            version: None,
-        }]).unwrap();
-
-        let zset = db.get_table_zset("users").unwrap();
-        assert_eq!(zset.get("u1"), Some(&1i64));
-        assert_eq!(result.membership_deltas["users"].get("u1"), Some(&1i64));
-    }
-
-    #[test]
-    fn rejects_colon_in_table_name() {
-        let dir = tempfile::tempdir().unwrap();
-        let mut db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
-
-        let result = db.apply_batch(vec![DbMutation {
-            table: SmolStr::new("bad:name"),
-            id: SmolStr::new("rec1"),
-            op: Operation::Delete,
-            data: None,
-            version: None,
-        }]);
-
-        assert!(result.is_err());
-        let msg = result.unwrap_err().to_string();
-        assert!(msg.contains(':'), "error message should mention the colon: {msg}");
-    }
-
-    #[test]
-    fn rejects_empty_table_name() {
-        let dir = tempfile::tempdir().unwrap();
-        let mut db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
-
-        let result = db.apply_batch(vec![DbMutation {
-            table: SmolStr::new(""),
-            id: SmolStr::new("rec1"),
-            op: Operation::Delete,
-            data: None,
-            version: None,
-        }]);
+    fn test_bulk_load_cbor_stream_array_and_sequence() -> Result<(), Box<dyn std::error::Error>> {
+        use cbor4ii::core::Value;
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let item = |id: &str, n: i128| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+            let map = Value::Map(vec![
+                (Value::Text("id".into()), Value::Text(id.into())),
+                (Value::Text("n".into()), Value::Integer(n)),
+                (Value::Text("tags".into()), Value::Array(vec![Value::Text("x".into())])),
+            ]);
+            Ok(cbor4ii::serde::to_vec(Vec::new(), &map)?)
+        };
 
-        assert!(result.is_err());
+        let sequence = [item("a", 1)?, item("b", 2)?].concat();
+        assert_eq!(db.bulk_load_cbor_stream("t", &sequence[..])?, 2);
+        // Definite and indefinite arrays.
+        let definite = [vec![0x82], item("c", 3)?, item("d", 4)?].concat();
+        assert_eq!(db.bulk_load_cbor_stream("t", &definite[..])?, 2);
+        let indefinite = [vec![0x9f], item("e", 5)?, vec![0xff]].concat();
+        assert_eq!(db.bulk_load_cbor_stream("t", &indefinite[..])?, 1);
+
+        let fields = ["id", "n"];
+        let d = db.get_record_typed("t", "d", &fields)?.expect("loaded");
+        assert!(d.get("id").is_none());
+        assert_eq!(d.get("n").and_then(|v| v.as_i64()), Some(4));
+        assert_eq!(db.table_len("t"), 5);
+
+        let no_id = cbor4ii::serde::to_vec(Vec::new(), &Value::Map(vec![]))?;
+        let err = db.bulk_load_cbor_stream("t", &[item("f", 6)?, no_id].concat()[..]);
+        assert!(matches!(err, Err(SpookyDbError::Serialization(msg)) if msg.starts_with("item 1")));
+        let truncated = &definite[..definite.len() - 1];
+        assert!(db.bulk_load_cbor_stream("t", truncated).is_err());
+        Ok(())
     }
 
     #[test]
-    fn get_record_returns_none_for_missing() {
-        let dir = tempfile::tempdir().unwrap();
-        let db = SpookyDb::new(dir.path().join("test.redb")).unwrap();
+    fn test_live_notifications_apply_as_mutations() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::db::ingest::live_mutation;
+        use cbor4ii::core::Value;
+        use cbor4ii::core::enc::Encode;
+        let tmp = NamedTempFile::new()?;
+        let mut db = SpookyDb::new(tmp.path())?;
+        let text = |s: &str| Value::Text(s.into());
+        let thing = |id: &str| Value::Tag(8, Box::new(Value::Array(vec![text("user"), text(id)])));
+        let notification = |action: &str, id: &str, rv: i128| {
+            let record = Value::Map(vec![
+                (text("id"), thing(id)),
+                (text("name"), text("Ann")),
+                (text("spooky_rv"), Value::Integer(rv)),
+            ]);
+            let map = Value::Map(vec![
+                (text("action"), text(action)),
+                (text("record"), thing(id)),
+                (text("result"), record),
+            ]);
+            let mut writer = cbor4ii::core::utils::BufWriter::new(Vec::new());
+            map.encode(&mut writer).map(|_| writer.into_inner())
+        };
 
-        let result = db.get_row_record("users", "nonexistent");
-        assert!(result.is_ok(), "expected Ok, got {result:?}");
-        assert!(result.unwrap().is_none());
+        let create = live_mutation(&notification("CREATE", "ann", 1)?)?.expect("a mutation");
+        assert_eq!((create.table.as_str(), create.id.as_str()), ("user", "ann"));
+        db.apply_batch(vec![create])?;
+        // In the RPC envelope, as SurrealDB sends it.
+        let update = notification("UPDATE", "ann", 2)?;
+        let envelope = [vec![0xa1, 0x66], b"result".to_vec(), update].concat();
+        db.apply_batch(vec![live_mutation(&envelope)?.expect("a mutation")])?;
+        assert_eq!(db.get_version("user", "ann")?, Some(2));
+        let fields = ["id", "name"];
+        let ann = db.get_record_typed("user", "ann", &fields)?.expect("stored");
+        assert_eq!(ann.get("id").and_then(|v| v.as_str()), Some("user:ann"));
+
+        let delete = live_mutation(&notification("DELETE", "ann", 2)?)?.expect("a mutation");
+        assert_eq!((delete.op, delete.data.is_none()), (Operation::Delete, true));
+        db.apply_batch(vec![delete])?;
+        assert_eq!(db.get_zset_weight("user", "ann"), 0);
+        assert!(live_mutation(&notification("KILLED", "ann", 0)?)?.is_none());
+        assert!(live_mutation(&notification("PATCH", "ann", 0)?).is_err());
+        Ok(())
     }
 }
@@ -0,0 +1,453 @@
+//! A minimal HTTP/JSON API over a [`SpookyDb`], for poking at a store
+//! during development. Not hardened for production: one request per
+//! connection, no TLS, no authentication.
+//!
+//! | Request | Response |
+//! |---------|----------|
+//! | `GET /tables` | `{"users": 2, ...}`: record count per table |
+//! | `GET /tables/{t}?after=&limit=&fields=` | `{"records": [...], "next": id \| null}` |
+//! | `GET /tables/{t}/{id}?fields=` | The record as a JSON object |
+//! | `PUT /tables/{t}/{id}` | Store the JSON object body: 201 created, 204 replaced |
+//! | `DELETE /tables/{t}/{id}` | 204, or 404 if absent |
+//! | `GET /changes/{t}` | Server-sent events, one per committed write |
+//!
+//! The change feed lives outside `/tables/{t}/` so no record id can shadow
+//! it.
+//!
+//! Records do not store field names, so reads take them from `fields`
+//! (comma-separated) or the table's schema, as `export_jsonl` does; the
+//! record id is added under `"id"`.
+//!
+//! ```rust,ignore
+//! let server = HttpServer::bind(db, "127.0.0.1:8080")?;
+//! server.run()?; // curl localhost:8080/tables/users?fields=name,age
+//! ```
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::Bound;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use smol_str::SmolStr;
+
+use super::db::{SpookyDb, record_typed};
+use super::types::{ChangeEvent, FastMap, Operation, SpookyDbError};
+use crate::serialization::from_json_str;
+use crate::spooky_value::SpookyValue;
+
+/// Page size of a table scan without `limit`, and the largest allowed.
+const DEFAULT_PAGE: usize = 100;
+const MAX_PAGE: usize = 10_000;
+
+/// Largest accepted request body.
+const MAX_BODY: usize = 16 << 20;
+
+/// Interval of SSE keep-alive comments; a write failing on one ends the
+/// stream of a client that has gone away.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// A bound listener serving one database. See the module docs.
+pub struct HttpServer {
+    listener: TcpListener,
+    db: Arc<Mutex<SpookyDb>>,
+}
+
+impl HttpServer {
+    /// Bind `addr`; port 0 picks a free one (see `local_addr`).
+    pub fn bind(db: SpookyDb, addr: impl ToSocketAddrs) -> Result<Self, SpookyDbError> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            db: Arc::new(Mutex::new(db)),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, SpookyDbError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve until accepting fails, each connection on its own thread.
+    /// Requests hold the database lock while they run, change streams only
+    /// while subscribing.
+    pub fn run(self) -> Result<(), SpookyDbError> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let db = Arc::clone(&self.db);
+            std::thread::spawn(move || {
+                // The client went away; nothing to report to.
+                let _ = serve(&db, stream);
+            });
+        }
+        Ok(())
+    }
+}
+
+struct Request {
+    method: String,
+    /// Percent-decoded path segments.
+    path: Vec<String>,
+    query: FastMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Status code and JSON body.
+type Response = (u16, String);
+
+fn lock(db: &Mutex<SpookyDb>) -> MutexGuard<'_, SpookyDb> {
+    db.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn serve(db: &Mutex<SpookyDb>, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = match read_request(&mut reader) {
+        Ok(request) => request,
+        Err(msg) => return respond(stream, error(400, msg)),
+    };
+    let path: Vec<&str> = request.path.iter().map(String::as_str).collect();
+    match (request.method.as_str(), path.as_slice()) {
+        ("GET", ["changes", table]) => {
+            let events = lock(db).subscribe(table);
+            match events {
+                Ok(events) => stream_changes(stream, events),
+                Err(e) => respond(stream, db_error(e)),
+            }
+        }
+        _ => {
+            let response = route(&mut lock(db), &request, &path);
+            respond(stream, response)
+        }
+    }
+}
+
+fn route(db: &mut SpookyDb, request: &Request, path: &[&str]) -> Response {
+    let result = match (request.method.as_str(), path) {
+        ("GET", ["tables"]) => Ok(list_tables(db)),
+        ("GET", ["tables", table]) => scan(db, table, request),
+        ("GET", ["tables", table, id]) => get(db, table, id, request),
+        ("PUT", ["tables", table, id]) => put(db, table, id, &request.body),
+        ("DELETE", ["tables", table, id]) => delete(db, table, id),
+        (_, ["tables" | "changes", ..]) => return error(405, "method not allowed"),
+        _ => return error(404, "not found"),
+    };
+    result.unwrap_or_else(db_error)
+}
+
+fn list_tables(db: &SpookyDb) -> Response {
+    let mut counts = crate::spooky_value::FastMap::new();
+    for table in db.table_names() {
+        let count = SpookyValue::from(db.table_len(table) as u64);
+        counts.insert(table.clone(), count);
+    }
+    (200, SpookyValue::Object(counts).to_json_string())
+}
+
+/// Field names for reading `table`: `?fields=` or the table's schema.
+fn field_names(db: &SpookyDb, table: &str, request: &Request) -> Result<Vec<String>, Response> {
+    match (request.query.get("fields"), db.schema(table)) {
+        (Some(fields), _) => Ok(fields.split(',').map(str::to_owned).collect()),
+        (None, Some(schema)) => Ok(schema.fields().map(|(name, _)| name.to_string()).collect()),
+        (None, None) => Err(error(400, "no ?fields= given and no schema set on the table")),
+    }
+}
+
+/// Record bytes as a JSON object of `names`, with the id under `"id"`.
+fn record_json(id: &str, bytes: &[u8], names: &[String]) -> Result<SpookyValue, SpookyDbError> {
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let mut value = record_typed(bytes, &names)?;
+    if let SpookyValue::Object(map) = &mut value {
+        map.insert(SmolStr::new("id"), SpookyValue::from(id));
+    }
+    Ok(value)
+}
+
+fn scan(db: &SpookyDb, table: &str, request: &Request) -> Result<Response, SpookyDbError> {
+    let names = match field_names(db, table, request) {
+        Ok(names) => names,
+        Err(response) => return Ok(response),
+    };
+    let limit = match request.query.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => DEFAULT_PAGE,
+        Some(Ok(limit)) if (1..=MAX_PAGE).contains(&limit) => limit,
+        Some(_) => return Ok(error(400, format!("limit must be 1 to {MAX_PAGE}"))),
+    };
+    let start = match request.query.get("after") {
+        Some(after) => Bound::Excluded(after.as_str()),
+        None => Bound::Unbounded,
+    };
+    let mut records = Vec::new();
+    let mut next = None;
+    let mut failure = None;
+    db.scan_keys(table, start, Bound::Unbounded, |id, bytes| {
+        if records.len() == limit {
+            next = records.last().and_then(|r: &SpookyValue| r.get("id")).cloned();
+            return false;
+        }
+        match record_json(id, bytes, &names) {
+            Ok(record) => records.push(record),
+            Err(e) => failure = Some(e),
+        }
+        failure.is_none()
+    })?;
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    let mut page = crate::spooky_value::FastMap::new();
+    page.insert(SmolStr::new("records"), SpookyValue::Array(records));
+    page.insert(SmolStr::new("next"), next.unwrap_or(SpookyValue::Null));
+    Ok((200, SpookyValue::Object(page).to_json_string()))
+}
+
+fn get(db: &SpookyDb, table: &str, id: &str, request: &Request) -> Result<Response, SpookyDbError> {
+    let names = match field_names(db, table, request) {
+        Ok(names) => names,
+        Err(response) => return Ok(response),
+    };
+    match db.get_record_bytes(table, id)? {
+        Some(bytes) => Ok((200, record_json(id, &bytes, &names)?.to_json_string())),
+        None => Ok(error(404, "no such record")),
+    }
+}
+
+fn put(db: &mut SpookyDb, table: &str, id: &str, body: &[u8]) -> Result<Response, SpookyDbError> {
+    let Ok(text) = std::str::from_utf8(body) else {
+        return Ok(error(400, "body is not UTF-8"));
+    };
+    let bytes = match from_json_str(text) {
+        Ok((bytes, _)) => bytes,
+        Err(e) => return Ok(error(400, e)),
+    };
+    let (op, status) = match db.get_zset_weight(table, id) > 0 {
+        true => (Operation::Update, 204),
+        false => (Operation::Create, 201),
+    };
+    db.apply_mutation(table, op, id, Some(&bytes), None)?;
+    Ok((status, String::new()))
+}
+
+fn delete(db: &mut SpookyDb, table: &str, id: &str) -> Result<Response, SpookyDbError> {
+    if db.get_zset_weight(table, id) <= 0 {
+        return Ok(error(404, "no such record"));
+    }
+    db.apply_mutation(table, Operation::Delete, id, None, None)?;
+    Ok((204, String::new()))
+}
+
+fn error(status: u16, msg: impl std::fmt::Display) -> Response {
+    let mut body = crate::spooky_value::FastMap::new();
+    body.insert(SmolStr::new("error"), SpookyValue::from(msg.to_string()));
+    (status, SpookyValue::Object(body).to_json_string())
+}
+
+fn db_error(e: SpookyDbError) -> Response {
+    let status = match e {
+        SpookyDbError::InvalidKey(_)
+        | SpookyDbError::Serialization(_)
+        | SpookyDbError::SchemaViolation { .. } => 400,
+        SpookyDbError::VersionConflict { .. } | SpookyDbError::UniqueViolation { .. } => 409,
+        _ => 500,
+    };
+    error(status, e)
+}
+
+// ─── Wire Format ─────────────────────────────────────────────────────────────
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".into());
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.split('/').filter(|s| !s.is_empty()).map(percent_decode).collect();
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+    let method = method.to_owned();
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().map_err(|_| "bad Content-Length")?;
+        }
+    }
+    if length > MAX_BODY {
+        return Err(format!("body over {MAX_BODY} bytes"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(Request {
+        method,
+        path,
+        query,
+        body,
+    })
+}
+
+/// `%XX` escapes decoded, and `+` as a space; invalid escapes kept as is.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => out.push(b' '),
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn respond(mut stream: TcpStream, (status, body): Response) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Send each change as an SSE event named after its operation, with
+/// `{"id", "version"}` as data, until the client goes away.
+fn stream_changes(
+    mut stream: TcpStream,
+    events: std::sync::mpsc::Receiver<ChangeEvent>,
+) -> io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+          Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+    )?;
+    stream.flush()?;
+    loop {
+        match events.recv_timeout(KEEP_ALIVE) {
+            Ok(event) => {
+                let name = match event.op {
+                    Operation::Create => "create",
+                    Operation::Update => "update",
+                    Operation::Delete => "delete",
+                };
+                let mut data = crate::spooky_value::FastMap::new();
+                data.insert(SmolStr::new("id"), SpookyValue::from(event.id.as_str()));
+                let version = event.version.map_or(SpookyValue::Null, SpookyValue::from);
+                data.insert(SmolStr::new("version"), version);
+                let data = SpookyValue::Object(data).to_json_string();
+                write!(stream, "event: {name}\ndata: {data}\n\n")?;
+            }
+            Err(RecvTimeoutError::Timeout) => stream.write_all(b": keep-alive\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        stream.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    /// Send one request and return the status and body.
+    fn call(addr: SocketAddr, method: &str, target: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let head = format!("{method} {target} HTTP/1.1\r\nContent-Length: {}\r\n\r\n", body.len());
+        stream.write_all(format!("{head}{body}").as_bytes()).unwrap();
+        let mut response = String::new();
+        std::io::Read::read_to_string(&mut stream, &mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_owned();
+        (status, body)
+    }
+
+    #[test]
+    fn test_crud_scan_and_change_stream() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let server = HttpServer::bind(SpookyDb::new(tmp.path())?, "127.0.0.1:0")?;
+        let addr = server.local_addr()?;
+        std::thread::spawn(move || server.run());
+
+        let mut changes = TcpStream::connect(addr)?;
+        changes.write_all(b"GET /changes/users HTTP/1.1\r\n\r\n")?;
+        let mut changes = BufReader::new(changes);
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            changes.read_line(&mut line)?;
+        }
+
+        assert_eq!(call(addr, "PUT", "/tables/users/ann", r#"{"name":"Ann","age":40}"#).0, 201);
+        assert_eq!(call(addr, "PUT", "/tables/users/b%20b", r#"{"name":"Bob"}"#).0, 201);
+        assert_eq!(call(addr, "PUT", "/tables/users/ann", r#"{"name":"Ann","age":41}"#).0, 204);
+        assert_eq!(call(addr, "PUT", "/tables/users/x", "[1]").0, 400);
+
+        let (status, body) = call(addr, "GET", "/tables/users/ann?fields=age", "");
+        assert_eq!((status, body.as_str()), (200, r#"{"age":41,"id":"ann"}"#));
+        assert_eq!(call(addr, "GET", "/tables/users/ann", "").0, 400);
+        assert_eq!(call(addr, "GET", "/tables", "").1, r#"{"users":2}"#);
+
+        let (_, page) = call(addr, "GET", "/tables/users?fields=name&limit=1", "");
+        assert_eq!(page, r#"{"next":"ann","records":[{"id":"ann","name":"Ann"}]}"#);
+        let (_, page) = call(addr, "GET", "/tables/users?fields=name&after=ann", "");
+        assert_eq!(page, r#"{"next":null,"records":[{"id":"b b","name":"Bob"}]}"#);
+
+        assert_eq!(call(addr, "DELETE", "/tables/users/ann", "").0, 204);
+        assert_eq!(call(addr, "DELETE", "/tables/users/ann", "").0, 404);
+        assert_eq!(call(addr, "GET", "/tables/users/ann?fields=age", "").0, 404);
+
+        let mut events = Vec::new();
+        while events.len() < 4 {
+            line.clear();
+            changes.read_line(&mut line)?;
+            if let Some(name) = line.strip_prefix("event: ") {
+                events.push(name.trim_end().to_owned());
+            }
+        }
+        assert_eq!(events, ["create", "create", "update", "delete"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_named_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let server = HttpServer::bind(SpookyDb::new(tmp.path())?, "127.0.0.1:0")?;
+        let addr = server.local_addr()?;
+        std::thread::spawn(move || server.run());
+
+        assert_eq!(call(addr, "PUT", "/tables/feeds/changes", r#"{"n":1}"#).0, 201);
+        let (status, body) = call(addr, "GET", "/tables/feeds/changes?fields=n", "");
+        assert_eq!((status, body.as_str()), (200, r#"{"id":"changes","n":1}"#));
+        assert_eq!(call(addr, "DELETE", "/tables/feeds/changes", "").0, 204);
+        assert_eq!(call(addr, "PUT", "/changes/feeds", "{}").0, 405);
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+//! In-memory secondary indexes kept in step with RECORDS_TABLE.
+//!
+//! Indexes are keyed by a field's raw stored form — type tag followed by the
+//! field bytes — so lookups never decode a record. Integers are always stored
+//! as I64 when they fit, which makes the raw form canonical for ints; `1` and
+//! `1.0` are distinct keys. Null and absent fields are not indexed.
+
+use smol_str::SmolStr;
+
+use super::types::{FastHashSet, FastMap, SpookyDbError};
+use crate::serialization::from_bytes;
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+use crate::spooky_value::SpookyValue;
+use crate::types::{FieldRef, TAG_NULL};
+use crate::value_ref::SpookyValueRef;
+
+/// Raw index key: `[type_tag, data @ ..]`.
+pub(crate) type IndexKey = Vec<u8>;
+
+/// Index key for `field` in `record`, or `None` if the field is absent or null.
+pub(crate) fn index_key(record: &impl SpookyReadable, field: &str) -> Option<IndexKey> {
+    let raw = record.get_raw(field)?;
+    if raw.type_tag == TAG_NULL {
+        return None;
+    }
+    let mut key = Vec::with_capacity(1 + raw.data.len());
+    key.push(raw.type_tag);
+    key.extend_from_slice(raw.data);
+    Some(key)
+}
+
+/// Decode an index key back into the value it was built from (for errors).
+pub(crate) fn key_value(key: &[u8]) -> SpookyValue {
+    let field = FieldRef {
+        name_hash: 0,
+        type_tag: key[0],
+        data: &key[1..],
+    };
+    SpookyValueRef::from_field(field)
+        .to_value()
+        .unwrap_or(SpookyValue::Null)
+}
+
+/// One-to-one map between the values of a field and the ids holding them.
+#[derive(Debug)]
+pub(crate) struct UniqueIndex {
+    pub(crate) field: SmolStr,
+    by_value: FastMap<IndexKey, SmolStr>,
+    by_id: FastMap<SmolStr, IndexKey>,
+}
+
+impl UniqueIndex {
+    pub(crate) fn new(field: SmolStr) -> Self {
+        Self {
+            field,
+            by_value: FastMap::default(),
+            by_id: FastMap::default(),
+        }
+    }
+
+    pub(crate) fn holder(&self, key: &[u8]) -> Option<&SmolStr> {
+        self.by_value.get(key)
+    }
+
+    /// Point `id` at `key`, releasing whatever value it held before.
+    /// `None` removes `id` from the index.
+    pub(crate) fn set(&mut self, id: &SmolStr, key: Option<IndexKey>) {
+        if let Some(old) = self.by_id.remove(id) {
+            self.by_value.remove(&old);
+        }
+        if let Some(key) = key {
+            self.by_value.insert(key.clone(), id.clone());
+            self.by_id.insert(id.clone(), key);
+        }
+    }
+
+    fn key_of(&self, id: &str) -> Option<&IndexKey> {
+        self.by_id.get(id)
+    }
+}
+
+/// Reverse index of a reference field: the ids whose string `field` names
+/// each id of the `target` table.
+#[derive(Debug)]
+pub(crate) struct JoinIndex {
+    pub(crate) field: SmolStr,
+    pub(crate) target: SmolStr,
+    by_target: FastMap<SmolStr, FastHashSet<SmolStr>>,
+    by_id: FastMap<SmolStr, SmolStr>,
+}
+
+impl JoinIndex {
+    pub(crate) fn new(field: SmolStr, target: SmolStr) -> Self {
+        Self {
+            field,
+            target,
+            by_target: FastMap::default(),
+            by_id: FastMap::default(),
+        }
+    }
+
+    /// Ids pointing at `target_id`, in no particular order.
+    pub(crate) fn referrers(&self, target_id: &str) -> impl Iterator<Item = &SmolStr> {
+        self.by_target.get(target_id).into_iter().flatten()
+    }
+
+    /// Point `id` at `target_id`; `None` removes `id` from the index.
+    pub(crate) fn set(&mut self, id: &SmolStr, target_id: Option<SmolStr>) {
+        if let Some(old) = self.by_id.remove(id)
+            && let Some(ids) = self.by_target.get_mut(&old)
+        {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.by_target.remove(&old);
+            }
+        }
+        if let Some(target_id) = target_id {
+            self.by_target.entry(target_id.clone()).or_default().insert(id.clone());
+            self.by_id.insert(id.clone(), target_id);
+        }
+    }
+
+    /// Forget every id, keeping the declaration.
+    pub(crate) fn clear(&mut self) {
+        self.by_target.clear();
+        self.by_id.clear();
+    }
+}
+
+/// Join index change staged from a write, applied after commit.
+pub(crate) struct JoinUpdate {
+    pub(crate) table: SmolStr,
+    pub(crate) slot: usize,
+    pub(crate) id: SmolStr,
+    pub(crate) target_id: Option<SmolStr>,
+}
+
+/// Index change produced by a successful check, applied after commit.
+#[derive(Clone)]
+pub(crate) struct IndexUpdate {
+    pub(crate) table: SmolStr,
+    pub(crate) slot: usize,
+    pub(crate) id: SmolStr,
+    pub(crate) key: Option<IndexKey>,
+}
+
+/// Validates a sequence of writes against the committed indexes plus the
+/// writes already seen, so that one batch can move a value from one id to
+/// another but cannot hand it to two ids at once.
+#[derive(Clone)]
+pub(crate) struct UniqueCheck<'a> {
+    indexes: &'a FastMap<SmolStr, Vec<UniqueIndex>>,
+    values: FastMap<(SmolStr, usize, IndexKey), Option<SmolStr>>,
+    ids: FastMap<(SmolStr, usize, SmolStr), Option<IndexKey>>,
+    updates: Vec<IndexUpdate>,
+}
+
+impl<'a> UniqueCheck<'a> {
+    pub(crate) fn new(indexes: &'a FastMap<SmolStr, Vec<UniqueIndex>>) -> Self {
+        Self {
+            indexes,
+            values: FastMap::default(),
+            ids: FastMap::default(),
+            updates: Vec::new(),
+        }
+    }
+
+    /// Stage one write. `data: None` on a non-delete leaves the record bytes —
+    /// and so the index — untouched.
+    pub(crate) fn write(
+        &mut self,
+        table: &str,
+        id: &str,
+        delete: bool,
+        data: Option<&[u8]>,
+    ) -> Result<(), SpookyDbError> {
+        let Some(indexes) = self.indexes.get(table) else {
+            return Ok(());
+        };
+        let record = match (delete, data) {
+            (true, _) => None,
+            (false, Some(bytes)) => Some(from_bytes(bytes)?),
+            (false, None) => return Ok(()),
+        };
+        let table = SmolStr::new(table);
+        let id = SmolStr::new(id);
+        for (slot, index) in indexes.iter().enumerate() {
+            let key = record
+                .as_ref()
+                .and_then(|&(buf, count)| index_key(&SpookyRecord::new(buf, count), &index.field));
+
+            if let Some(key) = &key {
+                let holder = match self.values.get(&(table.clone(), slot, key.clone())) {
+                    Some(staged) => staged.as_ref(),
+                    None => index.holder(key),
+                };
+                if let Some(existing) = holder.filter(|h| **h != id) {
+                    return Err(SpookyDbError::UniqueViolation {
+                        field: index.field.clone(),
+                        value: key_value(key),
+                        existing_id: existing.clone(),
+                    });
+                }
+            }
+
+            let id_slot = (table.clone(), slot, id.clone());
+            let old = match self.ids.get(&id_slot) {
+                Some(staged) => staged.clone(),
+                None => index.key_of(&id).cloned(),
+            };
+            if let Some(old) = old {
+                self.values.insert((table.clone(), slot, old), None);
+            }
+            if let Some(key) = &key {
+                self.values
+                    .insert((table.clone(), slot, key.clone()), Some(id.clone()));
+            }
+            self.ids.insert(id_slot, key.clone());
+            self.updates.push(IndexUpdate {
+                table: table.clone(),
+                slot,
+                id: id.clone(),
+                key,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Vec<IndexUpdate> {
+        self.updates
+    }
+}
@@ -0,0 +1,81 @@
+//! SurrealDB LIVE query notifications as `DbMutation`s.
+//!
+//! A live query delivers one CBOR notification per change:
+//!
+//! ```text
+//! {"id": live-query uuid, "action": "CREATE" | "UPDATE" | "DELETE" | "KILLED",
+//!  "record": Thing, "result": record}
+//! ```
+//!
+//! optionally inside the RPC envelope `{"result": notification}`. The record
+//! id is the `Thing` under `"record"` (SurrealDB 2), else the record's own
+//! `"id"` (SurrealDB 1), parsed as a [`RecordId`]. Tags are mapped as
+//! everywhere else (see `crate::surreal`), so the stored record matches what
+//! `apply_mutation_cbor` would store for the same map.
+//!
+//! ```rust,ignore
+//! while let Some(bytes) = feed.next() {
+//!     if let Some(mutation) = ingest::live_mutation(&bytes)? {
+//!         db.apply_batch(vec![mutation])?;
+//!     }
+//! }
+//! ```
+
+use smol_str::SmolStr;
+
+use super::record_id::RecordId;
+use super::types::{DbMutation, Operation, SpookyDbError};
+use crate::spooky_value::SpookyValue;
+
+/// Record field read as the mutation's version, when it holds a
+/// non-negative integer.
+pub const VERSION_FIELD: &str = "spooky_rv";
+
+fn invalid(msg: impl std::fmt::Display) -> SpookyDbError {
+    SpookyDbError::Serialization(format!("live notification: {msg}"))
+}
+
+/// The mutation for one CBOR-encoded notification, or `None` for a
+/// `KILLED` notification (the live query ended; nothing changed).
+///
+/// `Create` and `Update` carry the notification's record as record bytes
+/// and its `VERSION_FIELD` as the version; `Delete` carries neither.
+pub fn live_mutation(notification: &[u8]) -> Result<Option<DbMutation>, SpookyDbError> {
+    let value = crate::cbor::decode_slice(notification)?;
+    let value = match value.get("result") {
+        Some(inner) if value.get("action").is_none() => inner.clone(),
+        _ => value,
+    };
+    let op = match value.get("action").and_then(SpookyValue::as_str) {
+        Some("CREATE") => Operation::Create,
+        Some("UPDATE") => Operation::Update,
+        Some("DELETE") => Operation::Delete,
+        Some("KILLED") => return Ok(None),
+        Some(other) => return Err(invalid(format!("unknown action {other:?}"))),
+        None => return Err(invalid("missing action")),
+    };
+    let record = value.get("result").filter(|r| r.is_object());
+    let thing = value
+        .get("record")
+        .or_else(|| record.and_then(|r| r.get("id")))
+        .and_then(SpookyValue::as_str)
+        .ok_or_else(|| invalid("missing record id"))?;
+    let thing = RecordId::parse(thing)?;
+
+    let (data, version) = match op {
+        Operation::Delete => (None, None),
+        _ => {
+            let record = record.ok_or_else(|| invalid("missing result"))?;
+            let version = record.get(VERSION_FIELD).and_then(SpookyValue::as_u64);
+            (Some(crate::serialization::from_spooky(record)?.0), version)
+        }
+    };
+    Ok(Some(DbMutation {
+        table: SmolStr::new(thing.table()),
+        id: SmolStr::new(thing.id()),
+        op,
+        data,
+        version,
+        expires_at: None,
+    }))
+}
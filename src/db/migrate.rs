@@ -0,0 +1,93 @@
+//! Record rewriting for `SpookyDb::migrate`.
+//!
+//! Every step checks the record before changing it and leaves it alone when
+//! the step already holds: a field to add is present, a field to rename or
+//! drop is gone, a field to convert has the target type. Re-running a
+//! migration over records it has partly rewritten is therefore a no-op for
+//! those records, which is what makes an interrupted `migrate` resumable.
+
+use smol_str::SmolStr;
+
+use super::types::{MigrationStep, ValueKind};
+use crate::coerce::{is_truthy, to_f64, to_i64};
+use crate::serialization::from_bytes;
+use crate::spooky_record::record_mut::SpookyRecordMut;
+use crate::spooky_record::SpookyReadable;
+use crate::spooky_value::{SpookyNumber, SpookyValue};
+
+/// Apply `steps` to one stored record. `Ok(None)` if nothing changed;
+/// `Err` carries the reason a step could not be applied.
+pub(super) fn migrate_record(
+    bytes: &[u8],
+    steps: &[MigrationStep],
+) -> Result<Option<Vec<u8>>, String> {
+    let (_, count) = from_bytes(bytes).map_err(|e| e.to_string())?;
+    let mut record = SpookyRecordMut::new(bytes.to_vec(), count);
+    let mut changed = false;
+    for step in steps {
+        changed |= apply_step(&mut record, step)?;
+    }
+    Ok(changed.then_some(record.data_buf))
+}
+
+/// Apply one step; `Ok(false)` if it already held.
+fn apply_step(record: &mut SpookyRecordMut, step: &MigrationStep) -> Result<bool, String> {
+    let fail = |e: crate::error::RecordError| e.to_string();
+    match step {
+        MigrationStep::AddField { name, default } => {
+            if record.has_field(name) {
+                return Ok(false);
+            }
+            record.add_field(name, default).map_err(fail)?;
+        }
+        MigrationStep::RenameField { from, to } => {
+            if !record.has_field(from) {
+                return Ok(false);
+            }
+            if record.has_field(to) {
+                return Err(format!("cannot rename {from} to {to}: {to} exists"));
+            }
+            record.rename_field(from, to).map_err(fail)?;
+        }
+        MigrationStep::DropField { name } => {
+            if !record.has_field(name) {
+                return Ok(false);
+            }
+            record.remove_field(name).map_err(fail)?;
+        }
+        MigrationStep::ChangeType { name, to } => {
+            let Some(value) = record.get_field::<SpookyValue>(name) else {
+                return Ok(false);
+            };
+            let Some(converted) = convert(&value, *to) else {
+                return Err(format!("cannot convert {name} = {value} to {to:?}"));
+            };
+            if converted == value {
+                return Ok(false);
+            }
+            record.set_field(name, &converted).map_err(fail)?;
+        }
+    }
+    Ok(true)
+}
+
+/// `value` as `kind` under the `coerce` rules; null stays null.
+fn convert(value: &SpookyValue, kind: ValueKind) -> Option<SpookyValue> {
+    if matches!(value, SpookyValue::Null) {
+        return Some(SpookyValue::Null);
+    }
+    let converted = match kind {
+        ValueKind::Int => SpookyValue::Number(SpookyNumber::I64(to_i64(value)?)),
+        ValueKind::Float => SpookyValue::Number(SpookyNumber::F64(to_f64(value)?)),
+        ValueKind::Bool => SpookyValue::Bool(is_truthy(value)),
+        ValueKind::Str => match value {
+            SpookyValue::Str(_) => value.clone(),
+            SpookyValue::Bool(b) => SpookyValue::Str(SmolStr::new(b.to_string())),
+            SpookyValue::Number(SpookyNumber::I64(n)) => SpookyValue::Str(SmolStr::new(n.to_string())),
+            SpookyValue::Number(SpookyNumber::U64(n)) => SpookyValue::Str(SmolStr::new(n.to_string())),
+            SpookyValue::Number(SpookyNumber::F64(n)) => SpookyValue::Str(SmolStr::new(n.to_string())),
+            _ => return None,
+        },
+    };
+    Some(converted)
+}
@@ -1,9 +1,58 @@
+pub mod aggregate;
+#[cfg(feature = "async")]
+pub mod async_db;
+mod backup;
+mod blobs;
+mod cache;
+pub mod circuit;
+mod compress;
 #[allow(clippy::module_inception)]
 pub mod db;
+#[cfg(feature = "http")]
+pub mod http;
+mod index;
+pub mod ingest;
+mod migrate;
+mod namespace;
+mod oplog;
+mod reader;
+mod record_id;
+pub mod shared;
+mod sharded;
+mod tiered;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod topk;
+mod typed;
 pub mod types;
+mod view;
+pub mod window;
+pub mod zset;
+mod zsets;
 
-pub use db::{DbBackend, SpookyDb};
+pub use aggregate::{Aggregate, Aggregator, AvgBy, CountBy, RecordOperator, SumBy};
+pub use circuit::{Circuit, Stream};
+pub use compress::Compressor;
+#[cfg(feature = "zstd")]
+pub use compress::ZstdCompressor;
+#[cfg(feature = "async")]
+pub use async_db::{AsyncSpookyDb, Commit};
+pub use db::{DbBackend, SpookyDb, StagedView};
+#[cfg(feature = "http")]
+pub use http::HttpServer;
+pub use namespace::Namespace;
+pub use reader::SpookyDbReader;
+pub use record_id::RecordId;
+pub use shared::SharedSpookyDb;
+pub use sharded::ShardedDb;
+pub use tiered::TieredDb;
+pub use topk::{RankChange, TopK};
+pub use typed::Table;
+pub use view::View;
+pub use window::{ClosedWindow, TimeWindow};
 pub use types::{
-    BatchMutationResult, BulkRecord, DbMutation, FastHashSet, FastMap, Operation, SpookyDbConfig,
-    SpookyDbError, TableName, ZSet,
+    BackupProgress, BatchMutationResult, BulkRecord, CachePolicy, ChangeEvent, CoalesceConfig, CompactionReport, DbMutation,
+    DbStats, DeltaBatch, Durability, DurabilityCallback, FastHashSet, FastMap, IntegrityReport, Migration, MigrationProgress, MigrationStep, Operation,
+    OplogEntry, OplogMode, RecordChange, RecordMeta, SavepointBatchResult, SortDirection, SpookyDbConfig, SpookyDbError, StatsHook, TableDeltas, TableName,
+    TableStats, TimedOp, TimingHook, ValueKind, ZSet, ZSetSnapshot,
 };
@@ -0,0 +1,195 @@
+//! Tenant-scoped handles over one `SpookyDb`.
+//!
+//! A [`Namespace`] prefixes every table it touches with `"name:"`, so
+//! tenant `"a"` writing to `users` lands in table `"a:users"`. Tables, ZSets,
+//! row-cache entries, versions and stats are all keyed by table name, which
+//! makes the prefix enough to keep tenants apart. Namespace names may not
+//! contain ':', so no tenant's table can alias another's.
+//!
+//! Results hand table names back without the prefix. The unscoped
+//! `SpookyDb` still sees every tenant's tables under their full names.
+
+use smol_str::SmolStr;
+
+use super::db::{DbBackend, SpookyDb};
+use super::types::{
+    BatchMutationResult, BulkRecord, DbMutation, FastMap, Operation, SpookyDbError, ZSet,
+};
+use crate::spooky_value::SpookyValue;
+
+/// A `SpookyDb` borrowed for one tenant. See the module docs.
+///
+/// Writes and point reads go through its [`DbBackend`] impl, so code
+/// written against the trait runs unchanged inside a namespace.
+pub struct Namespace<'a> {
+    db: &'a mut SpookyDb,
+    /// `"name:"`.
+    prefix: SmolStr,
+}
+
+impl<'a> Namespace<'a> {
+    pub(super) fn new(db: &'a mut SpookyDb, name: &str) -> Result<Self, SpookyDbError> {
+        if name.is_empty() || name.contains(':') {
+            return Err(SpookyDbError::InvalidKey(format!(
+                "namespace must be non-empty and not contain ':': received {name:?}"
+            )));
+        }
+        let prefix = SmolStr::new(format!("{name}:"));
+        Ok(Self { db, prefix })
+    }
+
+    /// The namespace name.
+    pub fn name(&self) -> &str {
+        &self.prefix[..self.prefix.len() - 1]
+    }
+
+    /// The full table name `table` is stored under.
+    pub fn qualify(&self, table: &str) -> SmolStr {
+        SmolStr::new(format!("{}{table}", self.prefix))
+    }
+
+    /// `qualify`, rejecting the empty name the prefix would otherwise hide.
+    fn scoped(&self, table: &str) -> Result<SmolStr, SpookyDbError> {
+        if table.is_empty() {
+            return Err(SpookyDbError::InvalidKey(
+                "table name must not be empty".into(),
+            ));
+        }
+        Ok(self.qualify(table))
+    }
+
+    /// `table` without the prefix, or `None` if it belongs elsewhere.
+    fn local<'t>(&self, table: &'t str) -> Option<&'t str> {
+        table.strip_prefix(self.prefix.as_str())
+    }
+
+    /// The underlying database, for calls the handle does not wrap. Table
+    /// names passed to it are not prefixed.
+    pub fn db(&mut self) -> &mut SpookyDb {
+        self.db
+    }
+
+    /// This namespace's tables, without the prefix.
+    pub fn table_names(&self) -> impl Iterator<Item = &str> {
+        self.db.table_names().filter_map(|t| self.local(t))
+    }
+
+    pub fn table_len(&self, table: &str) -> usize {
+        self.db.table_len(&self.qualify(table))
+    }
+
+    pub fn get_version(&self, table: &str, id: &str) -> Result<Option<u64>, SpookyDbError> {
+        self.db.get_version(&self.scoped(table)?, id)
+    }
+
+    pub fn ids_sorted(&self, table: &str) -> Result<Vec<SmolStr>, SpookyDbError> {
+        self.db.ids_sorted(&self.scoped(table)?)
+    }
+
+    pub fn scan_ids(&self, table: &str, prefix: &str) -> Result<Vec<SmolStr>, SpookyDbError> {
+        self.db.scan_ids(&self.scoped(table)?, prefix)
+    }
+
+    pub fn truncate_table(&mut self, table: &str) -> Result<usize, SpookyDbError> {
+        let table = self.scoped(table)?;
+        self.db.truncate_table(&table)
+    }
+
+    pub fn drop_table(&mut self, table: &str) -> Result<usize, SpookyDbError> {
+        let table = self.scoped(table)?;
+        self.db.drop_table(&table)
+    }
+
+    /// Drop every table of the namespace. Returns the number of records
+    /// removed.
+    pub fn drop_all(&mut self) -> Result<usize, SpookyDbError> {
+        let tables: Vec<SmolStr> = self.table_names().map(|t| self.qualify(t)).collect();
+        let mut removed = 0;
+        for table in tables {
+            removed += self.db.drop_table(&table)?;
+        }
+        Ok(removed)
+    }
+
+    /// Strip the prefix from every table name in `result`.
+    fn localize(&self, result: BatchMutationResult) -> BatchMutationResult {
+        fn strip<V>(ns: &Namespace<'_>, map: FastMap<SmolStr, V>) -> FastMap<SmolStr, V> {
+            map.into_iter()
+                .map(|(t, v)| (SmolStr::new(ns.local(&t).unwrap_or(&t)), v))
+                .collect()
+        }
+        BatchMutationResult {
+            tick: result.tick,
+            changed_tables: result
+                .changed_tables
+                .iter()
+                .map(|t| SmolStr::new(self.local(t).unwrap_or(t)))
+                .collect(),
+            membership_deltas: strip(self, result.membership_deltas),
+            content_updates: strip(self, result.content_updates),
+            versions: strip(self, result.versions),
+        }
+    }
+}
+
+impl DbBackend for Namespace<'_> {
+    fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
+        self.db.get_table_zset(&self.qualify(table))
+    }
+
+    fn get_record_bytes(&self, table: &str, id: &str) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        self.db.get_record_bytes(&self.scoped(table)?, id)
+    }
+
+    fn get_row_record_bytes<'r>(&'r self, table: &str, id: &str) -> Option<&'r [u8]> {
+        DbBackend::get_row_record_bytes(&*self.db, &self.qualify(table), id)
+    }
+
+    fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+        let table = self.scoped(table)?;
+        self.db.ensure_table(&table)
+    }
+
+    fn apply_mutation(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let table = self.scoped(table)?;
+        self.db.apply_mutation(&table, op, id, data, version)
+    }
+
+    fn apply_batch(
+        &mut self,
+        mut mutations: Vec<DbMutation>,
+    ) -> Result<BatchMutationResult, SpookyDbError> {
+        for mutation in &mut mutations {
+            mutation.table = self.scoped(&mutation.table)?;
+        }
+        let result = self.db.apply_batch(mutations)?;
+        Ok(self.localize(result))
+    }
+
+    fn bulk_load(&mut self, mut records: Vec<BulkRecord>) -> Result<(), SpookyDbError> {
+        for record in &mut records {
+            record.table = self.scoped(&record.table)?;
+        }
+        self.db.bulk_load(records)
+    }
+
+    fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
+        self.db.get_zset_weight(&self.qualify(table), id)
+    }
+
+    fn get_record_typed(
+        &self,
+        table: &str,
+        id: &str,
+        fields: &[&str],
+    ) -> Result<Option<SpookyValue>, SpookyDbError> {
+        self.db.get_record_typed(&self.scoped(table)?, id, fields)
+    }
+}
@@ -0,0 +1,145 @@
+//! Binary encoding of OPLOG_TABLE and DELTA_TABLE values.
+//!
+//! ```text
+//! [op u8][flags u8][version u64 LE]?[table_len u32 LE][table][id_len u32 LE][id][data ..]?
+//! ```
+//!
+//! `flags` bit 0: version present; bit 1: data present (runs to the end).
+//!
+//! A DELTA_TABLE value is one commit's membership deltas, table by table:
+//!
+//! ```text
+//! ([table_len u32 LE][table][count u32 LE]([id_len u32 LE][id][weight i64 LE]){count})*
+//! ```
+
+use smol_str::SmolStr;
+
+use super::types::{Operation, OplogEntry, SpookyDbError, TableDeltas, ZSet};
+
+const HAS_VERSION: u8 = 1;
+const HAS_DATA: u8 = 2;
+
+pub(crate) fn encode(
+    table: &str,
+    id: &str,
+    op: Operation,
+    version: Option<u64>,
+    data: Option<&[u8]>,
+) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(18 + table.len() + id.len() + data.map_or(0, <[u8]>::len));
+    buf.push(match op {
+        Operation::Create => 0,
+        Operation::Update => 1,
+        Operation::Delete => 2,
+    });
+    let mut flags = 0;
+    if version.is_some() {
+        flags |= HAS_VERSION;
+    }
+    if data.is_some() {
+        flags |= HAS_DATA;
+    }
+    buf.push(flags);
+    if let Some(v) = version {
+        buf.extend_from_slice(&v.to_le_bytes());
+    }
+    for s in [table, id] {
+        buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+    if let Some(d) = data {
+        buf.extend_from_slice(d);
+    }
+    buf
+}
+
+pub(crate) fn decode(seq: u64, bytes: &[u8]) -> Result<OplogEntry, SpookyDbError> {
+    let corrupt = || SpookyDbError::Serialization(format!("corrupt oplog entry {seq}"));
+    let mut rest = bytes;
+    let mut take = |n: usize| -> Result<&[u8], SpookyDbError> {
+        if rest.len() < n {
+            return Err(corrupt());
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Ok(head)
+    };
+
+    let op = match take(1)?[0] {
+        0 => Operation::Create,
+        1 => Operation::Update,
+        2 => Operation::Delete,
+        _ => return Err(corrupt()),
+    };
+    let flags = take(1)?[0];
+    let version = if flags & HAS_VERSION != 0 {
+        Some(u64::from_le_bytes(take(8)?.try_into().unwrap()))
+    } else {
+        None
+    };
+    let mut text = || -> Result<SmolStr, SpookyDbError> {
+        let len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        std::str::from_utf8(take(len)?)
+            .map(SmolStr::new)
+            .map_err(|_| corrupt())
+    };
+    let table = text()?;
+    let id = text()?;
+    let data = (flags & HAS_DATA != 0).then(|| rest.to_vec());
+    Ok(OplogEntry {
+        seq,
+        table,
+        id,
+        op,
+        version,
+        data,
+    })
+}
+
+pub(crate) fn encode_deltas(deltas: &TableDeltas) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let text = |buf: &mut Vec<u8>, s: &str| {
+        buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    };
+    for (table, zset) in deltas {
+        text(&mut buf, table);
+        buf.extend_from_slice(&(zset.len() as u32).to_le_bytes());
+        for (id, weight) in zset {
+            text(&mut buf, id);
+            buf.extend_from_slice(&weight.to_le_bytes());
+        }
+    }
+    buf
+}
+
+pub(crate) fn decode_deltas(
+    tick: u64,
+    bytes: &[u8],
+) -> Result<TableDeltas, SpookyDbError> {
+    let corrupt = || SpookyDbError::Serialization(format!("corrupt delta entry {tick}"));
+    fn take<'a>(rest: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+        let (head, tail) = rest.split_at_checked(n)?;
+        *rest = tail;
+        Some(head)
+    }
+    fn text(rest: &mut &[u8]) -> Option<SmolStr> {
+        let len = u32::from_le_bytes(take(rest, 4)?.try_into().ok()?) as usize;
+        std::str::from_utf8(take(rest, len)?).ok().map(SmolStr::new)
+    }
+    let mut rest = bytes;
+    let mut deltas = TableDeltas::default();
+    while !rest.is_empty() {
+        let table = text(&mut rest).ok_or_else(corrupt)?;
+        let count = take(&mut rest, 4).ok_or_else(corrupt)?;
+        let count = u32::from_le_bytes(count.try_into().unwrap());
+        let mut zset = ZSet::default();
+        for _ in 0..count {
+            let id = text(&mut rest).ok_or_else(corrupt)?;
+            let weight = take(&mut rest, 8).ok_or_else(corrupt)?;
+            zset.insert(id, i64::from_le_bytes(weight.try_into().unwrap()));
+        }
+        deltas.insert(table, zset);
+    }
+    Ok(deltas)
+}
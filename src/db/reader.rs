@@ -0,0 +1,163 @@
+//! Read-only point-in-time view of a [`SpookyDb`] for worker threads.
+//!
+//! A [`SpookyDbReader`] holds one redb read transaction and a copy of every
+//! ZSet taken at the same moment, so record bytes and membership always
+//! agree. It is `Clone + Send + Sync`: clones share the snapshot, and the
+//! owner keeps writing while workers read. Later commits are not visible;
+//! call `SpookyDb::reader` again for a fresh view.
+//!
+//! ```rust,ignore
+//! let reader = db.reader()?;
+//! let ids = db.ids_sorted("users")?;
+//! let names: Vec<_> = ids
+//!     .par_iter()
+//!     .map(|id| reader.with_row_record("users", id, |r| r.get_str("name").map(str::to_owned)))
+//!     .collect();
+//! db.apply_batch(mutations)?;
+//! ```
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use redb::ReadTransaction;
+use smol_str::SmolStr;
+
+use super::compress::{self, Compressor};
+use super::db::{RECORDS_TABLE, VERSION_TABLE, make_key, record_typed, validate_table_name};
+use super::types::{FastMap, SpookyDbError, ZSet};
+use crate::serialization::from_bytes;
+use crate::spooky_record::SpookyRecord;
+use crate::spooky_value::SpookyValue;
+
+/// See the module docs.
+#[derive(Clone)]
+pub struct SpookyDbReader {
+    snapshot: Arc<Snapshot>,
+}
+
+struct Snapshot {
+    txn: ReadTransaction,
+    zsets: FastMap<SmolStr, ZSet>,
+    /// `config.compressor`, for decoding what the reader fetches.
+    compressor: Option<Arc<dyn Compressor>>,
+    /// `SpookyDb::commit_tick` when the snapshot was taken.
+    tick: u64,
+}
+
+impl SpookyDbReader {
+    pub(super) fn new(
+        txn: ReadTransaction,
+        zsets: FastMap<SmolStr, ZSet>,
+        compressor: Option<Arc<dyn Compressor>>,
+        tick: u64,
+    ) -> Self {
+        let snapshot = Snapshot {
+            txn,
+            zsets,
+            compressor,
+            tick,
+        };
+        Self {
+            snapshot: Arc::new(snapshot),
+        }
+    }
+
+    /// Commit tick the snapshot observes: every batch up to and including
+    /// it is visible, none after.
+    pub fn commit_tick(&self) -> u64 {
+        self.snapshot.tick
+    }
+
+    /// Copy of a record's bytes as of the snapshot.
+    pub fn get_record_bytes(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        self.with_bytes(table, id, <[u8]>::to_vec)
+    }
+
+    /// Run `f` over a zero-copy view of a record; the bytes are borrowed
+    /// from the snapshot's page unless they are compressed.
+    pub fn with_row_record<R>(
+        &self,
+        table: &str,
+        id: &str,
+        f: impl FnOnce(&SpookyRecord<'_>) -> R,
+    ) -> Result<Option<R>, SpookyDbError> {
+        let viewed = self.with_bytes(table, id, |bytes| {
+            let (buf, count) = from_bytes(bytes)?;
+            Ok::<_, SpookyDbError>(f(&SpookyRecord::new(buf, count)))
+        })?;
+        viewed.transpose()
+    }
+
+    /// [`SpookyDb::get_record_typed`](super::SpookyDb::get_record_typed) as
+    /// of the snapshot.
+    pub fn get_record_typed(
+        &self,
+        table: &str,
+        id: &str,
+        fields: &[&str],
+    ) -> Result<Option<SpookyValue>, SpookyDbError> {
+        self.with_bytes(table, id, |bytes| record_typed(bytes, fields))?
+            .transpose()
+    }
+
+    /// VERSION_TABLE entry for a record present in the snapshot.
+    pub fn get_version(&self, table: &str, id: &str) -> Result<Option<u64>, SpookyDbError> {
+        validate_table_name(table)?;
+        if self.get_zset_weight(table, id) <= 0 {
+            return Ok(None);
+        }
+        let versions = self.snapshot.txn.open_table(VERSION_TABLE)?;
+        let key = make_key(table, id)?;
+        Ok(versions.get(key.as_str())?.map(|guard| guard.value()))
+    }
+
+    /// Weight for a single record. Returns 0 if absent.
+    pub fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
+        self.snapshot
+            .zsets
+            .get(table)
+            .and_then(|z| z.get(id).copied())
+            .unwrap_or(0)
+    }
+
+    /// A table's ZSet as of the snapshot (`None` if the table is unknown).
+    pub fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
+        self.snapshot.zsets.get(table)
+    }
+
+    /// Record count for a table.
+    pub fn table_len(&self, table: &str) -> usize {
+        self.snapshot.zsets.get(table).map_or(0, |z| z.len())
+    }
+
+    /// All table names in the snapshot, in no particular order.
+    pub fn table_names(&self) -> impl Iterator<Item = &SmolStr> {
+        self.snapshot.zsets.keys()
+    }
+
+    /// `f` over the decoded bytes of a present record.
+    fn with_bytes<R>(
+        &self,
+        table: &str,
+        id: &str,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Result<Option<R>, SpookyDbError> {
+        validate_table_name(table)?;
+        if self.get_zset_weight(table, id) <= 0 {
+            return Ok(None);
+        }
+        let records = self.snapshot.txn.open_table(RECORDS_TABLE)?;
+        let Some(guard) = records.get(make_key(table, id)?.as_str())? else {
+            return Ok(None);
+        };
+        Ok(Some(f(&self.decode(guard.value())?)))
+    }
+
+    fn decode<'a>(&self, stored: &'a [u8]) -> Result<Cow<'a, [u8]>, SpookyDbError> {
+        compress::decode(self.snapshot.compressor.as_ref(), stored)
+    }
+}
@@ -0,0 +1,183 @@
+//! SurrealDB record ids (`Thing`s) in their text form.
+//!
+//! `table:id`, where either part may be escaped as SurrealDB writes them:
+//! a table name that is not a plain identifier in backticks, an id that is
+//! not made of `[A-Za-z0-9_]` — or that is all digits, and so would read as
+//! a number — in `⟨…⟩`. Inside the delimiters the closing one is escaped
+//! with a backslash. Both delimiters are accepted around either part, as
+//! SurrealQL does.
+//!
+//! ```rust,ignore
+//! let rid = RecordId::parse("user:⟨6b1f-…⟩")?;
+//! assert_eq!((rid.table(), rid.id()), ("user", "6b1f-…"));
+//! db.get_record_bytes(rid.table(), rid.id())?;
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use smol_str::SmolStr;
+
+use super::db::{make_key, split_key, validate_table_name};
+use super::types::SpookyDbError;
+
+/// A record's table and id, unescaped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RecordId {
+    table: SmolStr,
+    id: SmolStr,
+}
+
+impl RecordId {
+    pub fn new(table: &str, id: &str) -> Self {
+        Self {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+        }
+    }
+
+    /// Parse `table:id`, undoing the escaping described in the module docs.
+    /// An unescaped table ends at the first `':'`; an unescaped id is the
+    /// rest of the text as is, so `user:[1, 2]` has the id `[1, 2]`.
+    pub fn parse(text: &str) -> Result<Self, SpookyDbError> {
+        let invalid = |why: &str| SpookyDbError::InvalidKey(format!("record id {text:?}: {why}"));
+        let (table, rest) = match unquote(text) {
+            Some(quoted) => quoted.ok_or_else(|| invalid("unterminated table name"))?,
+            None => {
+                let end = text.find(':').unwrap_or(text.len());
+                (text[..end].to_owned(), &text[end..])
+            }
+        };
+        let rest = rest.strip_prefix(':').ok_or_else(|| invalid("missing ':'"))?;
+        let id = match unquote(rest) {
+            Some(Some((id, ""))) => id,
+            Some(Some(_)) => return Err(invalid("text after the id")),
+            Some(None) => return Err(invalid("unterminated id")),
+            None => rest.to_owned(),
+        };
+        if id.is_empty() {
+            return Err(invalid("empty id"));
+        }
+        validate_table_name(&table)?;
+        Ok(Self::new(&table, &id))
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn into_parts(self) -> (SmolStr, SmolStr) {
+        (self.table, self.id)
+    }
+
+    /// The flat RECORDS_TABLE key, as `SpookyDb` builds it: `table:id` with
+    /// each ':' of the table name escaped. Not the SurrealDB text form; that
+    /// is `to_string`.
+    /// Fails with `SpookyDbError::InvalidKey` if the key is longer than
+    /// `MAX_KEY_LEN`, as every write of such a record would.
+    pub fn db_key(&self) -> Result<String, SpookyDbError> {
+        Ok(make_key(&self.table, &self.id)?.to_string())
+    }
+
+    /// The record id a `db_key` stands for.
+    pub fn from_db_key(key: &str) -> Option<Self> {
+        let (table, id) = split_key(key)?;
+        Some(Self::new(&table, id))
+    }
+}
+
+/// SurrealDB's text form, escaped as described in the module docs.
+impl fmt::Display for RecordId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plain = |s: &str| s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+        let starts_numeric = self.table.starts_with(|c: char| c.is_ascii_digit());
+        match plain(&self.table) && !starts_numeric {
+            true => f.write_str(&self.table)?,
+            false => write!(f, "`{}`", self.table.replace('`', "\\`"))?,
+        }
+        f.write_str(":")?;
+        match plain(&self.id) && !self.id.bytes().all(|b| b.is_ascii_digit()) {
+            true => f.write_str(&self.id),
+            false => write!(f, "⟨{}⟩", self.id.replace('⟩', "\\⟩")),
+        }
+    }
+}
+
+impl FromStr for RecordId {
+    type Err = SpookyDbError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+
+/// If `text` opens with a backtick or `⟨`: `Some` of the unescaped content
+/// and the text after the closing delimiter, or `Some(None)` if it is never
+/// closed. `None` if `text` is not delimited.
+fn unquote(text: &str) -> Option<Option<(String, &str)>> {
+    let open = text.chars().next()?;
+    let close = match open {
+        '`' => '`',
+        '⟨' => '⟩',
+        _ => return None,
+    };
+    let body = &text[open.len_utf8()..];
+    let mut out = String::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if body[i + 1..].starts_with(close) => {
+                out.push(close);
+                chars.next();
+            }
+            c if c == close => return Some(Some((out, &body[i + c.len_utf8()..]))),
+            c => out.push(c),
+        }
+    }
+    Some(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db::MAX_KEY_LEN;
+
+    #[test]
+    fn test_parse_escapes_and_round_trip() {
+        let rid = RecordId::parse("user:⟨6b1f-42⟩").unwrap();
+        assert_eq!((rid.table(), rid.id()), ("user", "6b1f-42"));
+        assert_eq!(rid.to_string(), "user:⟨6b1f-42⟩");
+
+        let plain: RecordId = "user:ann".parse().unwrap();
+        assert_eq!((plain.table(), plain.id()), ("user", "ann"));
+        assert_eq!(plain.to_string(), "user:ann");
+
+        // Escaped delimiters, a backticked table, and a numeric-looking id.
+        let odd = RecordId::new("my table", "a⟩b");
+        assert_eq!(odd.to_string(), "`my table`:⟨a\\⟩b⟩");
+        assert_eq!(RecordId::parse(&odd.to_string()).unwrap(), odd);
+        assert_eq!(RecordId::new("t", "42").to_string(), "t:⟨42⟩");
+        assert_eq!(RecordId::parse("t:`x:y`").unwrap().id(), "x:y");
+        // Unescaped ids keep their ':'s; arrays and objects are kept as text.
+        assert_eq!(RecordId::parse("t:a:b").unwrap().id(), "a:b");
+        assert_eq!(RecordId::parse("t:[1, 2]").unwrap().id(), "[1, 2]");
+
+        for bad in ["user", "user:", ":ann", "user:⟨ann", "user:⟨a⟩b", "`user:ann"] {
+            assert!(RecordId::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_db_key_escapes_table_colons() {
+        let rid = RecordId::parse("`ns:user`:ann").unwrap();
+        assert_eq!(rid.table(), "ns:user");
+        assert_eq!(rid.db_key().unwrap(), "ns\u{1f}user:ann");
+        assert_eq!(RecordId::from_db_key(&rid.db_key().unwrap()), Some(rid));
+        let long = RecordId::new("t", &"x".repeat(MAX_KEY_LEN));
+        assert!(matches!(long.db_key(), Err(SpookyDbError::InvalidKey(_))));
+    }
+}
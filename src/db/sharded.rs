@@ -0,0 +1,286 @@
+//! `DbBackend` that spreads tables over several redb files.
+//!
+//! A [`ShardedDb`] opens one `SpookyDb` per shard file and places each table
+//! on the shard picked by the xxh64 of its name, so a table's records, ZSet
+//! and per-table options all live in one file. Unrelated tables then take
+//! separate write locks and fsyncs, and each file grows, compacts and backs
+//! up on its own.
+//!
+//! The placement depends only on the table name and the shard count, which
+//! is fixed when the directory is created: reopening with another count
+//! fails rather than losing tables. A batch that spans shards commits each
+//! shard's part in its own transaction, in parallel — atomic per shard, not
+//! across them.
+
+use std::path::{Path, PathBuf};
+
+use smol_str::SmolStr;
+use xxhash_rust::xxh64::xxh64;
+
+use super::db::{DbBackend, SpookyDb, validate_key, validate_table_name};
+use super::types::{
+    BatchMutationResult, BulkRecord, DbMutation, FastMap, Operation, SpookyDbConfig,
+    SpookyDbError, ZSet,
+};
+use crate::spooky_value::SpookyValue;
+
+/// See the module docs.
+pub struct ShardedDb {
+    shards: Vec<SpookyDb>,
+}
+
+impl ShardedDb {
+    /// Open or create `count` shard files `shard-<n>.redb` in `dir` with the
+    /// default configuration. See `open_with_config`.
+    pub fn open(dir: impl AsRef<Path>, count: usize) -> Result<Self, SpookyDbError> {
+        Self::open_with_config(dir, count, |_| SpookyDbConfig::default())
+    }
+
+    /// Open or create `count` shard files in `dir`, the `n`th opened with
+    /// `config(n)`. Cache limits in the configuration apply per shard.
+    ///
+    /// Fails with `SpookyDbError::Io` if `count` is 0 or `dir` already holds
+    /// a different number of shards.
+    pub fn open_with_config(
+        dir: impl AsRef<Path>,
+        count: usize,
+        config: impl Fn(usize) -> SpookyDbConfig,
+    ) -> Result<Self, SpookyDbError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let existing = (0..).take_while(|&n| shard_path(dir, n).exists()).count();
+        if count == 0 || (existing != 0 && existing != count) {
+            let msg = format!(
+                "{} holds {existing} shards, cannot open it with {count}",
+                dir.display()
+            );
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg).into());
+        }
+        let shards = (0..count)
+            .map(|n| SpookyDb::new_with_config(shard_path(dir, n), config(n)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { shards })
+    }
+
+    /// Number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard that holds `table`.
+    pub fn shard_of(&self, table: &str) -> usize {
+        (xxh64(table.as_bytes(), 0) % self.shards.len() as u64) as usize
+    }
+
+    /// The shard that holds `table`, for everything beyond `DbBackend`:
+    /// scans, queries, versions.
+    pub fn shard(&self, table: &str) -> &SpookyDb {
+        &self.shards[self.shard_of(table)]
+    }
+
+    /// Mutable access to the shard that holds `table`, for per-table
+    /// options such as schemas, unique constraints or subscriptions.
+    pub fn shard_mut(&mut self, table: &str) -> &mut SpookyDb {
+        let n = self.shard_of(table);
+        &mut self.shards[n]
+    }
+
+    /// Every shard, in index order.
+    pub fn shards(&self) -> &[SpookyDb] {
+        &self.shards
+    }
+
+    /// Table names across all shards.
+    pub fn table_names(&self) -> impl Iterator<Item = &SmolStr> {
+        self.shards.iter().flat_map(SpookyDb::table_names)
+    }
+
+    /// Version of a record, from its table's shard.
+    pub fn get_version(&self, table: &str, id: &str) -> Result<Option<u64>, SpookyDbError> {
+        self.shard(table).get_version(table, id)
+    }
+
+    /// `SpookyDb::sync` on every shard.
+    pub fn sync(&mut self) -> Result<(), SpookyDbError> {
+        self.shards.iter_mut().try_for_each(SpookyDb::sync)
+    }
+
+    /// Split `items` by the shard of `table(item)`.
+    fn partition<T>(&self, items: Vec<T>, table: impl Fn(&T) -> &str) -> Vec<Vec<T>> {
+        let mut parts: Vec<Vec<T>> = (0..self.shards.len()).map(|_| Vec::new()).collect();
+        for item in items {
+            parts[self.shard_of(table(&item))].push(item);
+        }
+        parts
+    }
+
+    /// Run `write` on each shard with a non-empty part, in parallel when
+    /// more than one has one. Results are in shard order.
+    fn write_parts<T: Send, R: Send>(
+        &mut self,
+        parts: Vec<Vec<T>>,
+        write: impl Fn(&mut SpookyDb, Vec<T>) -> Result<R, SpookyDbError> + Sync,
+    ) -> Result<Vec<R>, SpookyDbError> {
+        let mut work: Vec<(&mut SpookyDb, Vec<T>)> = self
+            .shards
+            .iter_mut()
+            .zip(parts)
+            .filter(|(_, part)| !part.is_empty())
+            .collect();
+        if work.len() == 1 {
+            let (shard, part) = work.pop().expect("one part");
+            return Ok(vec![write(shard, part)?]);
+        }
+        let write = &write;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = work
+                .into_iter()
+                .map(|(shard, part)| scope.spawn(move || write(shard, part)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .collect()
+        })
+    }
+}
+
+/// File of shard `n` in `dir`.
+fn shard_path(dir: &Path, n: usize) -> PathBuf {
+    dir.join(format!("shard-{n}.redb"))
+}
+
+impl DbBackend for ShardedDb {
+    fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
+        self.shard(table).get_table_zset(table)
+    }
+
+    fn get_record_bytes(&self, table: &str, id: &str) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        self.shard(table).get_record_bytes(table, id)
+    }
+
+    fn get_row_record_bytes<'a>(&'a self, table: &str, id: &str) -> Option<&'a [u8]> {
+        DbBackend::get_row_record_bytes(self.shard(table), table, id)
+    }
+
+    fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+        self.shard_mut(table).ensure_table(table)
+    }
+
+    fn apply_mutation(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        self.shard_mut(table).apply_mutation(table, op, id, data, version)
+    }
+
+    /// One transaction per shard touched, committed in parallel. If one
+    /// fails, the other shards' parts may already have committed.
+    fn apply_batch(
+        &mut self,
+        mutations: Vec<DbMutation>,
+    ) -> Result<BatchMutationResult, SpookyDbError> {
+        for mutation in &mutations {
+            validate_table_name(&mutation.table)?;
+            validate_key(&mutation.table, &mutation.id)?;
+        }
+        let parts = self.partition(mutations, |m| &m.table);
+        let results = self.write_parts(parts, SpookyDb::apply_batch)?;
+        let mut merged = BatchMutationResult {
+            tick: 0,
+            membership_deltas: FastMap::default(),
+            content_updates: FastMap::default(),
+            changed_tables: Vec::new(),
+            versions: FastMap::default(),
+        };
+        // Each table lives on one shard, so the per-table maps are disjoint.
+        // Shards count ticks separately; the merged result keeps the largest.
+        for result in results {
+            merged.tick = merged.tick.max(result.tick);
+            merged.membership_deltas.extend(result.membership_deltas);
+            merged.content_updates.extend(result.content_updates);
+            merged.changed_tables.extend(result.changed_tables);
+            merged.versions.extend(result.versions);
+        }
+        Ok(merged)
+    }
+
+    /// One transaction per shard touched, committed in parallel.
+    fn bulk_load(&mut self, records: Vec<BulkRecord>) -> Result<(), SpookyDbError> {
+        for record in &records {
+            validate_table_name(&record.table)?;
+            validate_key(&record.table, &record.id)?;
+        }
+        let parts = self.partition(records, |r| &r.table);
+        self.write_parts(parts, SpookyDb::bulk_load).map(drop)
+    }
+
+    fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
+        self.shard(table).get_zset_weight(table, id)
+    }
+
+    fn get_record_typed(
+        &self,
+        table: &str,
+        id: &str,
+        fields: &[&str],
+    ) -> Result<Option<SpookyValue>, SpookyDbError> {
+        self.shard(table).get_record_typed(table, id, fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::record;
+    use crate::db::types::FastHashSet;
+
+    fn create(table: &str, id: &str, bytes: &[u8]) -> DbMutation {
+        DbMutation {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+            op: Operation::Create,
+            data: Some(bytes.to_vec()),
+            version: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_sharded_routes_tables_and_reopens() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let bytes = record(r#"{"name":"alice"}"#);
+        let tables = ["users", "posts", "orders", "events", "tags", "likes"];
+        {
+            let mut db = ShardedDb::open(dir.path(), 3)?;
+            let batch = tables.iter().map(|t| create(t, "1", &bytes)).collect();
+            let result = db.apply_batch(batch)?;
+            assert_eq!(result.changed_tables.len(), tables.len());
+            assert_eq!(result.membership_deltas["posts"]["1"], 1);
+            db.apply_mutation("users", Operation::Create, "2", Some(&bytes), Some(4))?;
+
+            let used: FastHashSet<usize> = tables.iter().map(|t| db.shard_of(t)).collect();
+            assert!(used.len() > 1, "tables spread over several shards");
+            for table in tables {
+                let shard = db.shard_of(table);
+                for (n, s) in db.shards().iter().enumerate() {
+                    assert_eq!(s.table_len(table) > 0, n == shard);
+                }
+            }
+        }
+
+        let db = ShardedDb::open(dir.path(), 3)?;
+        assert_eq!(db.table_names().count(), tables.len());
+        assert_eq!(db.get_zset_weight("users", "2"), 1);
+        assert_eq!(db.get_version("users", "2")?, Some(4));
+        assert_eq!(db.get_record_bytes("events", "1")?, Some(bytes));
+        drop(db);
+        assert!(ShardedDb::open(dir.path(), 2).is_err());
+        assert!(ShardedDb::open(dir.path().join("empty"), 0).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,1071 @@
+//! Single-writer / multi-reader wrapper around [`SpookyDb`].
+//!
+//! [`SharedSpookyDb`] is `Clone + Send + Sync`. Writes are serialized through
+//! one `Mutex<SpookyDb>` and do their redb work exactly as the owned API does.
+//! Reads never take that mutex: they see a published copy of the ZSets behind
+//! an `RwLock`, a sharded row cache, and — on a cache miss — their own redb
+//! read transaction. Readers therefore keep running while the writer
+//! validates, writes and fsyncs a batch; they are only excluded for the short
+//! in-memory publish after each commit, which makes a whole batch visible at
+//! once.
+//!
+//! `apply_mutation` and `apply_batch` commit in groups: a caller queues its
+//! mutations, then waits for the writer lock. Whoever gets it first commits
+//! everything queued so far in one transaction, so writers that arrive during
+//! a commit share the next one and its fsync. Each caller still gets its own
+//! result.
+//!
+//! Under the `async` feature, `AsyncSpookyDb` puts a background writer thread
+//! in front of this type.
+//!
+//! ```rust,ignore
+//! let db = SharedSpookyDb::new("data.redb")?;
+//! let reader = db.clone();
+//! let worker = std::thread::spawn(move || reader.get_zset_weight("users", "alice"));
+//! db.apply_batch(mutations)?;
+//! ```
+
+use std::borrow::Cow;
+use std::hash::{BuildHasher, BuildHasherDefault};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use redb::{Database as RedbDatabase, ReadableDatabase};
+use rustc_hash::FxHasher;
+use smol_str::SmolStr;
+
+use super::cache::{RowCache, RowKey};
+use super::compress::{self, Compressor};
+use super::db::{
+    RECORDS_TABLE, SpookyDb, VERSION_TABLE, copy_snapshot, key_range, make_key, read_record_meta,
+    resolve_blob, validate_table_name,
+};
+use super::types::{
+    BackupProgress, BatchMutationResult, BulkRecord, CachePolicy, DbMutation, DbStats, Durability,
+    FastMap, Operation, RecordMeta, SpookyDbConfig, SpookyDbError, ZSet,
+};
+use crate::serialization::{from_bytes, from_cbor_slice, from_spooky};
+use crate::spooky_record::SpookyRecord;
+use crate::spooky_value::SpookyValue;
+
+/// Number of independently locked row-cache shards.
+const CACHE_SHARDS: usize = 16;
+
+type CacheShard = Mutex<RowCache<Arc<[u8]>>>;
+
+/// Thread-safe handle to a [`SpookyDb`]: one writer at a time, any number of
+/// concurrent readers. Cloning is cheap and yields another handle to the same
+/// database.
+///
+/// The wrapped `SpookyDb` keeps only a minimal row cache of its own;
+/// `config.cache_capacity` sizes the shared cache that readers use.
+#[derive(Clone)]
+pub struct SharedSpookyDb {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// Owns all write paths and per-table options. Locked for every write.
+    writer: Mutex<SpookyDb>,
+    /// Same database as `writer`'s; readers open their own read transactions.
+    redb: Arc<RedbDatabase>,
+    /// `config.compressor`, for decoding what readers fetch.
+    compressor: Option<Arc<dyn Compressor>>,
+    /// ZSets as of the last publish. Readers hold the read lock for the whole
+    /// lookup (including a redb fallback), so a publish never interleaves
+    /// with one.
+    zsets: RwLock<FastMap<SmolStr, ZSet>>,
+    cache: ShardedCache,
+    /// Writes waiting for the next group commit, in arrival order.
+    queue: Mutex<Vec<QueuedWrite>>,
+}
+
+/// One `group_commit` caller's mutations and where its result goes.
+struct QueuedWrite {
+    mutations: Vec<DbMutation>,
+    slot: Arc<Mutex<Option<Result<BatchMutationResult, SpookyDbError>>>>,
+}
+
+/// What a committed write does to one published row.
+enum RowChange {
+    /// Row present; new bytes when the write carried them.
+    Put(Option<Vec<u8>>),
+    Remove,
+}
+
+impl RowChange {
+    fn of(op: Operation, data: Option<Vec<u8>>) -> Self {
+        match op {
+            Operation::Delete => RowChange::Remove,
+            Operation::Create | Operation::Update => RowChange::Put(data),
+        }
+    }
+}
+
+// ─── Construction ─────────────────────────────────────────────────────────────
+
+impl SharedSpookyDb {
+    /// Open or create the database at `path` with the default configuration.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, SpookyDbError> {
+        Self::new_with_config(path, SpookyDbConfig::default())
+    }
+
+    /// Open or create the database at `path`. `config.cache_capacity` and
+    /// `config.cache_max_bytes` bound the shared row cache (each shard gets
+    /// an equal share of both); everything else configures the writer, except
+    /// `coalesce`, which is ignored (use `AsyncSpookyDb` for group commit).
+    ///
+    /// Loads every table's ZSet up front (`SpookyDb::load_tables`).
+    pub fn new_with_config(
+        path: impl AsRef<Path>,
+        config: SpookyDbConfig,
+    ) -> Result<Self, SpookyDbError> {
+        let (capacity, max_bytes) = (config.cache_capacity, config.cache_max_bytes);
+        let compressor = config.compressor.clone();
+        // Coalesced writes would be published before they commit.
+        let writer_config = SpookyDbConfig {
+            cache_capacity: NonZeroUsize::MIN,
+            cache_max_bytes: None,
+            coalesce: None,
+            ..config
+        };
+        let writer = SpookyDb::new_with_config(path, writer_config)?;
+        // Readers see a full copy of the ZSets, so nothing stays lazy here.
+        writer.load_tables()?;
+        let shared = Shared {
+            redb: Arc::clone(writer.redb()),
+            compressor,
+            zsets: RwLock::new(zset_copy(&writer)),
+            writer: Mutex::new(writer),
+            cache: ShardedCache::new(capacity, max_bytes),
+            queue: Mutex::default(),
+        };
+        Ok(Self {
+            shared: Arc::new(shared),
+        })
+    }
+}
+
+// ─── Write Operations ─────────────────────────────────────────────────────────
+
+impl SharedSpookyDb {
+    /// [`SpookyDb::apply_mutation`], published to readers after commit.
+    /// Group-committed with concurrent callers (see the module docs).
+    pub fn apply_mutation(
+        &self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let mutation = DbMutation {
+            table: SmolStr::new(table),
+            id: SmolStr::new(id),
+            op,
+            data: data.map(<[u8]>::to_vec),
+            version,
+            expires_at: None,
+        };
+        self.group_commit(vec![mutation])?;
+        Ok((SmolStr::new(id), op.weight()))
+    }
+
+    /// [`SpookyDb::apply_mutation_versioned`], published to readers after commit.
+    pub fn apply_mutation_versioned(
+        &self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64, Option<u64>), SpookyDbError> {
+        let mut writer = self.writer();
+        let result = writer.apply_mutation_versioned(table, op, id, data, version)?;
+        self.publish(&writer, vec![single_change(table, id, op, data)]);
+        Ok(result)
+    }
+
+    /// [`SpookyDb::apply_mutation_cas`], published to readers after commit.
+    pub fn apply_mutation_cas(
+        &self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        expected_version: Option<u64>,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let mut writer = self.writer();
+        let result = writer.apply_mutation_cas(table, op, id, expected_version, data, version)?;
+        self.publish(&writer, vec![single_change(table, id, op, data)]);
+        Ok(result)
+    }
+
+    /// [`SpookyDb::apply_mutation_cbor`]. Serializes before taking the
+    /// writer lock, into a fresh buffer.
+    pub fn apply_mutation_cbor(
+        &self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        cbor: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let data = cbor.map(from_cbor_slice).transpose()?;
+        let data = data.as_ref().map(|(bytes, _)| bytes.as_slice());
+        self.apply_mutation(table, op, id, data, version)
+    }
+
+    /// [`SpookyDb::apply_mutation_value`]. Serializes before taking the
+    /// writer lock, into a fresh buffer.
+    pub fn apply_mutation_value(
+        &self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        value: Option<&SpookyValue>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        let data = value.map(from_spooky).transpose()?;
+        let data = data.as_ref().map(|(bytes, _)| bytes.as_slice());
+        self.apply_mutation(table, op, id, data, version)
+    }
+
+    /// [`SpookyDb::apply_batch`]. Readers see either none or all of the batch.
+    /// Group-committed with concurrent callers (see the module docs); a
+    /// batch that fails validation fails alone.
+    pub fn apply_batch(
+        &self,
+        mutations: Vec<DbMutation>,
+    ) -> Result<BatchMutationResult, SpookyDbError> {
+        self.group_commit(mutations)
+    }
+
+    /// [`SpookyDb::apply_batches`]. Only the accepted batches are published,
+    /// all at once.
+    pub fn apply_batches(
+        &self,
+        batches: Vec<Vec<DbMutation>>,
+    ) -> Result<Vec<Result<BatchMutationResult, SpookyDbError>>, SpookyDbError> {
+        let mut writer = self.writer();
+        self.commit_batches(&mut writer, batches)
+    }
+
+    /// `apply_batches` under a writer lock the caller holds.
+    fn commit_batches(
+        &self,
+        writer: &mut SpookyDb,
+        batches: Vec<Vec<DbMutation>>,
+    ) -> Result<Vec<Result<BatchMutationResult, SpookyDbError>>, SpookyDbError> {
+        let changes: Vec<_> = batches.iter().map(|b| batch_changes(b)).collect();
+        let results = writer.apply_batches(batches)?;
+        let accepted = changes
+            .into_iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .flat_map(|(changes, _)| changes)
+            .collect();
+        self.publish(writer, accepted);
+        Ok(results)
+    }
+
+    /// Queue `mutations`, then commit every queued write as one group unless
+    /// an earlier lock holder already committed ours.
+    ///
+    /// The lock holder fills every slot of its group before releasing the
+    /// writer, so a caller that finds its slot empty once it holds the lock
+    /// still has its write in the queue. On a storage error the first
+    /// caller in the group gets the error and the others
+    /// `SpookyDbError::Writer` with its text.
+    fn group_commit(
+        &self,
+        mutations: Vec<DbMutation>,
+    ) -> Result<BatchMutationResult, SpookyDbError> {
+        let slot = Arc::new(Mutex::new(None));
+        lock(&self.shared.queue).push(QueuedWrite {
+            mutations,
+            slot: Arc::clone(&slot),
+        });
+        let mut writer = self.writer();
+        if let Some(result) = lock(&slot).take() {
+            return result;
+        }
+        let group = std::mem::take(&mut *lock(&self.shared.queue));
+        let (batches, slots): (Vec<_>, Vec<_>) =
+            group.into_iter().map(|q| (q.mutations, q.slot)).unzip();
+        match self.commit_batches(&mut writer, batches) {
+            Ok(results) => {
+                for (slot, result) in slots.iter().zip(results) {
+                    *lock(slot) = Some(result);
+                }
+            }
+            Err(e) => {
+                let message = format!("group commit failed: {e}");
+                let mut first = Some(e);
+                for slot in &slots {
+                    let error = first
+                        .take()
+                        .unwrap_or_else(|| SpookyDbError::Writer(message.clone()));
+                    *lock(slot) = Some(Err(error));
+                }
+            }
+        }
+        drop(writer);
+        // Empty only if a previous holder panicked after taking our write.
+        lock(&slot).take().unwrap_or_else(|| {
+            Err(SpookyDbError::Writer("group commit abandoned".to_owned()))
+        })
+    }
+
+    /// [`SpookyDb::bulk_load`]. Readers see either none or all of the records.
+    pub fn bulk_load(&self, records: Vec<BulkRecord>) -> Result<(), SpookyDbError> {
+        let changes = records
+            .iter()
+            .map(|r| {
+                let change = RowChange::Put(Some(r.data.clone()));
+                (r.table.clone(), r.id.clone(), change)
+            })
+            .collect();
+        let mut writer = self.writer();
+        writer.bulk_load(records)?;
+        self.publish(&writer, changes);
+        Ok(())
+    }
+
+    /// [`SpookyDb::sweep_expired`], published to readers after commit.
+    pub fn sweep_expired(&self, now: u64) -> Result<BatchMutationResult, SpookyDbError> {
+        let mut writer = self.writer();
+        let result = writer.sweep_expired(now)?;
+        let changes = result
+            .membership_deltas
+            .iter()
+            .flat_map(|(table, delta)| {
+                delta
+                    .keys()
+                    .map(|id| (table.clone(), id.clone(), RowChange::Remove))
+            })
+            .collect();
+        self.publish(&writer, changes);
+        Ok(result)
+    }
+
+    /// [`SpookyDb::truncate_table`], published to readers after commit.
+    pub fn truncate_table(&self, table: &str) -> Result<usize, SpookyDbError> {
+        self.remove_table(table, false)
+    }
+
+    /// [`SpookyDb::drop_table`], published to readers after commit.
+    pub fn drop_table(&self, table: &str) -> Result<usize, SpookyDbError> {
+        self.remove_table(table, true)
+    }
+
+    /// [`SpookyDb::rename_table`], published to readers after commit.
+    pub fn rename_table(&self, old: &str, new: &str) -> Result<usize, SpookyDbError> {
+        let mut writer = self.writer();
+        let moved = writer.rename_table(old, new)?;
+        let mut zsets = write(&self.shared.zsets);
+        if old != new
+            && let Some(zset) = zsets.remove(old)
+        {
+            zsets.insert(SmolStr::new(new), zset);
+        }
+        self.shared.cache.rename(old, new);
+        Ok(moved)
+    }
+
+    fn remove_table(&self, table: &str, drop: bool) -> Result<usize, SpookyDbError> {
+        let mut writer = self.writer();
+        let removed = if drop {
+            writer.drop_table(table)?
+        } else {
+            writer.truncate_table(table)?
+        };
+        let mut zsets = write(&self.shared.zsets);
+        let old = if drop {
+            zsets.remove(table).unwrap_or_default()
+        } else {
+            zsets.get_mut(table).map(std::mem::take).unwrap_or_default()
+        };
+        let table = SmolStr::new(table);
+        for id in old.into_keys() {
+            let shard = self.shared.cache.shard(&table, &id);
+            lock(shard).pop(&(table.clone(), id));
+        }
+        if drop {
+            self.shared.cache.set_policy(&table, CachePolicy::Shared);
+        }
+        Ok(removed)
+    }
+
+    /// Run `f` with exclusive access to the wrapped `SpookyDb` — for schemas,
+    /// unique constraints, subscriptions and the other per-table options.
+    ///
+    /// Because `f` may change records in ways this wrapper cannot see, the
+    /// ZSets are re-published in full and the shared cache is cleared
+    /// afterwards: O(records). Prefer the dedicated write methods above for
+    /// record changes.
+    pub fn with_writer<R>(&self, f: impl FnOnce(&mut SpookyDb) -> R) -> R {
+        let mut writer = self.writer();
+        let result = f(&mut writer);
+        let mut zsets = write(&self.shared.zsets);
+        *zsets = zset_copy(&writer);
+        self.shared.cache.clear();
+        result
+    }
+
+    /// [`SpookyDb::set_durability`].
+    pub fn set_durability(&self, durability: Durability) -> Result<(), SpookyDbError> {
+        self.writer().set_durability(durability)
+    }
+
+    /// [`SpookyDb::sync`].
+    pub fn sync(&self) -> Result<(), SpookyDbError> {
+        self.writer().sync()
+    }
+
+    /// [`SpookyDb::backup_online`] without the writer lock: the copy reads
+    /// its own snapshot while writes keep committing.
+    pub fn backup_online(
+        &self,
+        dest: impl AsRef<Path>,
+        on_progress: impl FnMut(&BackupProgress),
+    ) -> Result<BackupProgress, SpookyDbError> {
+        copy_snapshot(&self.shared.redb, dest.as_ref(), on_progress)
+    }
+
+    /// [`SpookyDb::stats`], with the cache fields describing the shared
+    /// reader cache. Waits for the writer lock.
+    pub fn stats(&self) -> DbStats {
+        let mut stats = self.writer().stats();
+        self.shared.cache.fill_stats(&mut stats);
+        stats
+    }
+
+    /// [`SpookyDb::set_cache_policy`], applied to the shared reader cache.
+    /// A reserved share's limits are split evenly across the shards. After
+    /// `with_writer` clears the cache, a pinned table refills as it is read.
+    pub fn set_cache_policy(&self, table: &str, policy: CachePolicy) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        self.shared.cache.set_policy(table, policy);
+        if policy != CachePolicy::Pinned {
+            return Ok(());
+        }
+        // Under the ZSet read lock no publish can race the copy.
+        let _zsets = read(&self.shared.zsets);
+        let read_txn = self.shared.redb.begin_read()?;
+        let records = read_txn.open_table(RECORDS_TABLE)?;
+        let (lo, hi) = key_range(table);
+        let table = SmolStr::new(table);
+        for entry in records.range::<&str>(lo.as_str()..hi.as_str())? {
+            let (key, value) = entry?;
+            let id = SmolStr::new(&key.value()[lo.len()..]);
+            let shard = self.shared.cache.shard(&table, &id);
+            let bytes = self.decode(value.value())?;
+            self.shared
+                .cache
+                .put(shard, (table.clone(), id), Arc::from(bytes));
+        }
+        Ok(())
+    }
+
+    /// [`SpookyDb::set_compression`]. Readers decode with
+    /// `config.compressor` whatever the threshold.
+    pub fn set_compression(
+        &self,
+        table: &str,
+        min_bytes: Option<usize>,
+    ) -> Result<(), SpookyDbError> {
+        self.writer().set_compression(table, min_bytes)
+    }
+
+    /// [`SpookyDb::set_blob_threshold`].
+    pub fn set_blob_threshold(
+        &self,
+        table: &str,
+        min_bytes: Option<usize>,
+    ) -> Result<(), SpookyDbError> {
+        self.writer().set_blob_threshold(table, min_bytes)
+    }
+
+    /// [`SpookyDb::pin_table`], for the shared reader cache.
+    pub fn pin_table(&self, table: &str) -> Result<(), SpookyDbError> {
+        self.set_cache_policy(table, CachePolicy::Pinned)
+    }
+
+    fn writer(&self) -> MutexGuard<'_, SpookyDb> {
+        lock(&self.shared.writer)
+    }
+
+    /// Mirror committed changes into the reader-visible state. Called with
+    /// the writer lock held, so publishes happen in commit order.
+    fn publish(&self, writer: &SpookyDb, changes: Vec<(SmolStr, SmolStr, RowChange)>) {
+        let mut zsets = write(&self.shared.zsets);
+        for (table, id, change) in changes {
+            let zset = zsets.entry(table.clone()).or_default();
+            let shard = self.shared.cache.shard(&table, &id);
+            match change {
+                RowChange::Remove => {
+                    zset.remove(&id);
+                    lock(shard).pop(&(table, id));
+                }
+                RowChange::Put(data) => {
+                    zset.insert(id.clone(), 1);
+                    if let Some(bytes) = data {
+                        let bytes = writer.written_form(&table, bytes);
+                        self.shared.cache.put(shard, (table, id), Arc::from(bytes));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ─── Read Operations ──────────────────────────────────────────────────────────
+
+impl SharedSpookyDb {
+    /// Copy of a record's bytes: shared cache first, then redb. A miss
+    /// populates the cache.
+    pub fn get_record_bytes(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        Ok(self.fetch(table, id)?.map(|bytes| bytes.to_vec()))
+    }
+
+    /// [`SpookyDb::get_records_bulk`]: all misses are read in one redb read
+    /// transaction, and populate the cache.
+    pub fn get_records_bulk(
+        &self,
+        table: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Option<Vec<u8>>>, SpookyDbError> {
+        validate_table_name(table)?;
+        let zsets = read(&self.shared.zsets);
+        let table_key = SmolStr::new(table);
+        let mut out = Vec::with_capacity(ids.len());
+        let mut misses = Vec::new();
+        for (i, &id) in ids.iter().enumerate() {
+            if weight(&zsets, table, id) <= 0 {
+                out.push(None);
+                continue;
+            }
+            let key = (table_key.clone(), SmolStr::new(id));
+            let shard = self.shared.cache.shard(table, id);
+            match lock(shard).get(&key) {
+                Some(bytes) => {
+                    self.shared.cache.hits.fetch_add(1, Ordering::Relaxed);
+                    out.push(Some(bytes.to_vec()));
+                }
+                None => {
+                    self.shared.cache.misses.fetch_add(1, Ordering::Relaxed);
+                    misses.push(i);
+                    out.push(None);
+                }
+            }
+        }
+        if misses.is_empty() {
+            return Ok(out);
+        }
+
+        // As in `fetch`, `zsets` keeps publishes out until the reads are cached.
+        let read_txn = self.shared.redb.begin_read()?;
+        let records = read_txn.open_table(RECORDS_TABLE)?;
+        for i in misses {
+            let id = ids[i];
+            let Some(guard) = records.get(make_key(table, id)?.as_str())? else {
+                continue;
+            };
+            let bytes: Arc<[u8]> = Arc::from(self.decode(guard.value())?);
+            let shard = self.shared.cache.shard(table, id);
+            let key = (table_key.clone(), SmolStr::new(id));
+            self.shared.cache.put(shard, key, Arc::clone(&bytes));
+            out[i] = Some(bytes.to_vec());
+        }
+        Ok(out)
+    }
+
+    /// Run `f` over a zero-copy view of a record. No lock is held while `f`
+    /// runs, so it may read (or write) this database itself.
+    pub fn with_row_record<R>(
+        &self,
+        table: &str,
+        id: &str,
+        f: impl FnOnce(&SpookyRecord<'_>) -> R,
+    ) -> Result<Option<R>, SpookyDbError> {
+        let Some(bytes) = self.fetch(table, id)? else {
+            return Ok(None);
+        };
+        let (buf, count) = from_bytes(&bytes)?;
+        Ok(Some(f(&SpookyRecord::new(buf, count))))
+    }
+
+    /// [`SpookyDb::get_blob`], without the writer lock.
+    pub fn get_blob(
+        &self,
+        table: &str,
+        id: &str,
+        field: &str,
+    ) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        let Some(bytes) = self.fetch(table, id)? else {
+            return Ok(None);
+        };
+        resolve_blob(&self.shared.redb, &bytes, field)
+    }
+
+    /// VERSION_TABLE entry for a present record.
+    pub fn get_version(&self, table: &str, id: &str) -> Result<Option<u64>, SpookyDbError> {
+        validate_table_name(table)?;
+        let zsets = read(&self.shared.zsets);
+        if weight(&zsets, table, id) <= 0 {
+            return Ok(None);
+        }
+        let key = make_key(table, id)?;
+        let read_txn = self.shared.redb.begin_read()?;
+        let versions = read_txn.open_table(VERSION_TABLE)?;
+        Ok(versions.get(key.as_str())?.map(|guard| guard.value()))
+    }
+
+    /// [`SpookyDb::get_record_meta`], in the reader's own transaction.
+    pub fn get_record_meta(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> Result<Option<RecordMeta>, SpookyDbError> {
+        validate_table_name(table)?;
+        let zsets = read(&self.shared.zsets);
+        if weight(&zsets, table, id) <= 0 {
+            return Ok(None);
+        }
+        read_record_meta(&self.shared.redb, table, id)
+    }
+
+    /// [`SpookyDb::get_versions`], from one read transaction under the ZSet
+    /// read lock.
+    pub fn get_versions(
+        &self,
+        table: &str,
+        ids: &[&str],
+    ) -> Result<Vec<Option<u64>>, SpookyDbError> {
+        validate_table_name(table)?;
+        let zsets = read(&self.shared.zsets);
+        let read_txn = self.shared.redb.begin_read()?;
+        let versions = read_txn.open_table(VERSION_TABLE)?;
+        ids.iter()
+            .map(|&id| {
+                if weight(&zsets, table, id) <= 0 {
+                    return Ok(None);
+                }
+                let key = make_key(table, id)?;
+                Ok(versions.get(key.as_str())?.map(|guard| guard.value()))
+            })
+            .collect()
+    }
+
+    /// Shared cache, then redb under the ZSet read lock.
+    fn fetch(&self, table: &str, id: &str) -> Result<Option<Arc<[u8]>>, SpookyDbError> {
+        validate_table_name(table)?;
+        let zsets = read(&self.shared.zsets);
+        if weight(&zsets, table, id) <= 0 {
+            return Ok(None);
+        }
+        let key = (SmolStr::new(table), SmolStr::new(id));
+        let shard = self.shared.cache.shard(table, id);
+        if let Some(bytes) = lock(shard).get(&key) {
+            self.shared.cache.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(Arc::clone(bytes)));
+        }
+        self.shared.cache.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Miss. Holding `zsets` means no publish has happened since the
+        // presence check, so these bytes are at least as new as the cache's.
+        let db_key = make_key(table, id)?;
+        let read_txn = self.shared.redb.begin_read()?;
+        let records = read_txn.open_table(RECORDS_TABLE)?;
+        let Some(guard) = records.get(db_key.as_str())? else {
+            return Ok(None);
+        };
+        let bytes: Arc<[u8]> = Arc::from(self.decode(guard.value())?);
+        self.shared.cache.put(shard, key, Arc::clone(&bytes));
+        Ok(Some(bytes))
+    }
+
+    fn decode<'a>(&self, stored: &'a [u8]) -> Result<Cow<'a, [u8]>, SpookyDbError> {
+        compress::decode(self.shared.compressor.as_ref(), stored)
+    }
+}
+
+// ─── ZSet / Table Info (pure memory) ─────────────────────────────────────────
+
+impl SharedSpookyDb {
+    /// Weight for a single record. Returns 0 if absent.
+    pub fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
+        weight(&read(&self.shared.zsets), table, id)
+    }
+
+    /// Run `f` over a table's published ZSet (`None` if the table is unknown).
+    ///
+    /// Holds the ZSet read lock while `f` runs, which delays the writer's next
+    /// publish: keep `f` short and do not write to this database from it.
+    pub fn with_table_zset<R>(&self, table: &str, f: impl FnOnce(&ZSet) -> R) -> Option<R> {
+        read(&self.shared.zsets).get(table).map(f)
+    }
+
+    /// Snapshot of the ids present in `table`, in no particular order.
+    pub fn ids(&self, table: &str) -> Vec<SmolStr> {
+        read(&self.shared.zsets)
+            .get(table)
+            .map_or_else(Vec::new, |z| z.keys().cloned().collect())
+    }
+
+    /// Returns `true` if the table has at least one record.
+    pub fn table_exists(&self, table: &str) -> bool {
+        read(&self.shared.zsets)
+            .get(table)
+            .is_some_and(|z| !z.is_empty())
+    }
+
+    /// Record count for a table.
+    pub fn table_len(&self, table: &str) -> usize {
+        read(&self.shared.zsets).get(table).map_or(0, |z| z.len())
+    }
+
+    /// All known table names, in no particular order.
+    pub fn table_names(&self) -> Vec<SmolStr> {
+        read(&self.shared.zsets).keys().cloned().collect()
+    }
+}
+
+// ─── Sharded row cache ───────────────────────────────────────────────────────
+
+/// Row cache split into independently locked LRU shards so readers of
+/// different rows rarely contend. Values are `Arc`ed so a hit releases the
+/// shard lock before the caller touches the bytes.
+struct ShardedCache {
+    shards: Box<[CacheShard]>,
+    hasher: BuildHasherDefault<FxHasher>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ShardedCache {
+    fn new(capacity: NonZeroUsize, max_bytes: Option<usize>) -> Self {
+        let per_shard =
+            NonZeroUsize::new(capacity.get().div_ceil(CACHE_SHARDS)).unwrap_or(NonZeroUsize::MIN);
+        let bytes_per_shard = max_bytes.map(|max| max.div_ceil(CACHE_SHARDS));
+        let shards = (0..CACHE_SHARDS)
+            .map(|_| Mutex::new(RowCache::new(per_shard, bytes_per_shard)))
+            .collect();
+        Self {
+            shards,
+            hasher: BuildHasherDefault::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// `put` into `shard`, counting evictions.
+    fn put(&self, shard: &CacheShard, key: RowKey, bytes: Arc<[u8]>) {
+        let evicted = lock(shard).put(key, bytes) as u64;
+        if evicted > 0 {
+            self.evictions.fetch_add(evicted, Ordering::Relaxed);
+        }
+    }
+
+    /// Overwrite the cache fields of the writer's `stats` with this cache's.
+    fn fill_stats(&self, stats: &mut DbStats) {
+        stats.cache_hits = self.hits.load(Ordering::Relaxed);
+        stats.cache_misses = self.misses.load(Ordering::Relaxed);
+        stats.cache_evictions = self.evictions.load(Ordering::Relaxed);
+        stats.cache_len = self.shards.iter().map(|s| lock(s).len()).sum();
+        stats.cache_capacity = self.shards.iter().map(|s| lock(s).cap().get()).sum();
+        stats.cache_bytes = self.shards.iter().map(|s| lock(s).bytes()).sum();
+        stats.cache_max_bytes = lock(&self.shards[0])
+            .max_bytes()
+            .map(|max| max * CACHE_SHARDS);
+    }
+
+    fn set_policy(&self, table: &str, policy: CachePolicy) {
+        let policy = match policy {
+            CachePolicy::Reserved { entries, max_bytes } => CachePolicy::Reserved {
+                entries: NonZeroUsize::new(entries.get().div_ceil(CACHE_SHARDS))
+                    .unwrap_or(NonZeroUsize::MIN),
+                max_bytes: max_bytes.map(|max| max.div_ceil(CACHE_SHARDS)),
+            },
+            other => other,
+        };
+        let mut evicted = 0;
+        for shard in &self.shards {
+            evicted += lock(shard).set_policy(table, policy) as u64;
+        }
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    /// Move `old`'s rows and policy to `new`. Rows change shard with their key.
+    fn rename(&self, old: &str, new: &str) {
+        if old == new {
+            return;
+        }
+        let mut rows = Vec::new();
+        let mut policy = CachePolicy::Shared;
+        for shard in &self.shards {
+            let (shard_policy, shard_rows) = lock(shard).remove_table(old);
+            policy = shard_policy;
+            rows.extend(shard_rows);
+        }
+        let mut evicted = 0;
+        for shard in &self.shards {
+            evicted += lock(shard).set_policy(new, policy) as u64;
+        }
+        let new = SmolStr::new(new);
+        for ((_, id), bytes) in rows {
+            let shard = self.shard(&new, &id);
+            evicted += lock(shard).put((new.clone(), id), bytes) as u64;
+        }
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    fn shard(&self, table: &str, id: &str) -> &CacheShard {
+        let hash = self.hasher.hash_one((table, id)) as usize;
+        &self.shards[hash % CACHE_SHARDS]
+    }
+
+    fn clear(&self) {
+        for shard in &self.shards {
+            lock(shard).clear();
+        }
+    }
+}
+
+// ─── Helpers ──────────────────────────────────────────────────────────────────
+
+fn single_change(
+    table: &str,
+    id: &str,
+    op: Operation,
+    data: Option<&[u8]>,
+) -> (SmolStr, SmolStr, RowChange) {
+    let change = RowChange::of(op, data.map(<[u8]>::to_vec));
+    (SmolStr::new(table), SmolStr::new(id), change)
+}
+
+fn batch_changes(mutations: &[DbMutation]) -> Vec<(SmolStr, SmolStr, RowChange)> {
+    mutations
+        .iter()
+        .map(|m| {
+            let change = RowChange::of(m.op, m.data.clone());
+            (m.table.clone(), m.id.clone(), change)
+        })
+        .collect()
+}
+
+fn zset_copy(db: &SpookyDb) -> FastMap<SmolStr, ZSet> {
+    db.table_names()
+        .filter_map(|t| Some((t.clone(), db.get_table_zset(t)?.clone())))
+        .collect()
+}
+
+fn weight(zsets: &FastMap<SmolStr, ZSet>, table: &str, id: &str) -> i64 {
+    zsets
+        .get(table)
+        .and_then(|z| z.get(id).copied())
+        .unwrap_or(0)
+}
+
+// Poisoning is ignored: the caches and ZSets only ever hold committed state,
+// and the writer's transactions are atomic on disk.
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn read<T>(rwlock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    rwlock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(rwlock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    rwlock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::record;
+    use crate::spooky_record::SpookyReadable;
+    use tempfile::NamedTempFile;
+
+    fn mutation(id: &str, op: Operation, data: Option<Vec<u8>>) -> DbMutation {
+        DbMutation {
+            table: SmolStr::new("users"),
+            id: SmolStr::new(id),
+            op,
+            data,
+            version: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_shared_reads_follow_writes() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = SharedSpookyDb::new(tmp.path())?;
+        let alice = record(r#"{"name":"Alice","age":28}"#);
+        db.apply_mutation("users", Operation::Create, "alice", Some(&alice), Some(1))?;
+        db.apply_batch(vec![
+            mutation("bob", Operation::Create, Some(record(r#"{"name":"Bob"}"#))),
+            mutation("alice", Operation::Delete, None),
+        ])?;
+
+        assert_eq!(db.get_zset_weight("users", "alice"), 0);
+        assert_eq!(db.get_record_bytes("users", "alice")?, None);
+        let name = db.with_row_record("users", "bob", |r| r.get_str("name").map(str::to_owned))?;
+        assert_eq!(name, Some(Some("Bob".to_owned())));
+        assert_eq!(db.table_len("users"), 1);
+        assert_eq!(db.table_names(), vec![SmolStr::new("users")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_cache_miss_reads_redb() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        {
+            let mut db = SpookyDb::new(tmp.path())?;
+            let bytes = record(r#"{"name":"Alice"}"#);
+            db.apply_mutation("users", Operation::Create, "alice", Some(&bytes), Some(7))?;
+        }
+        let db = SharedSpookyDb::new(tmp.path())?;
+        assert_eq!(
+            db.get_record_bytes("users", "alice")?,
+            Some(record(r#"{"name":"Alice"}"#))
+        );
+        assert_eq!(db.get_version("users", "alice")?, Some(7));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_concurrent_readers() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = SharedSpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"n":1}"#);
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                let expected = bytes.clone();
+                std::thread::spawn(move || {
+                    for i in 0..200 {
+                        let id = format!("r{}", i % 50);
+                        if let Some(found) = db.get_record_bytes("users", &id).unwrap() {
+                            assert_eq!(found, expected);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for i in 0..50 {
+            let id = format!("r{i}");
+            db.apply_mutation("users", Operation::Create, &id, Some(&bytes), None)?;
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(db.table_len("users"), 50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_with_writer_republishes() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = SharedSpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"name":"Alice"}"#);
+        db.with_writer(|w| {
+            w.apply_mutation("users", Operation::Create, "alice", Some(&bytes), None)
+        })?;
+        assert_eq!(db.get_zset_weight("users", "alice"), 1);
+        assert_eq!(db.get_record_bytes("users", "alice")?, Some(bytes));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_backup_online_while_writing() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = SharedSpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"n":1}"#);
+        let records = (0..5000)
+            .map(|i| BulkRecord {
+                table: SmolStr::new("users"),
+                id: SmolStr::new(format!("u{i}")),
+                data: bytes.clone(),
+                version: Some(1),
+            })
+            .collect();
+        db.bulk_load(records)?;
+
+        let dir = tempfile::tempdir()?;
+        let dest = dir.path().join("copy.redb");
+        let mut calls = 0;
+        let done = db.backup_online(&dest, |progress| {
+            // Writes commit mid-copy but stay out of the snapshot.
+            if calls == 0 {
+                let id = format!("late{}", progress.copied);
+                db.apply_mutation("users", Operation::Create, &id, Some(&bytes), None)
+                    .unwrap();
+            }
+            calls += 1;
+        })?;
+        assert_eq!(done.copied, done.total);
+        assert!(calls > 2);
+        assert!(db.backup_online(&dest, |_| {}).is_err());
+
+        let copy = SpookyDb::new(&dest)?;
+        assert_eq!(copy.table_len("users"), 5000);
+        assert_eq!(copy.get_version("users", "u4999")?, Some(1));
+        assert_eq!(db.table_len("users"), 5001);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_group_commit() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let db = SharedSpookyDb::new(tmp.path())?;
+        let bytes = record(r#"{"n":1}"#);
+        let commits = db.stats().commits;
+
+        // Hold the writer until eight callers have queued; the first to get
+        // it next commits all of them together.
+        let workers = db.with_writer(|_| {
+            let workers: Vec<_> = (0..8)
+                .map(|i| {
+                    let (db, bytes) = (db.clone(), bytes.clone());
+                    std::thread::spawn(move || match i {
+                        7 => db.apply_batch(vec![DbMutation {
+                            table: SmolStr::new(""),
+                            ..mutation("bad", Operation::Create, Some(bytes))
+                        }])
+                        .map(drop),
+                        _ => {
+                            let id = format!("u{i}");
+                            db.apply_mutation("users", Operation::Create, &id, Some(&bytes), None)
+                                .map(drop)
+                        }
+                    })
+                })
+                .collect();
+            while lock(&db.shared.queue).len() < 8 {
+                std::thread::yield_now();
+            }
+            workers
+        });
+        let results: Vec<_> = workers.into_iter().map(|w| w.join().expect("worker")).collect();
+        assert!(results[..7].iter().all(Result::is_ok));
+        assert!(matches!(results[7], Err(SpookyDbError::InvalidKey(_))));
+        assert_eq!(db.stats().commits, commits + 1);
+        assert_eq!(db.table_len("users"), 7);
+        assert!(db.get_record_bytes("users", "u3")?.is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+//! Fixtures shared by the `db` unit tests.
+
+use crate::spooky_value::SpookyValue;
+
+/// Record bytes of a JSON object.
+pub(crate) fn record(json: &str) -> Vec<u8> {
+    let value = SpookyValue::from_json_str(json).expect("valid JSON");
+    crate::serialization::from_spooky(&value)
+        .expect("serializable")
+        .0
+}
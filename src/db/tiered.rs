@@ -0,0 +1,369 @@
+//! Memory-first `DbBackend` that spills cold tables to a `SpookyDb`.
+//!
+//! A table created through [`TieredDb`] lives in memory: its ZSet, record
+//! bytes and versions sit in plain maps and writes touch no disk. Once the
+//! hot tables hold more than `max_hot_records` records, the table written
+//! least recently is bulk-loaded into the `SpookyDb` and dropped from
+//! memory, repeating until the budget holds again.
+//!
+//! A table is in exactly one tier. Spilled tables, and tables the `SpookyDb`
+//! already held, stay on disk: their writes go straight to it. Recency is
+//! write order, as in the row cache, because reads take `&self`.
+//!
+//! Hot tables skip the `SpookyDb`'s per-table options — schemas, unique
+//! constraints, subscriptions, the oplog — until they spill. Hot records
+//! are lost if the process dies before `spill_all`; dropping a `TieredDb`
+//! spills on a best-effort basis.
+
+use smol_str::SmolStr;
+
+use super::db::{DbBackend, SpookyDb, record_typed, validate_key, validate_table_name};
+use super::types::{
+    BatchMutationResult, BulkRecord, DbMutation, FastMap, Operation, SpookyDbError, ZSet,
+};
+use super::zset::add_weight;
+use crate::spooky_value::SpookyValue;
+
+#[derive(Default)]
+struct HotTable {
+    zset: ZSet,
+    rows: FastMap<SmolStr, Vec<u8>>,
+    versions: FastMap<SmolStr, u64>,
+    /// Value of `TieredDb::clock` at the last write.
+    last_write: u64,
+}
+
+/// See the module docs.
+pub struct TieredDb {
+    disk: SpookyDb,
+    hot: FastMap<SmolStr, HotTable>,
+    max_hot_records: usize,
+    /// Records across all hot tables.
+    hot_records: usize,
+    clock: u64,
+}
+
+impl TieredDb {
+    /// Keep up to `max_hot_records` records in memory before spilling to
+    /// `disk`.
+    pub fn new(disk: SpookyDb, max_hot_records: usize) -> Self {
+        Self {
+            disk,
+            hot: FastMap::default(),
+            max_hot_records,
+            hot_records: 0,
+            clock: 0,
+        }
+    }
+
+    /// The disk tier. Tables still in memory are not visible through it.
+    pub fn disk(&self) -> &SpookyDb {
+        &self.disk
+    }
+
+    /// Whether `table` is held in memory.
+    pub fn is_hot(&self, table: &str) -> bool {
+        self.hot.contains_key(table)
+    }
+
+    /// Records currently held in memory.
+    pub fn hot_records(&self) -> usize {
+        self.hot_records
+    }
+
+    /// Version of a record, from whichever tier holds its table.
+    pub fn get_version(&self, table: &str, id: &str) -> Result<Option<u64>, SpookyDbError> {
+        match self.hot.get(table) {
+            Some(hot) => Ok(hot.versions.get(id).copied()),
+            None => self.disk.get_version(table, id),
+        }
+    }
+
+    /// Move `table` to disk in one `bulk_load`. Returns `false` if it was
+    /// not in memory.
+    pub fn spill(&mut self, table: &str) -> Result<bool, SpookyDbError> {
+        let Some(hot) = self.hot.get(table) else {
+            return Ok(false);
+        };
+        let records: Vec<BulkRecord> = hot
+            .zset
+            .keys()
+            .map(|id| BulkRecord {
+                table: SmolStr::new(table),
+                id: id.clone(),
+                data: hot.rows.get(id).cloned().unwrap_or_default(),
+                version: hot.versions.get(id).copied(),
+            })
+            .collect();
+        self.disk.ensure_table(table)?;
+        self.disk.bulk_load(records)?;
+        if let Some(hot) = self.hot.remove(table) {
+            self.hot_records -= hot.zset.len();
+        }
+        Ok(true)
+    }
+
+    /// Spill every hot table, e.g. before shutdown.
+    pub fn spill_all(&mut self) -> Result<(), SpookyDbError> {
+        let tables: Vec<SmolStr> = self.hot.keys().cloned().collect();
+        for table in tables {
+            self.spill(&table)?;
+        }
+        Ok(())
+    }
+
+    /// Spill the coldest tables until the hot records fit the budget.
+    fn enforce_budget(&mut self) -> Result<(), SpookyDbError> {
+        while self.hot_records > self.max_hot_records {
+            let coldest = self
+                .hot
+                .iter()
+                .min_by_key(|(_, hot)| hot.last_write)
+                .map(|(table, _)| table.clone());
+            match coldest {
+                Some(table) => self.spill(&table)?,
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    /// Whether writes to `table` stay in memory. A table the disk tier
+    /// holds records for stays there.
+    fn writes_hot(&self, table: &str) -> bool {
+        self.hot.contains_key(table) || self.disk.table_len(table) == 0
+    }
+
+    /// Apply one mutation to a hot table, creating it if needed. Returns
+    /// the actual ZSet weight change: 0 unless the record appeared or disappeared.
+    fn write_hot(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> i64 {
+        self.clock += 1;
+        let hot = self.hot.entry(SmolStr::new(table)).or_default();
+        hot.last_write = self.clock;
+        if matches!(op, Operation::Delete) {
+            hot.rows.remove(id);
+            hot.versions.remove(id);
+            let present = hot.zset.remove(id).is_some();
+            self.hot_records -= present as usize;
+            return -(present as i64);
+        }
+        let id = SmolStr::new(id);
+        if let Some(bytes) = data {
+            hot.rows.insert(id.clone(), bytes.to_vec());
+        }
+        if let Some(version) = version {
+            hot.versions.insert(id.clone(), version);
+        }
+        let added = hot.zset.insert(id, 1).is_none();
+        self.hot_records += added as usize;
+        added as i64
+    }
+}
+
+impl Drop for TieredDb {
+    /// Spills the hot tables. Errors are dropped; call `spill_all` to see
+    /// them.
+    fn drop(&mut self) {
+        let _ = self.spill_all();
+    }
+}
+
+impl DbBackend for TieredDb {
+    fn get_table_zset(&self, table: &str) -> Option<&ZSet> {
+        match self.hot.get(table) {
+            Some(hot) => Some(&hot.zset),
+            None => self.disk.get_table_zset(table),
+        }
+    }
+
+    fn get_record_bytes(&self, table: &str, id: &str) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        match self.hot.get(table) {
+            Some(hot) => Ok(hot.rows.get(id).cloned()),
+            None => self.disk.get_record_bytes(table, id),
+        }
+    }
+
+    fn get_row_record_bytes<'a>(&'a self, table: &str, id: &str) -> Option<&'a [u8]> {
+        match self.hot.get(table) {
+            Some(hot) => hot.rows.get(id).map(Vec::as_slice),
+            None => DbBackend::get_row_record_bytes(&self.disk, table, id),
+        }
+    }
+
+    fn ensure_table(&mut self, table: &str) -> Result<(), SpookyDbError> {
+        validate_table_name(table)?;
+        if !self.writes_hot(table) {
+            return Ok(());
+        }
+        self.clock += 1;
+        self.hot.entry(SmolStr::new(table)).or_default().last_write = self.clock;
+        Ok(())
+    }
+
+    fn apply_mutation(
+        &mut self,
+        table: &str,
+        op: Operation,
+        id: &str,
+        data: Option<&[u8]>,
+        version: Option<u64>,
+    ) -> Result<(SmolStr, i64), SpookyDbError> {
+        validate_table_name(table)?;
+        validate_key(table, id)?;
+        if !self.writes_hot(table) {
+            return self.disk.apply_mutation(table, op, id, data, version);
+        }
+        self.write_hot(table, op, id, data, version);
+        self.enforce_budget()?;
+        Ok((SmolStr::new(id), op.weight()))
+    }
+
+    /// The disk part commits in one transaction first; if it fails, no hot
+    /// table is touched.
+    fn apply_batch(
+        &mut self,
+        mutations: Vec<DbMutation>,
+    ) -> Result<BatchMutationResult, SpookyDbError> {
+        for mutation in &mutations {
+            validate_table_name(&mutation.table)?;
+            validate_key(&mutation.table, &mutation.id)?;
+        }
+        let (hot, cold): (Vec<_>, Vec<_>) = mutations
+            .into_iter()
+            .partition(|m| self.writes_hot(&m.table));
+        let mut result = match cold.is_empty() {
+            true => BatchMutationResult {
+                tick: self.disk.commit_tick(),
+                membership_deltas: FastMap::default(),
+                content_updates: FastMap::default(),
+                changed_tables: Vec::new(),
+                versions: FastMap::default(),
+            },
+            false => self.disk.apply_batch(cold)?,
+        };
+        for m in hot {
+            let delta = self.write_hot(&m.table, m.op, &m.id, m.data.as_deref(), m.version);
+            if delta != 0 {
+                let deltas = result.membership_deltas.entry(m.table.clone());
+                add_weight(deltas.or_default(), &m.id, delta);
+            }
+            if !matches!(m.op, Operation::Delete) {
+                let updates = result.content_updates.entry(m.table.clone());
+                updates.or_default().insert(m.id.clone());
+                if let Some(version) = m.version {
+                    let versions = result.versions.entry(m.table.clone()).or_default();
+                    versions.insert(m.id.clone(), version);
+                }
+            }
+            if !result.changed_tables.contains(&m.table) {
+                result.changed_tables.push(m.table);
+            }
+        }
+        self.enforce_budget()?;
+        Ok(result)
+    }
+
+    fn bulk_load(&mut self, records: Vec<BulkRecord>) -> Result<(), SpookyDbError> {
+        for record in &records {
+            validate_table_name(&record.table)?;
+            validate_key(&record.table, &record.id)?;
+        }
+        let (hot, cold): (Vec<_>, Vec<_>) =
+            records.into_iter().partition(|r| self.writes_hot(&r.table));
+        if !cold.is_empty() {
+            self.disk.bulk_load(cold)?;
+        }
+        for r in hot {
+            let data = Some(r.data.as_slice());
+            self.write_hot(&r.table, Operation::Create, &r.id, data, r.version);
+        }
+        self.enforce_budget()
+    }
+
+    fn get_zset_weight(&self, table: &str, id: &str) -> i64 {
+        self.get_table_zset(table)
+            .and_then(|z| z.get(id).copied())
+            .unwrap_or(0)
+    }
+
+    fn get_record_typed(
+        &self,
+        table: &str,
+        id: &str,
+        fields: &[&str],
+    ) -> Result<Option<SpookyValue>, SpookyDbError> {
+        match DbBackend::get_record_bytes(self, table, id)? {
+            Some(raw) => record_typed(&raw, fields).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_util::record;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_tiered_spills_coldest_table() -> Result<(), Box<dyn std::error::Error>> {
+        let tmp = NamedTempFile::new()?;
+        let mut db = TieredDb::new(SpookyDb::new(tmp.path())?, 3);
+        let bytes = record(r#"{"name":"alice"}"#);
+
+        db.apply_mutation("old", Operation::Create, "1", Some(&bytes), Some(7))?;
+        db.apply_mutation("old", Operation::Create, "2", Some(&bytes), None)?;
+        db.apply_mutation("new", Operation::Create, "1", Some(&bytes), None)?;
+        assert!(db.is_hot("old") && db.is_hot("new"));
+        assert_eq!(db.disk().table_len("old"), 0);
+
+        // Over budget: "old" was written least recently.
+        db.apply_mutation("new", Operation::Create, "2", Some(&bytes), None)?;
+        assert!(!db.is_hot("old") && db.is_hot("new"));
+        assert_eq!(db.hot_records(), 2);
+        assert_eq!(db.disk().table_len("old"), 2);
+        assert_eq!(db.get_version("old", "1")?, Some(7));
+        assert_eq!(db.get_record_bytes("old", "2")?, Some(bytes.clone()));
+
+        // Spilled tables take writes on disk.
+        db.apply_mutation("old", Operation::Delete, "2", None, None)?;
+        assert!(!db.is_hot("old"));
+        assert_eq!(db.get_zset_weight("old", "2"), 0);
+        assert_eq!(db.disk().table_len("old"), 1);
+
+        let result = db.apply_batch(vec![
+            DbMutation {
+                table: SmolStr::new("new"),
+                id: SmolStr::new("1"),
+                op: Operation::Delete,
+                data: None,
+                version: None,
+                expires_at: None,
+            },
+            DbMutation {
+                table: SmolStr::new("old"),
+                id: SmolStr::new("3"),
+                op: Operation::Create,
+                data: Some(bytes.clone()),
+                version: None,
+                expires_at: None,
+            },
+        ])?;
+        assert_eq!(result.membership_deltas["new"]["1"], -1);
+        assert_eq!(result.membership_deltas["old"]["3"], 1);
+        assert_eq!(db.get_table_zset("new").map(|z| z.len()), Some(1));
+
+        drop(db);
+        let disk = SpookyDb::new(tmp.path())?;
+        assert_eq!(disk.table_len("new"), 1);
+        assert_eq!(disk.table_len("old"), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! Incremental top-K by a numeric field.
+//!
+//! [`TopK`] keeps every folded record's score in an ordered set, so an
+//! insert, update or delete costs O(log n) and the K best are always the
+//! first K entries — a leaderboard never re-sorts its table. Scores are
+//! read with the zero-copy `get_number_as_f64`. `changes` reports how the
+//! ranking moved since it was last called, so a view pushes only the ranks
+//! that changed.
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use smol_str::SmolStr;
+
+use super::aggregate::RecordOperator;
+use super::types::{FastHashSet, FastMap, SortDirection};
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+
+/// Running top `k` records by `field`. Ties break by id, ascending.
+#[derive(Debug, Clone)]
+pub struct TopK {
+    field: SmolStr,
+    k: usize,
+    direction: SortDirection,
+    /// Every scored record, best first.
+    ordered: BTreeSet<(Score, SmolStr)>,
+    /// Sort key per id in `ordered`.
+    scores: FastMap<SmolStr, Score>,
+    /// Top ids as of the last `changes`.
+    emitted: Vec<SmolStr>,
+}
+
+/// A move in the ranking reported by `TopK::changes`. Ranks are 0-based;
+/// `None` is outside the top K.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankChange {
+    pub id: SmolStr,
+    pub from: Option<usize>,
+    pub to: Option<usize>,
+}
+
+/// Sort key: the score, negated for descending order, under `total_cmp`.
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl TopK {
+    /// The `k` records with the highest (`Desc`) or lowest (`Asc`) numeric
+    /// `field`.
+    pub fn new(field: &str, k: usize, direction: SortDirection) -> Self {
+        Self {
+            field: SmolStr::new(field),
+            k,
+            direction,
+            ordered: BTreeSet::new(),
+            scores: FastMap::default(),
+            emitted: Vec::new(),
+        }
+    }
+
+    /// Insert or re-score record `id`. A record whose field is missing or
+    /// not numeric is removed.
+    pub fn add(&mut self, id: &str, record: &impl SpookyReadable) {
+        let Some(value) = record.get_number_as_f64(&self.field) else {
+            self.remove(id);
+            return;
+        };
+        let score = match self.direction {
+            SortDirection::Asc => Score(value + 0.0),
+            SortDirection::Desc => Score(-value + 0.0),
+        };
+        let id = SmolStr::new(id);
+        match self.scores.insert(id.clone(), score) {
+            Some(old) if old == score => return,
+            Some(old) => {
+                self.ordered.remove(&(old, id.clone()));
+            }
+            None => {}
+        }
+        self.ordered.insert((score, id));
+    }
+
+    /// Forget record `id`, e.g. after it was deleted.
+    pub fn remove(&mut self, id: &str) {
+        if let Some((id, score)) = self.scores.remove_entry(id) {
+            self.ordered.remove(&(score, id));
+        }
+    }
+
+    /// The current top K as `(id, score)`, best first.
+    pub fn top(&self) -> impl Iterator<Item = (&SmolStr, f64)> {
+        let sign = match self.direction {
+            SortDirection::Asc => 1.0,
+            SortDirection::Desc => -1.0,
+        };
+        self.ordered
+            .iter()
+            .take(self.k)
+            .map(move |(score, id)| (id, sign * score.0 + 0.0))
+    }
+
+    /// Rank of `id` within the top K.
+    pub fn rank(&self, id: &str) -> Option<usize> {
+        self.top().position(|(top, _)| top == id)
+    }
+
+    /// Number of scored records, in the top K or not.
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// `true` if no record is scored.
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Every id whose rank differs from the last call (or from an empty
+    /// ranking on the first): ids that entered, left or moved within the
+    /// top K, in new-rank order followed by those that left.
+    pub fn changes(&mut self) -> Vec<RankChange> {
+        let current: Vec<SmolStr> = self.top().map(|(id, _)| id.clone()).collect();
+        let before: FastMap<&SmolStr, usize> =
+            self.emitted.iter().enumerate().map(|(rank, id)| (id, rank)).collect();
+        let mut changes = Vec::new();
+        for (rank, id) in current.iter().enumerate() {
+            let from = before.get(id).copied();
+            if from != Some(rank) {
+                let (id, to) = (id.clone(), Some(rank));
+                changes.push(RankChange { id, from, to });
+            }
+        }
+        let kept: FastHashSet<&SmolStr> = current.iter().collect();
+        for (rank, id) in self.emitted.iter().enumerate() {
+            if !kept.contains(id) {
+                let (id, from) = (id.clone(), Some(rank));
+                changes.push(RankChange { id, from, to: None });
+            }
+        }
+        self.emitted = current;
+        changes
+    }
+}
+
+impl RecordOperator for TopK {
+    fn add(&mut self, id: &str, record: &SpookyRecord<'_>) {
+        TopK::add(self, id, record);
+    }
+
+    fn remove(&mut self, id: &str) {
+        TopK::remove(self, id);
+    }
+}
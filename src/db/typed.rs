@@ -0,0 +1,135 @@
+//! Typed table handles over one `SpookyDb`.
+//!
+//! A [`Table<T>`] reads and writes a table as values of a serde struct `T`,
+//! so typed applications never touch `SpookyValue`. Writes serialize `T`
+//! straight to record bytes (`SpookyDb::put_struct`). Reads go through CBOR. Since
+//! records keep field hashes rather than names, `T`'s top-level field names
+//! are taken from its `Deserialize` impl when the handle is created. Nested
+//! values are stored with their keys and need no such help.
+//!
+//! Fields missing from a record are missing for serde too: use `Option` or
+//! `#[serde(default)]` for fields older records may lack.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::Serialize;
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use smol_str::SmolStr;
+
+use super::db::{SpookyDb, record_typed, validate_table_name};
+use super::types::{SortDirection, SpookyDbError};
+
+/// A `SpookyDb` table borrowed as values of `T`. See the module docs.
+pub struct Table<'a, T> {
+    db: &'a mut SpookyDb,
+    name: SmolStr,
+    /// `T`'s top-level field names, in declaration order.
+    fields: &'static [&'static str],
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Serialize + DeserializeOwned> Table<'a, T> {
+    pub(super) fn new(db: &'a mut SpookyDb, name: &str) -> Result<Self, SpookyDbError> {
+        validate_table_name(name)?;
+        let mut fields: &'static [&'static str] = &[];
+        let _ = T::deserialize(StructFields(&mut fields));
+        if fields.is_empty() {
+            return Err(SpookyDbError::Serialization(format!(
+                "{} is not a struct with named fields",
+                std::any::type_name::<T>()
+            )));
+        }
+        Ok(Self {
+            db,
+            name: SmolStr::new(name),
+            fields,
+            _value: PhantomData,
+        })
+    }
+
+    /// The table name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The record `id` as a `T`, or `None` if it is absent.
+    pub fn get(&self, id: &str) -> Result<Option<T>, SpookyDbError> {
+        match self.db.get_record_bytes(&self.name, id)? {
+            Some(bytes) => self.decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Write `value` as record `id`: a Create if it is absent, otherwise an
+    /// Update.
+    pub fn put(&mut self, id: &str, value: &T) -> Result<(), SpookyDbError> {
+        self.db.put_struct(&self.name, id, value, None)?;
+        Ok(())
+    }
+
+    /// Every record as `(id, T)`, in id order, streamed from one read
+    /// transaction (see `SpookyDb::iter_table_ordered`).
+    pub fn iter(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(SmolStr, T), SpookyDbError>> + '_, SpookyDbError> {
+        let rows = self.db.iter_table_ordered(&self.name, SortDirection::Asc)?;
+        Ok(rows.map(|row| {
+            let (id, bytes) = row?;
+            Ok((id, self.decode(&bytes)?))
+        }))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, SpookyDbError> {
+        let value = record_typed(bytes, self.fields)?;
+        let cbor = cbor4ii::serde::to_vec(Vec::new(), &value)
+            .map_err(|e| SpookyDbError::Serialization(e.to_string()))?;
+        cbor4ii::serde::from_slice(&cbor).map_err(|e| SpookyDbError::Serialization(e.to_string()))
+    }
+}
+
+/// A `Deserializer` that records the field list serde passes to
+/// `deserialize_struct` and then gives up.
+struct StructFields<'f>(&'f mut &'static [&'static str]);
+
+/// The only error `StructFields` returns.
+#[derive(Debug)]
+struct Stop;
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("field names collected")
+    }
+}
+
+impl std::error::Error for Stop {}
+
+impl de::Error for Stop {
+    fn custom<M: fmt::Display>(_: M) -> Self {
+        Stop
+    }
+}
+
+impl<'de> Deserializer<'de> for StructFields<'_> {
+    type Error = Stop;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Stop> {
+        Err(Stop)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Stop> {
+        *self.0 = fields;
+        Err(Stop)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map
+        enum identifier ignored_any
+    }
+}
@@ -3,15 +3,22 @@ use smol_str::SmolStr;
 use std::collections::HashSet;
 use std::hash::BuildHasherDefault;
 use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
+use super::compress::Compressor;
+
 pub type Weight = i64;
 pub type RowKey = SmolStr;
 pub type FastMap<K, V> = std::collections::HashMap<K, V, BuildHasherDefault<FxHasher>>;
 pub type FastHashSet<T> = HashSet<T, BuildHasherDefault<FxHasher>>;
 pub type ZSet = FastMap<RowKey, Weight>;
 
-/// Alias for table names — documents that this string must not contain ':'.
+/// Membership deltas per table, as in `BatchMutationResult::membership_deltas`.
+pub type TableDeltas = FastMap<SmolStr, ZSet>;
+
+/// Alias for table names. May contain ':'; must not be empty or contain U+001F.
 pub type TableName = SmolStr;
 
 /// Configuration for [`SpookyDb::new_with_config`].
@@ -23,25 +30,232 @@ pub struct SpookyDbConfig {
     ///
     /// Default: 10 000 records (~10–500 MB depending on average record size).
     pub cache_capacity: NonZeroUsize,
+
+    /// Upper bound on the row cache's total size in bytes, enforced alongside
+    /// `cache_capacity`. An entry weighs its record bytes plus its table and
+    /// id lengths; a record heavier than the whole bound is never cached.
+    ///
+    /// Default: `None` (bounded by entry count only).
+    pub cache_max_bytes: Option<usize>,
+
+    /// What each mutation appends to the persistent operation log.
+    ///
+    /// Default: [`OplogMode::Off`].
+    pub oplog: OplogMode,
+
+    /// Record each commit's net membership deltas under its commit tick, for
+    /// `SpookyDb::read_deltas`.
+    ///
+    /// Default: `false`.
+    pub delta_log: bool,
+
+    /// Buffer `apply_mutation` calls and commit each window in one write
+    /// transaction (one fsync) instead of one per call.
+    ///
+    /// Default: `None` (every call commits before returning).
+    pub coalesce: Option<CoalesceConfig>,
+
+    /// Commit durability. Can be changed later with `SpookyDb::set_durability`.
+    ///
+    /// Default: [`Durability::Immediate`].
+    pub durability: Durability,
+
+    /// Bytes of memory redb may use for its page cache (split 90/10 between
+    /// reads and writes). `None` keeps redb's default of 1 GiB.
+    ///
+    /// Default: `None`.
+    pub redb_cache_size: Option<usize>,
+
+    /// Codec for at-rest compression of record bytes, used for tables given
+    /// a threshold with `SpookyDb::set_compression`. Must be set to read a
+    /// file holding compressed values.
+    ///
+    /// Default: `None`.
+    pub compressor: Option<Arc<dyn Compressor>>,
+
+    /// Writes with `version: None` store the previous version plus one
+    /// (1 for a record without one) instead of leaving VERSION_TABLE as is.
+    /// Applies to `apply_mutation` and `apply_batch(es)`; `bulk_load` only
+    /// writes versions it is given.
+    ///
+    /// Default: `false`.
+    pub auto_version: bool,
+
+    /// Migrations run by `new_with_config` before it returns, as with
+    /// `SpookyDb::migrate`. Ones a table has already been through are
+    /// skipped, so the same list can be passed on every open.
+    ///
+    /// Default: empty.
+    pub migrations: Vec<Migration>,
+
+    /// Register `migrations` with `SpookyDb::migrate_lazy` instead, so
+    /// records are upgraded as they are read rather than all at open.
+    ///
+    /// Default: `false`.
+    pub lazy_migrations: bool,
 }
 
 impl Default for SpookyDbConfig {
     fn default() -> Self {
         Self {
             cache_capacity: NonZeroUsize::new(10_000).unwrap(),
+            cache_max_bytes: None,
+            oplog: OplogMode::Off,
+            delta_log: false,
+            coalesce: None,
+            durability: Durability::Immediate,
+            redb_cache_size: None,
+            compressor: None,
+            auto_version: false,
+            migrations: Vec::new(),
+            lazy_migrations: false,
+        }
+    }
+}
+
+/// How one table's rows share the row cache (see `SpookyDb::set_cache_policy`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Compete with every other shared table in one LRU bounded by
+    /// `cache_capacity` and `cache_max_bytes`.
+    #[default]
+    Shared,
+    /// A dedicated LRU with its own limits, on top of the shared ones, so
+    /// churn in other tables cannot push these rows out.
+    Reserved {
+        entries: NonZeroUsize,
+        max_bytes: Option<usize>,
+    },
+    /// Keep every row resident; never evicted. For small, hot tables such as
+    /// lookup or reference data.
+    Pinned,
+}
+
+/// How hard each commit works to reach disk (see `SpookyDbConfig::durability`).
+///
+/// Non-durable levels never risk corruption: after a crash the database
+/// opens at its last durable commit, losing only the writes after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// fsync on every commit: a write is durable once its call returns.
+    #[default]
+    Immediate,
+    /// Commits skip the fsync, except that at most once a second one is
+    /// fsync'd; a crash loses about a second of writes.
+    Eventual,
+    /// No fsync until `SpookyDb::sync`, a switch back to `Immediate`, or drop.
+    /// For bulk-load phases where a crash means starting over anyway.
+    None,
+}
+
+/// Write-coalescing window (see `SpookyDbConfig::coalesce`). A window is
+/// committed as soon as either limit is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoalesceConfig {
+    /// Longest a buffered mutation waits. Checked when a mutation arrives and
+    /// by `SpookyDb::flush_if_due` — there is no background timer.
+    pub max_delay: Duration,
+    /// Most mutations buffered before a commit.
+    pub max_mutations: usize,
+}
+
+impl Default for CoalesceConfig {
+    fn default() -> Self {
+        Self {
+            max_delay: Duration::from_millis(5),
+            max_mutations: 1_000,
         }
     }
 }
 
+/// Runs once a mutation's transaction has committed (`Ok`) or failed.
+/// See `SpookyDb::apply_mutation_durable`.
+pub type DurabilityCallback = Box<dyn FnOnce(Result<(), &SpookyDbError>) + Send>;
+
+/// Operation-log recording level (see `SpookyDb::read_oplog`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OplogMode {
+    /// Nothing is logged.
+    #[default]
+    Off,
+    /// Table, id, op and version — enough to know what changed.
+    Metadata,
+    /// Metadata plus the written record bytes — enough to replay.
+    Full,
+}
+
+/// One OPLOG_TABLE entry, as returned by `SpookyDb::read_oplog`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OplogEntry {
+    /// Commit-ordered sequence number, starting at 1. Never reused.
+    pub seq: u64,
+    pub table: SmolStr,
+    pub id: SmolStr,
+    pub op: Operation,
+    pub version: Option<u64>,
+    /// Record bytes for Create/Update under [`OplogMode::Full`].
+    pub data: Option<Vec<u8>>,
+}
+
 #[derive(Debug, Error)]
 pub enum SpookyDbError {
     #[error("redb error: {0}")]
     Redb(#[from] redb::Error),
     #[error("serialization error: {0}")]
     Serialization(String),
-    /// Table name contains ':' or key format is otherwise invalid.
+    /// A compressed value could not be decoded, or compression was asked
+    /// for without a `SpookyDbConfig::compressor`.
+    #[error("compression error: {0}")]
+    Compression(String),
+    /// Table name is empty or contains U+001F, or key format is otherwise invalid.
     #[error("invalid key: {0}")]
     InvalidKey(String),
+    /// Record bytes rejected by the table's schema (see `SpookyDb::set_schema`).
+    #[error("schema violation for {table}:{id}: {source}")]
+    SchemaViolation {
+        table: SmolStr,
+        id: SmolStr,
+        #[source]
+        source: crate::error::SchemaError,
+    },
+    /// `apply_mutation_cas` found a different VERSION_TABLE entry than expected.
+    /// `None` means the record had (or was expected to have) no version.
+    #[error("version conflict: expected {expected:?}, found {actual:?}")]
+    VersionConflict {
+        expected: Option<u64>,
+        actual: Option<u64>,
+    },
+    /// Write would give a unique field's value to a second id (see `SpookyDb::add_unique`).
+    #[error("unique violation on {field}: {value} is already held by {existing_id}")]
+    UniqueViolation {
+        field: SmolStr,
+        value: crate::spooky_value::SpookyValue,
+        existing_id: SmolStr,
+    },
+    /// Reading or writing an interchange stream or backup file.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// `rename_table` target, or the database given to `restore`, already
+    /// holds records.
+    #[error("table already exists: {0}")]
+    TableExists(SmolStr),
+    /// `SpookyDb::replay` was asked for entries that `trim_oplog` has removed.
+    #[error("oplog trimmed: entries after {requested} start at {oldest}")]
+    OplogTrimmed { requested: u64, oldest: u64 },
+    /// A `MigrationStep` could not be applied to a record. Batches committed
+    /// before it stay, and the table keeps its previous schema version.
+    #[error("migration of {table} to version {version} failed at {id}: {reason}")]
+    MigrationFailed {
+        table: SmolStr,
+        id: SmolStr,
+        version: u32,
+        reason: String,
+    },
+    /// `AsyncSpookyDb`'s writer could not deliver a result because it has
+    /// stopped, or a group commit (`AsyncSpookyDb` or `SharedSpookyDb`)
+    /// failed: the storage error, as text.
+    #[error("writer: {0}")]
+    Writer(String),
 }
 
 impl From<redb::DatabaseError> for SpookyDbError {
@@ -68,6 +282,18 @@ impl From<redb::CommitError> for SpookyDbError {
     }
 }
 
+impl From<redb::SetDurabilityError> for SpookyDbError {
+    fn from(e: redb::SetDurabilityError) -> Self {
+        SpookyDbError::Redb(e.into())
+    }
+}
+
+impl From<redb::CompactionError> for SpookyDbError {
+    fn from(e: redb::CompactionError) -> Self {
+        SpookyDbError::Redb(e.into())
+    }
+}
+
 impl From<redb::StorageError> for SpookyDbError {
     fn from(e: redb::StorageError) -> Self {
         SpookyDbError::Redb(e.into())
@@ -105,6 +331,10 @@ pub struct DbMutation {
     /// every mutation where version tracking matters, or accept that `get_version` may
     /// return a stale value after an update with `version: None`.
     pub version: Option<u64>,
+    /// Expiry time for `SpookyDb::sweep_expired`, in the caller's clock (for
+    /// example ms since the UNIX epoch). `None` leaves any existing expiry
+    /// unchanged; use `SpookyDb::set_expiry` to clear one. Ignored for `Delete`.
+    pub expires_at: Option<u64>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -128,17 +358,298 @@ impl Operation {
     }
 }
 
+/// One committed write, delivered to `SpookyDb::subscribe` receivers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub table: SmolStr,
+    pub id: SmolStr,
+    /// `Create` for `bulk_load` records.
+    pub op: Operation,
+    /// Version passed with the write (`None` if none was given).
+    pub version: Option<u64>,
+    /// Written bytes for Create/Update — only for `subscribe_with_data`.
+    pub data: Option<Vec<u8>>,
+}
+
+/// The deltas of one committed write transaction, delivered to
+/// `SpookyDb::delta_stream` receivers.
+#[derive(Debug, Clone)]
+pub struct DeltaBatch {
+    /// The transaction's commit tick, as in `BatchMutationResult::tick`:
+    /// increasing across batches and restarts, though not necessarily by 1.
+    pub tick: u64,
+    /// As `BatchMutationResult::membership_deltas`, merged over the
+    /// transaction.
+    pub membership_deltas: FastMap<SmolStr, ZSet>,
+    /// As `BatchMutationResult::content_updates`, merged over the
+    /// transaction.
+    pub content_updates: FastMap<SmolStr, FastHashSet<SmolStr>>,
+}
+
+/// One committed write to a single record, delivered to `SpookyDb::watch`
+/// receivers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordChange {
+    /// `Create` for `bulk_load` records.
+    pub op: Operation,
+    /// Version passed with the write (`None` if none was given).
+    pub version: Option<u64>,
+    /// Written bytes for Create/Update; `None` for Delete.
+    pub data: Option<Vec<u8>>,
+}
+
+/// Outcome of `SpookyDb::compact`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// File size before compaction, in bytes.
+    pub bytes_before: u64,
+    /// File size after compaction, in bytes.
+    pub bytes_after: u64,
+    /// `false` if redb found nothing to relocate.
+    pub compacted: bool,
+}
+
+impl CompactionReport {
+    /// Bytes returned to the file system.
+    pub fn reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Result of `SpookyDb::integrity_check`. Each list holds `(table, id)` pairs.
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    /// RECORDS_TABLE entries scanned.
+    pub records_scanned: usize,
+    /// On disk but absent from the table's ZSet.
+    pub missing_from_zset: Vec<(SmolStr, SmolStr)>,
+    /// In a ZSet but not on disk.
+    pub missing_from_disk: Vec<(SmolStr, SmolStr)>,
+    /// On disk and in the ZSet, but with a weight other than 1.
+    pub wrong_weight: Vec<(SmolStr, SmolStr, Weight)>,
+    /// Row-cache entries whose bytes differ from disk, or whose record is gone.
+    pub stale_cache: Vec<(SmolStr, SmolStr)>,
+    /// `(table, persisted, on_disk)` where the persisted table stats are wrong.
+    pub wrong_table_stats: Vec<(SmolStr, TableStats, TableStats)>,
+    /// The divergences above were fixed in memory.
+    pub repaired: bool,
+}
+
+impl IntegrityReport {
+    /// `true` if memory matched disk.
+    pub fn is_clean(&self) -> bool {
+        self.missing_from_zset.is_empty()
+            && self.missing_from_disk.is_empty()
+            && self.wrong_weight.is_empty()
+            && self.stale_cache.is_empty()
+            && self.wrong_table_stats.is_empty()
+    }
+}
+
+/// Snapshot returned by `SpookyDb::stats`. Counters are cumulative since open.
+#[derive(Debug, Clone, Default)]
+pub struct DbStats {
+    /// Row-cache lookups answered from memory.
+    pub cache_hits: u64,
+    /// Row-cache lookups that had to go to redb (or, for `get_row_record`,
+    /// returned `None`).
+    pub cache_misses: u64,
+    /// Rows pushed out of the cache to make room.
+    pub cache_evictions: u64,
+    /// Rows cached now.
+    pub cache_len: usize,
+    pub cache_capacity: usize,
+    /// Total weight of the cached rows (see `SpookyDbConfig::cache_max_bytes`).
+    pub cache_bytes: usize,
+    pub cache_max_bytes: Option<usize>,
+    /// Write transactions committed.
+    pub commits: u64,
+    /// Total time spent committing, including fsyncs.
+    pub commit_time: Duration,
+    /// Slowest single commit.
+    pub max_commit_time: Duration,
+    /// Record bytes written to RECORDS_TABLE.
+    pub bytes_written: u64,
+    /// Per-table record counts and sizes.
+    pub tables: FastMap<SmolStr, TableStats>,
+}
+
+impl DbStats {
+    /// `cache_hits / (cache_hits + cache_misses)`, or 0 before any lookup.
+    pub fn cache_hit_rate(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.cache_hits as f64 / lookups as f64
+    }
+
+    /// Average commit latency.
+    pub fn mean_commit_time(&self) -> Duration {
+        match u32::try_from(self.commits) {
+            Ok(0) => Duration::ZERO,
+            Ok(n) => self.commit_time / n,
+            Err(_) => Duration::from_secs_f64(self.commit_time.as_secs_f64() / self.commits as f64),
+        }
+    }
+}
+
+/// Size of one table, kept up to date by every write and persisted with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TableStats {
+    /// RECORDS_TABLE entries.
+    pub records: u64,
+    /// Sum of their record bytes (keys and redb overhead excluded).
+    pub bytes: u64,
+}
+
+/// How far `backup_online` has got; passed to its progress callback and
+/// returned when it finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BackupProgress {
+    /// Entries written to the copy so far, across all redb tables.
+    pub copied: u64,
+    /// Entries in the snapshot being copied.
+    pub total: u64,
+}
+
+/// Write timestamps of one record; see `SpookyDb::set_timestamps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordMeta {
+    /// First write seen while timestamps were on (ms since UNIX epoch).
+    pub created_at: u64,
+    /// Latest such write (ms since UNIX epoch).
+    pub updated_at: u64,
+}
+
+/// Per-table ZSets and tick counters, as saved by `SpookyDb::checkpoint`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ZSetSnapshot {
+    /// Every non-empty table's ZSet.
+    pub tables: FastMap<SmolStr, ZSet>,
+    /// Tick counters set with `SpookyDb::set_tick`.
+    pub ticks: FastMap<SmolStr, u64>,
+}
+
+/// Receives a `DbStats` snapshot; see `SpookyDb::set_stats_hook`.
+pub type StatsHook = Box<dyn FnMut(&DbStats) + Send>;
+
+/// An operation reported to the hook set with `SpookyDb::set_timing_hook`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimedOp {
+    /// Serializing a record in `apply_mutation_cbor`, `apply_mutation_value`
+    /// or `put_struct`.
+    Encode,
+    /// One unbuffered `apply_mutation` (or `_versioned`, `_cas`), commit
+    /// included.
+    Write,
+    /// One `apply_batch` or `apply_batches` call, commit included.
+    Batch,
+    /// One `bulk_load` call, commit included.
+    BulkLoad,
+    /// A redb commit, whichever write path made it.
+    Commit,
+    /// One `get_record_bytes` call, cache hit or miss.
+    Read,
+}
+
+/// Receives an operation and how long it took; see `SpookyDb::set_timing_hook`.
+pub type TimingHook = Box<dyn Fn(TimedOp, Duration) + Send>;
+
+/// One change `SpookyDb::migrate` applies to every record of a table.
+///
+/// A step leaves records it already holds for untouched, so a migration
+/// interrupted part way can run again from the start.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationStep {
+    /// Add `name` with `default` to records without it.
+    AddField { name: SmolStr, default: crate::spooky_value::SpookyValue },
+    /// Rename `from` to `to` in records that have `from`. Fails if a record
+    /// has both.
+    RenameField { from: SmolStr, to: SmolStr },
+    /// Remove `name` from records that have it.
+    DropField { name: SmolStr },
+    /// Convert `name` to `to` in records that have it, under the `coerce`
+    /// rules; null stays null. Fails for values that do not convert.
+    ChangeType { name: SmolStr, to: ValueKind },
+}
+
+/// Target type of `MigrationStep::ChangeType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// `I64`. Strings are parsed, floats must be whole, bools become 0/1.
+    Int,
+    /// `F64`. Strings are parsed, bools become 0/1.
+    Float,
+    /// Numbers and bools are formatted; arrays and objects fail.
+    Str,
+    /// SurrealQL truthiness.
+    Bool,
+}
+
+/// Steps that take `table` to schema version `version`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub table: SmolStr,
+    /// Applied only while the table's persisted schema version is lower,
+    /// which it becomes once every record has been rewritten.
+    pub version: u32,
+    pub steps: Vec<MigrationStep>,
+}
+
+/// How far `SpookyDb::migrate` has got with one migration; passed to its
+/// progress callback after each batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationProgress {
+    pub table: SmolStr,
+    pub version: u32,
+    /// Records visited so far.
+    pub done: u64,
+    /// Records in the table when the migration started.
+    pub total: u64,
+}
+
+/// Return value of `SpookyDb::apply_batch_with_savepoint`.
+#[derive(Debug)]
+pub struct SavepointBatchResult {
+    /// Deltas of the segments that committed.
+    pub result: BatchMutationResult,
+    /// How many leading segments committed.
+    pub committed: usize,
+    /// Why the segment after them was rolled back, if one was.
+    pub rolled_back: Option<SpookyDbError>,
+}
+
+/// Sort order for `SpookyDb::query_sorted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    /// Smallest first.
+    #[default]
+    Asc,
+    /// Largest first.
+    Desc,
+}
+
 /// Return value of `apply_batch`. Contains all per-table deltas accumulated
 /// in a single pass — no extra allocations after the batch commit.
 #[derive(Debug)]
 pub struct BatchMutationResult {
-    /// Per-table ZSet weight deltas (Create = +1, Delete = -1, Update = 0).
+    /// Tick of the commit that applied the batch (`SpookyDb::commit_tick`).
+    /// Batches committed together by `apply_batches` share it.
+    pub tick: u64,
+    /// Per-table ZSet weight deltas: the change each record's weight went
+    /// through, summed over the batch (+1 for a record that appeared, -1 for
+    /// one that disappeared). A record created and deleted in the same batch,
+    /// or rewritten while present, has no entry.
     /// Key: table name → ZSet<record_id, weight_delta>.
     pub membership_deltas: FastMap<SmolStr, ZSet>,
     /// Per-table set of record IDs whose content was written (Create or Update).
     pub content_updates: FastMap<SmolStr, FastHashSet<SmolStr>>,
     /// Tables that had at least one mutation (deduplicated).
     pub changed_tables: Vec<SmolStr>,
+    /// Per-table version written for each Create/Update that wrote one,
+    /// given or (with `SpookyDbConfig::auto_version`) incremented.
+    pub versions: FastMap<SmolStr, FastMap<SmolStr, u64>>,
 }
 
 /// One record for `bulk_load`. `data` must be pre-serialized SpookyRecord bytes.
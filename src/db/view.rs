@@ -0,0 +1,411 @@
+//! Incrementally maintained materialized views.
+//!
+//! A [`View`] selects the records of one table that pass a filter, projects
+//! them to some of their fields and, optionally, joins each to the record of
+//! another table that one of its fields names. Once registered with
+//! `SpookyDb::register_view`, the database feeds it the deltas of every
+//! committed write: only the records a batch touched are re-evaluated, so a
+//! view costs O(changes) per tick rather than a table scan.
+//!
+//! The output is a ZSet of the selected ids (weight 1 each) plus each id's
+//! projected record, built like any other record and readable zero-copy.
+//! `delta` and `updated` describe how the last write changed it.
+//!
+//! A `persisted` view also writes its records to the table `__view:<name>`
+//! after each change, so clients read them with `get_record_bytes`, `scan`
+//! or `query` like any other table, and re-registering the view after a
+//! restart loads them instead of recomputing the view.
+//!
+//! ```rust,ignore
+//! let view = View::new("feed", "posts")
+//!     .filter(|r| r.get_bool("published") == Some(true))
+//!     .project(&["title", "author"])
+//!     .join("author", "users", &["name"]);
+//! db.register_view(view)?;
+//! db.apply_batch(mutations)?;
+//! let feed = db.view("feed").expect("registered");
+//! let author = feed.get_record("p1").and_then(|r| r.get_field::<SpookyValue>("author"));
+//! ```
+
+use smol_str::SmolStr;
+
+use super::db::{SpookyDb, record_typed};
+use super::types::{
+    BatchMutationResult, DbMutation, FastHashSet, FastMap, Operation, SpookyDbError, ZSet,
+};
+use super::zset::add_weight;
+use crate::serialization::{from_bytes, from_spooky};
+use crate::spooky_record::record_mut::SpookyRecordMut;
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+
+/// Prefix of a persisted view's table.
+const TABLE_PREFIX: &str = "__view:";
+
+/// Record filter of a view.
+type Filter = Box<dyn Fn(&SpookyRecord<'_>) -> bool + Send + Sync>;
+
+/// See the module docs.
+pub struct View {
+    name: SmolStr,
+    table: SmolStr,
+    filter: Option<Filter>,
+    /// Projected fields; empty keeps every field.
+    fields: Vec<SmolStr>,
+    join: Option<Join>,
+    /// Selected ids, weight 1 each.
+    output: ZSet,
+    /// Projected record per selected id.
+    rows: FastMap<SmolStr, Vec<u8>>,
+    /// Joined id → ids of `table` whose join field names it.
+    refs: FastMap<SmolStr, FastHashSet<SmolStr>>,
+    /// Id of `table` → joined id it names, the inverse of `refs`.
+    targets: FastMap<SmolStr, SmolStr>,
+    /// Membership changes from the last write.
+    delta: ZSet,
+    /// Ids that stayed selected but whose projected record changed.
+    updated: FastHashSet<SmolStr>,
+    /// A record could not be read while maintaining the view.
+    stale: bool,
+    /// Records are mirrored to `storage_table`.
+    persisted: bool,
+}
+
+/// `View::join` arguments.
+struct Join {
+    /// Field of the view's table holding the joined record's id.
+    field: SmolStr,
+    table: SmolStr,
+    /// Fields of the joined record nested under `field`.
+    fields: Vec<SmolStr>,
+}
+
+impl View {
+    /// A view named `name` over every record of `table`, unprojected.
+    pub fn new(name: &str, table: &str) -> Self {
+        Self {
+            name: SmolStr::new(name),
+            table: SmolStr::new(table),
+            filter: None,
+            fields: Vec::new(),
+            join: None,
+            output: ZSet::default(),
+            rows: FastMap::default(),
+            refs: FastMap::default(),
+            targets: FastMap::default(),
+            delta: ZSet::default(),
+            updated: FastHashSet::default(),
+            stale: false,
+            persisted: false,
+        }
+    }
+
+    /// Select only the records for which `filter` returns `true`.
+    pub fn filter(
+        mut self,
+        filter: impl Fn(&SpookyRecord<'_>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Keep only `fields` in the view's records; absent fields are left out.
+    pub fn project(mut self, fields: &[&str]) -> Self {
+        self.fields = fields.iter().map(|&f| SmolStr::new(f)).collect();
+        self
+    }
+
+    /// Inner join: select only records whose string field `field` is the id
+    /// of a record in `table`, and replace `field` in the view's record with
+    /// an object holding that record's `fields`. Changes to `table` update
+    /// the records that name the changed ids.
+    pub fn join(mut self, field: &str, table: &str, fields: &[&str]) -> Self {
+        self.join = Some(Join {
+            field: SmolStr::new(field),
+            table: SmolStr::new(table),
+            fields: fields.iter().map(|&f| SmolStr::new(f)).collect(),
+        });
+        self
+    }
+
+    /// Mirror the view's records to the table `__view:<name>` and restore
+    /// them from it on registration.
+    pub fn persisted(mut self) -> Self {
+        self.persisted = true;
+        self
+    }
+
+    /// The view's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The table the view selects from.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// The table a persisted view is stored in.
+    pub fn storage_table(&self) -> Option<SmolStr> {
+        self.persisted
+            .then(|| SmolStr::new(format!("{TABLE_PREFIX}{}", self.name)))
+    }
+
+    /// Selected ids, weight 1 each.
+    pub fn zset(&self) -> &ZSet {
+        &self.output
+    }
+
+    /// Number of selected records.
+    pub fn len(&self) -> usize {
+        self.output.len()
+    }
+
+    /// `true` if no record is selected.
+    pub fn is_empty(&self) -> bool {
+        self.output.is_empty()
+    }
+
+    /// Selected ids, in no particular order.
+    pub fn ids(&self) -> impl Iterator<Item = &SmolStr> {
+        self.output.keys()
+    }
+
+    /// Projected record bytes of a selected id.
+    pub fn get(&self, id: &str) -> Option<&[u8]> {
+        self.rows.get(id).map(Vec::as_slice)
+    }
+
+    /// Zero-copy view of a selected id's projected record.
+    pub fn get_record(&self, id: &str) -> Option<SpookyRecord<'_>> {
+        let (buf, count) = from_bytes(self.get(id)?).ok()?;
+        Some(SpookyRecord::new(buf, count))
+    }
+
+    /// Membership changes from the last write: `+1` for ids that entered
+    /// the view, `-1` for ids that left it.
+    pub fn delta(&self) -> &ZSet {
+        &self.delta
+    }
+
+    /// Ids that were selected before and after the last write but whose
+    /// projected record changed.
+    pub fn updated(&self) -> &FastHashSet<SmolStr> {
+        &self.updated
+    }
+
+    /// `true` if a storage error left the view out of date; see
+    /// `SpookyDb::refresh_view`.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Recompute the view from a full scan of its table. `delta` then holds
+    /// the change from the previous state.
+    pub(super) fn rebuild(&mut self, db: &SpookyDb) -> Result<(), SpookyDbError> {
+        let ids: FastHashSet<SmolStr> = db
+            .ids(&self.table)
+            .chain(self.output.keys())
+            .cloned()
+            .collect();
+        self.begin();
+        self.stale = true;
+        for id in &ids {
+            self.refresh(db, id)?;
+        }
+        self.stale = false;
+        Ok(())
+    }
+
+    /// Apply the changes of committed `results`. Ids are re-evaluated once
+    /// each, however many results touch them.
+    pub(super) fn apply(&mut self, db: &SpookyDb, results: &[&BatchMutationResult]) {
+        let mut touched: FastHashSet<SmolStr> = FastHashSet::default();
+        for result in results {
+            let changed = |table: &str| {
+                let members = result.membership_deltas.get(table).into_iter().flat_map(ZSet::keys);
+                let written = result.content_updates.get(table).into_iter().flatten();
+                members.chain(written)
+            };
+            touched.extend(changed(&self.table).cloned());
+            if let Some(join) = &self.join {
+                for id in changed(&join.table) {
+                    touched.extend(self.refs.get(id).into_iter().flatten().cloned());
+                }
+            }
+        }
+        self.begin();
+        for id in &touched {
+            if self.refresh(db, id).is_err() {
+                self.stale = true;
+            }
+        }
+    }
+
+    /// Load a persisted view from its table instead of recomputing it.
+    /// `Ok(false)` if the table is empty. A join view still reads the join
+    /// field of every record of its table to learn which ids name which
+    /// joined records; no joined record is fetched.
+    pub(super) fn restore(&mut self, db: &SpookyDb) -> Result<bool, SpookyDbError> {
+        let Some(storage) = self.storage_table() else {
+            return Ok(false);
+        };
+        let stored: Vec<SmolStr> = db.ids(&storage).cloned().collect();
+        if stored.is_empty() {
+            return Ok(false);
+        }
+        self.begin();
+        self.output.clear();
+        self.rows.clear();
+        for id in stored {
+            if let Some(row) = db.get_record_bytes(&storage, &id)? {
+                self.output.insert(id.clone(), 1);
+                self.rows.insert(id, row);
+            }
+        }
+        if let Some(join) = &self.join {
+            let field = join.field.clone();
+            let ids: Vec<SmolStr> = db.ids(&self.table).cloned().collect();
+            for id in &ids {
+                let Some(bytes) = db.get_record_bytes(&self.table, id)? else {
+                    continue;
+                };
+                let (buf, count) = from_bytes(&bytes)?;
+                let target = SpookyRecord::new(buf, count).get_str(&field).map(SmolStr::new);
+                self.set_target(id, target);
+            }
+        }
+        self.stale = false;
+        Ok(true)
+    }
+
+    /// Writes that bring the storage table, currently holding `stored`, in
+    /// line with the view: the last change only, or every record if `full`.
+    pub(super) fn storage_writes(&self, stored: Option<&ZSet>, full: bool) -> Vec<DbMutation> {
+        let Some(table) = self.storage_table() else {
+            return Vec::new();
+        };
+        let write = |id: &SmolStr, op| DbMutation {
+            table: table.clone(),
+            id: id.clone(),
+            op,
+            data: match op {
+                Operation::Delete => None,
+                _ => self.rows.get(id).cloned(),
+            },
+            version: None,
+            expires_at: None,
+        };
+        if full {
+            let stale = stored.into_iter().flatten().map(|(id, _)| id);
+            let stale = stale.filter(|id| !self.rows.contains_key(*id));
+            let removed = stale.map(|id| write(id, Operation::Delete));
+            let written = self.rows.keys().map(|id| write(id, Operation::Update));
+            return removed.chain(written).collect();
+        }
+        let changed = self.delta.iter().map(|(id, &weight)| match weight > 0 {
+            true => write(id, Operation::Create),
+            false => write(id, Operation::Delete),
+        });
+        let updated = self.updated.iter().map(|id| write(id, Operation::Update));
+        changed.chain(updated).collect()
+    }
+
+    /// Flag the view as out of date, e.g. after its records failed to store.
+    pub(super) fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    fn begin(&mut self) {
+        self.delta.clear();
+        self.updated.clear();
+    }
+
+    /// Re-evaluate `id` against the current state of `db`.
+    fn refresh(&mut self, db: &SpookyDb, id: &SmolStr) -> Result<(), SpookyDbError> {
+        let row = self.evaluate(db, id)?;
+        let before = self.rows.remove(id);
+        match (before, row) {
+            (None, None) => {}
+            (Some(_), None) => {
+                self.output.remove(id);
+                add_weight(&mut self.delta, id, -1);
+            }
+            (None, Some(row)) => {
+                self.output.insert(id.clone(), 1);
+                add_weight(&mut self.delta, id, 1);
+                self.rows.insert(id.clone(), row);
+            }
+            (Some(old), Some(row)) => {
+                if old != row {
+                    self.updated.insert(id.clone());
+                }
+                self.rows.insert(id.clone(), row);
+            }
+        }
+        Ok(())
+    }
+
+    /// `id`'s view record, or `None` if it is not selected. Keeps the join
+    /// references in step with the record.
+    fn evaluate(&mut self, db: &SpookyDb, id: &SmolStr) -> Result<Option<Vec<u8>>, SpookyDbError> {
+        let Some(bytes) = db.get_record_bytes(&self.table, id)? else {
+            self.set_target(id, None);
+            return Ok(None);
+        };
+        let (buf, count) = from_bytes(&bytes)?;
+        let record = SpookyRecord::new(buf, count);
+        let target = self.join.as_ref().and_then(|join| record.get_str(&join.field));
+        let target = target.map(SmolStr::new);
+        self.set_target(id, target.clone());
+        if !self.filter.as_ref().is_none_or(|keep| keep(&record)) {
+            return Ok(None);
+        }
+        let (row, count) = self.projected(bytes, count)?;
+        let Some(join) = &self.join else {
+            return Ok(Some(row));
+        };
+        let joined = match &target {
+            Some(target) => db.get_record_bytes(&join.table, target)?,
+            None => None,
+        };
+        let Some(joined) = joined else {
+            return Ok(None);
+        };
+        let fields: Vec<&str> = join.fields.iter().map(SmolStr::as_str).collect();
+        let nested = record_typed(&joined, &fields)?;
+        let mut row = SpookyRecordMut::new(row, count);
+        match row.has_field(&join.field) {
+            true => row.set_field(&join.field, &nested)?,
+            false => row.add_field(&join.field, &nested)?,
+        }
+        Ok(Some(row.data_buf))
+    }
+
+    /// `bytes` reduced to the projected fields, with its field count.
+    fn projected(&self, bytes: Vec<u8>, count: usize) -> Result<(Vec<u8>, usize), SpookyDbError> {
+        if self.fields.is_empty() {
+            return Ok((bytes, count));
+        }
+        let fields: Vec<&str> = self.fields.iter().map(SmolStr::as_str).collect();
+        Ok(from_spooky(&record_typed(&bytes, &fields)?)?)
+    }
+
+    /// Point `id`'s join reference at `target`.
+    fn set_target(&mut self, id: &SmolStr, target: Option<SmolStr>) {
+        if self.join.is_none() {
+            return;
+        }
+        if let Some(old) = self.targets.remove(id)
+            && let Some(ids) = self.refs.get_mut(&old)
+        {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.refs.remove(&old);
+            }
+        }
+        if let Some(target) = target {
+            self.refs.entry(target.clone()).or_default().insert(id.clone());
+            self.targets.insert(id.clone(), target);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Tumbling and sliding time windows over the change stream.
+//!
+//! [`TimeWindow`] places each folded record in every window its timestamp
+//! falls in — one for a tumbling window, `size / slide` for a sliding one —
+//! and keeps each open window's members as a ZSet. `advance` moves the clock:
+//! windows that end at or before it close, their members are retracted, and
+//! records with no open window left are forgotten, so state stays bounded by
+//! the records of the open windows. "Events in the last 5 minutes" is the
+//! earliest open window of a 5-minute window sliding by 1 minute.
+//!
+//! ```rust,ignore
+//! let (size, slide) = (Duration::from_secs(300), Duration::from_secs(60));
+//! let mut recent = TimeWindow::sliding("at", size, slide);
+//! let result = db.apply_batch(mutations)?;
+//! db.fold_batch("events", &result, &mut recent)?;
+//! for closed in recent.advance(now_millis) {
+//!     publish(closed.start, closed.ids.len());
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use smol_str::SmolStr;
+
+use super::aggregate::RecordOperator;
+use super::types::{FastMap, ZSet};
+use super::zset::add_weight;
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+
+/// Records grouped into time windows by a Unix-millisecond field.
+#[derive(Debug, Clone)]
+pub struct TimeWindow {
+    field: SmolStr,
+    size: u64,
+    slide: u64,
+    /// Timestamp per record with an open window.
+    times: FastMap<SmolStr, u64>,
+    /// Open windows with members, by start.
+    open: BTreeMap<u64, ZSet>,
+    /// Latest time passed to `advance`; windows ending at or before it are closed.
+    now: u64,
+    /// Membership changes per window start since the last `changes`.
+    changed: BTreeMap<u64, ZSet>,
+}
+
+/// A window closed by `TimeWindow::advance`, with its final members.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedWindow {
+    pub start: u64,
+    /// Exclusive.
+    pub end: u64,
+    pub ids: ZSet,
+}
+
+impl TimeWindow {
+    /// Consecutive, non-overlapping windows of `size`.
+    pub fn tumbling(field: &str, size: Duration) -> Self {
+        Self::sliding(field, size, size)
+    }
+
+    /// Windows of `size` starting every `slide`; a record falls in each one
+    /// covering its timestamp. Durations are taken in whole milliseconds,
+    /// at least one.
+    pub fn sliding(field: &str, size: Duration, slide: Duration) -> Self {
+        let millis = |d: Duration| (d.as_millis() as u64).max(1);
+        Self {
+            field: SmolStr::new(field),
+            size: millis(size),
+            slide: millis(slide),
+            times: FastMap::default(),
+            open: BTreeMap::new(),
+            now: 0,
+            changed: BTreeMap::new(),
+        }
+    }
+
+    /// Place record `id` by its timestamp, replacing its previous placement.
+    /// A record without a numeric timestamp, or whose windows have all
+    /// closed, is removed.
+    pub fn add(&mut self, id: &str, record: &impl SpookyReadable) {
+        self.remove(id);
+        let Some(at) = record.get_number_as_f64(&self.field) else {
+            return;
+        };
+        let at = at as u64;
+        let id = SmolStr::new(id);
+        let mut placed = false;
+        for start in self.starts(at).filter(|start| start + self.size > self.now) {
+            self.open.entry(start).or_default().insert(id.clone(), 1);
+            add_weight(self.changed.entry(start).or_default(), &id, 1);
+            placed = true;
+        }
+        if placed {
+            self.times.insert(id, at);
+        }
+    }
+
+    /// Retract record `id` from its open windows.
+    pub fn remove(&mut self, id: &str) {
+        let Some((id, at)) = self.times.remove_entry(id) else {
+            return;
+        };
+        for start in self.starts(at) {
+            let Some(members) = self.open.get_mut(&start) else {
+                continue;
+            };
+            if members.remove(&id).is_some() {
+                add_weight(self.changed.entry(start).or_default(), &id, -1);
+            }
+            if members.is_empty() {
+                self.open.remove(&start);
+            }
+        }
+    }
+
+    /// Close every window ending at or before `now` (Unix milliseconds),
+    /// oldest first. Their members are retracted in `changes`. The clock
+    /// never moves back.
+    pub fn advance(&mut self, now: u64) -> Vec<ClosedWindow> {
+        self.now = self.now.max(now);
+        let open = match self.now.checked_sub(self.size) {
+            Some(last_closed) => self.open.split_off(&(last_closed + 1)),
+            None => return Vec::new(),
+        };
+        let closed = std::mem::replace(&mut self.open, open);
+        let (size, slide, now) = (self.size, self.slide, self.now);
+        // A record's last window starts at the slide boundary below it.
+        self.times.retain(|_, at| *at - *at % slide + size > now);
+        closed
+            .into_iter()
+            .map(|(start, ids)| {
+                let changed = self.changed.entry(start).or_default();
+                for id in ids.keys() {
+                    add_weight(changed, id, -1);
+                }
+                ClosedWindow {
+                    start,
+                    end: start + size,
+                    ids,
+                }
+            })
+            .collect()
+    }
+
+    /// Members of the open window starting at `start`.
+    pub fn window(&self, start: u64) -> Option<&ZSet> {
+        self.open.get(&start)
+    }
+
+    /// Open windows with members as `(start, end, ids)`, oldest first.
+    pub fn windows(&self) -> impl Iterator<Item = (u64, u64, &ZSet)> {
+        self.open.iter().map(|(&start, ids)| (start, start + self.size, ids))
+    }
+
+    /// Members of the oldest open window — with a sliding window, the
+    /// records of the last `size` as of `advance`.
+    pub fn oldest(&self) -> Option<&ZSet> {
+        self.open.values().next()
+    }
+
+    /// Number of records in at least one open window.
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    /// `true` if no window is open.
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// Membership changes per window start since the last call: `+1` for
+    /// ids placed in a window, `-1` for ids retracted from one, by a delete,
+    /// a new timestamp or the window closing.
+    pub fn changes(&mut self) -> BTreeMap<u64, ZSet> {
+        let mut changes = std::mem::take(&mut self.changed);
+        changes.retain(|_, delta| !delta.is_empty());
+        changes
+    }
+
+    /// Starts of the windows covering `at`, latest first.
+    fn starts(&self, at: u64) -> impl Iterator<Item = u64> + use<> {
+        let (size, slide) = (self.size, self.slide);
+        let last = at - at % slide;
+        (0..)
+            .map_while(move |k: u64| last.checked_sub(k * slide))
+            .take_while(move |start| start + size > at)
+    }
+}
+
+impl RecordOperator for TimeWindow {
+    fn add(&mut self, id: &str, record: &SpookyRecord<'_>) {
+        TimeWindow::add(self, id, record);
+    }
+
+    fn remove(&mut self, id: &str) {
+        TimeWindow::remove(self, id);
+    }
+}
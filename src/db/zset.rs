@@ -0,0 +1,233 @@
+//! ZSet algebra: the operators views are built from.
+//!
+//! A [`ZSet`] maps a key to an `i64` weight, and a delta is just a ZSet of
+//! weight changes, so every operator here applies to either. Results never
+//! hold zero weights. Each operator that builds a map has an `_into` or
+//! `_in_place` variant that writes into one the caller already owns, so a
+//! view can keep its output map across ticks instead of allocating a new one
+//! per delta.
+//!
+//! ```rust,ignore
+//! let mut output = ZSet::default();
+//! for result in batches {
+//!     let delta = &result.membership_deltas["orders"];
+//!     zset::union_into(&mut output, &zset::filter(delta, |id| id.starts_with("eu-")));
+//! }
+//! ```
+
+use std::hash::Hash;
+
+use super::types::{FastMap, RowKey, Weight, ZSet};
+
+/// Add `weight` to `key`'s entry, dropping the entry at zero.
+pub fn add_weight(zset: &mut ZSet, key: &RowKey, weight: Weight) {
+    if weight == 0 {
+        return;
+    }
+    let entry = zset.entry(key.clone()).or_insert(0);
+    *entry += weight;
+    if *entry == 0 {
+        zset.remove(key);
+    }
+}
+
+/// `a + b`: weights summed per key.
+pub fn union(a: &ZSet, b: &ZSet) -> ZSet {
+    let mut out = a.clone();
+    union_into(&mut out, b);
+    out
+}
+
+/// `acc += other`.
+pub fn union_into(acc: &mut ZSet, other: &ZSet) {
+    for (key, &weight) in other {
+        add_weight(acc, key, weight);
+    }
+}
+
+/// `a - b`: applied to two states, the delta that turns `b` into `a`.
+pub fn difference(a: &ZSet, b: &ZSet) -> ZSet {
+    let mut out = a.clone();
+    difference_into(&mut out, b);
+    out
+}
+
+/// `acc -= other`.
+pub fn difference_into(acc: &mut ZSet, other: &ZSet) {
+    for (key, &weight) in other {
+        add_weight(acc, key, -weight);
+    }
+}
+
+/// Equi-join: every left key and right key whose `left_key` and
+/// `right_key` agree produce `output(left, right)` with the product of
+/// their weights. Keys mapped to `None` join nothing.
+///
+/// Joining a delta against the other side's state gives that delta's
+/// contribution; the full change of `a ⋈ b` is `Δa ⋈ b + a ⋈ Δb + Δa ⋈ Δb`.
+pub fn join_by_key<K: Eq + Hash>(
+    left: &ZSet,
+    right: &ZSet,
+    left_key: impl Fn(&RowKey) -> Option<K>,
+    right_key: impl Fn(&RowKey) -> Option<K>,
+    output: impl Fn(&RowKey, &RowKey) -> RowKey,
+) -> ZSet {
+    let mut out = ZSet::default();
+    join_by_key_into(&mut out, left, right, left_key, right_key, output);
+    out
+}
+
+/// [`join_by_key`], adding its result into `out`.
+pub fn join_by_key_into<K: Eq + Hash>(
+    out: &mut ZSet,
+    left: &ZSet,
+    right: &ZSet,
+    left_key: impl Fn(&RowKey) -> Option<K>,
+    right_key: impl Fn(&RowKey) -> Option<K>,
+    output: impl Fn(&RowKey, &RowKey) -> RowKey,
+) {
+    let mut index: FastMap<K, Vec<(&RowKey, Weight)>> = FastMap::default();
+    for (key, &weight) in right {
+        if let Some(k) = right_key(key) {
+            index.entry(k).or_default().push((key, weight));
+        }
+    }
+    for (key, &weight) in left {
+        let Some(matches) = left_key(key).and_then(|k| index.get(&k)) else {
+            continue;
+        };
+        for &(other, other_weight) in matches {
+            add_weight(out, &output(key, other), weight * other_weight);
+        }
+    }
+}
+
+/// Re-key every entry with `f`; keys that collide have their weights summed.
+pub fn map_keys(zset: &ZSet, f: impl Fn(&RowKey) -> RowKey) -> ZSet {
+    let mut out = ZSet::default();
+    map_keys_into(&mut out, zset, f);
+    out
+}
+
+/// [`map_keys`], adding its result into `out`.
+pub fn map_keys_into(out: &mut ZSet, zset: &ZSet, f: impl Fn(&RowKey) -> RowKey) {
+    for (key, &weight) in zset {
+        add_weight(out, &f(key), weight);
+    }
+}
+
+/// The entries whose key satisfies `keep`.
+pub fn filter(zset: &ZSet, keep: impl Fn(&RowKey) -> bool) -> ZSet {
+    zset.iter()
+        .filter(|(key, _)| keep(key))
+        .map(|(key, &weight)| (key.clone(), weight))
+        .collect()
+}
+
+/// [`filter`] without a new map.
+pub fn filter_in_place(zset: &mut ZSet, keep: impl Fn(&RowKey) -> bool) {
+    zset.retain(|key, _| keep(key));
+}
+
+/// Set semantics: weight 1 for every key with a positive weight.
+pub fn distinct(zset: &ZSet) -> ZSet {
+    zset.iter()
+        .filter(|(_, weight)| **weight > 0)
+        .map(|(key, _)| (key.clone(), 1))
+        .collect()
+}
+
+/// [`distinct`] without a new map.
+pub fn distinct_in_place(zset: &mut ZSet) {
+    zset.retain(|_, weight| {
+        let keep = *weight > 0;
+        *weight = 1;
+        keep
+    });
+}
+
+/// Change of `distinct(state)` when `delta` is added to `state`, from the
+/// keys `delta` touches only. `state` is the input before the delta.
+pub fn distinct_delta(state: &ZSet, delta: &ZSet) -> ZSet {
+    let mut out = ZSet::default();
+    for (key, &weight) in delta {
+        let before = state.get(key).copied().unwrap_or(0);
+        let change = ((before + weight) > 0) as Weight - (before > 0) as Weight;
+        add_weight(&mut out, key, change);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smol_str::SmolStr;
+
+    fn zset(entries: &[(&str, Weight)]) -> ZSet {
+        entries.iter().map(|&(k, w)| (SmolStr::new(k), w)).collect()
+    }
+
+    fn sorted(zset: &ZSet) -> Vec<(&str, Weight)> {
+        let mut entries: Vec<_> = zset.iter().map(|(k, &w)| (k.as_str(), w)).collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn test_union_difference_distinct() {
+        let a = zset(&[("x", 1), ("y", 2)]);
+        let b = zset(&[("y", -2), ("z", 3)]);
+        assert_eq!(sorted(&union(&a, &b)), [("x", 1), ("z", 3)]);
+        assert_eq!(sorted(&difference(&a, &b)), [("x", 1), ("y", 4), ("z", -3)]);
+        let mut acc = a.clone();
+        union_into(&mut acc, &b);
+        difference_into(&mut acc, &b);
+        assert_eq!(acc, a);
+
+        let mixed = zset(&[("x", 3), ("y", -1)]);
+        assert_eq!(sorted(&distinct(&mixed)), [("x", 1)]);
+        let mut in_place = mixed.clone();
+        distinct_in_place(&mut in_place);
+        assert_eq!(in_place, distinct(&mixed));
+
+        // x: 3 → 0 leaves, y: -1 → 1 enters, z: absent → 2 enters.
+        let delta = zset(&[("x", -3), ("y", 2), ("z", 2)]);
+        let change = distinct_delta(&mixed, &delta);
+        assert_eq!(sorted(&change), [("x", -1), ("y", 1), ("z", 1)]);
+        let after = union(&mixed, &delta);
+        assert_eq!(union(&distinct(&mixed), &change), distinct(&after));
+    }
+
+    #[test]
+    fn test_join_map_filter() {
+        // orders "<user>:<n>" joined to users by the user part.
+        let orders = zset(&[("alice:1", 1), ("alice:2", 1), ("bob:1", 2), ("carol:1", 1)]);
+        let users = zset(&[("alice", 1), ("bob", -1)]);
+        let user_of = |id: &RowKey| id.split_once(':').map(|(user, _)| SmolStr::new(user));
+        let joined = join_by_key(
+            &orders,
+            &users,
+            user_of,
+            |id| Some(id.clone()),
+            |order, user| SmolStr::new(format!("{user}/{order}")),
+        );
+        assert_eq!(
+            sorted(&joined),
+            [("alice/alice:1", 1), ("alice/alice:2", 1), ("bob/bob:1", -2)]
+        );
+
+        let per_user = map_keys(&orders, |id| user_of(id).unwrap_or_default());
+        assert_eq!(sorted(&per_user), [("alice", 2), ("bob", 2), ("carol", 1)]);
+        let mut counts = per_user.clone();
+        map_keys_into(&mut counts, &zset(&[("bob:2", -2)]), |id| {
+            user_of(id).unwrap_or_default()
+        });
+        assert_eq!(sorted(&counts), [("alice", 2), ("carol", 1)]);
+
+        let alice = filter(&orders, |id| id.starts_with("alice"));
+        assert_eq!(sorted(&alice), [("alice:1", 1), ("alice:2", 1)]);
+        let mut in_place = orders.clone();
+        filter_in_place(&mut in_place, |id| id.starts_with("alice"));
+        assert_eq!(in_place, alice);
+    }
+}
@@ -0,0 +1,279 @@
+//! Per-table ZSets, loaded on first access.
+//!
+//! META_TABLE holds every table's [`TableStats`] (RECORDS_TABLE entry count
+//! and byte total) and is rewritten in each commit that changes them, so
+//! opening a database reads only the stats — O(tables), not O(records). A
+//! table's ZSet is rebuilt by a range scan of its own keys the first time
+//! something needs its ids; until then `len` answers from the persisted
+//! count. Files written before META_TABLE existed get one full scan on open,
+//! which also fills it in.
+//!
+//! Writers load every table they touch before opening their transaction, so
+//! an unloaded table has not changed since open and its disk state is exact.
+
+use std::sync::OnceLock;
+
+use redb::{Database as RedbDatabase, ReadableDatabase, ReadableTable, WriteTransaction};
+use smol_str::SmolStr;
+
+use super::db::{META_TABLE, RECORDS_TABLE, key_range, split_key, table_from_key, table_key};
+use super::types::{FastMap, SpookyDbError, TableStats, ZSet};
+
+/// META_TABLE key present once every table has an entry. Table keys escape
+/// ':' (see `table_key`), so it cannot collide with one.
+const COMPLETE: &str = ":complete";
+
+#[derive(Default)]
+pub(super) struct ZSets {
+    tables: FastMap<SmolStr, Slot>,
+}
+
+struct Slot {
+    /// As persisted in META_TABLE.
+    stats: TableStats,
+    zset: OnceLock<ZSet>,
+}
+
+impl Slot {
+    /// A loaded, empty table.
+    fn empty() -> Self {
+        Self {
+            stats: TableStats::default(),
+            zset: OnceLock::from(ZSet::default()),
+        }
+    }
+}
+
+/// Net change to one table's [`TableStats`] within a write transaction.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct TableDelta {
+    records: i64,
+    bytes: i64,
+}
+
+impl TableDelta {
+    /// A RECORDS_TABLE insert of `new` bytes over `old` (if any).
+    pub(super) fn insert(&mut self, old: Option<usize>, new: usize) {
+        self.records += i64::from(old.is_none());
+        self.bytes += new as i64 - old.unwrap_or(0) as i64;
+    }
+
+    /// A RECORDS_TABLE remove; `old` is the removed value's length.
+    pub(super) fn remove(&mut self, old: Option<usize>) {
+        if let Some(old) = old {
+            self.records -= 1;
+            self.bytes -= old as i64;
+        }
+    }
+}
+
+impl ZSets {
+    /// Read the table stats, or scan RECORDS_TABLE once if META_TABLE is
+    /// incomplete.
+    pub(super) fn open(db: &RedbDatabase) -> Result<Self, SpookyDbError> {
+        let read_txn = db.begin_read()?;
+        let meta = read_txn.open_table(META_TABLE)?;
+        if meta.get(COMPLETE)?.is_some() {
+            let mut tables = FastMap::default();
+            for entry in meta.iter()? {
+                let (table, value) = entry?;
+                if table.value() != COMPLETE {
+                    let (records, bytes) = value.value();
+                    let slot = Slot {
+                        stats: TableStats { records, bytes },
+                        zset: OnceLock::new(),
+                    };
+                    tables.insert(SmolStr::new(table_from_key(table.value())), slot);
+                }
+            }
+            return Ok(Self { tables });
+        }
+
+        let mut tables: FastMap<SmolStr, Slot> = FastMap::default();
+        let records = read_txn.open_table(RECORDS_TABLE)?;
+        for entry in records.iter()? {
+            let (key, value) = entry?;
+            if let Some((table, id)) = split_key(key.value()) {
+                let slot = tables
+                    .entry(SmolStr::new(table))
+                    .or_insert_with(Slot::empty);
+                slot.stats.records += 1;
+                slot.stats.bytes += value.value().len() as u64;
+                let zset = slot.zset.get_mut().expect("scanned slots are loaded");
+                zset.insert(SmolStr::new(id), 1);
+            }
+        }
+        drop((meta, records, read_txn));
+
+        let write_txn = db.begin_write()?;
+        {
+            let mut meta = write_txn.open_table(META_TABLE)?;
+            for (table, slot) in &tables {
+                let TableStats { records, bytes } = slot.stats;
+                meta.insert(&*table_key(table), (records, bytes))?;
+            }
+            meta.insert(COMPLETE, (0, 0))?;
+        }
+        write_txn.commit()?;
+        Ok(Self { tables })
+    }
+
+    /// The table's ZSet, loading it on first access. `None` if the table is
+    /// unknown.
+    pub(super) fn get(
+        &self,
+        db: &RedbDatabase,
+        table: &str,
+    ) -> Result<Option<&ZSet>, SpookyDbError> {
+        let Some(slot) = self.tables.get(table) else {
+            return Ok(None);
+        };
+        if let Some(zset) = slot.zset.get() {
+            return Ok(Some(zset));
+        }
+        let zset = load(db, table)?;
+        Ok(Some(slot.zset.get_or_init(|| zset)))
+    }
+
+    /// Make sure `table` has a loaded ZSet, creating an empty one if the
+    /// table is unknown. Writers call this before their transaction.
+    pub(super) fn load(&mut self, db: &RedbDatabase, table: &str) -> Result<(), SpookyDbError> {
+        match self.tables.get(table) {
+            Some(_) => self.get(db, table).map(drop),
+            None => {
+                self.tables.insert(SmolStr::new(table), Slot::empty());
+                Ok(())
+            }
+        }
+    }
+
+    /// Load every table that is not loaded yet.
+    pub(super) fn load_all(&self, db: &RedbDatabase) -> Result<(), SpookyDbError> {
+        for table in self.tables.keys() {
+            self.get(db, table)?;
+        }
+        Ok(())
+    }
+
+    /// The ZSet of a table passed to `load`.
+    ///
+    /// # Panics
+    /// If the table was not loaded.
+    pub(super) fn loaded_mut(&mut self, table: &str) -> &mut ZSet {
+        self.tables
+            .get_mut(table)
+            .and_then(|slot| slot.zset.get_mut())
+            .expect("table loaded before its write")
+    }
+
+    /// The table's ZSet if already loaded; never does I/O.
+    pub(super) fn peek(&self, table: &str) -> Option<&ZSet> {
+        self.tables.get(table)?.zset.get()
+    }
+
+    /// Members of a loaded table, else its persisted record count.
+    pub(super) fn len(&self, table: &str) -> usize {
+        self.tables
+            .get(table)
+            .map_or(0, |slot| match slot.zset.get() {
+                Some(zset) => zset.len(),
+                None => slot.stats.records as usize,
+            })
+    }
+
+    /// Persisted record count and size.
+    pub(super) fn stats(&self, table: &str) -> TableStats {
+        self.tables
+            .get(table)
+            .map_or_else(TableStats::default, |slot| slot.stats)
+    }
+
+    pub(super) fn names(&self) -> impl Iterator<Item = &SmolStr> {
+        self.tables.keys()
+    }
+
+    /// Tables with a loaded ZSet.
+    pub(super) fn iter_loaded(&self) -> impl Iterator<Item = (&SmolStr, &ZSet)> {
+        self.tables
+            .iter()
+            .filter_map(|(table, slot)| Some((table, slot.zset.get()?)))
+    }
+
+    /// Write the stats each table will have once `txn` commits. Returns them
+    /// for `apply_stats`.
+    pub(super) fn stage_stats(
+        &self,
+        txn: &WriteTransaction,
+        deltas: FastMap<SmolStr, TableDelta>,
+    ) -> Result<Vec<(SmolStr, TableStats)>, SpookyDbError> {
+        let stats: Vec<_> = deltas
+            .into_iter()
+            .filter(|(_, d)| d.records != 0 || d.bytes != 0)
+            .map(|(table, delta)| {
+                let old = self.stats(&table);
+                let new = TableStats {
+                    records: old.records.saturating_add_signed(delta.records),
+                    bytes: old.bytes.saturating_add_signed(delta.bytes),
+                };
+                (table, new)
+            })
+            .collect();
+        write_stats(txn, &stats)?;
+        Ok(stats)
+    }
+
+    /// Forget a table whose records were all removed in a committed
+    /// transaction that also dropped its META_TABLE entry.
+    pub(super) fn remove(&mut self, table: &str) {
+        self.tables.remove(table);
+    }
+
+    /// Move `old`'s slot to `new` after a committed rename. `new` must not
+    /// hold records.
+    pub(super) fn rename(&mut self, old: &str, new: &str) {
+        if let Some(slot) = self.tables.remove(old) {
+            self.tables.insert(SmolStr::new(new), slot);
+        }
+    }
+
+    /// Adopt stats staged in a transaction that has committed.
+    pub(super) fn apply_stats(&mut self, stats: Vec<(SmolStr, TableStats)>) {
+        for (table, new) in stats {
+            let slot = self.tables.entry(table).or_insert_with(|| Slot {
+                stats: TableStats::default(),
+                zset: OnceLock::new(),
+            });
+            slot.stats = new;
+        }
+    }
+}
+
+/// Persist absolute table stats; an empty table loses its entry.
+pub(super) fn write_stats(
+    txn: &WriteTransaction,
+    stats: &[(SmolStr, TableStats)],
+) -> Result<(), SpookyDbError> {
+    let mut meta = txn.open_table(META_TABLE)?;
+    for (table, TableStats { records, bytes }) in stats {
+        let key = table_key(table);
+        if *records == 0 {
+            meta.remove(&*key)?;
+        } else {
+            meta.insert(&*key, (*records, *bytes))?;
+        }
+    }
+    Ok(())
+}
+
+/// One table's ZSet from a range scan of its RECORDS_TABLE keys.
+fn load(db: &RedbDatabase, table: &str) -> Result<ZSet, SpookyDbError> {
+    let read_txn = db.begin_read()?;
+    let records = read_txn.open_table(RECORDS_TABLE)?;
+    let (lo, hi) = key_range(table);
+    let mut zset = ZSet::default();
+    for entry in records.range::<&str>(lo.as_str()..hi.as_str())? {
+        let (key, _) = entry?;
+        zset.insert(SmolStr::new(&key.value()[lo.len()..]), 1);
+    }
+    Ok(zset)
+}
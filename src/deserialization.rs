@@ -67,13 +67,13 @@ impl RecordDeserialize for SpookyValue {
 
     #[inline]
     fn from_cbor_bytes(data: &[u8]) -> Option<Self> {
-        let cbor_val: cbor4ii::core::Value = cbor4ii::serde::from_slice(data).ok()?;
-        Some(SpookyValue::from(cbor_val))
+        crate::cbor::decode_slice(data).ok()
     }
 }
 
 // ─── RecordDeserialize for serde_json::Value ────────────────────────────────
 
+#[cfg(feature = "json")]
 impl RecordDeserialize for serde_json::Value {
     #[inline]
     fn from_null() -> Self {
@@ -109,7 +109,11 @@ impl RecordDeserialize for serde_json::Value {
 
     #[inline]
     fn from_cbor_bytes(data: &[u8]) -> Option<Self> {
-        cbor4ii::serde::from_slice(data).ok()
+        // serde rejects CBOR tags; a stored `Tagged` value goes through the
+        // bridge instead, which keeps its payload.
+        cbor4ii::serde::from_slice(data)
+            .ok()
+            .or_else(|| crate::cbor::decode_slice(data).ok()?.try_into().ok())
     }
 }
 
@@ -148,7 +152,133 @@ impl RecordDeserialize for cbor4ii::core::Value {
 
     #[inline]
     fn from_cbor_bytes(data: &[u8]) -> Option<Self> {
-        cbor4ii::serde::from_slice(data).ok()
+        // The core decoder, unlike serde, keeps tags.
+        use cbor4ii::core::dec::Decode;
+        Self::decode(&mut cbor4ii::core::utils::SliceReader::new(data)).ok()
+    }
+}
+
+// ─── RecordDeserialize for ciborium::Value ──────────────────────────────────
+
+#[cfg(feature = "ciborium")]
+impl RecordDeserialize for ciborium::Value {
+    #[inline]
+    fn from_null() -> Self {
+        ciborium::Value::Null
+    }
+
+    #[inline]
+    fn from_bool(b: bool) -> Self {
+        ciborium::Value::Bool(b)
+    }
+
+    #[inline]
+    fn from_i64(v: i64) -> Self {
+        ciborium::Value::Integer(v.into())
+    }
+
+    #[inline]
+    fn from_u64(v: u64) -> Self {
+        ciborium::Value::Integer(v.into())
+    }
+
+    #[inline]
+    fn from_f64(v: f64) -> Self {
+        ciborium::Value::Float(v)
+    }
+
+    #[inline]
+    fn from_str(s: &str) -> Self {
+        ciborium::Value::Text(s.to_string())
+    }
+
+    #[inline]
+    fn from_cbor_bytes(data: &[u8]) -> Option<Self> {
+        ciborium::from_reader(data).ok()
+    }
+}
+
+// ─── RecordDeserialize for rmpv::Value ──────────────────────────────────────
+
+#[cfg(feature = "msgpack")]
+impl RecordDeserialize for rmpv::Value {
+    #[inline]
+    fn from_null() -> Self {
+        rmpv::Value::Nil
+    }
+
+    #[inline]
+    fn from_bool(b: bool) -> Self {
+        rmpv::Value::Boolean(b)
+    }
+
+    #[inline]
+    fn from_i64(v: i64) -> Self {
+        rmpv::Value::from(v)
+    }
+
+    #[inline]
+    fn from_u64(v: u64) -> Self {
+        rmpv::Value::from(v)
+    }
+
+    #[inline]
+    fn from_f64(v: f64) -> Self {
+        rmpv::Value::F64(v)
+    }
+
+    #[inline]
+    fn from_str(s: &str) -> Self {
+        rmpv::Value::from(s)
+    }
+
+    #[inline]
+    fn from_cbor_bytes(data: &[u8]) -> Option<Self> {
+        // serde rejects CBOR tags; a stored `Tagged` value goes through the
+        // bridge instead, which keeps its payload.
+        cbor4ii::serde::from_slice(data)
+            .ok()
+            .or_else(|| crate::cbor::decode_slice(data).ok().map(rmpv::Value::from))
+    }
+}
+
+// ─── RecordDeserialize for bson::Bson ───────────────────────────────────────
+
+#[cfg(feature = "bson")]
+impl RecordDeserialize for bson::Bson {
+    #[inline]
+    fn from_null() -> Self {
+        bson::Bson::Null
+    }
+
+    #[inline]
+    fn from_bool(b: bool) -> Self {
+        bson::Bson::Boolean(b)
+    }
+
+    #[inline]
+    fn from_i64(v: i64) -> Self {
+        bson::Bson::Int64(v)
+    }
+
+    #[inline]
+    fn from_u64(v: u64) -> Self {
+        bson::Bson::from(SpookyValue::from(v))
+    }
+
+    #[inline]
+    fn from_f64(v: f64) -> Self {
+        bson::Bson::Double(v)
+    }
+
+    #[inline]
+    fn from_str(s: &str) -> Self {
+        bson::Bson::String(s.to_string())
+    }
+
+    #[inline]
+    fn from_cbor_bytes(data: &[u8]) -> Option<Self> {
+        crate::cbor::decode_slice(data).ok().map(bson::Bson::from)
     }
 }
 
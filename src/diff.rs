@@ -0,0 +1,270 @@
+use crate::spooky_value::{FastMap, SpookyNumber, SpookyValue};
+use smol_str::SmolStr;
+
+// ─── Structural Diff ────────────────────────────────────────────────────────
+//
+// Paths are JSON Pointers (RFC 6901) relative to the diffed root, so a
+// `ValueDiff` maps 1:1 onto a JSON Patch document.
+//
+// Numbers are compared strictly: I64(1) → F64(1.0) is reported as a change,
+// because the two are stored under different record type tags.
+
+/// One path-level difference between two values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffOp {
+    /// Present only in the new value.
+    Added { path: String, value: SpookyValue },
+    /// Present only in the old value.
+    Removed { path: String, old: SpookyValue },
+    /// Present in both with different contents (or a different type).
+    Changed {
+        path: String,
+        old: SpookyValue,
+        new: SpookyValue,
+    },
+}
+
+impl DiffOp {
+    /// JSON Pointer the operation applies to.
+    #[inline]
+    pub fn path(&self) -> &str {
+        match self {
+            DiffOp::Added { path, .. }
+            | DiffOp::Removed { path, .. }
+            | DiffOp::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Ordered list of differences produced by [`SpookyValue::diff`].
+///
+/// Array removals are emitted from the highest index down, so applying the
+/// ops in order (e.g. as a JSON Patch) never shifts a pending index.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValueDiff {
+    pub ops: Vec<DiffOp>,
+}
+
+impl ValueDiff {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, DiffOp> {
+        self.ops.iter()
+    }
+
+    /// Convert to an RFC 6902 JSON Patch document (an array of operation objects).
+    pub fn to_json_patch(&self) -> SpookyValue {
+        SpookyValue::Array(
+            self.ops
+                .iter()
+                .map(|op| {
+                    let mut m = FastMap::new();
+                    let (name, path, value) = match op {
+                        DiffOp::Added { path, value } => ("add", path, Some(value)),
+                        DiffOp::Removed { path, .. } => ("remove", path, None),
+                        DiffOp::Changed { path, new, .. } => ("replace", path, Some(new)),
+                    };
+                    m.insert(SmolStr::new_static("op"), SpookyValue::from(name));
+                    m.insert(
+                        SmolStr::new_static("path"),
+                        SpookyValue::from(path.as_str()),
+                    );
+                    if let Some(v) = value {
+                        m.insert(SmolStr::new_static("value"), v.clone());
+                    }
+                    SpookyValue::Object(m)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<'a> IntoIterator for &'a ValueDiff {
+    type Item = &'a DiffOp;
+    type IntoIter = std::slice::Iter<'a, DiffOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.iter()
+    }
+}
+
+impl SpookyValue {
+    /// Path-based structural diff from `self` (old) to `other` (new).
+    pub fn diff(&self, other: &SpookyValue) -> ValueDiff {
+        let mut ops = Vec::new();
+        let mut path = String::new();
+        diff_into(self, other, &mut path, &mut ops);
+        ValueDiff { ops }
+    }
+}
+
+fn diff_into(old: &SpookyValue, new: &SpookyValue, path: &mut String, ops: &mut Vec<DiffOp>) {
+    match (old, new) {
+        (SpookyValue::Object(a), SpookyValue::Object(b)) => {
+            for (key, old_v) in a {
+                let len = path.len();
+                push_token(path, key);
+                match b.get(key) {
+                    Some(new_v) => diff_into(old_v, new_v, path, ops),
+                    None => ops.push(DiffOp::Removed {
+                        path: path.clone(),
+                        old: old_v.clone(),
+                    }),
+                }
+                path.truncate(len);
+            }
+            for (key, new_v) in b {
+                if !a.contains_key(key) {
+                    let len = path.len();
+                    push_token(path, key);
+                    ops.push(DiffOp::Added {
+                        path: path.clone(),
+                        value: new_v.clone(),
+                    });
+                    path.truncate(len);
+                }
+            }
+        }
+        (SpookyValue::Array(a), SpookyValue::Array(b)) => {
+            let common = a.len().min(b.len());
+            for i in 0..common {
+                let len = path.len();
+                push_index(path, i);
+                diff_into(&a[i], &b[i], path, ops);
+                path.truncate(len);
+            }
+            for (i, new_v) in b.iter().enumerate().skip(common) {
+                let len = path.len();
+                push_index(path, i);
+                ops.push(DiffOp::Added {
+                    path: path.clone(),
+                    value: new_v.clone(),
+                });
+                path.truncate(len);
+            }
+            for i in (common..a.len()).rev() {
+                let len = path.len();
+                push_index(path, i);
+                ops.push(DiffOp::Removed {
+                    path: path.clone(),
+                    old: a[i].clone(),
+                });
+                path.truncate(len);
+            }
+        }
+        _ => {
+            if !strict_eq(old, new) {
+                ops.push(DiffOp::Changed {
+                    path: path.clone(),
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Leaf equality that also distinguishes numeric variants.
+#[inline]
+fn strict_eq(a: &SpookyValue, b: &SpookyValue) -> bool {
+    match (a, b) {
+        (SpookyValue::Number(x), SpookyValue::Number(y)) => match (x, y) {
+            (SpookyNumber::I64(x), SpookyNumber::I64(y)) => x == y,
+            (SpookyNumber::U64(x), SpookyNumber::U64(y)) => x == y,
+            (SpookyNumber::F64(x), SpookyNumber::F64(y)) => x.to_bits() == y.to_bits(),
+            _ => false,
+        },
+        _ => a == b,
+    }
+}
+
+/// Append `/token` with RFC 6901 escaping.
+#[inline]
+fn push_token(path: &mut String, token: &str) {
+    path.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            c => path.push(c),
+        }
+    }
+}
+
+#[inline]
+fn push_index(path: &mut String, i: usize) {
+    use std::fmt::Write;
+    write!(path, "/{i}").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obj(text: &str) -> SpookyValue {
+        SpookyValue::from_json_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_identical_values_produce_empty_diff() {
+        let a = obj(r#"{"a":1,"b":{"c":[1,2]}}"#);
+        assert!(a.diff(&a).is_empty());
+    }
+
+    #[test]
+    fn test_object_add_remove_change() {
+        let a = obj(r#"{"keep":1,"gone":true,"nested":{"x":"old"}}"#);
+        let b = obj(r#"{"keep":1,"new":null,"nested":{"x":"new"}}"#);
+        let d = a.diff(&b);
+        assert_eq!(d.len(), 3);
+        assert!(d.ops.contains(&DiffOp::Removed {
+            path: "/gone".into(),
+            old: SpookyValue::Bool(true)
+        }));
+        assert!(d.ops.contains(&DiffOp::Added {
+            path: "/new".into(),
+            value: SpookyValue::Null
+        }));
+        assert!(d.ops.contains(&DiffOp::Changed {
+            path: "/nested/x".into(),
+            old: SpookyValue::from("old"),
+            new: SpookyValue::from("new")
+        }));
+    }
+
+    #[test]
+    fn test_array_removals_descend() {
+        let a = obj("[1,2,3,4]");
+        let b = obj("[1,9]");
+        let d = a.diff(&b);
+        let paths: Vec<&str> = d.iter().map(|op| op.path()).collect();
+        assert_eq!(paths, vec!["/1", "/3", "/2"]);
+    }
+
+    #[test]
+    fn test_numeric_variant_change_detected() {
+        let a = SpookyValue::from(1i64);
+        let b = SpookyValue::from(1.0f64);
+        assert_eq!(a.diff(&b).len(), 1);
+    }
+
+    #[test]
+    fn test_json_patch_shape() {
+        let a = obj(r#"{"a~b":1,"c":[1]}"#);
+        let b = obj(r#"{"a~b":2,"c":[]}"#);
+        let patch = a.diff(&b).to_json_patch();
+        assert_eq!(
+            patch.to_json_string(),
+            r#"[{"op":"replace","path":"/a~0b","value":2},{"op":"remove","path":"/c/0"}]"#
+        );
+    }
+}
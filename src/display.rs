@@ -0,0 +1,223 @@
+use crate::spooky_value::{SpookyNumber, SpookyValue};
+use std::fmt::{self, Write};
+
+// ─── Display ────────────────────────────────────────────────────────────────
+//
+// SurrealQL-flavoured text for logs and error contexts:
+//
+//   { active: true, name: 'Ada', score: 9.5f, tags: ['a', 'b'], x: NULL }
+//
+// Strings are single-quoted, floats carry an `f` suffix so they stay
+// distinguishable from integers, and keys are only quoted when they are not
+// plain identifiers. `{:#}` (or `.pretty()`) switches to the indented form.
+
+/// Indentation unit for the pretty form.
+const INDENT: &str = "    ";
+
+impl fmt::Display for SpookyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self, f.alternate(), 0)
+    }
+}
+
+impl fmt::Display for SpookyNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SpookyNumber::I64(i) => write!(f, "{i}"),
+            SpookyNumber::U64(u) => write!(f, "{u}"),
+            SpookyNumber::F64(v) if v.is_nan() => f.write_str("NaN"),
+            SpookyNumber::F64(v) if v.is_infinite() => {
+                f.write_str(if v > 0.0 { "Infinity" } else { "-Infinity" })
+            }
+            SpookyNumber::F64(v) => write!(f, "{v}f"),
+        }
+    }
+}
+
+/// Display adapter returned by [`SpookyValue::pretty`].
+#[derive(Debug, Clone, Copy)]
+pub struct Pretty<'a>(&'a SpookyValue);
+
+impl fmt::Display for Pretty<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self.0, true, 0)
+    }
+}
+
+impl SpookyValue {
+    /// Multi-line, indented form of the `Display` output. Same as `{:#}`.
+    /// Returns a lightweight adapter, so nothing is allocated until it is formatted.
+    #[inline]
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty(self)
+    }
+}
+
+fn write_value(
+    f: &mut fmt::Formatter<'_>,
+    value: &SpookyValue,
+    pretty: bool,
+    level: usize,
+) -> fmt::Result {
+    match value {
+        SpookyValue::Null => f.write_str("NULL"),
+        SpookyValue::Bool(b) => f.write_str(if *b { "true" } else { "false" }),
+        SpookyValue::Number(n) => fmt::Display::fmt(n, f),
+        SpookyValue::Str(s) => write_quoted(f, s, '\''),
+        SpookyValue::Array(arr) => {
+            if arr.is_empty() {
+                return f.write_str("[]");
+            }
+            f.write_char('[')?;
+            for (i, v) in arr.iter().enumerate() {
+                separator(f, pretty, i, level + 1, false)?;
+                write_value(f, v, pretty, level + 1)?;
+            }
+            close(f, pretty, level, false)?;
+            f.write_char(']')
+        }
+        SpookyValue::Object(map) => {
+            if map.is_empty() {
+                return f.write_str("{}");
+            }
+            f.write_char('{')?;
+            for (i, (k, v)) in map.iter().enumerate() {
+                separator(f, pretty, i, level + 1, true)?;
+                write_key(f, k)?;
+                f.write_str(": ")?;
+                write_value(f, v, pretty, level + 1)?;
+            }
+            close(f, pretty, level, true)?;
+            f.write_char('}')
+        }
+        SpookyValue::Tagged(_, inner) => write_value(f, inner, pretty, level),
+    }
+}
+
+/// Before the i-th element: `,` between elements, then a newline + indent
+/// (pretty) or a space (compact; arrays get none before the first element).
+#[inline]
+fn separator(
+    f: &mut fmt::Formatter<'_>,
+    pretty: bool,
+    i: usize,
+    level: usize,
+    padded: bool,
+) -> fmt::Result {
+    if i > 0 {
+        f.write_char(',')?;
+    }
+    if pretty {
+        newline(f, level)
+    } else if i > 0 || padded {
+        f.write_char(' ')
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+fn close(f: &mut fmt::Formatter<'_>, pretty: bool, level: usize, padded: bool) -> fmt::Result {
+    if pretty {
+        newline(f, level)
+    } else if padded {
+        f.write_char(' ')
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+fn newline(f: &mut fmt::Formatter<'_>, level: usize) -> fmt::Result {
+    f.write_char('\n')?;
+    for _ in 0..level {
+        f.write_str(INDENT)?;
+    }
+    Ok(())
+}
+
+/// Bare if the key is a plain identifier (`[A-Za-z_][A-Za-z0-9_]*`), else double-quoted.
+fn write_key(f: &mut fmt::Formatter<'_>, key: &str) -> fmt::Result {
+    let mut chars = key.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        f.write_str(key)
+    } else {
+        write_quoted(f, key, '"')
+    }
+}
+
+fn write_quoted(f: &mut fmt::Formatter<'_>, s: &str, quote: char) -> fmt::Result {
+    f.write_char(quote)?;
+    for c in s.chars() {
+        match c {
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c == quote => {
+                f.write_char('\\')?;
+                f.write_char(c)?;
+            }
+            c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char(quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(text: &str) -> SpookyValue {
+        SpookyValue::from_json_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_compact_display() {
+        let val = v(r#"{"name":"Ada","age":42,"score":9.5,"tags":["a","b"],"x":null,"on":true}"#);
+        assert_eq!(
+            val.to_string(),
+            "{ age: 42, name: 'Ada', on: true, score: 9.5f, tags: ['a', 'b'], x: NULL }"
+        );
+        assert_eq!(v("[]").to_string(), "[]");
+        assert_eq!(v("{}").to_string(), "{}");
+    }
+
+    #[test]
+    fn test_key_and_string_escaping() {
+        let val = v(r#"{"first name":"it's","_ok1":"a\nb","1st":"\"q\""}"#);
+        assert_eq!(
+            val.to_string(),
+            r#"{ "1st": '"q"', _ok1: 'a\nb', "first name": 'it\'s' }"#
+        );
+    }
+
+    #[test]
+    fn test_number_forms() {
+        assert_eq!(SpookyNumber::F64(1.0).to_string(), "1f");
+        assert_eq!(SpookyNumber::F64(f64::NAN).to_string(), "NaN");
+        assert_eq!(
+            SpookyNumber::F64(f64::NEG_INFINITY).to_string(),
+            "-Infinity"
+        );
+        assert_eq!(
+            SpookyNumber::U64(u64::MAX).to_string(),
+            "18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn test_pretty_matches_alternate() {
+        let val = v(r#"{"a":[1,{"b":2}],"c":{}}"#);
+        let expected =
+            "{\n    a: [\n        1,\n        {\n            b: 2\n        }\n    ],\n    c: {}\n}";
+        assert_eq!(val.pretty().to_string(), expected);
+        assert_eq!(format!("{val:#}"), expected);
+    }
+}
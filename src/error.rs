@@ -19,6 +19,167 @@ pub enum RecordError {
     FieldExists,
     #[error("CBOR error: {0}")]
     CborError(String),
+    #[error("MessagePack error: {0}")]
+    MsgpackError(String),
+    #[error("BSON error: {0}")]
+    BsonError(String),
+    #[error(transparent)]
+    JsonError(#[from] JsonError),
     #[error("Unknown type tag: {0}")]
     UnknownTypeTag(u8),
+    /// A record could not be deserialized into a Rust type (see
+    /// `spooky_record::from_record`). `field` is the top-level field at fault.
+    #[error("cannot deserialize record{}: {message}", at_field(.field))]
+    Deserialize {
+        field: Option<smol_str::SmolStr>,
+        message: String,
+    },
+    /// A Rust value could not be serialized as a record (see
+    /// `spooky_record::to_record`). `field` is the top-level field at fault.
+    #[error("cannot serialize record{}: {message}", at_field(.field))]
+    Serialize {
+        field: Option<smol_str::SmolStr>,
+        message: String,
+    },
+}
+
+fn at_field(field: &Option<smol_str::SmolStr>) -> String {
+    match field {
+        Some(name) => format!(" field {name:?}"),
+        None => String::new(),
+    }
+}
+
+impl RecordError {
+    /// Attribute a `Deserialize` / `Serialize` error without a field to `name`.
+    pub(crate) fn at_field(self, name: &str) -> Self {
+        match self {
+            RecordError::Deserialize { field: None, message } => RecordError::Deserialize {
+                field: Some(name.into()),
+                message,
+            },
+            RecordError::Serialize { field: None, message } => RecordError::Serialize {
+                field: Some(name.into()),
+                message,
+            },
+            other => other,
+        }
+    }
+}
+
+impl serde::de::Error for RecordError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        RecordError::Deserialize {
+            field: None,
+            message: msg.to_string(),
+        }
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        RecordError::Deserialize {
+            field: Some(field.into()),
+            message: "missing field".into(),
+        }
+    }
+}
+
+impl serde::ser::Error for RecordError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        RecordError::Serialize {
+            field: None,
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Failure converting a `SpookyValue` into a foreign value type or a Rust type.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ConversionError {
+    #[error("non-finite float {0} has no JSON representation")]
+    NonFiniteFloat(f64),
+    /// The value has a different kind (see `SpookyValue::type_name`).
+    #[error("expected {expected}, found {found}")]
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A number that cannot be represented exactly in the target type
+    /// (out of range, or a float with a fractional part for an integer target).
+    #[error("number {value} is not representable as {target}")]
+    NotRepresentable { target: &'static str, value: String },
+    /// Conversion of an array element failed.
+    #[error("at index {index}: {source}")]
+    Element {
+        index: usize,
+        source: Box<ConversionError>,
+    },
+    /// Conversion of an object member failed.
+    #[error("at key {key:?}: {source}")]
+    Member {
+        key: smol_str::SmolStr,
+        source: Box<ConversionError>,
+    },
+}
+
+/// JSON text parse failure. `offset` is the byte position in the input.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("JSON parse error at byte {offset}: {message}")]
+pub struct JsonError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+/// One schema violation. `path` is a JSON Pointer into the validated value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub path: String,
+    pub kind: ViolationKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// A required field is absent.
+    Missing,
+    /// Present with the wrong type.
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// Not declared by a schema that denies unknown fields.
+    Unknown,
+    /// A nested record field could not be decoded for inspection.
+    Undecodable,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() { "<root>" } else { &self.path };
+        match &self.kind {
+            ViolationKind::Missing => write!(f, "{path}: missing required field"),
+            ViolationKind::TypeMismatch { expected, found } => {
+                write!(f, "{path}: expected {expected}, found {found}")
+            }
+            ViolationKind::Unknown => write!(f, "{path}: unknown field"),
+            ViolationKind::Undecodable => write!(f, "{path}: nested value could not be decoded"),
+        }
+    }
+}
+
+/// All violations found by `Schema::validate` / `Schema::validate_record`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct SchemaError {
+    pub violations: Vec<Violation>,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("schema validation failed: ")?;
+        for (i, v) in self.violations.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{v}")?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,500 @@
+use crate::error::{JsonError, RecordError};
+use crate::spooky_value::{FastMap, SpookyNumber, SpookyValue};
+use smol_str::SmolStr;
+use std::fmt::Write;
+
+// ─── JSON Text ──────────────────────────────────────────────────────────────
+//
+// Self-contained JSON reader/writer for SpookyValue. Does not touch serde_json,
+// so fixtures and debug dumps work with `default-features = false`.
+//
+// Number rules match the CBOR bridge: integers become I64 when they fit, U64
+// otherwise; anything with a fraction/exponent (or out of u64 range) is F64.
+
+/// Nesting limit for the parser — guards the recursive descent against stack overflow.
+const MAX_DEPTH: usize = 128;
+
+impl SpookyValue {
+    /// Parse JSON text into a SpookyValue.
+    pub fn from_json_str(input: &str) -> Result<SpookyValue, JsonError> {
+        let mut parser = Parser {
+            src: input.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        parser.skip_ws();
+        let value = parser.parse_value()?;
+        parser.skip_ws();
+        if parser.pos != parser.src.len() {
+            return Err(parser.err("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// Compact JSON text. NaN / ±Inf are written as `null`.
+    pub fn to_json_string(&self) -> String {
+        let mut out = String::new();
+        write_value(&mut out, self, None, 0);
+        out
+    }
+
+    /// Indented JSON text (two spaces per level). NaN / ±Inf are written as `null`.
+    pub fn to_json_pretty(&self) -> String {
+        let mut out = String::new();
+        write_value(&mut out, self, Some("  "), 0);
+        out
+    }
+}
+
+/// Parse a JSON object, handing each top-level member to `member` in
+/// document order without building the object map. For encoders that write
+/// fields as they come, like `serialization::from_json_str`.
+pub(crate) fn for_each_member(
+    input: &str,
+    member: impl FnMut(SmolStr, SpookyValue) -> Result<(), RecordError>,
+) -> Result<(), RecordError> {
+    let mut parser = Parser {
+        src: input.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_ws();
+    match parser.peek() {
+        Some(b'{') => parser.parse_members(member)?,
+        Some(_) => return Err(RecordError::SerializationNotObject),
+        None => return Err(parser.err("unexpected end of input").into()),
+    }
+    parser.skip_ws();
+    if parser.pos != parser.src.len() {
+        return Err(parser.err("trailing characters").into());
+    }
+    Ok(())
+}
+
+// ─── Writer ─────────────────────────────────────────────────────────────────
+
+fn write_value(out: &mut String, value: &SpookyValue, indent: Option<&str>, level: usize) {
+    match value {
+        SpookyValue::Null => out.push_str("null"),
+        SpookyValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        SpookyValue::Number(n) => write_number(out, *n),
+        SpookyValue::Str(s) => write_str(out, s),
+        SpookyValue::Array(arr) => {
+            if arr.is_empty() {
+                out.push_str("[]");
+                return;
+            }
+            out.push('[');
+            for (i, v) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, indent, level + 1);
+                write_value(out, v, indent, level + 1);
+            }
+            newline(out, indent, level);
+            out.push(']');
+        }
+        SpookyValue::Object(map) => {
+            if map.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+            out.push('{');
+            for (i, (k, v)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, indent, level + 1);
+                write_str(out, k);
+                out.push(':');
+                if indent.is_some() {
+                    out.push(' ');
+                }
+                write_value(out, v, indent, level + 1);
+            }
+            newline(out, indent, level);
+            out.push('}');
+        }
+        // JSON has no tags; the payload stands in for the tagged item.
+        SpookyValue::Tagged(_, inner) => write_value(out, inner, indent, level),
+    }
+}
+
+#[inline]
+fn newline(out: &mut String, indent: Option<&str>, level: usize) {
+    if let Some(unit) = indent {
+        out.push('\n');
+        for _ in 0..level {
+            out.push_str(unit);
+        }
+    }
+}
+
+fn write_number(out: &mut String, n: SpookyNumber) {
+    match n {
+        SpookyNumber::I64(i) => write!(out, "{i}").unwrap(),
+        SpookyNumber::U64(u) => write!(out, "{u}").unwrap(),
+        // Debug keeps a ".0" on whole floats so they re-parse as F64.
+        SpookyNumber::F64(f) if f.is_finite() => write!(out, "{f:?}").unwrap(),
+        SpookyNumber::F64(_) => out.push_str("null"),
+    }
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// ─── Parser ─────────────────────────────────────────────────────────────────
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    #[inline]
+    fn err(&self, message: &'static str) -> JsonError {
+        JsonError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).copied()
+    }
+
+    #[inline]
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, lit: &'static [u8], value: SpookyValue) -> Result<SpookyValue, JsonError> {
+        if self.src[self.pos..].starts_with(lit) {
+            self.pos += lit.len();
+            Ok(value)
+        } else {
+            Err(self.err("invalid literal"))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<SpookyValue, JsonError> {
+        match self.peek() {
+            None => Err(self.err("unexpected end of input")),
+            Some(b'n') => self.expect_literal(b"null", SpookyValue::Null),
+            Some(b't') => self.expect_literal(b"true", SpookyValue::Bool(true)),
+            Some(b'f') => self.expect_literal(b"false", SpookyValue::Bool(false)),
+            Some(b'"') => Ok(SpookyValue::Str(self.parse_string()?)),
+            Some(b'[') => self.parse_array(),
+            Some(b'{') => self.parse_object(),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.err("expected value")),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), JsonError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.err("nesting too deep"));
+        }
+        Ok(())
+    }
+
+    fn parse_array(&mut self) -> Result<SpookyValue, JsonError> {
+        self.enter()?;
+        self.pos += 1; // '['
+        let mut arr = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(SpookyValue::Array(arr));
+        }
+        loop {
+            self.skip_ws();
+            arr.push(self.parse_value()?);
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.err("expected ',' or ']'")),
+            }
+        }
+        self.depth -= 1;
+        Ok(SpookyValue::Array(arr))
+    }
+
+    fn parse_object(&mut self) -> Result<SpookyValue, JsonError> {
+        let mut map = FastMap::new();
+        self.parse_members(|key, value| {
+            map.insert(key, value);
+            Ok::<_, JsonError>(())
+        })?;
+        Ok(SpookyValue::Object(map))
+    }
+
+    /// Parse an object, handing each member to `member` in document order
+    /// instead of collecting them into a map.
+    fn parse_members<E: From<JsonError>>(
+        &mut self,
+        mut member: impl FnMut(SmolStr, SpookyValue) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.enter()?;
+        self.pos += 1; // '{'
+        self.skip_ws();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(());
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b'"') {
+                return Err(self.err("expected string key").into());
+            }
+            let key = self.parse_string()?;
+            self.skip_ws();
+            if self.peek() != Some(b':') {
+                return Err(self.err("expected ':'").into());
+            }
+            self.pos += 1;
+            self.skip_ws();
+            let value = self.parse_value()?;
+            member(key, value)?;
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.err("expected ',' or '}'").into()),
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn parse_string(&mut self) -> Result<SmolStr, JsonError> {
+        self.pos += 1; // opening '"'
+        let start = self.pos;
+
+        // Fast path: no escapes — borrow the slice directly.
+        while let Some(b) = self.peek() {
+            match b {
+                b'"' => {
+                    let s = std::str::from_utf8(&self.src[start..self.pos])
+                        .map_err(|_| self.err("invalid UTF-8"))?;
+                    self.pos += 1;
+                    return Ok(SmolStr::new(s));
+                }
+                b'\\' => break,
+                0x00..=0x1f => return Err(self.err("control character in string")),
+                _ => self.pos += 1,
+            }
+        }
+
+        // Slow path: unescape into an owned buffer.
+        let mut buf: Vec<u8> = self.src[start..self.pos].to_vec();
+        loop {
+            match self.peek() {
+                None => return Err(self.err("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    let s = String::from_utf8(buf).map_err(|_| self.err("invalid UTF-8"))?;
+                    return Ok(SmolStr::from(s));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let esc = self.peek().ok_or_else(|| self.err("unterminated escape"))?;
+                    self.pos += 1;
+                    match esc {
+                        b'"' => buf.push(b'"'),
+                        b'\\' => buf.push(b'\\'),
+                        b'/' => buf.push(b'/'),
+                        b'b' => buf.push(0x08),
+                        b'f' => buf.push(0x0c),
+                        b'n' => buf.push(b'\n'),
+                        b'r' => buf.push(b'\r'),
+                        b't' => buf.push(b'\t'),
+                        b'u' => {
+                            let c = self.parse_unicode_escape()?;
+                            let mut tmp = [0u8; 4];
+                            buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+                        }
+                        _ => return Err(self.err("invalid escape")),
+                    }
+                }
+                Some(0x00..=0x1f) => return Err(self.err("control character in string")),
+                Some(b) => {
+                    buf.push(b);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .src
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.err("truncated \\u escape"))?;
+        let s = std::str::from_utf8(digits).map_err(|_| self.err("invalid \\u escape"))?;
+        let v = u32::from_str_radix(s, 16).map_err(|_| self.err("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(v)
+    }
+
+    /// Decode `XXXX` after `\u`, joining UTF-16 surrogate pairs.
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let hi = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&hi) {
+            if !self.src[self.pos..].starts_with(b"\\u") {
+                return Err(self.err("unpaired surrogate"));
+            }
+            self.pos += 2;
+            let lo = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&lo) {
+                return Err(self.err("unpaired surrogate"));
+            }
+            0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00)
+        } else {
+            hi
+        };
+        char::from_u32(code).ok_or_else(|| self.err("invalid code point"))
+    }
+
+    fn parse_number(&mut self) -> Result<SpookyValue, JsonError> {
+        let start = self.pos;
+        let mut is_float = false;
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                while let Some(b'0'..=b'9') = self.peek() {
+                    self.pos += 1;
+                }
+            }
+            _ => return Err(self.err("invalid number")),
+        }
+        if self.peek() == Some(b'.') {
+            is_float = true;
+            self.pos += 1;
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.err("invalid number"));
+            }
+            while let Some(b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_float = true;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.err("invalid number"));
+            }
+            while let Some(b'0'..=b'9') = self.peek() {
+                self.pos += 1;
+            }
+        }
+
+        // The scanned range is pure ASCII, so this cannot fail.
+        let text = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+        if !is_float {
+            if let Ok(i) = text.parse::<i64>() {
+                return Ok(SpookyValue::Number(SpookyNumber::I64(i)));
+            }
+            if let Ok(u) = text.parse::<u64>() {
+                return Ok(SpookyValue::Number(SpookyNumber::U64(u)));
+            }
+        }
+        text.parse::<f64>()
+            .map(|f| SpookyValue::Number(SpookyNumber::F64(f)))
+            .map_err(|_| JsonError {
+                offset: start,
+                message: "invalid number",
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_print_roundtrip() {
+        let text = r#"{"a":1,"b":[true,false,null],"c":{"d":"x\"y","e":-2.5},"f":18446744073709551615}"#;
+        let v = SpookyValue::from_json_str(text).unwrap();
+        assert_eq!(v.to_json_string(), text);
+        assert!(matches!(v.get("f"), Some(SpookyValue::Number(SpookyNumber::U64(u64::MAX)))));
+        assert!(matches!(v.get("a"), Some(SpookyValue::Number(SpookyNumber::I64(1)))));
+    }
+
+    #[test]
+    fn test_whole_float_stays_float() {
+        let v = SpookyValue::from(3.0f64);
+        assert_eq!(v.to_json_string(), "3.0");
+        let back = SpookyValue::from_json_str("3.0").unwrap();
+        assert!(matches!(back, SpookyValue::Number(SpookyNumber::F64(_))));
+    }
+
+    #[test]
+    fn test_pretty_output() {
+        let v = SpookyValue::from_json_str(r#"{"a":[1,2],"b":{}}"#).unwrap();
+        assert_eq!(v.to_json_pretty(), "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {}\n}");
+    }
+
+    #[test]
+    fn test_unicode_escapes() {
+        let v = SpookyValue::from_json_str(r#""é😀\n""#).unwrap();
+        assert_eq!(v.as_str(), Some("é😀\n"));
+        assert_eq!(v.to_json_string(), "\"é😀\\n\"");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(SpookyValue::from_json_str("").is_err());
+        assert!(SpookyValue::from_json_str("[1,]").is_err());
+        assert!(SpookyValue::from_json_str("{\"a\" 1}").is_err());
+        assert!(SpookyValue::from_json_str("01").is_err());
+        assert!(SpookyValue::from_json_str("1 2").is_err());
+        let deep = "[".repeat(MAX_DEPTH + 1);
+        assert_eq!(
+            SpookyValue::from_json_str(&deep).unwrap_err().message,
+            "nesting too deep"
+        );
+    }
+
+    #[test]
+    fn test_non_finite_prints_null() {
+        assert_eq!(SpookyValue::from(f64::INFINITY).to_json_string(), "null");
+    }
+}
@@ -1,7 +1,26 @@
 pub mod error;
 pub mod deserialization;
 pub mod serialization;
+pub mod cbor;
+pub mod surreal;
+pub mod json;
+pub mod display;
+pub mod diff;
+pub mod coerce;
+pub mod arena;
+pub mod buffer_pool;
+pub mod schema;
 pub mod spooky_record;
+pub mod small_map;
 pub mod spooky_value;
+pub mod value_ref;
 pub mod types;
 pub mod db;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "ffi")]
+pub mod spooky_ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "bench")]
+pub mod bench;
@@ -0,0 +1,325 @@
+//! pyo3 bindings: `SpookyDb` and `SpookyRecord` as Python classes.
+//!
+//! Values cross as plain Python objects: `None`, `bool`, `int`, `float`,
+//! `str`, `list` and `dict` with `str` keys map to and from `SpookyValue`.
+//! Records read like read-only dicts, looked up by field name.
+//!
+//! ```python
+//! from spooky_db_module import SpookyDb
+//! db = SpookyDb("app.redb")
+//! db.bulk_load("users", [{"id": "ann", "name": "Ann", "age": 40}])
+//! record = db.get("users", "ann")
+//! record["age"], record.get("email"), record.to_dict(["name", "age"])
+//! ```
+
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString};
+use smol_str::SmolStr;
+
+use crate::db::{BulkRecord, Operation, SpookyDb, SpookyDbError};
+use crate::error::RecordError;
+use crate::serialization::{from_bytes, from_spooky};
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+use crate::spooky_value::{FastMap, SpookyNumber, SpookyValue};
+
+fn db_err(e: SpookyDbError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn record_err(e: RecordError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// A `SpookyValue` as the matching Python object.
+pub fn to_py<'py>(py: Python<'py>, value: &SpookyValue) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        SpookyValue::Null => py.None().into_bound(py),
+        SpookyValue::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+        SpookyValue::Number(SpookyNumber::I64(n)) => n.into_pyobject(py)?.into_any(),
+        SpookyValue::Number(SpookyNumber::U64(n)) => n.into_pyobject(py)?.into_any(),
+        SpookyValue::Number(SpookyNumber::F64(n)) => PyFloat::new(py, *n).into_any(),
+        SpookyValue::Str(s) => PyString::new(py, s).into_any(),
+        SpookyValue::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(to_py(py, item)?)?;
+            }
+            list.into_any()
+        }
+        SpookyValue::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map.iter() {
+                dict.set_item(key.as_str(), to_py(py, item)?)?;
+            }
+            dict.into_any()
+        }
+        SpookyValue::Tagged(_, inner) => to_py(py, inner)?,
+    })
+}
+
+/// A Python object as a `SpookyValue`. Integers outside `i64` are taken as
+/// `u64`; other types raise `TypeError`.
+pub fn from_py(obj: &Bound<'_, PyAny>) -> PyResult<SpookyValue> {
+    if obj.is_none() {
+        Ok(SpookyValue::Null)
+    } else if let Ok(b) = obj.cast::<PyBool>() {
+        Ok(SpookyValue::Bool(b.is_true()))
+    } else if obj.is_instance_of::<PyInt>() {
+        match obj.extract::<i64>() {
+            Ok(n) => Ok(SpookyValue::Number(SpookyNumber::I64(n))),
+            Err(_) => Ok(SpookyValue::Number(SpookyNumber::U64(obj.extract()?))),
+        }
+    } else if let Ok(f) = obj.cast::<PyFloat>() {
+        Ok(SpookyValue::Number(SpookyNumber::F64(f.value())))
+    } else if let Ok(s) = obj.cast::<PyString>() {
+        Ok(SpookyValue::Str(SmolStr::new(s.to_str()?)))
+    } else if let Ok(list) = obj.cast::<PyList>() {
+        list.iter().map(|item| from_py(&item)).collect::<PyResult<_>>().map(SpookyValue::Array)
+    } else if let Ok(dict) = obj.cast::<PyDict>() {
+        let mut map = FastMap::new();
+        for (key, item) in dict.iter() {
+            let key = key
+                .cast::<PyString>()
+                .map_err(|_| PyTypeError::new_err("dict keys must be str"))?;
+            map.insert(SmolStr::new(key.to_str()?), from_py(&item)?);
+        }
+        Ok(SpookyValue::Object(map))
+    } else {
+        let kind = obj.get_type().name()?;
+        Err(PyTypeError::new_err(format!("cannot store a {kind}")))
+    }
+}
+
+/// Record bytes for a Python dict.
+fn record_bytes(record: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    let value = from_py(record)?;
+    if !value.is_object() {
+        return Err(PyTypeError::new_err("a record must be a dict"));
+    }
+    Ok(from_spooky(&value).map_err(record_err)?.0)
+}
+
+// ─── SpookyDb ────────────────────────────────────────────────────────────────
+
+#[pyclass(name = "SpookyDb", module = "spooky_db_module", unsendable)]
+pub struct PyDb {
+    db: SpookyDb,
+}
+
+#[pymethods]
+impl PyDb {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        Ok(Self {
+            db: SpookyDb::new(path).map_err(db_err)?,
+        })
+    }
+
+    /// Store `record` (a dict) as `id`, creating or replacing it.
+    #[pyo3(signature = (table, id, record, version=None))]
+    fn put(
+        &mut self,
+        table: &str,
+        id: &str,
+        record: &Bound<'_, PyAny>,
+        version: Option<u64>,
+    ) -> PyResult<()> {
+        let bytes = record_bytes(record)?;
+        let op = match self.db.get_zset_weight(table, id) > 0 {
+            true => Operation::Update,
+            false => Operation::Create,
+        };
+        self.db.apply_mutation(table, op, id, Some(&bytes), version).map_err(db_err)?;
+        Ok(())
+    }
+
+    /// Delete `id`; returns whether it existed.
+    fn delete(&mut self, table: &str, id: &str) -> PyResult<bool> {
+        if self.db.get_zset_weight(table, id) <= 0 {
+            return Ok(false);
+        }
+        self.db.apply_mutation(table, Operation::Delete, id, None, None).map_err(db_err)?;
+        Ok(true)
+    }
+
+    fn get(&self, table: &str, id: &str) -> PyResult<Option<PyRecord>> {
+        match self.db.get_record_bytes(table, id).map_err(db_err)? {
+            Some(bytes) => PyRecord::from_vec(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether record `id` of `table` exists.
+    fn has(&self, table: &str, id: &str) -> bool {
+        self.db.get_zset_weight(table, id) > 0
+    }
+
+    /// Store a list of dicts in one `bulk_load`. Each dict's `id_key` string
+    /// is its record id and is not stored. Returns how many were stored.
+    #[pyo3(signature = (table, records, id_key="id"))]
+    fn bulk_load(
+        &mut self,
+        table: &str,
+        records: &Bound<'_, PyList>,
+        id_key: &str,
+    ) -> PyResult<usize> {
+        let mut batch = Vec::with_capacity(records.len());
+        for (i, record) in records.iter().enumerate() {
+            let dict = record
+                .cast::<PyDict>()
+                .map_err(|_| PyTypeError::new_err(format!("records[{i}] is not a dict")))?
+                .copy()?;
+            let id: String = match dict.get_item(id_key)? {
+                Some(id) => id.extract()?,
+                None => return Err(PyKeyError::new_err(format!("records[{i}] has no {id_key:?}"))),
+            };
+            dict.del_item(id_key)?;
+            batch.push(BulkRecord {
+                table: SmolStr::new(table),
+                id: SmolStr::new(id),
+                data: record_bytes(dict.as_any())?,
+                version: None,
+            });
+        }
+        let count = batch.len();
+        self.db.bulk_load(batch).map_err(db_err)?;
+        Ok(count)
+    }
+
+    fn tables(&self) -> Vec<String> {
+        self.db.table_names().map(|name| name.to_string()).collect()
+    }
+
+    /// Number of records in `table`.
+    fn count(&self, table: &str) -> usize {
+        self.db.table_len(table)
+    }
+}
+
+// ─── SpookyRecord ────────────────────────────────────────────────────────────
+
+/// An owned record, read by field name. Field names are not stored, so
+/// there is no `keys()`; `to_dict` takes the names to read.
+#[pyclass(name = "SpookyRecord", module = "spooky_db_module", frozen)]
+pub struct PyRecord {
+    buf: Vec<u8>,
+    field_count: usize,
+}
+
+impl PyRecord {
+    fn from_vec(buf: Vec<u8>) -> PyResult<Self> {
+        let (_, field_count) = from_bytes(&buf).map_err(record_err)?;
+        Ok(Self { buf, field_count })
+    }
+
+    fn record(&self) -> SpookyRecord<'_> {
+        SpookyRecord::new(&self.buf, self.field_count)
+    }
+
+    fn field<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Option<Bound<'py, PyAny>>> {
+        match self.record().get_field::<SpookyValue>(name) {
+            Some(value) => to_py(py, &value).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[pymethods]
+impl PyRecord {
+    /// Wrap record bytes, or serialize a dict.
+    #[new]
+    fn new(data: &Bound<'_, PyAny>) -> PyResult<Self> {
+        match data.cast::<PyBytes>() {
+            Ok(bytes) => Self::from_vec(bytes.as_bytes().to_vec()),
+            Err(_) => Self::from_vec(record_bytes(data)?),
+        }
+    }
+
+    fn __getitem__<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+        self.field(py, name)?.ok_or_else(|| PyKeyError::new_err(name.to_owned()))
+    }
+
+    #[pyo3(signature = (name, default=None))]
+    fn get<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        default: Option<Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match self.field(py, name)? {
+            Some(value) => value,
+            None => default.unwrap_or_else(|| py.None().into_bound(py)),
+        })
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.record().has_field(name)
+    }
+
+    /// Number of fields.
+    fn __len__(&self) -> usize {
+        self.field_count
+    }
+
+    /// The named fields as a dict; absent names are left out.
+    fn to_dict<'py>(&self, py: Python<'py>, names: Vec<String>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for name in names {
+            if let Some(value) = self.field(py, &name)? {
+                dict.set_item(name, value)?;
+            }
+        }
+        Ok(dict)
+    }
+
+    /// The record bytes.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.buf)
+    }
+}
+
+/// The `spooky_db_module` Python module.
+#[pymodule]
+fn spooky_db_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyDb>()?;
+    m.add_class::<PyRecord>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_bulk_load_dicts_and_read_back() {
+        let tmp = NamedTempFile::new().unwrap();
+        Python::initialize();
+        Python::attach(|py| -> PyResult<()> {
+            let mut db = PyDb::new(tmp.path().to_str().unwrap())?;
+            let rows = c"[{'id': 'ann', 'age': 40, 'tags': ['x', None], 'big': 2**63}]";
+            let rows = py.eval(rows, None, None)?;
+            assert_eq!(db.bulk_load("users", rows.cast()?, "id")?, 1);
+
+            let record = db.get("users", "ann")?.unwrap();
+            assert_eq!(record.__len__(), 3);
+            assert!(!record.__contains__("id"));
+            assert_eq!(record.__getitem__(py, "age")?.extract::<i64>()?, 40);
+            assert_eq!(record.__getitem__(py, "big")?.extract::<u64>()?, 1 << 63);
+            assert!(record.__getitem__(py, "missing").is_err());
+            let dict = record.to_dict(py, vec!["tags".into(), "missing".into()])?;
+            assert_eq!(dict.repr()?.to_str()?, "{'tags': ['x', None]}");
+
+            let update = py.eval(c"{'age': 41}", None, None)?;
+            db.put("users", "ann", &update, Some(2))?;
+            let age = db.get("users", "ann")?.unwrap().get(py, "age", None)?;
+            assert_eq!(age.extract::<i64>()?, 41);
+            assert!(db.put("users", "bob", &py.eval(c"[1]", None, None)?, None).is_err());
+            assert!(db.delete("users", "ann")?);
+            assert!(!db.has("users", "ann"));
+            Ok(())
+        })
+        .unwrap();
+    }
+}
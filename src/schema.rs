@@ -0,0 +1,452 @@
+use crate::deserialization::decode_field;
+use crate::error::{SchemaError, Violation, ViolationKind};
+use crate::spooky_record::{PerfectIndex, SpookyReadable};
+use crate::spooky_value::{FastMap, SpookyNumber, SpookyValue};
+use crate::types::*;
+use smol_str::SmolStr;
+use xxhash_rust::const_xxh64::xxh64;
+
+// ─── Schema ─────────────────────────────────────────────────────────────────
+//
+// Declarative shape check for objects and stored records. Violations are
+// collected (not short-circuited) and reported with JSON Pointer paths.
+//
+// Records only store name hashes, so `validate_record` resolves schema fields
+// by hash; an unknown field can only be reported as `/#<hash>`. Nested
+// fields are decoded from CBOR only when their declared type needs it.
+
+/// Expected type of a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    /// Any value, including null.
+    Any,
+    Null,
+    Bool,
+    /// I64 or U64.
+    Int,
+    /// F64 only.
+    Float,
+    /// Any numeric variant.
+    Number,
+    Str,
+    /// Array whose every element matches the inner type.
+    Array(Box<FieldType>),
+    /// Object matching a nested schema.
+    Object(Schema),
+    /// Matches if any alternative matches.
+    OneOf(Vec<FieldType>),
+}
+
+impl FieldType {
+    #[inline]
+    pub fn array_of(inner: FieldType) -> Self {
+        FieldType::Array(Box::new(inner))
+    }
+
+    /// Short name used in violation messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldType::Any => "any",
+            FieldType::Null => "null",
+            FieldType::Bool => "bool",
+            FieldType::Int => "int",
+            FieldType::Float => "float",
+            FieldType::Number => "number",
+            FieldType::Str => "string",
+            FieldType::Array(_) => "array",
+            FieldType::Object(_) => "object",
+            FieldType::OneOf(_) => "one of",
+        }
+    }
+}
+
+/// A named field within a [`Schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDef {
+    pub ty: FieldType,
+    /// Absence is a violation.
+    pub required: bool,
+    /// An explicit null is accepted regardless of `ty`.
+    pub nullable: bool,
+}
+
+/// Object shape: named fields plus an unknown-field policy.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    fields: FastMap<SmolStr, FieldDef>,
+    deny_unknown: bool,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a required, non-nullable field.
+    pub fn required(mut self, name: &str, ty: FieldType) -> Self {
+        self.fields.insert(
+            SmolStr::new(name),
+            FieldDef {
+                ty,
+                required: true,
+                nullable: false,
+            },
+        );
+        self
+    }
+
+    /// Add an optional field. Null is accepted in place of `ty`.
+    pub fn optional(mut self, name: &str, ty: FieldType) -> Self {
+        self.fields.insert(
+            SmolStr::new(name),
+            FieldDef {
+                ty,
+                required: false,
+                nullable: true,
+            },
+        );
+        self
+    }
+
+    /// Add a field with an explicit definition.
+    pub fn field(mut self, name: &str, def: FieldDef) -> Self {
+        self.fields.insert(SmolStr::new(name), def);
+        self
+    }
+
+    /// Report fields that the schema does not declare.
+    pub fn deny_unknown(mut self) -> Self {
+        self.deny_unknown = true;
+        self
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&SmolStr, &FieldDef)> {
+        self.fields.iter()
+    }
+
+    /// Slot lookup over the declared top-level fields, for reading records
+    /// of this shape (see `spooky_record::perfect_index`).
+    pub fn perfect_index(&self) -> PerfectIndex {
+        PerfectIndex::new(self.fields.keys())
+    }
+
+    /// Validate a value. The root must be an object.
+    pub fn validate(&self, value: &SpookyValue) -> Result<(), SchemaError> {
+        let mut out = Vec::new();
+        let mut path = String::new();
+        match value {
+            SpookyValue::Object(map) => self.check_object(map, &mut path, &mut out),
+            other => out.push(Violation {
+                path,
+                kind: ViolationKind::TypeMismatch {
+                    expected: "object",
+                    found: other.type_name(),
+                },
+            }),
+        }
+        finish(out)
+    }
+
+    /// Validate a stored record without materializing it.
+    ///
+    /// Flat fields are checked against their type tag. Fields declared as
+    /// `Array(T)` (T ≠ `Any`), `Object(_)` or `OneOf(_)` are decoded and checked
+    /// like [`Schema::validate`].
+    pub fn validate_record<R: SpookyReadable>(&self, record: &R) -> Result<(), SchemaError> {
+        let mut out = Vec::new();
+        let mut path = String::new();
+
+        for (name, def) in &self.fields {
+            let len = path.len();
+            push_token(&mut path, name);
+            match record.get_raw(name) {
+                None => {
+                    if def.required {
+                        out.push(Violation {
+                            path: path.clone(),
+                            kind: ViolationKind::Missing,
+                        });
+                    }
+                }
+                Some(field) => check_field(def, &field, &mut path, &mut out),
+            }
+            path.truncate(len);
+        }
+
+        if self.deny_unknown {
+            let known: Vec<u64> = self.fields.keys().map(|k| xxh64(k.as_bytes(), 0)).collect();
+            for field in record.iter_fields() {
+                if !known.contains(&field.name_hash) {
+                    out.push(Violation {
+                        path: format!("/#{:016x}", field.name_hash),
+                        kind: ViolationKind::Unknown,
+                    });
+                }
+            }
+        }
+        finish(out)
+    }
+
+    fn check_object(
+        &self,
+        map: &FastMap<SmolStr, SpookyValue>,
+        path: &mut String,
+        out: &mut Vec<Violation>,
+    ) {
+        for (name, def) in &self.fields {
+            let len = path.len();
+            push_token(path, name);
+            match map.get(name) {
+                None if def.required => out.push(Violation {
+                    path: path.clone(),
+                    kind: ViolationKind::Missing,
+                }),
+                None => {}
+                Some(SpookyValue::Null) if def.nullable => {}
+                Some(v) => check_value(&def.ty, v, path, out),
+            }
+            path.truncate(len);
+        }
+        if self.deny_unknown {
+            for key in map.keys() {
+                if !self.fields.contains_key(key) {
+                    let len = path.len();
+                    push_token(path, key);
+                    out.push(Violation {
+                        path: path.clone(),
+                        kind: ViolationKind::Unknown,
+                    });
+                    path.truncate(len);
+                }
+            }
+        }
+    }
+}
+
+fn check_value(ty: &FieldType, value: &SpookyValue, path: &mut String, out: &mut Vec<Violation>) {
+    let ok = match (ty, value) {
+        (FieldType::Any, _) => true,
+        (FieldType::Null, SpookyValue::Null) => true,
+        (FieldType::Bool, SpookyValue::Bool(_)) => true,
+        (FieldType::Int, SpookyValue::Number(SpookyNumber::I64(_) | SpookyNumber::U64(_))) => true,
+        (FieldType::Float, SpookyValue::Number(SpookyNumber::F64(_))) => true,
+        (FieldType::Number, SpookyValue::Number(_)) => true,
+        (FieldType::Str, SpookyValue::Str(_)) => true,
+        (FieldType::Array(inner), SpookyValue::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                let len = path.len();
+                push_index(path, i);
+                check_value(inner, item, path, out);
+                path.truncate(len);
+            }
+            true
+        }
+        (FieldType::Object(schema), SpookyValue::Object(map)) => {
+            schema.check_object(map, path, out);
+            true
+        }
+        (FieldType::OneOf(alts), v) => alts.iter().any(|alt| {
+            let mut scratch = Vec::new();
+            check_value(alt, v, path, &mut scratch);
+            scratch.is_empty()
+        }),
+        _ => false,
+    };
+    if !ok {
+        out.push(Violation {
+            path: path.clone(),
+            kind: ViolationKind::TypeMismatch {
+                expected: ty.name(),
+                found: value.type_name(),
+            },
+        });
+    }
+}
+
+/// Record-level check for one present field.
+fn check_field(def: &FieldDef, field: &FieldRef<'_>, path: &mut String, out: &mut Vec<Violation>) {
+    if field.type_tag == TAG_NULL && def.nullable {
+        return;
+    }
+    let needs_decode = match &def.ty {
+        FieldType::Array(inner) => **inner != FieldType::Any,
+        FieldType::Object(_) | FieldType::OneOf(_) => true,
+        _ => false,
+    };
+    if needs_decode && field.type_tag == TAG_NESTED_CBOR || matches!(def.ty, FieldType::OneOf(_)) {
+        match decode_field::<SpookyValue>(*field) {
+            Some(v) => check_value(&def.ty, &v, path, out),
+            None => out.push(Violation {
+                path: path.clone(),
+                kind: ViolationKind::Undecodable,
+            }),
+        }
+        return;
+    }
+    let ok = match (&def.ty, field.type_tag) {
+        (FieldType::Any, _) => true,
+        (FieldType::Null, TAG_NULL) => true,
+        (FieldType::Bool, TAG_BOOL) => true,
+        (FieldType::Int, TAG_I64 | TAG_U64) => true,
+        (FieldType::Float, TAG_F64) => true,
+        (FieldType::Number, TAG_I64 | TAG_U64 | TAG_F64) => true,
+        (FieldType::Str, TAG_STR) => true,
+        // Array(Any): any nested value that decodes as an array.
+        (FieldType::Array(_), TAG_NESTED_CBOR) => {
+            matches!(
+                decode_field::<SpookyValue>(*field),
+                Some(SpookyValue::Array(_))
+            )
+        }
+        _ => false,
+    };
+    if !ok {
+        out.push(Violation {
+            path: path.clone(),
+            kind: ViolationKind::TypeMismatch {
+                expected: def.ty.name(),
+                found: tag_name(field.type_tag),
+            },
+        });
+    }
+}
+
+#[inline]
+fn finish(violations: Vec<Violation>) -> Result<(), SchemaError> {
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemaError { violations })
+    }
+}
+
+fn tag_name(tag: u8) -> &'static str {
+    match tag {
+        TAG_NULL => "null",
+        TAG_BOOL => "bool",
+        TAG_I64 | TAG_U64 => "int",
+        TAG_F64 => "float",
+        TAG_STR => "string",
+        TAG_NESTED_CBOR => "array or object",
+        _ => "unknown",
+    }
+}
+
+/// Append `/token` with RFC 6901 escaping.
+fn push_token(path: &mut String, token: &str) {
+    path.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            c => path.push(c),
+        }
+    }
+}
+
+fn push_index(path: &mut String, i: usize) {
+    use std::fmt::Write;
+    write!(path, "/{i}").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::from_spooky;
+    use crate::spooky_record::SpookyRecord;
+
+    fn v(text: &str) -> SpookyValue {
+        SpookyValue::from_json_str(text).unwrap()
+    }
+
+    fn user_schema() -> Schema {
+        Schema::new()
+            .required("name", FieldType::Str)
+            .required("age", FieldType::Int)
+            .optional("score", FieldType::Number)
+            .optional("tags", FieldType::array_of(FieldType::Str))
+            .optional(
+                "profile",
+                FieldType::Object(Schema::new().required("theme", FieldType::Str)),
+            )
+    }
+
+    fn paths(err: &SchemaError) -> Vec<&str> {
+        err.violations.iter().map(|v| v.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_valid_value_and_record() {
+        let val =
+            v(r#"{"name":"Ada","age":36,"score":9.5,"tags":["x"],"profile":{"theme":"dark"}}"#);
+        let schema = user_schema();
+        assert!(schema.validate(&val).is_ok());
+        let (buf, n) = from_spooky(&val).unwrap();
+        assert!(schema.validate_record(&SpookyRecord::new(&buf, n)).is_ok());
+    }
+
+    #[test]
+    fn test_collects_all_violations_with_paths() {
+        let val = v(r#"{"age":"old","tags":["x",1],"profile":{}}"#);
+        let err = user_schema().validate(&val).unwrap_err();
+        assert_eq!(
+            paths(&err),
+            vec!["/age", "/name", "/profile/theme", "/tags/1"]
+        );
+        assert_eq!(
+            err.violations[0].kind,
+            ViolationKind::TypeMismatch {
+                expected: "int",
+                found: "string"
+            }
+        );
+        assert_eq!(err.violations[1].kind, ViolationKind::Missing);
+    }
+
+    #[test]
+    fn test_record_matches_value_violations() {
+        let val = v(r#"{"age":2.5,"tags":["x",1],"profile":{"theme":3}}"#);
+        let (buf, n) = from_spooky(&val).unwrap();
+        let err = user_schema()
+            .validate_record(&SpookyRecord::new(&buf, n))
+            .unwrap_err();
+        assert_eq!(
+            paths(&err),
+            vec!["/age", "/name", "/profile/theme", "/tags/1"]
+        );
+    }
+
+    #[test]
+    fn test_nullable_and_one_of() {
+        let schema = Schema::new()
+            .optional("note", FieldType::Str)
+            .required("id", FieldType::OneOf(vec![FieldType::Int, FieldType::Str]));
+        assert!(schema.validate(&v(r#"{"note":null,"id":"a"}"#)).is_ok());
+        assert!(schema.validate(&v(r#"{"id":3}"#)).is_ok());
+        let err = schema.validate(&v(r#"{"id":true}"#)).unwrap_err();
+        assert_eq!(paths(&err), vec!["/id"]);
+    }
+
+    #[test]
+    fn test_deny_unknown() {
+        let schema = Schema::new().required("a", FieldType::Any).deny_unknown();
+        let val = v(r#"{"a":1,"b/c":2}"#);
+        let err = schema.validate(&val).unwrap_err();
+        assert_eq!(paths(&err), vec!["/b~1c"]);
+        assert_eq!(err.violations[0].kind, ViolationKind::Unknown);
+
+        let (buf, n) = from_spooky(&val).unwrap();
+        let err = schema
+            .validate_record(&SpookyRecord::new(&buf, n))
+            .unwrap_err();
+        assert_eq!(err.violations.len(), 1);
+        assert!(err.violations[0].path.starts_with("/#"));
+    }
+
+    #[test]
+    fn test_non_object_root() {
+        let err = Schema::new().validate(&v("[1]")).unwrap_err();
+        assert_eq!(paths(&err), vec![""]);
+        assert!(err.to_string().contains("expected object, found array"));
+    }
+}
@@ -1,9 +1,10 @@
-use super::error::RecordError;
-use super::spooky_value::{SpookyNumber, SpookyValue};
+use super::cbor::{self, Head, SliceReader};
+use super::error::{JsonError, RecordError};
+use super::spooky_value::{FastMap, SpookyNumber, SpookyValue};
 use super::types::*;
 use arrayvec::ArrayVec;
 use smol_str::SmolStr;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
 use xxhash_rust::const_xxh64::xxh64;
 
 // ─── RecordSerialize Trait ──────────────────────────────────────────────────
@@ -34,6 +35,12 @@ pub trait RecordSerialize: serde::Serialize {
 
     /// Check if this value is nested (array or object).
     fn is_nested(&self) -> bool;
+
+    /// Append a nested value to `buf` as CBOR. Defaults to the serde encoding.
+    #[inline]
+    fn write_nested(&self, buf: &mut Vec<u8>) -> Result<(), RecordError> {
+        cbor4ii::serde::to_writer(buf, &self).map_err(|e| RecordError::CborError(e.to_string()))
+    }
 }
 
 // ─── RecordSerialize for SpookyValue ────────────────────────────────────────
@@ -86,12 +93,22 @@ impl RecordSerialize for SpookyValue {
 
     #[inline]
     fn is_nested(&self) -> bool {
-        matches!(self, SpookyValue::Array(_) | SpookyValue::Object(_))
+        matches!(
+            self,
+            SpookyValue::Array(_) | SpookyValue::Object(_) | SpookyValue::Tagged(..)
+        )
+    }
+
+    /// Through the `cbor` encoder, so `Tagged` values keep their tag.
+    #[inline]
+    fn write_nested(&self, buf: &mut Vec<u8>) -> Result<(), RecordError> {
+        cbor::encode_into(buf, self)
     }
 }
 
 // ─── RecordSerialize for serde_json::Value ──────────────────────────────────
 
+#[cfg(feature = "json")]
 impl RecordSerialize for serde_json::Value {
     #[inline]
     fn is_null(&self) -> bool {
@@ -190,6 +207,95 @@ impl RecordSerialize for cbor4ii::core::Value {
     }
 }
 
+// ─── RecordSerialize for ciborium::Value ────────────────────────────────────
+
+#[cfg(feature = "ciborium")]
+impl RecordSerialize for ciborium::Value {
+    #[inline]
+    fn is_null(&self) -> bool {
+        self.is_null()
+    }
+
+    #[inline]
+    fn as_bool(&self) -> Option<bool> {
+        self.as_bool()
+    }
+
+    #[inline]
+    fn as_i64(&self) -> Option<i64> {
+        self.as_integer().and_then(|i| i64::try_from(i).ok())
+    }
+
+    #[inline]
+    fn as_u64(&self) -> Option<u64> {
+        self.as_integer().and_then(|i| u64::try_from(i).ok())
+    }
+
+    #[inline]
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            ciborium::Value::Float(f) => Some(*f),
+            ciborium::Value::Integer(i) => Some(i128::from(*i) as f64),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn as_str(&self) -> Option<&str> {
+        self.as_text()
+    }
+
+    #[inline]
+    fn is_nested(&self) -> bool {
+        self.is_array() || self.is_map()
+    }
+}
+
+// ─── RecordSerialize for rmpv::Value ────────────────────────────────────────
+
+#[cfg(feature = "msgpack")]
+impl RecordSerialize for rmpv::Value {
+    #[inline]
+    fn is_null(&self) -> bool {
+        self.is_nil()
+    }
+
+    #[inline]
+    fn as_bool(&self) -> Option<bool> {
+        self.as_bool()
+    }
+
+    #[inline]
+    fn as_i64(&self) -> Option<i64> {
+        self.as_i64()
+    }
+
+    #[inline]
+    fn as_u64(&self) -> Option<u64> {
+        self.as_u64()
+    }
+
+    #[inline]
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            rmpv::Value::F32(f) => Some(*f as f64),
+            rmpv::Value::F64(f) => Some(*f),
+            rmpv::Value::Integer(i) => i.as_f64(),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn as_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    #[inline]
+    fn is_nested(&self) -> bool {
+        matches!(self, rmpv::Value::Array(_) | rmpv::Value::Map(_))
+    }
+}
+
 // ─── RecordSerialize for &T ─────────────────────────────────────────────────
 
 /// Blanket implementation for references — allows passing &SpookyValue, etc.
@@ -228,6 +334,10 @@ impl<T: RecordSerialize> RecordSerialize for &T {
     fn is_nested(&self) -> bool {
         (**self).is_nested()
     }
+    #[inline]
+    fn write_nested(&self, buf: &mut Vec<u8>) -> Result<(), RecordError> {
+        (**self).write_nested(buf)
+    }
 }
 
 // ─── Writer ─────────────────────────────────────────────────────────────────
@@ -285,9 +395,8 @@ pub fn write_field_into<V: RecordSerialize>(
         buf.extend_from_slice(s.as_bytes());
         TAG_STR
     } else if value.is_nested() {
-        // Array or Object — serialize as CBOR using serde::Serialize
-        cbor4ii::serde::to_writer(&mut *buf, value)
-            .map_err(|e| RecordError::CborError(e.to_string()))?;
+        // Array or Object — serialize as CBOR
+        value.write_nested(buf)?;
         TAG_NESTED_CBOR
     } else {
         // Unknown type — cannot serialize, return error
@@ -296,7 +405,7 @@ pub fn write_field_into<V: RecordSerialize>(
 }
 
 pub fn prepare_buf<V: RecordSerialize>(
-    map: &BTreeMap<SmolStr, V>,
+    map: &FastMap<SmolStr, V>,
     buf: &mut Vec<u8>,
     field_count: usize,
 ) -> Result<(), RecordError> {
@@ -314,6 +423,16 @@ pub fn prepare_buf<V: RecordSerialize>(
             .map_err(|_| RecordError::TooManyFields)?;
     }
 
+    write_entries(buf, &mut entries, field_count)
+}
+
+/// Shared tail of `prepare_buf`: sort `(value, name_hash)` pairs and write the
+/// header, index and data area. `buf` must already be sized to the data start.
+pub(crate) fn write_entries<V: RecordSerialize>(
+    buf: &mut Vec<u8>,
+    entries: &mut [(&V, u64)],
+    field_count: usize,
+) -> Result<(), RecordError> {
     // Sort for O(log n) lookup in the reader
     entries.sort_unstable_by_key(|(_, hash)| *hash);
 
@@ -328,23 +447,35 @@ pub fn prepare_buf<V: RecordSerialize>(
         let data_length = buf.len() - data_offset;
 
         // B. Fill in the index entry
-        // All arithmetic must use usize
-        let idx = HEADER_SIZE + i * INDEX_ENTRY_SIZE;
-        let entry = &mut buf[idx..idx + INDEX_ENTRY_SIZE];
-        entry[0..8].copy_from_slice(&hash.to_le_bytes());
-        entry[8..12].copy_from_slice(&(data_offset as u32).to_le_bytes());
-        entry[12..16].copy_from_slice(&(data_length as u32).to_le_bytes());
-        entry[16] = tag;
+        write_index_entry(buf, i, *hash, data_offset, data_length, tag);
     }
     Ok(())
 }
 
+#[inline]
+pub(crate) fn write_index_entry(
+    buf: &mut [u8],
+    i: usize,
+    hash: u64,
+    data_offset: usize,
+    data_length: usize,
+    tag: u8,
+) {
+    // All arithmetic must use usize
+    let idx = HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+    let entry = &mut buf[idx..idx + INDEX_ENTRY_SIZE];
+    entry[0..8].copy_from_slice(&hash.to_le_bytes());
+    entry[8..12].copy_from_slice(&(data_offset as u32).to_le_bytes());
+    entry[12..16].copy_from_slice(&(data_length as u32).to_le_bytes());
+    entry[16] = tag;
+}
+
 // ════════════════════════════════════════════════════════════════════════
 // Serializations patterns
 // ════════════════════════════════════════════════════════════════════════
 
 pub fn serialize<V: RecordSerialize>(
-    map: &BTreeMap<SmolStr, V>,
+    map: &FastMap<SmolStr, V>,
 ) -> Result<(Vec<u8>, usize), RecordError> {
     let field_count = map.len();
 
@@ -386,18 +517,294 @@ pub fn from_cbor(data: &cbor4ii::core::Value) -> Result<(Vec<u8>, usize), Record
         _ => return Err(RecordError::InvalidBuffer),
     };
 
-    let mut map = BTreeMap::new();
+    let mut map = FastMap::new();
+    let mut tagged = false;
     for (k, v) in entries {
         let key_str = match k {
             cbor4ii::core::Value::Text(s) => SmolStr::from(s),
             _ => return Err(RecordError::CborError("Key must be a string".into())),
         };
+        tagged |= cbor::has_tags(v);
         map.insert(key_str, v.clone());
     }
 
+    if tagged {
+        // Tagged values (SurrealDB datetimes, record ids, ...) go through the
+        // bridge so they are stored as native scalars.
+        let map: FastMap<SmolStr, SpookyValue> = map
+            .into_iter()
+            .map(|(k, v)| (k, SpookyValue::from(v)))
+            .collect();
+        return serialize(&map);
+    }
     serialize(&map)
 }
 
+/// Serialize CBOR-encoded map bytes straight into the hybrid binary format.
+///
+/// Same output as `from_cbor(&cbor4ii::serde::from_slice(data)?)`, but the
+/// input is walked once with a pull parser and no intermediate value tree is
+/// built: scalars are written natively, and nested arrays/maps are copied
+/// into the record as their original CBOR bytes. Top-level tags follow the
+/// `cbor` bridge policy (bignums become numbers, unknown tags are kept).
+///
+/// Returns `(buf, field_count)`. Trailing bytes after the map are an error.
+pub fn from_cbor_slice(data: &[u8]) -> Result<(Vec<u8>, usize), RecordError> {
+    let mut buf = Vec::new();
+    let field_count = from_cbor_slice_into(data, &mut buf)?;
+    Ok((buf, field_count))
+}
+
+/// `from_cbor_slice` into a reusable buffer, like `serialize_into`. The
+/// buffer is cleared but retains its capacity. Returns the field count.
+pub fn from_cbor_slice_into(data: &[u8], buf: &mut Vec<u8>) -> Result<usize, RecordError> {
+    cbor_map_into(data, buf, None).map(|(field_count, _)| field_count)
+}
+
+/// `from_cbor_slice_into` for a map that carries its record id: the text
+/// value under `id_key` is returned instead of stored. `None` if the key is
+/// absent or not text.
+pub(crate) fn from_cbor_record_into(
+    data: &[u8],
+    id_key: &str,
+    buf: &mut Vec<u8>,
+) -> Result<(usize, Option<SmolStr>), RecordError> {
+    cbor_map_into(data, buf, Some(id_key))
+}
+
+fn cbor_map_into(
+    data: &[u8],
+    buf: &mut Vec<u8>,
+    id_key: Option<&str>,
+) -> Result<(usize, Option<SmolStr>), RecordError> {
+    let mut reader = SliceReader::new(data);
+    let mut remaining = match reader.head()? {
+        Head::Map(len) => len,
+        _ => return Err(RecordError::InvalidBuffer),
+    };
+
+    let mut fields: ArrayVec<(u64, StreamField<'_>), 32> = ArrayVec::new();
+    let mut id = None;
+    loop {
+        match remaining {
+            Some(0) => break,
+            Some(ref mut n) => *n -= 1,
+            None => {}
+        }
+        let key = match reader.head()? {
+            Head::Text(len) => reader.text_body(len)?,
+            Head::Break if remaining.is_none() => break,
+            _ => return Err(RecordError::CborError("Key must be a string".into())),
+        };
+        let field = read_stream_field(&mut reader)?;
+        if id_key == Some(&*key) {
+            id = match field {
+                StreamField::Str(s) => Some(SmolStr::from(s)),
+                _ => None,
+            };
+            continue;
+        }
+        let hash = xxh64(key.as_bytes(), 0);
+        // Duplicate keys: last one wins, as with `FastMap::insert`.
+        match fields.iter_mut().find(|(h, _)| *h == hash) {
+            Some(slot) => slot.1 = field,
+            None => fields
+                .try_push((hash, field))
+                .map_err(|_| RecordError::TooManyFields)?,
+        }
+    }
+    if !reader.is_empty() {
+        return Err(RecordError::CborError(
+            "trailing bytes after record map".into(),
+        ));
+    }
+
+    fields.sort_unstable_by_key(|(hash, _)| *hash);
+    let field_count = fields.len();
+    let data_start = HEADER_SIZE + field_count * INDEX_ENTRY_SIZE;
+    // Scalars never grow past their CBOR size by more than the 8-byte slot.
+    buf.clear();
+    buf.reserve(data_start + data.len() + field_count * 8);
+    buf.resize(data_start, 0);
+    buf[0..4].copy_from_slice(&(field_count as u32).to_le_bytes());
+
+    for (i, (hash, field)) in fields.iter().enumerate() {
+        let data_offset = buf.len();
+        let tag = match field {
+            StreamField::Null => TAG_NULL,
+            StreamField::Bool(b) => {
+                buf.push(*b as u8);
+                TAG_BOOL
+            }
+            StreamField::Number(n) => write_field_into(buf, &SpookyValue::Number(*n))?,
+            StreamField::Str(s) => {
+                buf.extend_from_slice(s.as_bytes());
+                TAG_STR
+            }
+            StreamField::Nested(raw) => {
+                buf.extend_from_slice(raw);
+                TAG_NESTED_CBOR
+            }
+        };
+        let data_length = buf.len() - data_offset;
+        write_index_entry(buf, i, *hash, data_offset, data_length, tag);
+    }
+
+    Ok((field_count, id))
+}
+
+/// A top-level field value as read by `from_cbor_slice`.
+enum StreamField<'a> {
+    Null,
+    Bool(bool),
+    Number(SpookyNumber),
+    Str(Cow<'a, str>),
+    /// CBOR bytes for a nested value: borrowed from the input where possible.
+    Nested(Cow<'a, [u8]>),
+}
+
+fn read_stream_field<'a>(reader: &mut SliceReader<'a>) -> Result<StreamField<'a>, RecordError> {
+    let start = reader.position();
+    Ok(match reader.head()? {
+        Head::Null => StreamField::Null,
+        Head::Bool(b) => StreamField::Bool(b),
+        Head::Unsigned(u) => StreamField::Number(cbor::int_to_number(u as i128)),
+        Head::Negative(n) => StreamField::Number(cbor::int_to_number(-1 - n as i128)),
+        Head::Float(f) => StreamField::Number(SpookyNumber::F64(f)),
+        Head::Text(len) => StreamField::Str(reader.text_body(len)?),
+        head @ (Head::Array(_) | Head::Map(_)) => {
+            if !reader.skip_body(head)? {
+                return Ok(StreamField::Nested(Cow::Borrowed(reader.since(start))));
+            }
+            // Tags inside: decode the subtree so they are mapped, then re-encode.
+            reader.seek(start);
+            StreamField::from_value(reader.read_value()?)?
+        }
+        Head::Bytes(_) | Head::Tag(_) => {
+            reader.seek(start);
+            StreamField::from_value(reader.read_value()?)?
+        }
+        Head::Break => return Err(RecordError::CborError("unexpected break".into())),
+    })
+}
+
+impl StreamField<'_> {
+    fn from_value(value: SpookyValue) -> Result<Self, RecordError> {
+        Ok(match value {
+            SpookyValue::Null => StreamField::Null,
+            SpookyValue::Bool(b) => StreamField::Bool(b),
+            SpookyValue::Number(n) => StreamField::Number(n),
+            SpookyValue::Str(s) => StreamField::Str(Cow::Owned(s.into())),
+            nested => StreamField::Nested(Cow::Owned(cbor::to_vec(&nested)?)),
+        })
+    }
+}
+
+/// Serialize JSON object text into the hybrid binary format, for producers
+/// (REST handlers, webhooks) that never had CBOR.
+///
+/// Members are parsed one at a time and written as fields; no object map is
+/// built. Numbers follow the CBOR rules: integers become I64 when they fit,
+/// else U64; fractions, exponents and larger integers become F64. Nested
+/// arrays and objects are stored as CBOR, as from every other input type.
+/// Duplicate keys: the last one wins. Returns `(buf, field_count)`.
+pub fn from_json_str(text: &str) -> Result<(Vec<u8>, usize), RecordError> {
+    let mut buf = Vec::new();
+    let field_count = from_json_str_into(text, &mut buf)?;
+    Ok((buf, field_count))
+}
+
+/// `from_json_str` into a reusable buffer, like `from_cbor_slice_into`. The
+/// buffer is cleared but retains its capacity. Returns the field count.
+pub fn from_json_str_into(text: &str, buf: &mut Vec<u8>) -> Result<usize, RecordError> {
+    let mut fields: ArrayVec<(u64, SpookyValue), 32> = ArrayVec::new();
+    crate::json::for_each_member(text, |key, value| {
+        let hash = xxh64(key.as_bytes(), 0);
+        match fields.iter_mut().find(|(h, _)| *h == hash) {
+            Some(slot) => slot.1 = value,
+            None => fields
+                .try_push((hash, value))
+                .map_err(|_| RecordError::TooManyFields)?,
+        }
+        Ok(())
+    })?;
+
+    let field_count = fields.len();
+    let mut entries: ArrayVec<(&SpookyValue, u64), 32> =
+        fields.iter().map(|(hash, value)| (value, *hash)).collect();
+    buf.clear();
+    buf.resize(HEADER_SIZE + field_count * INDEX_ENTRY_SIZE, 0);
+    write_entries(buf, &mut entries, field_count)?;
+    Ok(field_count)
+}
+
+/// `from_json_str` over JSON text read to the end of `reader`, e.g. a
+/// request body. Read failures are reported as a `JsonError` at offset 0.
+pub fn from_json_reader(mut reader: impl std::io::Read) -> Result<(Vec<u8>, usize), RecordError> {
+    let mut text = String::new();
+    if let Err(e) = reader.read_to_string(&mut text) {
+        let message = match e.kind() {
+            std::io::ErrorKind::InvalidData => "invalid UTF-8",
+            _ => "I/O error reading input",
+        };
+        return Err(RecordError::JsonError(JsonError { offset: 0, message }));
+    }
+    from_json_str(&text)
+}
+
+/// Serialize an rmpv::Value::Map into the hybrid binary format.
+///
+/// MessagePack counterpart of `from_cbor`: flat fields are written natively,
+/// nested arrays/maps are re-encoded as CBOR like every other input type.
+#[cfg(feature = "msgpack")]
+pub fn from_msgpack(data: &rmpv::Value) -> Result<(Vec<u8>, usize), RecordError> {
+    let entries = match data {
+        rmpv::Value::Map(entries) => entries,
+        _ => return Err(RecordError::InvalidBuffer),
+    };
+
+    let mut map = FastMap::new();
+    for (k, v) in entries {
+        let key_str = match k.as_str() {
+            Some(s) => SmolStr::from(s),
+            None => return Err(RecordError::MsgpackError("Key must be a string".into())),
+        };
+        map.insert(key_str, v.clone());
+    }
+
+    serialize(&map)
+}
+
+/// Decode MessagePack bytes holding a map and serialize it into the hybrid binary format.
+#[cfg(feature = "msgpack")]
+pub fn from_msgpack_slice(mut bytes: &[u8]) -> Result<(Vec<u8>, usize), RecordError> {
+    let value = rmpv::decode::read_value(&mut bytes)
+        .map_err(|e| RecordError::MsgpackError(e.to_string()))?;
+    from_msgpack(&value)
+}
+
+/// Serialize a BSON document into the hybrid binary format.
+///
+/// BSON counterpart of `from_msgpack`: each field goes through the
+/// `SpookyValue` conversion (object ids → hex text, datetimes → I64
+/// nanoseconds, see `spooky_value`) and is written like any other input.
+#[cfg(feature = "bson")]
+pub fn from_bson(doc: &bson::Document) -> Result<(Vec<u8>, usize), RecordError> {
+    let map: FastMap<SmolStr, SpookyValue> = doc
+        .iter()
+        .map(|(k, v)| (SmolStr::from(k), SpookyValue::from(v.clone())))
+        .collect();
+    serialize(&map)
+}
+
+/// Decode a BSON document from bytes and serialize it into the hybrid binary format.
+#[cfg(feature = "bson")]
+pub fn from_bson_slice(bytes: &[u8]) -> Result<(Vec<u8>, usize), RecordError> {
+    let doc = bson::Document::from_reader(bytes)
+        .map_err(|e| RecordError::BsonError(e.to_string()))?;
+    from_bson(&doc)
+}
+
 /// Create a mutable record by taking ownership of an existing serialized buffer.
 ///
 /// The buffer **must** have a sorted index (produced by `serialize_record()`,
@@ -444,7 +851,7 @@ pub fn from_bytes(buf: &[u8]) -> Result<(&[u8], usize), RecordError> {
 ///
 /// **IMPORTANT**: The index is sorted by name_hash.
 pub fn serialize_into<V: RecordSerialize>(
-    map: &BTreeMap<SmolStr, V>,
+    map: &FastMap<SmolStr, V>,
     buf: &mut Vec<u8>,
 ) -> Result<usize, RecordError> {
     let field_count = map.len();
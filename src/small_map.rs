@@ -0,0 +1,534 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, btree_map};
+use std::hash::{Hash, Hasher};
+
+// ─── SmallMap ───────────────────────────────────────────────────────────────
+//
+// Ordered map that keeps up to INLINE_CAP entries in one sorted Vec and only
+// upgrades to a BTreeMap beyond that. Records are usually 5–15 fields, so the
+// common case is a single allocation with binary search instead of a tree of
+// nodes. Iteration order is always ascending by key, exactly like BTreeMap,
+// so serialization output and Ord/Hash results do not depend on the mode.
+
+/// Entries held in the sorted Vec before upgrading to a tree.
+pub const INLINE_CAP: usize = 8;
+
+#[derive(Clone)]
+pub struct SmallMap<K, V> {
+    repr: Repr<K, V>,
+}
+
+#[derive(Clone)]
+enum Repr<K, V> {
+    Inline(Vec<(K, V)>),
+    Tree(BTreeMap<K, V>),
+}
+
+impl<K, V> SmallMap<K, V> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            repr: Repr::Inline(Vec::new()),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Inline(v) => v.len(),
+            Repr::Tree(t) => t.len(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// True while the map is still in its sorted-Vec representation.
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Inline(_))
+    }
+
+    /// Remove all entries and return to the inline representation.
+    pub fn clear(&mut self) {
+        match &mut self.repr {
+            Repr::Inline(v) => v.clear(),
+            Repr::Tree(_) => self.repr = Repr::Inline(Vec::new()),
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        match &self.repr {
+            Repr::Inline(v) => Iter::Inline(v.iter()),
+            Repr::Tree(t) => Iter::Tree(t.iter()),
+        }
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        match &mut self.repr {
+            Repr::Inline(v) => IterMut::Inline(v.iter_mut()),
+            Repr::Tree(t) => IterMut::Tree(t.iter_mut()),
+        }
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.iter().map(|(k, _)| k)
+    }
+
+    #[inline]
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.iter().map(|(_, v)| v)
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut V> + ExactSizeIterator {
+        self.iter_mut().map(|(_, v)| v)
+    }
+}
+
+impl<K: Ord, V> SmallMap<K, V> {
+    #[inline]
+    fn search<Q>(v: &[(K, V)], key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        v.binary_search_by(|(k, _)| k.borrow().cmp(key))
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match &self.repr {
+            Repr::Inline(v) => Self::search(v, key).ok().map(|i| &v[i].1),
+            Repr::Tree(t) => t.get(key),
+        }
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match &self.repr {
+            Repr::Inline(v) => Self::search(v, key).ok().map(|i| (&v[i].0, &v[i].1)),
+            Repr::Tree(t) => t.get_key_value(key),
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Inline(v) => match Self::search(v, key) {
+                Ok(i) => Some(&mut v[i].1),
+                Err(_) => None,
+            },
+            Repr::Tree(t) => t.get_mut(key),
+        }
+    }
+
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Insert or replace. Returns the previous value, like `BTreeMap::insert`.
+    /// Upgrades to the tree representation when the Vec would exceed `INLINE_CAP`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match &mut self.repr {
+            Repr::Inline(v) => match Self::search(v, &key) {
+                Ok(i) => Some(std::mem::replace(&mut v[i].1, value)),
+                Err(i) if v.len() < INLINE_CAP => {
+                    v.insert(i, (key, value));
+                    None
+                }
+                Err(_) => {
+                    let mut tree: BTreeMap<K, V> = std::mem::take(v).into_iter().collect();
+                    tree.insert(key, value);
+                    self.repr = Repr::Tree(tree);
+                    None
+                }
+            },
+            Repr::Tree(t) => t.insert(key, value),
+        }
+    }
+
+    /// Remove a key. A tree that shrinks back to `INLINE_CAP / 2` entries is
+    /// demoted to the Vec form; the gap avoids flapping at the boundary.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Inline(v) => Self::search(v, key).ok().map(|i| v.remove(i).1),
+            Repr::Tree(t) => {
+                let old = t.remove(key);
+                if t.len() <= INLINE_CAP / 2 {
+                    let v = std::mem::take(t).into_iter().collect();
+                    self.repr = Repr::Inline(v);
+                }
+                old
+            }
+        }
+    }
+
+    /// Keep only entries for which `f` returns true.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        match &mut self.repr {
+            Repr::Inline(v) => v.retain_mut(|(k, val)| f(k, val)),
+            Repr::Tree(t) => t.retain(|k, val| f(k, val)),
+        }
+    }
+}
+
+// ─── Iterators ──────────────────────────────────────────────────────────────
+
+pub enum Iter<'a, K, V> {
+    Inline(std::slice::Iter<'a, (K, V)>),
+    Tree(btree_map::Iter<'a, K, V>),
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::Inline(it) => it.next().map(|(k, v)| (k, v)),
+            Iter::Tree(it) => it.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Iter::Inline(it) => it.size_hint(),
+            Iter::Tree(it) => it.size_hint(),
+        }
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::Inline(it) => it.next_back().map(|(k, v)| (k, v)),
+            Iter::Tree(it) => it.next_back(),
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+pub enum IterMut<'a, K, V> {
+    Inline(std::slice::IterMut<'a, (K, V)>),
+    Tree(btree_map::IterMut<'a, K, V>),
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IterMut::Inline(it) => it.next().map(|(k, v)| (&*k, v)),
+            IterMut::Tree(it) => it.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IterMut::Inline(it) => it.size_hint(),
+            IterMut::Tree(it) => it.size_hint(),
+        }
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            IterMut::Inline(it) => it.next_back().map(|(k, v)| (&*k, v)),
+            IterMut::Tree(it) => it.next_back(),
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+pub enum IntoIter<K, V> {
+    Inline(std::vec::IntoIter<(K, V)>),
+    Tree(btree_map::IntoIter<K, V>),
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IntoIter::Inline(it) => it.next(),
+            IntoIter::Tree(it) => it.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IntoIter::Inline(it) => it.size_hint(),
+            IntoIter::Tree(it) => it.size_hint(),
+        }
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            IntoIter::Inline(it) => it.next_back(),
+            IntoIter::Tree(it) => it.next_back(),
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> IntoIterator for SmallMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    #[inline]
+    fn into_iter(self) -> IntoIter<K, V> {
+        match self.repr {
+            Repr::Inline(v) => IntoIter::Inline(v.into_iter()),
+            Repr::Tree(t) => IntoIter::Tree(t.into_iter()),
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a SmallMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut SmallMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+// ─── Construction ───────────────────────────────────────────────────────────
+
+impl<K, V> Default for SmallMap<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for SmallMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for SmallMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: Ord, V> From<BTreeMap<K, V>> for SmallMap<K, V> {
+    /// Already sorted and unique — no re-insertion needed.
+    fn from(tree: BTreeMap<K, V>) -> Self {
+        let repr = if tree.len() <= INLINE_CAP {
+            Repr::Inline(tree.into_iter().collect())
+        } else {
+            Repr::Tree(tree)
+        };
+        Self { repr }
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for SmallMap<K, V> {
+    fn from(arr: [(K, V); N]) -> Self {
+        arr.into_iter().collect()
+    }
+}
+
+impl<K: Ord, V> From<SmallMap<K, V>> for BTreeMap<K, V> {
+    fn from(map: SmallMap<K, V>) -> Self {
+        match map.repr {
+            Repr::Inline(v) => v.into_iter().collect(),
+            Repr::Tree(t) => t,
+        }
+    }
+}
+
+impl<K, Q, V> std::ops::Index<&Q> for SmallMap<K, V>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    type Output = V;
+
+    /// Panics if the key is absent, like `BTreeMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+// ─── Comparison / Hash / Debug ──────────────────────────────────────────────
+//
+// All defined over the sorted iteration order, so an inline map and a tree
+// map with the same entries are indistinguishable.
+
+impl<K: PartialEq, V: PartialEq> PartialEq for SmallMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for SmallMap<K, V> {}
+
+impl<K: PartialOrd, V: PartialOrd> PartialOrd for SmallMap<K, V> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord> Ord for SmallMap<K, V> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, V: Hash> Hash for SmallMap<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for (k, v) in self {
+            k.hash(state);
+            v.hash(state);
+        }
+    }
+}
+
+impl<K: std::fmt::Debug, V: std::fmt::Debug> std::fmt::Debug for SmallMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_until_cap_then_tree() {
+        let mut m = SmallMap::new();
+        for i in (0..INLINE_CAP as u32).rev() {
+            m.insert(i, i * 10);
+        }
+        assert!(m.is_inline());
+        assert_eq!(
+            m.keys().copied().collect::<Vec<_>>(),
+            (0..INLINE_CAP as u32).collect::<Vec<_>>()
+        );
+
+        m.insert(100, 0);
+        assert!(!m.is_inline());
+        assert_eq!(m.len(), INLINE_CAP + 1);
+        assert_eq!(m.get(&3), Some(&30));
+    }
+
+    #[test]
+    fn test_insert_replace_and_remove() {
+        let mut m: SmallMap<String, i32> = SmallMap::new();
+        assert_eq!(m.insert("b".into(), 1), None);
+        assert_eq!(m.insert("b".into(), 2), Some(1));
+        assert_eq!(m.get("b"), Some(&2));
+        *m.get_mut("b").unwrap() += 1;
+        assert_eq!(m["b"], 3);
+        assert_eq!(m.remove("b"), Some(3));
+        assert_eq!(m.remove("b"), None);
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_tree_demotes_after_shrinking() {
+        let mut m: SmallMap<u32, ()> = (0..20).map(|i| (i, ())).collect();
+        assert!(!m.is_inline());
+        for i in 0..16 {
+            m.remove(&i);
+        }
+        assert!(m.is_inline());
+        assert_eq!(m.keys().copied().collect::<Vec<_>>(), vec![16, 17, 18, 19]);
+    }
+
+    #[test]
+    fn test_representation_does_not_affect_eq_ord_hash() {
+        use std::hash::DefaultHasher;
+        let small: SmallMap<u32, u32> = (0..5).map(|i| (i, i)).collect();
+        // Tree of five: above the demotion threshold, so it stays a tree.
+        let mut big: SmallMap<u32, u32> = (0..12).map(|i| (i, i)).collect();
+        for i in 5..12 {
+            big.remove(&i);
+        }
+        assert!(!big.is_inline());
+        let tree_five = SmallMap::from(BTreeMap::from_iter((0..5).map(|i| (i, i))));
+        for other in [&big, &tree_five] {
+            assert_eq!(&small, other);
+            assert_eq!(small.cmp(other), Ordering::Equal);
+            let (mut a, mut b) = (DefaultHasher::new(), DefaultHasher::new());
+            small.hash(&mut a);
+            other.hash(&mut b);
+            assert_eq!(a.finish(), b.finish());
+        }
+    }
+
+    #[test]
+    fn test_btreemap_conversions_and_retain() {
+        let tree: BTreeMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        let mut m = SmallMap::from(tree.clone());
+        assert!(!m.is_inline());
+        m.retain(|k, _| k % 2 == 0);
+        assert_eq!(m.len(), 5);
+        let back: BTreeMap<u32, u32> = m.into();
+        assert_eq!(
+            back.keys().copied().collect::<Vec<_>>(),
+            vec![0, 2, 4, 6, 8]
+        );
+    }
+}
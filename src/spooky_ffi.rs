@@ -0,0 +1,446 @@
+//! C ABI for embedding from C, C++ or Swift hosts.
+//!
+//! A database is an opaque `SpookyDb *` from `spooky_db_open`, released with
+//! `spooky_db_close`. Every other function returns a status code: `SPOOKY_OK`
+//! (0), `SPOOKY_NOT_FOUND` (1) when the record or field is absent or of
+//! another type, or a negative `SPOOKY_ERR_*`. After an error,
+//! `spooky_last_error` describes it. A panic inside the library does not
+//! unwind into the host: the call returns `SPOOKY_ERR_INTERNAL` instead.
+//!
+//! Record reads take the record bytes as a pointer and length, e.g. from
+//! `spooky_db_get`, and copy nothing; strings come back as pointer and
+//! length into those bytes, not NUL-terminated.
+//!
+//! ```c
+//! SpookyDb *db;
+//! if (spooky_db_open("app.redb", &db) != SPOOKY_OK) {
+//!     fprintf(stderr, "%s\n", spooky_last_error());
+//! }
+//! uint8_t *bytes; size_t len; int64_t age;
+//! if (spooky_db_get(db, "users", "ann", &bytes, &len) == SPOOKY_OK) {
+//!     spooky_record_get_i64(bytes, len, "age", &age);
+//!     spooky_bytes_free(bytes, len);
+//! }
+//! spooky_db_close(db);
+//! ```
+//!
+//! # Safety
+//!
+//! Strings are NUL-terminated UTF-8. Pointers must be valid for the stated
+//! lengths and out-pointers writable; a null pointer is reported as
+//! `SPOOKY_ERR_ARG` rather than dereferenced. A `SpookyDb *` must not be
+//! used from two threads at once or after `spooky_db_close`.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::db::db::validate_key;
+use crate::db::{Operation, SpookyDb};
+use crate::serialization::from_bytes;
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+
+pub const SPOOKY_OK: i32 = 0;
+pub const SPOOKY_NOT_FOUND: i32 = 1;
+/// A null pointer, invalid UTF-8, an unknown operation, or a table and id
+/// too long for a key.
+pub const SPOOKY_ERR_ARG: i32 = -1;
+/// Storage, validation or constraint failure from the database.
+pub const SPOOKY_ERR_DB: i32 = -2;
+/// Malformed record bytes.
+pub const SPOOKY_ERR_RECORD: i32 = -3;
+/// A bug: the library panicked. A database handle used in the call may be
+/// left mid-operation; close it.
+pub const SPOOKY_ERR_INTERNAL: i32 = -4;
+
+pub const SPOOKY_OP_CREATE: i32 = 0;
+pub const SPOOKY_OP_UPDATE: i32 = 1;
+pub const SPOOKY_OP_DELETE: i32 = 2;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(code: i32, msg: impl Display) -> i32 {
+    let msg = CString::new(msg.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+    code
+}
+
+/// Unwrap a result, or record its error and return the status code.
+macro_rules! ffi_try {
+    ($expr:expr, $code:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(e) => return fail($code, e),
+        }
+    };
+}
+
+/// Run `body`, turning a panic into `SPOOKY_ERR_INTERNAL` so it never
+/// unwinds across the C boundary.
+fn guard(body: impl FnOnce() -> i32) -> i32 {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|panic| {
+        let msg = match panic.downcast_ref::<&str>() {
+            Some(msg) => msg,
+            None => panic.downcast_ref::<String>().map_or("unknown", String::as_str),
+        };
+        fail(SPOOKY_ERR_INTERNAL, format!("panic: {msg}"))
+    })
+}
+
+unsafe fn text<'a>(ptr: *const c_char, what: &str) -> Result<&'a str, String> {
+    if ptr.is_null() {
+        return Err(format!("{what} is null"));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|_| format!("{what} is not UTF-8"))
+}
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], String> {
+    match ptr.is_null() {
+        true if len == 0 => Ok(&[]),
+        true => Err("data is null".into()),
+        false => Ok(unsafe { std::slice::from_raw_parts(ptr, len) }),
+    }
+}
+
+/// The message of the last error on this thread, or null if none. Valid
+/// until the next failing call on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn spooky_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |msg| msg.as_ptr()))
+}
+
+// ─── Database ────────────────────────────────────────────────────────────────
+
+/// Open or create the database at `path` and store its handle in `*out`.
+///
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_db_open(path: *const c_char, out: *mut *mut SpookyDb) -> i32 {
+    guard(|| {
+        let path = ffi_try!(unsafe { text(path, "path") }, SPOOKY_ERR_ARG);
+        if out.is_null() {
+            return fail(SPOOKY_ERR_ARG, "out is null");
+        }
+        let db = ffi_try!(SpookyDb::new(path), SPOOKY_ERR_DB);
+        unsafe { *out = Box::into_raw(Box::new(db)) };
+        SPOOKY_OK
+    })
+}
+
+/// Close a handle from `spooky_db_open`. Null is ignored.
+///
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_db_close(db: *mut SpookyDb) {
+    if !db.is_null() {
+        guard(|| {
+            drop(unsafe { Box::from_raw(db) });
+            SPOOKY_OK
+        });
+    }
+}
+
+/// Apply one mutation, as `SpookyDb::apply_mutation`. `op` is a
+/// `SPOOKY_OP_*`; `data` is the record bytes (ignored for a delete) and
+/// `version` may be null.
+///
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_db_apply(
+    db: *mut SpookyDb,
+    table: *const c_char,
+    op: i32,
+    id: *const c_char,
+    data: *const u8,
+    len: usize,
+    version: *const u64,
+) -> i32 {
+    guard(|| {
+        let Some(db) = (unsafe { db.as_mut() }) else {
+            return fail(SPOOKY_ERR_ARG, "db is null");
+        };
+        let table = ffi_try!(unsafe { text(table, "table") }, SPOOKY_ERR_ARG);
+        let id = ffi_try!(unsafe { text(id, "id") }, SPOOKY_ERR_ARG);
+        ffi_try!(validate_key(table, id), SPOOKY_ERR_ARG);
+        let op = match op {
+            SPOOKY_OP_CREATE => Operation::Create,
+            SPOOKY_OP_UPDATE => Operation::Update,
+            SPOOKY_OP_DELETE => Operation::Delete,
+            other => return fail(SPOOKY_ERR_ARG, format!("unknown operation {other}")),
+        };
+        let data = match op {
+            Operation::Delete => None,
+            _ => Some(ffi_try!(unsafe { bytes(data, len) }, SPOOKY_ERR_ARG)),
+        };
+        let version = unsafe { version.as_ref() }.copied();
+        ffi_try!(db.apply_mutation(table, op, id, data, version), SPOOKY_ERR_DB);
+        SPOOKY_OK
+    })
+}
+
+/// Copy record `id` of `table` into a new buffer at `*out`, `*out_len`
+/// bytes long, to be released with `spooky_bytes_free`.
+///
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_db_get(
+    db: *const SpookyDb,
+    table: *const c_char,
+    id: *const c_char,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> i32 {
+    guard(|| {
+        let Some(db) = (unsafe { db.as_ref() }) else {
+            return fail(SPOOKY_ERR_ARG, "db is null");
+        };
+        let table = ffi_try!(unsafe { text(table, "table") }, SPOOKY_ERR_ARG);
+        let id = ffi_try!(unsafe { text(id, "id") }, SPOOKY_ERR_ARG);
+        ffi_try!(validate_key(table, id), SPOOKY_ERR_ARG);
+        if out.is_null() || out_len.is_null() {
+            return fail(SPOOKY_ERR_ARG, "out is null");
+        }
+        let Some(record) = ffi_try!(db.get_record_bytes(table, id), SPOOKY_ERR_DB) else {
+            return SPOOKY_NOT_FOUND;
+        };
+        let record = Box::into_raw(record.into_boxed_slice());
+        unsafe {
+            *out_len = record.len();
+            *out = record.cast();
+        }
+        SPOOKY_OK
+    })
+}
+
+/// Release a buffer from `spooky_db_get`. Null is ignored.
+///
+/// # Safety
+///
+/// `ptr` and `len` must be exactly as returned, and freed once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_bytes_free(ptr: *mut u8, len: usize) {
+    if !ptr.is_null() {
+        guard(|| {
+            drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+            SPOOKY_OK
+        });
+    }
+}
+
+// ─── Record Fields ───────────────────────────────────────────────────────────
+
+/// Run `read` on the record in `data[..len]` and store what it finds in
+/// `*out`: `SPOOKY_NOT_FOUND` if it finds nothing. Every record getter goes
+/// through here, so this is where their panics are caught.
+unsafe fn read_field<T>(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    out: *mut T,
+    read: impl FnOnce(&SpookyRecord<'_>, &str) -> Option<T>,
+) -> i32 {
+    guard(|| {
+        let data = ffi_try!(unsafe { bytes(data, len) }, SPOOKY_ERR_ARG);
+        let name = ffi_try!(unsafe { text(name, "name") }, SPOOKY_ERR_ARG);
+        if out.is_null() {
+            return fail(SPOOKY_ERR_ARG, "out is null");
+        }
+        let (buf, count) = ffi_try!(from_bytes(data), SPOOKY_ERR_RECORD);
+        match read(&SpookyRecord::new(buf, count), name) {
+            Some(value) => {
+                unsafe { out.write(value) };
+                SPOOKY_OK
+            }
+            None => SPOOKY_NOT_FOUND,
+        }
+    })
+}
+
+/// The field's type tag (`TAG_*` in `spooky_db_module::types`).
+///
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_record_field_type(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    out: *mut u8,
+) -> i32 {
+    unsafe { read_field(data, len, name, out, |r, name| r.field_type(name)) }
+}
+
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_record_get_i64(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    out: *mut i64,
+) -> i32 {
+    unsafe { read_field(data, len, name, out, |r, name| r.get_i64(name)) }
+}
+
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_record_get_u64(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    out: *mut u64,
+) -> i32 {
+    unsafe { read_field(data, len, name, out, |r, name| r.get_u64(name)) }
+}
+
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_record_get_f64(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    out: *mut f64,
+) -> i32 {
+    unsafe { read_field(data, len, name, out, |r, name| r.get_f64(name)) }
+}
+
+/// # Safety
+///
+/// See the module docs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_record_get_bool(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    out: *mut bool,
+) -> i32 {
+    unsafe { read_field(data, len, name, out, |r, name| r.get_bool(name)) }
+}
+
+/// A string field as `*out` and `*out_len`, pointing into `data`.
+///
+/// # Safety
+///
+/// See the module docs. `*out` lives as long as `data`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spooky_record_get_str(
+    data: *const u8,
+    len: usize,
+    name: *const c_char,
+    out: *mut *const u8,
+    out_len: *mut usize,
+) -> i32 {
+    if out_len.is_null() {
+        return fail(SPOOKY_ERR_ARG, "out_len is null");
+    }
+    let read = |r: &SpookyRecord<'_>, name: &str| {
+        let s = r.get_str(name)?;
+        unsafe { *out_len = s.len() };
+        Some(s.as_ptr())
+    };
+    unsafe { read_field(data, len, name, out, read) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::db::MAX_KEY_LEN;
+    use crate::spooky_value::SpookyValue;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_open_apply_get_and_read_fields() {
+        let tmp = NamedTempFile::new().unwrap();
+        let path = CString::new(tmp.path().to_str().unwrap()).unwrap();
+        let value = SpookyValue::from_json_str(r#"{"name":"Ann","age":40}"#).unwrap();
+        let (record, _) = crate::serialization::from_spooky(&value).unwrap();
+        let (users, ann) = (c"users", c"ann");
+
+        unsafe {
+            let mut db = std::ptr::null_mut();
+            assert_eq!(spooky_db_open(path.as_ptr(), &mut db), SPOOKY_OK);
+            let apply_to = |table: &CStr, op, data: &[u8]| {
+                let (table, id) = (table.as_ptr(), ann.as_ptr());
+                spooky_db_apply(db, table, op, id, data.as_ptr(), data.len(), &7)
+            };
+            let apply = |op, data: &[u8]| apply_to(users, op, data);
+            assert_eq!(apply(SPOOKY_OP_CREATE, &record), SPOOKY_OK);
+            assert_eq!(apply_to(c"bad\x1ftable", SPOOKY_OP_CREATE, &record), SPOOKY_ERR_DB);
+            assert!(!spooky_last_error().is_null());
+            assert_eq!(apply(9, &record), SPOOKY_ERR_ARG);
+
+            let (mut bytes, mut len) = (std::ptr::null_mut(), 0);
+            let status = spooky_db_get(db, users.as_ptr(), ann.as_ptr(), &mut bytes, &mut len);
+            assert_eq!(status, SPOOKY_OK);
+            let mut age = 0;
+            assert_eq!(spooky_record_get_i64(bytes, len, c"age".as_ptr(), &mut age), SPOOKY_OK);
+            assert_eq!(age, 40);
+            let (mut name, mut name_len) = (std::ptr::null(), 0);
+            let name_ptr = c"name".as_ptr();
+            let status = spooky_record_get_str(bytes, len, name_ptr, &mut name, &mut name_len);
+            assert_eq!(status, SPOOKY_OK);
+            assert_eq!(std::slice::from_raw_parts(name, name_len), b"Ann");
+            let mut flag = false;
+            let status = spooky_record_get_bool(bytes, len, c"age".as_ptr(), &mut flag);
+            assert_eq!(status, SPOOKY_NOT_FOUND);
+            spooky_bytes_free(bytes, len);
+
+            assert_eq!(apply(SPOOKY_OP_DELETE, &[]), SPOOKY_OK);
+            let status = spooky_db_get(db, users.as_ptr(), ann.as_ptr(), &mut bytes, &mut len);
+            assert_eq!(status, SPOOKY_NOT_FOUND);
+            spooky_db_close(db);
+        }
+    }
+
+    #[test]
+    fn test_long_keys_and_panics_become_status_codes() {
+        let tmp = NamedTempFile::new().unwrap();
+        let path = CString::new(tmp.path().to_str().unwrap()).unwrap();
+        let record = crate::serialization::from_spooky(&SpookyValue::from_json_str("{}").unwrap());
+        let (record, _) = record.unwrap();
+        let last_error = || unsafe { CStr::from_ptr(spooky_last_error()) }.to_str().unwrap();
+
+        unsafe {
+            let mut db = std::ptr::null_mut();
+            assert_eq!(spooky_db_open(path.as_ptr(), &mut db), SPOOKY_OK);
+            let users = c"users".as_ptr();
+            let fits = CString::new("x".repeat(MAX_KEY_LEN - 6)).unwrap();
+            let long = CString::new("x".repeat(MAX_KEY_LEN)).unwrap();
+            let apply = |id: &CString| {
+                let (data, len, version) = (record.as_ptr(), record.len(), std::ptr::null());
+                spooky_db_apply(db, users, SPOOKY_OP_CREATE, id.as_ptr(), data, len, version)
+            };
+            assert_eq!(apply(&fits), SPOOKY_OK);
+            assert_eq!(apply(&long), SPOOKY_ERR_ARG);
+            assert!(last_error().contains("518 bytes"));
+            let (mut bytes, mut len) = (std::ptr::null_mut(), 0);
+            let status = spooky_db_get(db, users, long.as_ptr(), &mut bytes, &mut len);
+            assert_eq!(status, SPOOKY_ERR_ARG);
+            spooky_db_close(db);
+        }
+
+        assert_eq!(guard(|| panic!("boom")), SPOOKY_ERR_INTERNAL);
+        assert_eq!(last_error(), "panic: boom");
+        assert_eq!(guard(|| panic!("{}", 42)), SPOOKY_ERR_INTERNAL);
+        assert_eq!(last_error(), "panic: 42");
+    }
+}
@@ -0,0 +1,229 @@
+//! serde `Deserializer` over record bytes.
+//!
+//! [`from_record`] reads a record straight into a struct: each field the
+//! struct declares is looked up by name hash, scalars are handed to the
+//! visitor as stored (strings borrowed from the record), and nested fields
+//! are decoded from their CBOR. No `SpookyValue` is built on the way.
+//!
+//! Records keep field-name hashes, not names, so the target must tell serde
+//! which names to look for: a struct with named fields works, a map or
+//! `#[serde(flatten)]` does not. A declared field the record lacks is
+//! missing for serde too; use `Option` or `#[serde(default)]` for it.
+//!
+//! Errors carry the top-level field they occurred in:
+//!
+//! ```rust,ignore
+//! #[derive(Deserialize)]
+//! struct User<'a> { name: &'a str, age: u32 }
+//! let user: User = from_record(&record)?;
+//! // Err: cannot deserialize record field "age": invalid value: integer `-1`, ...
+//! ```
+
+use std::convert::Infallible;
+
+use serde::de::value::{BorrowedStrDeserializer, StrDeserializer};
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, Visitor};
+
+use super::SpookyReadable;
+use crate::error::RecordError;
+use crate::spooky_value::SpookyNumber;
+use crate::types::{FieldRef, TAG_BLOB_REF};
+use crate::value_ref::SpookyValueRef;
+
+type CborDeserializer<'de> = cbor4ii::serde::Deserializer<cbor4ii::core::utils::SliceReader<'de>>;
+
+/// Deserialize `record` into `T`. See the module docs.
+pub fn from_record<'de, T: Deserialize<'de>, R: SpookyReadable>(
+    record: &'de R,
+) -> Result<T, RecordError> {
+    T::deserialize(RecordDeserializer::new(record))
+}
+
+/// The record-level `Deserializer` behind [`from_record`].
+pub struct RecordDeserializer<'de, R> {
+    record: &'de R,
+}
+
+impl<'de, R: SpookyReadable> RecordDeserializer<'de, R> {
+    pub fn new(record: &'de R) -> Self {
+        Self { record }
+    }
+}
+
+impl<'de, R: SpookyReadable> Deserializer<'de> for RecordDeserializer<'de, R> {
+    type Error = RecordError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, RecordError> {
+        Err(de::Error::custom(
+            "records store field-name hashes; deserialize into a struct with named fields",
+        ))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RecordError> {
+        visitor.visit_map(Fields {
+            record: self.record,
+            names: fields.iter(),
+            pending: None,
+        })
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, RecordError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RecordError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf option unit unit_struct seq tuple tuple_struct map enum identifier
+    }
+}
+
+/// The declared fields present in the record, in declaration order.
+struct Fields<'de, R> {
+    record: &'de R,
+    names: std::slice::Iter<'static, &'static str>,
+    /// The field whose key was just returned.
+    pending: Option<(&'static str, FieldRef<'de>)>,
+}
+
+impl<'de, R: SpookyReadable> MapAccess<'de> for Fields<'de, R> {
+    type Error = RecordError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, RecordError> {
+        for &name in self.names.by_ref() {
+            if let Some(raw) = self.record.get_raw(name) {
+                self.pending = Some((name, raw));
+                return seed.deserialize(BorrowedStrDeserializer::new(name)).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, RecordError> {
+        let (name, raw) = self
+            .pending
+            .take()
+            .ok_or_else(|| <RecordError as de::Error>::custom("value requested before its key"))?;
+        seed.deserialize(FieldDeserializer(raw))
+            .map_err(|e| e.at_field(name))
+    }
+}
+
+/// One field's value.
+struct FieldDeserializer<'de>(FieldRef<'de>);
+
+impl<'de> FieldDeserializer<'de> {
+    fn value(&self) -> SpookyValueRef<'de> {
+        SpookyValueRef::from_field(self.0)
+    }
+}
+
+fn nested<'de, T>(
+    bytes: &'de [u8],
+    read: impl FnOnce(&mut CborDeserializer<'de>) -> Result<T, cbor4ii::serde::DecodeError<Infallible>>,
+) -> Result<T, RecordError> {
+    let mut cbor = cbor4ii::serde::Deserializer::new(cbor4ii::core::utils::SliceReader::new(bytes));
+    read(&mut cbor).map_err(de::Error::custom)
+}
+
+/// Hand a `deserialize_*` hint on to the CBOR deserializer for nested
+/// fields; scalars go through `deserialize_any`.
+macro_rules! forward_nested {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {$(
+        fn $method<V: Visitor<'de>>(
+            self,
+            $($arg: $ty,)*
+            visitor: V,
+        ) -> Result<V::Value, RecordError> {
+            match self.value() {
+                SpookyValueRef::Nested(n) => nested(n.as_bytes(), |cbor| cbor.$method($($arg,)* visitor)),
+                _ => self.deserialize_any(visitor),
+            }
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for FieldDeserializer<'de> {
+    type Error = RecordError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RecordError> {
+        match self.value() {
+            SpookyValueRef::Null => visitor.visit_unit(),
+            SpookyValueRef::Bool(b) => visitor.visit_bool(b),
+            SpookyValueRef::Number(SpookyNumber::I64(i)) => visitor.visit_i64(i),
+            SpookyValueRef::Number(SpookyNumber::U64(u)) => visitor.visit_u64(u),
+            SpookyValueRef::Number(SpookyNumber::F64(f)) => visitor.visit_f64(f),
+            SpookyValueRef::Str(s) => visitor.visit_borrowed_str(s),
+            SpookyValueRef::Nested(n) => nested(n.as_bytes(), |cbor| cbor.deserialize_any(visitor)),
+            SpookyValueRef::Bytes(_) if self.0.type_tag == TAG_BLOB_REF => Err(de::Error::custom(
+                "stored out of line as a blob; read the record through SpookyDb",
+            )),
+            SpookyValueRef::Bytes(_) => Err(de::Error::custom(format_args!(
+                "unreadable data with type tag {}",
+                self.0.type_tag
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RecordError> {
+        match self.value() {
+            SpookyValueRef::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, RecordError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Unit variants are stored as strings, the rest as nested CBOR.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RecordError> {
+        match self.value() {
+            SpookyValueRef::Str(s) => visitor.visit_enum(StrDeserializer::new(s)),
+            SpookyValueRef::Nested(n) => {
+                nested(n.as_bytes(), |cbor| cbor.deserialize_enum(name, variants, visitor))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_nested! {
+        deserialize_seq();
+        deserialize_map();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_ignored_any();
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct identifier
+    }
+}
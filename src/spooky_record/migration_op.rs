@@ -5,11 +5,10 @@ use super::record_mut::SpookyRecordMut;
 use crate::error::RecordError;
 use crate::serialization::write_field_into;
 use crate::types::*;
-use xxhash_rust::xxh64::xxh64;
 
 impl SpookyRecordMut {
     // ════════════════════════════════════════════════════════════════════════
-    // Structural mutations — add/remove fields
+    // Structural mutations — add/remove/rename fields
     // ════════════════════════════════════════════════════════════════════════
 
     /// Add a new field. Maintains sorted index order.
@@ -17,10 +16,14 @@ impl SpookyRecordMut {
     /// Rebuilds the buffer with the new field inserted at the correct
     /// sorted position. This is simpler and less error-prone than in-place
     /// index insertion with offset fixups.
-    pub fn add_field<V: crate::serialization::RecordSerialize>(&mut self, name: &str, value: &V) -> Result<(), RecordError> {
-        let hash = xxh64(name.as_bytes(), 0);
-
-        if self.find_field(name).is_ok() {
+    pub fn add_field<V: crate::serialization::RecordSerialize>(
+        &mut self,
+        name: impl AsFieldKey,
+        value: &V,
+    ) -> Result<(), RecordError> {
+        let hash = name.field_hash();
+
+        if self.find_field_by_hash(hash).is_ok() {
             return Err(RecordError::FieldExists);
         }
 
@@ -53,7 +56,7 @@ impl SpookyRecordMut {
     /// Remove a field from the record.
     ///
     /// Rebuilds the buffer without the removed field.
-    pub fn remove_field(&mut self, name: &str) -> Result<(), RecordError> {
+    pub fn remove_field(&mut self, name: impl AsFieldKey) -> Result<(), RecordError> {
         let (remove_pos, _) = self.find_field(name)?;
         let old_n = self.field_count;
         let new_n = old_n - 1;
@@ -78,6 +81,46 @@ impl SpookyRecordMut {
         Ok(())
     }
 
+    /// Rename a field, keeping its value and type.
+    ///
+    /// The index is sorted by name hash, so the entry moves to the new
+    /// name's position; the buffer is rebuilt like `add_field`.
+    pub fn rename_field(
+        &mut self,
+        old: impl AsFieldKey,
+        new: impl AsFieldKey,
+    ) -> Result<(), RecordError> {
+        let (old_pos, meta) = self.find_field(old)?;
+        let hash = new.field_hash();
+        if self.find_field_by_hash(hash).is_ok() {
+            return Err(RecordError::FieldExists);
+        }
+        let data = self.data_buf[meta.data_offset..meta.data_offset + meta.data_len].to_vec();
+        // Position among the other fields, i.e. with the old entry removed.
+        let insert_pos = match self.find_insert_pos(hash) {
+            pos if pos > old_pos => pos - 1,
+            pos => pos,
+        };
+        let n = self.field_count;
+
+        let mut scratch = Vec::new();
+        self.rebuild_buffer_with(&mut scratch, n, n, |i| {
+            if i == insert_pos {
+                return FieldSource::New {
+                    hash,
+                    data: &data,
+                    tag: meta.type_tag,
+                };
+            }
+            let rest = if i < insert_pos { i } else { i - 1 };
+            FieldSource::Existing(if rest < old_pos { rest } else { rest + 1 })
+        })?;
+
+        self.data_buf = scratch;
+        self.generation += 1;
+        Ok(())
+    }
+
     // ════════════════════════════════════════════════════════════════════════
     // Internal: buffer rebuild helpers
     // ════════════════════════════════════════════════════════════════════════
@@ -1,11 +1,18 @@
+pub mod de;
 pub mod migration_op;
+pub mod perfect_index;
 mod read_op;
 pub mod record;
 pub mod record_mut;
+pub mod ser;
+mod simd;
 pub mod write_op;
 
+pub use de::{RecordDeserializer, from_record};
+pub use perfect_index::{IndexedRecord, PerfectIndex};
 pub use read_op::SpookyReadable;
 pub use record::SpookyRecord;
+pub use ser::{RecordSerializer, to_record, to_record_into};
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,174 @@
+//! Direct slot lookup for records with a known field set.
+//!
+//! A [`PerfectIndex`] is built once over the field names a schema declares.
+//! It maps each name hash, collision-free, to its rank among the sorted
+//! hashes. A record holding exactly those fields stores each one at its rank,
+//! so a lookup is one multiply, one table load and one hash compare instead
+//! of a binary search. A record that lacks some fields or carries extra ones
+//! fails the compare and falls back to the search, so results never differ.
+//!
+//! ```rust,ignore
+//! let index = schema.perfect_index();
+//! let record = IndexedRecord::new(SpookyRecord::new(&buf, n), &index);
+//! record.get_i64("age"); // slot lookup
+//! ```
+
+use super::SpookyRecord;
+use super::read_op::{SpookyReadable, search_index};
+use crate::error::RecordError;
+use crate::types::{AsFieldKey, FieldIter, IndexEntry};
+
+/// Marks a bucket no known field hashes to.
+const EMPTY: u8 = u8::MAX;
+
+/// Multipliers tried per table size before the table is doubled.
+const ATTEMPTS: u64 = 256;
+
+/// Minimal perfect hash from a fixed set of field-name hashes to their rank.
+/// See the module docs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerfectIndex {
+    /// The known hashes, sorted. A hash's rank is its position here.
+    hashes: Box<[u64]>,
+    /// Bucket → rank, `EMPTY` if unused.
+    table: Box<[u8]>,
+    mul: u64,
+    shift: u32,
+}
+
+impl PerfectIndex {
+    /// Build the index over `names`. Duplicates are ignored. Only the first
+    /// 254 hashes (in sorted order) get a slot; a record never holds more
+    /// than 32 fields.
+    pub fn new<K: AsFieldKey>(names: impl IntoIterator<Item = K>) -> Self {
+        let mut hashes: Vec<u64> = names.into_iter().map(|name| name.field_hash()).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        let ranked = &hashes[..hashes.len().min(EMPTY as usize)];
+
+        // Start at ≥ 2 buckets per key; collisions then stay rare enough
+        // that a few random multipliers find a perfect placement.
+        let mut bits = (ranked.len() * 2).next_power_of_two().trailing_zeros().max(1);
+        let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+        let mut table = Vec::new();
+        loop {
+            for _ in 0..ATTEMPTS {
+                seed = splitmix64(seed);
+                let mul = seed | 1;
+                let shift = 64 - bits;
+                if place(ranked, mul, shift, bits, &mut table) {
+                    return Self {
+                        hashes: hashes.into_boxed_slice(),
+                        table: table.into_boxed_slice(),
+                        mul,
+                        shift,
+                    };
+                }
+            }
+            bits += 1;
+        }
+    }
+
+    /// Number of known fields.
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// The rank of `hash` among the known hashes, or `None` if it is not
+    /// one of them.
+    #[inline]
+    pub fn rank(&self, hash: u64) -> Option<usize> {
+        let bucket = (hash.wrapping_mul(self.mul) >> self.shift) as usize;
+        let rank = self.table[bucket];
+        (rank != EMPTY && self.hashes[rank as usize] == hash).then_some(rank as usize)
+    }
+
+    /// Find a field of `record`: its ranked slot if the record has the
+    /// field there, otherwise the usual search.
+    #[inline]
+    pub fn find<R: SpookyReadable + ?Sized>(
+        &self,
+        record: &R,
+        hash: u64,
+    ) -> Result<(usize, IndexEntry), RecordError> {
+        if let Some(rank) = self.rank(hash)
+            && rank < record.field_count()
+            && record.read_hash(rank) == hash
+        {
+            return record
+                .read_index(rank)
+                .map(|meta| (rank, meta))
+                .ok_or(RecordError::InvalidBuffer);
+        }
+        search_index(record, hash)
+    }
+}
+
+/// Fill `table` with every hash's rank; `false` on a collision.
+fn place(hashes: &[u64], mul: u64, shift: u32, bits: u32, table: &mut Vec<u8>) -> bool {
+    table.clear();
+    table.resize(1 << bits, EMPTY);
+    for (rank, hash) in hashes.iter().enumerate() {
+        let bucket = (hash.wrapping_mul(mul) >> shift) as usize;
+        if table[bucket] != EMPTY {
+            return false;
+        }
+        table[bucket] = rank as u8;
+    }
+    true
+}
+
+#[inline]
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// A `SpookyRecord` read through a [`PerfectIndex`]. Every `SpookyReadable`
+/// getter uses the slot lookup.
+#[derive(Debug, Clone, Copy)]
+pub struct IndexedRecord<'a> {
+    record: SpookyRecord<'a>,
+    index: &'a PerfectIndex,
+}
+
+impl<'a> IndexedRecord<'a> {
+    #[inline]
+    pub fn new(record: SpookyRecord<'a>, index: &'a PerfectIndex) -> Self {
+        Self { record, index }
+    }
+
+    /// The plain record view.
+    #[inline]
+    pub fn record(&self) -> SpookyRecord<'a> {
+        self.record
+    }
+}
+
+impl SpookyReadable for IndexedRecord<'_> {
+    #[inline]
+    fn data_buf(&self) -> &[u8] {
+        self.record.data_buf
+    }
+
+    #[inline]
+    fn field_count(&self) -> usize {
+        self.record.field_count
+    }
+
+    #[inline]
+    fn iter_fields(&self) -> FieldIter<'_> {
+        self.record.iter_fields()
+    }
+
+    #[inline]
+    fn find_field_by_hash(&self, hash: u64) -> Result<(usize, IndexEntry), RecordError> {
+        self.index.find(&self.record, hash)
+    }
+}
@@ -1,7 +1,30 @@
+use super::simd;
 use crate::error::RecordError;
 use crate::spooky_value::SpookyValue;
 use crate::types::*;
-use xxhash_rust::xxh64::xxh64;
+use crate::value_ref::SpookyValueRef;
+
+/// Search `record`'s sorted index for `hash`: linear for up to 4 fields,
+/// bisection plus a vectorised scan above (see `simd`).
+#[inline]
+pub(crate) fn search_index<R: SpookyReadable + ?Sized>(
+    record: &R,
+    hash: u64,
+) -> Result<(usize, IndexEntry), RecordError> {
+    let n = record.field_count();
+
+    if n == 0 {
+        return Err(RecordError::FieldNotFound);
+    }
+    if n <= 4 {
+        return record.linear_hash_search(n, hash);
+    }
+    let i = simd::find_hash(record.data_buf(), n, hash).ok_or(RecordError::FieldNotFound)?;
+    record
+        .read_index(i)
+        .map(|meta| (i, meta))
+        .ok_or(RecordError::InvalidBuffer)
+}
 
 pub trait SpookyReadable {
     fn data_buf(&self) -> &[u8];
@@ -77,17 +100,16 @@ pub trait SpookyReadable {
 
     /// Find a field by name. Returns (index_position, IndexEntry).
     #[inline]
-    fn find_field(&self, name: &str) -> Result<(usize, IndexEntry), RecordError> {
-        let hash = xxh64(name.as_bytes(), 0);
-        let n = self.field_count();
+    fn find_field(&self, name: impl AsFieldKey) -> Result<(usize, IndexEntry), RecordError> {
+        self.find_field_by_hash(name.field_hash())
+    }
 
-        if n == 0 {
-            return Err(RecordError::FieldNotFound);
-        }
-        if n <= 4 {
-            return self.linear_hash_search(n, hash);
-        }
-        self.binary_hash_search(n, hash)
+    /// `find_field` with the name already hashed (see `types::field_hash`).
+    #[inline]
+    /// Every by-name and by-hash getter and setter goes through here, so an
+    /// implementor that overrides it (`IndexedRecord`) changes them all.
+    fn find_field_by_hash(&self, hash: u64) -> Result<(usize, IndexEntry), RecordError> {
+        search_index(self, hash)
     }
 
     // ════════════════════════════════════════════════════════════════════════
@@ -96,8 +118,89 @@ pub trait SpookyReadable {
 
     /// Get a string field (zero-copy).
     #[inline]
-    fn get_str(&self, name: &str) -> Option<&str> {
-        let (_, meta) = self.find_field(name).ok()?;
+    fn get_str(&self, name: impl AsFieldKey) -> Option<&str> {
+        self.get_str_by_hash(name.field_hash())
+    }
+
+    /// Get an i64 field.
+    #[inline]
+    fn get_i64(&self, name: impl AsFieldKey) -> Option<i64> {
+        self.get_i64_by_hash(name.field_hash())
+    }
+
+    /// Get a u64 field.
+    #[inline]
+    fn get_u64(&self, name: impl AsFieldKey) -> Option<u64> {
+        self.get_u64_by_hash(name.field_hash())
+    }
+
+    /// Get an f64 field.
+    #[inline]
+    fn get_f64(&self, name: impl AsFieldKey) -> Option<f64> {
+        self.get_f64_by_hash(name.field_hash())
+    }
+
+    /// Get a bool field.
+    #[inline]
+    fn get_bool(&self, name: impl AsFieldKey) -> Option<bool> {
+        self.get_bool_by_hash(name.field_hash())
+    }
+
+    /// Get raw field reference (zero-copy).
+    #[inline]
+    fn get_raw(&self, name: impl AsFieldKey) -> Option<FieldRef<'_>> {
+        self.get_raw_by_hash(name.field_hash())
+    }
+
+    /// Get any field as a value (deserializes nested CBOR if needed).
+    /// Specify the value type using turbofish syntax: `get_field::<SpookyValue>("name")`.
+    #[inline]
+    fn get_field<V: crate::deserialization::RecordDeserialize>(
+        &self,
+        name: impl AsFieldKey,
+    ) -> Option<V> {
+        let field = self.get_raw(name)?;
+        crate::deserialization::decode_field(field)
+    }
+
+    /// Get any field as a borrowed view: strings borrow the record buffer and
+    /// nested CBOR is decoded only on demand. See [`SpookyValueRef`].
+    #[inline]
+    fn get_field_ref(&self, name: impl AsFieldKey) -> Option<SpookyValueRef<'_>> {
+        self.get_raw(name).map(SpookyValueRef::from_field)
+    }
+
+    /// Get a numeric field as f64 (converting i64/u64 if needed).
+    fn get_number_as_f64(&self, name: impl AsFieldKey) -> Option<f64> {
+        self.get_number_as_f64_by_hash(name.field_hash())
+    }
+
+    /// Convert to SpookyValue (iterator-based full conversion placeholder).
+    /// Note: Keys are not recoverable from hashes in the current format.
+    fn to_value(&self) -> SpookyValue {
+        SpookyValue::Null // Placeholder as per parity plan constraint
+    }
+
+    /// Check if a field exists.
+    #[inline]
+    fn has_field(&self, name: impl AsFieldKey) -> bool {
+        self.find_field(name).is_ok()
+    }
+
+    /// Get the type tag for a field.
+    #[inline]
+    fn field_type(&self, name: impl AsFieldKey) -> Option<u8> {
+        self.find_field(name).ok().map(|(_, m)| m.type_tag)
+    }
+
+    // ════════════════════════════════════════════════════════════════════════
+    // Read access by precomputed hash — no xxh64 per call
+    // ════════════════════════════════════════════════════════════════════════
+
+    /// Get a string field by name hash (zero-copy).
+    #[inline]
+    fn get_str_by_hash(&self, hash: u64) -> Option<&str> {
+        let (_, meta) = self.find_field_by_hash(hash).ok()?;
         if meta.type_tag != TAG_STR {
             return None;
         }
@@ -105,10 +208,10 @@ pub trait SpookyReadable {
             .ok()
     }
 
-    /// Get an i64 field.
+    /// Get an i64 field by name hash.
     #[inline]
-    fn get_i64(&self, name: &str) -> Option<i64> {
-        let (_, meta) = self.find_field(name).ok()?;
+    fn get_i64_by_hash(&self, hash: u64) -> Option<i64> {
+        let (_, meta) = self.find_field_by_hash(hash).ok()?;
         if meta.type_tag != TAG_I64 || meta.data_len != 8 {
             return None;
         }
@@ -119,10 +222,10 @@ pub trait SpookyReadable {
         ))
     }
 
-    /// Get a u64 field.
+    /// Get a u64 field by name hash.
     #[inline]
-    fn get_u64(&self, name: &str) -> Option<u64> {
-        let (_, meta) = self.find_field(name).ok()?;
+    fn get_u64_by_hash(&self, hash: u64) -> Option<u64> {
+        let (_, meta) = self.find_field_by_hash(hash).ok()?;
         if meta.type_tag != TAG_U64 || meta.data_len != 8 {
             return None;
         }
@@ -133,10 +236,10 @@ pub trait SpookyReadable {
         ))
     }
 
-    /// Get an f64 field.
+    /// Get an f64 field by name hash.
     #[inline]
-    fn get_f64(&self, name: &str) -> Option<f64> {
-        let (_, meta) = self.find_field(name).ok()?;
+    fn get_f64_by_hash(&self, hash: u64) -> Option<f64> {
+        let (_, meta) = self.find_field_by_hash(hash).ok()?;
         if meta.type_tag != TAG_F64 || meta.data_len != 8 {
             return None;
         }
@@ -147,20 +250,20 @@ pub trait SpookyReadable {
         ))
     }
 
-    /// Get a bool field.
+    /// Get a bool field by name hash.
     #[inline]
-    fn get_bool(&self, name: &str) -> Option<bool> {
-        let (_, meta) = self.find_field(name).ok()?;
+    fn get_bool_by_hash(&self, hash: u64) -> Option<bool> {
+        let (_, meta) = self.find_field_by_hash(hash).ok()?;
         if meta.type_tag != TAG_BOOL || meta.data_len != 1 {
             return None;
         }
         Some(self.data_buf()[meta.data_offset] != 0)
     }
 
-    /// Get raw field reference (zero-copy).
+    /// Get a raw field reference by name hash (zero-copy).
     #[inline]
-    fn get_raw(&self, name: &str) -> Option<FieldRef<'_>> {
-        let (_, meta) = self.find_field(name).ok()?;
+    fn get_raw_by_hash(&self, hash: u64) -> Option<FieldRef<'_>> {
+        let (_, meta) = self.find_field_by_hash(hash).ok()?;
         let data = &self.data_buf()[meta.data_offset..meta.data_offset + meta.data_len];
         Some(FieldRef {
             name_hash: meta.name_hash,
@@ -169,17 +272,9 @@ pub trait SpookyReadable {
         })
     }
 
-    /// Get any field as a value (deserializes nested CBOR if needed).
-    /// Specify the value type using turbofish syntax: `get_field::<SpookyValue>("name")`.
-    #[inline]
-    fn get_field<V: crate::deserialization::RecordDeserialize>(&self, name: &str) -> Option<V> {
-        let field = self.get_raw(name)?;
-        crate::deserialization::decode_field(field)
-    }
-
-    /// Get a numeric field as f64 (converting i64/u64 if needed).
-    fn get_number_as_f64(&self, name: &str) -> Option<f64> {
-        let (_, meta) = self.find_field(name).ok()?;
+    /// Get a numeric field as f64 by name hash.
+    fn get_number_as_f64_by_hash(&self, hash: u64) -> Option<f64> {
+        let (_, meta) = self.find_field_by_hash(hash).ok()?;
         match meta.type_tag {
             TAG_F64 | TAG_I64 | TAG_U64 if meta.data_len == 8 => {}
             _ => return None,
@@ -195,22 +290,10 @@ pub trait SpookyReadable {
         }
     }
 
-    /// Convert to SpookyValue (iterator-based full conversion placeholder).
-    /// Note: Keys are not recoverable from hashes in the current format.
-    fn to_value(&self) -> SpookyValue {
-        SpookyValue::Null // Placeholder as per parity plan constraint
-    }
-
-    /// Check if a field exists.
+    /// Check if a field exists, by name hash.
     #[inline]
-    fn has_field(&self, name: &str) -> bool {
-        self.find_field(name).is_ok()
-    }
-
-    /// Get the type tag for a field.
-    #[inline]
-    fn field_type(&self, name: &str) -> Option<u8> {
-        self.find_field(name).ok().map(|(_, m)| m.type_tag)
+    fn has_field_by_hash(&self, hash: u64) -> bool {
+        self.find_field_by_hash(hash).is_ok()
     }
 
     // ════════════════════════════════════════════════════════════════════════
@@ -224,7 +307,7 @@ pub trait SpookyReadable {
     /// The returned slot is valid until a layout-changing operation
     /// (add_field, remove_field, or variable-length splice). Staleness
     /// is checked via debug assertions in all `_at` methods.
-    fn resolve(&self, name: &str) -> Option<FieldSlot> {
+    fn resolve(&self, name: impl AsFieldKey) -> Option<FieldSlot> {
         let (index_pos, meta) = self.find_field(name).ok()?;
         Some(FieldSlot {
             index_pos,
@@ -1,5 +1,9 @@
+use std::sync::Arc;
+
 use super::SpookyRecord;
-use super::read_op::SpookyReadable;
+use super::perfect_index::PerfectIndex;
+use super::read_op::{SpookyReadable, search_index};
+use crate::error::RecordError;
 use crate::types::*;
 
 pub struct SpookyRecordMut {
@@ -8,6 +12,8 @@ pub struct SpookyRecordMut {
     /// Generation counter, bumped on every layout-changing mutation.
     /// Used to detect stale FieldSlots.
     pub generation: usize,
+    /// Slot lookup for a known field set (see `with_index`).
+    index: Option<Arc<PerfectIndex>>,
 }
 
 impl SpookyRecordMut {
@@ -25,6 +31,7 @@ impl SpookyRecordMut {
             data_buf,
             field_count,
             generation: 0,
+            index: None,
         }
     }
 
@@ -36,9 +43,18 @@ impl SpookyRecordMut {
             data_buf,
             field_count: 0,
             generation: 0,
+            index: None,
         }
     }
 
+    /// Look fields up through `index` (usually `Schema::perfect_index` of
+    /// the record's table): getters and setters try the field's ranked slot
+    /// before searching. Results are the same either way.
+    pub fn with_index(mut self, index: Arc<PerfectIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
     #[inline]
     pub fn as_record(&self) -> SpookyRecord<'_> {
         SpookyRecord::new(&self.data_buf, self.field_count)
@@ -88,6 +104,14 @@ impl SpookyReadable for SpookyRecordMut {
     fn generation(&self) -> usize {
         self.generation
     }
+
+    #[inline]
+    fn find_field_by_hash(&self, hash: u64) -> Result<(usize, IndexEntry), RecordError> {
+        match &self.index {
+            Some(index) => index.find(self, hash),
+            None => search_index(self, hash),
+        }
+    }
 }
 
 
@@ -0,0 +1,492 @@
+//! serde `Serializer` that writes record bytes.
+//!
+//! [`to_record_into`] writes a struct (or a map with string keys) straight
+//! into the hybrid format: scalar fields go to their native slots, nested
+//! fields are encoded as CBOR in place. No `SpookyValue` or intermediate
+//! CBOR map is built, and the output matches `from_cbor_slice` on the same
+//! value's CBOR encoding.
+//!
+//! Unit variants are stored as strings and `None` as null, as `from_record`
+//! expects. Errors carry the top-level field they occurred in:
+//!
+//! ```rust,ignore
+//! let mut buf = Vec::new();
+//! let field_count = to_record_into(&user, &mut buf)?;
+//! let record = SpookyRecord::new(&buf, field_count);
+//! ```
+
+use std::fmt;
+
+use arrayvec::ArrayVec;
+use serde::ser::{self, Impossible, Serialize, SerializeMap, SerializeStruct, Serializer};
+use smol_str::SmolStr;
+use xxhash_rust::xxh64::xxh64;
+
+use crate::cbor::int_to_number;
+use crate::error::RecordError;
+use crate::serialization::{write_field_into, write_index_entry};
+use crate::spooky_value::{SpookyNumber, SpookyValue};
+use crate::types::{HEADER_SIZE, INDEX_ENTRY_SIZE, TAG_NESTED_CBOR, TAG_NULL, TAG_STR};
+
+/// Serialize `value` into record bytes. Returns `(buf, field_count)`.
+pub fn to_record<T: Serialize + ?Sized>(value: &T) -> Result<(Vec<u8>, usize), RecordError> {
+    let mut buf = Vec::new();
+    let field_count = to_record_into(value, &mut buf)?;
+    Ok((buf, field_count))
+}
+
+/// [`to_record`] into a reusable buffer. The buffer is cleared but keeps
+/// its capacity. Returns the field count.
+pub fn to_record_into<T: Serialize + ?Sized>(
+    value: &T,
+    buf: &mut Vec<u8>,
+) -> Result<usize, RecordError> {
+    value.serialize(RecordSerializer::new(buf))
+}
+
+/// Reject every `Serializer` method whose signature is listed; generic
+/// methods are written out by hand.
+macro_rules! reject {
+    ($err:expr; $($method:ident($($ty:ty),*) -> $ret:ident;)*) => {$(
+        fn $method(self, $(_: $ty),*) -> Result<Self::$ret, Self::Error> {
+            Err($err)
+        }
+    )*};
+}
+
+/// The record-level `Serializer` behind [`to_record_into`].
+pub struct RecordSerializer<'b> {
+    buf: &'b mut Vec<u8>,
+}
+
+impl<'b> RecordSerializer<'b> {
+    pub fn new(buf: &'b mut Vec<u8>) -> Self {
+        Self { buf }
+    }
+
+    fn fields(self, len: Option<usize>) -> Fields<'b> {
+        // Leave room for the index the hint promises; `end` moves the data
+        // if fewer or more fields arrive.
+        let reserved = HEADER_SIZE + len.unwrap_or(0) * INDEX_ENTRY_SIZE;
+        self.buf.clear();
+        self.buf.resize(reserved, 0);
+        Fields {
+            buf: self.buf,
+            reserved,
+            entries: ArrayVec::new(),
+            key: None,
+        }
+    }
+}
+
+fn not_a_record() -> RecordError {
+    ser::Error::custom("a record is a struct with named fields or a map with string keys")
+}
+
+impl<'b> Serializer for RecordSerializer<'b> {
+    type Ok = usize;
+    type Error = RecordError;
+    type SerializeSeq = Impossible<usize, RecordError>;
+    type SerializeTuple = Impossible<usize, RecordError>;
+    type SerializeTupleStruct = Impossible<usize, RecordError>;
+    type SerializeTupleVariant = Impossible<usize, RecordError>;
+    type SerializeMap = Fields<'b>;
+    type SerializeStruct = Fields<'b>;
+    type SerializeStructVariant = Impossible<usize, RecordError>;
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Fields<'b>, RecordError> {
+        Ok(self.fields(len))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Fields<'b>, RecordError> {
+        Ok(self.fields(Some(len)))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<usize, RecordError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<usize, RecordError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<usize, RecordError> {
+        Err(not_a_record())
+    }
+
+    reject! { not_a_record();
+        serialize_bool(bool) -> Ok;
+        serialize_i8(i8) -> Ok;
+        serialize_i16(i16) -> Ok;
+        serialize_i32(i32) -> Ok;
+        serialize_i64(i64) -> Ok;
+        serialize_u8(u8) -> Ok;
+        serialize_u16(u16) -> Ok;
+        serialize_u32(u32) -> Ok;
+        serialize_u64(u64) -> Ok;
+        serialize_f32(f32) -> Ok;
+        serialize_f64(f64) -> Ok;
+        serialize_char(char) -> Ok;
+        serialize_str(&str) -> Ok;
+        serialize_bytes(&[u8]) -> Ok;
+        serialize_none() -> Ok;
+        serialize_unit() -> Ok;
+        serialize_unit_struct(&'static str) -> Ok;
+        serialize_unit_variant(&'static str, u32, &'static str) -> Ok;
+        serialize_seq(Option<usize>) -> SerializeSeq;
+        serialize_tuple(usize) -> SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> SerializeTupleVariant;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> SerializeStructVariant;
+    }
+}
+
+/// One written field: `(name_hash, data_offset, data_length, type_tag)`.
+type Entry = (u64, usize, usize, u8);
+
+/// The fields written so far. Data is appended in arrival order; the index
+/// is sorted when the record ends.
+pub struct Fields<'b> {
+    buf: &'b mut Vec<u8>,
+    /// Where the data area starts: header plus the index the length hint
+    /// promised.
+    reserved: usize,
+    entries: ArrayVec<Entry, 32>,
+    /// The map key whose value comes next.
+    key: Option<SmolStr>,
+}
+
+impl Fields<'_> {
+    fn write<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), RecordError> {
+        let offset = self.buf.len();
+        let tag = match value.serialize(FieldSerializer(self.buf)) {
+            Ok(tag) => tag,
+            Err(FieldError::Nested) => {
+                self.buf.truncate(offset);
+                cbor4ii::serde::to_writer(&mut *self.buf, &value)
+                    .map_err(|e| <RecordError as ser::Error>::custom(e).at_field(name))?;
+                TAG_NESTED_CBOR
+            }
+            Err(FieldError::Record(e)) => return Err(e.at_field(name)),
+        };
+        let entry = (xxh64(name.as_bytes(), 0), offset, self.buf.len() - offset, tag);
+        // Duplicate keys: last one wins, as with `FastMap::insert`.
+        match self.entries.iter_mut().find(|e| e.0 == entry.0) {
+            Some(slot) => *slot = entry,
+            None => self
+                .entries
+                .try_push(entry)
+                .map_err(|_| RecordError::TooManyFields)?,
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<usize, RecordError> {
+        let field_count = self.entries.len();
+        let data_start = HEADER_SIZE + field_count * INDEX_ENTRY_SIZE;
+        if data_start != self.reserved {
+            let data_len = self.buf.len() - self.reserved;
+            if data_start > self.reserved {
+                self.buf.resize(data_start + data_len, 0);
+            }
+            self.buf.copy_within(self.reserved..self.reserved + data_len, data_start);
+            self.buf.truncate(data_start + data_len);
+            for entry in &mut self.entries {
+                entry.1 = entry.1 - self.reserved + data_start;
+            }
+        }
+
+        self.entries.sort_unstable_by_key(|e| e.0);
+        self.buf[0..4].copy_from_slice(&(field_count as u32).to_le_bytes());
+        for (i, &(hash, offset, len, tag)) in self.entries.iter().enumerate() {
+            write_index_entry(self.buf, i, hash, offset, len, tag);
+        }
+        Ok(field_count)
+    }
+}
+
+impl SerializeStruct for Fields<'_> {
+    type Ok = usize;
+    type Error = RecordError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), RecordError> {
+        self.write(name, value)
+    }
+
+    fn end(self) -> Result<usize, RecordError> {
+        self.finish()
+    }
+}
+
+impl SerializeMap for Fields<'_> {
+    type Ok = usize;
+    type Error = RecordError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), RecordError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RecordError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <RecordError as ser::Error>::custom("value written before its key"))?;
+        self.write(&key, value)
+    }
+
+    fn end(self) -> Result<usize, RecordError> {
+        self.finish()
+    }
+}
+
+/// Why a field was not written natively.
+#[derive(Debug)]
+enum FieldError {
+    /// Not a scalar: the caller encodes the value as nested CBOR.
+    Nested,
+    Record(RecordError),
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::Nested => f.write_str("nested value"),
+            FieldError::Record(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+impl ser::Error for FieldError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        FieldError::Record(ser::Error::custom(msg))
+    }
+}
+
+/// Writes one scalar field's data and returns its type tag.
+struct FieldSerializer<'b>(&'b mut Vec<u8>);
+
+impl FieldSerializer<'_> {
+    fn number(self, n: SpookyNumber) -> Result<u8, FieldError> {
+        write_field_into(self.0, &SpookyValue::Number(n)).map_err(FieldError::Record)
+    }
+}
+
+impl Serializer for FieldSerializer<'_> {
+    type Ok = u8;
+    type Error = FieldError;
+    type SerializeSeq = Impossible<u8, FieldError>;
+    type SerializeTuple = Impossible<u8, FieldError>;
+    type SerializeTupleStruct = Impossible<u8, FieldError>;
+    type SerializeTupleVariant = Impossible<u8, FieldError>;
+    type SerializeMap = Impossible<u8, FieldError>;
+    type SerializeStruct = Impossible<u8, FieldError>;
+    type SerializeStructVariant = Impossible<u8, FieldError>;
+
+    fn serialize_bool(self, v: bool) -> Result<u8, FieldError> {
+        write_field_into(self.0, &SpookyValue::Bool(v)).map_err(FieldError::Record)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::I64(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::I64(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::I64(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::I64(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::I64(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::I64(v.into()))
+    }
+
+    /// I64 when it fits, as for CBOR input.
+    fn serialize_u64(self, v: u64) -> Result<u8, FieldError> {
+        self.number(int_to_number(v.into()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::F64(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<u8, FieldError> {
+        self.number(SpookyNumber::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<u8, FieldError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<u8, FieldError> {
+        self.0.extend_from_slice(v.as_bytes());
+        Ok(TAG_STR)
+    }
+
+    fn serialize_none(self) -> Result<u8, FieldError> {
+        Ok(TAG_NULL)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<u8, FieldError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<u8, FieldError> {
+        Ok(TAG_NULL)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<u8, FieldError> {
+        Ok(TAG_NULL)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<u8, FieldError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<u8, FieldError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<u8, FieldError> {
+        Err(FieldError::Nested)
+    }
+
+    reject! { FieldError::Nested;
+        serialize_bytes(&[u8]) -> Ok;
+        serialize_seq(Option<usize>) -> SerializeSeq;
+        serialize_tuple(usize) -> SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> SerializeTupleVariant;
+        serialize_map(Option<usize>) -> SerializeMap;
+        serialize_struct(&'static str, usize) -> SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> SerializeStructVariant;
+    }
+}
+
+/// Accepts string map keys only.
+struct KeySerializer;
+
+fn key_not_a_string() -> RecordError {
+    ser::Error::custom("record field names must be strings")
+}
+
+impl Serializer for KeySerializer {
+    type Ok = SmolStr;
+    type Error = RecordError;
+    type SerializeSeq = Impossible<SmolStr, RecordError>;
+    type SerializeTuple = Impossible<SmolStr, RecordError>;
+    type SerializeTupleStruct = Impossible<SmolStr, RecordError>;
+    type SerializeTupleVariant = Impossible<SmolStr, RecordError>;
+    type SerializeMap = Impossible<SmolStr, RecordError>;
+    type SerializeStruct = Impossible<SmolStr, RecordError>;
+    type SerializeStructVariant = Impossible<SmolStr, RecordError>;
+
+    fn serialize_str(self, v: &str) -> Result<SmolStr, RecordError> {
+        Ok(SmolStr::new(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<SmolStr, RecordError> {
+        Ok(SmolStr::new(v.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<SmolStr, RecordError> {
+        Ok(SmolStr::new_static(variant))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<SmolStr, RecordError> {
+        Err(key_not_a_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<SmolStr, RecordError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<SmolStr, RecordError> {
+        Err(key_not_a_string())
+    }
+
+    reject! { key_not_a_string();
+        serialize_bool(bool) -> Ok;
+        serialize_i8(i8) -> Ok;
+        serialize_i16(i16) -> Ok;
+        serialize_i32(i32) -> Ok;
+        serialize_i64(i64) -> Ok;
+        serialize_u8(u8) -> Ok;
+        serialize_u16(u16) -> Ok;
+        serialize_u32(u32) -> Ok;
+        serialize_u64(u64) -> Ok;
+        serialize_f32(f32) -> Ok;
+        serialize_f64(f64) -> Ok;
+        serialize_bytes(&[u8]) -> Ok;
+        serialize_none() -> Ok;
+        serialize_unit() -> Ok;
+        serialize_unit_struct(&'static str) -> Ok;
+        serialize_seq(Option<usize>) -> SerializeSeq;
+        serialize_tuple(usize) -> SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> SerializeTupleVariant;
+        serialize_map(Option<usize>) -> SerializeMap;
+        serialize_struct(&'static str, usize) -> SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> SerializeStructVariant;
+    }
+}
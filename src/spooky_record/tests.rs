@@ -2,7 +2,9 @@
 // Helpers
 // ═══════════════════════════════════════════════════════════════════════
 mod spooky_record_tests {
-    use crate::serialization::{from_bytes, from_spooky, serialize_into};
+    use crate::serialization::{
+        from_bytes, from_cbor, from_cbor_slice, from_spooky, serialize_into,
+    };
     use crate::spooky_record::SpookyReadable;
     use crate::spooky_record::SpookyRecord;
     use crate::spooky_value::{FastMap, SpookyValue};
@@ -397,6 +399,119 @@ mod spooky_record_tests {
         assert_eq!(raw.data.len(), 0);
     }
 
+    #[test]
+    fn test_by_hash_getters_match_by_name() {
+        const AGE: u64 = crate::field_hash!("age");
+        assert_eq!(AGE, xxhash_rust::xxh64::xxh64(b"age", 0));
+
+        let (buf, fc) = from_spooky(&make_test_record()).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+        assert_eq!(record.get_i64_by_hash(AGE), Some(30));
+        assert_eq!(record.get_str_by_hash(field_hash("name")), Some("Alice"));
+        assert_eq!(record.get_u64_by_hash(field_hash("version")), Some(42));
+        assert_eq!(record.get_f64_by_hash(field_hash("score")), Some(99.5));
+        assert_eq!(record.get_bool_by_hash(field_hash("active")), Some(true));
+        assert_eq!(record.get_number_as_f64_by_hash(AGE), Some(30.0));
+        assert_eq!(record.get_raw_by_hash(AGE).unwrap().name_hash, AGE);
+        assert_eq!(record.get_str_by_hash(AGE), None);
+        assert!(!record.has_field_by_hash(field_hash("nope")));
+
+        let (buf, fc) = from_spooky(&make_linear_record()).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+        assert_eq!(record.get_i64_by_hash(crate::field_hash!("b")), Some(1));
+    }
+
+    #[test]
+    fn test_field_name_works_as_any_key() {
+        use crate::spooky_record::record_mut::SpookyRecordMut;
+
+        const AGE: FieldName = FieldName::from_static("age");
+        let name = FieldName::new("name");
+        assert_eq!(AGE.name_hash(), field_hash("age"));
+        assert_eq!(name.as_str(), "name");
+
+        let (buf, fc) = from_spooky(&make_test_record()).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+        assert_eq!(record.get_i64(&AGE), Some(30));
+        assert_eq!(record.get_str(&name), Some("Alice"));
+        assert_eq!(record.get_str(String::from("name")), Some("Alice"));
+        assert_eq!(record.get_str(SmolStr::new("name")), Some("Alice"));
+        assert_eq!(record.field_type(&AGE), Some(TAG_I64));
+
+        let mut rec = SpookyRecordMut::new(buf.clone(), fc);
+        rec.set_i64(&AGE, 31).unwrap();
+        rec.add_field(FieldName::new("nick"), &SpookyValue::from("al")).unwrap();
+        rec.rename_field(&name, "full_name").unwrap();
+        assert_eq!(rec.get_i64("age"), Some(31));
+        assert_eq!(rec.get_str("nick"), Some("al"));
+        assert_eq!(rec.get_str(&name), None);
+        assert_eq!(rec.get_str("full_name"), Some("Alice"));
+    }
+
+    #[test]
+    fn test_perfect_index_ranks_and_falls_back() {
+        use crate::spooky_record::record_mut::SpookyRecordMut;
+        use crate::spooky_record::{IndexedRecord, PerfectIndex};
+
+        for n in [0, 1, 5, 32, 40] {
+            let names: Vec<String> = (0..n).map(|i| format!("f{i}")).collect();
+            let index = PerfectIndex::new(&names);
+            let mut hashes: Vec<u64> = names.iter().map(|n| field_hash(n)).collect();
+            hashes.sort_unstable();
+            for (rank, hash) in hashes.iter().enumerate() {
+                assert_eq!(index.rank(*hash), Some(rank));
+            }
+            assert_eq!((index.len(), index.rank(field_hash("nope"))), (n, None));
+        }
+
+        // Exactly the indexed fields: every lookup hits its slot.
+        let index = PerfectIndex::new(["id", "name", "age", "score", "active", "version"]);
+        let (buf, fc) = from_spooky(&make_test_record()).unwrap();
+        let plain = SpookyRecord::new(&buf, fc);
+        let record = IndexedRecord::new(plain, &index);
+        for field in plain.iter_fields() {
+            let rank = index.rank(field.name_hash).unwrap();
+            assert_eq!(record.find_field_by_hash(field.name_hash).unwrap().0, rank);
+        }
+        assert_eq!(record.get_str("name"), Some("Alice"));
+        assert_eq!(record.get_u64("version"), Some(42));
+
+        // Fields missing or unknown to the index take the search path.
+        let index = PerfectIndex::new(["a", "name", "age", "zzz", "score"]);
+        let record = IndexedRecord::new(plain, &index);
+        assert_eq!(record.get_i64("age"), Some(30));
+        assert_eq!(record.get_bool("active"), Some(true));
+        assert_eq!(record.get_str("a"), None);
+
+        let mut rec = SpookyRecordMut::new(buf.clone(), fc).with_index(index.into());
+        rec.set_i64("age", 31).unwrap();
+        rec.set_str("name", "Alicia").unwrap();
+        assert_eq!((rec.get_i64("age"), rec.get_str("name")), (Some(31), Some("Alicia")));
+    }
+
+    #[test]
+    fn test_wide_record_search_matches_binary_search() {
+        for n in 1..=32 {
+            let mut map = FastMap::new();
+            for i in 0..n {
+                map.insert(SmolStr::new(format!("col_{i}")), SpookyValue::from(i as i64));
+            }
+            let (buf, fc) = from_spooky(&SpookyValue::Object(map)).unwrap();
+            let record = SpookyRecord::new(&buf, fc);
+            for i in 0..n {
+                let name = format!("col_{i}");
+                let hash = field_hash(&name);
+                let (pos, meta) = record.find_field(&name).unwrap();
+                assert_eq!(pos, record.binary_hash_search(n, hash).unwrap().0);
+                assert_eq!(meta.name_hash, hash);
+                assert_eq!(record.get_i64(&name), Some(i as i64));
+            }
+            for missing in ["", "col_99", "zzz"] {
+                assert!(record.find_field(missing).is_err());
+            }
+        }
+    }
+
     // ═══════════════════════════════════════════════════════════════════════
     // get_field (full decode via CBOR / native)
     // ═══════════════════════════════════════════════════════════════════════
@@ -851,6 +966,235 @@ mod spooky_record_tests {
         assert!(r2.get_i64("a").is_none()); // old field gone
     }
 
+    // ═══════════════════════════════════════════════════════════════════════
+    // from_cbor_slice (streaming path)
+    // ═══════════════════════════════════════════════════════════════════════
+
+    #[test]
+    fn test_from_cbor_slice_matches_from_cbor() {
+        let mut map = FastMap::new();
+        map.insert(SmolStr::from("id"), SpookyValue::from("user:123"));
+        map.insert(SmolStr::from("age"), SpookyValue::from(-30i64));
+        map.insert(SmolStr::from("big"), SpookyValue::from(u64::MAX));
+        map.insert(SmolStr::from("score"), SpookyValue::from(99.5f64));
+        map.insert(SmolStr::from("on"), SpookyValue::from(false));
+        map.insert(SmolStr::from("none"), SpookyValue::Null);
+        map.insert(
+            SmolStr::from("tags"),
+            SpookyValue::Array(vec![SpookyValue::from("a"), SpookyValue::from(1i64)]),
+        );
+        map.insert(SmolStr::from("profile"), make_test_record());
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &SpookyValue::Object(map)).unwrap();
+
+        let cbor: cbor4ii::core::Value = cbor4ii::serde::from_slice(&bytes).unwrap();
+        let expected = from_cbor(&cbor).unwrap();
+        assert_eq!(from_cbor_slice(&bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_from_cbor_slice_non_canonical_input() {
+        let bytes: &[u8] = &[
+            0xBF, // indefinite map
+            0x61, b'a', 0xF9, 0x3E, 0x00, // "a": 1.5 (f16)
+            0x61, b'b', 0xFA, 0x40, 0x20, 0x00, 0x00, // "b": 2.5 (f32)
+            0x61, b'c', 0xC2, 0x42, 0x01, 0x00, // "c": bignum 256
+            0x61, b'd', 0xD8, 0x20, 0x61, b'x', // "d": tag 32 (URI) over "x", kept
+            0x61, b'a', 0x20, // duplicate "a": -1, last wins
+            0x61, b'e', 0x7F, 0x62, b'h', b'e', 0x63, b'l', b'l', b'o', 0xFF, // "e": "hello"
+            0x61, b'n', 0x9F, 0x01, 0x81, 0x02, 0xFF, // "n": [1, [2]]
+            0xFF,
+        ];
+        let (buf, fc) = from_cbor_slice(bytes).unwrap();
+        assert_eq!(fc, 6);
+        let record = SpookyRecord::new(&buf, fc);
+        assert_eq!(record.get_i64("a"), Some(-1));
+        assert_eq!(record.get_f64("b"), Some(2.5));
+        assert_eq!(record.get_i64("c"), Some(256));
+        assert_eq!(record.field_type("d"), Some(TAG_NESTED_CBOR));
+        assert_eq!(
+            record.get_field::<SpookyValue>("d"),
+            Some(SpookyValue::Tagged(32, Box::new(SpookyValue::from("x"))))
+        );
+        assert_eq!(record.get_str("e"), Some("hello"));
+        assert_eq!(
+            record.get_field::<SpookyValue>("n"),
+            Some(SpookyValue::from_json_str("[1,[2]]").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_from_record_borrows_and_names_fields() {
+        use crate::spooky_record::from_record;
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Score(f64);
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct User<'a> {
+            name: &'a str,
+            age: i32,
+            score: Score,
+            active: bool,
+            version: Option<u64>,
+            missing: Option<String>,
+        }
+
+        let (buf, fc) = from_spooky(&make_test_record()).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+        let user: User = from_record(&record).unwrap();
+        assert_eq!(
+            user,
+            User {
+                name: "Alice",
+                age: 30,
+                score: Score(99.5),
+                active: true,
+                version: Some(42),
+                missing: None,
+            }
+        );
+
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Wrong {
+            name: u8,
+        }
+        match from_record::<Wrong, _>(&record) {
+            Err(crate::error::RecordError::Deserialize { field, .. }) => {
+                assert_eq!(field.as_deref(), Some("name"))
+            }
+            other => panic!("expected a Deserialize error, got {other:?}"),
+        }
+        // Maps have no field list to look names up by.
+        assert!(from_record::<std::collections::BTreeMap<String, i64>, _>(&record).is_err());
+    }
+
+    #[test]
+    fn test_to_record_matches_cbor_path() {
+        use crate::spooky_record::{from_record, to_record};
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        enum Kind {
+            Plain,
+            Tagged(String),
+        }
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Row {
+            name: String,
+            age: u8,
+            big: u64,
+            score: f32,
+            kind: Kind,
+            other: Kind,
+            tags: Vec<String>,
+            nick: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            gone: Option<i64>,
+        }
+        let row = Row {
+            name: "Ann".into(),
+            age: 40,
+            big: u64::MAX,
+            score: 1.5,
+            kind: Kind::Plain,
+            other: Kind::Tagged("x".into()),
+            tags: vec!["a".into()],
+            nick: None,
+            gone: None,
+        };
+
+        let (buf, fc) = to_record(&row).unwrap();
+        let cbor = cbor4ii::serde::to_vec(Vec::new(), &row).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+        let (expected_buf, expected_fc) = from_cbor_slice(&cbor).unwrap();
+        let expected = SpookyRecord::new(&expected_buf, expected_fc);
+        assert_eq!(fc, 8);
+        for field in expected.iter_fields() {
+            let name_hash = field.name_hash;
+            let ours = record.iter_fields().find(|f| f.name_hash == name_hash).unwrap();
+            assert_eq!((ours.type_tag, ours.data), (field.type_tag, field.data));
+        }
+        assert_eq!(record.get_i64("age"), Some(40));
+        assert_eq!(record.get_str("kind"), Some("Plain"));
+        assert_eq!(from_record::<Row, _>(&record).unwrap(), row);
+
+        // Maps without a length hint; duplicate keys keep the last value.
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("b", 2);
+        map.insert("a", 1);
+        let (buf, fc) = to_record(&map).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+        assert_eq!((fc, record.get_i64("a"), record.get_i64("b")), (2, Some(1), Some(2)));
+        let pairs = crate::spooky_value::SpookyValue::from_json_str(r#"{"a":1}"#).unwrap();
+        assert!(to_record(&pairs).is_ok());
+
+        let err = to_record(&vec![1]).unwrap_err();
+        assert!(matches!(err, crate::error::RecordError::Serialize { field: None, .. }));
+        let err = to_record(&std::collections::BTreeMap::from([(1, 2)])).unwrap_err();
+        assert!(err.to_string().contains("must be strings"), "{err}");
+    }
+
+    #[test]
+    fn test_from_cbor_slice_errors() {
+        use crate::error::RecordError;
+        let scalar = cbor4ii::serde::to_vec(Vec::new(), &1i64).unwrap();
+        assert!(matches!(
+            from_cbor_slice(&scalar),
+            Err(RecordError::InvalidBuffer)
+        ));
+        // {1: 2}
+        assert!(matches!(
+            from_cbor_slice(&[0xA1, 0x01, 0x02]),
+            Err(RecordError::CborError(_))
+        ));
+        // {"a": 1} followed by a stray byte
+        assert!(matches!(
+            from_cbor_slice(&[0xA1, 0x61, b'a', 0x01, 0x00]),
+            Err(RecordError::CborError(_))
+        ));
+        // truncated string value
+        assert!(matches!(
+            from_cbor_slice(&[0xA1, 0x61, b'a', 0x65, b'x']),
+            Err(RecordError::CborError(_))
+        ));
+    }
+
+    // ═══════════════════════════════════════════════════════════════════════
+    // from_json_str (JSON text path)
+    // ═══════════════════════════════════════════════════════════════════════
+
+    #[test]
+    fn test_from_json_str_matches_cbor_path() {
+        use crate::error::RecordError;
+        use crate::serialization::{from_json_reader, from_json_str};
+        let text = r#"{"id":"user:123","age":-30,"big":18446744073709551615,"score":99.5,
+            "whole":2.0,"on":false,"none":null,"tags":["a",1],"profile":{"name":"Alice"},
+            "age":31}"#;
+        let (buf, fc) = from_json_str(text).unwrap();
+        let value = SpookyValue::from_json_str(text).unwrap();
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &value).unwrap();
+        assert_eq!((buf.clone(), fc), from_cbor_slice(&bytes).unwrap());
+        assert_eq!(from_json_reader(text.as_bytes()).unwrap(), (buf.clone(), fc));
+
+        let record = SpookyRecord::new(&buf, fc);
+        assert_eq!(fc, 9);
+        assert_eq!(record.get_i64("age"), Some(31)); // duplicate key, last wins
+        assert_eq!(record.get_u64("big"), Some(u64::MAX));
+        assert_eq!(record.get_f64("whole"), Some(2.0));
+        assert_eq!(
+            record.get_field::<SpookyValue>("tags"),
+            Some(SpookyValue::from_json_str(r#"["a",1]"#).unwrap())
+        );
+
+        assert!(matches!(from_json_str("[1]"), Err(RecordError::SerializationNotObject)));
+        assert!(matches!(from_json_str(r#"{"a":1,}"#), Err(RecordError::JsonError(_))));
+        assert!(matches!(from_json_str(r#"{"a":1} x"#), Err(RecordError::JsonError(_))));
+        let wide = (0..33).map(|i| format!("\"f{i}\":{i}")).collect::<Vec<_>>().join(",");
+        assert!(matches!(
+            from_json_str(&format!("{{{wide}}}")),
+            Err(RecordError::TooManyFields)
+        ));
+    }
+
     // ═══════════════════════════════════════════════════════════════════════
     // to_value (current placeholder behaviour)
     // ═══════════════════════════════════════════════════════════════════════
@@ -904,7 +1248,7 @@ mod spooky_record_tests {
         assert_eq!(record.field_count(), 30);
         for i in 0..30 {
             assert_eq!(
-                record.get_i64(&format!("field_{i}")),
+                record.get_i64(format!("field_{i}")),
                 Some(i as i64),
                 "field_{i} not found"
             );
@@ -1235,6 +1579,35 @@ mod spooky_record_mut_tests {
         ));
     }
 
+    // ── rename_field ────────────────────────────────────────────────────────
+
+    #[test]
+    fn test_rename_field() {
+        for (old, new) in [("name", "full_name"), ("age", "a"), ("level", "zzz_level")] {
+            let mut rec = make_record_mut();
+            let before = make_record_mut();
+            let ty = rec.field_type(old);
+            rec.rename_field(old, new).unwrap();
+
+            assert_eq!(rec.field_count(), 6);
+            assert!(!rec.has_field(old));
+            assert_eq!(rec.field_type(new), ty);
+            assert_eq!(rec.get_raw(new).map(|f| f.data), before.get_raw(old).map(|f| f.data));
+            for other in ["id", "name", "age", "score", "active", "level"] {
+                if other != old {
+                    assert_eq!(
+                        rec.get_raw(other).map(|f| f.data),
+                        before.get_raw(other).map(|f| f.data)
+                    );
+                }
+            }
+        }
+
+        let mut rec = make_record_mut();
+        assert!(matches!(rec.rename_field("name", "age"), Err(RecordError::FieldExists)));
+        assert!(matches!(rec.rename_field("nope", "x"), Err(RecordError::FieldNotFound)));
+    }
+
     #[test]
     fn test_remove_then_add() {
         let mut rec = make_record_mut();
@@ -86,7 +86,7 @@ impl SpookyRecordMut {
 
     /// Set an i64 field. In-place overwrite, ~20ns. Zero allocation.
     #[inline]
-    pub fn set_i64(&mut self, name: &str, value: i64) -> Result<(), RecordError> {
+    pub fn set_i64(&mut self, name: impl AsFieldKey, value: i64) -> Result<(), RecordError> {
         let (_, meta) = self.find_field(name)?;
         if meta.type_tag != TAG_I64 {
             return Err(RecordError::TypeMismatch {
@@ -106,7 +106,7 @@ impl SpookyRecordMut {
 
     /// Set a u64 field. In-place overwrite, ~20ns. Zero allocation.
     #[inline]
-    pub fn set_u64(&mut self, name: &str, value: u64) -> Result<(), RecordError> {
+    pub fn set_u64(&mut self, name: impl AsFieldKey, value: u64) -> Result<(), RecordError> {
         let (_, meta) = self.find_field(name)?;
         if meta.type_tag != TAG_U64 {
             return Err(RecordError::TypeMismatch {
@@ -126,7 +126,7 @@ impl SpookyRecordMut {
 
     /// Set an f64 field. In-place overwrite, ~20ns. Zero allocation.
     #[inline]
-    pub fn set_f64(&mut self, name: &str, value: f64) -> Result<(), RecordError> {
+    pub fn set_f64(&mut self, name: impl AsFieldKey, value: f64) -> Result<(), RecordError> {
         let (_, meta) = self.find_field(name)?;
         if meta.type_tag != TAG_F64 {
             return Err(RecordError::TypeMismatch {
@@ -146,7 +146,7 @@ impl SpookyRecordMut {
 
     /// Set a bool field. In-place overwrite, ~18ns. Zero allocation.
     #[inline]
-    pub fn set_bool(&mut self, name: &str, value: bool) -> Result<(), RecordError> {
+    pub fn set_bool(&mut self, name: impl AsFieldKey, value: bool) -> Result<(), RecordError> {
         let (_, meta) = self.find_field(name)?;
         if meta.type_tag != TAG_BOOL {
             return Err(RecordError::TypeMismatch {
@@ -171,7 +171,7 @@ impl SpookyRecordMut {
     /// Set a string field. In-place if same byte length, splice if different.
     ///
     /// ~22ns for same length, ~150-350ns for different length.
-    pub fn set_str(&mut self, name: &str, value: &str) -> Result<(), RecordError> {
+    pub fn set_str(&mut self, name: impl AsFieldKey, value: &str) -> Result<(), RecordError> {
         let (pos, meta) = self.find_field(name)?;
         if meta.type_tag != TAG_STR {
             return Err(RecordError::TypeMismatch {
@@ -200,7 +200,7 @@ impl SpookyRecordMut {
     /// Set a string field only if the new value has the exact same byte length.
     /// Returns `RecordError::LengthMismatch` otherwise. Guaranteed zero-allocation.
     #[inline]
-    pub fn set_str_exact(&mut self, name: &str, value: &str) -> Result<(), RecordError> {
+    pub fn set_str_exact(&mut self, name: impl AsFieldKey, value: &str) -> Result<(), RecordError> {
         let (_, meta) = self.find_field(name)?;
         if meta.type_tag != TAG_STR {
             return Err(RecordError::TypeMismatch {
@@ -227,7 +227,11 @@ impl SpookyRecordMut {
     /// Set any field to any value. Automatically picks the optimal path:
     /// - Same size → in-place overwrite (~25ns)
     /// - Different size → splice + offset fixup (~200-500ns)
-    pub fn set_field<V: crate::serialization::RecordSerialize>(&mut self, name: &str, value: &V) -> Result<(), RecordError> {
+    pub fn set_field<V: crate::serialization::RecordSerialize>(
+        &mut self,
+        name: impl AsFieldKey,
+        value: &V,
+    ) -> Result<(), RecordError> {
         let (pos, meta) = self.find_field(name)?;
         let mut new_bytes = Vec::new();
         let new_tag = write_field_into(&mut new_bytes, value)?;
@@ -254,7 +258,7 @@ impl SpookyRecordMut {
     }
 
     /// Set a field to Null.
-    pub fn set_null(&mut self, name: &str) -> Result<(), RecordError> {
+    pub fn set_null(&mut self, name: impl AsFieldKey) -> Result<(), RecordError> {
         self.set_field(name, &SpookyValue::Null)
     }
 
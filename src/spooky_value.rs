@@ -1,11 +1,16 @@
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use crate::error::ConversionError;
+#[cfg(any(feature = "msgpack", feature = "bson"))]
+use crate::error::RecordError;
+use crate::small_map::SmallMap;
 use smol_str::SmolStr;
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
 
-pub type FastMap<K, V> = BTreeMap<K, V>;
+/// Object map: sorted Vec for small objects, BTreeMap beyond `INLINE_CAP` entries.
+pub type FastMap<K, V> = SmallMap<K, V>;
 
 // ─── SpookyNumber ───────────────────────────────────────────────────────────
 
@@ -136,6 +141,9 @@ pub enum SpookyValue {
     Str(SmolStr),
     Array(Vec<SpookyValue>),
     Object(FastMap<SmolStr, SpookyValue>),
+    /// A CBOR tag the crate has no mapping for, kept with its decoded payload
+    /// so it is written back out as the same tag. See `crate::cbor`.
+    Tagged(u64, Box<SpookyValue>),
 }
 
 impl Default for SpookyValue {
@@ -164,7 +172,7 @@ impl PartialOrd for SpookyValue {
 
 impl Ord for SpookyValue {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Discriminant ordering: Null < Bool < Number < Str < Array < Object
+        // Discriminant ordering: Null < Bool < Number < Str < Array < Object < Tagged
         let disc = |v: &SpookyValue| -> u8 {
             match v {
                 SpookyValue::Null => 0,
@@ -173,6 +181,7 @@ impl Ord for SpookyValue {
                 SpookyValue::Str(_) => 3,
                 SpookyValue::Array(_) => 4,
                 SpookyValue::Object(_) => 5,
+                SpookyValue::Tagged(..) => 6,
             }
         };
 
@@ -189,6 +198,7 @@ impl Ord for SpookyValue {
             (SpookyValue::Str(a), SpookyValue::Str(b)) => a.cmp(b),
             (SpookyValue::Array(a), SpookyValue::Array(b)) => a.cmp(b),
             (SpookyValue::Object(a), SpookyValue::Object(b)) => a.cmp(b),
+            (SpookyValue::Tagged(ta, a), SpookyValue::Tagged(tb, b)) => (ta, a).cmp(&(tb, b)),
             _ => unreachable!(),
         }
     }
@@ -215,6 +225,78 @@ impl Hash for SpookyValue {
                     v.hash(state);
                 }
             }
+            SpookyValue::Tagged(tag, inner) => {
+                tag.hash(state);
+                inner.hash(state);
+            }
+        }
+    }
+}
+
+// ─── Canonical Hash ─────────────────────────────────────────────────────────
+//
+// `Hash` above feeds whatever `Hasher` the caller picks, and goes through
+// `discriminant` / `usize` writes whose bytes are not guaranteed across
+// platforms or compiler versions. `canonical_hash` instead hashes a fixed,
+// documented byte stream with xxh64 (seed 0), so the result can be persisted
+// or compared across processes:
+//
+//   Null          0x00
+//   Bool          0x01 b
+//   Number        0x02 f64 bits LE   (I64/U64 promoted to f64, -0.0 → +0.0)
+//   Str           0x03 len:u64 LE bytes
+//   Array         0x04 len:u64 LE items…
+//   Object        0x05 len:u64 LE (key_len:u64 LE key value)…  in key order
+//   Tagged        0x06 tag:u64 LE value
+//
+// Values that compare equal hash equal: I64(1), U64(1) and F64(1.0) collide
+// on purpose, and object hashes do not depend on insertion order.
+
+impl SpookyValue {
+    /// Stable 64-bit hash, consistent with `Eq` across numeric variants.
+    /// Suitable for ZSet keys and dedup keys that outlive the process.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut h = xxhash_rust::xxh64::Xxh64::new(0);
+        self.canonical_hash_into(&mut h);
+        h.digest()
+    }
+
+    fn canonical_hash_into(&self, h: &mut xxhash_rust::xxh64::Xxh64) {
+        match self {
+            SpookyValue::Null => h.update(&[0x00]),
+            SpookyValue::Bool(b) => h.update(&[0x01, *b as u8]),
+            SpookyValue::Number(n) => {
+                let f = n.as_f64();
+                let bits = if f == 0.0 { 0u64 } else { f.to_bits() };
+                h.update(&[0x02]);
+                h.update(&bits.to_le_bytes());
+            }
+            SpookyValue::Str(s) => {
+                h.update(&[0x03]);
+                h.update(&(s.len() as u64).to_le_bytes());
+                h.update(s.as_bytes());
+            }
+            SpookyValue::Array(arr) => {
+                h.update(&[0x04]);
+                h.update(&(arr.len() as u64).to_le_bytes());
+                for v in arr {
+                    v.canonical_hash_into(h);
+                }
+            }
+            SpookyValue::Object(map) => {
+                h.update(&[0x05]);
+                h.update(&(map.len() as u64).to_le_bytes());
+                for (k, v) in map {
+                    h.update(&(k.len() as u64).to_le_bytes());
+                    h.update(k.as_bytes());
+                    v.canonical_hash_into(h);
+                }
+            }
+            SpookyValue::Tagged(tag, inner) => {
+                h.update(&[0x06]);
+                h.update(&tag.to_le_bytes());
+                inner.canonical_hash_into(h);
+            }
         }
     }
 }
@@ -262,6 +344,21 @@ impl SpookyValue {
         }
     }
 
+    /// Kind name used in error messages: `null`, `bool`, `int`, `float`,
+    /// `string`, `array`, `object` or `tagged`. I64 and U64 are both `int`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SpookyValue::Null => "null",
+            SpookyValue::Bool(_) => "bool",
+            SpookyValue::Number(SpookyNumber::F64(_)) => "float",
+            SpookyValue::Number(_) => "int",
+            SpookyValue::Str(_) => "string",
+            SpookyValue::Array(_) => "array",
+            SpookyValue::Object(_) => "object",
+            SpookyValue::Tagged(..) => "tagged",
+        }
+    }
+
     #[inline]
     pub fn as_object(&self) -> Option<&FastMap<SmolStr, SpookyValue>> {
         match self {
@@ -296,12 +393,12 @@ impl SpookyValue {
         }
     }
 
-    /// Field access by key. Uses BTreeMap's native lookup — no SmolStr allocation
-    /// thanks to SmolStr implementing Borrow<str>.
+    /// Field access by key. Binary search on the object map — no SmolStr
+    /// allocation thanks to SmolStr implementing Borrow<str>.
     #[inline]
     pub fn get(&self, key: &str) -> Option<&SpookyValue> {
-        // SmolStr implements Borrow<str>, and BTreeMap::get accepts Q where K: Borrow<Q>.
-        // However, BTreeMap<SmolStr, V>::get(&str) requires Ord consistency.
+        // SmolStr implements Borrow<str>, and FastMap::get accepts Q where K: Borrow<Q>.
+        // However, FastMap<SmolStr, V>::get(&str) requires Ord consistency.
         // SmolStr's Ord delegates to str's Ord, so this is safe.
         self.as_object()?.get(key)
     }
@@ -312,6 +409,45 @@ impl SpookyValue {
         self.as_object_mut()?.get_mut(key)
     }
 
+    /// Nested lookup by JSON Pointer (RFC 6901), e.g. `"/profile/settings/theme"`.
+    ///
+    /// `""` is the value itself. `~1` / `~0` unescape to `/` / `~`. Array
+    /// segments must be plain decimal indices. Mirrors `serde_json::Value::pointer`.
+    pub fn pointer(&self, pointer: &str) -> Option<&SpookyValue> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        pointer[1..]
+            .split('/')
+            .try_fold(self, |target, token| match target {
+                SpookyValue::Object(map) => map.get(unescape_pointer_token(token).as_ref()),
+                SpookyValue::Array(arr) => parse_pointer_index(token).and_then(|i| arr.get(i)),
+                _ => None,
+            })
+    }
+
+    /// Mutable JSON Pointer lookup. See [`SpookyValue::pointer`].
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut SpookyValue> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        pointer[1..]
+            .split('/')
+            .try_fold(self, |target, token| match target {
+                SpookyValue::Object(map) => map.get_mut(unescape_pointer_token(token).as_ref()),
+                SpookyValue::Array(arr) => {
+                    parse_pointer_index(token).and_then(move |i| arr.get_mut(i))
+                }
+                _ => None,
+            })
+    }
+
     #[inline]
     pub fn is_null(&self) -> bool {
         matches!(self, SpookyValue::Null)
@@ -338,6 +474,28 @@ impl SpookyValue {
     }
 }
 
+/// Undo RFC 6901 escaping. Borrows when the token has no `~`.
+#[inline]
+fn unescape_pointer_token(token: &str) -> std::borrow::Cow<'_, str> {
+    if token.contains('~') {
+        std::borrow::Cow::Owned(token.replace("~1", "/").replace("~0", "~"))
+    } else {
+        std::borrow::Cow::Borrowed(token)
+    }
+}
+
+/// Array index segment: decimal digits only, no leading zeros (except "0").
+#[inline]
+fn parse_pointer_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
 // ─── Serialize ──────────────────────────────────────────────────────────────
 
 impl Serialize for SpookyValue {
@@ -366,7 +524,166 @@ impl Serialize for SpookyValue {
                 }
                 m.end()
             }
+            // serde has no notion of tags; CBOR output that keeps them goes
+            // through `crate::cbor::to_vec`.
+            SpookyValue::Tagged(_, inner) => inner.serialize(serializer),
+        }
+    }
+}
+
+// ─── Deserialize ────────────────────────────────────────────────────────────
+//
+// Number promotion follows the CBOR bridge: integers become I64 when they fit,
+// U64 otherwise, and anything wider falls back to F64. Non-string map keys
+// (e.g. CBOR integer keys) are stringified, since Object keys are SmolStr.
+
+struct SpookyValueVisitor;
+
+impl<'de> Visitor<'de> for SpookyValueVisitor {
+    type Value = SpookyValue;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("any SpookyValue")
+    }
+
+    #[inline]
+    fn visit_unit<E: de::Error>(self) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Null)
+    }
+
+    #[inline]
+    fn visit_none<E: de::Error>(self) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Null)
+    }
+
+    #[inline]
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<SpookyValue, D::Error> {
+        SpookyValue::deserialize(deserializer)
+    }
+
+    #[inline]
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Bool(b))
+    }
+
+    #[inline]
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Number(SpookyNumber::I64(i)))
+    }
+
+    #[inline]
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Number(match i64::try_from(u) {
+            Ok(i) => SpookyNumber::I64(i),
+            Err(_) => SpookyNumber::U64(u),
+        }))
+    }
+
+    #[inline]
+    fn visit_i128<E: de::Error>(self, i: i128) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Number(crate::cbor::int_to_number(i)))
+    }
+
+    #[inline]
+    fn visit_u128<E: de::Error>(self, u: u128) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Number(crate::cbor::uint_to_number(u)))
+    }
+
+    #[inline]
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Number(SpookyNumber::F64(f)))
+    }
+
+    #[inline]
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Str(SmolStr::new(s)))
+    }
+
+    #[inline]
+    fn visit_string<E: de::Error>(self, s: String) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Str(SmolStr::from(s)))
+    }
+
+    /// Byte strings have no dedicated variant — they become an array of byte values.
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<SpookyValue, E> {
+        Ok(SpookyValue::Array(
+            bytes
+                .iter()
+                .map(|b| SpookyValue::Number(SpookyNumber::I64(*b as i64)))
+                .collect(),
+        ))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<SpookyValue, A::Error> {
+        // Cap the pre-allocation so a hostile length prefix can't OOM us.
+        let mut arr = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(v) = seq.next_element::<SpookyValue>()? {
+            arr.push(v);
+        }
+        Ok(SpookyValue::Array(arr))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<SpookyValue, A::Error> {
+        let mut map = FastMap::new();
+        while let Some(ObjectKey(k)) = access.next_key::<ObjectKey>()? {
+            let v = access.next_value::<SpookyValue>()?;
+            map.insert(k, v);
+        }
+        Ok(SpookyValue::Object(map))
+    }
+}
+
+impl<'de> Deserialize<'de> for SpookyValue {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(SpookyValueVisitor)
+    }
+}
+
+/// Object key that accepts strings and stringifies integer/bool keys.
+pub(crate) struct ObjectKey(pub(crate) SmolStr);
+
+impl<'de> Deserialize<'de> for ObjectKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = ObjectKey;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a string or integer map key")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<ObjectKey, E> {
+                Ok(ObjectKey(SmolStr::new(s)))
+            }
+
+            fn visit_string<E: de::Error>(self, s: String) -> Result<ObjectKey, E> {
+                Ok(ObjectKey(SmolStr::from(s)))
+            }
+
+            fn visit_i64<E: de::Error>(self, i: i64) -> Result<ObjectKey, E> {
+                Ok(ObjectKey(SmolStr::from(i.to_string())))
+            }
+
+            fn visit_u64<E: de::Error>(self, u: u64) -> Result<ObjectKey, E> {
+                Ok(ObjectKey(SmolStr::from(u.to_string())))
+            }
+
+            fn visit_i128<E: de::Error>(self, i: i128) -> Result<ObjectKey, E> {
+                Ok(ObjectKey(SmolStr::from(i.to_string())))
+            }
+
+            fn visit_u128<E: de::Error>(self, u: u128) -> Result<ObjectKey, E> {
+                Ok(ObjectKey(SmolStr::from(u.to_string())))
+            }
+
+            fn visit_bool<E: de::Error>(self, b: bool) -> Result<ObjectKey, E> {
+                Ok(ObjectKey(SmolStr::new(if b { "true" } else { "false" })))
+            }
         }
+
+        deserializer.deserialize_any(KeyVisitor)
     }
 }
 
@@ -435,71 +752,220 @@ impl From<SmolStr> for SpookyValue {
     }
 }
 
-// ─── From<ciborium::Value> ─────────────────────────────────────────────────
+// `From` conversions to and from `cbor4ii::core::Value` (and `ciborium::Value`
+// behind the `ciborium` feature) live in `crate::cbor`.
 
-impl From<cbor4ii::core::Value> for SpookyValue {
-    fn from(v: cbor4ii::core::Value) -> Self {
-        match v {
-            cbor4ii::core::Value::Null => SpookyValue::Null,
-            cbor4ii::core::Value::Bool(b) => SpookyValue::Bool(b),
-            cbor4ii::core::Value::Integer(i) => {
-                if let Ok(val) = i64::try_from(i) {
+// ─── From/Into rmpv::Value (MessagePack) ────────────────────────────────────
+
+/// Fails with `RecordError::MsgpackError` on a string or map key that is not
+/// valid UTF-8, rather than dropping it.
+#[cfg(feature = "msgpack")]
+impl TryFrom<rmpv::Value> for SpookyValue {
+    type Error = RecordError;
+
+    fn try_from(v: rmpv::Value) -> Result<Self, RecordError> {
+        Ok(match v {
+            rmpv::Value::Nil => SpookyValue::Null,
+            rmpv::Value::Boolean(b) => SpookyValue::Bool(b),
+            rmpv::Value::Integer(i) => {
+                if let Some(val) = i.as_i64() {
                     SpookyValue::Number(SpookyNumber::I64(val))
-                } else if let Ok(val) = u64::try_from(i) {
+                } else if let Some(val) = i.as_u64() {
                     SpookyValue::Number(SpookyNumber::U64(val))
                 } else {
-                    SpookyValue::Number(SpookyNumber::F64(i as f64))
+                    SpookyValue::Number(SpookyNumber::F64(i.as_f64().unwrap_or(0.0)))
                 }
             }
-            cbor4ii::core::Value::Float(f) => SpookyValue::Number(SpookyNumber::F64(f)),
-            cbor4ii::core::Value::Text(s) => SpookyValue::Str(SmolStr::from(s)),
-            cbor4ii::core::Value::Array(arr) => {
-                SpookyValue::Array(arr.into_iter().map(SpookyValue::from).collect())
-            }
-            cbor4ii::core::Value::Map(map) => SpookyValue::Object(
+            rmpv::Value::F32(f) => SpookyValue::Number(SpookyNumber::F64(f as f64)),
+            rmpv::Value::F64(f) => SpookyValue::Number(SpookyNumber::F64(f)),
+            rmpv::Value::String(s) => SpookyValue::Str(msgpack_str(s)?),
+            rmpv::Value::Array(arr) => SpookyValue::Array(
+                arr.into_iter()
+                    .map(SpookyValue::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            rmpv::Value::Map(map) => SpookyValue::Object(
                 map.into_iter()
                     .map(|(k, v)| {
                         let key = match k {
-                            cbor4ii::core::Value::Text(s) => SmolStr::from(s),
-                            cbor4ii::core::Value::Integer(i) => SmolStr::from(i.to_string()),
+                            rmpv::Value::String(s) => msgpack_str(s)?,
+                            rmpv::Value::Integer(i) => SmolStr::from(i.to_string()),
                             other => SmolStr::from(format!("{:?}", other)),
                         };
-                        (key, SpookyValue::from(v))
+                        Ok((key, SpookyValue::try_from(v)?))
                     })
-                    .collect(),
+                    .collect::<Result<_, RecordError>>()?,
             ),
             _ => SpookyValue::Null,
-        }
+        })
     }
 }
 
-// ─── Into<cbor4ii::core::Value> ──────────────────────────────────────────────────
+#[cfg(feature = "msgpack")]
+fn msgpack_str(s: rmpv::Utf8String) -> Result<SmolStr, RecordError> {
+    match s.into_str() {
+        Some(s) => Ok(SmolStr::from(s)),
+        None => Err(RecordError::MsgpackError("string is not valid UTF-8".into())),
+    }
+}
 
-impl From<SpookyValue> for cbor4ii::core::Value {
+#[cfg(feature = "msgpack")]
+impl From<SpookyValue> for rmpv::Value {
     fn from(val: SpookyValue) -> Self {
         match val {
-            SpookyValue::Null => cbor4ii::core::Value::Null,
-            SpookyValue::Bool(b) => cbor4ii::core::Value::Bool(b),
+            SpookyValue::Null => rmpv::Value::Nil,
+            SpookyValue::Bool(b) => rmpv::Value::Boolean(b),
             SpookyValue::Number(n) => match n {
-                SpookyNumber::I64(i) => cbor4ii::core::Value::Integer(i as i128),
-                SpookyNumber::U64(u) => cbor4ii::core::Value::Integer(u as i128),
-                SpookyNumber::F64(f) => cbor4ii::core::Value::Float(f),
+                SpookyNumber::I64(i) => rmpv::Value::from(i),
+                SpookyNumber::U64(u) => rmpv::Value::from(u),
+                SpookyNumber::F64(f) => rmpv::Value::F64(f),
             },
-            SpookyValue::Str(s) => cbor4ii::core::Value::Text(s.to_string()),
+            SpookyValue::Str(s) => rmpv::Value::from(s.as_str()),
             SpookyValue::Array(arr) => {
-                cbor4ii::core::Value::Array(arr.into_iter().map(|v| v.into()).collect())
+                rmpv::Value::Array(arr.into_iter().map(|v| v.into()).collect())
+            }
+            SpookyValue::Object(obj) => rmpv::Value::Map(
+                obj.into_iter()
+                    .map(|(k, v)| (rmpv::Value::from(k.as_str()), v.into()))
+                    .collect(),
+            ),
+            SpookyValue::Tagged(_, inner) => (*inner).into(),
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl SpookyValue {
+    /// Decode MessagePack bytes into a SpookyValue. Strings must be UTF-8.
+    pub fn from_msgpack(mut bytes: &[u8]) -> Result<SpookyValue, RecordError> {
+        let value = rmpv::decode::read_value(&mut bytes)
+            .map_err(|e| RecordError::MsgpackError(e.to_string()))?;
+        SpookyValue::try_from(value)
+    }
+
+    /// Encode this value as MessagePack bytes.
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, RecordError> {
+        let value = rmpv::Value::from(self.clone());
+        let mut buf = Vec::new();
+        rmpv::encode::write_value(&mut buf, &value)
+            .map_err(|e| RecordError::MsgpackError(e.to_string()))?;
+        Ok(buf)
+    }
+}
+
+// ─── From/Into bson::Bson (BSON) ────────────────────────────────────────────
+//
+// BSON types without a SpookyValue counterpart map as the CBOR bridge maps
+// SurrealDB tags: datetimes → I64 nanoseconds since the Unix epoch, object
+// ids → hex text, decimals → text, binary → array of byte values. They come
+// back as those plain values from `From<SpookyValue>`.
+
+#[cfg(feature = "bson")]
+impl From<bson::Bson> for SpookyValue {
+    fn from(v: bson::Bson) -> Self {
+        use bson::Bson;
+        match v {
+            Bson::Null | Bson::Undefined | Bson::MaxKey | Bson::MinKey | Bson::DbPointer(_) => {
+                SpookyValue::Null
+            }
+            Bson::Boolean(b) => SpookyValue::Bool(b),
+            Bson::Int32(i) => SpookyValue::Number(SpookyNumber::I64(i as i64)),
+            Bson::Int64(i) => SpookyValue::Number(SpookyNumber::I64(i)),
+            Bson::Double(f) => SpookyValue::Number(SpookyNumber::F64(f)),
+            Bson::DateTime(dt) => {
+                let nanos = dt.timestamp_millis().saturating_mul(1_000_000);
+                SpookyValue::Number(SpookyNumber::I64(nanos))
+            }
+            Bson::Timestamp(ts) => {
+                let packed = (ts.time as u64) << 32 | ts.increment as u64;
+                SpookyValue::Number(SpookyNumber::U64(packed))
+            }
+            Bson::String(s) | Bson::Symbol(s) | Bson::JavaScriptCode(s) => {
+                SpookyValue::Str(SmolStr::from(s))
             }
-            SpookyValue::Object(obj) => cbor4ii::core::Value::Map(
+            Bson::JavaScriptCodeWithScope(code) => SpookyValue::Str(SmolStr::from(code.code)),
+            Bson::ObjectId(oid) => SpookyValue::Str(SmolStr::from(oid.to_hex())),
+            Bson::Decimal128(d) => SpookyValue::Str(SmolStr::from(d.to_string())),
+            Bson::RegularExpression(re) => {
+                SpookyValue::Str(SmolStr::from(format!("/{}/{}", re.pattern, re.options)))
+            }
+            Bson::Binary(bin) => SpookyValue::Array(
+                bin.bytes
+                    .into_iter()
+                    .map(|b| SpookyValue::Number(SpookyNumber::I64(b as i64)))
+                    .collect(),
+            ),
+            Bson::Array(arr) => {
+                SpookyValue::Array(arr.into_iter().map(SpookyValue::from).collect())
+            }
+            Bson::Document(doc) => SpookyValue::from(doc),
+        }
+    }
+}
+
+#[cfg(feature = "bson")]
+impl From<bson::Document> for SpookyValue {
+    fn from(doc: bson::Document) -> Self {
+        SpookyValue::Object(
+            doc.into_iter()
+                .map(|(k, v)| (SmolStr::from(k), SpookyValue::from(v)))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "bson")]
+impl From<SpookyValue> for bson::Bson {
+    fn from(val: SpookyValue) -> Self {
+        use bson::Bson;
+        match val {
+            SpookyValue::Null => Bson::Null,
+            SpookyValue::Bool(b) => Bson::Boolean(b),
+            SpookyValue::Number(n) => match n {
+                SpookyNumber::I64(i) => Bson::Int64(i),
+                // BSON has no unsigned integers.
+                SpookyNumber::U64(u) => match i64::try_from(u) {
+                    Ok(i) => Bson::Int64(i),
+                    Err(_) => Bson::Double(u as f64),
+                },
+                SpookyNumber::F64(f) => Bson::Double(f),
+            },
+            SpookyValue::Str(s) => Bson::String(s.to_string()),
+            SpookyValue::Array(arr) => Bson::Array(arr.into_iter().map(|v| v.into()).collect()),
+            SpookyValue::Object(obj) => Bson::Document(
                 obj.into_iter()
-                    .map(|(k, v)| (cbor4ii::core::Value::Text(k.to_string()), v.into()))
+                    .map(|(k, v)| (k.to_string(), v.into()))
                     .collect(),
             ),
+            SpookyValue::Tagged(_, inner) => (*inner).into(),
         }
     }
 }
 
-// ─── From/Into serde_json::Value ────────────────────────────────────────────
+#[cfg(feature = "bson")]
+impl SpookyValue {
+    /// Decode a BSON document into a SpookyValue object.
+    pub fn from_bson(bytes: &[u8]) -> Result<SpookyValue, RecordError> {
+        let doc = bson::Document::from_reader(bytes)
+            .map_err(|e| RecordError::BsonError(e.to_string()))?;
+        Ok(SpookyValue::from(doc))
+    }
 
+    /// Encode this value as a BSON document. Only objects have a BSON
+    /// document form; anything else is `SerializationNotObject`.
+    pub fn to_bson(&self) -> Result<Vec<u8>, RecordError> {
+        let bson::Bson::Document(doc) = bson::Bson::from(self.clone()) else {
+            return Err(RecordError::SerializationNotObject);
+        };
+        let mut buf = Vec::new();
+        doc.to_writer(&mut buf).map_err(|e| RecordError::BsonError(e.to_string()))?;
+        Ok(buf)
+    }
+}
+
+// ─── From/TryFrom serde_json::Value ─────────────────────────────────────────
+
+#[cfg(feature = "json")]
 impl From<serde_json::Value> for SpookyValue {
     fn from(v: serde_json::Value) -> Self {
         match v {
@@ -527,25 +993,165 @@ impl From<serde_json::Value> for SpookyValue {
     }
 }
 
-impl From<SpookyValue> for serde_json::Value {
-    fn from(val: SpookyValue) -> Self {
-        match val {
+/// Fallible because JSON has no representation for NaN / ±Inf.
+/// I64 and U64 map to the matching `serde_json::Number` integer kind.
+#[cfg(feature = "json")]
+impl TryFrom<SpookyValue> for serde_json::Value {
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        Ok(match val {
             SpookyValue::Null => serde_json::Value::Null,
             SpookyValue::Bool(b) => serde_json::Value::Bool(b),
             SpookyValue::Number(n) => match n {
-                SpookyNumber::I64(i) => serde_json::json!(i),
-                SpookyNumber::U64(u) => serde_json::json!(u),
-                SpookyNumber::F64(f) => serde_json::json!(f),
+                SpookyNumber::I64(i) => serde_json::Value::Number(i.into()),
+                SpookyNumber::U64(u) => serde_json::Value::Number(u.into()),
+                SpookyNumber::F64(f) => serde_json::Number::from_f64(f)
+                    .map(serde_json::Value::Number)
+                    .ok_or(ConversionError::NonFiniteFloat(f))?,
             },
             SpookyValue::Str(s) => serde_json::Value::String(s.to_string()),
-            SpookyValue::Array(arr) => {
-                serde_json::Value::Array(arr.into_iter().map(|v| v.into()).collect())
-            }
+            SpookyValue::Array(arr) => serde_json::Value::Array(
+                arr.into_iter()
+                    .map(serde_json::Value::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
             SpookyValue::Object(obj) => serde_json::Value::Object(
                 obj.into_iter()
-                    .map(|(k, v)| (k.to_string(), v.into()))
-                    .collect(),
+                    .map(|(k, v)| Ok((k.to_string(), serde_json::Value::try_from(v)?)))
+                    .collect::<Result<_, ConversionError>>()?,
             ),
+            SpookyValue::Tagged(_, inner) => serde_json::Value::try_from(*inner)?,
+        })
+    }
+}
+
+// ─── TryFrom SpookyValue → Rust types ───────────────────────────────────────
+//
+// Strict by kind, lenient across numeric variants: `i64` accepts U64 or a
+// whole F64 in range, but never a string or bool. Element / member failures
+// are wrapped with their index or key so nested errors point at the culprit.
+
+#[inline]
+fn mismatch(expected: &'static str, found: &SpookyValue) -> ConversionError {
+    ConversionError::TypeMismatch {
+        expected,
+        found: found.type_name(),
+    }
+}
+
+impl TryFrom<SpookyValue> for i64 {
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        match val {
+            SpookyValue::Number(n) => n.as_i64().ok_or(ConversionError::NotRepresentable {
+                target: "i64",
+                value: format!("{n}"),
+            }),
+            other => Err(mismatch("int", &other)),
+        }
+    }
+}
+
+impl TryFrom<SpookyValue> for u64 {
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        match val {
+            SpookyValue::Number(n) => n.as_u64().ok_or(ConversionError::NotRepresentable {
+                target: "u64",
+                value: format!("{n}"),
+            }),
+            other => Err(mismatch("int", &other)),
+        }
+    }
+}
+
+/// Any numeric variant; integers beyond 2^53 round to the nearest f64.
+impl TryFrom<SpookyValue> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        match val {
+            SpookyValue::Number(n) => Ok(n.as_f64()),
+            other => Err(mismatch("number", &other)),
+        }
+    }
+}
+
+impl TryFrom<SpookyValue> for bool {
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        match val {
+            SpookyValue::Bool(b) => Ok(b),
+            other => Err(mismatch("bool", &other)),
+        }
+    }
+}
+
+impl TryFrom<SpookyValue> for SmolStr {
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        match val {
+            SpookyValue::Str(s) => Ok(s),
+            other => Err(mismatch("string", &other)),
+        }
+    }
+}
+
+impl TryFrom<SpookyValue> for String {
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        SmolStr::try_from(val).map(String::from)
+    }
+}
+
+impl<T> TryFrom<SpookyValue> for Vec<T>
+where
+    T: TryFrom<SpookyValue, Error = ConversionError>,
+{
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        match val {
+            SpookyValue::Array(arr) => arr
+                .into_iter()
+                .enumerate()
+                .map(|(index, v)| {
+                    T::try_from(v).map_err(|e| ConversionError::Element {
+                        index,
+                        source: Box::new(e),
+                    })
+                })
+                .collect(),
+            other => Err(mismatch("array", &other)),
+        }
+    }
+}
+
+impl<T> TryFrom<SpookyValue> for std::collections::BTreeMap<SmolStr, T>
+where
+    T: TryFrom<SpookyValue, Error = ConversionError>,
+{
+    type Error = ConversionError;
+
+    fn try_from(val: SpookyValue) -> Result<Self, Self::Error> {
+        match val {
+            SpookyValue::Object(map) => map
+                .into_iter()
+                .map(|(key, v)| match T::try_from(v) {
+                    Ok(t) => Ok((key, t)),
+                    Err(e) => Err(ConversionError::Member {
+                        key,
+                        source: Box::new(e),
+                    }),
+                })
+                .collect(),
+            other => Err(mismatch("object", &other)),
         }
     }
 }
@@ -571,3 +1177,263 @@ macro_rules! spooky_obj {
         $val
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SpookyValue {
+        let mut settings = FastMap::new();
+        settings.insert(SmolStr::new("theme"), SpookyValue::from("dark"));
+        settings.insert(SmolStr::new("level"), SpookyValue::from(3i64));
+
+        let mut map = FastMap::new();
+        map.insert(SmolStr::new("name"), SpookyValue::from("Alice"));
+        map.insert(SmolStr::new("age"), SpookyValue::from(-28i64));
+        map.insert(SmolStr::new("big"), SpookyValue::from(u64::MAX));
+        map.insert(SmolStr::new("score"), SpookyValue::from(99.5f64));
+        map.insert(SmolStr::new("active"), SpookyValue::from(true));
+        map.insert(SmolStr::new("metadata"), SpookyValue::Null);
+        map.insert(
+            SmolStr::new("tags"),
+            SpookyValue::Array(vec![SpookyValue::from("rust"), SpookyValue::from(1i64)]),
+        );
+        map.insert(SmolStr::new("settings"), SpookyValue::Object(settings));
+        SpookyValue::Object(map)
+    }
+
+    #[test]
+    fn test_deserialize_json_roundtrip() {
+        let original = sample();
+        let text = serde_json::to_string(&original).unwrap();
+        let back: SpookyValue = serde_json::from_str(&text).unwrap();
+        assert_eq!(back, original);
+    }
+
+    #[test]
+    fn test_deserialize_cbor_roundtrip() {
+        let original = sample();
+        let bytes = cbor4ii::serde::to_vec(Vec::new(), &original).unwrap();
+        let back: SpookyValue = cbor4ii::serde::from_slice(&bytes).unwrap();
+        assert_eq!(back, original);
+    }
+
+    #[test]
+    fn test_deserialize_number_variants() {
+        let v: SpookyValue = serde_json::from_str("[-1, 1, 18446744073709551615, 1.5]").unwrap();
+        let arr = v.as_array().unwrap();
+        assert!(matches!(arr[0], SpookyValue::Number(SpookyNumber::I64(-1))));
+        // Non-negative integers that fit i64 follow the CBOR bridge and become I64.
+        assert!(matches!(arr[1], SpookyValue::Number(SpookyNumber::I64(1))));
+        assert!(matches!(arr[2], SpookyValue::Number(SpookyNumber::U64(u64::MAX))));
+        assert!(matches!(arr[3], SpookyValue::Number(SpookyNumber::F64(f)) if f == 1.5));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_value_roundtrip_preserves_variants() {
+        let original = sample();
+        let json = serde_json::Value::try_from(original.clone()).unwrap();
+        assert!(json["big"].is_u64());
+        assert!(json["age"].is_i64());
+        assert!(json["score"].is_f64());
+        let back = SpookyValue::from(json);
+        assert_eq!(back, original);
+        assert!(matches!(back.get("big"), Some(SpookyValue::Number(SpookyNumber::U64(u64::MAX)))));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_value_rejects_non_finite() {
+        let v = SpookyValue::Array(vec![SpookyValue::from(f64::NAN)]);
+        assert!(matches!(
+            serde_json::Value::try_from(v),
+            Err(ConversionError::NonFiniteFloat(_))
+        ));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_roundtrip() {
+        let original = sample();
+        let bytes = original.to_msgpack().unwrap();
+        let back = SpookyValue::from_msgpack(&bytes).unwrap();
+        assert_eq!(back, original);
+        assert!(matches!(back.get("big"), Some(SpookyValue::Number(SpookyNumber::U64(u64::MAX)))));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_rejects_invalid_utf8() {
+        // fixstr of length 2 holding an invalid UTF-8 sequence.
+        let value = [0x92, 0x01, 0xa2, 0xc3, 0x28];
+        assert!(matches!(
+            SpookyValue::from_msgpack(&value),
+            Err(RecordError::MsgpackError(_))
+        ));
+        let key = [0x81, 0xa2, 0xc3, 0x28, 0x01];
+        assert!(SpookyValue::from_msgpack(&key).is_err());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_to_record_bytes() {
+        use crate::spooky_record::{SpookyReadable, SpookyRecord};
+        let bytes = sample().to_msgpack().unwrap();
+        let (buf, count) = crate::serialization::from_msgpack_slice(&bytes).unwrap();
+        let record = SpookyRecord::new(&buf, count);
+        assert_eq!(record.get_str("name"), Some("Alice"));
+        assert_eq!(record.get_i64("age"), Some(-28));
+        assert_eq!(record.get_u64("big"), Some(u64::MAX));
+        assert_eq!(
+            record
+                .get_field::<SpookyValue>("settings")
+                .and_then(|s| s.get("theme").cloned()),
+            Some(SpookyValue::from("dark"))
+        );
+    }
+
+    #[cfg(feature = "bson")]
+    #[test]
+    fn test_bson_roundtrip_and_record_bytes() {
+        use crate::spooky_record::{SpookyReadable, SpookyRecord};
+        let mut original = sample();
+        // BSON has no u64; one past i64::MAX comes back as a double.
+        original.as_object_mut().unwrap().remove("big");
+        let bytes = original.to_bson().unwrap();
+        assert_eq!(SpookyValue::from_bson(&bytes).unwrap(), original);
+        assert!(SpookyValue::from(1).to_bson().is_err());
+
+        let oid = bson::oid::ObjectId::parse_str("65a1f0c2e4b0a1b2c3d4e5f6").unwrap();
+        let doc = bson::doc! {
+            "_id": oid,
+            "name": "Alice",
+            "n": 7_i32,
+            "at": bson::DateTime::from_millis(1_500),
+            "tags": ["a", { "x": 1_i64 }],
+        };
+        let (buf, count) = crate::serialization::from_bson(&doc).unwrap();
+        let record = SpookyRecord::new(&buf, count);
+        assert_eq!(record.get_str("_id"), Some("65a1f0c2e4b0a1b2c3d4e5f6"));
+        assert_eq!(record.get_i64("n"), Some(7));
+        assert_eq!(record.get_i64("at"), Some(1_500_000_000));
+        let tags = record.get_field::<bson::Bson>("tags").unwrap();
+        assert_eq!(tags, bson::bson!(["a", { "x": 1_i64 }]));
+        let mut raw = Vec::new();
+        doc.to_writer(&mut raw).unwrap();
+        assert_eq!(crate::serialization::from_bson_slice(&raw).unwrap(), (buf, count));
+    }
+
+    #[test]
+    fn test_pointer_nested_lookup() {
+        let mut v = sample();
+        assert_eq!(v.pointer(""), Some(&v.clone()));
+        assert_eq!(
+            v.pointer("/settings/theme").and_then(|t| t.as_str()),
+            Some("dark")
+        );
+        assert_eq!(v.pointer("/tags/1").and_then(|t| t.as_i64()), Some(1));
+        assert!(v.pointer("/tags/01").is_none());
+        assert!(v.pointer("/tags/9").is_none());
+        assert!(v.pointer("/name/x").is_none());
+        assert!(v.pointer("settings").is_none());
+
+        *v.pointer_mut("/settings/level").unwrap() = SpookyValue::from(7i64);
+        assert_eq!(v.pointer("/settings/level").and_then(|l| l.as_i64()), Some(7));
+    }
+
+    #[test]
+    fn test_pointer_escaped_tokens() {
+        let mut map = FastMap::new();
+        map.insert(SmolStr::new("a/b"), SpookyValue::from(1i64));
+        map.insert(SmolStr::new("m~n"), SpookyValue::from(2i64));
+        let v = SpookyValue::Object(map);
+        assert_eq!(v.pointer("/a~1b").and_then(|x| x.as_i64()), Some(1));
+        assert_eq!(v.pointer("/m~0n").and_then(|x| x.as_i64()), Some(2));
+    }
+
+    #[test]
+    fn test_deserialize_integer_map_keys() {
+        // CBOR map {1: "a"} — integer keys are stringified.
+        let bytes = [0xa1, 0x01, 0x61, b'a'];
+        let v: SpookyValue = cbor4ii::serde::from_slice(&bytes).unwrap();
+        assert_eq!(v.get("1").and_then(|x| x.as_str()), Some("a"));
+    }
+
+    #[test]
+    fn test_canonical_hash_numeric_variants_and_order() {
+        let a = SpookyValue::from(1i64);
+        let b = SpookyValue::from(1u64);
+        let c = SpookyValue::from(1.0f64);
+        assert_eq!(a.canonical_hash(), b.canonical_hash());
+        assert_eq!(a.canonical_hash(), c.canonical_hash());
+        assert_eq!(
+            SpookyValue::from(0.0f64).canonical_hash(),
+            SpookyValue::from(-0.0f64).canonical_hash()
+        );
+        assert_ne!(a.canonical_hash(), SpookyValue::from(2i64).canonical_hash());
+        assert_ne!(a.canonical_hash(), SpookyValue::from("1").canonical_hash());
+
+        let x = SpookyValue::from_json_str(r#"{"a":1,"b":[true,null],"c":"x"}"#).unwrap();
+        let y = SpookyValue::from_json_str(r#"{"c":"x","b":[true,null],"a":1.0}"#).unwrap();
+        assert_eq!(x, y);
+        assert_eq!(x.canonical_hash(), y.canonical_hash());
+    }
+
+    #[test]
+    fn test_canonical_hash_is_stable() {
+        // Pinned: the byte stream is a format guarantee, not an implementation detail.
+        let v = SpookyValue::from_json_str(r#"{"id":"user:1","n":[1,2.5]}"#).unwrap();
+        assert_eq!(v.canonical_hash(), 0x40a6_cffd_3c38_7d10);
+        // Arrays/strings of the same content must not collide with a shifted split.
+        let s1 = SpookyValue::from_json_str(r#"["ab","c"]"#).unwrap();
+        let s2 = SpookyValue::from_json_str(r#"["a","bc"]"#).unwrap();
+        assert_ne!(s1.canonical_hash(), s2.canonical_hash());
+    }
+    #[test]
+    fn test_try_from_primitives() {
+        assert_eq!(i64::try_from(SpookyValue::from(7u64)), Ok(7));
+        assert_eq!(i64::try_from(SpookyValue::from(3.0f64)), Ok(3));
+        assert_eq!(u64::try_from(SpookyValue::from(u64::MAX)), Ok(u64::MAX));
+        assert_eq!(f64::try_from(SpookyValue::from(2i64)), Ok(2.0));
+        assert_eq!(bool::try_from(SpookyValue::Bool(true)), Ok(true));
+        assert_eq!(String::try_from(SpookyValue::from("hi")).as_deref(), Ok("hi"));
+        assert_eq!(
+            SmolStr::try_from(SpookyValue::from("hi")),
+            Ok(SmolStr::new("hi"))
+        );
+
+        assert_eq!(
+            i64::try_from(SpookyValue::from("7")),
+            Err(ConversionError::TypeMismatch {
+                expected: "int",
+                found: "string"
+            })
+        );
+        assert!(matches!(
+            i64::try_from(SpookyValue::from(2.5f64)),
+            Err(ConversionError::NotRepresentable { target: "i64", .. })
+        ));
+        assert!(matches!(
+            u64::try_from(SpookyValue::from(-1i64)),
+            Err(ConversionError::NotRepresentable { target: "u64", .. })
+        ));
+    }
+
+    #[test]
+    fn test_try_from_collections_report_location() {
+        let arr = SpookyValue::from_json_str("[1,2,3]").unwrap();
+        assert_eq!(Vec::<i64>::try_from(arr), Ok(vec![1, 2, 3]));
+
+        let obj = SpookyValue::from_json_str(r#"{"a":["x"],"b":["y","z"]}"#).unwrap();
+        let map = std::collections::BTreeMap::<SmolStr, Vec<String>>::try_from(obj).unwrap();
+        assert_eq!(map["b"], vec!["y".to_string(), "z".to_string()]);
+
+        let bad = SpookyValue::from_json_str(r#"{"a":[1],"b":[2,"x"]}"#).unwrap();
+        let err = std::collections::BTreeMap::<SmolStr, Vec<i64>>::try_from(bad).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at key \"b\": at index 1: expected int, found string"
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use crate::cbor::int_to_number;
+use crate::spooky_value::{SpookyNumber, SpookyValue};
+use smol_str::SmolStr;
+
+// ─── SurrealDB CBOR tags ────────────────────────────────────────────────────
+//
+// SurrealDB's CBOR protocol wraps its non-JSON types in tags. The bridge in
+// `crate::cbor` hands every tagged item to `apply_tag`, which maps them onto
+// the closest `SpookyValue` so they land in records as native scalars:
+//
+//   datetime (0, 1, 12) → I64 nanoseconds since the Unix epoch
+//   duration (13, 14)   → I64 nanoseconds
+//   record id (8)       → Str `table:id`
+//   uuid (9, 37)        → Str, lowercase hyphenated
+//   decimal (10)        → I64 when integral and in range, else F64 (lossy)
+//   NONE (6)            → Null
+//   table (7)           → Str
+//
+// Integer timestamps keep range queries and ORDER BY working on the plain
+// I64 record tag. Anything unparseable is kept as decoded. Every other tag
+// (ranges, geometries, futures, non-SurrealDB tags) is kept as
+// `SpookyValue::Tagged`, so it is written back out unchanged.
+
+/// RFC 3339 datetime string (standard tag).
+pub const TAG_DATETIME: u64 = 0;
+/// Epoch-based datetime in seconds, integer or float (standard tag).
+pub const TAG_EPOCH: u64 = 1;
+pub const TAG_NONE: u64 = 6;
+pub const TAG_TABLE: u64 = 7;
+/// `[table, id]`, or a `"table:id"` string.
+pub const TAG_RECORD_ID: u64 = 8;
+pub const TAG_UUID_STRING: u64 = 9;
+pub const TAG_DECIMAL_STRING: u64 = 10;
+/// `[seconds, nanoseconds]` since the epoch; either part may be omitted.
+pub const TAG_DATETIME_COMPACT: u64 = 12;
+pub const TAG_DURATION_STRING: u64 = 13;
+/// `[seconds, nanoseconds]`; either part may be omitted.
+pub const TAG_DURATION_COMPACT: u64 = 14;
+/// 16 raw bytes.
+pub const TAG_UUID: u64 = 37;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+/// Map a tagged item whose payload has already been decoded.
+pub(crate) fn apply_tag(tag: u64, inner: SpookyValue) -> SpookyValue {
+    match (tag, inner) {
+        (TAG_NONE, _) => SpookyValue::Null,
+        (TAG_DATETIME, SpookyValue::Str(s)) => nanos_or(parse_datetime(&s), s),
+        (TAG_DURATION_STRING, SpookyValue::Str(s)) => nanos_or(parse_duration(&s), s),
+        (TAG_EPOCH, SpookyValue::Number(n)) => match epoch_nanos(n) {
+            Some(ns) => SpookyValue::from(ns),
+            None => SpookyValue::Number(n),
+        },
+        (TAG_DATETIME_COMPACT | TAG_DURATION_COMPACT, SpookyValue::Array(parts)) => {
+            match compact_nanos(&parts) {
+                Some(ns) => SpookyValue::from(ns),
+                None => SpookyValue::Array(parts),
+            }
+        }
+        (TAG_RECORD_ID, SpookyValue::Array(parts)) if parts.len() == 2 => match &parts[0] {
+            SpookyValue::Str(table) => SpookyValue::Str(record_id(table, &parts[1])),
+            _ => SpookyValue::Array(parts),
+        },
+        (TAG_DECIMAL_STRING, SpookyValue::Str(s)) => match parse_decimal(&s) {
+            Some(n) => SpookyValue::Number(n),
+            None => SpookyValue::Str(s),
+        },
+        (tag, inner) if is_known_tag(tag) => inner,
+        (tag, inner) => SpookyValue::Tagged(tag, Box::new(inner)),
+    }
+}
+
+/// Whether `apply_tag` has a mapping for `tag`.
+fn is_known_tag(tag: u64) -> bool {
+    matches!(
+        tag,
+        TAG_DATETIME
+            | TAG_EPOCH
+            | TAG_NONE
+            | TAG_TABLE
+            | TAG_RECORD_ID
+            | TAG_UUID_STRING
+            | TAG_DECIMAL_STRING
+            | TAG_DATETIME_COMPACT
+            | TAG_DURATION_STRING
+            | TAG_DURATION_COMPACT
+            | TAG_UUID
+    )
+}
+
+/// Map a tag whose payload is a byte string, before it would be turned into
+/// an array of byte values. `None` if the tag does not take bytes.
+pub(crate) fn apply_bytes_tag(tag: u64, bytes: &[u8]) -> Option<SpookyValue> {
+    match tag {
+        TAG_UUID => uuid_string(bytes).map(SpookyValue::Str),
+        _ => None,
+    }
+}
+
+#[inline]
+fn nanos_or(ns: Option<i64>, fallback: SmolStr) -> SpookyValue {
+    match ns {
+        Some(ns) => SpookyValue::from(ns),
+        None => SpookyValue::Str(fallback),
+    }
+}
+
+fn epoch_nanos(n: SpookyNumber) -> Option<i64> {
+    match n {
+        SpookyNumber::I64(s) => s.checked_mul(NANOS_PER_SEC),
+        SpookyNumber::U64(s) => i64::try_from(s).ok()?.checked_mul(NANOS_PER_SEC),
+        SpookyNumber::F64(s) => {
+            let ns = (s * NANOS_PER_SEC as f64).round();
+            (ns.is_finite() && ns >= i64::MIN as f64 && ns < i64::MAX as f64).then_some(ns as i64)
+        }
+    }
+}
+
+fn compact_nanos(parts: &[SpookyValue]) -> Option<i64> {
+    let part = |i: usize| match parts.get(i) {
+        None => Some(0),
+        Some(SpookyValue::Number(n)) => n.as_i64(),
+        Some(_) => None,
+    };
+    if parts.len() > 2 {
+        return None;
+    }
+    part(0)?.checked_mul(NANOS_PER_SEC)?.checked_add(part(1)?)
+}
+
+/// `table:id`. String and integer ids are written as-is; other ids (objects,
+/// arrays) use the SurrealQL `Display` form.
+fn record_id(table: &str, id: &SpookyValue) -> SmolStr {
+    match id {
+        SpookyValue::Str(s) => SmolStr::from(format!("{table}:{s}")),
+        other => SmolStr::from(format!("{table}:{other}")),
+    }
+}
+
+fn parse_decimal(s: &str) -> Option<SpookyNumber> {
+    if let Ok(i) = s.parse::<i128>() {
+        return Some(int_to_number(i));
+    }
+    s.parse::<f64>().ok().map(SpookyNumber::F64)
+}
+
+fn uuid_string(bytes: &[u8]) -> Option<SmolStr> {
+    let b: &[u8; 16] = bytes.try_into().ok()?;
+    Some(SmolStr::from(format!(
+        "{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        b[0],
+        b[1],
+        b[2],
+        b[3],
+        b[4],
+        b[5],
+        b[6],
+        b[7],
+        b[8],
+        b[9],
+        b[10],
+        b[11],
+        b[12],
+        b[13],
+        b[14],
+        b[15]
+    )))
+}
+
+// ─── Parsers ────────────────────────────────────────────────────────────────
+
+/// Parse an RFC 3339 datetime (`2024-01-01T12:30:00.5Z`, `...+02:00`) into
+/// nanoseconds since the Unix epoch. Same unit as ingested SurrealDB
+/// datetimes, so it can be used to build range bounds.
+pub fn parse_datetime(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    let digits = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = b.get(range)?;
+        part.iter().all(u8::is_ascii_digit).then(|| {
+            part.iter()
+                .fold(0i64, |acc, d| acc * 10 + (d - b'0') as i64)
+        })
+    };
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || b[13] != b':' || b[16] != b':' {
+        return None;
+    }
+    if !matches!(b[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    let (year, month, day) = (digits(0..4)?, digits(5..7)?, digits(8..10)?);
+    let (hour, minute, second) = (digits(11..13)?, digits(14..16)?, digits(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    // 60 admits a leap second; it folds into the next minute.
+    if second > 60 {
+        return None;
+    }
+
+    let mut pos = 19;
+    let mut nanos = 0i64;
+    if b[pos] == b'.' {
+        pos += 1;
+        let start = pos;
+        while pos < b.len() && b[pos].is_ascii_digit() {
+            if pos - start < 9 {
+                nanos = nanos * 10 + (b[pos] - b'0') as i64;
+            }
+            pos += 1;
+        }
+        if pos == start {
+            return None;
+        }
+        for _ in (pos - start)..9 {
+            nanos *= 10;
+        }
+    }
+
+    let offset_secs = match b.get(pos..)? {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), ..] if b.len() == pos + 6 && b[pos + 3] == b':' => {
+            let secs = digits(pos + 1..pos + 3)? * 3600 + digits(pos + 4..pos + 6)? * 60;
+            if *sign == b'+' { secs } else { -secs }
+        }
+        _ => return None,
+    };
+
+    let days = days_from_civil(year, month, day);
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second - offset_secs;
+    secs.checked_mul(NANOS_PER_SEC)?.checked_add(nanos)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parse a SurrealQL duration such as `1h30m` or `2w3d` into nanoseconds.
+/// Units: `y` (365d), `w`, `d`, `h`, `m`, `s`, `ms`, `us`/`µs`, `ns`.
+pub fn parse_duration(s: &str) -> Option<i64> {
+    let mut rest = s;
+    let mut total = 0i64;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if split == 0 {
+            return None;
+        }
+        let amount: i64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+        let (unit, len) = [
+            ("ns", 1),
+            ("us", 1_000),
+            ("µs", 1_000),
+            ("ms", 1_000_000),
+            ("s", NANOS_PER_SEC),
+            ("m", 60 * NANOS_PER_SEC),
+            ("h", 3_600 * NANOS_PER_SEC),
+            ("d", 86_400 * NANOS_PER_SEC),
+            ("w", 7 * 86_400 * NANOS_PER_SEC),
+            ("y", 365 * 86_400 * NANOS_PER_SEC),
+        ]
+        .into_iter()
+        .find(|(suffix, _)| rest.starts_with(suffix))
+        .map(|(suffix, scale)| (scale, suffix.len()))?;
+        rest = &rest[len..];
+        total = total.checked_add(amount.checked_mul(unit)?)?;
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_datetime() {
+        assert_eq!(parse_datetime("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_datetime("2024-01-01T00:00:00Z"),
+            Some(1_704_067_200 * NANOS_PER_SEC)
+        );
+        assert_eq!(
+            parse_datetime("2024-01-01T02:00:00.25+02:00"),
+            Some(1_704_067_200 * NANOS_PER_SEC + 250_000_000)
+        );
+        assert_eq!(parse_datetime("1969-12-31T23:59:59.999999999Z"), Some(-1));
+        assert_eq!(parse_datetime("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_datetime("2024-01-01"), None);
+        assert_eq!(parse_datetime("2024-01-01T00:00:00"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1h30m"), Some(5_400 * NANOS_PER_SEC));
+        assert_eq!(parse_duration("1s500ms"), Some(1_500_000_000));
+        assert_eq!(parse_duration("2w1d"), Some(15 * 86_400 * NANOS_PER_SEC));
+        assert_eq!(parse_duration("10µs5ns"), Some(10_005));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("5"), None);
+        assert_eq!(parse_duration("1x"), None);
+    }
+
+    #[test]
+    fn test_apply_tag() {
+        assert_eq!(
+            apply_tag(
+                TAG_RECORD_ID,
+                SpookyValue::from_json_str(r#"["user","alice"]"#).unwrap()
+            ),
+            SpookyValue::from("user:alice")
+        );
+        assert_eq!(
+            apply_tag(
+                TAG_RECORD_ID,
+                SpookyValue::from_json_str(r#"["user",42]"#).unwrap()
+            ),
+            SpookyValue::from("user:42")
+        );
+        assert_eq!(
+            apply_tag(
+                TAG_DATETIME_COMPACT,
+                SpookyValue::from_json_str("[1704067200,5]").unwrap()
+            ),
+            SpookyValue::from(1_704_067_200 * NANOS_PER_SEC + 5)
+        );
+        assert_eq!(
+            apply_tag(TAG_EPOCH, SpookyValue::from(1.5f64)),
+            SpookyValue::from(1_500_000_000i64)
+        );
+        assert_eq!(
+            apply_tag(TAG_DECIMAL_STRING, SpookyValue::from("12.50")),
+            SpookyValue::from(12.5f64)
+        );
+        assert_eq!(
+            apply_tag(TAG_DECIMAL_STRING, SpookyValue::from("-7")),
+            SpookyValue::from(-7i64)
+        );
+        assert_eq!(apply_tag(TAG_NONE, SpookyValue::Null), SpookyValue::Null);
+        // Unparseable payloads are kept as decoded.
+        assert_eq!(
+            apply_tag(TAG_DATETIME, SpookyValue::from("yesterday")),
+            SpookyValue::from("yesterday")
+        );
+        assert_eq!(
+            apply_bytes_tag(TAG_UUID, &[0xab; 16]),
+            Some(SpookyValue::from("abababab-abab-abab-abab-abababababab"))
+        );
+    }
+    #[test]
+    fn test_tagged_record_ingest_paths_agree() {
+        use crate::serialization::{from_cbor, from_cbor_slice};
+        use crate::spooky_record::{SpookyReadable, SpookyRecord};
+        use cbor4ii::core::Value;
+        use cbor4ii::core::enc::Encode;
+
+        let tag = |t: u64, v: Value| Value::Tag(t, Box::new(v));
+        let text = |s: &str| Value::Text(s.into());
+        let record = Value::Map(vec![
+            (
+                text("id"),
+                tag(
+                    TAG_RECORD_ID,
+                    Value::Array(vec![text("user"), text("alice")]),
+                ),
+            ),
+            (
+                text("created"),
+                tag(TAG_DATETIME, text("2024-01-01T00:00:00Z")),
+            ),
+            (
+                text("ttl"),
+                tag(TAG_DURATION_COMPACT, Value::Array(vec![Value::Integer(90)])),
+            ),
+            (text("key"), tag(TAG_UUID, Value::Bytes(vec![0x11; 16]))),
+            (text("gone"), tag(TAG_NONE, Value::Null)),
+            (
+                text("meta"),
+                Value::Map(vec![(
+                    text("seen"),
+                    tag(
+                        TAG_DATETIME_COMPACT,
+                        Value::Array(vec![Value::Integer(1), Value::Integer(2)]),
+                    ),
+                )]),
+            ),
+        ]);
+        let mut writer = cbor4ii::core::utils::BufWriter::new(Vec::new());
+        record.encode(&mut writer).unwrap();
+        let bytes = writer.into_inner();
+
+        let streamed = from_cbor_slice(&bytes).unwrap();
+        assert_eq!(from_cbor(&record).unwrap(), streamed);
+        let from_tree = crate::serialization::from_spooky(&SpookyValue::from(record)).unwrap();
+        assert_eq!(from_tree, streamed);
+        assert_eq!(
+            crate::serialization::from_spooky(&crate::cbor::decode_slice(&bytes).unwrap()).unwrap(),
+            streamed
+        );
+
+        let (buf, fc) = streamed;
+        let rec = SpookyRecord::new(&buf, fc);
+        assert_eq!(rec.get_str("id"), Some("user:alice"));
+        assert_eq!(rec.get_i64("created"), Some(1_704_067_200 * NANOS_PER_SEC));
+        assert_eq!(rec.get_i64("ttl"), Some(90 * NANOS_PER_SEC));
+        assert_eq!(
+            rec.get_str("key"),
+            Some("11111111-1111-1111-1111-111111111111")
+        );
+        assert_eq!(rec.field_type("gone"), Some(crate::types::TAG_NULL));
+        assert_eq!(
+            rec.get_field::<SpookyValue>("meta"),
+            Some(SpookyValue::from_json_str(r#"{"seen":1000000002}"#).unwrap())
+        );
+    }
+}
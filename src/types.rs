@@ -1,3 +1,5 @@
+use smol_str::SmolStr;
+
 use super::spooky_record::{SpookyReadable, SpookyRecord};
 
 // ─── Type Tags ──────────────────────────────────────────────────────────────
@@ -8,6 +10,7 @@ pub const TAG_F64: u8 = 3;
 pub const TAG_STR: u8 = 4;
 pub const TAG_NESTED_CBOR: u8 = 5; // Array or Object
 pub const TAG_U64: u8 = 6; // Extension
+pub const TAG_BLOB_REF: u8 = 7; // Out-of-line field (db blobs)
 
 // ─── Binary Layout ──────────────────────────────────────────────────────────
 //
@@ -30,6 +33,121 @@ pub const TAG_U64: u8 = 6; // Extension
 pub const HEADER_SIZE: usize = 20; // 4 + 16
 pub const INDEX_ENTRY_SIZE: usize = 20; // 8 + 4 + 4 + 1 + 3
 
+// ─── Field Hash ─────────────────────────────────────────────────────────────
+
+/// The hash a field name is stored under in the index (xxh64, seed 0).
+///
+/// `const`, so hot loops can compute it once, or at compile time with
+/// [`field_hash!`](crate::field_hash), and read through the `*_by_hash`
+/// getters of `SpookyReadable`.
+#[inline]
+pub const fn field_hash(name: &str) -> u64 {
+    xxhash_rust::const_xxh64::xxh64(name.as_bytes(), 0)
+}
+
+/// [`field_hash`] evaluated at compile time: `field_hash!("age")` is a
+/// `u64` constant.
+#[macro_export]
+macro_rules! field_hash {
+    ($name:expr) => {
+        const { $crate::types::field_hash($name) }
+    };
+}
+
+/// Anything a record getter or setter accepts as a field name: `&str`,
+/// `String`, `SmolStr`, or a [`FieldName`] that carries its hash.
+pub trait AsFieldKey {
+    /// The hash the field is stored under (see [`field_hash`]).
+    fn field_hash(&self) -> u64;
+}
+
+impl AsFieldKey for str {
+    #[inline]
+    fn field_hash(&self) -> u64 {
+        field_hash(self)
+    }
+}
+
+impl AsFieldKey for String {
+    #[inline]
+    fn field_hash(&self) -> u64 {
+        field_hash(self)
+    }
+}
+
+impl AsFieldKey for SmolStr {
+    #[inline]
+    fn field_hash(&self) -> u64 {
+        field_hash(self)
+    }
+}
+
+impl<T: AsFieldKey + ?Sized> AsFieldKey for &T {
+    #[inline]
+    fn field_hash(&self) -> u64 {
+        (**self).field_hash()
+    }
+}
+
+/// A field name with its hash computed once. Create one per field and pass
+/// it to the record getters and setters instead of the `&str`, so reading
+/// the field from many records does not rehash the name each time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FieldName {
+    name: SmolStr,
+    hash: u64,
+}
+
+impl FieldName {
+    pub fn new(name: impl Into<SmolStr>) -> Self {
+        let name = name.into();
+        let hash = field_hash(&name);
+        Self { name, hash }
+    }
+
+    /// `const` constructor for names known at compile time.
+    pub const fn from_static(name: &'static str) -> Self {
+        Self {
+            name: SmolStr::new_static(name),
+            hash: field_hash(name),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    /// The cached hash the field is stored under.
+    pub fn name_hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl AsFieldKey for FieldName {
+    #[inline]
+    fn field_hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl From<&str> for FieldName {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<SmolStr> for FieldName {
+    fn from(name: SmolStr) -> Self {
+        Self::new(name)
+    }
+}
+
+impl std::fmt::Display for FieldName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
 // ─── FieldSlot (Cached Field Position) ─────────────────────────────────────
 
 /// Cached field position for O(1) access.
@@ -0,0 +1,360 @@
+use crate::cbor::{self, Head, SliceReader};
+use crate::spooky_value::{SpookyNumber, SpookyValue};
+use crate::types::*;
+
+// ─── SpookyValueRef ─────────────────────────────────────────────────────────
+//
+// Borrowed view over a field's bytes. Scalars are read straight out of the
+// record, strings borrow the record buffer, and nested CBOR stays encoded
+// until something asks for it: `NestedRef::get` / `index` walk the CBOR in
+// place and hand back further borrowed views, `to_value` decodes in full.
+
+/// Non-owning counterpart of `SpookyValue`, returned by
+/// [`SpookyReadable::get_field_ref`](crate::spooky_record::SpookyReadable::get_field_ref).
+#[derive(Debug, Clone, Copy)]
+pub enum SpookyValueRef<'a> {
+    Null,
+    Bool(bool),
+    Number(SpookyNumber),
+    Str(&'a str),
+    /// Field bytes with a type tag this build does not interpret.
+    Bytes(&'a [u8]),
+    /// A CBOR-encoded item (array, object, or a scalar that could not be
+    /// borrowed directly), decoded on demand.
+    Nested(NestedRef<'a>),
+}
+
+/// Encoded CBOR item inside a record. Cheap to copy; nothing is decoded until
+/// a method needs it.
+#[derive(Debug, Clone, Copy)]
+pub struct NestedRef<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SpookyValueRef<'a> {
+    /// View over a raw field. Malformed scalar payloads (wrong length) come
+    /// back as `Bytes`.
+    pub fn from_field(field: FieldRef<'a>) -> Self {
+        let data = field.data;
+        let word = || <[u8; 8]>::try_from(data).ok();
+        match field.type_tag {
+            TAG_NULL => SpookyValueRef::Null,
+            TAG_BOOL if data.len() == 1 => SpookyValueRef::Bool(data[0] != 0),
+            TAG_I64 => match word() {
+                Some(b) => SpookyValueRef::Number(SpookyNumber::I64(i64::from_le_bytes(b))),
+                None => SpookyValueRef::Bytes(data),
+            },
+            TAG_U64 => match word() {
+                Some(b) => SpookyValueRef::Number(SpookyNumber::U64(u64::from_le_bytes(b))),
+                None => SpookyValueRef::Bytes(data),
+            },
+            TAG_F64 => match word() {
+                Some(b) => SpookyValueRef::Number(SpookyNumber::F64(f64::from_le_bytes(b))),
+                None => SpookyValueRef::Bytes(data),
+            },
+            TAG_STR => match std::str::from_utf8(data) {
+                Ok(s) => SpookyValueRef::Str(s),
+                Err(_) => SpookyValueRef::Bytes(data),
+            },
+            TAG_NESTED_CBOR => SpookyValueRef::Nested(NestedRef { bytes: data }),
+            _ => SpookyValueRef::Bytes(data),
+        }
+    }
+
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        matches!(self, SpookyValueRef::Null)
+    }
+
+    #[inline]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            SpookyValueRef::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_number(&self) -> Option<SpookyNumber> {
+        match self {
+            SpookyValueRef::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number()?.as_i64()
+    }
+
+    #[inline]
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().map(SpookyNumber::as_f64)
+    }
+
+    /// The borrowed string, if this is a string.
+    #[inline]
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            SpookyValueRef::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_nested(&self) -> Option<NestedRef<'a>> {
+        match self {
+            SpookyValueRef::Nested(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Owned copy. `None` if nested CBOR fails to decode or the field holds
+    /// uninterpreted `Bytes`.
+    pub fn to_value(&self) -> Option<SpookyValue> {
+        Some(match self {
+            SpookyValueRef::Null => SpookyValue::Null,
+            SpookyValueRef::Bool(b) => SpookyValue::Bool(*b),
+            SpookyValueRef::Number(n) => SpookyValue::Number(*n),
+            SpookyValueRef::Str(s) => SpookyValue::from(*s),
+            SpookyValueRef::Bytes(_) => return None,
+            SpookyValueRef::Nested(n) => return n.to_value(),
+        })
+    }
+}
+
+impl<'a> NestedRef<'a> {
+    /// The raw CBOR bytes.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    #[inline]
+    fn head(&self) -> Option<Head> {
+        SliceReader::new(self.bytes).head().ok()
+    }
+
+    #[inline]
+    pub fn is_array(&self) -> bool {
+        matches!(self.head(), Some(Head::Array(_)))
+    }
+
+    #[inline]
+    pub fn is_object(&self) -> bool {
+        matches!(self.head(), Some(Head::Map(_)))
+    }
+
+    /// Element or entry count from the CBOR head; `None` for
+    /// indefinite-length containers and non-containers.
+    pub fn len(&self) -> Option<usize> {
+        match self.head()? {
+            Head::Array(Some(n)) | Head::Map(Some(n)) => usize::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    /// `Some(true)` for an empty container; `None` where `len` is `None`.
+    #[inline]
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|n| n == 0)
+    }
+
+    /// Decode into an owned value.
+    #[inline]
+    pub fn to_value(&self) -> Option<SpookyValue> {
+        cbor::decode_slice(self.bytes).ok()
+    }
+
+    /// Object member by key, without decoding the other members.
+    pub fn get(&self, key: &str) -> Option<SpookyValueRef<'a>> {
+        let mut reader = SliceReader::new(self.bytes);
+        let len = match reader.head().ok()? {
+            Head::Map(len) => len,
+            _ => return None,
+        };
+        let mut seen = 0u64;
+        let mut found = None;
+        loop {
+            if len.is_some_and(|n| seen >= n) {
+                break;
+            }
+            let key_head = reader.head().ok()?;
+            if key_head == Head::Break && len.is_none() {
+                break;
+            }
+            let matches = match key_head {
+                Head::Text(l) => reader.text_body(l).ok()? == key,
+                other => {
+                    reader.skip_body(other).ok()?;
+                    false
+                }
+            };
+            let value = read_ref(&mut reader)?;
+            // Duplicate keys: last one wins, as when decoding.
+            if matches {
+                found = Some(value);
+            }
+            seen += 1;
+        }
+        found
+    }
+
+    /// Array element by position, skipping (not decoding) its predecessors.
+    pub fn index(&self, i: usize) -> Option<SpookyValueRef<'a>> {
+        let mut reader = SliceReader::new(self.bytes);
+        let len = match reader.head().ok()? {
+            Head::Array(len) => len,
+            _ => return None,
+        };
+        if len.is_some_and(|n| i as u64 >= n) {
+            return None;
+        }
+        for _ in 0..i {
+            match reader.head().ok()? {
+                Head::Break => return None,
+                head => reader.skip_body(head).ok()?,
+            };
+        }
+        let start = reader.position();
+        if reader.head().ok()? == Head::Break {
+            return None;
+        }
+        reader.seek(start);
+        read_ref(&mut reader)
+    }
+}
+
+/// Borrowed view of the next item. Text is borrowed where it is contiguous;
+/// containers, byte strings and tagged items become a `Nested` sub-slice.
+fn read_ref<'a>(reader: &mut SliceReader<'a>) -> Option<SpookyValueRef<'a>> {
+    let start = reader.position();
+    let head = reader.head().ok()?;
+    Some(match head {
+        Head::Null => SpookyValueRef::Null,
+        Head::Bool(b) => SpookyValueRef::Bool(b),
+        Head::Unsigned(u) => SpookyValueRef::Number(cbor::int_to_number(u as i128)),
+        Head::Negative(n) => SpookyValueRef::Number(cbor::int_to_number(-1 - n as i128)),
+        Head::Float(f) => SpookyValueRef::Number(SpookyNumber::F64(f)),
+        Head::Text(len) => match reader.text_body(len).ok()? {
+            std::borrow::Cow::Borrowed(s) => SpookyValueRef::Str(s),
+            // Indefinite-length text is chunked; hand it back still encoded.
+            std::borrow::Cow::Owned(_) => SpookyValueRef::Nested(NestedRef {
+                bytes: reader.since(start),
+            }),
+        },
+        Head::Break => return None,
+        other => {
+            reader.skip_body(other).ok()?;
+            SpookyValueRef::Nested(NestedRef {
+                bytes: reader.since(start),
+            })
+        }
+    })
+}
+
+/// Compares by value; nested items are decoded for the comparison.
+impl PartialEq<SpookyValue> for SpookyValueRef<'_> {
+    fn eq(&self, other: &SpookyValue) -> bool {
+        match (self, other) {
+            (SpookyValueRef::Null, SpookyValue::Null) => true,
+            (SpookyValueRef::Bool(a), SpookyValue::Bool(b)) => a == b,
+            (SpookyValueRef::Number(a), SpookyValue::Number(b)) => a == b,
+            (SpookyValueRef::Str(a), SpookyValue::Str(b)) => *a == b.as_str(),
+            (SpookyValueRef::Nested(n), other) => n.to_value().as_ref() == Some(other),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> From<FieldRef<'a>> for SpookyValueRef<'a> {
+    #[inline]
+    fn from(field: FieldRef<'a>) -> Self {
+        SpookyValueRef::from_field(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::{from_cbor_slice, from_spooky};
+    use crate::spooky_record::{SpookyReadable, SpookyRecord};
+
+    fn v(text: &str) -> SpookyValue {
+        SpookyValue::from_json_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_scalar_views_borrow_the_record() {
+        let (buf, fc) = from_spooky(&v(
+            r#"{"name":"Ada","age":36,"score":9.5,"on":true,"x":null}"#,
+        ))
+        .unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+
+        let name = record.get_field_ref("name").unwrap().as_str().unwrap();
+        let range = buf.as_ptr_range();
+        assert!(
+            range.contains(&name.as_ptr()),
+            "string must point into the record"
+        );
+        assert_eq!(name, "Ada");
+
+        assert_eq!(record.get_field_ref("age").unwrap().as_i64(), Some(36));
+        assert_eq!(record.get_field_ref("score").unwrap().as_f64(), Some(9.5));
+        assert_eq!(record.get_field_ref("on").unwrap().as_bool(), Some(true));
+        assert!(record.get_field_ref("x").unwrap().is_null());
+        assert!(record.get_field_ref("missing").is_none());
+        assert!(record.get_field_ref("age").unwrap() == SpookyValue::from(36i64));
+    }
+
+    #[test]
+    fn test_nested_lookup_without_full_decode() {
+        let doc = v(r#"{"profile":{"city":"Oslo","tags":["a","b",{"deep":1}],"n":-2}}"#);
+        let (buf, fc) = from_spooky(&doc).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+
+        let profile = record
+            .get_field_ref("profile")
+            .unwrap()
+            .as_nested()
+            .unwrap();
+        assert!(profile.is_object());
+        assert_eq!(profile.len(), Some(3));
+        assert_eq!(profile.get("city").unwrap().as_str(), Some("Oslo"));
+        assert_eq!(profile.get("n").unwrap().as_i64(), Some(-2));
+        assert!(profile.get("nope").is_none());
+
+        let tags = profile.get("tags").unwrap().as_nested().unwrap();
+        assert!(tags.is_array());
+        assert_eq!(tags.index(1).unwrap().as_str(), Some("b"));
+        assert!(tags.index(3).is_none());
+        let deep = tags.index(2).unwrap().as_nested().unwrap();
+        assert_eq!(deep.get("deep").unwrap().as_i64(), Some(1));
+
+        let owned = record.get_field_ref("profile").unwrap().to_value().unwrap();
+        assert_eq!(Some(&owned), doc.get("profile"));
+    }
+
+    #[test]
+    fn test_indefinite_nested_input() {
+        // {"o": {_ "s": (_ "ab" "c"), "k": [_ 1, 2]}}
+        let bytes: &[u8] = &[
+            0xA1, 0x61, b'o', 0xBF, 0x61, b's', 0x7F, 0x62, b'a', b'b', 0x61, b'c', 0xFF, 0x61,
+            b'k', 0x9F, 0x01, 0x02, 0xFF, 0xFF,
+        ];
+        let (buf, fc) = from_cbor_slice(bytes).unwrap();
+        let record = SpookyRecord::new(&buf, fc);
+        let o = record.get_field_ref("o").unwrap().as_nested().unwrap();
+        assert_eq!(o.len(), None);
+
+        // Chunked text cannot be borrowed; it stays encoded but compares equal.
+        let s = o.get("s").unwrap();
+        assert!(s.as_str().is_none());
+        assert!(s == SpookyValue::from("abc"));
+
+        let k = o.get("k").unwrap().as_nested().unwrap();
+        assert_eq!(k.index(1).unwrap().as_i64(), Some(2));
+        assert!(k.index(2).is_none());
+    }
+}
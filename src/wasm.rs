@@ -0,0 +1,174 @@
+//! wasm-bindgen wrappers for reading record bytes in the browser.
+//!
+//! A client that receives record bytes over the wire (`get_record_bytes`,
+//! a change feed) reads fields straight from them, without decoding CBOR or
+//! JSON in JS:
+//!
+//! ```js
+//! import { SpookyRecord } from "spooky_db_module";
+//! const record = new SpookyRecord(bytes);
+//! record.getStr("name");   // "Ann" | undefined
+//! record.getI64("age");    // 40n | undefined
+//! record.toJson(["name", "age", "tags"]);
+//! ```
+//!
+//! Records store field-name hashes, not names, so iteration yields hashes;
+//! `SpookyRecord.hash(name)` maps a known name to its hash.
+
+use wasm_bindgen::prelude::*;
+use xxhash_rust::xxh64::xxh64;
+
+use crate::serialization::from_bytes;
+use crate::spooky_record::{SpookyReadable, SpookyRecord};
+use crate::spooky_value::{FastMap, SpookyValue};
+use crate::types::{
+    TAG_BLOB_REF, TAG_BOOL, TAG_F64, TAG_I64, TAG_NESTED_CBOR, TAG_NULL, TAG_STR, TAG_U64,
+};
+
+/// An owned copy of one record's bytes, exported to JS as `SpookyRecord`.
+#[wasm_bindgen(js_name = SpookyRecord)]
+pub struct WasmRecord {
+    buf: Vec<u8>,
+    field_count: usize,
+}
+
+impl WasmRecord {
+    fn record(&self) -> SpookyRecord<'_> {
+        SpookyRecord::new(&self.buf, self.field_count)
+    }
+}
+
+#[wasm_bindgen(js_class = SpookyRecord)]
+impl WasmRecord {
+    /// Copy and validate record bytes. Throws on a malformed buffer.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<WasmRecord, JsError> {
+        let (buf, field_count) = from_bytes(bytes)?;
+        Ok(Self {
+            buf: buf.to_vec(),
+            field_count,
+        })
+    }
+
+    /// The hash a field name is stored under, as in `fieldHashes`.
+    pub fn hash(name: &str) -> u64 {
+        xxh64(name.as_bytes(), 0)
+    }
+
+    #[wasm_bindgen(getter, js_name = fieldCount)]
+    pub fn field_count(&self) -> usize {
+        self.field_count
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.record().has_field(name)
+    }
+
+    /// `"null"`, `"bool"`, `"i64"`, `"u64"`, `"f64"`, `"str"`, `"nested"`
+    /// (array or object) or `"blob"` (an out-of-line field reference).
+    #[wasm_bindgen(js_name = fieldType)]
+    pub fn field_type(&self, name: &str) -> Option<String> {
+        self.record().field_type(name).map(|tag| type_name(tag).to_owned())
+    }
+
+    #[wasm_bindgen(js_name = getStr)]
+    pub fn get_str(&self, name: &str) -> Option<String> {
+        self.record().get_str(name).map(str::to_owned)
+    }
+
+    /// A `BigInt` in JS.
+    #[wasm_bindgen(js_name = getI64)]
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.record().get_i64(name)
+    }
+
+    /// A `BigInt` in JS.
+    #[wasm_bindgen(js_name = getU64)]
+    pub fn get_u64(&self, name: &str) -> Option<u64> {
+        self.record().get_u64(name)
+    }
+
+    #[wasm_bindgen(js_name = getF64)]
+    pub fn get_f64(&self, name: &str) -> Option<f64> {
+        self.record().get_f64(name)
+    }
+
+    /// Any numeric field as a JS number, converting integers.
+    #[wasm_bindgen(js_name = getNumber)]
+    pub fn get_number(&self, name: &str) -> Option<f64> {
+        self.record().get_number_as_f64(name)
+    }
+
+    #[wasm_bindgen(js_name = getBool)]
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        self.record().get_bool(name)
+    }
+
+    /// Any field as JSON text; arrays and objects are decoded here.
+    #[wasm_bindgen(js_name = getJson)]
+    pub fn get_json(&self, name: &str) -> Option<String> {
+        let value = self.record().get_field::<SpookyValue>(name)?;
+        Some(value.to_json_string())
+    }
+
+    /// The stored field-name hashes, in record order.
+    #[wasm_bindgen(js_name = fieldHashes)]
+    pub fn field_hashes(&self) -> Vec<u64> {
+        self.record().iter_fields().map(|field| field.name_hash).collect()
+    }
+
+    /// The type tags of `fieldHashes`, in the same order.
+    #[wasm_bindgen(js_name = fieldTypes)]
+    pub fn field_types(&self) -> Vec<u8> {
+        self.record().iter_fields().map(|field| field.type_tag).collect()
+    }
+
+    /// The named fields as one JSON object; absent names are left out.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self, names: Vec<String>) -> String {
+        let record = self.record();
+        let mut map = FastMap::new();
+        for name in names {
+            if let Some(value) = record.get_field::<SpookyValue>(&name) {
+                map.insert(name.into(), value);
+            }
+        }
+        SpookyValue::Object(map).to_json_string()
+    }
+}
+
+fn type_name(tag: u8) -> &'static str {
+    match tag {
+        TAG_NULL => "null",
+        TAG_BOOL => "bool",
+        TAG_I64 => "i64",
+        TAG_U64 => "u64",
+        TAG_F64 => "f64",
+        TAG_STR => "str",
+        TAG_NESTED_CBOR => "nested",
+        TAG_BLOB_REF => "blob",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_typed_fields_and_hashes() {
+        let value = SpookyValue::from_json_str(r#"{"name":"Ann","age":40,"tags":["x"]}"#).unwrap();
+        let (bytes, _) = crate::serialization::from_spooky(&value).unwrap();
+        let record = WasmRecord::new(&bytes).unwrap();
+
+        assert_eq!(record.field_count(), 3);
+        assert_eq!(record.get_str("name").as_deref(), Some("Ann"));
+        assert_eq!(record.get_i64("age"), Some(40));
+        assert_eq!(record.get_number("age"), Some(40.0));
+        assert_eq!(record.get_str("age"), None);
+        assert_eq!(record.field_type("tags").as_deref(), Some("nested"));
+        assert_eq!(record.get_json("tags").as_deref(), Some(r#"["x"]"#));
+        assert!(record.field_hashes().contains(&WasmRecord::hash("age")));
+        assert_eq!(record.to_json(vec!["age".into(), "nope".into()]), r#"{"age":40}"#);
+    }
+}